@@ -0,0 +1,413 @@
+/**
+ * <pre>
+ *  mini-canal对外的数据类型, 下游服务只依赖本crate(serde + prost), 不需要引入parser:
+ *      Entry           一个binlog事件, RowData时带有RowChange
+ *      RowChange       一个rows event或者DDL的变更, rows中每行为before/after两个image
+ *      ColumnValue     一列的值, 统一为文本, NULL时is_null为true
+ *      Position        binlog中的位点
+ *      TableSchema     从rows event中得到的表结构(列名/类型/主键)
+ *  字段和枚举值的编号一经发布不再修改, 只能追加; 枚举值0为Unknown, 表示发送方的版本更新, 接收方不认识该值.
+ *  protobuf使用prost编码, 例如 let bytes = entry.encode_to_vec(); Entry::decode(bytes.as_slice())?;
+ *  (Message从本crate导出, 不需要单独依赖prost)
+ *  json等其它格式使用serde, 枚举字段按编号输出
+ * </pre>
+ */
+use prost::Enumeration;
+use serde::{Deserialize, Serialize};
+
+pub use prost::Message;
+
+// 对应parser中的protocol::EntryType
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Enumeration, Serialize, Deserialize)]
+#[repr(i32)]
+pub enum EntryType {
+    Unknown = 0,
+    TransactionBegin = 1,
+    RowData = 2,
+    TransactionEnd = 3,
+    Heartbeat = 4,
+    GtidLog = 5,
+    Incident = 6,
+    Info = 7,
+    SourceChanged = 8,
+}
+
+// 对应canal中CanalEntry.EventType
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Enumeration, Serialize, Deserialize)]
+#[repr(i32)]
+pub enum EventType {
+    Unknown = 0,
+    Insert = 1,
+    Update = 2,
+    Delete = 3,
+    Create = 4,
+    Alter = 5,
+    Erase = 6,
+    Query = 7,
+    Truncate = 8,
+    Rename = 9,
+    CIndex = 10,
+    DIndex = 11,
+    Gtid = 12,
+    XaCommit = 13,
+    XaRollback = 14,
+    MHeartbeat = 15,
+}
+
+// binlog中的位点, position为event的起始位置
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct Position {
+    #[prost(string, tag = "1")]
+    journal_name: String,
+    #[prost(uint64, tag = "2")]
+    position: u64,
+    // master执行的时间, 毫秒
+    #[prost(int64, tag = "3")]
+    timestamp: i64,
+    #[prost(uint32, tag = "4")]
+    server_id: u32,
+    #[prost(string, tag = "5")]
+    gtid: String,
+}
+
+impl Position {
+    pub fn new(journal_name: &str, position: u64) -> Position {
+        Position { journal_name: journal_name.to_string(), position, ..Position::default() }
+    }
+
+    pub fn journal_name(&self) -> &str {
+        &self.journal_name
+    }
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn gtid(&self) -> &str {
+        &self.gtid
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: i64) {
+        self.timestamp = timestamp;
+    }
+    pub fn set_server_id(&mut self, server_id: u32) {
+        self.server_id = server_id;
+    }
+    pub fn set_gtid(&mut self, gtid: &str) {
+        self.gtid = gtid.to_string();
+    }
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct Header {
+    #[prost(message, optional, tag = "1")]
+    position: Option<Position>,
+    #[prost(string, tag = "2")]
+    schema_name: String,
+    #[prost(string, tag = "3")]
+    table_name: String,
+    // 没有对应的变更类型时为Unknown
+    #[prost(enumeration = "EventType", tag = "4")]
+    event_type: i32,
+    #[prost(uint32, tag = "5")]
+    event_length: u32,
+    // 超过大小限制的rows event拆分后的序号, 从0开始, prost生成的part()在没有拆分时返回0
+    #[prost(uint32, optional, tag = "6")]
+    part: Option<u32>,
+}
+
+impl Header {
+    pub fn new(position: Position, schema_name: &str, table_name: &str) -> Header {
+        Header {
+            position: Some(position),
+            schema_name: schema_name.to_string(),
+            table_name: table_name.to_string(),
+            ..Header::default()
+        }
+    }
+
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_ref()
+    }
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn event_length(&self) -> u32 {
+        self.event_length
+    }
+
+    pub fn set_event_length(&mut self, event_length: u32) {
+        self.event_length = event_length;
+    }
+    pub fn set_part(&mut self, part: Option<u32>) {
+        self.part = part;
+    }
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct ColumnValue {
+    // 列在表中的序号
+    #[prost(uint32, tag = "1")]
+    index: u32,
+    #[prost(string, tag = "2")]
+    name: String,
+    // 例如varchar, datetime(3), int unsigned
+    #[prost(string, tag = "3")]
+    mysql_type: String,
+    // java.sql.Types
+    #[prost(int32, tag = "4")]
+    sql_type: i32,
+    #[prost(bool, tag = "5")]
+    is_key: bool,
+    #[prost(bool, tag = "6")]
+    is_null: bool,
+    // update的after image中值是否变化
+    #[prost(bool, tag = "7")]
+    updated: bool,
+    #[prost(string, tag = "8")]
+    value: String,
+}
+
+impl ColumnValue {
+    pub fn new(index: u32, name: &str, mysql_type: &str, sql_type: i32) -> ColumnValue {
+        ColumnValue {
+            index,
+            name: name.to_string(),
+            mysql_type: mysql_type.to_string(),
+            sql_type,
+            ..ColumnValue::default()
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn mysql_type(&self) -> &str {
+        &self.mysql_type
+    }
+    pub fn sql_type(&self) -> i32 {
+        self.sql_type
+    }
+    pub fn is_key(&self) -> bool {
+        self.is_key
+    }
+    pub fn is_null(&self) -> bool {
+        self.is_null
+    }
+    pub fn updated(&self) -> bool {
+        self.updated
+    }
+    // NULL时为None
+    pub fn value(&self) -> Option<&str> {
+        (!self.is_null).then_some(self.value.as_str())
+    }
+
+    pub fn set_is_key(&mut self, is_key: bool) {
+        self.is_key = is_key;
+    }
+    pub fn set_updated(&mut self, updated: bool) {
+        self.updated = updated;
+    }
+    pub fn set_value(&mut self, value: Option<&str>) {
+        self.is_null = value.is_none();
+        self.value = value.unwrap_or("").to_string();
+    }
+}
+
+// insert只有after, delete只有before
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct RowData {
+    #[prost(message, repeated, tag = "1")]
+    before: Vec<ColumnValue>,
+    #[prost(message, repeated, tag = "2")]
+    after: Vec<ColumnValue>,
+}
+
+impl RowData {
+    pub fn new(before: Vec<ColumnValue>, after: Vec<ColumnValue>) -> RowData {
+        RowData { before, after }
+    }
+
+    pub fn before(&self) -> &Vec<ColumnValue> {
+        &self.before
+    }
+    pub fn after(&self) -> &Vec<ColumnValue> {
+        &self.after
+    }
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct RowChange {
+    #[prost(enumeration = "EventType", tag = "1")]
+    event_type: i32,
+    #[prost(uint64, tag = "2")]
+    table_id: u64,
+    #[prost(bool, tag = "3")]
+    is_ddl: bool,
+    // DDL的语句
+    #[prost(string, tag = "4")]
+    sql: String,
+    #[prost(string, tag = "5")]
+    ddl_schema_name: String,
+    #[prost(message, repeated, tag = "6")]
+    rows: Vec<RowData>,
+}
+
+impl RowChange {
+    pub fn new(event_type: EventType, table_id: u64) -> RowChange {
+        RowChange { event_type: event_type as i32, table_id, ..RowChange::default() }
+    }
+
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+    pub fn is_ddl(&self) -> bool {
+        self.is_ddl
+    }
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+    pub fn ddl_schema_name(&self) -> &str {
+        &self.ddl_schema_name
+    }
+    pub fn rows(&self) -> &Vec<RowData> {
+        &self.rows
+    }
+
+    pub fn set_ddl(&mut self, sql: &str, ddl_schema_name: &str) {
+        self.is_ddl = true;
+        self.sql = sql.to_string();
+        self.ddl_schema_name = ddl_schema_name.to_string();
+    }
+    pub fn add_row(&mut self, row: RowData) {
+        self.rows.push(row);
+    }
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    #[prost(uint32, tag = "1")]
+    index: u32,
+    #[prost(string, tag = "2")]
+    name: String,
+    #[prost(string, tag = "3")]
+    mysql_type: String,
+    #[prost(int32, tag = "4")]
+    sql_type: i32,
+    #[prost(bool, tag = "5")]
+    is_key: bool,
+}
+
+impl ColumnSchema {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn mysql_type(&self) -> &str {
+        &self.mysql_type
+    }
+    pub fn sql_type(&self) -> i32 {
+        self.sql_type
+    }
+    pub fn is_key(&self) -> bool {
+        self.is_key
+    }
+}
+
+impl From<&ColumnValue> for ColumnSchema {
+    fn from(column: &ColumnValue) -> ColumnSchema {
+        ColumnSchema {
+            index: column.index,
+            name: column.name.clone(),
+            mysql_type: column.mysql_type.clone(),
+            sql_type: column.sql_type,
+            is_key: column.is_key,
+        }
+    }
+}
+
+// 只包含binlog中能得到的信息, 没有默认值/注释等, binlog_row_image=MINIMAL时可能只有部分列
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct TableSchema {
+    #[prost(string, tag = "1")]
+    schema_name: String,
+    #[prost(string, tag = "2")]
+    table_name: String,
+    #[prost(message, repeated, tag = "3")]
+    columns: Vec<ColumnSchema>,
+}
+
+impl TableSchema {
+    pub fn new(schema_name: &str, table_name: &str, columns: Vec<ColumnSchema>) -> TableSchema {
+        TableSchema { schema_name: schema_name.to_string(), table_name: table_name.to_string(), columns }
+    }
+
+    // 取第一行中的列, 不是rows的变更(DDL等)或者没有行时返回None
+    pub fn from_entry(entry: &Entry) -> Option<TableSchema> {
+        let header = entry.header.as_ref()?;
+        let row = entry.row_change.as_ref().filter(|row_change| !row_change.is_ddl)?.rows.first()?;
+        let columns = if row.after.is_empty() { &row.before } else { &row.after };
+        Some(TableSchema::new(&header.schema_name, &header.table_name, columns.iter().map(ColumnSchema::from).collect()))
+    }
+
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn columns(&self) -> &Vec<ColumnSchema> {
+        &self.columns
+    }
+}
+
+#[derive(Clone, PartialEq, Message, Serialize, Deserialize)]
+pub struct Entry {
+    #[prost(enumeration = "EntryType", tag = "1")]
+    entry_type: i32,
+    #[prost(message, optional, tag = "2")]
+    header: Option<Header>,
+    #[prost(message, optional, tag = "3")]
+    row_change: Option<RowChange>,
+    // 同一个事务中的entry相同, 由prost生成的transaction_id()/message()在没有值时返回0/空字符串
+    #[prost(uint64, optional, tag = "4")]
+    transaction_id: Option<u64>,
+    // Incident/Info/SourceChanged的说明
+    #[prost(string, optional, tag = "5")]
+    message: Option<String>,
+}
+
+impl Entry {
+    pub fn new(entry_type: EntryType, header: Header) -> Entry {
+        Entry { entry_type: entry_type as i32, header: Some(header), ..Entry::default() }
+    }
+
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+    pub fn row_change(&self) -> Option<&RowChange> {
+        self.row_change.as_ref()
+    }
+
+    pub fn set_row_change(&mut self, row_change: RowChange) {
+        self.row_change = Some(row_change);
+    }
+    pub fn set_transaction_id(&mut self, transaction_id: Option<u64>) {
+        self.transaction_id = transaction_id;
+    }
+    pub fn set_message(&mut self, message: Option<&str>) {
+        self.message = message.map(|message| message.to_string());
+    }
+}
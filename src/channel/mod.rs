@@ -1,19 +1,47 @@
-use std::fmt::format;
 use std::io::{Read, Write, Result, Error, ErrorKind};
-use std::net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4, TcpStream};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4, TcpStream, ToSocketAddrs};
 use std::str::FromStr;
-use std::time::{SystemTime, UNIX_EPOCH};
-use chrono::Local;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::metrics::ChannelStats;
 
-pub trait SocketChannel {
+
+pub trait SocketChannel: Send {
     fn write(&mut self, buf: &[u8]) -> Result<usize>;
     fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
-    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: i64) -> std::result::Result<usize, Error>;
+    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> std::result::Result<usize, Error>;
+    // socket层面的读超时, None表示一直阻塞; 超时后read返回WouldBlock/TimedOut
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()>;
     fn is_connected(&self) -> bool;
     fn get_remote_address(&self) -> Option<SocketAddrV4>;
     fn get_local_address(&self) -> Option<SocketAddrV4>;
     fn close(&self) -> Result<()>;
+
+    // 读满buf, 对端关闭连接时返回UnexpectedEof
+    fn read_fully(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut offset = 0;
+        while offset < buf.len() {
+            let size = self.read(&mut buf[offset..])?;
+            if size == 0 {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+            offset += size;
+        }
+        Ok(())
+    }
+
+    fn write_fully(&mut self, buf: &[u8]) -> Result<()> {
+        let mut offset = 0;
+        while offset < buf.len() {
+            let size = self.write(&buf[offset..])?;
+            if size == 0 {
+                return Err(Error::from(ErrorKind::WriteZero));
+            }
+            offset += size;
+        }
+        Ok(())
+    }
 }
 
 
@@ -21,85 +49,144 @@ pub struct TcpChannel {
     channel: TcpStream,
     address: Option<SocketAddrV4>,
     is_connected: bool,
+    // 读写字节数以及错误分类, 可以与其它连接共享句柄
+    stats: Arc<Mutex<ChannelStats>>,
 }
 
 // 默认超时时间
-const DEFAULT_CONNECT_TIMEOUT: i32 = 10 * 1000;
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 //
-const SO_TIMEOUT: i32 = 1000;
+pub const SO_TIMEOUT: Duration = Duration::from_secs(1);
 
 
 impl TcpChannel {
     pub fn new(addr: &str, port: u16) -> TcpChannel {
-        let channel = TcpStream::connect(format!("{}:{}", addr, port)).map(|channel| {
-            let addr = Ipv4Addr::from_str(addr).map(|addr| {
-                SocketAddrV4::new(addr, port)
-            }).unwrap();
-            TcpChannel {
-                channel,
-                address: Option::Some(addr),
-                is_connected: true,
+        TcpChannel::connect(addr, port).unwrap()
+    }
+
+    pub fn connect(addr: &str, port: u16) -> Result<TcpChannel> {
+        TcpChannel::connect_timeout(addr, port, DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    pub fn connect_timeout(addr: &str, port: u16, timeout: Duration) -> Result<TcpChannel> {
+        let mut last_error = Error::new(ErrorKind::NotFound, format!("can't resolve {}:{}", addr, port));
+        for socket_addr in (addr, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&socket_addr, timeout) {
+                Ok(channel) => {
+                    channel.set_nodelay(true)?;
+                    let address = match socket_addr {
+                        SocketAddr::V4(addr) => Option::Some(addr),
+                        SocketAddr::V6(_) => Ipv4Addr::from_str(addr).ok().map(|addr| SocketAddrV4::new(addr, port))
+                    };
+                    return Ok(TcpChannel {
+                        channel,
+                        address,
+                        is_connected: true,
+                        stats: Arc::new(Mutex::new(ChannelStats::new())),
+                    });
+                }
+                Err(e) => last_error = e
             }
-        }).unwrap();
-        channel
+        }
+        Err(last_error)
+    }
+
+    // 使用已经建立的连接, 例如服务端accept得到的连接
+    pub fn from_stream(channel: TcpStream) -> Result<TcpChannel> {
+        channel.set_nodelay(true)?;
+        let address = match channel.peer_addr()? {
+            SocketAddr::V4(addr) => Option::Some(addr),
+            SocketAddr::V6(_) => Option::None,
+        };
+        Ok(TcpChannel { channel, address, is_connected: true, stats: Arc::new(Mutex::new(ChannelStats::new())) })
+    }
+
+    pub fn stats(&self) -> Arc<Mutex<ChannelStats>> {
+        self.stats.clone()
+    }
+
+    pub fn set_stats(&mut self, stats: Arc<Mutex<ChannelStats>>) {
+        self.stats = stats;
+    }
+
+    fn record<F: FnOnce(&mut ChannelStats)>(&self, f: F) {
+        if let Ok(mut stats) = self.stats.lock() {
+            f(&mut stats);
+        }
     }
 }
 
 impl SocketChannel for TcpChannel {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        let size = self.channel.write(buf)?;
-        Ok(size)
+        match self.channel.write(buf) {
+            Ok(size) => {
+                self.record(|stats| stats.record_write(size));
+                Ok(size)
+            }
+            Err(e) => {
+                self.record(|stats| { stats.record_error(&e); });
+                Err(e)
+            }
+        }
     }
 
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        self.channel.read(buf)
+        match self.channel.read(buf) {
+            Ok(size) => {
+                self.record(|stats| stats.record_read(buf.len(), size));
+                Ok(size)
+            }
+            Err(e) => {
+                self.record(|stats| { stats.record_error(&e); });
+                Err(e)
+            }
+        }
     }
 
-    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: i64) -> std::result::Result<usize, Error> {
-        let now = Local::now().timestamp_millis();
+    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> std::result::Result<usize, Error> {
+        let now = Instant::now();
         let mut remain = buf.len();
         loop {
             let mut tmp = [0u8; 1];
-            let size = self.channel.read(&mut tmp)?;
+            let size = self.read(&mut tmp)?;
             buf[buf.len() - remain] = tmp[0];
             remain -= size;
             if remain as i64 <= 0 {
                 break;
             }
-            if Local::now().timestamp_millis() - now > timeout {
+            if now.elapsed() > timeout {
+                self.record(|stats| { stats.record_error(&Error::from(ErrorKind::TimedOut)); });
                 return std::result::Result::Err(Error::from(ErrorKind::TimedOut));
             }
         }
         std::result::Result::Ok(buf.len() - remain)
     }
 
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.channel.set_read_timeout(timeout)
+    }
+
     fn is_connected(&self) -> bool {
         self.is_connected
     }
 
     fn get_remote_address(&self) -> Option<SocketAddrV4> {
-        todo!()
+        self.address
     }
 
     fn get_local_address(&self) -> Option<SocketAddrV4> {
-        let addr = self.channel.local_addr().ok();
-        match addr {
-            Some(addr) => {
-                let ip = addr.ip();
-                let ip_byte = ip.to_string();
-                let ip_byte = ip_byte.as_bytes();
-                Option::from(SocketAddrV4::new(Ipv4Addr::new(ip_byte[0], ip_byte[1], ip_byte[2], ip_byte[3]), addr.port()))
-            }
-            None => {
-                Option::None
-            }
+        match self.channel.local_addr().ok() {
+            Some(SocketAddr::V4(addr)) => Option::Some(addr),
+            _ => Option::None
         }
     }
 
     fn close(&self) -> Result<()> {
-        return self.channel.shutdown(Shutdown::Both);
+        self.channel.shutdown(Shutdown::Both)
     }
 }
 
 
-mod mysql_socket;
\ No newline at end of file
+pub mod mysql_socket;
+
+pub mod tls;
\ No newline at end of file
@@ -0,0 +1,374 @@
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::channel::tls::{SslMode, TlsChannel};
+use crate::channel::{SocketChannel, TcpChannel, DEFAULT_CONNECT_TIMEOUT};
+use crate::command::capability::CLIENT_SSL;
+use crate::command::msc::{AUTH_MORE_DATA_HEADER, AUTH_SWITCH_HEADER, EOF_HEADER, ERROR_HEADER, OK_HEADER};
+use crate::command::packet_utils::{read_packet, write_body, write_pkg};
+use crate::command::password::{scramble, CACHING_SHA2_PASSWORD, MYSQL_NATIVE_PASSWORD};
+use crate::command::errno::ServerError;
+use crate::command::com::{Command, QueryCommand, QuitCommand};
+use crate::error::CanalError;
+use crate::metrics::ChannelStats;
+use crate::command::{AuthSwitchRequestPacket, ClientAuthenticationPacket, FieldPacket, HandshakeInitializationPacket, OKPacket, Packet,
+                     ResultSetHeaderPacket, ResultSetPacket, RowDataPacket};
+
+// caching_sha2_password的auth more data状态
+const FAST_AUTH_SUCCESS: u8 = 0x03;
+const PERFORM_FULL_AUTHENTICATION: u8 = 0x04;
+
+/**
+ * <pre>
+ *  对应canal中的MysqlConnector, 负责建立连接, 握手认证以及执行简单的sql,
+ *  dump之后的binlog读取由DirectLogFetcher直接操作底层的channel
+ * </pre>
+ */
+pub struct MysqlConnector {
+    address: String,
+    port: u16,
+    username: String,
+    password: String,
+    default_schema: String,
+    channel: Option<Box<dyn SocketChannel>>,
+    connection_id: u32,
+    server_version: String,
+    // 最近一个命令收到的ErrorPacket, 用于按errno区分处理
+    last_error: Option<ServerError>,
+    connect_timeout: Duration,
+    // 每次连接时传给新的TcpChannel, 重连之后继续累加
+    stats: Arc<Mutex<ChannelStats>>,
+    ssl_mode: SslMode,
+    // pem格式的ca证书路径, verify_ca/verify_identity时必须配置
+    ssl_ca: Option<String>,
+    // 当前连接协商的tls版本和加密套件, 明文连接为None
+    ssl_cipher: Option<String>,
+}
+
+impl MysqlConnector {
+    pub fn new(address: &str, port: u16, username: &str, password: &str, default_schema: &str) -> MysqlConnector {
+        MysqlConnector {
+            address: address.to_string(),
+            port,
+            username: username.to_string(),
+            password: password.to_string(),
+            default_schema: default_schema.to_string(),
+            channel: None,
+            connection_id: 0,
+            server_version: String::new(),
+            last_error: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            stats: Arc::new(Mutex::new(ChannelStats::new())),
+            ssl_mode: SslMode::Disabled,
+            ssl_ca: None,
+            ssl_cipher: None,
+        }
+    }
+
+    pub fn connect(&mut self) -> Result<(), CanalError> {
+        if self.is_connected() {
+            return Ok(());
+        }
+        let mut channel = match TcpChannel::connect_timeout(&self.address, self.port, self.connect_timeout) {
+            Ok(channel) => channel,
+            Err(e) => {
+                let class = self.stats.lock().map(|mut stats| stats.record_connect_failure(&e).name()).unwrap_or("other");
+                return Err(CanalError::Io(e.kind(), format!("connect {}:{} failure ({}): {}", self.address, self.port,
+                                                            class, e)));
+            }
+        };
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.record_connect();
+        }
+        channel.set_stats(self.stats.clone());
+        self.channel = Some(Box::new(channel));
+        if let Err(e) = self.negotiate() {
+            self.disconnect();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn reconnect(&mut self) -> Result<(), CanalError> {
+        self.disconnect();
+        self.connect()
+    }
+
+    pub fn disconnect(&mut self) {
+        self.ssl_cipher = None;
+        if let Some(channel) = self.channel.take() {
+            let _ = channel.close();
+        }
+    }
+
+    // 使用相同的配置创建一个新的connector, 用于查询等与dump连接分离的场景, 网络统计记入同一个句柄
+    pub fn fork(&self) -> MysqlConnector {
+        let mut connector = MysqlConnector::new(&self.address, self.port, &self.username, &self.password,
+                                                &self.default_schema);
+        connector.set_connect_timeout(self.connect_timeout);
+        connector.set_stats(self.stats.clone());
+        connector.set_ssl_mode(self.ssl_mode);
+        connector.set_ssl_ca(self.ssl_ca.as_deref());
+        connector
+    }
+
+    pub fn stats(&self) -> Arc<Mutex<ChannelStats>> {
+        self.stats.clone()
+    }
+
+    pub fn set_stats(&mut self, stats: Arc<Mutex<ChannelStats>>) {
+        self.stats = stats;
+    }
+
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.connect_timeout = connect_timeout;
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn set_ssl_mode(&mut self, ssl_mode: SslMode) {
+        self.ssl_mode = ssl_mode;
+    }
+
+    pub fn ssl_mode(&self) -> SslMode {
+        self.ssl_mode
+    }
+
+    pub fn set_ssl_ca(&mut self, ssl_ca: Option<&str>) {
+        self.ssl_ca = ssl_ca.map(|ssl_ca| ssl_ca.to_string());
+    }
+
+    pub fn ssl_ca(&self) -> Option<&str> {
+        self.ssl_ca.as_deref()
+    }
+
+    pub fn ssl_cipher(&self) -> Option<&str> {
+        self.ssl_cipher.as_deref()
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.ssl_cipher.is_some()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.channel.as_ref().map(|channel| channel.is_connected()).unwrap_or(false)
+    }
+
+    pub fn channel(&mut self) -> Result<&mut dyn SocketChannel, CanalError> {
+        match self.channel.as_mut() {
+            Some(channel) => Ok(channel.as_mut()),
+            None => Err(CanalError::Io(ErrorKind::NotConnected,
+                                       format!("connection to {}:{} is not established", self.address, self.port))),
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
+    pub fn server_version(&self) -> &str {
+        &self.server_version
+    }
+    pub fn last_error(&self) -> Option<&ServerError> {
+        self.last_error.as_ref()
+    }
+
+    fn negotiate(&mut self) -> Result<(), CanalError> {
+        let (header, body) = read_packet(self.channel()?)?;
+        if body.first() == Some(&ERROR_HEADER) {
+            return Err(self.server_error(&body));
+        }
+        let mut handshake = HandshakeInitializationPacket::default();
+        handshake.from_bytes(&body);
+        self.connection_id = handshake.thread_id();
+        self.server_version = handshake.server_version().to_string();
+
+        let mut seed = handshake.seed().to_vec();
+        seed.extend_from_slice(handshake.rest_of_scramble_buff());
+        let plugin = match std::str::from_utf8(handshake.auth_plugin_name()).unwrap_or_default() {
+            CACHING_SHA2_PASSWORD => CACHING_SHA2_PASSWORD,
+            _ => MYSQL_NATIVE_PASSWORD,
+        };
+        let scrumble_password = scramble(plugin, self.password.as_bytes(), &seed);
+        let default_schema = self.default_schema.clone();
+        let username = self.username.clone();
+        let mut auth = ClientAuthenticationPacket::new(&username, &scrumble_password, &default_schema, plugin);
+        let mut sequence = header.get_packet_sequence_number().wrapping_add(1);
+        if self.ssl_mode != SslMode::Disabled {
+            if (handshake.server_capabilities() as i32 & CLIENT_SSL) != 0 {
+                write_pkg(self.channel()?, sequence, &auth.ssl_request())?;
+                sequence = sequence.wrapping_add(1);
+                self.start_tls()?;
+                auth.set_client_capability(auth.client_capability() | CLIENT_SSL);
+            } else if self.ssl_mode.is_required() {
+                return Err(CanalError::Auth(format!("{}:{} does not support ssl, which ssl mode {} requires",
+                                                    self.address, self.port, self.ssl_mode.name())));
+            }
+        }
+        write_pkg(self.channel()?, sequence, &auth.to_bytes())?;
+
+        loop {
+            let (header, body) = read_packet(self.channel()?)?;
+            sequence = header.get_packet_sequence_number().wrapping_add(1);
+            match body.first() {
+                Some(&OK_HEADER) => return Ok(()),
+                // 认证阶段的ErrorPacket, 例如1045
+                Some(&ERROR_HEADER) => return Err(CanalError::Auth(self.server_error(&body).to_string())),
+                Some(&AUTH_SWITCH_HEADER) => {
+                    let mut switch = AuthSwitchRequestPacket::default();
+                    switch.from_bytes(&body);
+                    let seed = switch.auth_data();
+                    let password = scramble(switch.auth_name(), self.password.as_bytes(), seed);
+                    write_pkg(self.channel()?, sequence, &password)?;
+                }
+                Some(&AUTH_MORE_DATA_HEADER) => match body.get(1) {
+                    Some(&FAST_AUTH_SUCCESS) => continue,
+                    // tls连接上可以直接发送明文密码
+                    Some(&PERFORM_FULL_AUTHENTICATION) if self.is_secure() => {
+                        let mut password = self.password.as_bytes().to_vec();
+                        password.push(0);
+                        write_pkg(self.channel()?, sequence, &password)?;
+                    }
+                    Some(&PERFORM_FULL_AUTHENTICATION) => {
+                        return Err(CanalError::Auth(format!("user {} requires caching_sha2_password full \
+                                                             authentication, which needs a secure connection",
+                                                            self.username)));
+                    }
+                    _ => return Err(CanalError::protocol("unexpected auth more data packet")),
+                },
+                _ => return Err(CanalError::Protocol(None, format!("unexpected auth response {:?}", body.first()))),
+            }
+        }
+    }
+
+    // 在SSLRequest之后把当前的明文channel包装为tls channel
+    fn start_tls(&mut self) -> Result<(), CanalError> {
+        let channel = self.channel.take()
+            .ok_or_else(|| CanalError::Io(ErrorKind::NotConnected,
+                                          format!("connection to {}:{} is not established", self.address, self.port)))?;
+        let channel = TlsChannel::connect(channel, &self.address, self.ssl_mode, self.ssl_ca.as_deref())?;
+        self.ssl_cipher = Some(channel.cipher().unwrap_or_default());
+        self.channel = Some(Box::new(channel));
+        Ok(())
+    }
+
+    // 执行查询, 返回完整的结果集
+    pub fn query(&mut self, sql: &str) -> Result<ResultSetPacket, CanalError> {
+        self.send(&QueryCommand::new(sql))?;
+        let (_, body) = self.read_body()?;
+        if body.first() == Some(&ERROR_HEADER) {
+            return Err(self.sql_error(&body, sql));
+        }
+        let mut result_set = ResultSetPacket::new();
+        result_set.set_socket_address(format!("{}:{}", self.address, self.port));
+        if body.first() == Some(&OK_HEADER) {
+            return Ok(result_set);
+        }
+        let mut result_set_header = ResultSetHeaderPacket::default();
+        result_set_header.from_bytes(&body);
+
+        let mut field_descriptors = vec![];
+        loop {
+            let (_, body) = self.read_body()?;
+            if is_eof_packet(&body) {
+                break;
+            }
+            let mut field = FieldPacket::new();
+            field.from_bytes(&body);
+            field_descriptors.push(field.name().to_string());
+        }
+
+        let mut field_values = vec![];
+        loop {
+            let (_, body) = self.read_body()?;
+            if is_eof_packet(&body) {
+                break;
+            }
+            if body.first() == Some(&ERROR_HEADER) {
+                return Err(self.sql_error(&body, sql));
+            }
+            let mut row = RowDataPacket::new();
+            row.from_bytes(&body);
+            field_values.extend(row.columns().iter().map(|column| column.to_string()));
+        }
+        result_set.set_field_descriptors(field_descriptors);
+        result_set.set_field_values(field_values);
+        Ok(result_set)
+    }
+
+    // 执行不返回结果集的sql, 例如set/update
+    pub fn update(&mut self, sql: &str) -> Result<i64, CanalError> {
+        self.send(&QueryCommand::new(sql))?;
+        let (_, body) = self.read_body()?;
+        match body.first() {
+            Some(&ERROR_HEADER) => Err(self.sql_error(&body, sql)),
+            _ => {
+                let mut ok = OKPacket::default();
+                ok.from_bytes(&body);
+                Ok(ok.affected_rows())
+            }
+        }
+    }
+
+    pub fn send<C: Command>(&mut self, command: &C) -> Result<(), CanalError> {
+        self.send_command(&command.encode())
+    }
+
+    pub fn send_command(&mut self, body: &[u8]) -> Result<(), CanalError> {
+        self.last_error = None;
+        write_body(self.channel()?, body).map_err(CanalError::from)
+    }
+
+    pub fn read_body(&mut self) -> Result<(u8, Vec<u8>), CanalError> {
+        let (header, body) = read_packet(self.channel()?)?;
+        Ok((header.get_packet_sequence_number(), body))
+    }
+
+    // 记录ErrorPacket并返回带有排查建议的错误信息
+    pub fn server_error(&mut self, body: &[u8]) -> CanalError {
+        let error = ServerError::from_packet(body);
+        let message = error.as_ref().map(|error| error.to_string())
+            .unwrap_or_else(|| format!("malformed error packet {:?}", body));
+        self.last_error = error.clone();
+        CanalError::Protocol(error, message)
+    }
+
+    fn sql_error(&mut self, body: &[u8], sql: &str) -> CanalError {
+        match self.server_error(body) {
+            CanalError::Protocol(error, message) => {
+                CanalError::Protocol(error, format!("{} for sql: {}", message, sql))
+            }
+            e => e,
+        }
+    }
+
+    pub fn quit(&mut self) {
+        if self.is_connected() {
+            let _ = self.send(&QuitCommand);
+        }
+        self.disconnect();
+    }
+}
+
+fn is_eof_packet(body: &[u8]) -> bool {
+    body.first() == Some(&EOF_HEADER) && body.len() < 9
+}
+
+pub fn error_packet_message(body: &[u8]) -> String {
+    ServerError::from_packet(body).map(|error| error.to_string())
+        .unwrap_or_else(|| format!("malformed error packet {:?}", body))
+}
@@ -0,0 +1,244 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::SocketAddrV4;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme,
+             StreamOwned};
+
+use crate::channel::SocketChannel;
+use crate::error::CanalError;
+
+/**
+ * <pre>
+ *  与mysql客户端的--ssl-mode含义一致:
+ *      disabled            不使用ssl
+ *      preferred           server支持时使用ssl, 否则使用明文连接, 不校验证书
+ *      required            必须使用ssl, 不校验证书
+ *      verify_ca           必须使用ssl, 证书需要由ssl_ca签发, 不校验主机名
+ *      verify_identity     在verify_ca的基础上校验证书中的主机名与address一致
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SslMode {
+    #[default]
+    Disabled,
+    Preferred,
+    Required,
+    VerifyCa,
+    VerifyIdentity,
+}
+
+impl SslMode {
+    pub fn from_name(name: &str) -> std::result::Result<SslMode, String> {
+        match name.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "disabled" => Ok(SslMode::Disabled),
+            "preferred" => Ok(SslMode::Preferred),
+            "required" => Ok(SslMode::Required),
+            "verify_ca" => Ok(SslMode::VerifyCa),
+            "verify_identity" => Ok(SslMode::VerifyIdentity),
+            _ => Err(format!("unknown ssl mode {}, expect disabled/preferred/required/verify_ca/verify_identity", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SslMode::Disabled => "disabled",
+            SslMode::Preferred => "preferred",
+            SslMode::Required => "required",
+            SslMode::VerifyCa => "verify_ca",
+            SslMode::VerifyIdentity => "verify_identity",
+        }
+    }
+
+    // server不支持ssl时是否报错
+    pub fn is_required(&self) -> bool {
+        !matches!(self, SslMode::Disabled | SslMode::Preferred)
+    }
+
+    pub fn verifies_certificate(&self) -> bool {
+        matches!(self, SslMode::VerifyCa | SslMode::VerifyIdentity)
+    }
+}
+
+/**
+ * <pre>
+ *  在已经建立的连接上完成tls握手, 之后的读写都经过加密:
+ *      mysql协议中客户端先以明文收到handshake, 回复带CLIENT_SSL的SSLRequest之后才开始tls握手,
+ *      因此包装的是已经连接的channel而不是直接连接server. 底层channel的统计记录的是加密后的字节数
+ * </pre>
+ */
+pub struct TlsChannel {
+    stream: StreamOwned<ClientConnection, ChannelIo>,
+}
+
+impl TlsChannel {
+    pub fn connect(inner: Box<dyn SocketChannel>, host: &str, mode: SslMode, ca: Option<&str>)
+                   -> std::result::Result<TlsChannel, CanalError> {
+        let config = client_config(mode, ca)?;
+        let server_name = match ServerName::try_from(host.to_string()) {
+            Ok(server_name) => server_name,
+            Err(e) if mode == SslMode::VerifyIdentity => {
+                return Err(CanalError::State(format!("invalid ssl server name {}: {}", host, e)));
+            }
+            // 不校验主机名时只用于SNI
+            Err(_) => ServerName::try_from("localhost").map_err(|e| CanalError::State(e.to_string()))?,
+        };
+        let connection = ClientConnection::new(config, server_name)
+            .map_err(|e| CanalError::State(format!("create ssl connection to {} failure: {}", host, e)))?;
+        let mut stream = StreamOwned::new(connection, ChannelIo(inner));
+        // 立即完成握手, 证书错误在connect时返回而不是在第一次读写时.
+        // rustls的tls错误(证书校验失败等)为InvalidData, 其它为socket错误
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock).map_err(|e| {
+                let message = format!("ssl handshake with {} failure: {}", host, e);
+                match e.kind() {
+                    ErrorKind::InvalidData => CanalError::Auth(message),
+                    kind => CanalError::Io(kind, message),
+                }
+            })?;
+        }
+        Ok(TlsChannel { stream })
+    }
+
+    // 协商的tls版本和加密套件, 例如 TLSv1_3 TLS13_AES_256_GCM_SHA384
+    pub fn cipher(&self) -> Option<String> {
+        let version = self.stream.conn.protocol_version()?;
+        let suite = self.stream.conn.negotiated_cipher_suite()?;
+        Some(format!("{:?} {:?}", version, suite.suite()))
+    }
+}
+
+impl SocketChannel for TlsChannel {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let size = self.stream.write(buf)?;
+        self.stream.flush()?;
+        Ok(size)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.stream.read(buf)
+    }
+
+    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> std::result::Result<usize, Error> {
+        let now = Instant::now();
+        let mut offset = 0;
+        while offset < buf.len() {
+            let size = self.read(&mut buf[offset..])?;
+            if size == 0 {
+                return Err(Error::from(ErrorKind::UnexpectedEof));
+            }
+            offset += size;
+            if offset < buf.len() && now.elapsed() > timeout {
+                return Err(Error::from(ErrorKind::TimedOut));
+            }
+        }
+        Ok(offset)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.stream.sock.0.set_read_timeout(timeout)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.stream.sock.0.is_connected()
+    }
+
+    fn get_remote_address(&self) -> Option<SocketAddrV4> {
+        self.stream.sock.0.get_remote_address()
+    }
+
+    fn get_local_address(&self) -> Option<SocketAddrV4> {
+        self.stream.sock.0.get_local_address()
+    }
+
+    fn close(&self) -> Result<()> {
+        self.stream.sock.0.close()
+    }
+}
+
+// rustls需要std::io::Read/Write
+struct ChannelIo(Box<dyn SocketChannel>);
+
+impl Read for ChannelIo {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for ChannelIo {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn client_config(mode: SslMode, ca: Option<&str>) -> std::result::Result<Arc<ClientConfig>, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+    let verifier: Arc<dyn ServerCertVerifier> = if mode.verifies_certificate() {
+        let ca = ca.ok_or_else(|| format!("ssl mode {} requires a ca certificate", mode.name()))?;
+        let mut roots = RootCertStore::empty();
+        let certificates = CertificateDer::pem_file_iter(ca).map_err(|e| format!("read ssl ca {} failure: {}", ca, e))?;
+        for certificate in certificates {
+            let certificate = certificate.map_err(|e| format!("read ssl ca {} failure: {}", ca, e))?;
+            roots.add(certificate).map_err(|e| format!("invalid ssl ca {}: {}", ca, e))?;
+        }
+        let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|e| format!("invalid ssl ca {}: {}", ca, e))?;
+        Arc::new(CertificateVerifier { mode, webpki: Some(webpki), provider })
+    } else {
+        Arc::new(CertificateVerifier { mode, webpki: None, provider })
+    };
+    Ok(Arc::new(builder.dangerous().with_custom_certificate_verifier(verifier).with_no_client_auth()))
+}
+
+// preferred/required不校验证书, verify_ca忽略主机名不匹配, 但握手签名总是校验
+#[derive(Debug)]
+struct CertificateVerifier {
+    mode: SslMode,
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for CertificateVerifier {
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>],
+                          server_name: &ServerName<'_>, ocsp_response: &[u8], now: UnixTime)
+                          -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let webpki = match self.webpki.as_ref() {
+            Some(webpki) => webpki,
+            None => return Ok(ServerCertVerified::assertion()),
+        };
+        match webpki.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now) {
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName))
+            | Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForNameContext { .. }))
+            if self.mode == SslMode::VerifyCa => Ok(ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+                              -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct)
+                              -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,53 @@
+pub const UTF8_GENERAL_CI: u16 = 33;
+pub const BINARY: u16 = 63;
+pub const UTF8MB4_GENERAL_CI: u16 = 45;
+
+// cp1252中0x80-0x9f对应的unicode, 未定义的位置与mysql保持一致直接映射
+const CP1252_HIGH: [u16; 32] = [
+    0x20ac, 0x0081, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021,
+    0x02c6, 0x2030, 0x0160, 0x2039, 0x0152, 0x008d, 0x017d, 0x008f,
+    0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014,
+    0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x009d, 0x017e, 0x0178,
+];
+
+pub fn charset_name(collation_id: u16) -> Option<&'static str> {
+    match collation_id {
+        33 | 83 | 192..=215 | 223 | 76 => Some("utf8"),
+        45 | 46 | 224..=247 | 255..=323 => Some("utf8mb4"),
+        5 | 8 | 15 | 31 | 47 | 48 | 49 | 94 => Some("latin1"),
+        11 | 65 => Some("ascii"),
+        63 => Some("binary"),
+        1 | 84 => Some("big5"),
+        24 | 86 => Some("gb2312"),
+        28 | 87 => Some("gbk"),
+        248..=250 => Some("gb18030"),
+        _ => None,
+    }
+}
+
+/**
+ * <pre>
+ *  按collation id对应的字符集解码, 只覆盖binlog解析中常见的字符集:
+ *  utf8/utf8mb4    按utf8解码
+ *  latin1          mysql中的latin1实际为cp1252
+ *  ascii/binary    按字节解码
+ *  其它字符集按utf8做lossy解码
+ * </pre>
+ */
+pub fn decode(bytes: &[u8], collation_id: u16) -> String {
+    match charset_name(collation_id) {
+        Some("latin1") => bytes.iter().map(|b| decode_cp1252(*b)).collect(),
+        Some("ascii") | Some("binary") => match std::str::from_utf8(bytes) {
+            Ok(value) => value.to_string(),
+            Err(_) => bytes.iter().map(|b| *b as char).collect(),
+        },
+        _ => String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+fn decode_cp1252(b: u8) -> char {
+    match b {
+        0x80..=0x9f => char::from_u32(CP1252_HIGH[(b - 0x80) as usize] as u32).unwrap_or('\u{fffd}'),
+        _ => b as char,
+    }
+}
@@ -0,0 +1,525 @@
+use crate::command::command_type::{COM_BINLOG_DUMP, COM_BINLOG_DUMP_GTID, COM_PING, COM_QUERY, COM_QUIT, COM_REGISTER_SLAVE};
+use crate::command::event::BINLOG_MAGIC;
+use crate::command::gtid::GtidSet;
+
+// semi-sync复制中slave回复的ack, 不是COM_*命令, 但同样以一个固定的字节开头
+pub const SEMI_SYNC_ACK_HEADER: u8 = 0xef;
+
+// COM_BINLOG_DUMP的flags
+pub const BINLOG_DUMP_NON_BLOCK: u16 = 0x01;
+// MariaDB: 发送ANNOTATE_ROWS_EVENT
+pub const BINLOG_SEND_ANNOTATE_ROWS_EVENT: u16 = 0x02;
+// COM_BINLOG_DUMP_GTID: 带有gtid set, 从第一个不在集合中的事务开始发送
+pub const BINLOG_THROUGH_GTID: u16 = 0x04;
+
+/**
+ * <pre>
+ *  client发送给master的命令, 编码结果为packet body(不包括4字节的packet header):
+ *      1       command(COMMAND)
+ *      n       命令的参数, 由encode_body/decode_body处理
+ *  decode主要用于mock master和抓包排查, 会校验第一个字节并在长度不足时返回Err
+ * </pre>
+ */
+pub trait Command {
+    const COMMAND: u8;
+
+    fn encode_body(&self, out: &mut Vec<u8>);
+
+    fn decode_body(body: &mut CommandReader) -> Result<Self, String> where Self: Sized;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = vec![Self::COMMAND];
+        self.encode_body(&mut out);
+        out
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, String> where Self: Sized {
+        match buf.first() {
+            Some(command) if *command == Self::COMMAND => Self::decode_body(&mut CommandReader::new(&buf[1..])),
+            command => Err(format!("expect command {:#04x}, got {:?}", Self::COMMAND, command)),
+        }
+    }
+}
+
+// decode时使用的小端读取, 越界时返回Err而不是panic
+pub struct CommandReader<'a> {
+    buf: &'a [u8],
+    index: usize,
+}
+
+impl<'a> CommandReader<'a> {
+    pub fn new(buf: &'a [u8]) -> CommandReader<'a> {
+        CommandReader { buf, index: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.index
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.remaining() < len {
+            return Err(format!("command is truncated, need {} bytes at {}, remaining {}", len, self.index, self.remaining()));
+        }
+        let bytes = &self.buf[self.index..self.index + len];
+        self.index += len;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    // 1字节长度 + 内容
+    pub fn read_short_string(&mut self) -> Result<String, String> {
+        let len = self.read_u8()? as usize;
+        Ok(String::from_utf8_lossy(self.read_bytes(len)?).to_string())
+    }
+
+    pub fn read_rest_string(&mut self) -> String {
+        let rest = &self.buf[self.index..];
+        self.index = self.buf.len();
+        String::from_utf8_lossy(rest).to_string()
+    }
+}
+
+fn put_short_string(out: &mut Vec<u8>, value: &str) {
+    // 超过255字节时截断, 与mysql的net_store_data一致
+    let bytes = &value.as_bytes()[..value.len().min(u8::MAX as usize)];
+    out.push(bytes.len() as u8);
+    out.extend_from_slice(bytes);
+}
+
+// COM_QUIT, 没有参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuitCommand;
+
+impl Command for QuitCommand {
+    const COMMAND: u8 = COM_QUIT;
+
+    fn encode_body(&self, _out: &mut Vec<u8>) {}
+
+    fn decode_body(_body: &mut CommandReader) -> Result<Self, String> {
+        Ok(QuitCommand)
+    }
+}
+
+// COM_PING, 没有参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PingCommand;
+
+impl Command for PingCommand {
+    const COMMAND: u8 = COM_PING;
+
+    fn encode_body(&self, _out: &mut Vec<u8>) {}
+
+    fn decode_body(_body: &mut CommandReader) -> Result<Self, String> {
+        Ok(PingCommand)
+    }
+}
+
+/**
+ * <pre>
+ *  COM_QUERY
+ *  Bytes                       Name
+ *  -----                       ----
+ *  1                           command
+ *  n                           query string
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryCommand {
+    sql: String,
+}
+
+impl QueryCommand {
+    pub fn new(sql: &str) -> QueryCommand {
+        QueryCommand { sql: sql.to_string() }
+    }
+
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+}
+
+impl Command for QueryCommand {
+    const COMMAND: u8 = COM_QUERY;
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.sql.as_bytes());
+    }
+
+    fn decode_body(body: &mut CommandReader) -> Result<Self, String> {
+        Ok(QueryCommand { sql: body.read_rest_string() })
+    }
+}
+
+/**
+ * <pre>
+ *  COM_REGISTER_SLAVE
+ *  Bytes                       Name
+ *  -----                       ----
+ *  1                           command
+ *  4                           server_id
+ *  1 + n                       report_host
+ *  1 + n                       report_user
+ *  1 + n                       report_password
+ *  2                           report_port
+ *  4                           replication_rank
+ *  4                           master_id
+ *      let command = RegisterSlaveCommand::builder(65535)
+ *          .report_host("127.0.0.1").report_port(3306).report_user("canal").build();
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegisterSlaveCommand {
+    server_id: u32,
+    report_host: String,
+    report_user: String,
+    report_password: String,
+    report_port: u16,
+    replication_rank: u32,
+    master_id: u32,
+}
+
+impl RegisterSlaveCommand {
+    pub fn builder(server_id: u32) -> RegisterSlaveCommandBuilder {
+        RegisterSlaveCommandBuilder { command: RegisterSlaveCommand { server_id, ..RegisterSlaveCommand::default() } }
+    }
+
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn report_host(&self) -> &str {
+        &self.report_host
+    }
+    pub fn report_user(&self) -> &str {
+        &self.report_user
+    }
+    pub fn report_password(&self) -> &str {
+        &self.report_password
+    }
+    pub fn report_port(&self) -> u16 {
+        self.report_port
+    }
+    pub fn replication_rank(&self) -> u32 {
+        self.replication_rank
+    }
+    pub fn master_id(&self) -> u32 {
+        self.master_id
+    }
+}
+
+pub struct RegisterSlaveCommandBuilder {
+    command: RegisterSlaveCommand,
+}
+
+impl RegisterSlaveCommandBuilder {
+    pub fn report_host(mut self, report_host: &str) -> Self {
+        self.command.report_host = report_host.to_string();
+        self
+    }
+    pub fn report_user(mut self, report_user: &str) -> Self {
+        self.command.report_user = report_user.to_string();
+        self
+    }
+    pub fn report_password(mut self, report_password: &str) -> Self {
+        self.command.report_password = report_password.to_string();
+        self
+    }
+    pub fn report_port(mut self, report_port: u16) -> Self {
+        self.command.report_port = report_port;
+        self
+    }
+    pub fn replication_rank(mut self, replication_rank: u32) -> Self {
+        self.command.replication_rank = replication_rank;
+        self
+    }
+    pub fn master_id(mut self, master_id: u32) -> Self {
+        self.command.master_id = master_id;
+        self
+    }
+    pub fn build(self) -> RegisterSlaveCommand {
+        self.command
+    }
+}
+
+impl Command for RegisterSlaveCommand {
+    const COMMAND: u8 = COM_REGISTER_SLAVE;
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.server_id.to_le_bytes());
+        put_short_string(out, &self.report_host);
+        put_short_string(out, &self.report_user);
+        put_short_string(out, &self.report_password);
+        out.extend_from_slice(&self.report_port.to_le_bytes());
+        out.extend_from_slice(&self.replication_rank.to_le_bytes());
+        out.extend_from_slice(&self.master_id.to_le_bytes());
+    }
+
+    fn decode_body(body: &mut CommandReader) -> Result<Self, String> {
+        Ok(RegisterSlaveCommand {
+            server_id: body.read_u32()?,
+            report_host: body.read_short_string()?,
+            report_user: body.read_short_string()?,
+            report_password: body.read_short_string()?,
+            report_port: body.read_u16()?,
+            replication_rank: body.read_u32()?,
+            master_id: body.read_u32()?,
+        })
+    }
+}
+
+/**
+ * <pre>
+ *  COM_BINLOG_DUMP
+ *  Bytes                       Name
+ *  -----                       ----
+ *  1                           command
+ *  4                           binlog position to start at (little endian)
+ *  2                           binlog flags, BINLOG_DUMP_NON_BLOCK等
+ *  4                           server_id of the slave (little endian)
+ *  n                           binlog file name (optional)
+ *      let command = BinlogDumpCommand::builder(65535).binlog_file("mysql-bin.000001").binlog_position(4).build();
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BinlogDumpCommand {
+    binlog_position: u32,
+    flags: u16,
+    server_id: u32,
+    binlog_file: String,
+}
+
+impl BinlogDumpCommand {
+    pub fn builder(server_id: u32) -> BinlogDumpCommandBuilder {
+        BinlogDumpCommandBuilder { command: BinlogDumpCommand { server_id, ..BinlogDumpCommand::default() } }
+    }
+
+    pub fn binlog_position(&self) -> u32 {
+        self.binlog_position
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn binlog_file(&self) -> &str {
+        &self.binlog_file
+    }
+}
+
+pub struct BinlogDumpCommandBuilder {
+    command: BinlogDumpCommand,
+}
+
+impl BinlogDumpCommandBuilder {
+    pub fn binlog_file(mut self, binlog_file: &str) -> Self {
+        self.command.binlog_file = binlog_file.to_string();
+        self
+    }
+    pub fn binlog_position(mut self, binlog_position: u32) -> Self {
+        self.command.binlog_position = binlog_position;
+        self
+    }
+    pub fn flags(mut self, flags: u16) -> Self {
+        self.command.flags = flags;
+        self
+    }
+    pub fn build(self) -> BinlogDumpCommand {
+        self.command
+    }
+}
+
+impl Command for BinlogDumpCommand {
+    const COMMAND: u8 = COM_BINLOG_DUMP;
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.binlog_position.to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.server_id.to_le_bytes());
+        out.extend_from_slice(self.binlog_file.as_bytes());
+    }
+
+    fn decode_body(body: &mut CommandReader) -> Result<Self, String> {
+        Ok(BinlogDumpCommand {
+            binlog_position: body.read_u32()?,
+            flags: body.read_u16()?,
+            server_id: body.read_u32()?,
+            binlog_file: body.read_rest_string(),
+        })
+    }
+}
+
+/**
+ * <pre>
+ *  COM_BINLOG_DUMP_GTID, mysql 5.6+, 按已执行的gtid set开始dump
+ *  Bytes                       Name
+ *  -----                       ----
+ *  1                           command
+ *  2                           binlog flags, 有gtid set时带上BINLOG_THROUGH_GTID
+ *  4                           server_id of the slave (little endian)
+ *  4                           binlog file name length
+ *  n                           binlog file name, 通常为空, 由master按gtid set定位
+ *  8                           binlog position
+ *  if flags & BINLOG_THROUGH_GTID:
+ *      4                       gtid set data length
+ *      n                       gtid set, 编码见GtidSet::encode
+ *      let command = BinlogDumpGtidCommand::builder(65535).gtid_set(GtidSet::parse("...:1-100")?).build();
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BinlogDumpGtidCommand {
+    flags: u16,
+    server_id: u32,
+    binlog_file: String,
+    binlog_position: u64,
+    gtid_set: Option<GtidSet>,
+}
+
+impl BinlogDumpGtidCommand {
+    pub fn builder(server_id: u32) -> BinlogDumpGtidCommandBuilder {
+        BinlogDumpGtidCommandBuilder {
+            command: BinlogDumpGtidCommand {
+                server_id,
+                binlog_position: BINLOG_MAGIC.len() as u64,
+                ..BinlogDumpGtidCommand::default()
+            },
+        }
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn binlog_file(&self) -> &str {
+        &self.binlog_file
+    }
+    pub fn binlog_position(&self) -> u64 {
+        self.binlog_position
+    }
+    pub fn gtid_set(&self) -> Option<&GtidSet> {
+        self.gtid_set.as_ref()
+    }
+}
+
+pub struct BinlogDumpGtidCommandBuilder {
+    command: BinlogDumpGtidCommand,
+}
+
+impl BinlogDumpGtidCommandBuilder {
+    pub fn binlog_file(mut self, binlog_file: &str) -> Self {
+        self.command.binlog_file = binlog_file.to_string();
+        self
+    }
+    pub fn binlog_position(mut self, binlog_position: u64) -> Self {
+        self.command.binlog_position = binlog_position;
+        self
+    }
+    pub fn flags(mut self, flags: u16) -> Self {
+        self.command.flags = flags;
+        self
+    }
+    pub fn gtid_set(mut self, gtid_set: GtidSet) -> Self {
+        self.command.gtid_set = Some(gtid_set);
+        self
+    }
+    pub fn build(mut self) -> BinlogDumpGtidCommand {
+        if self.command.gtid_set.is_some() {
+            self.command.flags |= BINLOG_THROUGH_GTID;
+        }
+        self.command
+    }
+}
+
+impl Command for BinlogDumpGtidCommand {
+    const COMMAND: u8 = COM_BINLOG_DUMP_GTID;
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&self.server_id.to_le_bytes());
+        out.extend_from_slice(&(self.binlog_file.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.binlog_file.as_bytes());
+        out.extend_from_slice(&self.binlog_position.to_le_bytes());
+        if let Some(gtid_set) = self.gtid_set.as_ref() {
+            out.extend_from_slice(&(gtid_set.encoded_length() as u32).to_le_bytes());
+            out.extend_from_slice(&gtid_set.encode());
+        }
+    }
+
+    fn decode_body(body: &mut CommandReader) -> Result<Self, String> {
+        let flags = body.read_u16()?;
+        let server_id = body.read_u32()?;
+        let len = body.read_u32()? as usize;
+        let binlog_file = String::from_utf8_lossy(body.read_bytes(len)?).to_string();
+        let binlog_position = body.read_u64()?;
+        let gtid_set = if flags & BINLOG_THROUGH_GTID != 0 {
+            let len = body.read_u32()? as usize;
+            Some(GtidSet::decode(body.read_bytes(len)?)?)
+        } else {
+            None
+        };
+        Ok(BinlogDumpGtidCommand { flags, server_id, binlog_file, binlog_position, gtid_set })
+    }
+}
+
+/**
+ * <pre>
+ *  semi-sync ack, slave收到带有ack标记的event并处理完成后回复:
+ *  Bytes                       Name
+ *  -----                       ----
+ *  1                           0xef
+ *  8                           binlog position (little endian)
+ *  n                           binlog file name
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SemiAckCommand {
+    binlog_file: String,
+    binlog_position: u64,
+}
+
+impl SemiAckCommand {
+    pub fn new(binlog_file: &str, binlog_position: u64) -> SemiAckCommand {
+        SemiAckCommand { binlog_file: binlog_file.to_string(), binlog_position }
+    }
+
+    pub fn binlog_file(&self) -> &str {
+        &self.binlog_file
+    }
+    pub fn binlog_position(&self) -> u64 {
+        self.binlog_position
+    }
+}
+
+impl Command for SemiAckCommand {
+    const COMMAND: u8 = SEMI_SYNC_ACK_HEADER;
+
+    fn encode_body(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.binlog_position.to_le_bytes());
+        out.extend_from_slice(self.binlog_file.as_bytes());
+    }
+
+    fn decode_body(body: &mut CommandReader) -> Result<Self, String> {
+        let binlog_position = body.read_u64()?;
+        Ok(SemiAckCommand { binlog_file: body.read_rest_string(), binlog_position })
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::command::msc::ERROR_HEADER;
+
+macro_rules! server_errnos {
+    ($($name:ident = $code:expr, $hint:expr;)*) => {
+        /**
+         * <pre>
+         *  ErrorPacket中常见的errno, 每一种都带有排查建议,
+         *  没有列出的errno保留在Other中
+         * </pre>
+         */
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ServerErrno {
+            $($name,)*
+            Other(u16),
+        }
+
+        impl ServerErrno {
+            pub fn from_code(code: u16) -> ServerErrno {
+                match code {
+                    $($code => ServerErrno::$name,)*
+                    _ => ServerErrno::Other(code),
+                }
+            }
+
+            pub fn code(self) -> u16 {
+                match self {
+                    $(ServerErrno::$name => $code,)*
+                    ServerErrno::Other(code) => code,
+                }
+            }
+
+            pub fn hint(self) -> Option<&'static str> {
+                match self {
+                    $(ServerErrno::$name => Some($hint),)*
+                    ServerErrno::Other(_) => None,
+                }
+            }
+        }
+    };
+}
+
+server_errnos! {
+    TooManyConnections = 1040,
+        "the server reached max_connections, close idle connections or raise max_connections";
+    AccessDenied = 1045,
+        "check the username/password and that the user is allowed to connect from this host";
+    ServerShutdown = 1053,
+        "the server is shutting down, the connection will be re-established after it restarts";
+    HostBlocked = 1129,
+        "the host is blocked because of many connection errors, run FLUSH HOSTS on the server";
+    NoSuchTable = 1146,
+        "the table doesn't exist, it may have been dropped after the binlog position";
+    NetReadInterrupted = 1159,
+        "the connection was interrupted by the server, usually a KILL of the dump thread during maintenance";
+    UnknownSystemVariable = 1193,
+        "the server doesn't support this variable, usually because it is older than the expected version";
+    SpecificAccessDenied = 1227,
+        "grant the user REPLICATION SLAVE, REPLICATION CLIENT (and SELECT for table meta)";
+    MasterFatalReadingBinlog = 1236,
+        "the binlog file/position is purged or invalid, check SHOW BINARY LOGS and reset the position";
+    QueryKilled = 1317,
+        "the query or connection was killed on the server, check the processlist and the server log";
+    ConnectionKilled = 1927,
+        "the connection was killed on the server (MariaDB), usually during maintenance";
+    QueryInterrupted = 3024,
+        "the query exceeded max_execution_time, raise or disable it for the user";
+}
+
+impl ServerErrno {
+    // master主动断开连接(KILL, shutdown), 不是dump本身的问题
+    pub fn is_killed(self) -> bool {
+        matches!(self, ServerErrno::ServerShutdown | ServerErrno::NetReadInterrupted | ServerErrno::QueryKilled
+            | ServerErrno::ConnectionKilled)
+    }
+}
+
+/**
+ * <pre>
+ *  解析后的ErrorPacket
+ *      1               0xff
+ *      2               errno
+ *      1               sql state marker '#' (4.1+)
+ *      5               sql state
+ *      rest            message
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    errno: ServerErrno,
+    sql_state: String,
+    message: String,
+}
+
+impl ServerError {
+    pub fn new(code: u16, sql_state: &str, message: &str) -> ServerError {
+        ServerError { errno: ServerErrno::from_code(code), sql_state: sql_state.to_string(), message: message.to_string() }
+    }
+
+    // body不是ErrorPacket时返回None
+    pub fn from_packet(body: &[u8]) -> Option<ServerError> {
+        if body.first() != Some(&ERROR_HEADER) || body.len() < 3 {
+            return None;
+        }
+        let code = u16::from_le_bytes([body[1], body[2]]);
+        let (sql_state, message) = if body.get(3) == Some(&b'#') && body.len() >= 9 {
+            (String::from_utf8_lossy(&body[4..9]).to_string(), &body[9..])
+        } else {
+            (String::new(), &body[3..])
+        };
+        Some(ServerError {
+            errno: ServerErrno::from_code(code),
+            sql_state,
+            message: String::from_utf8_lossy(message).to_string(),
+        })
+    }
+
+    pub fn errno(&self) -> ServerErrno {
+        self.errno
+    }
+    pub fn code(&self) -> u16 {
+        self.errno.code()
+    }
+    pub fn sql_state(&self) -> &str {
+        &self.sql_state
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    pub fn hint(&self) -> Option<&'static str> {
+        self.errno.hint()
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ErrorPacket [errorNumber={}, message={}, sqlState={}]", self.code(), self.message, self.sql_state)?;
+        if let Some(hint) = self.hint() {
+            write!(f, ", hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use crate::command::event::{checksum, FormatDescriptionLogEvent, LogHeader, TableMapLogEvent};
+use crate::error::CanalError;
+use crate::instance::EntryPosition;
+
+/**
+ * <pre>
+ *  对应canal中的LogContext, 保存解析过程中跨event的状态:
+ *  当前的format description, checksum算法, 位点以及table_id到table map的映射
+ * </pre>
+ */
+pub struct LogContext {
+    format_description: Option<FormatDescriptionLogEvent>,
+    checksum_alg: u8,
+    log_position: EntryPosition,
+    table_maps: HashMap<u64, TableMapLogEvent>,
+    // table map的metadata解析失败时是否继续
+    tolerant: bool,
+    // 任何导致数据失真的情况都返回错误, 优先于tolerant
+    strict: bool,
+    // strict模式下第一个导致失真的问题
+    violation: Option<String>,
+    // 最近一个GTID event中的gtid, 属于之后的事务
+    gtid: Option<String>,
+    // 同一个GTID event中的(original, immediate)commit timestamp, 微秒
+    commit_timestamps: Option<(i64, i64)>,
+    // 最近一个ROWS_QUERY/ANNOTATE_ROWS中的语句, 属于之后的rows event
+    statement: Option<String>,
+}
+
+impl Default for LogContext {
+    fn default() -> Self {
+        LogContext::new()
+    }
+}
+
+impl LogContext {
+    pub fn new() -> LogContext {
+        LogContext {
+            format_description: None,
+            checksum_alg: checksum::BINLOG_CHECKSUM_ALG_OFF,
+            log_position: EntryPosition::default(),
+            table_maps: HashMap::new(),
+            tolerant: false,
+            strict: false,
+            violation: None,
+            gtid: None,
+            commit_timestamps: None,
+            statement: None,
+        }
+    }
+
+    pub fn format_description(&self) -> Option<&FormatDescriptionLogEvent> {
+        self.format_description.as_ref()
+    }
+
+    /**
+     * <pre>
+     *  之后的event按新的format description中的checksum alg计算长度, 同一个dump中可能变化:
+     *      server从5.5升级     之前的文件没有checksum(UNDEF), 之后为CRC32
+     *      binlog_checksum修改  rotate之后的文件从CRC32变为NONE或者相反
+     *  UNDEF表示format description来自5.6.1之前的server, event没有checksum
+     * </pre>
+     */
+    pub fn set_format_description(&mut self, format_description: FormatDescriptionLogEvent) {
+        self.checksum_alg = match format_description.checksum_alg() {
+            checksum::BINLOG_CHECKSUM_ALG_UNDEF => checksum::BINLOG_CHECKSUM_ALG_OFF,
+            checksum_alg => checksum_alg,
+        };
+        self.format_description = Some(format_description);
+    }
+
+    pub fn checksum_alg(&self) -> u8 {
+        self.checksum_alg
+    }
+
+    pub fn set_checksum_alg(&mut self, checksum_alg: u8) {
+        self.checksum_alg = checksum_alg;
+    }
+
+    pub fn log_position(&self) -> &EntryPosition {
+        &self.log_position
+    }
+
+    pub fn log_position_mut(&mut self) -> &mut EntryPosition {
+        &mut self.log_position
+    }
+
+    pub fn set_log_position(&mut self, log_position: EntryPosition) {
+        self.log_position = log_position;
+    }
+
+    pub fn put_table(&mut self, table_map: TableMapLogEvent) {
+        self.table_maps.insert(table_map.table_id(), table_map);
+    }
+
+    pub fn get_table(&self, table_id: u64) -> Option<&TableMapLogEvent> {
+        self.table_maps.get(&table_id)
+    }
+
+    // rotate到新文件时table_id会重新分配
+    pub fn clear_all_tables(&mut self) {
+        self.table_maps.clear();
+    }
+
+    pub fn is_tolerant(&self) -> bool {
+        self.tolerant
+    }
+
+    pub fn set_tolerant(&mut self, tolerant: bool) {
+        self.tolerant = tolerant;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn violation(&self) -> Option<&str> {
+        self.violation.as_deref()
+    }
+
+    /**
+     * <pre>
+     *  遇到会导致数据失真的情况(不认识的status var, 没有映射的字符集, 不完整的metadata, 跳过的event)时调用:
+     *  非strict模式返回Ok, 由调用方按原来的方式处理(打印日志后继续);
+     *  strict模式下记录并返回带有位置的错误, 例如
+     *      strict mode: unknown status var 14 in query event, at mysql-bin.000001:1200 (event type 2, server_id 1, event_len 90)
+     * </pre>
+     */
+    pub fn fidelity_loss(&mut self, header: &LogHeader, problem: &str) -> Result<(), CanalError> {
+        if !self.strict {
+            return Ok(());
+        }
+        let offset = header.log_pos().saturating_sub(header.event_len());
+        let report = format!("strict mode: {}, at {}:{} (event type {}, server_id {}, event_len {})", problem,
+                             self.log_position.journal_name(), offset, header.kind(), header.server_id(),
+                             header.event_len());
+        self.violation = Some(report.clone());
+        Err(CanalError::Decode(report))
+    }
+
+    pub fn gtid(&self) -> Option<&str> {
+        self.gtid.as_deref()
+    }
+
+    pub fn set_gtid(&mut self, gtid: Option<String>) {
+        self.gtid = gtid;
+    }
+
+    pub fn commit_timestamps(&self) -> Option<(i64, i64)> {
+        self.commit_timestamps
+    }
+
+    pub fn set_commit_timestamps(&mut self, commit_timestamps: Option<(i64, i64)>) {
+        self.commit_timestamps = commit_timestamps;
+    }
+
+    pub fn statement(&self) -> Option<&str> {
+        self.statement.as_deref()
+    }
+
+    pub fn set_statement(&mut self, statement: Option<String>) {
+        self.statement = statement;
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use crate::command::event::{EventType, FormatDescriptionLogEvent, GtidTaggedLogEvent, HeartbeatLogEvent,
+                            IncidentLogEvent, LogContext, LogHeader, QueryLogEvent, RotateLogEvent, RowsLogEvent,
+                            RowsQueryLogEvent, TableMapCache, TableMapLogEvent, LOG_HEADER_LEN};
+use crate::command::charset;
+use crate::command::gtid::{event_commit_timestamps, event_gtid};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+use crate::metrics::MemoryGauge;
+
+#[derive(Debug, Clone)]
+pub enum LogEvent {
+    FormatDescription(FormatDescriptionLogEvent),
+    Rotate(RotateLogEvent),
+    Query(QueryLogEvent),
+    TableMap(TableMapLogEvent),
+    Rows(RowsLogEvent),
+    // ROWS_QUERY/MariaDB ANNOTATE_ROWS
+    RowsQuery(RowsQueryLogEvent),
+    // master通知的异常, 例如LOST_EVENTS
+    Incident(IncidentLogEvent),
+    // master空闲时发送, 不在binlog文件中
+    Heartbeat(HeartbeatLogEvent),
+    // 暂不解析的event, 只保留header
+    Unknown(LogHeader),
+}
+
+impl LogEvent {
+    pub fn header(&self) -> &LogHeader {
+        match self {
+            LogEvent::FormatDescription(event) => event.header(),
+            LogEvent::Rotate(event) => event.header(),
+            LogEvent::Query(event) => event.header(),
+            LogEvent::TableMap(event) => event.header(),
+            LogEvent::Rows(event) => event.header(),
+            LogEvent::RowsQuery(event) => event.header(),
+            LogEvent::Incident(event) => event.header(),
+            LogEvent::Heartbeat(event) => event.header(),
+            LogEvent::Unknown(header) => header,
+        }
+    }
+}
+
+// 遇到无法解析的event时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsupportedEventPolicy {
+    // 直接丢弃
+    Skip,
+    // 丢弃并在每种event type第一次出现时打印警告
+    Warn,
+    // 返回错误, 中断dump
+    Fail,
+}
+
+impl UnsupportedEventPolicy {
+    pub fn from_name(name: &str) -> Result<UnsupportedEventPolicy, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(UnsupportedEventPolicy::Skip),
+            "warn" => Ok(UnsupportedEventPolicy::Warn),
+            "fail" => Ok(UnsupportedEventPolicy::Fail),
+            _ => Err(format!("unknown unsupported event policy {}, expect skip/warn/fail", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnsupportedEventPolicy::Skip => "skip",
+            UnsupportedEventPolicy::Warn => "warn",
+            UnsupportedEventPolicy::Fail => "fail",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  对应canal中的LogDecoder, 根据header中的event type解析event,
+ *  并把解析过程中需要跨event保存的信息(format description, table map)写入LogContext.
+ *  无法解析的event分为两类, 分别按照各自的policy处理并计数:
+ *  unknown         未定义的event type, 通常来自更新版本的master, 默认Warn
+ *  unimplemented   已知但不会解析且不带行变更的event type(VIEW_CHANGE, TRANSACTION_CONTEXT等), 默认Skip
+ *  undecoded rows  带有行变更但尚未解码的event type(PARTIAL_UPDATE_ROWS, TRANSACTION_PAYLOAD), 默认Fail,
+ *                  跳过时丢失行变更, 不受LOG_EVENT_IGNORABLE_F影响
+ *  前两类中header带有LOG_EVENT_IGNORABLE_F的event由master声明可以忽略, 改为按ignorable_event_policy处理,
+ *  默认Skip, 并且strict时不算作fidelity loss
+ *  内容相同的table map通过TableMapCache复用解析结果.
+ *  LogContext为strict时, 跳过event, 不认识的status var, 没有映射的字符集都通过LogContext::fidelity_loss返回错误
+ * </pre>
+ */
+pub struct LogDecoder {
+    unknown_event_policy: UnsupportedEventPolicy,
+    unimplemented_event_policy: UnsupportedEventPolicy,
+    undecoded_rows_event_policy: UnsupportedEventPolicy,
+    ignorable_event_policy: UnsupportedEventPolicy,
+    // event type -> 遇到的数量
+    unsupported_counts: BTreeMap<u8, u64>,
+    table_map_cache: TableMapCache,
+}
+
+impl Default for LogDecoder {
+    fn default() -> Self {
+        LogDecoder::new()
+    }
+}
+
+impl LogDecoder {
+    pub fn new() -> LogDecoder {
+        LogDecoder {
+            unknown_event_policy: UnsupportedEventPolicy::Warn,
+            unimplemented_event_policy: UnsupportedEventPolicy::Skip,
+            undecoded_rows_event_policy: UnsupportedEventPolicy::Fail,
+            ignorable_event_policy: UnsupportedEventPolicy::Skip,
+            unsupported_counts: BTreeMap::new(),
+            table_map_cache: TableMapCache::default(),
+        }
+    }
+
+    pub fn unknown_event_policy(&self) -> UnsupportedEventPolicy {
+        self.unknown_event_policy
+    }
+
+    pub fn set_unknown_event_policy(&mut self, policy: UnsupportedEventPolicy) {
+        self.unknown_event_policy = policy;
+    }
+
+    pub fn unimplemented_event_policy(&self) -> UnsupportedEventPolicy {
+        self.unimplemented_event_policy
+    }
+
+    pub fn set_unimplemented_event_policy(&mut self, policy: UnsupportedEventPolicy) {
+        self.unimplemented_event_policy = policy;
+    }
+
+    pub fn undecoded_rows_event_policy(&self) -> UnsupportedEventPolicy {
+        self.undecoded_rows_event_policy
+    }
+
+    pub fn set_undecoded_rows_event_policy(&mut self, policy: UnsupportedEventPolicy) {
+        self.undecoded_rows_event_policy = policy;
+    }
+
+    pub fn ignorable_event_policy(&self) -> UnsupportedEventPolicy {
+        self.ignorable_event_policy
+    }
+
+    pub fn set_ignorable_event_policy(&mut self, policy: UnsupportedEventPolicy) {
+        self.ignorable_event_policy = policy;
+    }
+
+    pub fn unsupported_counts(&self) -> &BTreeMap<u8, u64> {
+        &self.unsupported_counts
+    }
+
+    pub fn unsupported_count(&self) -> u64 {
+        self.unsupported_counts.values().sum()
+    }
+
+    pub fn table_map_cache(&self) -> &TableMapCache {
+        &self.table_map_cache
+    }
+
+    // capacity为0时关闭缓存
+    pub fn set_table_map_cache_capacity(&mut self, capacity: usize) {
+        let mut table_map_cache = TableMapCache::new(capacity);
+        if let Some(gauge) = self.table_map_cache.memory_gauge() {
+            table_map_cache.set_memory_gauge(gauge.clone());
+        }
+        self.table_map_cache = table_map_cache;
+    }
+
+    pub fn set_table_map_cache_gauge(&mut self, gauge: MemoryGauge) {
+        self.table_map_cache.set_memory_gauge(gauge);
+    }
+
+    pub fn decode(&mut self, event: &[u8], context: &mut LogContext) -> Result<LogEvent, CanalError> {
+        let mut buffer = LogBuffer::new(event);
+        let header = LogHeader::from(&mut buffer, context.checksum_alg())?;
+        // packet与header中的长度不一致, 通常是传输中被截断, 重新dump可以恢复
+        if event.len() < header.event_len() as usize {
+            return Err(CanalError::Protocol(None, format!("event truncated, expect {} bytes but got {}",
+                                                          header.event_len(), event.len())));
+        }
+        match header.event_type() {
+            Some(EventType::FormatDescriptionEvent) => {
+                let description = FormatDescriptionLogEvent::from(header, &mut buffer)?;
+                // 缓存的table map按之前的post header长度解析
+                self.table_map_cache.clear();
+                context.set_format_description(description.clone());
+                Ok(LogEvent::FormatDescription(description))
+            }
+            Some(EventType::RotateEvent) => {
+                let rotate = RotateLogEvent::from(header, &mut buffer, context.format_description())?;
+                let position = context.log_position_mut();
+                position.set_journal_name(rotate.filename());
+                position.set_position(rotate.position());
+                Ok(LogEvent::Rotate(rotate))
+            }
+            Some(EventType::QueryEvent) => {
+                context.set_statement(None);
+                let description = context.format_description()
+                    .ok_or_else(|| "format description event is missing before query".to_string())?;
+                let query = QueryLogEvent::from(header, &mut buffer, description)?;
+                if let Some(code) = query.unknown_status_var() {
+                    context.fidelity_loss(query.header(), &format!("unknown status var {} in query event", code))?;
+                }
+                let client_charset = query.client_charset().unwrap_or(charset::UTF8_GENERAL_CI);
+                if charset::charset_name(client_charset).is_none() {
+                    context.fidelity_loss(query.header(), &format!("no charset mapping for collation {} of query",
+                                                                   client_charset))?;
+                }
+                Ok(LogEvent::Query(query))
+            }
+            Some(EventType::TableMapEvent) => {
+                let body = &event[LOG_HEADER_LEN..LOG_HEADER_LEN + header.data_len()];
+                if let Some(table_map) = self.table_map_cache.get(&header, body) {
+                    context.put_table(table_map.clone());
+                    return Ok(LogEvent::TableMap(table_map));
+                }
+                let description = context.format_description()
+                    .ok_or_else(|| "format description event is missing before table map".to_string())?;
+                let tolerant = context.is_tolerant() && !context.is_strict();
+                let table_map = TableMapLogEvent::from(header, &mut buffer, description, tolerant)?;
+                if let Some(e) = table_map.partial_error() {
+                    println!("table map of {}.{} (table_id={}) is partially decoded: {}",
+                             table_map.db_name(), table_map.table_name(), table_map.table_id(), e);
+                }
+                if context.is_strict() {
+                    let unmapped = table_map.column_info().iter()
+                        .filter_map(|info| info.charset())
+                        .find(|collation| charset::charset_name(*collation as u16).is_none());
+                    if let Some(collation) = unmapped {
+                        context.fidelity_loss(table_map.header(), &format!("no charset mapping for collation {} of {}.{}",
+                                                                           collation, table_map.db_name(),
+                                                                           table_map.table_name()))?;
+                    }
+                }
+                self.table_map_cache.put(body, &table_map);
+                context.put_table(table_map.clone());
+                Ok(LogEvent::TableMap(table_map))
+            }
+            Some(kind) if kind.has_undecoded_rows() => {
+                self.unsupported(header, self.undecoded_rows_event_policy, "undecoded rows", context)
+            }
+            Some(kind) if kind.is_unimplemented() => {
+                self.unsupported(header, self.unimplemented_event_policy, "unimplemented", context)
+            }
+            // 列值依赖table map, 在LogEventConvert中解析
+            Some(kind) if kind.is_rows() => {
+                let description = context.format_description()
+                    .ok_or_else(|| "format description event is missing before rows".to_string())?;
+                Ok(LogEvent::Rows(RowsLogEvent::from(header, &mut buffer, description)?))
+            }
+            // 之后的rows event都来自这条语句, 直到下一个语句或者事务结束
+            Some(EventType::RowsQueryLogEvent) | Some(EventType::AnnotateRowsEvent) => {
+                let rows_query = RowsQueryLogEvent::from(header, &mut buffer)?;
+                context.set_statement(Some(rows_query.query().to_string()));
+                Ok(LogEvent::RowsQuery(rows_query))
+            }
+            Some(EventType::IncidentEvent) => {
+                Ok(LogEvent::Incident(IncidentLogEvent::from(header, &mut buffer, context.format_description())?))
+            }
+            Some(kind) if kind.is_heartbeat() => {
+                Ok(LogEvent::Heartbeat(HeartbeatLogEvent::from(header, &mut buffer)?))
+            }
+            Some(EventType::XidEvent) => {
+                context.set_statement(None);
+                Ok(LogEvent::Unknown(header))
+            }
+            // 无法解析时按照没有gtid处理
+            Some(EventType::GtidTaggedLogEvent) => {
+                context.set_statement(None);
+                match GtidTaggedLogEvent::from(header.clone(), &mut buffer, context.format_description()) {
+                    Ok(tagged) => {
+                        context.set_gtid(Some(tagged.gtid()));
+                        context.set_commit_timestamps(Some((tagged.original_commit_timestamp() as i64,
+                                                            tagged.immediate_commit_timestamp() as i64)));
+                    }
+                    Err(e) => {
+                        context.set_gtid(None);
+                        context.set_commit_timestamps(None);
+                        context.fidelity_loss(&header, &format!("undecodable tagged gtid event: {}", e))?;
+                    }
+                }
+                Ok(LogEvent::Unknown(header))
+            }
+            // anonymous gtid清空之前的gtid
+            Some(kind) if kind.is_gtid() => {
+                context.set_statement(None);
+                let post_header_len = context.format_description().and_then(|description| description.post_header_len(kind));
+                context.set_gtid(event_gtid(&header, event));
+                context.set_commit_timestamps(event_commit_timestamps(&header, event, post_header_len));
+                Ok(LogEvent::Unknown(header))
+            }
+            Some(_) => Ok(LogEvent::Unknown(header)),
+            None => self.unsupported(header, self.unknown_event_policy, "unknown", context),
+        }
+    }
+
+    fn unsupported(&mut self, header: LogHeader, policy: UnsupportedEventPolicy, reason: &str, context: &mut LogContext)
+                   -> Result<LogEvent, CanalError> {
+        let ignorable = header.is_ignorable() && !header.event_type().is_some_and(|kind| kind.has_undecoded_rows());
+        let (policy, reason) = if ignorable { (self.ignorable_event_policy, "ignorable") } else { (policy, reason) };
+        let message = format!("{} event type {} (log_pos={}, event_len={})",
+                              reason, header.kind(), header.log_pos(), header.event_len());
+        let count = self.unsupported_counts.entry(header.kind()).or_insert(0);
+        *count += 1;
+        if policy == UnsupportedEventPolicy::Fail {
+            return Err(CanalError::Decode(message));
+        }
+        if !ignorable {
+            context.fidelity_loss(&header, &format!("skip {} event type {}", reason, header.kind()))?;
+        }
+        if policy == UnsupportedEventPolicy::Warn && *count == 1 {
+            println!("skip {}", message);
+        }
+        Ok(LogEvent::Unknown(header))
+    }
+}
@@ -0,0 +1,137 @@
+use std::convert::TryFrom;
+
+// mysql 8.0定义的event type数量, format description中post header len数组的长度.
+// 更新的版本数组更长(8.4为43), 超出的code按FormatDescriptionLogEvent::post_header_len取得
+pub const ENUM_END_EVENT: u8 = 42;
+
+macro_rules! event_types {
+    ($($name:ident = $code:expr,)*) => {
+        /**
+         * <pre>
+         *  binlog event header中的type code, 未定义的code通过TryFrom<u8>返回Err(code).
+         *  format description中post header len按code - 1索引, 见FormatDescriptionLogEvent::post_header_len
+         * </pre>
+         */
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum EventType {
+            $($name = $code,)*
+        }
+
+        impl TryFrom<u8> for EventType {
+            type Error = u8;
+
+            fn try_from(code: u8) -> Result<Self, Self::Error> {
+                match code {
+                    $($code => Ok(EventType::$name),)*
+                    _ => Err(code),
+                }
+            }
+        }
+    };
+}
+
+event_types! {
+    UnknownEvent = 0,
+    StartEventV3 = 1,
+    QueryEvent = 2,
+    StopEvent = 3,
+    RotateEvent = 4,
+    IntvarEvent = 5,
+    LoadEvent = 6,
+    SlaveEvent = 7,
+    CreateFileEvent = 8,
+    AppendBlockEvent = 9,
+    ExecLoadEvent = 10,
+    DeleteFileEvent = 11,
+    NewLoadEvent = 12,
+    RandEvent = 13,
+    UserVarEvent = 14,
+    FormatDescriptionEvent = 15,
+    XidEvent = 16,
+    BeginLoadQueryEvent = 17,
+    ExecuteLoadQueryEvent = 18,
+    TableMapEvent = 19,
+    PreGaWriteRowsEvent = 20,
+    PreGaUpdateRowsEvent = 21,
+    PreGaDeleteRowsEvent = 22,
+    WriteRowsEventV1 = 23,
+    UpdateRowsEventV1 = 24,
+    DeleteRowsEventV1 = 25,
+    IncidentEvent = 26,
+    HeartbeatLogEvent = 27,
+    IgnorableLogEvent = 28,
+    RowsQueryLogEvent = 29,
+    WriteRowsEvent = 30,
+    UpdateRowsEvent = 31,
+    DeleteRowsEvent = 32,
+    GtidLogEvent = 33,
+    AnonymousGtidLogEvent = 34,
+    PreviousGtidsLogEvent = 35,
+    TransactionContextEvent = 36,
+    ViewChangeEvent = 37,
+    XaPrepareLogEvent = 38,
+    PartialUpdateRowsEvent = 39,
+    TransactionPayloadEvent = 40,
+    HeartbeatLogEventV2 = 41,
+    // mysql 8.3+
+    GtidTaggedLogEvent = 42,
+
+    // mariadb
+    AnnotateRowsEvent = 160,
+    BinlogCheckpointEvent = 161,
+    GtidEvent = 162,
+    GtidListEvent = 163,
+    StartEncryptionEvent = 164,
+}
+
+impl EventType {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn is_heartbeat(self) -> bool {
+        matches!(self, EventType::HeartbeatLogEvent | EventType::HeartbeatLogEventV2)
+    }
+
+    // 事务(event group)的第一个event
+    pub fn is_gtid(self) -> bool {
+        matches!(self, EventType::GtidLogEvent | EventType::AnonymousGtidLogEvent | EventType::GtidTaggedLogEvent
+            | EventType::GtidEvent)
+    }
+
+    pub fn is_rows(self) -> bool {
+        matches!(self, EventType::WriteRowsEventV1 | EventType::UpdateRowsEventV1 | EventType::DeleteRowsEventV1
+            | EventType::WriteRowsEvent | EventType::UpdateRowsEvent | EventType::DeleteRowsEvent
+            | EventType::PartialUpdateRowsEvent)
+    }
+
+    // 带有行变更但尚未解码的event: PARTIAL_JSON的部分更新和binlog_transaction_compression压缩的事务,
+    // 跳过会丢失行变更, 与is_unimplemented分开处理
+    pub fn has_undecoded_rows(self) -> bool {
+        matches!(self, EventType::PartialUpdateRowsEvent | EventType::TransactionPayloadEvent)
+    }
+
+    // 已知但mini-canal不会解析的event: 早期版本的load/rows event, group replication以及尚未支持的新特性.
+    // 这里不使用通配, 新增event type时需要明确归类
+    pub fn is_unimplemented(self) -> bool {
+        match self {
+            EventType::StartEventV3 | EventType::LoadEvent | EventType::SlaveEvent | EventType::CreateFileEvent
+            | EventType::AppendBlockEvent | EventType::ExecLoadEvent | EventType::DeleteFileEvent
+            | EventType::NewLoadEvent | EventType::BeginLoadQueryEvent | EventType::ExecuteLoadQueryEvent
+            | EventType::PreGaWriteRowsEvent | EventType::PreGaUpdateRowsEvent | EventType::PreGaDeleteRowsEvent
+            | EventType::TransactionContextEvent | EventType::ViewChangeEvent
+            | EventType::StartEncryptionEvent => true,
+            EventType::PartialUpdateRowsEvent | EventType::TransactionPayloadEvent | EventType::UnknownEvent | EventType::QueryEvent | EventType::StopEvent | EventType::RotateEvent
+            | EventType::IntvarEvent | EventType::RandEvent | EventType::UserVarEvent
+            | EventType::FormatDescriptionEvent | EventType::XidEvent | EventType::TableMapEvent
+            | EventType::WriteRowsEventV1 | EventType::UpdateRowsEventV1 | EventType::DeleteRowsEventV1
+            | EventType::IncidentEvent | EventType::HeartbeatLogEvent | EventType::IgnorableLogEvent
+            | EventType::RowsQueryLogEvent | EventType::WriteRowsEvent | EventType::UpdateRowsEvent
+            | EventType::DeleteRowsEvent | EventType::GtidLogEvent | EventType::AnonymousGtidLogEvent
+            | EventType::PreviousGtidsLogEvent | EventType::XaPrepareLogEvent | EventType::HeartbeatLogEventV2
+            | EventType::GtidTaggedLogEvent | EventType::AnnotateRowsEvent | EventType::BinlogCheckpointEvent | EventType::GtidEvent
+            | EventType::GtidListEvent => false,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::command::event::{checksum, EventType, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+pub const ST_SERVER_VER_LEN: usize = 50;
+
+// 5.6.1开始format description中带有checksum alg
+const CHECKSUM_VERSION_SPLIT: [u32; 3] = [5, 6, 1];
+
+/**
+ * <pre>
+ *  FORMAT_DESCRIPTION_EVENT
+ *  Bytes       Name
+ *  -----       ----
+ *  2           binlog version
+ *  50          server version
+ *  4           create timestamp
+ *  1           event header length
+ *  n           post header length of each event type (index = type - 1)
+ *  1           checksum alg (5.6.1+)
+ *  4           checksum (5.6.1+)
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct FormatDescriptionLogEvent {
+    header: LogHeader,
+    binlog_version: u16,
+    server_version: String,
+    create_timestamp: u32,
+    common_header_len: u8,
+    post_header_len: Vec<u8>,
+    checksum_alg: u8,
+}
+
+impl FormatDescriptionLogEvent {
+    pub fn from(mut header: LogHeader, buffer: &mut LogBuffer) -> Result<FormatDescriptionLogEvent, CanalError> {
+        buffer.set_position(LOG_HEADER_LEN)?;
+        let binlog_version = buffer.get_uint16()?;
+        let server_version = buffer.get_fix_string(ST_SERVER_VER_LEN)?;
+        let create_timestamp = buffer.get_uint32()?;
+        let common_header_len = buffer.get_uint8()?;
+        let mut number_of_event_types = (header.event_len() as usize)
+            .checked_sub(LOG_HEADER_LEN + 2 + ST_SERVER_VER_LEN + 4 + 1)
+            .ok_or_else(|| CanalError::Decode(format!("format description event too short: {}", header.event_len())))?;
+
+        let has_checksum = version_product(&server_version) >= version_product_of(CHECKSUM_VERSION_SPLIT);
+        if has_checksum {
+            number_of_event_types = number_of_event_types
+                .checked_sub(1 + checksum::BINLOG_CHECKSUM_LEN)
+                .ok_or_else(|| CanalError::Decode(format!("format description event too short: {}",
+                                                          header.event_len())))?;
+        }
+        let post_header_len = buffer.get_bytes(number_of_event_types)?.to_vec();
+        let checksum_alg = if has_checksum { buffer.get_uint8()? } else { checksum::BINLOG_CHECKSUM_ALG_UNDEF };
+        header.set_checksum_alg(checksum_alg);
+        Ok(FormatDescriptionLogEvent {
+            header,
+            binlog_version,
+            server_version,
+            create_timestamp,
+            common_header_len,
+            post_header_len,
+            checksum_alg,
+        })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn binlog_version(&self) -> u16 {
+        self.binlog_version
+    }
+    pub fn server_version(&self) -> &str {
+        &self.server_version
+    }
+    pub fn create_timestamp(&self) -> u32 {
+        self.create_timestamp
+    }
+    pub fn common_header_len(&self) -> u8 {
+        self.common_header_len
+    }
+    pub fn checksum_alg(&self) -> u8 {
+        self.checksum_alg
+    }
+
+    // post_header_len按event type - 1索引, master不认识的event type返回None
+    pub fn post_header_len(&self, kind: EventType) -> Option<usize> {
+        let index = (kind.code() as usize).checked_sub(1)?;
+        self.post_header_len.get(index).map(|len| *len as usize)
+    }
+
+    pub fn post_header_lens(&self) -> &Vec<u8> {
+        &self.post_header_len
+    }
+}
+
+fn version_product(server_version: &str) -> u32 {
+    let mut split = [0u32; 3];
+    for (i, part) in server_version.split('.').take(3).enumerate() {
+        let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        split[i] = digits.parse().unwrap_or(0);
+    }
+    version_product_of(split)
+}
+
+fn version_product_of(split: [u32; 3]) -> u32 {
+    (split[0] * 256 + split[1]) * 256 + split[2]
+}
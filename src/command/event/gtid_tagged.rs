@@ -0,0 +1,163 @@
+use crate::command::event::{EventType, FormatDescriptionLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::gtid::format_uuid;
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// 目前认识的最后一个字段(commit_group_ticket)的id
+const LAST_KNOWN_FIELD_ID: u64 = 11;
+
+/**
+ * <pre>
+ *  GTID_TAGGED_LOG_EVENT(mysql 8.3+, gtid_mode带tag时代替GTID_LOG_EVENT), body为serialization库的消息:
+ *      varlen          消息的总字节数
+ *      varlen          last_non_ignorable_field_id, 不认识的字段id不大于该值时无法跳过
+ *      (varlen id, value)...   字段按id递增, 可选字段没有设置时省略
+ *  字段id:
+ *      0   gtid_flags                  uint
+ *      1   uuid                        16个uint
+ *      2   gno                         int
+ *      3   tag                         varlen长度 + 内容
+ *      4   last_committed              int
+ *      5   sequence_number             int
+ *      6   immediate_commit_timestamp  uint, 微秒
+ *      7   original_commit_timestamp   uint, 省略时与immediate相同
+ *      8   transaction_length          uint
+ *      9   immediate_server_version    uint
+ *      10  original_server_version     uint, 省略时与immediate相同
+ *      11  commit_group_ticket         uint, 可选
+ *  更新的版本在末尾追加的可忽略字段被跳过; post header长度按format description跳过(8.4中为0)
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct GtidTaggedLogEvent {
+    header: LogHeader,
+    flags: u8,
+    sid: [u8; 16],
+    gno: i64,
+    tag: String,
+    last_committed: i64,
+    sequence_number: i64,
+    immediate_commit_timestamp: u64,
+    original_commit_timestamp: Option<u64>,
+    transaction_length: u64,
+    immediate_server_version: u32,
+    original_server_version: Option<u32>,
+    commit_group_ticket: Option<u64>,
+}
+
+impl GtidTaggedLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer, description: Option<&FormatDescriptionLogEvent>)
+                -> Result<GtidTaggedLogEvent, CanalError> {
+        let post_header_len = description
+            .and_then(|description| description.post_header_len(EventType::GtidTaggedLogEvent))
+            .unwrap_or(0);
+        buffer.set_position(LOG_HEADER_LEN + post_header_len)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        let start = buffer.position();
+        let size = buffer.get_varlen_uint()? as usize;
+        let end = start.checked_add(size).filter(|end| *end <= LOG_HEADER_LEN + header.data_len())
+            .ok_or_else(|| CanalError::Decode(format!("tagged gtid event message size {} exceeds event", size)))?;
+        buffer.set_limit(end)?;
+        let last_non_ignorable = buffer.get_varlen_uint()?;
+        if last_non_ignorable > LAST_KNOWN_FIELD_ID {
+            return Err(CanalError::Decode(format!("tagged gtid event has non-ignorable field {} beyond the known {}",
+                               last_non_ignorable, LAST_KNOWN_FIELD_ID)));
+        }
+        let mut event = GtidTaggedLogEvent {
+            header,
+            flags: 0,
+            sid: [0u8; 16],
+            gno: 0,
+            tag: String::new(),
+            last_committed: 0,
+            sequence_number: 0,
+            immediate_commit_timestamp: 0,
+            original_commit_timestamp: None,
+            transaction_length: 0,
+            immediate_server_version: 0,
+            original_server_version: None,
+            commit_group_ticket: None,
+        };
+        while buffer.has_remaining() {
+            let id = buffer.get_varlen_uint()?;
+            match id {
+                0 => event.flags = varlen_u8(buffer)?,
+                1 => {
+                    for byte in event.sid.iter_mut() {
+                        *byte = varlen_u8(buffer)?;
+                    }
+                }
+                2 => event.gno = buffer.get_varlen_int()?,
+                3 => {
+                    let len = buffer.get_varlen_uint()? as usize;
+                    event.tag = String::from_utf8_lossy(buffer.get_bytes(len)?).to_string();
+                }
+                4 => event.last_committed = buffer.get_varlen_int()?,
+                5 => event.sequence_number = buffer.get_varlen_int()?,
+                6 => event.immediate_commit_timestamp = buffer.get_varlen_uint()?,
+                7 => event.original_commit_timestamp = Some(buffer.get_varlen_uint()?),
+                8 => event.transaction_length = buffer.get_varlen_uint()?,
+                9 => event.immediate_server_version = buffer.get_varlen_uint()? as u32,
+                10 => event.original_server_version = Some(buffer.get_varlen_uint()? as u32),
+                11 => event.commit_group_ticket = Some(buffer.get_varlen_uint()?),
+                // 字段按id递增, 之后都是不认识的可忽略字段
+                _ => break,
+            }
+        }
+        Ok(event)
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+    pub fn sid(&self) -> &[u8; 16] {
+        &self.sid
+    }
+    pub fn gno(&self) -> i64 {
+        self.gno
+    }
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+    pub fn last_committed(&self) -> i64 {
+        self.last_committed
+    }
+    pub fn sequence_number(&self) -> i64 {
+        self.sequence_number
+    }
+    pub fn immediate_commit_timestamp(&self) -> u64 {
+        self.immediate_commit_timestamp
+    }
+    pub fn original_commit_timestamp(&self) -> u64 {
+        self.original_commit_timestamp.unwrap_or(self.immediate_commit_timestamp)
+    }
+    pub fn transaction_length(&self) -> u64 {
+        self.transaction_length
+    }
+    pub fn immediate_server_version(&self) -> u32 {
+        self.immediate_server_version
+    }
+    pub fn original_server_version(&self) -> u32 {
+        self.original_server_version.unwrap_or(self.immediate_server_version)
+    }
+    pub fn commit_group_ticket(&self) -> Option<u64> {
+        self.commit_group_ticket
+    }
+
+    // uuid:tag:gno, 没有tag时与GTID_LOG_EVENT相同为uuid:gno
+    pub fn gtid(&self) -> String {
+        if self.tag.is_empty() {
+            format!("{}:{}", format_uuid(&self.sid), self.gno)
+        } else {
+            format!("{}:{}:{}", format_uuid(&self.sid), self.tag, self.gno)
+        }
+    }
+}
+
+fn varlen_u8(buffer: &mut LogBuffer) -> Result<u8, CanalError> {
+    let value = buffer.get_varlen_uint()?;
+    u8::try_from(value).map_err(|_| CanalError::Decode(format!("tagged gtid event byte field overflow: {}", value)))
+}
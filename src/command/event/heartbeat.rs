@@ -0,0 +1,66 @@
+use crate::command::event::{EventType, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// HEARTBEAT_LOG_EVENT_V2的字段类型
+const HEADER_END_MARK: u8 = 0;
+const LOG_FILENAME_FIELD: u8 = 1;
+const LOG_POSITION_FIELD: u8 = 2;
+
+/**
+ * <pre>
+ *  设置了master_heartbeat_period时, master在空闲超过该时间之后发送, 不写入binlog文件(LOG_EVENT_ARTIFICIAL_F).
+ *  HEARTBEAT_LOG_EVENT
+ *  Bytes       Name
+ *  -----       ----
+ *  n           当前的binlog文件名 (not null-terminated), header的log_pos为master在该文件中的当前位置
+ *  HEARTBEAT_LOG_EVENT_V2(mysql 8.0.26+, 位置超过4G时log_pos无法表示), 字段按以下格式重复, 直到type为0:
+ *  1           type, 1: binlog文件名, 2: 位置
+ *  packed      length
+ *  length      value, 位置为packed integer
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct HeartbeatLogEvent {
+    header: LogHeader,
+    filename: String,
+    position: u64,
+}
+
+impl HeartbeatLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer) -> Result<HeartbeatLogEvent, CanalError> {
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        if header.event_type() != Some(EventType::HeartbeatLogEventV2) {
+            let filename = buffer.get_rest_string();
+            let position = header.log_pos() as u64;
+            return Ok(HeartbeatLogEvent { header, filename, position });
+        }
+        let mut filename = String::new();
+        let mut position = header.log_pos() as u64;
+        while buffer.has_remaining() {
+            let kind = buffer.get_uint8()?;
+            if kind == HEADER_END_MARK {
+                break;
+            }
+            let len = buffer.get_packed_long()?.unwrap_or(0) as usize;
+            match kind {
+                LOG_FILENAME_FIELD => filename = buffer.get_fix_string(len)?,
+                LOG_POSITION_FIELD => position = buffer.get_packed_long()?.unwrap_or(position),
+                // 之后版本增加的字段
+                _ => buffer.forward(len)?,
+            }
+        }
+        Ok(HeartbeatLogEvent { header, filename, position })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
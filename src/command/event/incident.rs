@@ -0,0 +1,82 @@
+use crate::command::event::{EventType, FormatDescriptionLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// incident的post header长度
+const INCIDENT_HEADER_LEN: usize = 2;
+
+// mysql中的Incident_event::enum_incident
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncidentType {
+    None,
+    // master上有事务没有写入binlog(例如非事务表的修改写binlog失败), replica的数据可能已经不一致
+    LostEvents,
+    Unknown(u16),
+}
+
+impl IncidentType {
+    pub fn from_code(code: u16) -> IncidentType {
+        match code {
+            0 => IncidentType::None,
+            1 => IncidentType::LostEvents,
+            _ => IncidentType::Unknown(code),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            IncidentType::None => 0,
+            IncidentType::LostEvents => 1,
+            IncidentType::Unknown(code) => *code,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            IncidentType::None => "NONE".to_string(),
+            IncidentType::LostEvents => "LOST_EVENTS".to_string(),
+            IncidentType::Unknown(code) => format!("UNKNOWN({})", code),
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  master通知replica的异常, mysql的replica收到之后停止复制(Last_SQL_Errno 1590):
+ *  Bytes       Name
+ *  -----       ----
+ *  2           incident type (post header)
+ *  1           message length
+ *  n           message
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct IncidentLogEvent {
+    header: LogHeader,
+    incident: IncidentType,
+    message: String,
+}
+
+impl IncidentLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer, description: Option<&FormatDescriptionLogEvent>)
+                -> Result<IncidentLogEvent, CanalError> {
+        let post_header_len = description.and_then(|description| description.post_header_len(EventType::IncidentEvent))
+            .unwrap_or(INCIDENT_HEADER_LEN);
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        let incident = IncidentType::from_code(buffer.get_uint16()?);
+        buffer.set_position(LOG_HEADER_LEN + post_header_len)?;
+        let message = if buffer.has_remaining() { buffer.get_length_string()? } else { String::new() };
+        Ok(IncidentLogEvent { header, incident, message })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn incident(&self) -> IncidentType {
+        self.incident
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
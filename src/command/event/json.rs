@@ -0,0 +1,154 @@
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// mysql binary json中的value类型
+const SMALL_OBJECT: u8 = 0x00;
+const LARGE_OBJECT: u8 = 0x01;
+const SMALL_ARRAY: u8 = 0x02;
+const LARGE_ARRAY: u8 = 0x03;
+const LITERAL: u8 = 0x04;
+const INT16: u8 = 0x05;
+const UINT16: u8 = 0x06;
+const INT32: u8 = 0x07;
+const UINT32: u8 = 0x08;
+const INT64: u8 = 0x09;
+const UINT64: u8 = 0x0a;
+const DOUBLE: u8 = 0x0b;
+const STRING: u8 = 0x0c;
+const OPAQUE: u8 = 0x0f;
+
+const LITERAL_NULL: u8 = 0x00;
+const LITERAL_TRUE: u8 = 0x01;
+const LITERAL_FALSE: u8 = 0x02;
+
+/**
+ * <pre>
+ *  mysql binary json转换为json文本:
+ *      1               value type
+ *      n               value
+ *  object/array:
+ *      2 / 4           element count (small / large)
+ *      2 / 4           size in bytes
+ *      (2|4 + 2) * n   key entries: key offset + key length (只有object)
+ *      (1 + 2|4) * n   value entries: type + offset, literal/int16/uint16(large时包括int32/uint32)直接内联
+ *  offset均相对于object/array的起始位置(不包含type字节)
+ * </pre>
+ *  空的json列(长度为0)输出null
+ */
+pub fn to_json_string(bytes: &[u8]) -> Result<String, CanalError> {
+    if bytes.is_empty() {
+        return Ok("null".to_string());
+    }
+    let mut out = String::new();
+    write_value(bytes[0], &bytes[1..], &mut out)?;
+    Ok(out)
+}
+
+fn write_value(kind: u8, data: &[u8], out: &mut String) -> Result<(), CanalError> {
+    let mut buffer = LogBuffer::new(data);
+    match kind {
+        SMALL_OBJECT | LARGE_OBJECT => write_container(data, kind == LARGE_OBJECT, true, out)?,
+        SMALL_ARRAY | LARGE_ARRAY => write_container(data, kind == LARGE_ARRAY, false, out)?,
+        LITERAL => out.push_str(match buffer.get_uint8()? {
+            LITERAL_NULL => "null",
+            LITERAL_TRUE => "true",
+            LITERAL_FALSE => "false",
+            literal => return Err(CanalError::Decode(format!("unknown json literal {}", literal))),
+        }),
+        INT16 => out.push_str(&buffer.get_int16()?.to_string()),
+        UINT16 => out.push_str(&buffer.get_uint16()?.to_string()),
+        INT32 => out.push_str(&buffer.get_int32()?.to_string()),
+        UINT32 => out.push_str(&buffer.get_uint32()?.to_string()),
+        INT64 => out.push_str(&buffer.get_int64()?.to_string()),
+        UINT64 => out.push_str(&buffer.get_uint64()?.to_string()),
+        DOUBLE => out.push_str(&f64::from_bits(buffer.get_uint64()?).to_string()),
+        STRING => {
+            let len = get_variable_length(&mut buffer)?;
+            write_string(&String::from_utf8_lossy(buffer.get_bytes(len)?), out);
+        }
+        // decimal/date/time等mysql类型, 保留原始字节
+        OPAQUE => {
+            buffer.get_uint8()?;
+            let len = get_variable_length(&mut buffer)?;
+            write_string(&String::from_utf8_lossy(buffer.get_bytes(len)?), out);
+        }
+        _ => return Err(CanalError::Decode(format!("unknown json value type {}", kind))),
+    }
+    Ok(())
+}
+
+fn write_container(data: &[u8], large: bool, object: bool, out: &mut String) -> Result<(), CanalError> {
+    let offset_size = if large { 4 } else { 2 };
+    let mut buffer = LogBuffer::new(data);
+    let count = buffer.get_unsigned(offset_size)? as usize;
+    let size = buffer.get_unsigned(offset_size)? as usize;
+    if size > data.len() {
+        return Err(CanalError::Decode(format!("json container size {} exceeds {}", size, data.len())));
+    }
+    let mut keys = Vec::with_capacity(if object { count } else { 0 });
+    if object {
+        for _ in 0..count {
+            let key_offset = buffer.get_unsigned(offset_size)? as usize;
+            let key_len = buffer.get_uint16()? as usize;
+            let key = data.get(key_offset..key_offset + key_len)
+                .ok_or_else(|| CanalError::Decode(format!("json key out of range {}+{}", key_offset, key_len)))?;
+            keys.push(String::from_utf8_lossy(key).to_string());
+        }
+    }
+    out.push(if object { '{' } else { '[' });
+    for i in 0..count {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        if let Some(key) = keys.get(i) {
+            write_string(key, out);
+            out.push_str(": ");
+        }
+        let kind = buffer.get_uint8()?;
+        let inlined = match kind {
+            LITERAL | INT16 | UINT16 => true,
+            INT32 | UINT32 => large,
+            _ => false,
+        };
+        if inlined {
+            let value = buffer.get_bytes(offset_size)?;
+            write_value(kind, value, out)?;
+        } else {
+            let value_offset = buffer.get_unsigned(offset_size)? as usize;
+            let value = data.get(value_offset..size)
+                .ok_or_else(|| CanalError::Decode(format!("json value out of range {}", value_offset)))?;
+            write_value(kind, value, out)?;
+        }
+    }
+    out.push(if object { '}' } else { ']' });
+    Ok(())
+}
+
+// 每个字节的低7位为数据, 最高位为1表示后面还有字节
+fn get_variable_length(buffer: &mut LogBuffer) -> Result<usize, CanalError> {
+    let mut len = 0usize;
+    for i in 0..5 {
+        let b = buffer.get_uint8()?;
+        len |= ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(len);
+        }
+    }
+    Err(CanalError::decode("invalid json variable length"))
+}
+
+fn write_string(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
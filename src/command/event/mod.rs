@@ -0,0 +1,209 @@
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+pub mod context;
+
+pub mod decoder;
+
+pub mod event_type;
+
+pub mod format_description;
+
+pub mod gtid_tagged;
+
+pub mod heartbeat;
+
+pub mod incident;
+
+pub mod json;
+
+pub mod query;
+
+pub mod rotate;
+
+pub mod rows;
+
+pub mod rows_query;
+
+pub mod rows_buffer;
+
+pub mod table_map;
+
+pub mod table_map_cache;
+
+pub use context::LogContext;
+pub use decoder::{LogDecoder, LogEvent, UnsupportedEventPolicy};
+pub use event_type::EventType;
+pub use format_description::FormatDescriptionLogEvent;
+pub use gtid_tagged::GtidTaggedLogEvent;
+pub use heartbeat::HeartbeatLogEvent;
+pub use incident::{IncidentLogEvent, IncidentType};
+pub use query::QueryLogEvent;
+pub use rotate::RotateLogEvent;
+pub use rows::RowsLogEvent;
+pub use rows_query::RowsQueryLogEvent;
+pub use rows_buffer::{DecodeStep, RowValue, RowsLogBuffer};
+pub use table_map::TableMapLogEvent;
+pub use table_map_cache::TableMapCache;
+
+// binlog文件开头的4字节magic number
+pub const BINLOG_MAGIC: [u8; 4] = [0xfe, b'b', b'i', b'n'];
+
+pub const LOG_HEADER_LEN: usize = 19;
+
+pub mod column_type {
+    pub const MYSQL_TYPE_DECIMAL: u8 = 0;
+    pub const MYSQL_TYPE_TINY: u8 = 1;
+    pub const MYSQL_TYPE_SHORT: u8 = 2;
+    pub const MYSQL_TYPE_LONG: u8 = 3;
+    pub const MYSQL_TYPE_FLOAT: u8 = 4;
+    pub const MYSQL_TYPE_DOUBLE: u8 = 5;
+    pub const MYSQL_TYPE_NULL: u8 = 6;
+    pub const MYSQL_TYPE_TIMESTAMP: u8 = 7;
+    pub const MYSQL_TYPE_LONGLONG: u8 = 8;
+    pub const MYSQL_TYPE_INT24: u8 = 9;
+    pub const MYSQL_TYPE_DATE: u8 = 10;
+    pub const MYSQL_TYPE_TIME: u8 = 11;
+    pub const MYSQL_TYPE_DATETIME: u8 = 12;
+    pub const MYSQL_TYPE_YEAR: u8 = 13;
+    pub const MYSQL_TYPE_NEWDATE: u8 = 14;
+    pub const MYSQL_TYPE_VARCHAR: u8 = 15;
+    pub const MYSQL_TYPE_BIT: u8 = 16;
+    pub const MYSQL_TYPE_TIMESTAMP2: u8 = 17;
+    pub const MYSQL_TYPE_DATETIME2: u8 = 18;
+    pub const MYSQL_TYPE_TIME2: u8 = 19;
+    pub const MYSQL_TYPE_TYPED_ARRAY: u8 = 20;
+    pub const MYSQL_TYPE_VECTOR: u8 = 242;
+    pub const MYSQL_TYPE_JSON: u8 = 245;
+    pub const MYSQL_TYPE_NEWDECIMAL: u8 = 246;
+    pub const MYSQL_TYPE_ENUM: u8 = 247;
+    pub const MYSQL_TYPE_SET: u8 = 248;
+    pub const MYSQL_TYPE_TINY_BLOB: u8 = 249;
+    pub const MYSQL_TYPE_MEDIUM_BLOB: u8 = 250;
+    pub const MYSQL_TYPE_LONG_BLOB: u8 = 251;
+    pub const MYSQL_TYPE_BLOB: u8 = 252;
+    pub const MYSQL_TYPE_VAR_STRING: u8 = 253;
+    pub const MYSQL_TYPE_STRING: u8 = 254;
+    pub const MYSQL_TYPE_GEOMETRY: u8 = 255;
+}
+
+pub mod event_flag {
+    pub const LOG_EVENT_BINLOG_IN_USE_F: u16 = 0x1;
+    pub const LOG_EVENT_THREAD_SPECIFIC_F: u16 = 0x4;
+    pub const LOG_EVENT_SUPPRESS_USE_F: u16 = 0x8;
+    // 由master伪造的event(例如dump开始时的fake rotate), 并不存在于binlog文件中
+    pub const LOG_EVENT_ARTIFICIAL_F: u16 = 0x20;
+    pub const LOG_EVENT_RELAY_LOG_F: u16 = 0x40;
+    pub const LOG_EVENT_IGNORABLE_F: u16 = 0x80;
+}
+
+pub mod checksum {
+    pub const BINLOG_CHECKSUM_ALG_OFF: u8 = 0;
+    pub const BINLOG_CHECKSUM_ALG_CRC32: u8 = 1;
+    pub const BINLOG_CHECKSUM_ALG_UNDEF: u8 = 255;
+    pub const BINLOG_CHECKSUM_LEN: usize = 4;
+
+    pub fn from_name(name: &str) -> u8 {
+        match name.to_uppercase().as_str() {
+            "CRC32" => BINLOG_CHECKSUM_ALG_CRC32,
+            "NONE" | "" => BINLOG_CHECKSUM_ALG_OFF,
+            _ => BINLOG_CHECKSUM_ALG_UNDEF,
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  v4 event header
+ *  Bytes       Name
+ *  -----       ----
+ *  4           timestamp
+ *  1           event type
+ *  4           server_id
+ *  4           event_size
+ *  4           log_pos (下一个event的起始位置)
+ *  2           flags
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogHeader {
+    when: u32,
+    kind: u8,
+    server_id: u32,
+    event_len: u32,
+    log_pos: u32,
+    flags: u16,
+    checksum_alg: u8,
+}
+
+impl LogHeader {
+    pub fn new(kind: EventType) -> LogHeader {
+        LogHeader { kind: kind.code(), ..LogHeader::default() }
+    }
+
+    pub fn from(buffer: &mut LogBuffer, checksum_alg: u8) -> Result<LogHeader, CanalError> {
+        Ok(LogHeader {
+            when: buffer.get_uint32()?,
+            kind: buffer.get_uint8()?,
+            server_id: buffer.get_uint32()?,
+            event_len: buffer.get_uint32()?,
+            log_pos: buffer.get_uint32()?,
+            flags: buffer.get_uint16()?,
+            checksum_alg,
+        })
+    }
+
+    pub fn from_bytes(buf: &[u8], checksum_alg: u8) -> Result<LogHeader, CanalError> {
+        LogHeader::from(&mut LogBuffer::new(buf), checksum_alg)
+    }
+
+    pub fn when(&self) -> u32 {
+        self.when
+    }
+    // header中原始的type code, 可能是未定义的event type
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+
+    pub fn event_type(&self) -> Option<EventType> {
+        EventType::try_from(self.kind).ok()
+    }
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn event_len(&self) -> u32 {
+        self.event_len
+    }
+    pub fn log_pos(&self) -> u32 {
+        self.log_pos
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn checksum_alg(&self) -> u8 {
+        self.checksum_alg
+    }
+
+    pub fn set_checksum_alg(&mut self, checksum_alg: u8) {
+        self.checksum_alg = checksum_alg;
+    }
+
+    pub fn is_artificial(&self) -> bool {
+        self.flags & event_flag::LOG_EVENT_ARTIFICIAL_F != 0
+    }
+
+    // master声明slave不认识该event时可以安全忽略, 例如ROWS_QUERY以及更新版本中新增的event
+    pub fn is_ignorable(&self) -> bool {
+        self.flags & event_flag::LOG_EVENT_IGNORABLE_F != 0
+    }
+
+    // event body去掉checksum之后的长度
+    pub fn data_len(&self) -> usize {
+        let data_len = (self.event_len as usize).saturating_sub(LOG_HEADER_LEN);
+        if self.checksum_alg == checksum::BINLOG_CHECKSUM_ALG_CRC32 {
+            data_len.saturating_sub(checksum::BINLOG_CHECKSUM_LEN)
+        } else {
+            data_len
+        }
+    }
+}
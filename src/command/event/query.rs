@@ -0,0 +1,260 @@
+use crate::command::charset;
+use crate::command::event::{EventType, FormatDescriptionLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// v4 QUERY_EVENT的post header长度
+pub const QUERY_HEADER_LEN: usize = 13;
+
+pub mod status_var {
+    pub const Q_FLAGS2_CODE: u8 = 0;
+    pub const Q_SQL_MODE_CODE: u8 = 1;
+    pub const Q_CATALOG_CODE: u8 = 2;
+    pub const Q_AUTO_INCREMENT: u8 = 3;
+    pub const Q_CHARSET_CODE: u8 = 4;
+    pub const Q_TIME_ZONE_CODE: u8 = 5;
+    pub const Q_CATALOG_NZ_CODE: u8 = 6;
+    pub const Q_LC_TIME_NAMES_CODE: u8 = 7;
+    pub const Q_CHARSET_DATABASE_CODE: u8 = 8;
+    pub const Q_TABLE_MAP_FOR_UPDATE_CODE: u8 = 9;
+    pub const Q_MASTER_DATA_WRITTEN_CODE: u8 = 10;
+    pub const Q_INVOKER: u8 = 11;
+    pub const Q_UPDATED_DB_NAMES: u8 = 12;
+    pub const Q_MICROSECONDS: u8 = 13;
+    pub const Q_COMMIT_TS: u8 = 14;
+    pub const Q_COMMIT_TS2: u8 = 15;
+    pub const Q_EXPLICIT_DEFAULTS_FOR_TIMESTAMP: u8 = 16;
+    pub const Q_DDL_LOGGED_WITH_XID: u8 = 17;
+    pub const Q_DEFAULT_COLLATION_FOR_UTF8MB4: u8 = 18;
+    pub const Q_SQL_REQUIRE_PRIMARY_KEY: u8 = 19;
+    pub const Q_DEFAULT_TABLE_ENCRYPTION: u8 = 20;
+
+    // mariadb
+    pub const Q_HRNOW: u8 = 128;
+    pub const Q_XID: u8 = 129;
+
+    // Q_UPDATED_DB_NAMES中db数量超过上限时的标记, 之后不再携带db名字
+    pub const OVER_MAX_DBS_IN_EVENT_MTS: u8 = 254;
+}
+
+/**
+ * <pre>
+ *  QUERY_EVENT
+ *  post header:
+ *      4               thread id
+ *      4               execute time
+ *      1               db name length
+ *      2               error code
+ *      2               status vars length (v4)
+ *  body:
+ *      n               status vars
+ *      db_len + 1      db name (以0x00结尾)
+ *      rest            query, 按照status vars中的client charset解码
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct QueryLogEvent {
+    header: LogHeader,
+    thread_id: u32,
+    exec_time: u32,
+    error_code: u16,
+    db_name: String,
+    query: String,
+    catalog: String,
+    flags2: u32,
+    // 没有Q_SQL_MODE_CODE时为None, 此时使用master的@@sql_mode
+    sql_mode: Option<u64>,
+    auto_increment_increment: u16,
+    auto_increment_offset: u16,
+    client_charset: Option<u16>,
+    client_collation: Option<u16>,
+    server_collation: Option<u16>,
+    time_zone: String,
+    lc_time_names: u16,
+    charset_database_number: u16,
+    table_map_for_update: u64,
+    user: String,
+    host: String,
+    updated_db_names: Vec<String>,
+    when_micros: u32,
+    explicit_defaults_for_timestamp: Option<bool>,
+    ddl_xid: Option<u64>,
+    default_collation_for_utf8mb4: Option<u16>,
+    xid: Option<u64>,
+    // 第一个不认识的status var, 之后的status var都被跳过
+    unknown_status_var: Option<u8>,
+}
+
+impl QueryLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer, description: &FormatDescriptionLogEvent)
+                -> Result<QueryLogEvent, CanalError> {
+        let post_header_len = description.post_header_len(EventType::QueryEvent).unwrap_or(QUERY_HEADER_LEN);
+        let data_len = header.data_len();
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + data_len)?;
+        let mut event = QueryLogEvent { header, ..QueryLogEvent::default() };
+        event.thread_id = buffer.get_uint32()?;
+        event.exec_time = buffer.get_uint32()?;
+        let db_len = buffer.get_uint8()? as usize;
+        event.error_code = buffer.get_uint16()?;
+        // 4.0之前的版本没有status vars
+        let status_vars_len = if post_header_len >= QUERY_HEADER_LEN { buffer.get_uint16()? as usize } else { 0 };
+        let status_vars_start = LOG_HEADER_LEN + post_header_len;
+        buffer.set_position(status_vars_start)?;
+        if status_vars_len > 0 {
+            let mut vars = buffer.duplicate(status_vars_start, status_vars_len)?;
+            event.unpack_variables(&mut vars)?;
+        }
+
+        buffer.set_position(status_vars_start + status_vars_len)?;
+        let db = buffer.get_bytes(db_len)?;
+        event.db_name = String::from_utf8_lossy(db).to_string();
+        buffer.forward(1)?;
+        let query = buffer.get_rest_bytes();
+        event.query = charset::decode(query, event.client_charset.unwrap_or(charset::UTF8_GENERAL_CI));
+        Ok(event)
+    }
+
+    // 与canal一致, 遇到不认识的status var时停止解析, 剩余部分直接跳过
+    fn unpack_variables(&mut self, vars: &mut LogBuffer) -> Result<(), CanalError> {
+        use status_var::*;
+        while vars.has_remaining() {
+            match vars.get_uint8()? {
+                Q_FLAGS2_CODE => self.flags2 = vars.get_uint32()?,
+                Q_SQL_MODE_CODE => self.sql_mode = Some(vars.get_uint64()?),
+                Q_CATALOG_CODE => {
+                    self.catalog = vars.get_length_string()?;
+                    vars.forward(1)?;
+                }
+                Q_CATALOG_NZ_CODE => self.catalog = vars.get_length_string()?,
+                Q_AUTO_INCREMENT => {
+                    self.auto_increment_increment = vars.get_uint16()?;
+                    self.auto_increment_offset = vars.get_uint16()?;
+                }
+                Q_CHARSET_CODE => {
+                    self.client_charset = Some(vars.get_uint16()?);
+                    self.client_collation = Some(vars.get_uint16()?);
+                    self.server_collation = Some(vars.get_uint16()?);
+                }
+                Q_TIME_ZONE_CODE => self.time_zone = vars.get_length_string()?,
+                Q_LC_TIME_NAMES_CODE => self.lc_time_names = vars.get_uint16()?,
+                Q_CHARSET_DATABASE_CODE => self.charset_database_number = vars.get_uint16()?,
+                Q_TABLE_MAP_FOR_UPDATE_CODE => self.table_map_for_update = vars.get_uint64()?,
+                Q_MASTER_DATA_WRITTEN_CODE => {
+                    vars.get_uint32()?;
+                }
+                Q_INVOKER => {
+                    self.user = vars.get_length_string()?;
+                    self.host = vars.get_length_string()?;
+                }
+                Q_UPDATED_DB_NAMES => {
+                    let count = vars.get_uint8()?;
+                    if count != OVER_MAX_DBS_IN_EVENT_MTS {
+                        for _ in 0..count {
+                            self.updated_db_names.push(vars.get_null_terminated_string()?);
+                        }
+                    }
+                }
+                Q_MICROSECONDS => self.when_micros = vars.get_uint24()?,
+                Q_EXPLICIT_DEFAULTS_FOR_TIMESTAMP => self.explicit_defaults_for_timestamp = Some(vars.get_uint8()? != 0),
+                Q_DDL_LOGGED_WITH_XID => self.ddl_xid = Some(vars.get_uint64()?),
+                Q_DEFAULT_COLLATION_FOR_UTF8MB4 => self.default_collation_for_utf8mb4 = Some(vars.get_uint16()?),
+                Q_SQL_REQUIRE_PRIMARY_KEY | Q_DEFAULT_TABLE_ENCRYPTION => {
+                    vars.get_uint8()?;
+                }
+                Q_HRNOW => self.when_micros = vars.get_uint24()?,
+                Q_XID => self.xid = Some(vars.get_uint64()?),
+                code => {
+                    self.unknown_status_var = Some(code);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+    pub fn exec_time(&self) -> u32 {
+        self.exec_time
+    }
+    pub fn error_code(&self) -> u16 {
+        self.error_code
+    }
+    pub fn db_name(&self) -> &str {
+        &self.db_name
+    }
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+    pub fn catalog(&self) -> &str {
+        &self.catalog
+    }
+    pub fn flags2(&self) -> u32 {
+        self.flags2
+    }
+    pub fn sql_mode(&self) -> u64 {
+        self.sql_mode.unwrap_or_default()
+    }
+    pub fn has_sql_mode(&self) -> bool {
+        self.sql_mode.is_some()
+    }
+    pub fn auto_increment_increment(&self) -> u16 {
+        self.auto_increment_increment
+    }
+    pub fn auto_increment_offset(&self) -> u16 {
+        self.auto_increment_offset
+    }
+    pub fn client_charset(&self) -> Option<u16> {
+        self.client_charset
+    }
+    pub fn client_collation(&self) -> Option<u16> {
+        self.client_collation
+    }
+    pub fn server_collation(&self) -> Option<u16> {
+        self.server_collation
+    }
+    pub fn time_zone(&self) -> &str {
+        &self.time_zone
+    }
+    pub fn lc_time_names(&self) -> u16 {
+        self.lc_time_names
+    }
+    pub fn charset_database_number(&self) -> u16 {
+        self.charset_database_number
+    }
+    pub fn table_map_for_update(&self) -> u64 {
+        self.table_map_for_update
+    }
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+    pub fn updated_db_names(&self) -> &Vec<String> {
+        &self.updated_db_names
+    }
+    pub fn when_micros(&self) -> u32 {
+        self.when_micros
+    }
+    pub fn explicit_defaults_for_timestamp(&self) -> Option<bool> {
+        self.explicit_defaults_for_timestamp
+    }
+    pub fn ddl_xid(&self) -> Option<u64> {
+        self.ddl_xid
+    }
+    pub fn default_collation_for_utf8mb4(&self) -> Option<u16> {
+        self.default_collation_for_utf8mb4
+    }
+    pub fn xid(&self) -> Option<u64> {
+        self.xid
+    }
+    pub fn unknown_status_var(&self) -> Option<u8> {
+        self.unknown_status_var
+    }
+}
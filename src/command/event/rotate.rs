@@ -0,0 +1,53 @@
+use crate::command::event::{EventType, FormatDescriptionLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// rotate的post header长度
+const ROTATE_HEADER_LEN: usize = 8;
+
+/**
+ * <pre>
+ *  ROTATE_EVENT
+ *  Bytes       Name
+ *  -----       ----
+ *  8           position of the first event in the next log file
+ *  n           name of the next binlog (not null-terminated)
+ * </pre>
+ *  dump开始或重连时master会先发送一个fake rotate(timestamp=0, log_pos=0),
+ *  只用于告知当前的binlog文件名. fake rotate在format description之前, 此时post header长度按8处理
+ */
+#[derive(Debug, Clone)]
+pub struct RotateLogEvent {
+    header: LogHeader,
+    position: u64,
+    filename: String,
+}
+
+impl RotateLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer, description: Option<&FormatDescriptionLogEvent>)
+                -> Result<RotateLogEvent, CanalError> {
+        let post_header_len = description.and_then(|description| description.post_header_len(EventType::RotateEvent))
+            .unwrap_or(ROTATE_HEADER_LEN);
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        let position = buffer.get_uint64()?;
+        // 更新版本的master在post header末尾追加的字段
+        buffer.set_position(LOG_HEADER_LEN + post_header_len.max(ROTATE_HEADER_LEN))?;
+        let filename = buffer.get_rest_string();
+        Ok(RotateLogEvent { header, position, filename })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn is_fake(&self) -> bool {
+        self.header.when() == 0 || self.header.log_pos() == 0
+    }
+}
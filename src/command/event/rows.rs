@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use crate::command::event::{EventType, FormatDescriptionLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+// 5.1.0 ~ 5.1.15的rows event中table id只有4个字节
+pub const ROWS_HEADER_LEN_V1_OLD: usize = 6;
+pub const ROWS_HEADER_LEN_V1: usize = 8;
+// v2在v1的基础上增加了2字节的extra data长度
+pub const ROWS_HEADER_LEN_V2: usize = 10;
+
+// rows event的flags
+pub const STMT_END_F: u16 = 1;
+pub const NO_FOREIGN_KEY_CHECKS_F: u16 = 1 << 1;
+pub const RELAXED_UNIQUE_CHECKS_F: u16 = 1 << 2;
+pub const COMPLETE_ROWS_F: u16 = 1 << 3;
+
+/**
+ * <pre>
+ *  WRITE/UPDATE/DELETE_ROWS_EVENT (v1/v2)
+ *  post header:
+ *      6 (4 in 5.1.0~5.1.15)   table id
+ *      2                       flags
+ *      2                       extra data length (v2, 包含自身的2个字节)
+ *  body:
+ *      extra_len - 2           extra data (v2)
+ *      packed                  column count
+ *      (n + 7) / 8             columns present bitmap (before image)
+ *      (n + 7) / 8             columns present bitmap (after image, 只有update)
+ *      rest                    rows, 按照table map中的列类型由RowsLogBuffer解析
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct RowsLogEvent {
+    header: LogHeader,
+    table_id: u64,
+    flags: u16,
+    extra_data: Vec<u8>,
+    column_count: usize,
+    columns: Vec<bool>,
+    change_columns: Vec<bool>,
+    // lazy列与event共享rows的内存
+    rows: Arc<Vec<u8>>,
+}
+
+impl RowsLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer, description: &FormatDescriptionLogEvent)
+                -> Result<RowsLogEvent, CanalError> {
+        let kind = header.event_type()
+            .ok_or_else(|| CanalError::Decode(format!("unknown rows event type {}", header.kind())))?;
+        let post_header_len = description.post_header_len(kind).unwrap_or(ROWS_HEADER_LEN_V1);
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        let table_id = if post_header_len == ROWS_HEADER_LEN_V1_OLD {
+            buffer.get_uint32()? as u64
+        } else {
+            buffer.get_uint48()?
+        };
+        let flags = buffer.get_uint16()?;
+        let mut extra_data = vec![];
+        if post_header_len == ROWS_HEADER_LEN_V2 {
+            let extra_len = buffer.get_uint16()? as usize;
+            extra_data = buffer.get_bytes(extra_len.saturating_sub(2))?.to_vec();
+        } else {
+            buffer.set_position(LOG_HEADER_LEN + post_header_len)?;
+        }
+
+        let column_count = buffer.get_packed_long()?.unwrap_or(0) as usize;
+        let columns = get_bitmap(buffer, column_count)?;
+        let change_columns = if matches!(kind, EventType::UpdateRowsEventV1 | EventType::UpdateRowsEvent
+            | EventType::PartialUpdateRowsEvent) {
+            get_bitmap(buffer, column_count)?
+        } else {
+            columns.clone()
+        };
+        let rows = Arc::new(buffer.get_rest_bytes().to_vec());
+        Ok(RowsLogEvent { header, table_id, flags, extra_data, column_count, columns, change_columns, rows })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn extra_data(&self) -> &Vec<u8> {
+        &self.extra_data
+    }
+    pub fn column_count(&self) -> usize {
+        self.column_count
+    }
+    // before image中出现的列
+    pub fn columns(&self) -> &Vec<bool> {
+        &self.columns
+    }
+    // after image中出现的列, 非update event与columns相同
+    pub fn change_columns(&self) -> &Vec<bool> {
+        &self.change_columns
+    }
+    pub fn rows(&self) -> &Arc<Vec<u8>> {
+        &self.rows
+    }
+
+    pub fn is_insert(&self) -> bool {
+        matches!(self.header.event_type(), Some(EventType::WriteRowsEventV1 | EventType::WriteRowsEvent))
+    }
+
+    pub fn is_update(&self) -> bool {
+        matches!(self.header.event_type(),
+            Some(EventType::UpdateRowsEventV1 | EventType::UpdateRowsEvent | EventType::PartialUpdateRowsEvent))
+    }
+
+    pub fn is_delete(&self) -> bool {
+        matches!(self.header.event_type(), Some(EventType::DeleteRowsEventV1 | EventType::DeleteRowsEvent))
+    }
+
+    pub fn is_stmt_end(&self) -> bool {
+        self.flags & STMT_END_F != 0
+    }
+}
+
+// 低位在前的bitmap, 每个bit对应一列
+pub fn get_bitmap(buffer: &mut LogBuffer, len: usize) -> Result<Vec<bool>, CanalError> {
+    let bytes = buffer.get_bytes(len.div_ceil(8))?;
+    Ok((0..len).map(|i| bytes[i / 8] & (1 << (i % 8)) != 0).collect())
+}
@@ -0,0 +1,540 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use chrono::{Local, TimeZone};
+
+use crate::command::charset;
+use crate::command::event::column_type::*;
+use crate::command::event::json;
+use crate::command::event::table_map::ColumnInfo;
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+use crate::protocol::{LazyValue, ZeroDatePolicy};
+
+// decimal中每9位十进制数字占4个字节, 不足9位时按照下表占用的字节数
+const DIG2BYTES: [usize; 10] = [0, 1, 1, 2, 2, 3, 3, 4, 4, 4];
+const DIG_PER_DEC: usize = 9;
+
+const TIMEF_OFS: i64 = 0x800000000000;
+const TIMEF_INT_OFS: i64 = 0x800000;
+const DATETIMEF_INT_OFS: i64 = 0x8000000000;
+
+pub const ZERO_DATETIME: &str = "0000-00-00 00:00:00";
+
+/**
+ * <pre>
+ *  对应canal中的RowsLogBuffer, 按照table map中的列类型逐行解析rows event:
+ *      (present + 7) / 8   null bitmap, 只包含出现在image中的列
+ *      ...                 各列的值, null的列不占用空间
+ *  解析结果统一转换为字符串:
+ *      整数/浮点/decimal   十进制表示, 没有signedness metadata时按有符号处理
+ *      date/time/datetime  yyyy-MM-dd HH:mm:ss[.ffffff], 小数位数与列定义一致
+ *      timestamp           按本地时区转换
+ *      char/varchar/text   按列的charset解码, 没有charset信息时按utf8
+ *      enum/set            有optional metadata时输出名字, 否则输出下标/bitmap
+ *      json                转换为json文本
+ *  通过with_lazy_blob构造时, 长度不小于阈值的blob/text/geometry列不解码,
+ *  只记录在rows中的位置, 返回RowValue::Lazy.
+ *  date/datetime/timestamp中的非法日期(0000-00-00等)按zero_date处理, 默认原样输出.
+ *  enable_trace之后记录每一步读取的位置/长度/类型(DecodeStep), 用于排查解码错位
+ * </pre>
+ */
+pub struct RowsLogBuffer<'a> {
+    buffer: LogBuffer<'a>,
+    // (rows, 阈值), 与buffer是同一块内存
+    lazy: Option<(Arc<Vec<u8>>, usize)>,
+    zero_date: ZeroDatePolicy,
+    trace: Option<Vec<DecodeStep>>,
+}
+
+// trace中值的预览最多输出的字符数
+const TRACE_PREVIEW_CHARS: usize = 64;
+
+/**
+ * <pre>
+ *  解码rows时的一步:
+ *      offset/length   在rows(行数据部分)中的字节区间
+ *      kind            null_bitmap, null, lazy, value, error
+ *      column          列的下标, null_bitmap时为None
+ *      column_type     table map中的类型和meta, 以及按real_type_and_meta修正之后的类型和meta
+ *      detail          null_bitmap的十六进制, 值的预览或者错误信息
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeStep {
+    offset: usize,
+    length: usize,
+    kind: &'static str,
+    column: Option<usize>,
+    column_type: Option<(u8, u16, u8, u16)>,
+    detail: String,
+}
+
+impl DecodeStep {
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    pub fn length(&self) -> usize {
+        self.length
+    }
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+    pub fn column_type(&self) -> Option<(u8, u16, u8, u16)> {
+        self.column_type
+    }
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowValue {
+    Decoded(String),
+    Lazy(LazyValue),
+}
+
+impl RowValue {
+    pub fn into_string(self) -> String {
+        match self {
+            RowValue::Decoded(value) => value,
+            RowValue::Lazy(lazy) => lazy.get().to_string(),
+        }
+    }
+}
+
+impl<'a> RowsLogBuffer<'a> {
+    pub fn new(rows: &'a [u8]) -> RowsLogBuffer<'a> {
+        RowsLogBuffer { buffer: LogBuffer::new(rows), lazy: None, zero_date: ZeroDatePolicy::Keep, trace: None }
+    }
+
+    pub fn with_lazy_blob(rows: &'a Arc<Vec<u8>>, threshold: usize) -> RowsLogBuffer<'a> {
+        RowsLogBuffer {
+            buffer: LogBuffer::new(rows),
+            lazy: Some((rows.clone(), threshold)),
+            zero_date: ZeroDatePolicy::Keep,
+            trace: None,
+        }
+    }
+
+    pub fn set_zero_date_policy(&mut self, zero_date: ZeroDatePolicy) {
+        self.zero_date = zero_date;
+    }
+
+    pub fn enable_trace(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    // 返回enable_trace之后记录的步骤, 解码失败时最后一步为error
+    pub fn take_trace(&mut self) -> Vec<DecodeStep> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.buffer.has_remaining()
+    }
+
+    // 解析一行, 返回present中为true的列的(下标, 值), None表示NULL
+    pub fn next_row(&mut self, present: &[bool], column_info: &[ColumnInfo])
+                    -> Result<Vec<(usize, Option<String>)>, CanalError> {
+        Ok(self.next_row_values(present, column_info)?.into_iter()
+            .map(|(i, value)| (i, value.map(RowValue::into_string)))
+            .collect())
+    }
+
+    // 与next_row相同, 大字段可能是RowValue::Lazy
+    pub fn next_row_values(&mut self, present: &[bool], column_info: &[ColumnInfo])
+                           -> Result<Vec<(usize, Option<RowValue>)>, CanalError> {
+        let present_count = present.iter().filter(|p| **p).count();
+        let start = self.buffer.position();
+        let null_bits = self.buffer.get_bytes(present_count.div_ceil(8));
+        if let Some(trace) = self.trace.as_mut() {
+            let detail = match &null_bits {
+                Ok(null_bits) => format!("present={}, bitmap={}", present_count,
+                                         null_bits.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+                Err(e) => e.to_string(),
+            };
+            let kind = if null_bits.is_ok() { "null_bitmap" } else { "error" };
+            trace.push(DecodeStep { offset: start, length: present_count.div_ceil(8), kind, column: None, column_type: None, detail });
+        }
+        let null_bits = null_bits?;
+        let mut values = Vec::with_capacity(present_count);
+        for (null_index, (i, _)) in present.iter().enumerate().filter(|(_, p)| **p).enumerate() {
+            // NULL的列同样需要检查, 否则调用方按下标取列信息时越界
+            let info = column_info.get(i)
+                .ok_or_else(|| CanalError::Decode(format!("column {} is out of table map range {}", i,
+                                                          column_info.len())))?;
+            let is_null = null_bits[null_index / 8] & (1 << (null_index % 8)) != 0;
+            let start = self.buffer.position();
+            if is_null {
+                self.trace_column(i, info, start, "null", String::new());
+                values.push((i, None));
+                continue;
+            }
+            let value = match self.fetch_lazy(info) {
+                Ok(Some(lazy)) => Ok(Some(RowValue::Lazy(lazy))),
+                Ok(None) => self.fetch_value(info).and_then(|value| self.check_date(info, value)),
+                Err(e) => Err(e),
+            };
+            if self.trace.is_some() {
+                let (kind, detail) = match &value {
+                    Ok(Some(RowValue::Lazy(lazy))) => ("lazy", format!("{} bytes", lazy.len())),
+                    Ok(Some(RowValue::Decoded(value))) => ("value", value.chars().take(TRACE_PREVIEW_CHARS).collect()),
+                    Ok(None) => ("null", "null by zero date policy".to_string()),
+                    Err(e) => ("error", e.to_string()),
+                };
+                self.trace_column(i, info, start, kind, detail);
+            }
+            let value = value
+                .map_err(|e| CanalError::Decode(format!("decode column {} (type={}, meta={}) failure: {}", i,
+                                                        info.kind(), info.meta(), e)))?;
+            values.push((i, value));
+        }
+        Ok(values)
+    }
+
+    // 按next_row_values相同的方式跳过一行, 返回非NULL列的值在rows中的字节区间, 用于遮盖敏感列
+    pub fn next_row_spans(&mut self, present: &[bool], column_info: &[ColumnInfo])
+                          -> Result<Vec<(usize, Range<usize>)>, CanalError> {
+        let present_count = present.iter().filter(|p| **p).count();
+        let null_bits = self.buffer.get_bytes(present_count.div_ceil(8))?;
+        let mut spans = Vec::with_capacity(present_count);
+        for (null_index, (i, _)) in present.iter().enumerate().filter(|(_, p)| **p).enumerate() {
+            let info = column_info.get(i)
+                .ok_or_else(|| CanalError::Decode(format!("column {} is out of table map range {}", i,
+                                                          column_info.len())))?;
+            if null_bits[null_index / 8] & (1 << (null_index % 8)) != 0 {
+                continue;
+            }
+            let start = self.buffer.position();
+            self.fetch_value(info)
+                .map_err(|e| CanalError::Decode(format!("decode column {} (type={}, meta={}) failure: {}", i,
+                                                        info.kind(), info.meta(), e)))?;
+            spans.push((i, start..self.buffer.position()));
+        }
+        Ok(spans)
+    }
+
+    fn trace_column(&mut self, index: usize, info: &ColumnInfo, start: usize, kind: &'static str, detail: String) {
+        let length = self.buffer.position().saturating_sub(start);
+        if let Some(trace) = self.trace.as_mut() {
+            let (real_type, real_meta) = real_type_and_meta(info);
+            let column_type = Some((info.kind(), info.meta(), real_type, real_meta));
+            trace.push(DecodeStep { offset: start, length, kind, column: Some(index), column_type, detail });
+        }
+    }
+
+    fn check_date(&self, info: &ColumnInfo, value: String) -> Result<Option<RowValue>, CanalError> {
+        let (kind, _) = real_type_and_meta(info);
+        let temporal = matches!(kind, MYSQL_TYPE_DATE | MYSQL_TYPE_NEWDATE | MYSQL_TYPE_DATETIME | MYSQL_TYPE_DATETIME2
+            | MYSQL_TYPE_TIMESTAMP | MYSQL_TYPE_TIMESTAMP2);
+        if !temporal || self.zero_date == ZeroDatePolicy::Keep {
+            return Ok(Some(RowValue::Decoded(value)));
+        }
+        Ok(self.zero_date.apply(&value)?.map(RowValue::Decoded))
+    }
+
+    // 只处理长度不小于阈值的blob/text/geometry列, 其它情况返回None且不移动position
+    fn fetch_lazy(&mut self, info: &ColumnInfo) -> Result<Option<LazyValue>, CanalError> {
+        let threshold = match &self.lazy {
+            Some((_, threshold)) => *threshold,
+            None => return Ok(None),
+        };
+        let (kind, meta) = real_type_and_meta(info);
+        let collation = match kind {
+            MYSQL_TYPE_TINY_BLOB | MYSQL_TYPE_MEDIUM_BLOB | MYSQL_TYPE_LONG_BLOB | MYSQL_TYPE_BLOB => collation(info),
+            MYSQL_TYPE_GEOMETRY | MYSQL_TYPE_VECTOR => charset::BINARY,
+            _ => return Ok(None),
+        };
+        let start = self.buffer.position();
+        let len = self.buffer.get_unsigned(meta as usize)? as usize;
+        if len < threshold {
+            self.buffer.set_position(start)?;
+            return Ok(None);
+        }
+        let offset = self.buffer.position();
+        self.buffer.get_bytes(len)?;
+        match &self.lazy {
+            Some((rows, _)) => Ok(Some(LazyValue::new(rows.clone(), offset, len, collation)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_value(&mut self, info: &ColumnInfo) -> Result<String, CanalError> {
+        let (kind, meta) = real_type_and_meta(info);
+        let buffer = &mut self.buffer;
+        let value = match kind {
+            MYSQL_TYPE_TINY => {
+                let value = buffer.get_uint8()?;
+                if info.unsigned() { value.to_string() } else { (value as i8).to_string() }
+            }
+            MYSQL_TYPE_SHORT => {
+                let value = buffer.get_uint16()?;
+                if info.unsigned() { value.to_string() } else { (value as i16).to_string() }
+            }
+            MYSQL_TYPE_INT24 => {
+                if info.unsigned() { buffer.get_uint24()?.to_string() } else { buffer.get_int24()?.to_string() }
+            }
+            MYSQL_TYPE_LONG => {
+                let value = buffer.get_uint32()?;
+                if info.unsigned() { value.to_string() } else { (value as i32).to_string() }
+            }
+            MYSQL_TYPE_LONGLONG => {
+                let value = buffer.get_uint64()?;
+                if info.unsigned() { value.to_string() } else { (value as i64).to_string() }
+            }
+            MYSQL_TYPE_FLOAT => f32::from_bits(buffer.get_uint32()?).to_string(),
+            MYSQL_TYPE_DOUBLE => f64::from_bits(buffer.get_uint64()?).to_string(),
+            MYSQL_TYPE_NEWDECIMAL => decimal(buffer, (meta >> 8) as usize, (meta & 0xff) as usize)?,
+            MYSQL_TYPE_YEAR => match buffer.get_uint8()? {
+                0 => "0000".to_string(),
+                year => (1900 + year as u32).to_string(),
+            },
+            MYSQL_TYPE_DATE | MYSQL_TYPE_NEWDATE => {
+                let value = buffer.get_uint24()?;
+                format!("{:04}-{:02}-{:02}", value >> 9, (value >> 5) % 16, value % 32)
+            }
+            MYSQL_TYPE_TIME => {
+                let value = buffer.get_int24()?;
+                let (sign, value) = if value < 0 { ("-", -value) } else { ("", value) };
+                format!("{}{:02}:{:02}:{:02}", sign, value / 10000, (value % 10000) / 100, value % 100)
+            }
+            MYSQL_TYPE_TIME2 => time2(buffer, meta as usize)?,
+            MYSQL_TYPE_DATETIME => {
+                let value = buffer.get_uint64()?;
+                if value == 0 {
+                    ZERO_DATETIME.to_string()
+                } else {
+                    let (date, time) = (value / 1000000, value % 1000000);
+                    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", date / 10000, (date % 10000) / 100, date % 100,
+                            time / 10000, (time % 10000) / 100, time % 100)
+                }
+            }
+            MYSQL_TYPE_DATETIME2 => datetime2(buffer, meta as usize)?,
+            MYSQL_TYPE_TIMESTAMP => timestamp(buffer.get_uint32()? as i64, 0, 0),
+            MYSQL_TYPE_TIMESTAMP2 => {
+                let seconds = buffer.get_unsigned_be(4)? as i64;
+                let micros = fraction(buffer, meta as usize)?;
+                timestamp(seconds, micros, meta as usize)
+            }
+            MYSQL_TYPE_VARCHAR | MYSQL_TYPE_VAR_STRING | MYSQL_TYPE_STRING => {
+                let len = if meta < 256 { buffer.get_uint8()? as usize } else { buffer.get_uint16()? as usize };
+                string(buffer.get_bytes(len)?, info)
+            }
+            MYSQL_TYPE_ENUM => {
+                let index = buffer.get_unsigned(meta as usize & 0xff)? as usize;
+                match index.checked_sub(1).and_then(|i| info.set_enum_values().get(i)) {
+                    Some(name) => name.clone(),
+                    None if index == 0 && !info.set_enum_values().is_empty() => String::new(),
+                    None => index.to_string(),
+                }
+            }
+            MYSQL_TYPE_SET => {
+                let bits = buffer.get_unsigned(meta as usize & 0xff)?;
+                if info.set_enum_values().is_empty() {
+                    bits.to_string()
+                } else {
+                    info.set_enum_values().iter().enumerate()
+                        .filter(|(i, _)| *i < 64 && bits & (1 << i) != 0)
+                        .map(|(_, name)| name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(",")
+                }
+            }
+            MYSQL_TYPE_BIT => {
+                let bits = ((meta >> 8) * 8 + (meta & 0xff)) as usize;
+                buffer.get_unsigned_be(bits.div_ceil(8))?.to_string()
+            }
+            MYSQL_TYPE_TINY_BLOB | MYSQL_TYPE_MEDIUM_BLOB | MYSQL_TYPE_LONG_BLOB | MYSQL_TYPE_BLOB => {
+                let len = buffer.get_unsigned(meta as usize)? as usize;
+                string(buffer.get_bytes(len)?, info)
+            }
+            MYSQL_TYPE_GEOMETRY | MYSQL_TYPE_VECTOR => {
+                let len = buffer.get_unsigned(meta as usize)? as usize;
+                charset::decode(buffer.get_bytes(len)?, charset::BINARY)
+            }
+            MYSQL_TYPE_JSON => {
+                let len = buffer.get_unsigned(meta as usize)? as usize;
+                json::to_json_string(buffer.get_bytes(len)?)?
+            }
+            _ => return Err(CanalError::Decode(format!("unsupported column type {}", kind))),
+        };
+        Ok(value)
+    }
+}
+
+/**
+ * <pre>
+ *  STRING类型的meta中高字节为真实类型, 低字节为长度,
+ *  长度超过255时借用了高字节中的0x30两位:
+ *      byte0 & 0x30 != 0x30    length = byte1 | (((byte0 & 0x30) ^ 0x30) << 4), type = byte0 | 0x30
+ *      byte0 为 ENUM/SET       type = byte0, length = byte1 (存储字节数)
+ * </pre>
+ */
+pub fn real_type_and_meta(info: &ColumnInfo) -> (u8, u16) {
+    let (kind, meta) = (info.kind(), info.meta());
+    if kind != MYSQL_TYPE_STRING || meta < 256 {
+        return (kind, meta);
+    }
+    let byte0 = (meta >> 8) as u8;
+    let byte1 = meta & 0xff;
+    if byte0 & 0x30 != 0x30 {
+        return (byte0 | 0x30, byte1 | ((((byte0 & 0x30) ^ 0x30) as u16) << 4));
+    }
+    match byte0 {
+        MYSQL_TYPE_ENUM | MYSQL_TYPE_SET => (byte0, byte1),
+        _ => (MYSQL_TYPE_STRING, byte1),
+    }
+}
+
+fn string(bytes: &[u8], info: &ColumnInfo) -> String {
+    charset::decode(bytes, collation(info))
+}
+
+// 没有charset信息时按utf8
+fn collation(info: &ColumnInfo) -> u16 {
+    info.charset().map(|charset| charset as u16).unwrap_or(charset::UTF8_GENERAL_CI)
+}
+
+// 对应mysql中的bin2decimal
+fn decimal(buffer: &mut LogBuffer, precision: usize, scale: usize) -> Result<String, CanalError> {
+    let intg = precision.checked_sub(scale)
+        .ok_or_else(|| CanalError::Decode(format!("invalid decimal({},{})", precision, scale)))?;
+    let (intg0, intg0x) = (intg / DIG_PER_DEC, intg % DIG_PER_DEC);
+    let (frac0, frac0x) = (scale / DIG_PER_DEC, scale % DIG_PER_DEC);
+    let size = intg0 * 4 + DIG2BYTES[intg0x] + frac0 * 4 + DIG2BYTES[frac0x];
+    let mut bytes = buffer.get_bytes(size)?.to_vec();
+    if bytes.is_empty() {
+        return Ok("0".to_string());
+    }
+    // 最高位为1表示非负数, 负数的所有字节按位取反
+    let negative = bytes[0] & 0x80 == 0;
+    bytes[0] ^= 0x80;
+    if negative {
+        bytes.iter_mut().for_each(|b| *b = !*b);
+    }
+    let mut data = LogBuffer::new(&bytes);
+    let mut int_part = String::new();
+    if intg0x > 0 {
+        int_part.push_str(&data.get_unsigned_be(DIG2BYTES[intg0x])?.to_string());
+    }
+    for _ in 0..intg0 {
+        let value = data.get_unsigned_be(4)?;
+        if int_part.is_empty() {
+            int_part.push_str(&value.to_string());
+        } else {
+            int_part.push_str(&format!("{:09}", value));
+        }
+    }
+    let int_part = int_part.trim_start_matches('0');
+    let mut value = String::new();
+    if negative {
+        value.push('-');
+    }
+    value.push_str(if int_part.is_empty() { "0" } else { int_part });
+    if scale > 0 {
+        value.push('.');
+        for _ in 0..frac0 {
+            value.push_str(&format!("{:09}", data.get_unsigned_be(4)?));
+        }
+        if frac0x > 0 {
+            value.push_str(&format!("{:0width$}", data.get_unsigned_be(DIG2BYTES[frac0x])?, width = frac0x));
+        }
+    }
+    Ok(value)
+}
+
+// datetime2/timestamp2的小数秒部分, 返回微秒
+fn fraction(buffer: &mut LogBuffer, dec: usize) -> Result<i64, CanalError> {
+    Ok(match dec {
+        1 | 2 => buffer.get_unsigned_be(1)? as i64 * 10000,
+        3 | 4 => buffer.get_unsigned_be(2)? as i64 * 100,
+        5 | 6 => buffer.get_unsigned_be(3)? as i64,
+        _ => 0,
+    })
+}
+
+fn micros_suffix(micros: i64, dec: usize) -> String {
+    if dec == 0 {
+        return String::new();
+    }
+    let digits = format!("{:06}", micros);
+    format!(".{}", &digits[..dec.min(6)])
+}
+
+/**
+ * <pre>
+ *  DATETIME2, 5字节大端序 + 小数秒
+ *      1 bit   sign (总是1)
+ *      17 bits year * 13 + month
+ *      5 bits  day
+ *      5 bits  hour
+ *      6 bits  minute
+ *      6 bits  second
+ * </pre>
+ */
+fn datetime2(buffer: &mut LogBuffer, dec: usize) -> Result<String, CanalError> {
+    let intpart = buffer.get_unsigned_be(5)? as i64 - DATETIMEF_INT_OFS;
+    let micros = fraction(buffer, dec)?;
+    if intpart == 0 {
+        return Ok(format!("{}{}", ZERO_DATETIME, micros_suffix(0, dec)));
+    }
+    let ymd = intpart >> 17;
+    let ym = ymd >> 5;
+    let hms = intpart % (1 << 17);
+    Ok(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}", ym / 13, ym % 13, ymd % (1 << 5),
+               hms >> 12, (hms >> 6) % (1 << 6), hms % (1 << 6), micros_suffix(micros, dec)))
+}
+
+/**
+ * <pre>
+ *  TIME2, 3字节大端序 + 小数秒, 可以为负数
+ *      1 bit   sign
+ *      1 bit   unused
+ *      10 bits hour
+ *      6 bits  minute
+ *      6 bits  second
+ * </pre>
+ */
+fn time2(buffer: &mut LogBuffer, dec: usize) -> Result<String, CanalError> {
+    let packed = match dec {
+        1 | 2 => {
+            let mut intpart = buffer.get_unsigned_be(3)? as i64 - TIMEF_INT_OFS;
+            let mut frac = buffer.get_unsigned_be(1)? as i8 as i64;
+            if intpart < 0 && frac != 0 {
+                intpart += 1;
+                frac -= 0x100;
+            }
+            (intpart << 24) + frac * 10000
+        }
+        3 | 4 => {
+            let mut intpart = buffer.get_unsigned_be(3)? as i64 - TIMEF_INT_OFS;
+            let mut frac = buffer.get_unsigned_be(2)? as i16 as i64;
+            if intpart < 0 && frac != 0 {
+                intpart += 1;
+                frac -= 0x10000;
+            }
+            (intpart << 24) + frac * 100
+        }
+        5 | 6 => buffer.get_unsigned_be(6)? as i64 - TIMEF_OFS,
+        _ => (buffer.get_unsigned_be(3)? as i64 - TIMEF_INT_OFS) << 24,
+    };
+    let (sign, packed) = if packed < 0 { ("-", -packed) } else { ("", packed) };
+    let hms = packed >> 24;
+    let micros = packed % (1 << 24);
+    Ok(format!("{}{:02}:{:02}:{:02}{}", sign, (hms >> 12) % (1 << 10), (hms >> 6) % (1 << 6), hms % (1 << 6),
+               micros_suffix(micros, dec)))
+}
+
+fn timestamp(seconds: i64, micros: i64, dec: usize) -> String {
+    if seconds == 0 {
+        return format!("{}{}", ZERO_DATETIME, micros_suffix(0, dec));
+    }
+    match Local.timestamp_opt(seconds, 0).single() {
+        Some(time) => format!("{}{}", time.format("%Y-%m-%d %H:%M:%S"), micros_suffix(micros, dec)),
+        None => seconds.to_string(),
+    }
+}
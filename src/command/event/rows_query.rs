@@ -0,0 +1,40 @@
+use crate::command::event::{EventType, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+/**
+ * <pre>
+ *  产生之后rows event的原始语句, 两种event统一解析为RowsQueryLogEvent:
+ *  ROWS_QUERY_LOG_EVENT(mysql, binlog_rows_query_log_events=ON)
+ *  Bytes       Name
+ *  -----       ----
+ *  1           length, 超过255时被截断, 不使用
+ *  n           query (not null-terminated)
+ *  ANNOTATE_ROWS_EVENT(MariaDB, binlog_annotate_row_events=ON, dump时需要BINLOG_SEND_ANNOTATE_ROWS_EVENT)
+ *  n           query (not null-terminated)
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct RowsQueryLogEvent {
+    header: LogHeader,
+    query: String,
+}
+
+impl RowsQueryLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer) -> Result<RowsQueryLogEvent, CanalError> {
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        if header.event_type() == Some(EventType::RowsQueryLogEvent) {
+            buffer.get_uint8()?;
+        }
+        let query = buffer.get_rest_string();
+        Ok(RowsQueryLogEvent { header, query })
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+}
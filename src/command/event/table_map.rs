@@ -0,0 +1,326 @@
+use crate::command::event::column_type::*;
+use crate::command::event::{EventType, FormatDescriptionLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::log_buffer::LogBuffer;
+use crate::error::CanalError;
+
+pub const TABLE_MAP_POST_HEADER_LEN_V1: usize = 6;
+
+// binlog_row_metadata=FULL时table map尾部的optional metadata类型
+mod optional_metadata {
+    pub const SIGNEDNESS: u8 = 1;
+    pub const DEFAULT_CHARSET: u8 = 2;
+    pub const COLUMN_CHARSET: u8 = 3;
+    pub const COLUMN_NAME: u8 = 4;
+    pub const SET_STR_VALUE: u8 = 5;
+    pub const ENUM_STR_VALUE: u8 = 6;
+    pub const GEOMETRY_TYPE: u8 = 7;
+    pub const SIMPLE_PRIMARY_KEY: u8 = 8;
+    pub const PRIMARY_KEY_WITH_PREFIX: u8 = 9;
+    pub const ENUM_AND_SET_DEFAULT_CHARSET: u8 = 10;
+    pub const ENUM_AND_SET_COLUMN_CHARSET: u8 = 11;
+    pub const COLUMN_VISIBILITY: u8 = 12;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnInfo {
+    kind: u8,
+    meta: u16,
+    nullable: bool,
+    name: Option<String>,
+    unsigned: bool,
+    charset: Option<u32>,
+    pk: bool,
+    set_enum_values: Vec<String>,
+    geometry_type: Option<u32>,
+    visible: bool,
+}
+
+impl ColumnInfo {
+    pub fn new(kind: u8, meta: u16) -> ColumnInfo {
+        ColumnInfo { kind, meta, visible: true, ..ColumnInfo::default() }
+    }
+
+    pub fn kind(&self) -> u8 {
+        self.kind
+    }
+    pub fn meta(&self) -> u16 {
+        self.meta
+    }
+    pub fn nullable(&self) -> bool {
+        self.nullable
+    }
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    pub fn unsigned(&self) -> bool {
+        self.unsigned
+    }
+    pub fn charset(&self) -> Option<u32> {
+        self.charset
+    }
+    pub fn pk(&self) -> bool {
+        self.pk
+    }
+    pub fn set_enum_values(&self) -> &Vec<String> {
+        &self.set_enum_values
+    }
+    pub fn geometry_type(&self) -> Option<u32> {
+        self.geometry_type
+    }
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    // 没有FULL metadata时由DDL解析出的表结构补全
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+    pub fn set_unsigned(&mut self, unsigned: bool) {
+        self.unsigned = unsigned;
+    }
+    pub fn set_pk(&mut self, pk: bool) {
+        self.pk = pk;
+    }
+
+    // STRING类型的meta高字节为真实类型(ENUM/SET/STRING)
+    pub fn real_type(&self) -> u8 {
+        if self.kind == MYSQL_TYPE_STRING && self.meta >= 256 {
+            let real_type = (self.meta >> 8) as u8;
+            if real_type == MYSQL_TYPE_ENUM || real_type == MYSQL_TYPE_SET {
+                return real_type;
+            }
+        }
+        self.kind
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self.kind, MYSQL_TYPE_TINY | MYSQL_TYPE_SHORT | MYSQL_TYPE_INT24 | MYSQL_TYPE_LONG
+            | MYSQL_TYPE_LONGLONG | MYSQL_TYPE_NEWDECIMAL | MYSQL_TYPE_FLOAT | MYSQL_TYPE_DOUBLE | MYSQL_TYPE_DECIMAL)
+    }
+
+    fn is_character(&self) -> bool {
+        matches!(self.real_type(), MYSQL_TYPE_STRING | MYSQL_TYPE_VAR_STRING | MYSQL_TYPE_VARCHAR | MYSQL_TYPE_BLOB)
+    }
+
+    fn is_enum_or_set(&self) -> bool {
+        matches!(self.real_type(), MYSQL_TYPE_ENUM | MYSQL_TYPE_SET)
+    }
+}
+
+/**
+ * <pre>
+ *  TABLE_MAP_EVENT
+ *  post header:
+ *      6 (4 in 5.0)    table id
+ *      2               flags
+ *  body:
+ *      1 + n + 1       database name (length + name + 0x00)
+ *      1 + n + 1       table name (length + name + 0x00)
+ *      packed          column count
+ *      n               column types
+ *      packed + n      column metadata
+ *      (n + 7) / 8     null bitmap
+ *      ...             optional metadata (8.0.1+)
+ * </pre>
+ *  tolerant模式下, column metadata之后的内容解析失败时不会中断解析,
+ *  而是返回已经解析出的部分信息并记录失败原因
+ */
+#[derive(Debug, Clone)]
+pub struct TableMapLogEvent {
+    header: LogHeader,
+    table_id: u64,
+    flags: u16,
+    db_name: String,
+    table_name: String,
+    column_info: Vec<ColumnInfo>,
+    partial_error: Option<String>,
+}
+
+impl TableMapLogEvent {
+    pub fn from(header: LogHeader, buffer: &mut LogBuffer, description: &FormatDescriptionLogEvent,
+                tolerant: bool) -> Result<TableMapLogEvent, CanalError> {
+        let post_header_len = description.post_header_len(EventType::TableMapEvent)
+            .unwrap_or(TABLE_MAP_POST_HEADER_LEN_V1 + 2);
+        buffer.set_position(LOG_HEADER_LEN)?;
+        buffer.set_limit(LOG_HEADER_LEN + header.data_len())?;
+        let table_id = if post_header_len == TABLE_MAP_POST_HEADER_LEN_V1 {
+            buffer.get_uint32()? as u64
+        } else {
+            buffer.get_uint48()?
+        };
+        let flags = buffer.get_uint16()?;
+        buffer.set_position(LOG_HEADER_LEN + post_header_len)?;
+
+        let db_name = buffer.get_length_string()?;
+        buffer.forward(1)?;
+        let table_name = buffer.get_length_string()?;
+        buffer.forward(1)?;
+        let column_count = buffer.get_packed_long()?.unwrap_or(0) as usize;
+        let types = buffer.get_bytes(column_count)?;
+        let mut column_info: Vec<ColumnInfo> = types.iter().map(|kind| ColumnInfo::new(*kind, 0)).collect();
+
+        let mut event = TableMapLogEvent { header, table_id, flags, db_name, table_name, column_info: vec![], partial_error: None };
+        let result = decode_metadata(buffer, &mut column_info);
+        event.column_info = column_info;
+        if let Err(e) = result {
+            if !tolerant {
+                return Err(CanalError::Decode(format!("decode table map of {}.{} failure: {}", event.db_name,
+                                                      event.table_name, e)));
+            }
+            event.partial_error = Some(e.to_string());
+        }
+        Ok(event)
+    }
+
+    pub fn header(&self) -> &LogHeader {
+        &self.header
+    }
+    // 复用缓存的解析结果时替换为当前event的header
+    pub fn set_header(&mut self, header: LogHeader) {
+        self.header = header;
+    }
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn db_name(&self) -> &str {
+        &self.db_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn column_count(&self) -> usize {
+        self.column_info.len()
+    }
+    pub fn column_info(&self) -> &Vec<ColumnInfo> {
+        &self.column_info
+    }
+    pub fn column_info_mut(&mut self) -> &mut Vec<ColumnInfo> {
+        &mut self.column_info
+    }
+
+    // tolerant模式下部分解析失败的原因, None表示完整解析
+    pub fn partial_error(&self) -> Option<&str> {
+        self.partial_error.as_deref()
+    }
+
+    pub fn is_partial(&self) -> bool {
+        self.partial_error.is_some()
+    }
+}
+
+fn decode_metadata(buffer: &mut LogBuffer, column_info: &mut [ColumnInfo]) -> Result<(), CanalError> {
+    let meta_len = buffer.get_packed_long()?.unwrap_or(0) as usize;
+    let mut meta = buffer.duplicate(buffer.position(), meta_len)?;
+    buffer.forward(meta_len)?;
+    for info in column_info.iter_mut() {
+        info.meta = match info.kind {
+            MYSQL_TYPE_TINY_BLOB | MYSQL_TYPE_BLOB | MYSQL_TYPE_MEDIUM_BLOB | MYSQL_TYPE_LONG_BLOB
+            | MYSQL_TYPE_DOUBLE | MYSQL_TYPE_FLOAT | MYSQL_TYPE_GEOMETRY | MYSQL_TYPE_JSON
+            | MYSQL_TYPE_TIME2 | MYSQL_TYPE_DATETIME2 | MYSQL_TYPE_TIMESTAMP2 => meta.get_uint8()? as u16,
+            MYSQL_TYPE_SET | MYSQL_TYPE_ENUM | MYSQL_TYPE_STRING | MYSQL_TYPE_NEWDECIMAL => meta.get_unsigned_be(2)? as u16,
+            MYSQL_TYPE_BIT | MYSQL_TYPE_VARCHAR | MYSQL_TYPE_VAR_STRING => meta.get_uint16()?,
+            MYSQL_TYPE_VECTOR => meta.get_uint32()? as u16,
+            _ => 0,
+        };
+    }
+
+    let null_bits = buffer.get_bytes(column_info.len().div_ceil(8))?;
+    for (i, info) in column_info.iter_mut().enumerate() {
+        info.nullable = null_bits[i / 8] & (1 << (i % 8)) != 0;
+    }
+
+    while buffer.has_remaining() {
+        let kind = buffer.get_uint8()?;
+        let len = buffer.get_packed_long()?.unwrap_or(0) as usize;
+        let mut field = buffer.duplicate(buffer.position(), len)?;
+        buffer.forward(len)?;
+        decode_optional_metadata(kind, &mut field, column_info)?;
+    }
+    Ok(())
+}
+
+fn decode_optional_metadata(kind: u8, field: &mut LogBuffer, column_info: &mut [ColumnInfo]) -> Result<(), CanalError> {
+    match kind {
+        optional_metadata::SIGNEDNESS => {
+            let bits = field.get_rest_bytes();
+            for (i, info) in column_info.iter_mut().filter(|info| info.is_numeric()).enumerate() {
+                info.unsigned = bits.get(i / 8).map(|b| b & (0x80 >> (i % 8)) != 0).unwrap_or(false);
+            }
+        }
+        optional_metadata::DEFAULT_CHARSET | optional_metadata::ENUM_AND_SET_DEFAULT_CHARSET => {
+            let enum_or_set = kind == optional_metadata::ENUM_AND_SET_DEFAULT_CHARSET;
+            let default_charset = get_packed(field)? as u32;
+            let mut overrides = std::collections::HashMap::new();
+            while field.has_remaining() {
+                overrides.insert(get_packed(field)? as usize, get_packed(field)? as u32);
+            }
+            for (i, info) in column_info.iter_mut().filter(|info| charset_target(info, enum_or_set)).enumerate() {
+                info.charset = Some(*overrides.get(&i).unwrap_or(&default_charset));
+            }
+        }
+        optional_metadata::COLUMN_CHARSET | optional_metadata::ENUM_AND_SET_COLUMN_CHARSET => {
+            let enum_or_set = kind == optional_metadata::ENUM_AND_SET_COLUMN_CHARSET;
+            for info in column_info.iter_mut().filter(|info| charset_target(info, enum_or_set)) {
+                info.charset = Some(get_packed(field)? as u32);
+            }
+        }
+        optional_metadata::COLUMN_NAME => {
+            for info in column_info.iter_mut() {
+                let len = get_packed(field)? as usize;
+                info.name = Some(String::from_utf8_lossy(field.get_bytes(len)?).to_string());
+            }
+        }
+        optional_metadata::SET_STR_VALUE | optional_metadata::ENUM_STR_VALUE => {
+            let target = if kind == optional_metadata::SET_STR_VALUE { MYSQL_TYPE_SET } else { MYSQL_TYPE_ENUM };
+            for info in column_info.iter_mut().filter(|info| info.real_type() == target) {
+                let count = get_packed(field)?;
+                for _ in 0..count {
+                    let len = get_packed(field)? as usize;
+                    info.set_enum_values.push(String::from_utf8_lossy(field.get_bytes(len)?).to_string());
+                }
+            }
+        }
+        optional_metadata::GEOMETRY_TYPE => {
+            for info in column_info.iter_mut().filter(|info| info.kind == MYSQL_TYPE_GEOMETRY) {
+                info.geometry_type = Some(get_packed(field)? as u32);
+            }
+        }
+        optional_metadata::SIMPLE_PRIMARY_KEY => {
+            while field.has_remaining() {
+                let index = get_packed(field)? as usize;
+                if let Some(info) = column_info.get_mut(index) {
+                    info.pk = true;
+                }
+            }
+        }
+        optional_metadata::PRIMARY_KEY_WITH_PREFIX => {
+            while field.has_remaining() {
+                let index = get_packed(field)? as usize;
+                let _prefix = get_packed(field)?;
+                if let Some(info) = column_info.get_mut(index) {
+                    info.pk = true;
+                }
+            }
+        }
+        optional_metadata::COLUMN_VISIBILITY => {
+            let bits = field.get_rest_bytes();
+            for (i, info) in column_info.iter_mut().enumerate() {
+                info.visible = bits.get(i / 8).map(|b| b & (0x80 >> (i % 8)) != 0).unwrap_or(true);
+            }
+        }
+        // 新版本增加的类型, 按长度跳过
+        _ => {}
+    }
+    Ok(())
+}
+
+fn charset_target(info: &ColumnInfo, enum_or_set: bool) -> bool {
+    if enum_or_set { info.is_enum_or_set() } else { info.is_character() }
+}
+
+fn get_packed(buffer: &mut LogBuffer) -> Result<u64, CanalError> {
+    buffer.get_packed_long()?.ok_or_else(|| CanalError::decode("unexpected NULL in optional metadata"))
+}
@@ -0,0 +1,162 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::command::event::{LogHeader, TableMapLogEvent};
+use crate::metrics::MemoryGauge;
+
+// 超过后清空重新缓存, 正常情况下远大于活跃的table_id数量
+pub const DEFAULT_TABLE_MAP_CACHE_CAPACITY: usize = 4096;
+
+/**
+ * <pre>
+ *  master在每个rows event之前都会重新发送table map, 内容通常完全相同.
+ *  按event body(post header + body, 不包含common header和checksum)的hash缓存解析结果,
+ *  body以table_id开头, 因此相当于以(table_id, 表结构)为key.
+ *  命中时比较原始字节避免hash冲突, 只替换header, 不再解析column metadata.
+ *  部分解析(tolerant)的table map不缓存
+ * </pre>
+ */
+#[derive(Debug)]
+pub struct TableMapCache {
+    capacity: usize,
+    // hash -> (body, 解析结果)
+    entries: HashMap<u64, (Vec<u8>, TableMapLogEvent)>,
+    hits: u64,
+    misses: u64,
+    // 缓存的估计字节数
+    bytes: usize,
+    memory_gauge: Option<MemoryGauge>,
+}
+
+impl Default for TableMapCache {
+    fn default() -> Self {
+        TableMapCache::new(DEFAULT_TABLE_MAP_CACHE_CAPACITY)
+    }
+}
+
+impl TableMapCache {
+    pub fn new(capacity: usize) -> TableMapCache {
+        TableMapCache { capacity, entries: HashMap::new(), hits: 0, misses: 0, bytes: 0, memory_gauge: None }
+    }
+
+    // capacity为0时关闭缓存
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+    pub fn memory_gauge(&self) -> Option<&MemoryGauge> {
+        self.memory_gauge.as_ref()
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    // 同步缓存的字节数, 例如 MemoryStats::gauge("table_map_cache")
+    pub fn set_memory_gauge(&mut self, gauge: MemoryGauge) {
+        if let Some(previous) = self.memory_gauge.as_ref() {
+            previous.sub(self.bytes);
+        }
+        gauge.add(self.bytes);
+        self.memory_gauge = Some(gauge);
+    }
+
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0.0 } else { self.hits as f64 / total as f64 }
+    }
+
+    pub fn get(&mut self, header: &LogHeader, body: &[u8]) -> Option<TableMapLogEvent> {
+        if self.capacity == 0 {
+            return None;
+        }
+        match self.entries.get(&hash(body)) {
+            Some((raw, table_map)) if raw == body => {
+                self.hits += 1;
+                let mut table_map = table_map.clone();
+                table_map.set_header(header.clone());
+                Some(table_map)
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn put(&mut self, body: &[u8], table_map: &TableMapLogEvent) {
+        if self.capacity == 0 || table_map.is_partial() {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.clear();
+        }
+        let size = entry_bytes(body);
+        if let Some((previous, _)) = self.entries.insert(hash(body), (body.to_vec(), table_map.clone())) {
+            self.release(entry_bytes(&previous));
+        }
+        self.bytes += size;
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.add(size);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.release(self.bytes);
+    }
+
+    fn release(&mut self, size: usize) {
+        self.bytes -= size;
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.sub(size);
+        }
+    }
+}
+
+// 复制的缓存同样计入gauge
+impl Clone for TableMapCache {
+    fn clone(&self) -> Self {
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.add(self.bytes);
+        }
+        TableMapCache {
+            capacity: self.capacity,
+            entries: self.entries.clone(),
+            hits: self.hits,
+            misses: self.misses,
+            bytes: self.bytes,
+            memory_gauge: self.memory_gauge.clone(),
+        }
+    }
+}
+
+impl Drop for TableMapCache {
+    fn drop(&mut self) {
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.sub(self.bytes);
+        }
+    }
+}
+
+// 原始的body加上解析结果, 解析结果(列类型, metadata, 库表名)按与body相同的大小估计
+fn entry_bytes(body: &[u8]) -> usize {
+    2 * body.len() + std::mem::size_of::<TableMapLogEvent>()
+}
+
+fn hash(body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    hasher.finish()
+}
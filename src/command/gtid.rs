@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::command::event::{EventType, GtidTaggedLogEvent, LogHeader, LOG_HEADER_LEN};
+use crate::command::hex::encode_hex;
+use crate::command::log_buffer::LogBuffer;
+
+// GTID_LOG_EVENT的post header长度, 之后是commit timestamp
+const GTID_COMMIT_TIMESTAMP_OFFSET: usize = 42;
+// immediate_commit_timestamp的最高位, 表示后面跟着original_commit_timestamp
+const COMMIT_TIMESTAMP_FLAG: u64 = 1 << 55;
+
+/**
+ * <pre>
+ *  GTID集合, 对应server中的gtid_executed/gtid_purged格式:
+ *      3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:11-18,
+ *      8a94f357-aab4-11df-86ab-c80aa9429563:1-10
+ *  用于COM_BINLOG_DUMP_GTID, 位点持久化以及PREVIOUS_GTIDS_LOG_EVENT的解析
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GtidSet {
+    uuid_sets: BTreeMap<String, UuidSet>,
+}
+
+// 单个server_uuid对应的区间集合
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UuidSet {
+    sid: [u8; 16],
+    intervals: Vec<Interval>,
+}
+
+// 区间[start, stop), 与server内部表示一致, stop不包含在区间内
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    start: u64,
+    stop: u64,
+}
+
+impl Interval {
+    pub fn new(start: u64, stop: u64) -> Interval {
+        Interval { start, stop }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn stop(&self) -> u64 {
+        self.stop
+    }
+}
+
+impl UuidSet {
+    pub fn new(sid: [u8; 16]) -> UuidSet {
+        UuidSet { sid, intervals: vec![] }
+    }
+
+    pub fn sid(&self) -> &[u8; 16] {
+        &self.sid
+    }
+
+    pub fn uuid(&self) -> String {
+        format_uuid(&self.sid)
+    }
+
+    pub fn intervals(&self) -> &Vec<Interval> {
+        &self.intervals
+    }
+
+    pub fn contains(&self, gno: u64) -> bool {
+        self.intervals.iter().any(|interval| interval.start <= gno && gno < interval.stop)
+    }
+
+    pub fn add_interval(&mut self, interval: Interval) {
+        if interval.start >= interval.stop {
+            return;
+        }
+        self.intervals.push(interval);
+        self.intervals.sort_by_key(|interval| interval.start);
+        // 合并重叠以及相邻的区间
+        let mut merged: Vec<Interval> = Vec::with_capacity(self.intervals.len());
+        for interval in self.intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if interval.start <= last.stop => {
+                    last.stop = last.stop.max(interval.stop);
+                }
+                _ => merged.push(interval),
+            }
+        }
+        self.intervals = merged;
+    }
+
+    pub fn add_gno(&mut self, gno: u64) -> Result<(), String> {
+        self.add_interval(Interval::new(gno, next_gno(gno)?));
+        Ok(())
+    }
+
+    // 最后一个已执行的事务编号
+    pub fn last_gno(&self) -> Option<u64> {
+        self.intervals.last().map(|interval| interval.stop - 1)
+    }
+}
+
+impl Display for UuidSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format_uuid(&self.sid))?;
+        for interval in &self.intervals {
+            if interval.stop - interval.start == 1 {
+                write!(f, ":{}", interval.start)?;
+            } else {
+                write!(f, ":{}-{}", interval.start, interval.stop - 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GtidSet {
+    pub fn new() -> GtidSet {
+        GtidSet::default()
+    }
+
+    pub fn parse(gtid_set: &str) -> Result<GtidSet, String> {
+        let mut set = GtidSet::new();
+        for uuid_set in gtid_set.split(',') {
+            let uuid_set: String = uuid_set.chars().filter(|c| !c.is_whitespace()).collect();
+            if uuid_set.is_empty() {
+                continue;
+            }
+            let mut parts = uuid_set.split(':');
+            let sid = parse_uuid(parts.next().unwrap_or_default())?;
+            let mut intervals = vec![];
+            for part in parts {
+                intervals.push(parse_interval(part)?);
+            }
+            if intervals.is_empty() {
+                return Err(format!("gtid set {} has no interval", uuid_set));
+            }
+            for interval in intervals {
+                set.add_interval(sid, interval);
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uuid_sets.is_empty()
+    }
+
+    pub fn uuid_sets(&self) -> impl Iterator<Item=&UuidSet> {
+        self.uuid_sets.values()
+    }
+
+    pub fn get(&self, uuid: &str) -> Option<&UuidSet> {
+        self.uuid_sets.get(&uuid.to_lowercase())
+    }
+
+    pub fn add_interval(&mut self, sid: [u8; 16], interval: Interval) {
+        self.uuid_sets.entry(format_uuid(&sid))
+            .or_insert_with(|| UuidSet::new(sid))
+            .add_interval(interval);
+    }
+
+    pub fn add_transaction(&mut self, sid: [u8; 16], gno: u64) -> Result<(), String> {
+        self.add_interval(sid, Interval::new(gno, next_gno(gno)?));
+        Ok(())
+    }
+
+    // 添加单个gtid, 格式为uuid:gno
+    pub fn add(&mut self, gtid: &str) -> Result<(), String> {
+        let (uuid, gno) = gtid.trim().split_once(':')
+            .ok_or_else(|| format!("invalid gtid {}", gtid))?;
+        let gno = u64::from_str(gno).map_err(|e| format!("invalid gtid {}: {}", gtid, e))?;
+        self.add_transaction(parse_uuid(uuid)?, gno)
+    }
+
+    pub fn merge(&mut self, other: &GtidSet) {
+        for uuid_set in other.uuid_sets.values() {
+            for interval in &uuid_set.intervals {
+                self.add_interval(uuid_set.sid, *interval);
+            }
+        }
+    }
+
+    pub fn contains(&self, sid: &[u8; 16], gno: u64) -> bool {
+        self.uuid_sets.get(&format_uuid(sid))
+            .map(|uuid_set| uuid_set.contains(gno))
+            .unwrap_or(false)
+    }
+
+    /**
+     * <pre>
+     *  COM_BINLOG_DUMP_GTID以及PREVIOUS_GTIDS_LOG_EVENT中的编码格式:
+     *  8                   n_sids
+     *  for n_sids:
+     *      16              sid
+     *      8               n_intervals
+     *      for n_intervals:
+     *          8           start
+     *          8           stop (exclusive)
+     * </pre>
+     */
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.encoded_length());
+        out.extend_from_slice(&(self.uuid_sets.len() as u64).to_le_bytes());
+        for uuid_set in self.uuid_sets.values() {
+            out.extend_from_slice(&uuid_set.sid);
+            out.extend_from_slice(&(uuid_set.intervals.len() as u64).to_le_bytes());
+            for interval in &uuid_set.intervals {
+                out.extend_from_slice(&interval.start.to_le_bytes());
+                out.extend_from_slice(&interval.stop.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn encoded_length(&self) -> usize {
+        8 + self.uuid_sets.values()
+            .map(|uuid_set| 16 + 8 + uuid_set.intervals.len() * 16)
+            .sum::<usize>()
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<GtidSet, String> {
+        let mut index = 0;
+        let n_sids = read_u64(buf, &mut index)?;
+        let mut set = GtidSet::new();
+        for _ in 0..n_sids {
+            let sid: [u8; 16] = buf.get(index..index + 16)
+                .ok_or_else(|| format!("gtid set truncated at {}", index))?
+                .try_into()
+                .unwrap();
+            index += 16;
+            let n_intervals = read_u64(buf, &mut index)?;
+            for _ in 0..n_intervals {
+                let start = read_u64(buf, &mut index)?;
+                let stop = read_u64(buf, &mut index)?;
+                set.add_interval(sid, Interval::new(start, stop));
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl Display for GtidSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut first = true;
+        for uuid_set in self.uuid_sets.values() {
+            if !first {
+                write!(f, ",")?;
+            }
+            first = false;
+            write!(f, "{}", uuid_set)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GtidSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GtidSet::parse(s)
+    }
+}
+
+pub fn parse_uuid(uuid: &str) -> Result<[u8; 16], String> {
+    let hex: Vec<u8> = uuid.bytes().filter(|b| *b != b'-').collect();
+    if hex.len() != 32 || uuid.len() != 36 {
+        return Err(format!("invalid server uuid {}", uuid));
+    }
+    let mut sid = [0u8; 16];
+    for (i, pair) in hex.chunks(2).enumerate() {
+        let pair = std::str::from_utf8(pair).map_err(|e| e.to_string())?;
+        sid[i] = u8::from_str_radix(pair, 16).map_err(|_| format!("invalid server uuid {}", uuid))?;
+    }
+    Ok(sid)
+}
+
+pub fn format_uuid(sid: &[u8; 16]) -> String {
+    let hex = encode_hex(sid);
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+// GTID_LOG_EVENT/MariaDB GTID_EVENT中的gtid, mysql: uuid:gno, 带tag时为uuid:tag:gno, MariaDB: domain-server_id-seq
+pub fn event_gtid(header: &LogHeader, event: &[u8]) -> Option<String> {
+    let body = event.get(LOG_HEADER_LEN..)?;
+    match header.event_type()? {
+        EventType::GtidTaggedLogEvent => {
+            GtidTaggedLogEvent::from(header.clone(), &mut LogBuffer::new(event), None).ok().map(|event| event.gtid())
+        }
+        EventType::GtidLogEvent if body.len() >= 25 => {
+            let mut sid = [0u8; 16];
+            sid.copy_from_slice(&body[1..17]);
+            let mut gno = [0u8; 8];
+            gno.copy_from_slice(&body[17..25]);
+            Some(format!("{}:{}", format_uuid(&sid), u64::from_le_bytes(gno)))
+        }
+        EventType::GtidEvent if body.len() >= 12 => {
+            let mut seq = [0u8; 8];
+            seq.copy_from_slice(&body[..8]);
+            let domain = u32::from_le_bytes([body[8], body[9], body[10], body[11]]);
+            Some(format!("{}-{}-{}", domain, header.server_id(), u64::from_le_bytes(seq)))
+        }
+        _ => None,
+    }
+}
+
+/**
+ * <pre>
+ *  mysql 8.0.1+ GTID_LOG_EVENT中的(original_commit_timestamp, immediate_commit_timestamp), 微秒:
+ *      post header     flags(1) sid(16) gno(8) lt_type(1) last_committed(8) sequence_number(8)
+ *      immediate       7字节, 最高位为1时后面跟着7字节的original, 否则original与immediate相同
+ *  original为事务在最初的master上提交的时间, immediate为在当前连接的server上提交的时间.
+ *  post_header_len为format description中的长度, 更新的版本在post header末尾追加字段时跳过.
+ *  5.7以及MariaDB的GTID event没有这两个字段, GTID_TAGGED_LOG_EVENT见GtidTaggedLogEvent, 都返回None
+ * </pre>
+ */
+pub fn event_commit_timestamps(header: &LogHeader, event: &[u8], post_header_len: Option<usize>) -> Option<(i64, i64)> {
+    if header.event_type()? != EventType::GtidLogEvent {
+        return None;
+    }
+    let body = event.get(LOG_HEADER_LEN..LOG_HEADER_LEN + header.data_len())?;
+    let offset = post_header_len.unwrap_or(GTID_COMMIT_TIMESTAMP_OFFSET).max(GTID_COMMIT_TIMESTAMP_OFFSET);
+    let read_u56 = |offset: usize| -> Option<u64> {
+        let bytes = body.get(offset..offset + 7)?;
+        let mut buf = [0u8; 8];
+        buf[..7].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(buf))
+    };
+    let immediate = read_u56(offset)?;
+    let original = if immediate & COMMIT_TIMESTAMP_FLAG != 0 {
+        read_u56(offset + 7)?
+    } else {
+        immediate
+    };
+    let immediate = immediate & !COMMIT_TIMESTAMP_FLAG;
+    Some((original as i64, immediate as i64))
+}
+
+fn parse_interval(interval: &str) -> Result<Interval, String> {
+    let parse = |gno: &str| u64::from_str(gno).map_err(|e| format!("invalid gtid interval {}: {}", interval, e));
+    let (start, stop) = match interval.split_once('-') {
+        Some((start, stop)) => (parse(start)?, parse(stop)?),
+        None => {
+            let gno = parse(interval)?;
+            (gno, gno)
+        }
+    };
+    if start == 0 || stop < start {
+        return Err(format!("invalid gtid interval {}", interval));
+    }
+    let stop = next_gno(stop).map_err(|e| format!("invalid gtid interval {}: {}", interval, e))?;
+    Ok(Interval::new(start, stop))
+}
+
+// 区间不包含stop, u64::MAX之后没有可以作为stop的编号
+fn next_gno(gno: u64) -> Result<u64, String> {
+    gno.checked_add(1).ok_or_else(|| format!("gno {} is out of range", gno))
+}
+
+fn read_u64(buf: &[u8], index: &mut usize) -> Result<u64, String> {
+    let bytes = buf.get(*index..*index + 8)
+        .ok_or_else(|| format!("gtid set truncated at {}", index))?;
+    *index += 8;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
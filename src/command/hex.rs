@@ -0,0 +1,201 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+// hex dump每行的字节数
+pub const BYTES_PER_LINE: usize = 16;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const INVALID_NIBBLE: u8 = 0xff;
+// 遮盖的字节在hex中输出为**, 在ascii中输出为*
+const REDACTED: u8 = b'*';
+
+// 每个字节对应的两个hex字符
+const HEX_TABLE: [[u8; 2]; 256] = {
+    let mut table = [[0u8; 2]; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = [HEX_DIGITS[i >> 4], HEX_DIGITS[i & 0x0f]];
+        i += 1;
+    }
+    table
+};
+
+// hex字符对应的值, 不是hex字符时为INVALID_NIBBLE
+const NIBBLE_TABLE: [u8; 256] = {
+    let mut table = [INVALID_NIBBLE; 256];
+    let mut i = 0;
+    while i < 10 {
+        table[b'0' as usize + i] = i as u8;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 6 {
+        table[b'a' as usize + i] = 10 + i as u8;
+        table[b'A' as usize + i] = 10 + i as u8;
+        i += 1;
+    }
+    table
+};
+
+// 小写hex, 查表后按2字节一组写入预先分配的缓冲, 没有逐字节的格式化
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut out = vec![0u8; bytes.len() * 2];
+    encode_into(bytes, &mut out);
+    // HEX_TABLE中只有ascii字符
+    String::from_utf8(out).unwrap_or_default()
+}
+
+fn encode_into(bytes: &[u8], out: &mut [u8]) {
+    for (pair, byte) in out.chunks_exact_mut(2).zip(bytes) {
+        pair.copy_from_slice(&HEX_TABLE[*byte as usize]);
+    }
+}
+
+// 忽略大小写, 长度必须是偶数
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex = hex.as_bytes();
+    if !hex.len().is_multiple_of(2) {
+        return Err(format!("invalid hex length {}", hex.len()));
+    }
+    let mut out = Vec::with_capacity(hex.len() / 2);
+    for (i, pair) in hex.chunks_exact(2).enumerate() {
+        let high = NIBBLE_TABLE[pair[0] as usize];
+        let low = NIBBLE_TABLE[pair[1] as usize];
+        if high == INVALID_NIBBLE || low == INVALID_NIBBLE {
+            let (offset, byte) = if high == INVALID_NIBBLE { (i * 2, pair[0]) } else { (i * 2 + 1, pair[1]) };
+            return Err(format!("invalid hex character 0x{:02x} at {}", byte, offset));
+        }
+        out.push(high << 4 | low);
+    }
+    Ok(out)
+}
+
+/**
+ * <pre>
+ *  event等原始数据的hex输出, 用于错误日志和DLQ记录:
+ *      to_compact()    一行连续的hex, 例如 fe0a00...
+ *      Display         与hexdump -C相同的格式, 每行16个字节:
+ *                      00000000  fe 0a 00 00 01 02 03 04  05 06 07 08 09 0a 0b 0c  |................|
+ *  redact()设置的区间(例如敏感列的值)输出为**, ascii部分为*, 只保留长度信息.
+ *  超过max_bytes的部分省略, compact以...结尾, Display最后一行说明省略的字节数
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    max_bytes: usize,
+    // 按起点排序, 互不重叠
+    redacted: Vec<Range<usize>>,
+}
+
+impl<'a> HexDump<'a> {
+    pub fn new(bytes: &'a [u8]) -> HexDump<'a> {
+        HexDump { bytes, max_bytes: usize::MAX, redacted: vec![] }
+    }
+
+    // 原始数据的字节数, 包括省略的部分
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    // 超出bytes的部分忽略
+    pub fn redact(&mut self, range: Range<usize>) {
+        let range = range.start..range.end.min(self.bytes.len());
+        if range.is_empty() {
+            return;
+        }
+        self.redacted.push(range);
+        self.redacted.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(self.redacted.len());
+        for range in self.redacted.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        self.redacted = merged;
+    }
+
+    pub fn redacted(&self) -> &Vec<Range<usize>> {
+        &self.redacted
+    }
+
+    pub fn redacted_bytes(&self) -> usize {
+        self.redacted.iter().map(|range| range.len()).sum()
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.bytes.len() > self.max_bytes
+    }
+
+    fn shown(&self) -> &'a [u8] {
+        &self.bytes[..self.bytes.len().min(self.max_bytes)]
+    }
+
+    pub fn to_compact(&self) -> String {
+        let shown = self.shown();
+        let mut out = vec![0u8; shown.len() * 2];
+        encode_into(shown, &mut out);
+        for range in self.redacted.iter().filter(|range| range.start < shown.len()) {
+            out[range.start * 2..range.end.min(shown.len()) * 2].fill(REDACTED);
+        }
+        if self.is_truncated() {
+            out.extend_from_slice(b"...");
+        }
+        String::from_utf8(out).unwrap_or_default()
+    }
+
+    // cursor为当前检查到的区间, offset递增时只需要向后移动
+    fn is_redacted(&self, cursor: &mut usize, offset: usize) -> bool {
+        while self.redacted.get(*cursor).is_some_and(|range| range.end <= offset) {
+            *cursor += 1;
+        }
+        self.redacted.get(*cursor).is_some_and(|range| range.start <= offset)
+    }
+}
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let shown = self.shown();
+        let mut cursor = 0;
+        let mut line = String::with_capacity(80);
+        for (index, chunk) in shown.chunks(BYTES_PER_LINE).enumerate() {
+            let offset = index * BYTES_PER_LINE;
+            let mut ascii = String::with_capacity(BYTES_PER_LINE);
+            line.clear();
+            for (i, byte) in chunk.iter().enumerate() {
+                line.push_str(if i == BYTES_PER_LINE / 2 { "  " } else { " " });
+                if self.is_redacted(&mut cursor, offset + i) {
+                    line.push_str("**");
+                    ascii.push(REDACTED as char);
+                    continue;
+                }
+                let [high, low] = HEX_TABLE[*byte as usize];
+                line.push(high as char);
+                line.push(low as char);
+                ascii.push(if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' });
+            }
+            // 最后一行不足16个字节时补齐, 保证ascii部分对齐
+            for i in chunk.len()..BYTES_PER_LINE {
+                line.push_str(if i == BYTES_PER_LINE / 2 { "    " } else { "   " });
+            }
+            writeln!(f, "{:08x} {}  |{}|", offset, line, ascii)?;
+        }
+        if self.is_truncated() {
+            writeln!(f, "... {} more bytes", self.bytes.len() - shown.len())?;
+        }
+        Ok(())
+    }
+}
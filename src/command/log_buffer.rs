@@ -0,0 +1,237 @@
+use std::str::from_utf8;
+
+use crate::error::CanalError;
+
+/**
+ * <pre>
+ *  对应canal中的LogBuffer, 以小端序读取binlog event中的各类字段,
+ *  所有读取操作都会做越界检查, 越界时返回Err而不是panic
+ * </pre>
+ */
+pub struct LogBuffer<'a> {
+    buffer: &'a [u8],
+    origin: usize,
+    position: usize,
+    limit: usize,
+}
+
+impl<'a> LogBuffer<'a> {
+    pub fn new(buffer: &'a [u8]) -> LogBuffer<'a> {
+        LogBuffer { buffer, origin: 0, position: 0, limit: buffer.len() }
+    }
+
+    // 以[origin, origin + limit)构造一个新的buffer, position从0开始
+    pub fn duplicate(&self, pos: usize, len: usize) -> Result<LogBuffer<'a>, CanalError> {
+        self.check(pos, len)?;
+        let origin = self.origin + pos;
+        Ok(LogBuffer { buffer: self.buffer, origin, position: origin, limit: origin + len })
+    }
+
+    pub fn position(&self) -> usize {
+        self.position - self.origin
+    }
+
+    pub fn set_position(&mut self, position: usize) -> Result<(), CanalError> {
+        if self.origin + position > self.limit {
+            return Err(CanalError::Decode(format!("limit exceeded: {}", position)));
+        }
+        self.position = self.origin + position;
+        Ok(())
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit - self.origin
+    }
+
+    // 缩小可读范围, 常用于去掉event尾部的checksum
+    pub fn set_limit(&mut self, limit: usize) -> Result<(), CanalError> {
+        if self.origin + limit > self.buffer.len() {
+            return Err(CanalError::Decode(format!("capacity exceeded: {}", limit)));
+        }
+        self.limit = self.origin + limit;
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.limit - self.position
+    }
+
+    pub fn has_remaining(&self) -> bool {
+        self.position < self.limit
+    }
+
+    pub fn forward(&mut self, len: usize) -> Result<(), CanalError> {
+        self.check_remaining(len)?;
+        self.position += len;
+        Ok(())
+    }
+
+    pub fn get_uint8(&mut self) -> Result<u8, CanalError> {
+        self.check_remaining(1)?;
+        let value = self.buffer[self.position];
+        self.position += 1;
+        Ok(value)
+    }
+
+    pub fn get_int8(&mut self) -> Result<i8, CanalError> {
+        Ok(self.get_uint8()? as i8)
+    }
+
+    pub fn get_uint16(&mut self) -> Result<u16, CanalError> {
+        Ok(self.get_unsigned(2)? as u16)
+    }
+
+    pub fn get_int16(&mut self) -> Result<i16, CanalError> {
+        Ok(self.get_uint16()? as i16)
+    }
+
+    pub fn get_uint24(&mut self) -> Result<u32, CanalError> {
+        Ok(self.get_unsigned(3)? as u32)
+    }
+
+    pub fn get_int24(&mut self) -> Result<i32, CanalError> {
+        let value = self.get_uint24()?;
+        Ok(((value << 8) as i32) >> 8)
+    }
+
+    pub fn get_uint32(&mut self) -> Result<u32, CanalError> {
+        Ok(self.get_unsigned(4)? as u32)
+    }
+
+    pub fn get_int32(&mut self) -> Result<i32, CanalError> {
+        Ok(self.get_uint32()? as i32)
+    }
+
+    pub fn get_uint48(&mut self) -> Result<u64, CanalError> {
+        self.get_unsigned(6)
+    }
+
+    pub fn get_uint64(&mut self) -> Result<u64, CanalError> {
+        self.get_unsigned(8)
+    }
+
+    pub fn get_int64(&mut self) -> Result<i64, CanalError> {
+        Ok(self.get_uint64()? as i64)
+    }
+
+    // 小端序读取len个字节的无符号整数, len最大为8
+    pub fn get_unsigned(&mut self, len: usize) -> Result<u64, CanalError> {
+        self.check_remaining(len)?;
+        let mut value = 0u64;
+        for (i, b) in self.buffer[self.position..self.position + len].iter().enumerate() {
+            value |= (*b as u64) << (8 * i);
+        }
+        self.position += len;
+        Ok(value)
+    }
+
+    // 大端序读取, 用于decimal/datetime2等类型
+    pub fn get_unsigned_be(&mut self, len: usize) -> Result<u64, CanalError> {
+        self.check_remaining(len)?;
+        let mut value = 0u64;
+        for b in &self.buffer[self.position..self.position + len] {
+            value = (value << 8) | *b as u64;
+        }
+        self.position += len;
+        Ok(value)
+    }
+
+    /**
+     * <pre>
+     *  Length Coded Binary
+     *  0-250       1 byte
+     *  251         NULL
+     *  252         2 bytes
+     *  253         3 bytes
+     *  254         8 bytes
+     * </pre>
+     */
+    pub fn get_packed_long(&mut self) -> Result<Option<u64>, CanalError> {
+        let mark = self.get_uint8()?;
+        match mark {
+            251 => Ok(None),
+            252 => Ok(Some(self.get_unsigned(2)?)),
+            253 => Ok(Some(self.get_unsigned(3)?)),
+            254 => Ok(Some(self.get_unsigned(8)?)),
+            _ => Ok(Some(mark as u64)),
+        }
+    }
+
+    /**
+     * <pre>
+     *  mysql 8.3+ serialization库的变长整数, 用于GTID_TAGGED_LOG_EVENT等新event:
+     *  第一个字节末尾连续的1的个数 + 1为总字节数, 小端序, 去掉最低的n位之后为值;
+     *  第一个字节为0xff时后面跟着8字节的值
+     * </pre>
+     */
+    pub fn get_varlen_uint(&mut self) -> Result<u64, CanalError> {
+        let first = self.get_uint8()?;
+        if first == 0xff {
+            return self.get_uint64();
+        }
+        let len = first.trailing_ones() as usize + 1;
+        self.set_position(self.position() - 1)?;
+        Ok(self.get_unsigned(len)? >> len)
+    }
+
+    // 符号在最低位: 非负数为value << 1, 负数为((-value - 1) << 1) | 1
+    pub fn get_varlen_int(&mut self) -> Result<i64, CanalError> {
+        let value = self.get_varlen_uint()?;
+        Ok(((value >> 1) as i64) ^ -((value & 1) as i64))
+    }
+
+    pub fn get_bytes(&mut self, len: usize) -> Result<&'a [u8], CanalError> {
+        self.check_remaining(len)?;
+        let bytes = &self.buffer[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    pub fn get_rest_bytes(&mut self) -> &'a [u8] {
+        let bytes = &self.buffer[self.position..self.limit];
+        self.position = self.limit;
+        bytes
+    }
+
+    // 定长字符串, 遇到0x00截断
+    pub fn get_fix_string(&mut self, len: usize) -> Result<String, CanalError> {
+        let bytes = self.get_bytes(len)?;
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    // 以0x00结尾的字符串, 会跳过结尾的0x00
+    pub fn get_null_terminated_string(&mut self) -> Result<String, CanalError> {
+        let rest = &self.buffer[self.position..self.limit];
+        let end = rest.iter().position(|b| *b == 0)
+            .ok_or_else(|| CanalError::Decode(format!("missing null terminator at {}", self.position())))?;
+        let value = from_utf8(&rest[..end]).map_err(|e| CanalError::Decode(e.to_string()))?.to_string();
+        self.position += end + 1;
+        Ok(value)
+    }
+
+    // 1 byte长度 + 内容
+    pub fn get_length_string(&mut self) -> Result<String, CanalError> {
+        let len = self.get_uint8()? as usize;
+        let bytes = self.get_bytes(len)?;
+        Ok(String::from_utf8_lossy(bytes).to_string())
+    }
+
+    pub fn get_rest_string(&mut self) -> String {
+        String::from_utf8_lossy(self.get_rest_bytes()).to_string()
+    }
+
+    fn check(&self, pos: usize, len: usize) -> Result<(), CanalError> {
+        if self.origin + pos + len > self.limit {
+            return Err(CanalError::Decode(format!("limit exceeded: {}", pos + len)));
+        }
+        Ok(())
+    }
+
+    fn check_remaining(&self, len: usize) -> Result<(), CanalError> {
+        if self.position + len > self.limit {
+            return Err(CanalError::Decode(format!("limit exceeded: {}", self.position() + len)));
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,107 @@
-use std::borrow::Borrow;
-use std::slice::SliceIndex;
 use std::str::{*};
 use msc::{*};
 use capability::{*};
 
 
-pub mod packet_utils {}
+pub mod charset;
+
+pub mod com;
+
+pub mod errno;
+
+pub mod event;
+
+pub mod gtid;
+
+pub mod hex;
+
+pub mod log_buffer;
+
+pub mod password;
+
+pub mod packet_utils {
+    use std::io::Result;
+    use crate::channel::SocketChannel;
+    use super::{HeaderPacket, Packet};
+    use super::msc::{HEADER_PACKET_LENGTH, MAX_PACKET_LENGTH};
+
+    pub fn read_header(channel: &mut dyn SocketChannel) -> Result<HeaderPacket> {
+        let mut buf = [0u8; HEADER_PACKET_LENGTH];
+        channel.read_fully(&mut buf)?;
+        let mut header = HeaderPacket::default();
+        header.from_bytes(&buf);
+        Ok(header)
+    }
+
+    pub fn read_bytes(channel: &mut dyn SocketChannel, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        channel.read_fully(&mut buf)?;
+        Ok(buf)
+    }
+
+    // 读取一个完整的packet body, 超过16M的packet会被拆分为多个连续的packet
+    pub fn read_packet(channel: &mut dyn SocketChannel) -> Result<(HeaderPacket, Vec<u8>)> {
+        let header = read_header(channel)?;
+        let mut body = read_bytes(channel, header.packet_body_length() as usize)?;
+        let mut last = header.packet_body_length() as usize;
+        let mut sequence = header.get_packet_sequence_number();
+        while last == MAX_PACKET_LENGTH {
+            let next = read_header(channel)?;
+            last = next.packet_body_length() as usize;
+            sequence = next.get_packet_sequence_number();
+            body.extend(read_bytes(channel, last)?);
+        }
+        Ok((HeaderPacket::new(body.len() as i32, sequence), body))
+    }
+
+    pub fn write_pkg(channel: &mut dyn SocketChannel, sequence: u8, body: &[u8]) -> Result<()> {
+        let mut sequence = sequence;
+        let mut chunks = body.chunks(MAX_PACKET_LENGTH).peekable();
+        if chunks.peek().is_none() {
+            return write_chunk(channel, sequence, &[]);
+        }
+        while let Some(chunk) = chunks.next() {
+            write_chunk(channel, sequence, chunk)?;
+            sequence = sequence.wrapping_add(1);
+            if chunks.peek().is_none() && chunk.len() == MAX_PACKET_LENGTH {
+                write_chunk(channel, sequence, &[])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_body(channel: &mut dyn SocketChannel, body: &[u8]) -> Result<()> {
+        write_pkg(channel, 0, body)
+    }
+
+    fn write_chunk(channel: &mut dyn SocketChannel, sequence: u8, chunk: &[u8]) -> Result<()> {
+        let mut header = HeaderPacket::new(chunk.len() as i32, sequence);
+        let mut data = header.to_bytes().to_vec();
+        data.extend_from_slice(chunk);
+        channel.write_fully(&data)
+    }
+}
 
 pub mod msc {
     pub const DEFAULT_PROTOCOL_VERSION: u8 = 0x0a;
     pub const NULL_TERMINATED_STRING_DELIMITER: u8 = 0x00;
+    pub const HEADER_PACKET_LENGTH: usize = 4;
+    pub const MAX_PACKET_LENGTH: usize = (1 << 24) - 1;
+    pub const DEFAULT_CHARSET_NUMBER: u8 = 33;
+    pub const OK_HEADER: u8 = 0x00;
+    pub const EOF_HEADER: u8 = 0xfe;
+    pub const ERROR_HEADER: u8 = 0xff;
+    pub const AUTH_SWITCH_HEADER: u8 = 0xfe;
+    pub const AUTH_MORE_DATA_HEADER: u8 = 0x01;
+}
+
+pub mod command_type {
+    pub const COM_QUIT: u8 = 0x01;
+    pub const COM_QUERY: u8 = 0x03;
+    pub const COM_PING: u8 = 0x0e;
+    pub const COM_BINLOG_DUMP: u8 = 0x12;
+    pub const COM_REGISTER_SLAVE: u8 = 0x15;
+    pub const COM_BINLOG_DUMP_GTID: u8 = 0x1e;
 }
 
 pub mod capability {
@@ -175,34 +267,34 @@ pub mod capability {
     pub const CLIENT_DEPRECATE_EOF: i32 = 0x01000000;
 }
 
-trait Packet<'a> {
+#[allow(clippy::wrong_self_convention)]
+pub trait Packet<'a> {
     fn from_bytes(&mut self, buf: &'a [u8]);
     fn to_bytes(&mut self) -> Box<[u8]>;
 }
 
 
-struct AuthSwitchRequestMoreData<'a> {
+pub struct AuthSwitchRequestMoreData<'a> {
     command: u8,
     status: i32,
     auth_data: &'a [u8],
 }
 
 impl<'a> AuthSwitchRequestMoreData<'a> {
-    fn set_command(&mut self, command: u8) {
+    pub fn set_command(&mut self, command: u8) {
         self.command = command
     }
 
-    fn get_command(&self) -> u8 {
+    pub fn get_command(&self) -> u8 {
         self.command
     }
 }
 
 impl<'a, 'b: 'a> Packet<'b> for AuthSwitchRequestMoreData<'a> {
     fn from_bytes(&mut self, buf: &'b [u8]) {
-        let mut index = 0;
+        let index = 0;
         self.status = buf[index] as i32;
-        index += 1;
-        self.auth_data = read_none_terminated_bytes(&buf);
+        self.auth_data = read_none_terminated_bytes(&buf[index + 1..]);
     }
 
     fn to_bytes(&mut self) -> Box<[u8]> {
@@ -210,20 +302,29 @@ impl<'a, 'b: 'a> Packet<'b> for AuthSwitchRequestMoreData<'a> {
     }
 }
 
-struct AuthSwitchRequestPacket<'a> {
+#[derive(Default)]
+pub struct AuthSwitchRequestPacket<'a> {
     command: u8,
     auth_name: &'a str,
     auth_data: &'a [u8],
 }
 
 impl<'a> AuthSwitchRequestPacket<'a> {
-    fn set_command(&mut self, command: u8) {
+    pub fn set_command(&mut self, command: u8) {
         self.command = command;
     }
 
-    fn get_command(&self) -> u8 {
+    pub fn get_command(&self) -> u8 {
         self.command
     }
+
+    pub fn auth_name(&self) -> &'a str {
+        self.auth_name
+    }
+
+    pub fn auth_data(&self) -> &'a [u8] {
+        self.auth_data
+    }
 }
 
 impl<'a, 'b: 'a> Packet<'b> for AuthSwitchRequestPacket<'a> {
@@ -232,8 +333,8 @@ impl<'a, 'b: 'a> Packet<'b> for AuthSwitchRequestPacket<'a> {
         self.command = buf[index];
         index += 1;
         let auth_name = read_none_terminated_bytes(&buf[index..]);
-        self.auth_name = from_utf8(auth_name).unwrap();
-        index = auth_name.len() + 1;
+        self.auth_name = from_utf8(auth_name).unwrap_or_default();
+        index += auth_name.len() + 1;
 
         self.auth_data = read_none_terminated_bytes(&buf[index..])
     }
@@ -243,15 +344,15 @@ impl<'a, 'b: 'a> Packet<'b> for AuthSwitchRequestPacket<'a> {
     }
 }
 
-struct HeaderPacket {
+#[derive(Default)]
+pub struct HeaderPacket {
     packet_body_length: i32,
     packet_sequence_number: u8,
 }
 
 impl<'b> Packet<'b> for HeaderPacket {
-    #[allow(arithmetic_overflow)]
     fn from_bytes(&mut self, buf: &[u8]) {
-        self.packet_body_length = ((buf[0] & 0xFF) | ((buf[1] & 0xFF) << 8) | ((buf[2] & 0xFF) << 16)) as i32;
+        self.packet_body_length = read_unsigned_medium_little_endian_index(buf, 0) as i32;
         self.packet_sequence_number = buf[3];
     }
 
@@ -266,13 +367,47 @@ impl<'b> Packet<'b> for HeaderPacket {
 }
 
 impl HeaderPacket {
-    fn get_packet_sequence_number(&self) -> u8 {
+    pub fn new(packet_body_length: i32, packet_sequence_number: u8) -> HeaderPacket {
+        HeaderPacket { packet_body_length, packet_sequence_number }
+    }
+
+    pub fn packet_body_length(&self) -> i32 {
+        self.packet_body_length
+    }
+
+    pub fn get_packet_sequence_number(&self) -> u8 {
         self.packet_sequence_number
     }
 }
 
 
-struct EOFPacket {
+// 对应canal中的PacketWithHeaderPacket, 记录packet读取时的header信息
+pub trait PacketWithHeader {
+    fn header(&self) -> &HeaderPacket;
+    fn set_header(&mut self, header: HeaderPacket);
+}
+
+macro_rules! packet_with_header {
+    ($($packet:ident$(<$lt:lifetime>)?),*) => {
+        $(
+            impl$(<$lt>)? PacketWithHeader for $packet$(<$lt>)? {
+                fn header(&self) -> &HeaderPacket {
+                    &self.header
+                }
+
+                fn set_header(&mut self, header: HeaderPacket) {
+                    self.header = header;
+                }
+            }
+        )*
+    };
+}
+
+packet_with_header!(EOFPacket, ErrorPacket<'a>, FieldPacket<'a>, HandshakeInitializationPacket<'a>,
+    OKPacket<'a>, Reply323Packet<'a>, ResultSetHeaderPacket);
+
+#[derive(Default)]
+pub struct EOFPacket {
     header: HeaderPacket,
     field_count: u8,
     warning_count: u16,
@@ -291,7 +426,7 @@ impl<'a> Packet<'a> for EOFPacket {
 
     fn to_bytes(&mut self) -> Box<[u8]> {
         let mut index = 0;
-        let mut data = [0 as u8, 5];
+        let mut data = [0u8; 5];
         data[index] = self.field_count;
         index += 1;
         write_unsigned_short_little_endian(self.warning_count, index, &mut data[index..]);
@@ -301,7 +436,8 @@ impl<'a> Packet<'a> for EOFPacket {
     }
 }
 
-struct ErrorPacket<'a> {
+#[derive(Default)]
+pub struct ErrorPacket<'a> {
     header: HeaderPacket,
     field_count: u8,
     error_number: u16,
@@ -312,15 +448,18 @@ struct ErrorPacket<'a> {
 
 
 impl<'a> ErrorPacket<'a> {
-    fn new() -> ErrorPacket<'a> {
-        ErrorPacket {
-            header: HeaderPacket { packet_body_length: 0, packet_sequence_number: 0 },
-            field_count: 0,
-            error_number: 0,
-            sql_state_marker: 0,
-            sql_state: [0 as u8, 1].borrow(),
-            message: "",
-        }
+    pub fn new() -> ErrorPacket<'a> {
+        ErrorPacket::default()
+    }
+
+    pub fn error_number(&self) -> u16 {
+        self.error_number
+    }
+    pub fn sql_state(&self) -> &'a [u8] {
+        self.sql_state
+    }
+    pub fn message(&self) -> &'a str {
+        self.message
     }
 }
 
@@ -336,11 +475,7 @@ impl<'a, 'b: 'a> Packet<'b> for ErrorPacket<'a> {
         index += 1;
         self.sql_state = &buf[index..(index + 5)];
         index += 5;
-        let s = match from_utf8(&buf[index..]) {
-            Ok(v) => v,
-            Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-        };
-        self.message = s;
+        self.message = from_utf8(&buf[index..]).unwrap_or("Invalid UTF-8 sequence");
     }
 
     fn to_bytes(&mut self) -> Box<[u8]> {
@@ -348,7 +483,8 @@ impl<'a, 'b: 'a> Packet<'b> for ErrorPacket<'a> {
     }
 }
 
-struct FieldPacket<'a> {
+#[derive(Default)]
+pub struct FieldPacket<'a> {
     header: HeaderPacket,
     catalog: &'a str,
     db: &'a str,
@@ -369,8 +505,8 @@ impl<'a, 'b : 'a> Packet<'b> for FieldPacket<'a> {
     fn from_bytes(&mut self, buf: &'a [u8]) {
         let mut index = 0;
         let mut reader = LengthCodedStringReader::new(index);
-        self.catalog = reader.read_length_coded_string(&buf);
-        self.db = reader.read_length_coded_string(&buf);
+        self.catalog = reader.read_length_coded_string(buf);
+        self.db = reader.read_length_coded_string(buf);
         self.table = reader.read_length_coded_string(buf);
         self.original_table = reader.read_length_coded_string(buf);
         self.name = reader.read_length_coded_string(buf);
@@ -399,8 +535,27 @@ impl<'a, 'b : 'a> Packet<'b> for FieldPacket<'a> {
     }
 }
 
+impl<'a> FieldPacket<'a> {
+    pub fn new() -> FieldPacket<'a> {
+        FieldPacket::default()
+    }
+    pub fn db(&self) -> &'a str {
+        self.db
+    }
+    pub fn table(&self) -> &'a str {
+        self.table
+    }
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+    pub fn type_(&self) -> u8 {
+        self.type_
+    }
+}
+
 
-struct HandshakeInitializationPacket<'a> {
+#[derive(Default)]
+pub struct HandshakeInitializationPacket<'a> {
     header: HeaderPacket,
     protocol_version: u8,
     server_version: &'a str,
@@ -415,15 +570,14 @@ struct HandshakeInitializationPacket<'a> {
 
 
 impl<'a, 'b: 'a> Packet<'b> for HandshakeInitializationPacket<'a> {
-    #[allow(arithmetic_overflow)]
     fn from_bytes(&mut self, buf: &'b [u8]) {
         let mut index = 0;
         self.protocol_version = buf[index];
         index += 1;
-        let server_version_bytes = read_null_terminated_bytes(buf);
-        self.server_version = from_utf8(server_version_bytes).unwrap();
+        let server_version_bytes = read_null_terminated_bytes(&buf[index..]);
+        self.server_version = from_utf8(server_version_bytes).unwrap_or_default();
         index += server_version_bytes.len() + 1;
-        self.thread_id = read_unsigned_integer_little_endian(buf);
+        self.thread_id = read_unsigned_integer_little_endian(&buf[index..]);
         index += 4;
         self.seed = &buf[index..index + 8];
         index += 8;
@@ -436,7 +590,8 @@ impl<'a, 'b: 'a> Packet<'b> for HandshakeInitializationPacket<'a> {
             self.server_status = read_unsigned_short_little_endian(&buf[index..index + 2]);
             index += 2;
             let capability_flags2 = read_unsigned_short_little_endian(&buf[index..index + 2]);
-            let capabilities = ((capability_flags2 << 16) | self.server_capabilities) as i32;
+            index += 2;
+            let capabilities = ((capability_flags2 as i32) << 16) | self.server_capabilities as i32;
             // int authPluginDataLen = -1;
             // if ((capabilities & Capability.CLIENT_PLUGIN_AUTH) != 0) {
             // authPluginDataLen = data[index];
@@ -455,8 +610,8 @@ impl<'a, 'b: 'a> Packet<'b> for HandshakeInitializationPacket<'a> {
             }
             index += 12 + 1;
 
-            if (capabilities & CLIENT_PLUGIN_AUTH) != 0 {
-                self.auth_plugin_name = read_null_terminated_bytes(buf).borrow()
+            if (capabilities & CLIENT_PLUGIN_AUTH) != 0 && index < buf.len() {
+                self.auth_plugin_name = read_null_terminated_bytes(&buf[index..])
             }
         }
     }
@@ -466,8 +621,39 @@ impl<'a, 'b: 'a> Packet<'b> for HandshakeInitializationPacket<'a> {
     }
 }
 
+impl<'a> HandshakeInitializationPacket<'a> {
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+    pub fn server_version(&self) -> &'a str {
+        self.server_version
+    }
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+    pub fn seed(&self) -> &'a [u8] {
+        self.seed
+    }
+    pub fn server_capabilities(&self) -> u16 {
+        self.server_capabilities
+    }
+    pub fn server_charset_number(&self) -> u8 {
+        self.server_charset_number
+    }
+    pub fn server_status(&self) -> u16 {
+        self.server_status
+    }
+    pub fn rest_of_scramble_buff(&self) -> &'a [u8] {
+        self.rest_of_scramble_buff
+    }
+    pub fn auth_plugin_name(&self) -> &'a [u8] {
+        self.auth_plugin_name
+    }
+}
 
-struct OKPacket<'a> {
+
+#[derive(Default)]
+pub struct OKPacket<'a> {
     header: HeaderPacket,
     field_count: u8,
     affected_rows: &'a [u8],
@@ -500,11 +686,15 @@ impl<'a, 'b: 'a> Packet<'b> for OKPacket<'a> {
         self.affected_rows = read_binary_coded_length_bytes(buf, index);
         index += self.affected_rows.len();
         self.insert_id = read_binary_coded_length_bytes(buf, index);
+        index += self.insert_id.len();
+        if index + 4 > buf.len() {
+            return;
+        }
         self.server_status = read_unsigned_short_little_endian(&buf[index..index + 2]);
         index += 2;
         self.warning_count = read_unsigned_short_little_endian(&buf[index..index + 2]);
         index += 2;
-        self.message = from_utf8(&buf[index..]).unwrap();
+        self.message = from_utf8(&buf[index..]).unwrap_or_default();
     }
 
     fn to_bytes(&mut self) -> Box<[u8]> {
@@ -512,32 +702,42 @@ impl<'a, 'b: 'a> Packet<'b> for OKPacket<'a> {
     }
 }
 
+impl<'a> OKPacket<'a> {
+    pub fn affected_rows(&self) -> i64 {
+        if self.affected_rows.is_empty() { 0 } else { read_length_coded_binary(self.affected_rows, 0) }
+    }
+    pub fn server_status(&self) -> u16 {
+        self.server_status
+    }
+    pub fn message(&self) -> &'a str {
+        self.message
+    }
+}
+
 
-struct Reply323Packet<'a> {
+#[derive(Default)]
+pub struct Reply323Packet<'a> {
     header: HeaderPacket,
     seed: &'a [u8],
 }
 
 
 impl<'a, 'b: 'a> Packet<'b> for Reply323Packet<'a> {
-    fn from_bytes(&mut self, buf: &'b [u8]) {
+    fn from_bytes(&mut self, _buf: &'b [u8]) {
         todo!()
     }
 
     fn to_bytes(&mut self) -> Box<[u8]> {
-        return if self.seed.len() == 0 {
-            Box::from([0 as u8])
+        if self.seed.is_empty() {
+            Box::from([0u8])
         } else {
-            let mut out = vec![];
-            for index in 0..self.seed.len() {
-                out.push(self.seed[index])
-            }
-            Box::from(out)
-        };
+            Box::from(self.seed.to_vec())
+        }
     }
 }
 
-struct ResultSetHeaderPacket {
+#[derive(Default)]
+pub struct ResultSetHeaderPacket {
     header: HeaderPacket,
     column_count: i64,
     extra: i64,
@@ -559,45 +759,50 @@ impl<'b> Packet<'b> for ResultSetHeaderPacket {
     }
 }
 
-struct ResultSetPacket<'a> {
-    socket_address: &'a str,
-    field_descriptors: Vec<FieldPacket<'a>>,
-    field_values: Vec<&'a str>,
+#[derive(Default)]
+pub struct ResultSetPacket {
+    socket_address: String,
+    field_descriptors: Vec<String>,
+    field_values: Vec<String>,
 }
 
-impl<'a> ResultSetPacket<'a> {
-    fn new() -> ResultSetPacket<'a> {
-        ResultSetPacket {
-            socket_address: "",
-            field_descriptors: vec![],
-            field_values: vec![],
-        }
+impl ResultSetPacket {
+    pub fn new() -> ResultSetPacket {
+        ResultSetPacket::default()
     }
 
 
-    pub fn socket_address(&self) -> &'a str {
-        self.socket_address
+    pub fn socket_address(&self) -> &str {
+        &self.socket_address
     }
-    pub fn field_descriptors(&self) -> &Vec<FieldPacket<'a>> {
+    // 返回结果集的列名
+    pub fn field_descriptors(&self) -> &Vec<String> {
         &self.field_descriptors
     }
-    pub fn field_values(&self) -> &Vec<&'a str> {
+    // 所有行按顺序平铺的列值
+    pub fn field_values(&self) -> &Vec<String> {
         &self.field_values
     }
 
-    pub fn set_socket_address(&mut self, socket_address: &'a str) {
+    pub fn set_socket_address(&mut self, socket_address: String) {
         self.socket_address = socket_address;
     }
-    pub fn set_field_descriptors(&mut self, field_descriptors: Vec<FieldPacket<'a>>) {
+    pub fn set_field_descriptors(&mut self, field_descriptors: Vec<String>) {
         self.field_descriptors = field_descriptors;
     }
-    pub fn set_field_values(&mut self, field_values: Vec<&'a str>) {
+    pub fn set_field_values(&mut self, field_values: Vec<String>) {
         self.field_values = field_values;
     }
+
+    // 按行访问结果集
+    pub fn rows(&self) -> impl Iterator<Item=&[String]> {
+        self.field_values.chunks(self.field_descriptors.len().max(1))
+    }
 }
 
 
-struct RowDataPacket<'a> {
+#[derive(Default)]
+pub struct RowDataPacket<'a> {
     header: HeaderPacket,
     columns: Vec<&'a str>,
 }
@@ -638,142 +843,245 @@ impl <'a>RowDataPacket<'a> {
         self.columns = columns;
     }
     pub fn new() -> Self {
-        Self { header: HeaderPacket{ packet_body_length: 0, packet_sequence_number: 0 }, columns: vec![] }
+        Self::default()
     }
 }
 
-struct LengthCodedStringReader<'a> {
+/**
+ * <pre>
+ *  VERSION 4.1
+ *  Bytes                       Name
+ *  -----                       ----
+ *  4                           client_flags
+ *  4                           max_packet_size
+ *  1                           charset_number
+ *  23                          (filler) always 0x00...
+ *  n (Null-Terminated String)  user
+ *  n (Length Coded Binary)     scramble_buff (1 + x bytes)
+ *  n (Null-Terminated String)  databasename (optional)
+ *  n (Null-Terminated String)  auth_plugin_name (optional)
+ * </pre>
+ */
+#[derive(Default)]
+pub struct ClientAuthenticationPacket<'a> {
+    client_capability: i32,
+    charset_number: u8,
+    username: &'a str,
+    scrumble_password: &'a [u8],
+    database_name: &'a str,
+    auth_plugin_name: &'a str,
+}
+
+impl<'a> ClientAuthenticationPacket<'a> {
+    pub fn new(username: &'a str, scrumble_password: &'a [u8], database_name: &'a str, auth_plugin_name: &'a str) -> ClientAuthenticationPacket<'a> {
+        let mut client_capability = CLIENT_LONG_PASSWORD | CLIENT_LONG_FLAG | CLIENT_PROTOCOL_41
+            | CLIENT_INTERACTIVE | CLIENT_TRANSACTIONS | CLIENT_SECURE_CONNECTION
+            | CLIENT_MULTI_STATEMENTS | CLIENT_PLUGIN_AUTH;
+        if !database_name.is_empty() {
+            client_capability |= CLIENT_CONNECT_WITH_DB;
+        }
+        ClientAuthenticationPacket {
+            client_capability,
+            charset_number: DEFAULT_CHARSET_NUMBER,
+            username,
+            scrumble_password,
+            database_name,
+            auth_plugin_name,
+        }
+    }
+
+    pub fn client_capability(&self) -> i32 {
+        self.client_capability
+    }
+
+    pub fn set_client_capability(&mut self, client_capability: i32) {
+        self.client_capability = client_capability;
+    }
+
+    pub fn username(&self) -> &str {
+        self.username
+    }
+
+    pub fn scrumble_password(&self) -> &[u8] {
+        self.scrumble_password
+    }
+
+    pub fn database_name(&self) -> &str {
+        self.database_name
+    }
+
+    pub fn auth_plugin_name(&self) -> &str {
+        self.auth_plugin_name
+    }
+
+    // SSLRequest: 与认证包前32字节相同并带上CLIENT_SSL, 之后开始tls握手
+    pub fn ssl_request(&self) -> Box<[u8]> {
+        let mut out = vec![];
+        self.write_capabilities(&mut out, self.client_capability | CLIENT_SSL);
+        Box::from(out)
+    }
+
+    fn write_capabilities(&self, out: &mut Vec<u8>, client_capability: i32) {
+        out.extend_from_slice(&client_capability.to_le_bytes());
+        out.extend_from_slice(&(MAX_PACKET_LENGTH as u32).to_le_bytes());
+        out.push(self.charset_number);
+        out.extend_from_slice(&[0u8; 23]);
+    }
+}
+
+impl<'a, 'b: 'a> Packet<'b> for ClientAuthenticationPacket<'a> {
+    // 截断的包只解析到已有的字段为止
+    fn from_bytes(&mut self, buf: &'b [u8]) {
+        if buf.len() < 32 {
+            return;
+        }
+        self.client_capability = i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        self.charset_number = buf[8];
+        let mut index = 32;
+        let username = read_null_terminated_bytes(&buf[index..]);
+        self.username = from_utf8(username).unwrap_or_default();
+        index += username.len() + 1;
+        if index >= buf.len() {
+            return;
+        }
+        let length = buf[index] as usize;
+        index += 1;
+        let end = (index + length).min(buf.len());
+        self.scrumble_password = &buf[index..end];
+        index = end;
+        if (self.client_capability & CLIENT_CONNECT_WITH_DB) != 0 && index < buf.len() {
+            let database_name = read_null_terminated_bytes(&buf[index..]);
+            self.database_name = from_utf8(database_name).unwrap_or_default();
+            index += database_name.len() + 1;
+        }
+        if (self.client_capability & CLIENT_PLUGIN_AUTH) != 0 && index < buf.len() {
+            self.auth_plugin_name = from_utf8(read_null_terminated_bytes(&buf[index..])).unwrap_or_default();
+        }
+    }
+
+    fn to_bytes(&mut self) -> Box<[u8]> {
+        let mut out = vec![];
+        self.write_capabilities(&mut out, self.client_capability);
+        out.extend_from_slice(self.username.as_bytes());
+        out.push(NULL_TERMINATED_STRING_DELIMITER);
+        out.push(self.scrumble_password.len() as u8);
+        out.extend_from_slice(self.scrumble_password);
+        if !self.database_name.is_empty() {
+            out.extend_from_slice(self.database_name.as_bytes());
+            out.push(NULL_TERMINATED_STRING_DELIMITER);
+        }
+        if !self.auth_plugin_name.is_empty() {
+            out.extend_from_slice(self.auth_plugin_name.as_bytes());
+            out.push(NULL_TERMINATED_STRING_DELIMITER);
+        }
+        Box::from(out)
+    }
+}
+
+pub struct LengthCodedStringReader<'a> {
     encoding: &'a str,
     index: usize,
 }
 
 
 impl<'a> LengthCodedStringReader<'a> {
-    fn new(index: usize) -> LengthCodedStringReader<'a> {
+    pub fn new(index: usize) -> LengthCodedStringReader<'a> {
         LengthCodedStringReader {
             encoding: "",
             index,
         }
     }
 
-    fn read_length_coded_string<'b>(&mut self, buf: &'b [u8]) -> &'b str {
+    pub fn read_length_coded_string<'b>(&mut self, buf: &'b [u8]) -> &'b str {
         let bytes = read_binary_coded_length_bytes(buf, self.index);
         let length = read_length_coded_binary(buf, self.index);
         self.set_index(self.index + bytes.len());
         if length == NULL_LENGTH as i64 {
             return "";
         }
-        from_utf8(&buf[self.index..(self.index + length as usize)]).unwrap()
+        let start = self.index;
+        self.set_index(start + length as usize);
+        from_utf8(&buf[start..self.index]).unwrap()
     }
 
     pub fn index(&self) -> usize {
         self.index
     }
 
+    pub fn encoding(&self) -> &'a str {
+        self.encoding
+    }
+
     pub fn set_index(&mut self, index: usize) {
         self.index = index;
     }
 }
 
 
-const NULL_LENGTH: i32 = -1;
+pub const NULL_LENGTH: i32 = -1;
 
-fn read_none_terminated_bytes(buf: &[u8]) -> &[u8] {
+pub fn read_none_terminated_bytes(buf: &[u8]) -> &[u8] {
     for (i, b) in buf.iter().enumerate() {
-        if *b as i32 == NULL_TERMINATED_STRING_DELIMITER as i32 {
+        if *b == NULL_TERMINATED_STRING_DELIMITER {
             return &buf[0..i];
         }
     }
-    &buf[..]
+    buf
 }
 
-#[allow(arithmetic_overflow)]
-fn read_unsigned_short_little_endian(buf: &[u8]) -> u16 {
-    ((buf[0] & 0xFF) | ((buf[1] & 0xFF) << 8)) as u16
+pub fn read_unsigned_short_little_endian(buf: &[u8]) -> u16 {
+    read_unsigned_short_little_endian_index(buf, 0)
 }
 
-#[allow(arithmetic_overflow)]
-fn read_unsigned_short_little_endian_index(buf: &[u8], index: usize) -> u16 {
-    ((buf[index] & 0xFF) | ((buf[index + 1] & 0xFF) << 8)) as u16
+pub fn read_unsigned_short_little_endian_index(buf: &[u8], index: usize) -> u16 {
+    buf[index] as u16 | (buf[index + 1] as u16) << 8
 }
 
-#[allow(arithmetic_overflow)]
-fn read_unsigned_integer_little_endian(buf: &[u8]) -> u32 {
-    ((buf[0] & 0xFF) as u32 | ((buf[1] & 0xFF) << 8) as u32
-        | ((buf[2] & 0xFF) << 16) as u32 | ((buf[3] & 0xFF) << 24) as u32)
+pub fn read_unsigned_integer_little_endian(buf: &[u8]) -> u32 {
+    buf[0] as u32 | (buf[1] as u32) << 8 | (buf[2] as u32) << 16 | (buf[3] as u32) << 24
 }
 
-#[allow(arithmetic_overflow)]
-fn read_unsigned_medium_little_endian_index(buf: &[u8], index: usize) -> u32 {
-    ((buf[index] & 0xFF) | ((buf[index + 1] & 0xFF) << 8) | ((buf[index + 2] & 0xFF) << 16)) as u32
+pub fn read_unsigned_medium_little_endian_index(buf: &[u8], index: usize) -> u32 {
+    buf[index] as u32 | (buf[index + 1] as u32) << 8 | (buf[index + 2] as u32) << 16
 }
 
-#[allow(arithmetic_overflow)]
-fn read_unsigned_long_little_endian_index(buf: &[u8], index: usize) -> u64 {
+pub fn read_unsigned_long_little_endian_index(buf: &[u8], index: usize) -> u64 {
     let mut accumulation = 0;
-    let mut shift_by = 0;
-    for index in index..index + 8 {
-        accumulation |= ((buf[index] as u64 & 0xff) << shift_by);
-        shift_by += 8;
+    for (shift_by, b) in buf[index..index + 8].iter().enumerate() {
+        accumulation |= (*b as u64) << (shift_by * 8);
     }
-    accumulation as u64
+    accumulation
 }
 
-fn write_unsigned_short_little_endian(data: u16, mut start: usize, buf: &mut [u8]) {
+pub fn write_unsigned_short_little_endian(data: u16, mut start: usize, buf: &mut [u8]) {
     buf[start] = (data & 0xFF) as u8;
     start += 1;
     buf[start] = ((data >> 8) & 0xFF) as u8;
 }
 
-fn read_binary_coded_length_bytes(buf: &[u8], mut index: usize) -> &[u8] {
-    let mark = buf[index] & 0xFF;
-    return match mark {
-        251 => {
-            &buf[index..index]
-        }
-        252 => {
-            &buf[index..index + 2]
-        }
-        253 => {
-            &buf[index..index + 3]
-        }
-        254 => {
-            &buf[index..index + 8]
-        }
-        _ => {
-            &[]
-        }
-    };
+// 返回length coded binary的完整编码(包含首字节标记)
+pub fn read_binary_coded_length_bytes(buf: &[u8], index: usize) -> &[u8] {
+    let mark = buf[index];
+    match mark {
+        252 => &buf[index..index + 3],
+        253 => &buf[index..index + 4],
+        254 => &buf[index..index + 9],
+        _ => &buf[index..index + 1],
+    }
 }
 
-fn read_length_coded_binary(buf: &[u8], mut index: usize) -> i64 {
-    let mark = buf[index] & 0xFF;
+pub fn read_length_coded_binary(buf: &[u8], mut index: usize) -> i64 {
+    let mark = buf[index];
     index += 1;
     match mark {
-        251 => {
-            return NULL_LENGTH as i64;
-        }
-        252 => {
-            return read_unsigned_short_little_endian_index(buf, index) as i64;
-        }
-        253 => {
-            return read_unsigned_medium_little_endian_index(buf, index) as i64;
-        }
-        254 => {
-            return read_unsigned_long_little_endian_index(buf, index) as i64;
-        }
-        _ => {}
+        251 => NULL_LENGTH as i64,
+        252 => read_unsigned_short_little_endian_index(buf, index) as i64,
+        253 => read_unsigned_medium_little_endian_index(buf, index) as i64,
+        254 => read_unsigned_long_little_endian_index(buf, index) as i64,
+        _ => mark as i64,
     }
-    return mark as i64;
 }
 
-fn read_null_terminated_bytes(buf: &[u8]) -> &[u8] {
-    let i = 0;
-    for b in buf {
-        let item = *b;
-        if item == NULL_TERMINATED_STRING_DELIMITER {
-            break;
-        }
-    }
-    &buf[0..i]
-}
\ No newline at end of file
+pub fn read_null_terminated_bytes(buf: &[u8]) -> &[u8] {
+    read_none_terminated_bytes(buf)
+}
@@ -0,0 +1,50 @@
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+pub const MYSQL_NATIVE_PASSWORD: &str = "mysql_native_password";
+pub const CACHING_SHA2_PASSWORD: &str = "caching_sha2_password";
+
+/**
+ * <pre>
+ *  mysql_native_password:
+ *  SHA1( password ) XOR SHA1( "20-bytes random data from server" <concat> SHA1( SHA1( password ) ) )
+ * </pre>
+ */
+pub fn scramble411(password: &[u8], seed: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return vec![];
+    }
+    let pass1 = Sha1::digest(password);
+    let pass2 = Sha1::digest(pass1);
+    let mut hasher = Sha1::new();
+    hasher.update(seed);
+    hasher.update(pass2);
+    let pass3 = hasher.finalize();
+    pass1.iter().zip(pass3.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+/**
+ * <pre>
+ *  caching_sha2_password:
+ *  XOR( SHA256( password ), SHA256( SHA256( SHA256( password ) ), seed ) )
+ * </pre>
+ */
+pub fn scramble_caching_sha2(password: &[u8], seed: &[u8]) -> Vec<u8> {
+    if password.is_empty() {
+        return vec![];
+    }
+    let pass1 = Sha256::digest(password);
+    let pass2 = Sha256::digest(pass1);
+    let mut hasher = Sha256::new();
+    hasher.update(pass2);
+    hasher.update(seed);
+    let pass3 = hasher.finalize();
+    pass1.iter().zip(pass3.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+pub fn scramble(auth_plugin_name: &str, password: &[u8], seed: &[u8]) -> Vec<u8> {
+    match auth_plugin_name {
+        CACHING_SHA2_PASSWORD => scramble_caching_sha2(password, seed),
+        _ => scramble411(password, seed),
+    }
+}
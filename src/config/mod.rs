@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// 配置项, 与canal的instance.properties一样使用 key=value 格式
+pub type Properties = BTreeMap<String, String>;
+
+// 修改后需要重启才能生效的配置, 热加载时会被拒绝并保留原值
+pub const RESTART_REQUIRED_KEYS: [&str; 9] = [
+    "master.address",
+    "master.port",
+    "master.username",
+    "master.password",
+    "master.default_database",
+    "master.ssl.mode",
+    "master.ssl.ca",
+    "slave.id",
+    "relay.directory",
+];
+
+// 审计日志中不输出原值的配置
+const SECRET_KEY_SUFFIX: &str = "password";
+
+pub fn requires_restart(key: &str) -> bool {
+    RESTART_REQUIRED_KEYS.contains(&key)
+}
+
+/**
+ * <pre>
+ *  解析properties格式的配置:
+ *      # 注释
+ *      master.address = 127.0.0.1
+ *      destination.example.filter = canal\\..*
+ *  key和value两端的空白会被去掉, 空行和以#/!开头的行被忽略
+ * </pre>
+ */
+pub fn parse_properties(text: &str) -> Result<Properties, String> {
+    let mut properties = Properties::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let (key, value) = line.split_once('=')
+            .ok_or_else(|| format!("line {}: expect key=value, got {}", i + 1, line))?;
+        properties.insert(key.trim().to_string(), value.trim().to_string());
+    }
+    Ok(properties)
+}
+
+/**
+ * <pre>
+ *  解析带单位的时间, 写法与humantime一致, 多个部分可以连写:
+ *      100ms, 30s, 5m, 1h30m, 2d, 1.5s
+ *  单位: ns/us/ms/s/m/h/d, 除了0之外必须带单位, 避免毫秒和秒混淆
+ * </pre>
+ */
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let text = value.trim();
+    if text == "0" {
+        return Ok(Duration::ZERO);
+    }
+    let invalid = || format!("invalid duration {}, expect a number with unit, e.g. 500ms, 30s, 5m, 1h30m", value);
+    if text.is_empty() {
+        return Err(invalid());
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let unit_len = rest[number_len..].find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len() - number_len);
+        let number: f64 = rest[..number_len].parse().map_err(|_| invalid())?;
+        let unit = rest[number_len..number_len + unit_len].trim();
+        let seconds = match unit {
+            "ns" => 1e-9,
+            "us" => 1e-6,
+            "ms" => 1e-3,
+            "s" | "sec" => 1.0,
+            "m" | "min" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            _ => return Err(invalid()),
+        };
+        total += Duration::try_from_secs_f64(number * seconds).map_err(|_| invalid())?;
+        rest = rest[number_len + unit_len..].trim_start();
+    }
+    Ok(total)
+}
+
+// parse_duration的逆过程, 例如1h30m, 500ms, 用于日志和配置导出
+pub fn format_duration(duration: Duration) -> String {
+    if duration.is_zero() {
+        return "0s".to_string();
+    }
+    let mut out = String::new();
+    let mut seconds = duration.as_secs();
+    for (unit, size) in [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if seconds >= size {
+            out.push_str(&format!("{}{}", seconds / size, unit));
+            seconds %= size;
+        }
+    }
+    let nanos = duration.subsec_nanos();
+    if nanos == 0 {
+        return out;
+    }
+    if nanos.is_multiple_of(1_000_000) {
+        out.push_str(&format!("{}ms", nanos / 1_000_000));
+    } else if nanos.is_multiple_of(1_000) {
+        out.push_str(&format!("{}us", nanos / 1_000));
+    } else {
+        out.push_str(&format!("{}ns", nanos));
+    }
+    out
+}
+
+// 没有配置时返回None
+pub fn get_duration(properties: &Properties, key: &str) -> Result<Option<Duration>, String> {
+    properties.get(key)
+        .map(|value| parse_duration(value).map_err(|e| format!("{}: {}", key, e)))
+        .transpose()
+}
+
+pub fn load_properties(path: &Path) -> Result<Properties, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("read config {} failure: {}", path.display(), e))?;
+    parse_properties(&text)
+}
+
+// 一次热加载的结果, rejected中的配置没有生效
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigChange {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<String>,
+    rejected: Vec<String>,
+    old: Properties,
+    new: Properties,
+}
+
+impl ConfigChange {
+    pub fn diff(old: &Properties, new: &Properties) -> ConfigChange {
+        let mut change = ConfigChange { old: old.clone(), new: new.clone(), ..ConfigChange::default() };
+        for (key, value) in new {
+            match old.get(key) {
+                None => change.added.push(key.clone()),
+                Some(old_value) if old_value != value => change.changed.push(key.clone()),
+                _ => {}
+            }
+        }
+        change.removed = old.keys().filter(|key| !new.contains_key(*key)).cloned().collect();
+
+        // 需要重启的配置保持原值
+        for key in change.keys().filter(|key| requires_restart(key)).cloned().collect::<Vec<String>>() {
+            match old.get(&key) {
+                Some(value) => change.new.insert(key.clone(), value.clone()),
+                None => change.new.remove(&key),
+            };
+            change.rejected.push(key);
+        }
+        change.added.retain(|key| !requires_restart(key));
+        change.removed.retain(|key| !requires_restart(key));
+        change.changed.retain(|key| !requires_restart(key));
+        change
+    }
+
+    pub fn added(&self) -> &Vec<String> {
+        &self.added
+    }
+    pub fn removed(&self) -> &Vec<String> {
+        &self.removed
+    }
+    pub fn changed(&self) -> &Vec<String> {
+        &self.changed
+    }
+    pub fn rejected(&self) -> &Vec<String> {
+        &self.rejected
+    }
+    // 生效之后的完整配置
+    pub fn properties(&self) -> &Properties {
+        &self.new
+    }
+    // 加载之前的完整配置, 应用失败时用于回滚
+    pub fn old_properties(&self) -> &Properties {
+        &self.old
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.rejected.is_empty()
+    }
+
+    // 生效的配置项中是否有以prefix开头的key
+    pub fn touches(&self, prefix: &str) -> bool {
+        self.keys().any(|key| key.starts_with(prefix))
+    }
+
+    // key本身是否被添加, 修改或者删除
+    pub fn touches_key(&self, key: &str) -> bool {
+        self.keys().any(|changed| changed == key)
+    }
+
+    // 审计日志, 例如: changed destination.a.filter: test\..* -> orders\..*; rejected master.address (requires restart)
+    pub fn describe(&self) -> String {
+        let mut items = vec![];
+        for key in &self.added {
+            items.push(format!("added {}={}", key, self.display(&self.new, key)));
+        }
+        for key in &self.removed {
+            items.push(format!("removed {}", key));
+        }
+        for key in &self.changed {
+            items.push(format!("changed {}: {} -> {}", key, self.display(&self.old, key), self.display(&self.new, key)));
+        }
+        for key in &self.rejected {
+            items.push(format!("rejected {} (requires restart)", key));
+        }
+        if items.is_empty() {
+            return "no changes".to_string();
+        }
+        items.join("; ")
+    }
+
+    fn keys(&self) -> impl Iterator<Item=&String> {
+        self.added.iter().chain(self.removed.iter()).chain(self.changed.iter())
+    }
+
+    fn display(&self, properties: &Properties, key: &str) -> String {
+        if key.ends_with(SECRET_KEY_SUFFIX) {
+            return "******".to_string();
+        }
+        properties.get(key).cloned().unwrap_or_default()
+    }
+}
+
+/**
+ * <pre>
+ *  配置文件热加载:
+ *  poll()      文件修改时间变化时重新加载, 由调用方定期调用
+ *  reload()    立即重新加载, 对应管理接口或SIGHUP
+ *  每次加载都会输出一条审计日志, 需要重启的配置(RESTART_REQUIRED_KEYS)被拒绝并保留原值,
+ *  其它配置由调用方根据ConfigChange应用:
+ *      DestinationDispatcher::reload_filters   destination的filter和列投影
+ *      DestinationDispatcher::reload_sinks     destination的sink配置, 例如批次大小和logger的verbose
+ *      EntryDelivery::reload_quotas            destination的限流配额
+ *      MysqlEventParser::reload_handle         parser的追赶限速(master.catch_up.*)和解码跟踪(master.decode_trace.*)
+ * </pre>
+ */
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    current: Properties,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Result<ConfigWatcher, String> {
+        let current = load_properties(path)?;
+        Ok(ConfigWatcher { path: path.to_path_buf(), modified: modified_time(path), current })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn current(&self) -> &Properties {
+        &self.current
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.current.get(key).map(|value| value.as_str())
+    }
+
+    // 文件没有变化时返回None
+    pub fn poll(&mut self) -> Result<Option<ConfigChange>, String> {
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return Ok(None);
+        }
+        self.reload().map(Some)
+    }
+
+    // 加载失败时保留当前配置
+    pub fn reload(&mut self) -> Result<ConfigChange, String> {
+        self.modified = modified_time(&self.path);
+        let loaded = load_properties(&self.path)
+            .inspect_err(|e| println!("config reload {} failure, keep current config: {}", self.path.display(), e))?;
+        let change = ConfigChange::diff(&self.current, &loaded);
+        println!("config reload {}: {}", self.path.display(), change.describe());
+        self.current = change.properties().clone();
+        Ok(change)
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
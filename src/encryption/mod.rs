@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+use crate::command::hex::decode_hex;
+use crate::config::Properties;
+
+// 加密文件的magic number, 最后一个字节为格式版本
+pub const ENCRYPTED_FILE_MAGIC: [u8; 4] = [b'M', b'C', b'E', 1];
+
+// magic + key id + nonce prefix
+pub const ENCRYPTED_HEADER_LEN: usize = 4 + 4 + NONCE_PREFIX_LEN;
+
+pub const KEY_LEN: usize = 32;
+
+// AES-GCM的tag长度, 每条记录的密文比明文多TAG_LEN字节
+pub const TAG_LEN: usize = 16;
+
+const NONCE_PREFIX_LEN: usize = 8;
+
+// encryption.key.<id>=<64个十六进制字符>, encryption.key.current=<id>
+pub const KEY_PREFIX: &str = "encryption.key.";
+pub const CURRENT_KEY: &str = "encryption.key.current";
+
+/**
+ * <pre>
+ *  加密relay/archive文件使用的密钥, 由使用方提供(KMS, vault, 本地配置等).
+ *  新文件使用current_key(), 文件头中记录key id, 读取或者续写时按key id取回对应的密钥,
+ *  因此轮换密钥时只需要增加新的key并切换current, 旧的key在对应的文件清理之前需要保留
+ * </pre>
+ */
+pub trait KeyProvider: Send + Sync {
+    fn current_key(&self) -> Result<(u32, [u8; KEY_LEN]), String>;
+
+    fn key(&self, key_id: u32) -> Result<[u8; KEY_LEN], String>;
+}
+
+// 内存中的密钥, 可以从配置中加载
+#[derive(Clone, Default)]
+pub struct StaticKeyProvider {
+    keys: BTreeMap<u32, [u8; KEY_LEN]>,
+    current: Option<u32>,
+}
+
+impl StaticKeyProvider {
+    pub fn new(key_id: u32, key: [u8; KEY_LEN]) -> StaticKeyProvider {
+        let mut provider = StaticKeyProvider::default();
+        provider.add_key(key_id, key);
+        provider.current = Some(key_id);
+        provider
+    }
+
+    // 没有设置encryption.key.current时使用id最大的key
+    pub fn from_properties(properties: &Properties) -> Result<StaticKeyProvider, String> {
+        let mut provider = StaticKeyProvider::default();
+        for (name, value) in properties.range(KEY_PREFIX.to_string()..) {
+            let id = match name.strip_prefix(KEY_PREFIX) {
+                Some(id) => id,
+                None => break,
+            };
+            if name == CURRENT_KEY {
+                continue;
+            }
+            let key_id = id.parse().map_err(|_| format!("invalid key id in {}", name))?;
+            provider.add_key(key_id, parse_key(value).map_err(|e| format!("{}: {}", name, e))?);
+        }
+        provider.current = match properties.get(CURRENT_KEY) {
+            Some(id) => Some(id.parse().map_err(|_| format!("invalid {}: {}", CURRENT_KEY, id))?),
+            None => provider.keys.keys().last().copied(),
+        };
+        Ok(provider)
+    }
+
+    pub fn add_key(&mut self, key_id: u32, key: [u8; KEY_LEN]) {
+        self.keys.insert(key_id, key);
+    }
+
+    pub fn set_current(&mut self, key_id: u32) {
+        self.current = Some(key_id);
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn current_key(&self) -> Result<(u32, [u8; KEY_LEN]), String> {
+        let key_id = self.current.ok_or_else(|| "no encryption key is configured".to_string())?;
+        Ok((key_id, self.key(key_id)?))
+    }
+
+    fn key(&self, key_id: u32) -> Result<[u8; KEY_LEN], String> {
+        self.keys.get(&key_id).copied().ok_or_else(|| format!("encryption key {} is not found", key_id))
+    }
+}
+
+// 64个十六进制字符
+pub fn parse_key(hex: &str) -> Result<[u8; KEY_LEN], String> {
+    let hex = hex.trim();
+    if hex.len() != KEY_LEN * 2 {
+        return Err(format!("expect {} hex characters", KEY_LEN * 2));
+    }
+    let mut key = [0u8; KEY_LEN];
+    key.copy_from_slice(&decode_hex(hex)?);
+    Ok(key)
+}
+
+/**
+ * <pre>
+ *  加密文件格式:
+ *      4               magic "MCE" + 版本
+ *      4               key id
+ *      8               nonce prefix, 每个文件随机生成
+ *      记录...
+ *  每条记录:
+ *      4               密文长度
+ *      n               AES-256-GCM密文 + 16字节tag
+ *  第i条记录的nonce为 nonce prefix + i(4字节大端), 记录被篡改或者调换顺序时解密失败.
+ *  明文按记录拼接即为原始文件的内容
+ * </pre>
+ */
+pub struct FileCipher {
+    key_id: u32,
+    cipher: Aes256Gcm,
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    counter: u32,
+}
+
+impl FileCipher {
+    // 新文件, 使用当前的key
+    pub fn create(provider: &dyn KeyProvider) -> Result<FileCipher, String> {
+        let (key_id, key) = provider.current_key()?;
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        Ok(FileCipher { key_id, cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)), nonce_prefix, counter: 0 })
+    }
+
+    // 已有的文件, 按文件头中的key id取回密钥
+    pub fn open(header: &[u8], provider: &dyn KeyProvider) -> Result<FileCipher, String> {
+        if header.len() < ENCRYPTED_HEADER_LEN || header[..4] != ENCRYPTED_FILE_MAGIC {
+            return Err("not an encrypted file or unsupported version".to_string());
+        }
+        let key_id = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let key = provider.key(key_id)?;
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&header[8..ENCRYPTED_HEADER_LEN]);
+        Ok(FileCipher { key_id, cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)), nonce_prefix, counter: 0 })
+    }
+
+    pub fn key_id(&self) -> u32 {
+        self.key_id
+    }
+
+    // 已经加密/解密的记录数, 续写文件时需要跳过已有的记录
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    pub fn set_counter(&mut self, counter: u32) {
+        self.counter = counter;
+    }
+
+    pub fn header(&self) -> Vec<u8> {
+        let mut header = ENCRYPTED_FILE_MAGIC.to_vec();
+        header.extend_from_slice(&self.key_id.to_le_bytes());
+        header.extend_from_slice(&self.nonce_prefix);
+        header
+    }
+
+    // 返回带长度前缀的记录
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = self.next_nonce();
+        let ciphertext = self.cipher.encrypt(&nonce, plaintext)
+            .map_err(|_| format!("encrypt record {} failure", self.counter))?;
+        let mut record = (ciphertext.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&ciphertext);
+        Ok(record)
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        let counter = self.counter;
+        let nonce = self.next_nonce();
+        self.cipher.decrypt(&nonce, ciphertext)
+            .map_err(|_| format!("decrypt record {} failure, the file is corrupted or the key is wrong", counter))
+    }
+
+    fn next_nonce(&mut self) -> Nonce<aes_gcm::aead::consts::U12> {
+        let mut nonce = [0u8; 12];
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter += 1;
+        *Nonce::from_slice(&nonce)
+    }
+}
+
+// 按记录读取加密文件
+pub struct EncryptedFileReader<R: Read> {
+    reader: R,
+    cipher: FileCipher,
+}
+
+impl<R: Read> EncryptedFileReader<R> {
+    pub fn new(mut reader: R, provider: &dyn KeyProvider) -> Result<EncryptedFileReader<R>, String> {
+        let mut header = [0u8; ENCRYPTED_HEADER_LEN];
+        reader.read_exact(&mut header).map_err(|e| format!("read encrypted file header failure: {}", e))?;
+        Ok(EncryptedFileReader { reader, cipher: FileCipher::open(&header, provider)? })
+    }
+
+    pub fn key_id(&self) -> u32 {
+        self.cipher.key_id()
+    }
+
+    pub fn cipher(&self) -> &FileCipher {
+        &self.cipher
+    }
+
+    pub fn into_cipher(self) -> FileCipher {
+        self.cipher
+    }
+
+    // 文件结束时返回None, 最后一条记录不完整(写入时进程退出)时同样返回None
+    pub fn next_record(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let mut len = [0u8; 4];
+        match read_full(&mut self.reader, &mut len)? {
+            4 => {}
+            _ => return Ok(None),
+        }
+        let mut ciphertext = vec![0u8; u32::from_le_bytes(len) as usize];
+        if read_full(&mut self.reader, &mut ciphertext)? < ciphertext.len() {
+            return Ok(None);
+        }
+        self.cipher.decrypt(&ciphertext).map(Some)
+    }
+}
+
+impl<R: Read + Seek> EncryptedFileReader<R> {
+    // 跳到第record条记录的开头, offset为该记录在文件中的偏移
+    pub fn seek(&mut self, offset: u64, record: u32) -> Result<(), String> {
+        self.reader.seek(SeekFrom::Start(offset)).map_err(|e| format!("seek encrypted file failure: {}", e))?;
+        self.cipher.set_counter(record);
+        Ok(())
+    }
+}
+
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && magic == ENCRYPTED_FILE_MAGIC
+}
+
+// 解密整个文件, 返回原始内容
+pub fn decrypt_file(path: &Path, provider: &dyn KeyProvider) -> Result<Vec<u8>, String> {
+    let file = File::open(path).map_err(|e| format!("open {} failure: {}", path.display(), e))?;
+    let mut reader = EncryptedFileReader::new(BufReader::new(file), provider)?;
+    let mut content = vec![];
+    while let Some(record) = reader.next_record()? {
+        content.extend_from_slice(&record);
+    }
+    Ok(content)
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, String> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        match reader.read(&mut buf[offset..]) {
+            Ok(0) => break,
+            Ok(size) => offset += size,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("read encrypted file failure: {}", e)),
+        }
+    }
+    Ok(offset)
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::ErrorKind;
+
+use crate::command::errno::{ServerErrno, ServerError};
+
+/**
+ * <pre>
+ *  crate统一的错误类型, 调用方可以按类型区分可以通过重连恢复的网络错误和binlog损坏等无法重试的错误:
+ *      Io          socket读写失败, 连接被关闭或者超时, 重新连接之后通常可以恢复
+ *      Protocol    master返回了ErrorPacket或者不符合协议的packet, 例如1236(binlog被purge)
+ *      Decode      event/packet的内容无法解码, 例如binlog损坏或者不支持的格式, 从同一个位点重试通常仍然失败
+ *      Auth        认证失败, 包括ssl协商和证书校验
+ *      State       配置错误, sink失败, parser被abort等调用方和下游的错误
+ *  Display只输出message, 与之前的String错误一致. 仍然返回String的函数可以直接用?传递CanalError,
+ *  反过来String转换为State, 用于还没有迁移的错误
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanalError {
+    Io(ErrorKind, String),
+    // master返回ErrorPacket时带有解析后的错误
+    Protocol(Option<ServerError>, String),
+    Decode(String),
+    Auth(String),
+    State(String),
+}
+
+impl CanalError {
+    pub fn io(e: &io::Error, message: &str) -> CanalError {
+        CanalError::Io(e.kind(), message.to_string())
+    }
+
+    pub fn protocol(message: &str) -> CanalError {
+        CanalError::Protocol(None, message.to_string())
+    }
+
+    pub fn server(error: ServerError, message: &str) -> CanalError {
+        CanalError::Protocol(Some(error), message.to_string())
+    }
+
+    pub fn decode(message: &str) -> CanalError {
+        CanalError::Decode(message.to_string())
+    }
+
+    pub fn auth(message: &str) -> CanalError {
+        CanalError::Auth(message.to_string())
+    }
+
+    pub fn state(message: &str) -> CanalError {
+        CanalError::State(message.to_string())
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CanalError::Io(..) => "io",
+            CanalError::Protocol(..) => "protocol",
+            CanalError::Decode(_) => "decode",
+            CanalError::Auth(_) => "auth",
+            CanalError::State(_) => "state",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            CanalError::Io(_, message)
+            | CanalError::Protocol(_, message)
+            | CanalError::Decode(message)
+            | CanalError::Auth(message)
+            | CanalError::State(message) => message,
+        }
+    }
+
+    pub fn io_kind(&self) -> Option<ErrorKind> {
+        match self {
+            CanalError::Io(kind, _) => Some(*kind),
+            _ => None,
+        }
+    }
+
+    pub fn server_error(&self) -> Option<&ServerError> {
+        match self {
+            CanalError::Protocol(error, _) => error.as_ref(),
+            _ => None,
+        }
+    }
+
+    // 网络错误和master返回的错误(被kill, 主从切换等)可以重新连接之后重试, 1236说明请求的binlog无法读取, 重试仍然失败
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            CanalError::Io(..) => true,
+            CanalError::Protocol(error, _) => {
+                !error.as_ref().is_some_and(|error| error.errno() == ServerErrno::MasterFatalReadingBinlog)
+            }
+            _ => false,
+        }
+    }
+
+    // 在message前面加上上下文, 类型不变
+    pub fn context(self, context: &str) -> CanalError {
+        match self {
+            CanalError::Io(kind, message) => CanalError::Io(kind, format!("{}: {}", context, message)),
+            CanalError::Protocol(error, message) => CanalError::Protocol(error, format!("{}: {}", context, message)),
+            CanalError::Decode(message) => CanalError::Decode(format!("{}: {}", context, message)),
+            CanalError::Auth(message) => CanalError::Auth(format!("{}: {}", context, message)),
+            CanalError::State(message) => CanalError::State(format!("{}: {}", context, message)),
+        }
+    }
+}
+
+impl Display for CanalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl Error for CanalError {}
+
+impl From<io::Error> for CanalError {
+    fn from(e: io::Error) -> CanalError {
+        CanalError::Io(e.kind(), e.to_string())
+    }
+}
+
+impl From<String> for CanalError {
+    fn from(message: String) -> CanalError {
+        CanalError::State(message)
+    }
+}
+
+impl From<CanalError> for String {
+    fn from(e: CanalError) -> String {
+        match e {
+            CanalError::Io(_, message)
+            | CanalError::Protocol(_, message)
+            | CanalError::Decode(message)
+            | CanalError::Auth(message)
+            | CanalError::State(message) => message,
+        }
+    }
+}
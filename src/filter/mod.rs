@@ -0,0 +1,73 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::protocol::{Entry, EntryType};
+
+pub mod projection;
+
+pub mod replication;
+
+pub use projection::ColumnProjection;
+pub use replication::ReplicationFilter;
+
+/**
+ * <pre>
+ *  对应canal中的CanalEventFilter, 决定一个entry是否需要投递.
+ *  只对RowData(DML/DDL)生效, 事务头尾以及heartbeat总是投递
+ * </pre>
+ */
+pub trait EventFilter: Send + Sync {
+    fn filter(&self, entry: &Entry) -> bool;
+}
+
+/**
+ * <pre>
+ *  对应canal中的AviaterRegexFilter, 按schema.table匹配:
+ *  pattern以逗号分隔, 每一项是一个忽略大小写的完整匹配正则, 例如
+ *      .*\\..*                 所有表
+ *      canal\\..*              canal库下的所有表
+ *      canal\\.canal.*         canal库下以canal开头的表
+ *      canal.test1,canal.test2 多个规则
+ *  pattern为空时匹配所有表
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct RegexFilter {
+    pattern: String,
+    regex: Option<Regex>,
+}
+
+impl RegexFilter {
+    pub fn new(pattern: &str) -> Result<RegexFilter, String> {
+        let parts: Vec<&str> = pattern.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()).collect();
+        if parts.is_empty() {
+            return Ok(RegexFilter { pattern: String::new(), regex: None });
+        }
+        let joined = parts.iter().map(|part| format!("(?:{})", part)).collect::<Vec<String>>().join("|");
+        let regex = RegexBuilder::new(&format!("^(?:{})$", joined))
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("invalid filter pattern {}: {}", pattern, e))?;
+        Ok(RegexFilter { pattern: parts.join(","), regex: Some(regex) })
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(name),
+            None => true,
+        }
+    }
+}
+
+impl EventFilter for RegexFilter {
+    fn filter(&self, entry: &Entry) -> bool {
+        if entry.entry_type() != EntryType::RowData {
+            return true;
+        }
+        let header = entry.header();
+        self.matches(&format!("{}.{}", header.schema_name(), header.table_name()))
+    }
+}
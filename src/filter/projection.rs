@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use crate::instance::describe::{TableDescription, TableSchemas};
+use crate::protocol::{Column, Entry, EntryType, RowData};
+
+/**
+ * <pre>
+ *  按表裁剪投递的列, 在序列化之前生效, 用于减小payload以及去掉敏感列:
+ *      orders.order: id, status, amount; orders.user: id, name
+ *  每一项是schema.table: 列名列表, 多项以分号分隔, 表名和列名都忽略大小写.
+ *  配置了投影的表只保留列出的列(包括主键, 主键没有列出时同样被去掉), 其它表以及DDL不受影响.
+ *  列保留原来的序号, 输出的image与binlog_row_image=MINIMAL时一样只有部分列
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ColumnProjection {
+    // schema.table(小写) -> 列名(小写)
+    tables: BTreeMap<String, Vec<String>>,
+}
+
+impl ColumnProjection {
+    pub fn new(config: &str) -> Result<ColumnProjection, String> {
+        let mut tables = BTreeMap::new();
+        for item in config.split(';').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+            let (table, columns) = item.split_once(':')
+                .ok_or_else(|| format!("invalid column projection {}, expect schema.table: column, ...", item))?;
+            let table = table.trim().to_ascii_lowercase();
+            if table.split('.').count() != 2 || table.split('.').any(|part| part.is_empty()) {
+                return Err(format!("invalid column projection {}, table must be schema.table", item));
+            }
+            let columns: Vec<String> = columns.split(',').map(|column| column.trim().to_ascii_lowercase())
+                .filter(|column| !column.is_empty()).collect();
+            if columns.is_empty() {
+                return Err(format!("column projection of {} is empty", table));
+            }
+            if tables.insert(table.clone(), columns).is_some() {
+                return Err(format!("column projection of {} is duplicated", table));
+            }
+        }
+        Ok(ColumnProjection { tables })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    // schema.table
+    pub fn tables(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    pub fn columns(&self, schema: &str, table: &str) -> Option<&Vec<String>> {
+        self.tables.get(&format!("{}.{}", schema, table).to_ascii_lowercase())
+    }
+
+    /**
+     * <pre>
+     *  返回裁剪之后的entry, 不需要裁剪(非DML或者表没有配置投影)时返回None,
+     *  调用方继续使用原来的entry, 避免拷贝
+     * </pre>
+     */
+    pub fn apply(&self, entry: &Entry) -> Option<Entry> {
+        if entry.entry_type() != EntryType::RowData {
+            return None;
+        }
+        let row_change = entry.row_change().filter(|row_change| !row_change.is_ddl())?;
+        let header = entry.header();
+        let columns = self.columns(header.schema_name(), header.table_name())?;
+        let keep = |image: &Vec<Column>| -> Vec<Column> {
+            image.iter().filter(|column| columns.iter().any(|name| name.eq_ignore_ascii_case(column.name())))
+                .cloned().collect()
+        };
+        let row_datas = row_change.row_datas().iter()
+            .map(|row_data| RowData::new(keep(row_data.before_columns()), keep(row_data.after_columns())))
+            .collect();
+        let mut row_change = row_change.clone();
+        row_change.set_row_datas(row_datas);
+        let mut projected = entry.clone();
+        projected.set_row_change(row_change);
+        Some(projected)
+    }
+
+    /**
+     * <pre>
+     *  按parser当前缓存的表结构检查列名, 返回所有不存在的列, 例如:
+     *      column projection of orders.order: unknown column amont
+     *  还没有收到table map的表, 以及列名由解析器生成(binlog_row_metadata不是FULL)的表不做检查
+     * </pre>
+     */
+    pub fn validate(&self, schemas: &TableSchemas) -> Result<(), String> {
+        let mut errors = vec![];
+        for (table, columns) in self.tables.iter() {
+            let description = match describe_ignore_case(schemas, table) {
+                Some(description) if !description.has_generated_names() => description,
+                _ => continue,
+            };
+            for column in columns.iter() {
+                if !description.columns().iter().any(|known| known.name().eq_ignore_ascii_case(column)) {
+                    errors.push(format!("column projection of {}: unknown column {}", table, column));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+// TableSchemas以binlog中的大小写为key
+fn describe_ignore_case(schemas: &TableSchemas, table: &str) -> Option<TableDescription> {
+    let name = schemas.names().into_iter().find(|name| name.eq_ignore_ascii_case(table))?;
+    let (schema_name, table_name) = name.split_once('.')?;
+    schemas.describe(schema_name, table_name)
+}
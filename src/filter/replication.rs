@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::Path;
+
+use crate::filter::EventFilter;
+use crate::protocol::{Entry, EntryType};
+
+// schema.table的LIKE模式, %匹配任意个字符, _匹配一个字符, \转义
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WildTable {
+    schema: Vec<char>,
+    table: Vec<char>,
+}
+
+impl WildTable {
+    fn matches(&self, schema: &str, table: &str) -> bool {
+        like(&self.schema, &schema.chars().collect::<Vec<char>>())
+            && like(&self.table, &table.chars().collect::<Vec<char>>())
+    }
+}
+
+/**
+ * <pre>
+ *  与mysql replica的复制过滤规则一致(row格式), 可以直接使用replica的my.cnf中的配置:
+ *      replicate-do-db=orders
+ *      replicate-ignore-table=orders.audit_log
+ *      replicate-wild-do-table=orders.order_%
+ *  库级别: 配置了do_db时只保留这些库(此时忽略ignore_db), 否则去掉ignore_db中的库.
+ *  表级别(通过库级别检查之后), 按顺序:
+ *      1. 没有任何表规则时保留
+ *      2. 命中do_table时保留, 命中ignore_table时去掉
+ *      3. 命中wild_do_table时保留, 命中wild_ignore_table时去掉
+ *      4. 都没有命中时, 配置了do_table或者wild_do_table则去掉, 否则保留
+ *  没有表名的DDL(例如create database)只检查库级别. 库名和表名区分大小写(lower_case_table_names=0),
+ *  每一行一个规则, '-'和'_'等价, 同一个选项可以出现多次, 其它选项, 注释和[mysqld]等section被忽略.
+ *  不支持replicate-rewrite-db, 配置时返回Err
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ReplicationFilter {
+    do_db: Vec<String>,
+    ignore_db: Vec<String>,
+    // (schema, table)
+    do_table: Vec<(String, String)>,
+    ignore_table: Vec<(String, String)>,
+    wild_do_table: Vec<WildTable>,
+    wild_ignore_table: Vec<WildTable>,
+}
+
+impl ReplicationFilter {
+    pub fn new(config: &str) -> Result<ReplicationFilter, String> {
+        let mut filter = ReplicationFilter::default();
+        for line in config.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') || line.starts_with('[') {
+                continue;
+            }
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim().replace('_', "-").to_ascii_lowercase(), unquote(value.trim())),
+                None => continue,
+            };
+            let name = name.trim_start_matches("--");
+            if !name.starts_with("replicate-") {
+                continue;
+            }
+            match name {
+                "replicate-do-db" => filter.do_db.push(value.to_string()),
+                "replicate-ignore-db" => filter.ignore_db.push(value.to_string()),
+                "replicate-do-table" => filter.do_table.push(table_name(value)?),
+                "replicate-ignore-table" => filter.ignore_table.push(table_name(value)?),
+                "replicate-wild-do-table" => filter.wild_do_table.push(wild_table(value)?),
+                "replicate-wild-ignore-table" => filter.wild_ignore_table.push(wild_table(value)?),
+                "replicate-rewrite-db" => return Err(format!("{} is not supported", line)),
+                // replicate-same-server-id等不是过滤规则
+                _ => continue,
+            }
+        }
+        Ok(filter)
+    }
+
+    // 例如replica的my.cnf
+    pub fn from_file(path: &Path) -> Result<ReplicationFilter, String> {
+        let config = fs::read_to_string(path).map_err(|e| format!("read {} failure: {}", path.display(), e))?;
+        ReplicationFilter::new(&config)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.do_db.is_empty() && self.ignore_db.is_empty() && !self.has_table_rules()
+    }
+
+    pub fn do_db(&self) -> &Vec<String> {
+        &self.do_db
+    }
+    pub fn ignore_db(&self) -> &Vec<String> {
+        &self.ignore_db
+    }
+
+    pub fn matches(&self, schema: &str, table: &str) -> bool {
+        if !self.do_db.is_empty() {
+            if !self.do_db.iter().any(|db| db == schema) {
+                return false;
+            }
+        } else if self.ignore_db.iter().any(|db| db == schema) {
+            return false;
+        }
+        if table.is_empty() || !self.has_table_rules() {
+            return true;
+        }
+        let exact = |tables: &Vec<(String, String)>| tables.iter().any(|(s, t)| s == schema && t == table);
+        if exact(&self.do_table) {
+            return true;
+        }
+        if exact(&self.ignore_table) {
+            return false;
+        }
+        if self.wild_do_table.iter().any(|wild| wild.matches(schema, table)) {
+            return true;
+        }
+        if self.wild_ignore_table.iter().any(|wild| wild.matches(schema, table)) {
+            return false;
+        }
+        self.do_table.is_empty() && self.wild_do_table.is_empty()
+    }
+
+    fn has_table_rules(&self) -> bool {
+        !self.do_table.is_empty() || !self.ignore_table.is_empty() || !self.wild_do_table.is_empty()
+            || !self.wild_ignore_table.is_empty()
+    }
+}
+
+impl EventFilter for ReplicationFilter {
+    fn filter(&self, entry: &Entry) -> bool {
+        if entry.entry_type() != EntryType::RowData {
+            return true;
+        }
+        let header = entry.header();
+        self.matches(header.schema_name(), header.table_name())
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"').and_then(|value| value.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')))
+        .unwrap_or(value)
+}
+
+fn table_name(value: &str) -> Result<(String, String), String> {
+    match value.split_once('.') {
+        Some((schema, table)) if !schema.is_empty() && !table.is_empty() => Ok((schema.to_string(), table.to_string())),
+        _ => Err(format!("invalid replicate table {}, expect db_name.tbl_name", value)),
+    }
+}
+
+fn wild_table(value: &str) -> Result<WildTable, String> {
+    let (schema, table) = table_name(value)?;
+    Ok(WildTable { schema: schema.chars().collect(), table: table.chars().collect() })
+}
+
+// sql LIKE
+fn like(pattern: &[char], value: &[char]) -> bool {
+    match pattern.first() {
+        None => value.is_empty(),
+        Some('%') => (0..=value.len()).any(|skip| like(&pattern[1..], &value[skip..])),
+        Some('_') => !value.is_empty() && like(&pattern[1..], &value[1..]),
+        Some('\\') if pattern.len() > 1 => value.first() == Some(&pattern[1]) && like(&pattern[2..], &value[1..]),
+        Some(c) => value.first() == Some(c) && like(&pattern[1..], &value[1..]),
+    }
+}
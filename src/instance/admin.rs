@@ -0,0 +1,230 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::instance::status::{Instance, STATUS_VERSION};
+use crate::metrics::memory;
+use crate::store::inspect::StoreSnapshot;
+use crate::sink::mq::flat_message::json_string;
+
+// 读取请求的超时, 避免半开的连接占住accept线程
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 在admin线程中调用, 返回store当前的快照, 不能修改store的状态. 持有store的锁被poison时返回None
+pub type StoreInspector = Box<dyn Fn() -> Option<StoreSnapshot> + Send>;
+
+/**
+ * <pre>
+ *  只读的http admin接口, 返回Instance::status_json:
+ *      GET /status             {"version":1,"instances":[<status_json>, ...]}
+ *      GET /status/<name>      单个instance的status_json, 不存在时返回404
+ *      GET /memory             {"version":1,"memory":<MemoryStats::to_json>}, 进程内存以及各子系统的gauge
+ *      GET /store/<name>       {"version":1,"name":..,"store":<StoreSnapshot::to_json>}, 不存在时返回404.
+ *                              只调用EntryStore::inspect, 不会移动游标, 也不会刷新消费方的活跃时间
+ *  每个请求处理完之后关闭连接(Connection: close), 请求在accept线程中串行处理
+ * </pre>
+ */
+pub struct AdminServer {
+    instances: Arc<Mutex<Vec<Instance>>>,
+    stores: Arc<Mutex<Vec<(String, StoreInspector)>>>,
+    running: Arc<AtomicBool>,
+    address: Option<SocketAddr>,
+}
+
+impl Default for AdminServer {
+    fn default() -> Self {
+        AdminServer::new()
+    }
+}
+
+impl AdminServer {
+    pub fn new() -> AdminServer {
+        AdminServer {
+            instances: Arc::new(Mutex::new(vec![])),
+            stores: Arc::new(Mutex::new(vec![])),
+            running: Arc::new(AtomicBool::new(false)),
+            address: None,
+        }
+    }
+
+    // 同名的instance被替换
+    pub fn add_instance(&self, instance: Instance) {
+        if let Ok(mut instances) = self.instances.lock() {
+            instances.retain(|known| known.name() != instance.name());
+            instances.push(instance);
+        }
+    }
+
+    pub fn remove_instance(&self, name: &str) {
+        if let Ok(mut instances) = self.instances.lock() {
+            instances.retain(|known| known.name() != name);
+        }
+    }
+
+    /**
+     * <pre>
+     *  注册GET /store/<name>返回的store, 同名的store被替换, 例如:
+     *      let store = Arc::new(Mutex::new(delivery));
+     *      admin.add_store("example", move || store.lock().ok().map(|delivery| delivery.store().inspect()));
+     * </pre>
+     */
+    pub fn add_store<F>(&self, name: &str, inspector: F)
+    where
+        F: Fn() -> Option<StoreSnapshot> + Send + 'static,
+    {
+        if let Ok(mut stores) = self.stores.lock() {
+            stores.retain(|(known, _)| known != name);
+            stores.push((name.to_string(), Box::new(inspector)));
+        }
+    }
+
+    pub fn remove_store(&self, name: &str) {
+        if let Ok(mut stores) = self.stores.lock() {
+            stores.retain(|(known, _)| known != name);
+        }
+    }
+
+    // 监听的地址, 端口为0时返回实际分配的端口
+    pub fn address(&self) -> Option<SocketAddr> {
+        self.address
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    // 例如0.0.0.0:11112
+    pub fn start(&mut self, address: &str) -> Result<(), String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("bind admin server {} failure: {}", address, e))?;
+        self.address = Some(listener.local_addr().map_err(|e| e.to_string())?);
+        self.running.store(true, Ordering::SeqCst);
+        let instances = self.instances.clone();
+        let stores = self.stores.clone();
+        let running = self.running.clone();
+        thread::Builder::new()
+            .name("admin-server".to_string())
+            .spawn(move || accept(listener, instances, stores, running))
+            .map_err(|e| format!("spawn admin server failure: {}", e))?;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        // 唤醒阻塞在accept上的线程
+        if let Some(address) = self.address {
+            let _ = TcpStream::connect(address);
+        }
+    }
+
+    // GET请求的路径对应的(status code, body)
+    pub fn handle(&self, path: &str) -> (u16, String) {
+        dispatch(&self.instances, &self.stores, path)
+    }
+}
+
+impl Drop for AdminServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept(listener: TcpListener, instances: Arc<Mutex<Vec<Instance>>>, stores: Arc<Mutex<Vec<(String, StoreInspector)>>>,
+          running: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        if let Err(e) = serve(stream, &instances, &stores) {
+            println!("admin request failure: {}", e);
+        }
+    }
+}
+
+fn serve(mut stream: TcpStream, instances: &Mutex<Vec<Instance>>, stores: &Mutex<Vec<(String, StoreInspector)>>)
+         -> Result<(), String> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|e| format!("read request failure: {}", e))?;
+    // 忽略请求头
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) if line.trim().is_empty() => break,
+            Ok(_) => continue,
+            Err(e) => return Err(format!("read request failure: {}", e)),
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) => dispatch(instances, stores, path),
+        (Some(_), Some(_)) => (405, error_json("only GET is supported")),
+        _ => (400, error_json("invalid request")),
+    };
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let response = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, reason, body.len(), body);
+    stream.write_all(response.as_bytes()).map_err(|e| format!("write response failure: {}", e))
+}
+
+fn dispatch(instances: &Mutex<Vec<Instance>>, stores: &Mutex<Vec<(String, StoreInspector)>>, path: &str) -> (u16, String) {
+    let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+    if let Some(name) = path.strip_prefix("/store/") {
+        return match stores.lock() {
+            Ok(stores) => store(&stores, name),
+            Err(_) => (500, error_json("stores are poisoned")),
+        };
+    }
+    match instances.lock() {
+        Ok(instances) => route(&instances, path),
+        Err(_) => (500, error_json("instances are poisoned")),
+    }
+}
+
+fn store(stores: &[(String, StoreInspector)], name: &str) -> (u16, String) {
+    match stores.iter().find(|(known, _)| known == name) {
+        Some((_, inspector)) => match inspector() {
+            Some(snapshot) => (200, format!("{{\"version\":{},\"name\":{},\"store\":{}}}", STATUS_VERSION,
+                                            json_string(name), snapshot.to_json())),
+            None => (500, error_json(&format!("store {} is poisoned", name))),
+        },
+        None => (404, error_json(&format!("store {} is not found", name))),
+    }
+}
+
+fn route(instances: &[Instance], path: &str) -> (u16, String) {
+    if path == "/status" {
+        let documents: Vec<String> = instances.iter().map(|instance| instance.status_json()).collect();
+        return (200, format!("{{\"version\":{},\"instances\":[{}]}}", STATUS_VERSION, documents.join(",")));
+    }
+    if path == "/memory" {
+        return (200, format!("{{\"version\":{},\"memory\":{}}}", STATUS_VERSION, memory::global().to_json()));
+    }
+    match path.strip_prefix("/status/") {
+        Some(name) => match instances.iter().find(|instance| instance.name() == name) {
+            Some(instance) => (200, instance.status_json()),
+            None => (404, error_json(&format!("instance {} is not found", name))),
+        },
+        None => (404, error_json(&format!("unknown path {}", path))),
+    }
+}
+
+fn error_json(message: &str) -> String {
+    format!("{{\"version\":{},\"error\":{}}}", STATUS_VERSION, json_string(message))
+}
@@ -0,0 +1,128 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use crate::config::{get_duration, Properties};
+
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/**
+ * <pre>
+ *  dump失败后的重试间隔, 每次失败间隔翻倍直到max_delay:
+ *  1s, 2s, 4s ... max_delay
+ *  max_retries为None时一直重试直到parser被stop, 成功收到event后调用reset重新计数.
+ *  jitter为0~1的比例, 每次间隔再加上最多该比例的随机值, 避免多个parser在master恢复之后同时重连
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_retries: Option<u32>,
+    jitter: f64,
+    attempts: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(DEFAULT_INITIAL_DELAY, DEFAULT_MAX_DELAY)
+    }
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration) -> Backoff {
+        Backoff { initial_delay, max_delay, max_retries: Some(DEFAULT_MAX_RETRIES), jitter: 0.0, attempts: 0 }
+    }
+
+    /**
+     * <pre>
+     *  从配置中读取重连策略, prefix例如master.reconnect, 没有配置的项使用默认值:
+     *      {prefix}.initial_delay      第一次重试前的等待时间, 默认1s
+     *      {prefix}.max_delay          等待时间的上限, 默认30s
+     *      {prefix}.max_retries        连续失败的最大重试次数, unlimited表示一直重试, 默认3
+     *      {prefix}.jitter             随机增加的比例, 0~1, 默认0
+     *  一项都没有配置时返回None
+     * </pre>
+     */
+    pub fn from_properties(properties: &Properties, prefix: &str) -> Result<Option<Backoff>, String> {
+        let key = |name: &str| format!("{}.{}", prefix, name);
+        let names = ["initial_delay", "max_delay", "max_retries", "jitter"];
+        if !names.iter().any(|name| properties.contains_key(&key(name))) {
+            return Ok(None);
+        }
+        let initial_delay = get_duration(properties, &key("initial_delay"))?.unwrap_or(DEFAULT_INITIAL_DELAY);
+        let max_delay = get_duration(properties, &key("max_delay"))?.unwrap_or(DEFAULT_MAX_DELAY);
+        if initial_delay.is_zero() || max_delay < initial_delay {
+            return Err(format!("{}: invalid delay {:?}~{:?}, expect 0 < initial_delay <= max_delay", prefix,
+                               initial_delay, max_delay));
+        }
+        let mut backoff = Backoff::new(initial_delay, max_delay);
+        if let Some(value) = properties.get(&key("max_retries")) {
+            let max_retries = match value.trim() {
+                "unlimited" => None,
+                value => Some(value.parse::<u32>()
+                    .map_err(|_| format!("{}: invalid value {}, expect a number or unlimited", key("max_retries"),
+                                         value))?),
+            };
+            backoff.set_max_retries(max_retries);
+        }
+        if let Some(value) = properties.get(&key("jitter")) {
+            let jitter = value.trim().parse::<f64>().ok().filter(|jitter| (0.0..=1.0).contains(jitter))
+                .ok_or_else(|| format!("{}: invalid value {}, expect 0~1", key("jitter"), value))?;
+            backoff.set_jitter(jitter);
+        }
+        Ok(Some(backoff))
+    }
+
+    pub fn initial_delay(&self) -> Duration {
+        self.initial_delay
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    pub fn set_max_retries(&mut self, max_retries: Option<u32>) {
+        self.max_retries = max_retries;
+    }
+
+    pub fn max_retries(&self) -> Option<u32> {
+        self.max_retries
+    }
+
+    pub fn set_jitter(&mut self, jitter: f64) {
+        self.jitter = jitter.clamp(0.0, 1.0);
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // 返回下一次重试前需要等待的时间, None表示已经超过最大重试次数
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if let Some(max_retries) = self.max_retries {
+            if self.attempts >= max_retries {
+                return None;
+            }
+        }
+        let factor = 1u32.checked_shl(self.attempts.min(31)).unwrap_or(u32::MAX);
+        self.attempts += 1;
+        let delay = self.initial_delay.saturating_mul(factor).min(self.max_delay);
+        if self.jitter == 0.0 {
+            return Some(delay);
+        }
+        // RandomState每次使用不同的随机种子
+        let random = RandomState::new().hash_one(self.attempts) as f64 / u64::MAX as f64;
+        Some(delay + delay.mul_f64(self.jitter * random))
+    }
+
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
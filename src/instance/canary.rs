@@ -0,0 +1,150 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::channel::mysql_socket::MysqlConnector;
+use crate::instance::AuthenticationInfo;
+use crate::protocol::{Entry, EntryType, EventType};
+
+pub const DEFAULT_CANARY_SCHEMA: &str = "canal";
+pub const DEFAULT_CANARY_TABLE: &str = "canal_canary";
+pub const DEFAULT_CANARY_INTERVAL: Duration = Duration::from_secs(1);
+
+// canary表的列: id int primary key, ts bigint(写入时的毫秒时间戳)
+pub const CANARY_ID_INDEX: usize = 0;
+pub const CANARY_TS_INDEX: usize = 1;
+
+// 等待期间检查running状态的间隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/**
+ * <pre>
+ *  canary模式的写入端: 按interval向源库的canary表写入当前时间,
+ *      replace into canal.canal_canary(id, ts) values(<canary_id>, <now>)
+ *  该行经过binlog -> parser -> sink, 由CanarySink在sink确认之后计算 now - ts 作为端到端延迟.
+ *  写入和确认使用同一个进程的时钟, 不受master与本机时钟偏差的影响, 也不依赖event header中秒级的timestamp.
+ *  第一次写入时会尝试建表, 没有建表或写入权限时打印原因后退出, 不影响parser.
+ *  多个instance共用一个canary表时需要设置不同的canary_id, 可以通过executor运行:
+ *      executor.spawn("canary", move |running| writer.run(running))?;
+ * </pre>
+ */
+pub struct CanaryWriter {
+    authentication_info: AuthenticationInfo,
+    schema: String,
+    table: String,
+    canary_id: u32,
+    interval: Duration,
+    written: u64,
+}
+
+impl CanaryWriter {
+    pub fn new(authentication_info: AuthenticationInfo) -> CanaryWriter {
+        CanaryWriter {
+            authentication_info,
+            schema: DEFAULT_CANARY_SCHEMA.to_string(),
+            table: DEFAULT_CANARY_TABLE.to_string(),
+            canary_id: 1,
+            interval: DEFAULT_CANARY_INTERVAL,
+            written: 0,
+        }
+    }
+
+    pub fn set_table(&mut self, schema: &str, table: &str) {
+        self.schema = schema.to_string();
+        self.table = table.to_string();
+    }
+
+    pub fn set_canary_id(&mut self, canary_id: u32) {
+        self.canary_id = canary_id;
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval.max(STOP_CHECK_INTERVAL);
+    }
+
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+    pub fn canary_id(&self) -> u32 {
+        self.canary_id
+    }
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    // running变为false时返回, 没有权限时直接返回Ok
+    pub fn run(&mut self, running: Arc<AtomicBool>) -> Result<(), String> {
+        let info = &self.authentication_info;
+        let mut connector = MysqlConnector::new(info.address(), info.port(), info.username(),
+                                                info.password(), info.default_database_name());
+        connector.set_ssl_mode(info.ssl_mode());
+        connector.set_ssl_ca(info.ssl_ca());
+        let mut prepared = false;
+        while running.load(Ordering::SeqCst) {
+            let started = Instant::now();
+            if !connector.is_connected() {
+                if let Err(e) = connector.connect() {
+                    println!("canary connect failure: {}", e);
+                }
+            }
+            if connector.is_connected() {
+                if !prepared {
+                    if let Err(e) = connector.update(&self.create_table_sql()) {
+                        println!("canary is disabled, create table {}.{} failure: {}", self.schema, self.table, e);
+                        break;
+                    }
+                    prepared = true;
+                }
+                match connector.update(&self.write_sql(Utc::now().timestamp_millis())) {
+                    Ok(_) => self.written += 1,
+                    Err(e) if connector.last_error().is_some() => {
+                        // master返回了错误, 例如没有写权限或者是只读实例
+                        println!("canary is disabled, write {}.{} failure: {}", self.schema, self.table, e);
+                        break;
+                    }
+                    Err(e) => {
+                        println!("canary write failure: {}, reconnect at next interval", e);
+                        connector.disconnect();
+                    }
+                }
+            }
+            let deadline = started + self.interval;
+            while running.load(Ordering::SeqCst) && Instant::now() < deadline {
+                thread::sleep(STOP_CHECK_INTERVAL.min(deadline - Instant::now()));
+            }
+        }
+        connector.disconnect();
+        Ok(())
+    }
+
+    fn create_table_sql(&self) -> String {
+        format!("create table if not exists `{}`.`{}` (id int not null primary key, ts bigint not null)",
+                self.schema, self.table)
+    }
+
+    fn write_sql(&self, ts: i64) -> String {
+        format!("replace into `{}`.`{}`(id, ts) values({}, {})", self.schema, self.table, self.canary_id, ts)
+    }
+}
+
+// 返回entry中canary行写入时的时间戳, 不是canary表的insert/update时返回空
+pub fn canary_timestamps(entry: &Entry, schema: &str, table: &str) -> Vec<i64> {
+    let header = entry.header();
+    let row_change = match entry.row_change() {
+        Some(row_change) if entry.entry_type() == EntryType::RowData && !row_change.is_ddl() => row_change,
+        _ => return vec![],
+    };
+    if header.schema_name() != schema || header.table_name() != table || row_change.event_type() == EventType::Delete {
+        return vec![];
+    }
+    row_change.rows().filter_map(|row| row.value(CANARY_TS_INDEX)?.parse().ok()).collect()
+}
@@ -0,0 +1,251 @@
+use std::time::{Duration, Instant};
+
+use crate::config::{get_duration, Properties};
+use crate::filter::RegexFilter;
+use crate::instance::EntryPosition;
+
+// 剩余的binlog超过该值时进入追赶模式
+pub const DEFAULT_CATCH_UP_THRESHOLD: u64 = 1024 * 1024 * 1024;
+// 查询show binary logs的间隔
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+// 限速时单次最长的等待, 避免长时间不检查running状态
+const MAX_THROTTLE_DELAY: Duration = Duration::from_millis(100);
+
+/**
+ * <pre>
+ *  当前位点之后还没有读取的binlog字节数: 当前文件的剩余部分加上之后所有文件的大小.
+ *  logs为show binary logs的(Log_name, File_size), 当前文件不在其中(例如已经被purge)时返回None
+ * </pre>
+ */
+pub fn remaining_bytes(logs: &[(String, u64)], position: &EntryPosition) -> Option<u64> {
+    let index = logs.iter().position(|(name, _)| name == position.journal_name())?;
+    let current = logs[index].1.saturating_sub(position.position());
+    Some(current + logs[index + 1..].iter().map(|(_, size)| size).sum::<u64>())
+}
+
+// 追赶进度, 每次查询binlog大小之后更新
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatchUpStatus {
+    active: bool,
+    remaining_bytes: Option<u64>,
+    // 当前位点之后的binlog文件数
+    files_behind: usize,
+    // 最近一个刷新周期内读取binlog的速率, 字节/秒
+    fetch_rate: f64,
+    // remaining_bytes减少的速率, 即读取速率减去master写入的速率, 不大于0时追不上
+    closing_rate: f64,
+    eta: Option<Duration>,
+    // 追赶期间提前丢弃的非关键表的rows event数
+    skipped_events: u64,
+}
+
+impl CatchUpStatus {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+    pub fn remaining_bytes(&self) -> Option<u64> {
+        self.remaining_bytes
+    }
+    pub fn files_behind(&self) -> usize {
+        self.files_behind
+    }
+    pub fn fetch_rate(&self) -> f64 {
+        self.fetch_rate
+    }
+    pub fn closing_rate(&self) -> f64 {
+        self.closing_rate
+    }
+    // 没有足够的数据或者剩余字节没有减少时为None
+    pub fn eta(&self) -> Option<Duration> {
+        self.eta
+    }
+    pub fn skipped_events(&self) -> u64 {
+        self.skipped_events
+    }
+}
+
+/**
+ * <pre>
+ *  落后较多时的追赶模式:
+ *      threshold               剩余binlog字节数不小于该值时进入追赶模式, 小于时退出
+ *      critical_tables         追赶期间只解析这些表的rows event(RegexFilter的格式), 其它表的rows event
+ *                              在解码行数据之前丢弃, 这些变更不会再补发; DDL/事务边界不受影响. 为空时不过滤
+ *      max_bytes_per_second    追赶期间读取binlog的速率上限, 避免占满master的网络和磁盘
+ *      refresh_interval        通过另一个连接查询show binary logs的间隔
+ *  ETA = 剩余字节数 / 剩余字节数减少的速率, 已经考虑了master在追赶期间继续写入的binlog.
+ *  配置: master.catch_up.threshold=1073741824, master.catch_up.critical_tables=shop\\.orders,
+ *       master.catch_up.max_bytes_per_second=52428800, master.catch_up.refresh_interval=10s
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct CatchUpPolicy {
+    threshold: u64,
+    critical_tables: Option<RegexFilter>,
+    max_bytes_per_second: Option<u64>,
+    refresh_interval: Duration,
+}
+
+impl Default for CatchUpPolicy {
+    fn default() -> Self {
+        CatchUpPolicy::new(DEFAULT_CATCH_UP_THRESHOLD)
+    }
+}
+
+impl CatchUpPolicy {
+    pub fn new(threshold: u64) -> CatchUpPolicy {
+        CatchUpPolicy { threshold, critical_tables: None, max_bytes_per_second: None, refresh_interval: DEFAULT_REFRESH_INTERVAL }
+    }
+
+    // 没有配置任何master.catch_up.*时返回None
+    pub fn from_properties(properties: &Properties) -> Result<Option<CatchUpPolicy>, String> {
+        if !properties.keys().any(|key| key.starts_with("master.catch_up.")) {
+            return Ok(None);
+        }
+        let number = |key: &str| properties.get(key)
+            .map(|value| value.trim().parse::<u64>().map_err(|_| format!("{}: invalid number {}", key, value)))
+            .transpose();
+        let mut policy = CatchUpPolicy::new(number("master.catch_up.threshold")?.unwrap_or(DEFAULT_CATCH_UP_THRESHOLD));
+        if let Some(pattern) = properties.get("master.catch_up.critical_tables") {
+            policy.set_critical_tables(Some(RegexFilter::new(pattern)?));
+        }
+        policy.set_max_bytes_per_second(number("master.catch_up.max_bytes_per_second")?);
+        if let Some(refresh_interval) = get_duration(properties, "master.catch_up.refresh_interval")? {
+            policy.set_refresh_interval(refresh_interval);
+        }
+        Ok(Some(policy))
+    }
+
+    pub fn set_critical_tables(&mut self, critical_tables: Option<RegexFilter>) {
+        self.critical_tables = critical_tables.filter(|filter| !filter.pattern().is_empty());
+    }
+
+    pub fn set_max_bytes_per_second(&mut self, max_bytes_per_second: Option<u64>) {
+        self.max_bytes_per_second = max_bytes_per_second.filter(|rate| *rate > 0);
+    }
+
+    pub fn set_refresh_interval(&mut self, refresh_interval: Duration) {
+        self.refresh_interval = refresh_interval;
+    }
+
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+    pub fn critical_tables(&self) -> Option<&RegexFilter> {
+        self.critical_tables.as_ref()
+    }
+    pub fn max_bytes_per_second(&self) -> Option<u64> {
+        self.max_bytes_per_second
+    }
+    pub fn refresh_interval(&self) -> Duration {
+        self.refresh_interval
+    }
+}
+
+// 一次刷新时的采样
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    fetched: u64,
+    remaining: Option<u64>,
+}
+
+/**
+ * <pre>
+ *  parser中追赶模式的运行状态, 跨重连保留:
+ *      on_event()      每个event之后调用, 累计读取的字节数, 返回限速需要等待的时间
+ *      refresh_due()   到达refresh_interval时, parser查询show binary logs后调用refresh()
+ *      skip()          追赶期间非关键表的rows event返回true
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct CatchUp {
+    policy: CatchUpPolicy,
+    status: CatchUpStatus,
+    fetched: u64,
+    last: Option<Sample>,
+    // 限速窗口, 每次刷新时重新开始
+    window_start: Instant,
+    window_bytes: u64,
+}
+
+impl CatchUp {
+    pub fn new(policy: CatchUpPolicy) -> CatchUp {
+        CatchUp { policy, status: CatchUpStatus::default(), fetched: 0, last: None, window_start: Instant::now(), window_bytes: 0 }
+    }
+
+    pub fn policy(&self) -> &CatchUpPolicy {
+        &self.policy
+    }
+
+    // 热加载时替换策略, 追赶状态不变, 限速窗口重新开始
+    pub fn set_policy(&mut self, policy: CatchUpPolicy) {
+        self.policy = policy;
+        self.window_start = Instant::now();
+        self.window_bytes = 0;
+    }
+
+    pub fn status(&self) -> &CatchUpStatus {
+        &self.status
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status.active
+    }
+
+    pub fn refresh_due(&self, now: Instant) -> bool {
+        self.last.is_none_or(|last| now.saturating_duration_since(last.at) >= self.policy.refresh_interval)
+    }
+
+    pub fn on_event(&mut self, len: usize) -> Option<Duration> {
+        self.fetched += len as u64;
+        let rate = self.policy.max_bytes_per_second.filter(|_| self.status.active)?;
+        self.window_bytes += len as u64;
+        let expected = Duration::from_secs_f64(self.window_bytes as f64 / rate as f64);
+        let elapsed = self.window_start.elapsed();
+        (expected > elapsed).then(|| (expected - elapsed).min(MAX_THROTTLE_DELAY))
+    }
+
+    // 返回是否进入或者退出了追赶模式
+    pub fn refresh(&mut self, logs: &[(String, u64)], position: &EntryPosition, now: Instant) -> bool {
+        let remaining = remaining_bytes(logs, position);
+        let sample = Sample { at: now, fetched: self.fetched, remaining };
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last.at).as_secs_f64();
+            if elapsed > 0.0 {
+                self.status.fetch_rate = (sample.fetched - last.fetched) as f64 / elapsed;
+                self.status.closing_rate = match (last.remaining, remaining) {
+                    (Some(before), Some(after)) => (before as f64 - after as f64) / elapsed,
+                    _ => 0.0,
+                };
+            }
+        }
+        self.status.eta = match remaining {
+            Some(0) => Some(Duration::ZERO),
+            Some(remaining) if self.status.closing_rate > 0.0 => {
+                Some(Duration::from_secs_f64(remaining as f64 / self.status.closing_rate))
+            }
+            _ => None,
+        };
+        self.status.remaining_bytes = remaining;
+        self.status.files_behind = logs.iter().position(|(name, _)| name == position.journal_name())
+            .map_or(0, |index| logs.len() - index - 1);
+        self.last = Some(sample);
+        self.window_start = now;
+        self.window_bytes = 0;
+        let active = remaining.is_some_and(|remaining| remaining >= self.policy.threshold);
+        let changed = active != self.status.active;
+        self.status.active = active;
+        changed
+    }
+
+    pub fn skip(&mut self, schema: &str, table: &str) -> bool {
+        let skip = match self.policy.critical_tables.as_ref() {
+            Some(filter) if self.status.active => !filter.matches(&format!("{}.{}", schema, table)),
+            _ => false,
+        };
+        if skip {
+            self.status.skipped_events += 1;
+        }
+        skip
+    }
+}
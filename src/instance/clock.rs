@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use crate::config::parse_duration;
+use crate::protocol::Header;
+
+// Auto模式下参与估计的最近的样本数
+pub const DEFAULT_SKEW_WINDOW: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSkewMode {
+    // 不修正
+    Off,
+    // 固定的偏移, 毫秒, master时钟快于本地时为正
+    Fixed(i64),
+    // 根据heartbeat之后收到的event估计
+    Auto,
+}
+
+impl ClockSkewMode {
+    /**
+     * <pre>
+     *  off, auto, 或者带符号的时间, 例如 +1500ms, -2s, 30s(按+处理)
+     * </pre>
+     */
+    pub fn from_name(name: &str) -> Result<ClockSkewMode, String> {
+        let name = name.trim();
+        match name {
+            "off" | "0" => return Ok(ClockSkewMode::Off),
+            "auto" => return Ok(ClockSkewMode::Auto),
+            _ => {}
+        }
+        let (sign, duration) = match name.strip_prefix('-') {
+            Some(duration) => (-1, duration),
+            None => (1, name.strip_prefix('+').unwrap_or(name)),
+        };
+        let duration = parse_duration(duration).map_err(|e| format!("invalid clock offset {}: {}", name, e))?;
+        Ok(ClockSkewMode::Fixed(sign * duration.as_millis() as i64))
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ClockSkewMode::Off => "off".to_string(),
+            ClockSkewMode::Fixed(offset) => format!("{:+}ms", offset),
+            ClockSkewMode::Auto => "auto".to_string(),
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  修正master时钟与本地时钟的偏差, 避免时钟不准的master让延迟的指标失真(例如为负被截断为0, 或者一直偏大).
+ *  offset = master时钟 - 本地时钟, 修正后的时间 = event的时间 - offset, 用于:
+ *      ParserStatus的lag, 位点的timestamp, entry header的execute_time以及commit timestamp
+ *  Auto模式: 收到heartbeat说明已经追上master, 之后收到的第一个event几乎是实时的,
+ *  样本 = event的时间 - 收到的时间 = offset - 延迟(传输以及事务的执行时间), 延迟不小于0,
+ *  取最近window个样本的最大值作为offset. event的时间优先使用毫秒精度的immediate commit timestamp(mysql 8),
+ *  否则使用秒级的header.when, 此时估计值最多偏小1秒. 没有样本之前offset为0.
+ *  一直有写入的master不会发送heartbeat, 此时保持之前的估计值
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct ClockSkew {
+    mode: ClockSkewMode,
+    offset: i64,
+    window: usize,
+    samples: VecDeque<i64>,
+    // 收到heartbeat之后还没有收到event
+    caught_up: bool,
+}
+
+impl Default for ClockSkew {
+    fn default() -> Self {
+        ClockSkew::new(ClockSkewMode::Off)
+    }
+}
+
+impl ClockSkew {
+    pub fn new(mode: ClockSkewMode) -> ClockSkew {
+        let offset = match mode {
+            ClockSkewMode::Fixed(offset) => offset,
+            _ => 0,
+        };
+        ClockSkew { mode, offset, window: DEFAULT_SKEW_WINDOW, samples: VecDeque::new(), caught_up: false }
+    }
+
+    pub fn mode(&self) -> ClockSkewMode {
+        self.mode
+    }
+    // 毫秒, master时钟快于本地时为正
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+    pub fn samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(1);
+        while self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    // 重新连接之后heartbeat之前的event可能是积压的, 不作为样本
+    pub fn reset(&mut self) {
+        self.caught_up = false;
+    }
+
+    pub fn on_heartbeat(&mut self) {
+        self.caught_up = true;
+    }
+
+    // event_time为master上的时间, now为收到的时间, 都是毫秒
+    pub fn observe(&mut self, event_time: i64, now: i64) {
+        if self.mode != ClockSkewMode::Auto || !self.caught_up || event_time <= 0 {
+            return;
+        }
+        self.caught_up = false;
+        self.samples.push_back(event_time - now);
+        if self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
+        self.offset = self.samples.iter().copied().max().unwrap_or(0);
+    }
+
+    // 毫秒, 0表示没有时间, 不修正
+    pub fn correct(&self, timestamp: i64) -> i64 {
+        if timestamp > 0 {
+            timestamp - self.offset
+        } else {
+            timestamp
+        }
+    }
+
+    // original commit timestamp来自事务最初的master, 只在与immediate相同(即在这个master上提交)时修正
+    pub fn correct_header(&self, header: &mut Header) {
+        if self.offset == 0 {
+            return;
+        }
+        header.set_execute_time(self.correct(header.execute_time()));
+        if let (Some(original), Some(immediate)) = (header.original_commit_timestamp(), header.immediate_commit_timestamp()) {
+            let corrected = if immediate > 0 { immediate - self.offset * 1000 } else { immediate };
+            header.set_commit_timestamps(if original == immediate { corrected } else { original }, corrected);
+        }
+    }
+}
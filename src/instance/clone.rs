@@ -0,0 +1,196 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::channel::mysql_socket::MysqlConnector;
+use crate::channel::DEFAULT_CONNECT_TIMEOUT;
+use crate::command::gtid::GtidSet;
+use crate::command::ResultSetPacket;
+use crate::instance::backoff::Backoff;
+use crate::instance::purge::Snapshotter;
+use crate::instance::{AuthenticationInfo, EntryPosition};
+
+// 等待recipient重启以及clone完成的默认间隔和次数
+pub const DEFAULT_CLONE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_CLONE_MAX_POLLS: u32 = 720;
+
+// performance_schema.clone_status中最近一次clone的状态
+enum CloneStatus {
+    InProgress,
+    Completed(EntryPosition, GtidSet),
+    Failed(String),
+}
+
+/**
+ * <pre>
+ *  使用mysql 8.0.17+的CLONE插件做物理快照, 用于select全量快照太慢的大实例:
+ *      1. 检查donor和recipient上的clone插件都是ACTIVE
+ *      2. 在recipient上设置clone_valid_donor_list并执行 CLONE INSTANCE FROM user@host:port IDENTIFIED BY ...
+ *      3. 没有设置data_directory时recipient的数据被替换并自动重启, 连接断开之后按poll_interval重新连接
+ *      4. 从recipient的performance_schema.clone_status读取快照对应的binlog位点和gtid_executed
+ *  parser从clone_status中的位点继续dump, 并用gtid_executed初始化GTID跳号检查, 快照和增量之间不重不漏.
+ *  donor必须是parser dump的master, clone用户需要donor上的BACKUP_ADMIN以及recipient上的CLONE_ADMIN权限
+ *      let snapshotter = CloneSnapshotter::new(recipient, donor);
+ *      parser.set_purged_binlog_strategy(PurgedBinlogStrategy::Snapshot);
+ *      parser.set_snapshotter(Box::new(snapshotter));
+ * </pre>
+ */
+pub struct CloneSnapshotter {
+    recipient: AuthenticationInfo,
+    donor: AuthenticationInfo,
+    // 克隆到recipient上的该目录, recipient不会重启, 需要之后用该目录启动新的实例
+    data_directory: Option<String>,
+    connect_timeout: Duration,
+    poll_interval: Duration,
+    max_polls: u32,
+    position: Option<EntryPosition>,
+    gtid_executed: Option<GtidSet>,
+}
+
+impl CloneSnapshotter {
+    pub fn new(recipient: AuthenticationInfo, donor: AuthenticationInfo) -> CloneSnapshotter {
+        CloneSnapshotter {
+            recipient,
+            donor,
+            data_directory: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            poll_interval: DEFAULT_CLONE_POLL_INTERVAL,
+            max_polls: DEFAULT_CLONE_MAX_POLLS,
+            position: None,
+            gtid_executed: None,
+        }
+    }
+
+    pub fn set_data_directory(&mut self, data_directory: Option<&str>) {
+        self.data_directory = data_directory.map(|directory| directory.to_string());
+    }
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.connect_timeout = connect_timeout;
+    }
+    pub fn set_poll(&mut self, poll_interval: Duration, max_polls: u32) {
+        self.poll_interval = poll_interval;
+        self.max_polls = max_polls.max(1);
+    }
+
+    pub fn data_directory(&self) -> Option<&str> {
+        self.data_directory.as_deref()
+    }
+    // 最近一次clone对应的位点
+    pub fn position(&self) -> Option<&EntryPosition> {
+        self.position.as_ref()
+    }
+
+    // donor和recipient上的clone插件都已经安装并且是ACTIVE
+    pub fn check(&self) -> Result<(), String> {
+        for (role, info) in [("donor", &self.donor), ("recipient", &self.recipient)] {
+            let mut connector = self.connector(info);
+            connector.connect()?;
+            let result = connector.query("select plugin_status from information_schema.plugins where plugin_name = 'clone'");
+            connector.disconnect();
+            match result?.field_values().first() {
+                Some(status) if status.eq_ignore_ascii_case("ACTIVE") => {}
+                Some(status) => return Err(format!("clone plugin on {} {}:{} is {}", role, info.address(), info.port(), status)),
+                None => return Err(format!("clone plugin is not installed on {} {}:{}, INSTALL PLUGIN clone SONAME \
+                                            'mysql_clone.so'", role, info.address(), info.port())),
+            }
+        }
+        Ok(())
+    }
+
+    // 执行clone并返回clone_status中的位点
+    pub fn clone_instance(&mut self) -> Result<EntryPosition, String> {
+        self.check()?;
+        let donor = format!("{}:{}", self.donor.address(), self.donor.port());
+        let mut connector = self.connector(&self.recipient);
+        connector.connect()?;
+        connector.update(&format!("set global clone_valid_donor_list = {}", quote(&donor)))?;
+        let mut sql = format!("clone instance from {}@{}:{} identified by {}", quote(self.donor.username()),
+                              quote(self.donor.address()), self.donor.port(), quote(self.donor.password()));
+        if let Some(directory) = self.data_directory.as_ref() {
+            sql.push_str(&format!(" data directory = {}", quote(directory)));
+        }
+        println!("clone instance from {} to {}:{}", donor, self.recipient.address(), self.recipient.port());
+        // 替换数据之后recipient重启, 连接会被断开, 结果以clone_status为准
+        if let Err(e) = connector.update(&sql) {
+            println!("clone instance returns: {}, check clone_status", e);
+        }
+        connector.disconnect();
+        let (position, gtid_executed) = self.wait_completed()?;
+        println!("clone instance completed at {}:{}", position.journal_name(), position.position());
+        self.position = Some(position.clone());
+        self.gtid_executed = Some(gtid_executed);
+        Ok(position)
+    }
+
+    fn wait_completed(&self) -> Result<(EntryPosition, GtidSet), String> {
+        let mut backoff = Backoff::new(self.poll_interval, self.poll_interval);
+        backoff.set_max_retries(Some(self.max_polls));
+        loop {
+            let e = match self.clone_status() {
+                Ok(CloneStatus::Completed(position, gtid_executed)) => return Ok((position, gtid_executed)),
+                Ok(CloneStatus::Failed(e)) => return Err(e),
+                Ok(CloneStatus::InProgress) => "clone is in progress".to_string(),
+                // recipient正在重启
+                Err(e) => e,
+            };
+            match backoff.next_delay() {
+                Some(delay) => thread::sleep(delay),
+                None => return Err(format!("wait clone completed timeout: {}", e)),
+            }
+        }
+    }
+
+    fn clone_status(&self) -> Result<CloneStatus, String> {
+        let mut connector = self.connector(&self.recipient);
+        connector.connect()?;
+        let result = connector.query("select state, error_no, error_message, binlog_file, binlog_position, gtid_executed \
+                                      from performance_schema.clone_status");
+        connector.disconnect();
+        let result = result?;
+        let value = |name: &str| column(&result, name).unwrap_or_default();
+        match value("state").to_ascii_lowercase().as_str() {
+            "completed" => {}
+            "failed" => return Ok(CloneStatus::Failed(format!("clone failure: {} {}", value("error_no"), value("error_message")))),
+            "" => return Err("clone_status is empty".to_string()),
+            _ => return Ok(CloneStatus::InProgress),
+        }
+        let position = value("binlog_position").parse::<u64>()
+            .map_err(|e| format!("invalid clone binlog position {}: {}", value("binlog_position"), e))?;
+        // gtid_executed中可能带有换行
+        let gtid_executed = GtidSet::parse(&value("gtid_executed").replace(['\n', ' '], ""))?;
+        Ok(CloneStatus::Completed(EntryPosition::new(&value("binlog_file"), position), gtid_executed))
+    }
+
+    fn connector(&self, info: &AuthenticationInfo) -> MysqlConnector {
+        let mut connector = MysqlConnector::new(info.address(), info.port(), info.username(), info.password(),
+                                                info.default_database_name());
+        connector.set_connect_timeout(self.connect_timeout);
+        connector.set_ssl_mode(info.ssl_mode());
+        connector.set_ssl_ca(info.ssl_ca());
+        connector
+    }
+}
+
+impl Snapshotter for CloneSnapshotter {
+    // clone的位点不早于position, 从position继续时重复投递两者之间的变更
+    fn snapshot(&mut self, _position: &EntryPosition) -> Result<(), String> {
+        self.clone_instance().map(|_| ())
+    }
+
+    fn snapshot_at(&mut self, _position: &EntryPosition) -> Result<EntryPosition, String> {
+        self.clone_instance()
+    }
+
+    fn gtid_executed(&self) -> Option<GtidSet> {
+        self.gtid_executed.clone()
+    }
+}
+
+// 第一行中name列的值
+fn column(result: &ResultSetPacket, name: &str) -> Option<String> {
+    let index = result.field_descriptors().iter().position(|field| field.eq_ignore_ascii_case(name))?;
+    result.rows().next()?.get(index).cloned()
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+}
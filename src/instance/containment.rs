@@ -0,0 +1,66 @@
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::command::hex::HexDump;
+
+// 错误信息中最多附带的event字节数, 超出部分省略
+pub const MAX_EVENT_HEX_BYTES: usize = 4096;
+
+/**
+ * <pre>
+ *  解码(LogDecoder + LogEventConvert)过程中panic时的处理方式.
+ *  解码代码中仍有少量unwrap/下标访问, 一个畸形的event会导致整个进程退出, 在全部改为返回Err之前:
+ *      Off         不捕获, 与之前的行为一致
+ *      Error       转换为Err, 错误信息中附带event的hex(敏感列已遮盖, 见SensitiveColumns), 按普通的dump错误重试
+ *      DeadLetter  把错误信息(包括hex)作为Incident entry交给dead letter sink, 跳过该event继续
+ *  捕获之后LogContext可能只更新了一部分(例如table map), 跳过的event之后的数据需要人工核对
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicContainment {
+    #[default]
+    Off,
+    Error,
+    DeadLetter,
+}
+
+impl PanicContainment {
+    pub fn from_name(name: &str) -> Result<PanicContainment, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Ok(PanicContainment::Off),
+            "error" => Ok(PanicContainment::Error),
+            "dlq" | "dead_letter" => Ok(PanicContainment::DeadLetter),
+            _ => Err(format!("unknown panic containment {}, expect off/error/dlq", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PanicContainment::Off => "off",
+            PanicContainment::Error => "error",
+            PanicContainment::DeadLetter => "dlq",
+        }
+    }
+}
+
+// 执行f, 外层的Err表示f发生了panic, 内容为panic的信息
+pub fn contain<T, E, F>(f: F) -> Result<Result<T, E>, String>
+    where F: FnOnce() -> Result<T, E> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|payload| panic_message(payload.as_ref()))
+}
+
+// 错误日志和DLQ中的信息, 附带event的hex(最多MAX_EVENT_HEX_BYTES个字节)
+pub fn panic_report(panic: &str, mut dump: HexDump) -> String {
+    dump.set_max_bytes(MAX_EVENT_HEX_BYTES);
+    format!("decode panicked: {}, event({} bytes): {}", panic, dump.len(), dump.to_compact())
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
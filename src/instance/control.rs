@@ -0,0 +1,108 @@
+use crate::instance::EntryPosition;
+use crate::protocol::{Entry, EntryType, EventType};
+
+pub const DEFAULT_CONTROL_SCHEMA: &str = "canal";
+pub const DEFAULT_CONTROL_TABLE: &str = "_mini_canal_control";
+
+// control表的列: id bigint auto_increment primary key, target varchar, command varchar, argument varchar
+pub const CONTROL_ID_INDEX: usize = 0;
+pub const CONTROL_TARGET_INDEX: usize = 1;
+pub const CONTROL_COMMAND_INDEX: usize = 2;
+pub const CONTROL_ARGUMENT_INDEX: usize = 3;
+
+// target为空或者为*时对所有instance生效
+pub const CONTROL_TARGET_ALL: &str = "*";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    // 暂停向下游投递, entry缓存在ControlSink中
+    Pause,
+    // 恢复投递, 先投递暂停期间缓存的entry
+    Resume,
+    // 在当前位点对一张表重新做全量快照, argument为schema.table
+    Resnapshot { schema: String, table: String },
+}
+
+impl ControlCommand {
+    // command忽略大小写, resnapshot的argument必须是schema.table
+    pub fn from_name(command: &str, argument: &str) -> Result<ControlCommand, String> {
+        match command.trim().to_ascii_lowercase().as_str() {
+            "pause" => Ok(ControlCommand::Pause),
+            "resume" => Ok(ControlCommand::Resume),
+            "resnapshot" => {
+                let (schema, table) = argument.trim().split_once('.')
+                    .filter(|(schema, table)| !schema.is_empty() && !table.is_empty())
+                    .ok_or_else(|| format!("resnapshot requires schema.table, got '{}'", argument))?;
+                Ok(ControlCommand::Resnapshot { schema: schema.to_string(), table: table.to_string() })
+            }
+            _ => Err(format!("unknown control command '{}', expect pause/resume/resnapshot", command)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlCommand::Pause => "pause",
+            ControlCommand::Resume => "resume",
+            ControlCommand::Resnapshot { .. } => "resnapshot",
+        }
+    }
+}
+
+// control表中的一行, command无法识别时为Err, 仍然记录下来便于排查
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlRequest {
+    id: String,
+    command: Result<ControlCommand, String>,
+    position: EntryPosition,
+}
+
+impl ControlRequest {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    pub fn command(&self) -> Result<&ControlCommand, &str> {
+        self.command.as_ref().map_err(|e| e.as_str())
+    }
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+}
+
+/**
+ * <pre>
+ *  从源库的control表接收命令, DBA在没有其它管理通道时可以直接用sql控制同步:
+ *      create table canal._mini_canal_control (id bigint not null auto_increment primary key,
+ *          target varchar(64) not null default '', command varchar(32) not null, argument varchar(255) not null default '');
+ *      insert into canal._mini_canal_control(target, command) values('', 'pause');
+ *      insert into canal._mini_canal_control(target, command, argument) values('example', 'resnapshot', 'shop.orders');
+ *  只处理insert和update之后的行, delete忽略. target为空或者*时对所有instance生效, 否则只对同名的instance生效.
+ *  命令随binlog按顺序到达, 因此resnapshot的位点就是该行所在的位点
+ * </pre>
+ */
+pub fn control_requests(entry: &Entry, schema: &str, table: &str, instance: &str) -> Vec<ControlRequest> {
+    let header = entry.header();
+    let row_change = match entry.row_change() {
+        Some(row_change) if entry.entry_type() == EntryType::RowData && !row_change.is_ddl() => row_change,
+        _ => return vec![],
+    };
+    if header.schema_name() != schema || header.table_name() != table || row_change.event_type() == EventType::Delete {
+        return vec![];
+    }
+    row_change.rows()
+        .filter(|row| match row.value(CONTROL_TARGET_INDEX).map(str::trim) {
+            None | Some("") | Some(CONTROL_TARGET_ALL) => true,
+            Some(target) => target == instance,
+        })
+        .map(|row| ControlRequest {
+            id: row.value(CONTROL_ID_INDEX).unwrap_or_default().to_string(),
+            command: ControlCommand::from_name(row.value(CONTROL_COMMAND_INDEX).unwrap_or_default(),
+                                               row.value(CONTROL_ARGUMENT_INDEX).unwrap_or_default()),
+            position: header.position(),
+        })
+        .collect()
+}
+
+// 执行resnapshot命令, 在sink线程中同步调用, 返回之后才继续投递之后的entry
+pub trait TableSnapshotter: Send {
+    fn snapshot_table(&mut self, schema: &str, table: &str, position: &EntryPosition) -> Result<(), String>;
+}
@@ -0,0 +1,459 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::command::event::column_type::*;
+use crate::command::event::rows_buffer::real_type_and_meta;
+use crate::command::event::table_map::ColumnInfo;
+use crate::command::event::{EventType as LogEventType, LogContext, LogEvent, LogHeader, QueryLogEvent,
+                            RowValue, RowsLogBuffer, RowsLogEvent, TableMapLogEvent};
+use crate::instance::cutover::{Cutover, CutoverDetector};
+use crate::instance::ddl::{parse_ddl, SqlMode};
+use crate::instance::decode_trace::DecodeTrace;
+use crate::instance::transient::TransientTables;
+use crate::protocol::{Column, Entry, EntryType, EventType, Header, RowChange, RowData, RowSchema, ZeroDatePolicy};
+
+// rows event引用的table_id不在LogContext中时的处理方式(例如从事务中间的位点开始dump)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingTableMetaPolicy {
+    // 返回错误, parser从事务开头重新dump, master会重新发送table map
+    Refetch,
+    // 输出只有table_id和event type的entry, 没有列类型时无法切分出各列的值
+    Raw,
+    // 丢弃, 每个table_id第一次出现时打印警告
+    Skip,
+}
+
+/**
+ * <pre>
+ *  对应canal中的LogEventConvert, 把解析后的LogEvent转换为Entry:
+ *  BEGIN                       TransactionBegin
+ *  COMMIT / XID                TransactionEnd
+ *  其它query                   RowData(DDL), 按照sql_mode识别DDL类型和操作的表
+ *  WRITE/UPDATE/DELETE_ROWS    RowData, 列名/主键来自table map的optional metadata,
+ *                              没有列名时使用@1, @2...
+ *  INCIDENT                    Incident, message为 "binlog incident <type>: <message>", 位点为该event的位点
+ *  其它event不产生entry, 按照transient_tables丢弃的临时表/中间表的DDL和rows同样不产生entry.
+ *  开启cutover时online DDL切换的RENAME输出为业务表的Alter, 见CutoverDetector
+ * </pre>
+ */
+pub struct LogEventConvert {
+    missing_table_meta_policy: MissingTableMetaPolicy,
+    // Skip策略下已经打印过警告的table_id
+    missing_tables: HashSet<u64>,
+    // 长度不小于该值的blob/text/geometry列延迟解码, None表示全部立即解码
+    lazy_blob_threshold: Option<usize>,
+    // query event没有携带sql_mode时使用, 一般为master的@@global.sql_mode
+    default_sql_mode: SqlMode,
+    // date/datetime/timestamp中非法日期的处理方式
+    zero_date_policy: ZeroDatePolicy,
+    // 临时表以及online DDL中间表的处理方式
+    transient_tables: TransientTables,
+    // 识别online DDL的切换, None时RENAME原样输出
+    cutover: Option<CutoverDetector>,
+}
+
+impl Default for LogEventConvert {
+    fn default() -> Self {
+        LogEventConvert::new()
+    }
+}
+
+impl LogEventConvert {
+    pub fn new() -> LogEventConvert {
+        LogEventConvert {
+            missing_table_meta_policy: MissingTableMetaPolicy::Refetch,
+            missing_tables: HashSet::new(),
+            lazy_blob_threshold: None,
+            default_sql_mode: SqlMode::default(),
+            zero_date_policy: ZeroDatePolicy::Keep,
+            transient_tables: TransientTables::new(),
+            cutover: None,
+        }
+    }
+
+    pub fn missing_table_meta_policy(&self) -> MissingTableMetaPolicy {
+        self.missing_table_meta_policy
+    }
+
+    pub fn set_missing_table_meta_policy(&mut self, policy: MissingTableMetaPolicy) {
+        self.missing_table_meta_policy = policy;
+    }
+
+    pub fn lazy_blob_threshold(&self) -> Option<usize> {
+        self.lazy_blob_threshold
+    }
+
+    // 大字段只在sink读取Column::value时解码, 被filter丢弃的行/列不产生拷贝
+    pub fn set_lazy_blob_threshold(&mut self, threshold: Option<usize>) {
+        self.lazy_blob_threshold = threshold;
+    }
+
+    pub fn default_sql_mode(&self) -> SqlMode {
+        self.default_sql_mode
+    }
+
+    pub fn set_default_sql_mode(&mut self, sql_mode: SqlMode) {
+        self.default_sql_mode = sql_mode;
+    }
+
+    pub fn zero_date_policy(&self) -> &ZeroDatePolicy {
+        &self.zero_date_policy
+    }
+
+    // Error策略下遇到非法日期时parse返回错误, parser停止
+    pub fn set_zero_date_policy(&mut self, policy: ZeroDatePolicy) {
+        self.zero_date_policy = policy;
+    }
+
+    pub fn transient_tables(&self) -> &TransientTables {
+        &self.transient_tables
+    }
+
+    pub fn transient_tables_mut(&mut self) -> &mut TransientTables {
+        &mut self.transient_tables
+    }
+
+    pub fn set_transient_tables(&mut self, transient_tables: TransientTables) {
+        self.transient_tables = transient_tables;
+    }
+
+    pub fn cutover_detector(&self) -> Option<&CutoverDetector> {
+        self.cutover.as_ref()
+    }
+
+    // 关闭时丢弃已经记录的ALTER
+    pub fn set_cutover_detection(&mut self, enabled: bool) {
+        if enabled != self.cutover.is_some() {
+            self.cutover = enabled.then(CutoverDetector::new);
+        }
+    }
+
+    // in_transaction为false时事务开头不在本次dump的范围内, Refetch无法拿到table map, 按Skip处理
+    pub fn parse(&mut self, event: &LogEvent, context: &LogContext, in_transaction: bool) -> Result<Option<Entry>, String> {
+        match event {
+            LogEvent::Query(query) => Ok(self.parse_query(query, context)),
+            LogEvent::Rows(rows) => match context.get_table(rows.table_id()) {
+                Some(table) if self.transient_tables.skip(table.db_name(), table.table_name(), false) => Ok(None),
+                Some(table) => Ok(Some(self.parse_rows(rows, table, context)?)),
+                None => self.missing_table(rows, context, in_transaction),
+            },
+            LogEvent::Incident(incident) => {
+                let message = format!("binlog incident {}: {}", incident.incident().name(), incident.message());
+                Ok(Some(Entry::incident(create_header(incident.header(), context, "", ""), &message)))
+            }
+            LogEvent::Unknown(header) if header.event_type() == Some(LogEventType::XidEvent) => {
+                Ok(Some(Entry::new(create_header(header, context, "", ""), EntryType::TransactionEnd)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_query(&mut self, query: &QueryLogEvent, context: &LogContext) -> Option<Entry> {
+        let sql = query.query().trim();
+        let header = create_header(query.header(), context, query.db_name(), "");
+        if sql.eq_ignore_ascii_case("BEGIN") {
+            return Some(Entry::new(header, EntryType::TransactionBegin));
+        }
+        if sql.eq_ignore_ascii_case("COMMIT") {
+            return Some(Entry::new(header, EntryType::TransactionEnd));
+        }
+        // ANSI_QUOTES/NO_BACKSLASH_ESCAPES会改变引号的含义, 以执行DDL时的sql_mode为准
+        let sql_mode = if query.has_sql_mode() { SqlMode::new(query.sql_mode()) } else { self.default_sql_mode };
+        let ddl = parse_ddl(sql, sql_mode);
+        let schema_name = if ddl.schema_name().is_empty() { query.db_name() } else { ddl.schema_name() };
+        if let Some(detector) = self.cutover.as_mut() {
+            let cutover = detector.observe(&ddl, schema_name, query.db_name(), sql, sql_mode, &self.transient_tables);
+            if let Some(cutover) = cutover {
+                return Some(cutover_entry(header, &cutover, query.query()));
+            }
+        }
+        if self.transient_tables.skip(schema_name, ddl.table_name(), ddl.is_temporary()) {
+            return None;
+        }
+        let mut row_change = RowChange::new(ddl.event_type());
+        row_change.set_is_ddl(true);
+        row_change.set_sql(query.query());
+        row_change.set_ddl_schema_name(schema_name);
+        let mut header = header;
+        header.set_schema_name(schema_name);
+        header.set_table_name(ddl.table_name());
+        header.set_event_type(row_change.event_type());
+        Some(Entry::row_data(header, row_change))
+    }
+
+    // 并行解码时在dump线程中调用, table map缺失或者需要丢弃时返回None, 由parse按照对应的策略处理
+    pub fn rows_task(&self, rows: &RowsLogEvent, context: &LogContext) -> Option<RowsTask> {
+        let table = context.get_table(rows.table_id())?;
+        if self.transient_tables.skip_table(table.db_name(), table.table_name(), false) {
+            return None;
+        }
+        Some(RowsTask {
+            rows: rows.clone(),
+            table: table.clone(),
+            header: rows_header(rows, table, context),
+            lazy_blob_threshold: self.lazy_blob_threshold,
+            zero_date_policy: self.zero_date_policy.clone(),
+        })
+    }
+
+    // 按trace方式解码一次, 失败时同样返回记录到失败为止的步骤; table map缺失时返回None
+    pub fn trace_rows(&self, rows: &RowsLogEvent, context: &LogContext) -> Option<DecodeTrace> {
+        let table = context.get_table(rows.table_id())?;
+        let header = rows_header(rows, table, context);
+        let mut buffer = rows_buffer(rows, self.lazy_blob_threshold, &self.zero_date_policy);
+        buffer.enable_trace();
+        let error = decode_rows_with(&mut buffer, rows, table, header.clone()).err();
+        Some(DecodeTrace::new(header.position(), table.db_name(), table.table_name(), rows.table_id(),
+                              rows_event_type(rows), rows.rows().len(), rows.columns(), buffer.take_trace(), error))
+    }
+
+    fn parse_rows(&self, rows: &RowsLogEvent, table: &TableMapLogEvent, context: &LogContext) -> Result<Entry, String> {
+        decode_rows(rows, table, rows_header(rows, table, context), self.lazy_blob_threshold, &self.zero_date_policy)
+    }
+
+    fn missing_table(&mut self, rows: &RowsLogEvent, context: &LogContext, in_transaction: bool)
+                     -> Result<Option<Entry>, String> {
+        let position = format!("{}:{}", context.log_position().journal_name(), rows.header().log_pos());
+        match self.missing_table_meta_policy {
+            MissingTableMetaPolicy::Refetch if in_transaction => {
+                Err(format!("table map of table_id {} is missing at {}, restart from the transaction begin",
+                            rows.table_id(), position))
+            }
+            MissingTableMetaPolicy::Raw => {
+                let event_type = rows_event_type(rows);
+                let mut header = create_header(rows.header(), context, "", "");
+                header.set_event_type(event_type);
+                let mut row_change = RowChange::new(event_type);
+                row_change.set_table_id(rows.table_id());
+                Ok(Some(Entry::row_data(header, row_change)))
+            }
+            _ => {
+                if self.missing_tables.insert(rows.table_id()) {
+                    println!("skip rows event of table_id {} at {}, table map is missing", rows.table_id(), position);
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  并行解码时一个rows event的行数据解码任务, 携带创建时的table map副本和header,
+ *  之后收到的table map/DDL不会影响已经创建的任务
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct RowsTask {
+    rows: RowsLogEvent,
+    table: TableMapLogEvent,
+    header: Header,
+    lazy_blob_threshold: Option<usize>,
+    zero_date_policy: ZeroDatePolicy,
+}
+
+impl RowsTask {
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    pub fn decode(self) -> Result<Entry, String> {
+        decode_rows(&self.rows, &self.table, self.header, self.lazy_blob_threshold, &self.zero_date_policy)
+    }
+}
+
+// rows event的header, 在dump线程中创建, 位点和事务信息来自当时的LogContext
+fn rows_header(rows: &RowsLogEvent, table: &TableMapLogEvent, context: &LogContext) -> Header {
+    let mut header = create_header(rows.header(), context, table.db_name(), table.table_name());
+    header.set_event_type(rows_event_type(rows));
+    header
+}
+
+// 只依赖rows event和table map, 可以在其它线程中执行
+fn decode_rows(rows: &RowsLogEvent, table: &TableMapLogEvent, header: Header, lazy_blob_threshold: Option<usize>,
+               zero_date_policy: &ZeroDatePolicy) -> Result<Entry, String> {
+    decode_rows_with(&mut rows_buffer(rows, lazy_blob_threshold, zero_date_policy), rows, table, header)
+}
+
+fn rows_buffer<'a>(rows: &'a RowsLogEvent, lazy_blob_threshold: Option<usize>, zero_date_policy: &ZeroDatePolicy)
+                   -> RowsLogBuffer<'a> {
+    let mut buffer = match lazy_blob_threshold {
+        Some(threshold) => RowsLogBuffer::with_lazy_blob(rows.rows(), threshold),
+        None => RowsLogBuffer::new(rows.rows()),
+    };
+    buffer.set_zero_date_policy(zero_date_policy.clone());
+    buffer
+}
+
+fn decode_rows_with(buffer: &mut RowsLogBuffer, rows: &RowsLogEvent, table: &TableMapLogEvent, header: Header)
+                    -> Result<Entry, String> {
+    let mut row_change = RowChange::new(rows_event_type(rows));
+    row_change.set_table_id(rows.table_id());
+    let column_info = table.column_info();
+    if rows.column_count() > column_info.len() {
+        return Err(format!("rows event of {}.{} has {} columns but the table map has {}", table.db_name(),
+                           table.table_name(), rows.column_count(), column_info.len()));
+    }
+    let names = column_info.iter().enumerate().map(|(index, info)| column_name(info, index)).collect();
+    row_change.set_schema(Arc::new(RowSchema::new(names)));
+    while buffer.has_next() {
+        let before = buffer.next_row_values(rows.columns(), column_info)
+            .map_err(|e| format!("decode rows of {}.{} failure: {}", table.db_name(), table.table_name(), e))?;
+        let before = to_columns(before, column_info, None);
+        let row_data = if rows.is_update() {
+            let after = buffer.next_row_values(rows.change_columns(), column_info)
+                .map_err(|e| format!("decode rows of {}.{} failure: {}", table.db_name(), table.table_name(), e))?;
+            let after = to_columns(after, column_info, Some(&before));
+            RowData::new(before, after)
+        } else if rows.is_delete() {
+            RowData::new(before, vec![])
+        } else {
+            RowData::new(vec![], before)
+        };
+        row_change.add_row_data(row_data);
+    }
+    Ok(Entry::row_data(header, row_change))
+}
+
+// 切换时代替RENAME的业务表Alter, statement为原来的RENAME, 没有记录到ALTER时sql同样为RENAME
+fn cutover_entry(mut header: Header, cutover: &Cutover, rename: &str) -> Entry {
+    let mut row_change = RowChange::new(EventType::Alter);
+    row_change.set_is_ddl(true);
+    row_change.set_sql(&cutover.sql().unwrap_or_else(|| rename.to_string()));
+    row_change.set_ddl_schema_name(cutover.schema_name());
+    header.set_schema_name(cutover.schema_name());
+    header.set_table_name(cutover.table_name());
+    header.set_event_type(EventType::Alter);
+    header.set_statement(rename);
+    Entry::row_data(header, row_change)
+}
+
+fn create_header(log_header: &LogHeader, context: &LogContext, schema_name: &str, table_name: &str) -> Header {
+    let offset = (log_header.log_pos() as u64).saturating_sub(log_header.event_len() as u64);
+    let mut header = Header::new(context.log_position().journal_name(), offset);
+    header.set_server_id(log_header.server_id());
+    header.set_execute_time(log_header.when() as i64 * 1000);
+    header.set_event_length(log_header.event_len());
+    header.set_schema_name(schema_name);
+    header.set_table_name(table_name);
+    if let Some(gtid) = context.gtid() {
+        header.set_gtid(gtid);
+    }
+    if let Some((original, immediate)) = context.commit_timestamps() {
+        header.set_commit_timestamps(original, immediate);
+    }
+    if let Some(statement) = context.statement() {
+        header.set_statement(statement);
+    }
+    header
+}
+
+fn rows_event_type(rows: &RowsLogEvent) -> EventType {
+    if rows.is_update() {
+        EventType::Update
+    } else if rows.is_delete() {
+        EventType::Delete
+    } else {
+        EventType::Insert
+    }
+}
+
+// before不为None时(update的after image)按照值是否变化设置updated
+fn to_columns(values: Vec<(usize, Option<RowValue>)>, column_info: &[ColumnInfo], before: Option<&Vec<Column>>) -> Vec<Column> {
+    values.into_iter().map(|(index, value)| {
+        let info = &column_info[index];
+        let mut column = Column::new(index, &column_name(info, index));
+        column.set_is_key(info.pk());
+        column.set_mysql_type(&mysql_type(info));
+        column.set_sql_type(sql_type(info));
+        column.set_is_null(value.is_none());
+        match value {
+            Some(RowValue::Lazy(lazy)) => column.set_lazy_value(lazy),
+            Some(RowValue::Decoded(value)) => column.set_value(&value),
+            None => column.set_value(""),
+        }
+        // before按下标有序, 宽表中逐个查找是O(n^2)
+        let old = before.and_then(|before| before.binary_search_by_key(&index, |old| old.index()).ok().map(|i| &before[i]));
+        let updated = match old {
+            Some(old) => old.is_null() != column.is_null() || !same_value(old, &column),
+            None => true,
+        };
+        column.set_updated(updated);
+        column
+    }).collect()
+}
+
+// 没有列名时使用@1, @2...
+fn column_name(info: &ColumnInfo, index: usize) -> String {
+    info.name().map(|name| name.to_string()).unwrap_or_else(|| format!("@{}", index + 1))
+}
+
+// 有lazy列时比较原始字节, 不触发解码
+fn same_value(old: &Column, column: &Column) -> bool {
+    match (old.lazy_value(), column.lazy_value()) {
+        (Some(old), Some(lazy)) => old == lazy,
+        (None, None) => old.value() == column.value(),
+        // 一个超过阈值一个没有, 原始长度不同
+        _ => false,
+    }
+}
+
+// 根据table map中的类型推断的列类型, 与show create table中的写法尽量一致
+pub fn mysql_type(info: &ColumnInfo) -> String {
+    let (kind, meta) = real_type_and_meta(info);
+    let unsigned = if info.unsigned() { " unsigned" } else { "" };
+    let fsp = |meta: u16| if meta > 0 { format!("({})", meta) } else { String::new() };
+    match kind {
+        MYSQL_TYPE_TINY => format!("tinyint{}", unsigned),
+        MYSQL_TYPE_SHORT => format!("smallint{}", unsigned),
+        MYSQL_TYPE_INT24 => format!("mediumint{}", unsigned),
+        MYSQL_TYPE_LONG => format!("int{}", unsigned),
+        MYSQL_TYPE_LONGLONG => format!("bigint{}", unsigned),
+        MYSQL_TYPE_FLOAT => format!("float{}", unsigned),
+        MYSQL_TYPE_DOUBLE => format!("double{}", unsigned),
+        MYSQL_TYPE_NEWDECIMAL => format!("decimal({},{}){}", meta >> 8, meta & 0xff, unsigned),
+        MYSQL_TYPE_YEAR => "year".to_string(),
+        MYSQL_TYPE_DATE | MYSQL_TYPE_NEWDATE => "date".to_string(),
+        MYSQL_TYPE_TIME => "time".to_string(),
+        MYSQL_TYPE_TIME2 => format!("time{}", fsp(meta)),
+        MYSQL_TYPE_DATETIME => "datetime".to_string(),
+        MYSQL_TYPE_DATETIME2 => format!("datetime{}", fsp(meta)),
+        MYSQL_TYPE_TIMESTAMP => "timestamp".to_string(),
+        MYSQL_TYPE_TIMESTAMP2 => format!("timestamp{}", fsp(meta)),
+        MYSQL_TYPE_VARCHAR | MYSQL_TYPE_VAR_STRING => "varchar".to_string(),
+        MYSQL_TYPE_STRING => "char".to_string(),
+        MYSQL_TYPE_ENUM => "enum".to_string(),
+        MYSQL_TYPE_SET => "set".to_string(),
+        MYSQL_TYPE_BIT => "bit".to_string(),
+        MYSQL_TYPE_TINY_BLOB | MYSQL_TYPE_MEDIUM_BLOB | MYSQL_TYPE_LONG_BLOB | MYSQL_TYPE_BLOB => "blob".to_string(),
+        MYSQL_TYPE_JSON => "json".to_string(),
+        MYSQL_TYPE_GEOMETRY => "geometry".to_string(),
+        MYSQL_TYPE_VECTOR => "vector".to_string(),
+        _ => format!("type({})", kind),
+    }
+}
+
+// java.sql.Types
+pub fn sql_type(info: &ColumnInfo) -> i32 {
+    match real_type_and_meta(info).0 {
+        MYSQL_TYPE_TINY => -6,
+        MYSQL_TYPE_SHORT => 5,
+        MYSQL_TYPE_INT24 | MYSQL_TYPE_LONG | MYSQL_TYPE_ENUM => 4,
+        MYSQL_TYPE_LONGLONG | MYSQL_TYPE_SET => -5,
+        MYSQL_TYPE_FLOAT => 7,
+        MYSQL_TYPE_DOUBLE => 8,
+        MYSQL_TYPE_NEWDECIMAL => 3,
+        MYSQL_TYPE_DATE | MYSQL_TYPE_NEWDATE => 91,
+        MYSQL_TYPE_TIME | MYSQL_TYPE_TIME2 => 92,
+        MYSQL_TYPE_DATETIME | MYSQL_TYPE_DATETIME2 | MYSQL_TYPE_TIMESTAMP | MYSQL_TYPE_TIMESTAMP2 => 93,
+        MYSQL_TYPE_STRING => 1,
+        MYSQL_TYPE_BIT => -7,
+        MYSQL_TYPE_TINY_BLOB | MYSQL_TYPE_MEDIUM_BLOB | MYSQL_TYPE_LONG_BLOB | MYSQL_TYPE_BLOB
+        | MYSQL_TYPE_GEOMETRY | MYSQL_TYPE_VECTOR => 2004,
+        _ => 12,
+    }
+}
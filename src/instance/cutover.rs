@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::instance::ddl::{alter_table_clauses, rename_tables, DdlResult, SqlMode};
+use crate::instance::transient::TransientTables;
+use crate::protocol::EventType;
+
+/**
+ * <pre>
+ *  一次online DDL的切换:
+ *      schema_name/table_name      业务表
+ *      ghost_table                 换入的中间表, 例如_t_gho/_t_new
+ *      old_table                   业务表被改名成的表, 例如_t_del/_t_old
+ *      clauses                     迁移期间在中间表上执行的ALTER, 从迁移中途开始dump时为空
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cutover {
+    schema_name: String,
+    table_name: String,
+    ghost_table: String,
+    old_table: String,
+    clauses: Vec<String>,
+}
+
+impl Cutover {
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn ghost_table(&self) -> &str {
+        &self.ghost_table
+    }
+    pub fn old_table(&self) -> &str {
+        &self.old_table
+    }
+    pub fn clauses(&self) -> &Vec<String> {
+        &self.clauses
+    }
+
+    // 等价的作用在业务表上的ALTER, 例如 ALTER TABLE `db`.`t` ADD COLUMN c int, DROP KEY k; 没有记录到ALTER时为None
+    pub fn sql(&self) -> Option<String> {
+        if self.clauses.is_empty() {
+            return None;
+        }
+        Some(format!("ALTER TABLE `{}`.`{}` {}", self.schema_name.replace('`', "``"),
+                     self.table_name.replace('`', "``"), self.clauses.join(", ")))
+    }
+}
+
+/**
+ * <pre>
+ *  识别gh-ost/pt-online-schema-change的切换:
+ *      CREATE TABLE _t_gho LIKE t          开始记录_t_gho上的ALTER
+ *      ALTER TABLE _t_gho ...              记录ALTER之后的原文
+ *      RENAME TABLE t TO _t_del, _t_gho TO t
+ *                                          同一条RENAME中业务表被换出, 中间表被换入, 判断为切换
+ *  中间表按TransientTables::is_ghost识别, 与ghost_table_policy无关.
+ *  DROP中间表时丢弃记录. 切换时由LogEventConvert输出业务表的Alter DDL代替原来的RENAME,
+ *  中间表本身的DDL和rows是否投递由ghost_table_policy决定
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CutoverDetector {
+    // (schema, 中间表) -> 记录的ALTER
+    pending: HashMap<(String, String), Vec<String>>,
+    cutovers: u64,
+}
+
+impl CutoverDetector {
+    pub fn new() -> CutoverDetector {
+        CutoverDetector::default()
+    }
+
+    pub fn cutovers(&self) -> u64 {
+        self.cutovers
+    }
+
+    // 正在记录ALTER的中间表数
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // schema_name为DDL操作的schema, db_name为执行时的当前库, 用于RENAME中没有指定schema的表
+    pub fn observe(&mut self, ddl: &DdlResult, schema_name: &str, db_name: &str, sql: &str, sql_mode: SqlMode,
+                   transient_tables: &TransientTables) -> Option<Cutover> {
+        let key = (schema_name.to_string(), ddl.table_name().to_string());
+        match ddl.event_type() {
+            EventType::Create | EventType::Erase if transient_tables.is_ghost(schema_name, ddl.table_name()) => {
+                self.pending.remove(&key);
+                if ddl.event_type() == EventType::Create {
+                    self.pending.insert(key, vec![]);
+                }
+                None
+            }
+            EventType::Alter if transient_tables.is_ghost(schema_name, ddl.table_name()) => {
+                if let Some(clauses) = alter_table_clauses(sql, sql_mode) {
+                    self.pending.entry(key).or_default().push(clauses);
+                }
+                None
+            }
+            EventType::Rename => self.rename(db_name, sql, sql_mode, transient_tables),
+            _ => None,
+        }
+    }
+
+    fn rename(&mut self, db_name: &str, sql: &str, sql_mode: SqlMode, transient_tables: &TransientTables)
+              -> Option<Cutover> {
+        let qualify = |(schema, table): (String, String)| {
+            (if schema.is_empty() { db_name.to_string() } else { schema }, table)
+        };
+        let pairs: Vec<_> = rename_tables(sql, sql_mode).into_iter()
+            .map(|(from, to)| (qualify(from), qualify(to)))
+            .collect();
+        // 业务表t -> old, 中间表ghost -> t
+        let (table, old, ghost) = pairs.iter().find_map(|(table, old)| {
+            let (ghost, _) = pairs.iter()
+                .find(|(ghost, to)| to == table && ghost != table && transient_tables.is_ghost(&ghost.0, &ghost.1))?;
+            Some((table, old, ghost))
+        })?;
+        let clauses = self.pending.remove(ghost).unwrap_or_default();
+        self.cutovers += 1;
+        Some(Cutover {
+            schema_name: table.0.clone(),
+            table_name: table.1.clone(),
+            ghost_table: ghost.1.clone(),
+            old_table: old.1.clone(),
+            clauses,
+        })
+    }
+}
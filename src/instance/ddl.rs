@@ -0,0 +1,790 @@
+use crate::protocol::EventType;
+
+/**
+ * <pre>
+ *  sql_mode的bit, 与mysql的MODE_*一致, QueryLogEvent的Q_SQL_MODE_CODE中保存的是展开之后的值
+ *  (例如ANSI会同时设置ANSI_QUOTES), 解析DDL时只关心影响词法的两个:
+ *      ANSI_QUOTES             双引号包围的是标识符而不是字符串
+ *      NO_BACKSLASH_ESCAPES    字符串中的反斜杠是普通字符
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SqlMode(u64);
+
+impl SqlMode {
+    pub const REAL_AS_FLOAT: u64 = 1;
+    pub const PIPES_AS_CONCAT: u64 = 1 << 1;
+    pub const ANSI_QUOTES: u64 = 1 << 2;
+    pub const IGNORE_SPACE: u64 = 1 << 3;
+    pub const ONLY_FULL_GROUP_BY: u64 = 1 << 5;
+    pub const NO_UNSIGNED_SUBTRACTION: u64 = 1 << 6;
+    pub const NO_DIR_IN_CREATE: u64 = 1 << 7;
+    pub const ANSI: u64 = 1 << 18;
+    pub const NO_AUTO_VALUE_ON_ZERO: u64 = 1 << 19;
+    pub const NO_BACKSLASH_ESCAPES: u64 = 1 << 20;
+    pub const STRICT_TRANS_TABLES: u64 = 1 << 21;
+    pub const STRICT_ALL_TABLES: u64 = 1 << 22;
+    pub const NO_ZERO_IN_DATE: u64 = 1 << 23;
+    pub const NO_ZERO_DATE: u64 = 1 << 24;
+    pub const ALLOW_INVALID_DATES: u64 = 1 << 25;
+    pub const ERROR_FOR_DIVISION_BY_ZERO: u64 = 1 << 26;
+    pub const TRADITIONAL: u64 = 1 << 27;
+    pub const HIGH_NOT_PRECEDENCE: u64 = 1 << 29;
+    pub const NO_ENGINE_SUBSTITUTION: u64 = 1 << 30;
+    pub const PAD_CHAR_TO_FULL_LENGTH: u64 = 1 << 31;
+
+    const NAMES: [(&'static str, u64); 19] = [
+        ("REAL_AS_FLOAT", SqlMode::REAL_AS_FLOAT),
+        ("PIPES_AS_CONCAT", SqlMode::PIPES_AS_CONCAT),
+        ("ANSI_QUOTES", SqlMode::ANSI_QUOTES),
+        ("IGNORE_SPACE", SqlMode::IGNORE_SPACE),
+        ("ONLY_FULL_GROUP_BY", SqlMode::ONLY_FULL_GROUP_BY),
+        ("NO_UNSIGNED_SUBTRACTION", SqlMode::NO_UNSIGNED_SUBTRACTION),
+        ("NO_DIR_IN_CREATE", SqlMode::NO_DIR_IN_CREATE),
+        ("ANSI", SqlMode::ANSI),
+        ("NO_AUTO_VALUE_ON_ZERO", SqlMode::NO_AUTO_VALUE_ON_ZERO),
+        ("NO_BACKSLASH_ESCAPES", SqlMode::NO_BACKSLASH_ESCAPES),
+        ("STRICT_TRANS_TABLES", SqlMode::STRICT_TRANS_TABLES),
+        ("STRICT_ALL_TABLES", SqlMode::STRICT_ALL_TABLES),
+        ("NO_ZERO_IN_DATE", SqlMode::NO_ZERO_IN_DATE),
+        ("NO_ZERO_DATE", SqlMode::NO_ZERO_DATE),
+        ("ALLOW_INVALID_DATES", SqlMode::ALLOW_INVALID_DATES),
+        ("ERROR_FOR_DIVISION_BY_ZERO", SqlMode::ERROR_FOR_DIVISION_BY_ZERO),
+        ("TRADITIONAL", SqlMode::TRADITIONAL),
+        ("HIGH_NOT_PRECEDENCE", SqlMode::HIGH_NOT_PRECEDENCE),
+        ("NO_ENGINE_SUBSTITUTION", SqlMode::NO_ENGINE_SUBSTITUTION),
+    ];
+
+    pub fn new(bits: u64) -> SqlMode {
+        SqlMode(bits)
+    }
+
+    /**
+     * <pre>
+     *  解析@@sql_mode的值, 例如 ANSI_QUOTES,STRICT_TRANS_TABLES.
+     *  组合模式按mysql的规则展开: ANSI包含ANSI_QUOTES, 不认识的名字忽略
+     * </pre>
+     */
+    pub fn from_names(names: &str) -> SqlMode {
+        let mut bits = 0;
+        for name in names.split(',').map(|name| name.trim().to_ascii_uppercase()) {
+            if let Some((_, bit)) = SqlMode::NAMES.iter().find(|(known, _)| *known == name) {
+                bits |= bit;
+            }
+            if name == "ANSI" {
+                bits |= SqlMode::REAL_AS_FLOAT | SqlMode::PIPES_AS_CONCAT | SqlMode::ANSI_QUOTES
+                    | SqlMode::IGNORE_SPACE | SqlMode::ONLY_FULL_GROUP_BY;
+            }
+        }
+        SqlMode(bits)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub fn contains(&self, bit: u64) -> bool {
+        self.0 & bit == bit
+    }
+
+    pub fn ansi_quotes(&self) -> bool {
+        self.contains(SqlMode::ANSI_QUOTES)
+    }
+
+    pub fn no_backslash_escapes(&self) -> bool {
+        self.contains(SqlMode::NO_BACKSLASH_ESCAPES)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        SqlMode::NAMES.iter().filter(|(_, bit)| self.contains(*bit)).map(|(name, _)| *name).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DdlToken {
+    // 没有引号的关键字或者标识符
+    Word(String),
+    // `name`, ANSI_QUOTES下也包括"name"
+    Identifier(String),
+    // 'text', 非ANSI_QUOTES下也包括"text"
+    Literal(String),
+    Symbol(char),
+}
+
+impl DdlToken {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, DdlToken::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            DdlToken::Word(name) | DdlToken::Identifier(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  按照sql_mode对DDL做词法切分:
+ *  1. 跳过 -- / # / 注释, 可执行注释 版本号 ... 中的内容作为正常语句处理
+ *  2. 引号内连续两个引号表示引号本身, 反斜杠转义只在字符串中且没有NO_BACKSLASH_ESCAPES时生效
+ *  3. 未闭合的引号读到语句末尾, 不返回错误
+ * </pre>
+ */
+pub fn tokenize(sql: &str, sql_mode: SqlMode) -> Vec<DdlToken> {
+    token_spans(sql, sql_mode).into_iter().map(|(token, _)| token).collect()
+}
+
+// 与tokenize相同, 同时返回每个token结束位置的字符下标
+fn token_spans(sql: &str, sql_mode: SqlMode) -> Vec<(DdlToken, usize)> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '#' || (c == '-' && next == Some('-') && chars.get(i + 2).is_none_or(|c| c.is_whitespace())) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            if chars.get(i + 2) == Some(&'!') {
+                // 可执行注释, 去掉版本号后继续切分
+                i += 3;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            } else {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+        } else if c == '*' && next == Some('/') {
+            // 可执行注释的结尾
+            i += 2;
+        } else if c == '`' || c == '"' || c == '\'' {
+            let identifier = c == '`' || (c == '"' && sql_mode.ansi_quotes());
+            let escapes = !identifier && !sql_mode.no_backslash_escapes();
+            let (text, end) = quoted(&chars, i, escapes);
+            tokens.push((if identifier { DdlToken::Identifier(text) } else { DdlToken::Literal(text) }, end));
+            i = end;
+        } else if c.is_alphanumeric() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push((DdlToken::Word(chars[start..i].iter().collect()), i));
+        } else {
+            tokens.push((DdlToken::Symbol(c), i + 1));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+// 返回引号内的内容和结束引号之后的位置
+fn quoted(chars: &[char], start: usize, escapes: bool) -> (String, usize) {
+    let quote = chars[start];
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        let c = chars[i];
+        if escapes && c == '\\' && i + 1 < chars.len() {
+            text.push(chars[i + 1]);
+            i += 2;
+        } else if c == quote && chars.get(i + 1) == Some(&quote) {
+            text.push(quote);
+            i += 2;
+        } else if c == quote {
+            return (text, i + 1);
+        } else {
+            text.push(c);
+            i += 1;
+        }
+    }
+    (text, i)
+}
+
+// DDL的类型和操作的对象, schema为空时使用QueryLogEvent的db_name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdlResult {
+    event_type: EventType,
+    schema_name: String,
+    table_name: String,
+    // CREATE/DROP TEMPORARY TABLE
+    temporary: bool,
+}
+
+impl DdlResult {
+    pub fn event_type(&self) -> EventType {
+        self.event_type
+    }
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn is_temporary(&self) -> bool {
+        self.temporary
+    }
+}
+
+/**
+ * <pre>
+ *  识别DDL的类型和第一个操作对象, 对应canal中SimpleDdlParser的范围:
+ *      CREATE [TEMPORARY] TABLE [IF NOT EXISTS] t      Create
+ *      CREATE [UNIQUE|FULLTEXT|SPATIAL] INDEX i ON t   CIndex
+ *      ALTER [ONLINE|IGNORE] TABLE t                   Alter
+ *      DROP [TEMPORARY] TABLE [IF EXISTS] t            Erase
+ *      DROP INDEX i ON t                               DIndex
+ *      TRUNCATE [TABLE] t                              Truncate
+ *      RENAME TABLE t TO ...                           Rename
+ *      CREATE/DROP/ALTER DATABASE|SCHEMA s             Create/Erase/Alter, 只有schema
+ *  其它语句为Query, 没有操作对象. 带TEMPORARY时is_temporary为true
+ * </pre>
+ */
+pub fn parse_ddl(sql: &str, sql_mode: SqlMode) -> DdlResult {
+    let tokens = tokenize(sql, sql_mode);
+    let mut parser = DdlTokens { tokens: &tokens, index: 0 };
+    let mut result = DdlResult {
+        event_type: EventType::Query,
+        schema_name: String::new(),
+        table_name: String::new(),
+        temporary: false,
+    };
+    let event_type = match parser.next_word().as_deref() {
+        Some("CREATE") => {
+            let start = parser.index;
+            parser.skip_any(&["OR", "REPLACE", "TEMPORARY", "UNIQUE", "FULLTEXT", "SPATIAL"]);
+            result.temporary = parser.tokens[start..parser.index].iter().any(|token| token.is_keyword("TEMPORARY"));
+            match parser.next_word().as_deref() {
+                Some("TABLE") => EventType::Create,
+                Some("INDEX") => EventType::CIndex,
+                Some("DATABASE") | Some("SCHEMA") => return parser.database(result, EventType::Create),
+                _ => return result,
+            }
+        }
+        Some("ALTER") => {
+            parser.skip_any(&["ONLINE", "OFFLINE", "IGNORE"]);
+            match parser.next_word().as_deref() {
+                Some("TABLE") => EventType::Alter,
+                Some("DATABASE") | Some("SCHEMA") => return parser.database(result, EventType::Alter),
+                _ => return result,
+            }
+        }
+        Some("DROP") => {
+            result.temporary = parser.peek().is_some_and(|token| token.is_keyword("TEMPORARY"));
+            parser.skip_any(&["TEMPORARY"]);
+            match parser.next_word().as_deref() {
+                Some("TABLE") | Some("TABLES") => EventType::Erase,
+                Some("INDEX") => EventType::DIndex,
+                Some("DATABASE") | Some("SCHEMA") => return parser.database(result, EventType::Erase),
+                _ => return result,
+            }
+        }
+        Some("TRUNCATE") => {
+            parser.skip_any(&["TABLE"]);
+            EventType::Truncate
+        }
+        Some("RENAME") => match parser.next_word().as_deref() {
+            Some("TABLE") | Some("TABLES") => EventType::Rename,
+            _ => return result,
+        },
+        _ => return result,
+    };
+    result.event_type = event_type;
+    if matches!(event_type, EventType::CIndex | EventType::DIndex) {
+        // 跳过索引名, 表名在ON之后
+        while let Some(token) = parser.next() {
+            if token.is_keyword("ON") {
+                break;
+            }
+        }
+    } else {
+        parser.skip_if_exists();
+    }
+    if let Some((schema, table)) = parser.qualified_name() {
+        result.schema_name = schema;
+        result.table_name = table;
+    }
+    result
+}
+
+// RENAME TABLE a TO b, c TO d 中每一对(原表, 新表), 均为(schema, table), schema为空时为当前库; 不是RENAME TABLE时为空
+pub fn rename_tables(sql: &str, sql_mode: SqlMode) -> Vec<((String, String), (String, String))> {
+    let tokens = tokenize(sql, sql_mode);
+    let mut parser = DdlTokens { tokens: &tokens, index: 0 };
+    let mut pairs = vec![];
+    if parser.next_word().as_deref() != Some("RENAME")
+        || !matches!(parser.next_word().as_deref(), Some("TABLE") | Some("TABLES")) {
+        return pairs;
+    }
+    while let Some(from) = parser.qualified_name() {
+        if parser.next_word().as_deref() != Some("TO") {
+            break;
+        }
+        match parser.qualified_name() {
+            Some(to) => pairs.push((from, to)),
+            None => break,
+        }
+        if parser.next() != Some(&DdlToken::Symbol(',')) {
+            break;
+        }
+    }
+    pairs
+}
+
+// ALTER TABLE t之后的原文, 例如 "ADD COLUMN c int", 不是ALTER TABLE或者没有内容时返回None
+pub fn alter_table_clauses(sql: &str, sql_mode: SqlMode) -> Option<String> {
+    let spans = token_spans(sql, sql_mode);
+    let tokens: Vec<DdlToken> = spans.iter().map(|(token, _)| token.clone()).collect();
+    let mut parser = DdlTokens { tokens: &tokens, index: 0 };
+    if parser.next_word().as_deref() != Some("ALTER") {
+        return None;
+    }
+    parser.skip_any(&["ONLINE", "OFFLINE", "IGNORE"]);
+    if parser.next_word().as_deref() != Some("TABLE") {
+        return None;
+    }
+    parser.qualified_name()?;
+    let end = spans[parser.index - 1].1;
+    let clauses = sql.chars().skip(end).collect::<String>().trim().trim_end_matches(';').trim().to_string();
+    if clauses.is_empty() { None } else { Some(clauses) }
+}
+
+// CREATE/ALTER TABLE中的列定义, 只保留解码rows event需要的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDefinition {
+    name: String,
+    // 小写的类型和参数, 例如 varchar(32), decimal(10,2), enum('a','b')
+    data_type: String,
+    unsigned: bool,
+    nullable: bool,
+    primary_key: bool,
+}
+
+impl ColumnDefinition {
+    pub fn new(name: &str, data_type: &str) -> ColumnDefinition {
+        ColumnDefinition { name: name.to_string(), data_type: data_type.to_string(), unsigned: false, nullable: true,
+                           primary_key: false }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn data_type(&self) -> &str {
+        &self.data_type
+    }
+    pub fn unsigned(&self) -> bool {
+        self.unsigned
+    }
+    pub fn nullable(&self) -> bool {
+        self.nullable
+    }
+    pub fn primary_key(&self) -> bool {
+        self.primary_key
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    // 主键列总是NOT NULL
+    pub fn set_primary_key(&mut self, primary_key: bool) {
+        self.primary_key = primary_key;
+        if primary_key {
+            self.nullable = false;
+        }
+    }
+}
+
+// ADD/MODIFY/CHANGE COLUMN之后的FIRST/AFTER, 没有时为Last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnPosition {
+    Last,
+    First,
+    After(String),
+}
+
+// ALTER TABLE中影响列的操作, 索引/分区/表选项等不影响rows event的操作不返回
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlterOperation {
+    AddColumn(ColumnDefinition, ColumnPosition),
+    DropColumn(String),
+    // MODIFY时原列名与新定义的列名相同
+    ChangeColumn(String, ColumnDefinition, ColumnPosition),
+    RenameColumn(String, String),
+    AddPrimaryKey(Vec<String>),
+    DropPrimaryKey,
+    // RENAME [TO|AS] (schema, table), schema为空时为当前库
+    RenameTable(String, String),
+}
+
+// 改变表结构的DDL, 表名均为(schema, table), schema为空时为QueryLogEvent的db_name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableChange {
+    // columns为空表示无法从语句得到表结构, 例如CREATE TABLE ... SELECT
+    Create { table: (String, String), if_not_exists: bool, columns: Vec<ColumnDefinition> },
+    CreateLike { table: (String, String), like: (String, String) },
+    Alter { table: (String, String), operations: Vec<AlterOperation> },
+    Drop(Vec<(String, String)>),
+    Rename(Vec<((String, String), (String, String))>),
+    DropDatabase(String),
+}
+
+/**
+ * <pre>
+ *  解析改变表结构的DDL, 用于按位点维护表结构的历史版本:
+ *      CREATE TABLE [IF NOT EXISTS] t (列定义, PRIMARY KEY (...), 索引...)
+ *      CREATE TABLE t LIKE s
+ *      ALTER TABLE t ADD/DROP/MODIFY/CHANGE [COLUMN], RENAME COLUMN, ADD/DROP PRIMARY KEY, RENAME [TO|AS]
+ *      DROP TABLE [IF EXISTS] t1, t2
+ *      RENAME TABLE t1 TO t2, ...
+ *      DROP DATABASE|SCHEMA s
+ *  临时表不出现在row格式的binlog中, 与其它不改变表结构的语句一样返回None
+ * </pre>
+ */
+pub fn parse_table_change(sql: &str, sql_mode: SqlMode) -> Option<TableChange> {
+    let tokens = tokenize(sql, sql_mode);
+    let mut parser = DdlTokens { tokens: &tokens, index: 0 };
+    match parser.next_word()?.as_str() {
+        "CREATE" => {
+            parser.skip_any(&["OR", "REPLACE"]);
+            if parser.next_word()? != "TABLE" {
+                return None;
+            }
+            let if_not_exists = parser.peek().is_some_and(|token| token.is_keyword("IF"));
+            parser.skip_if_exists();
+            let table = parser.qualified_name()?;
+            // CREATE TABLE t LIKE s 或者 CREATE TABLE t (LIKE s)
+            let like = |index: usize| tokens.get(index).is_some_and(|token| token.is_keyword("LIKE"));
+            if like(parser.index) || (parser.peek() == Some(&DdlToken::Symbol('(')) && like(parser.index + 1)) {
+                parser.skip_any(&["LIKE"]);
+                let like = match parser.group() {
+                    Some(group) => DdlTokens { tokens: &group[1..], index: 0 }.qualified_name()?,
+                    None => parser.qualified_name()?,
+                };
+                return Some(TableChange::CreateLike { table, like });
+            }
+            let columns = parser.group().map(create_definitions).unwrap_or_default();
+            // CREATE TABLE ... SELECT的列来自查询结果
+            let select = tokens[parser.index..].iter().any(|token| token.is_keyword("SELECT"));
+            Some(TableChange::Create { table, if_not_exists, columns: if select { vec![] } else { columns } })
+        }
+        "ALTER" => {
+            parser.skip_any(&["ONLINE", "OFFLINE", "IGNORE"]);
+            if parser.next_word()? != "TABLE" {
+                return None;
+            }
+            let table = parser.qualified_name()?;
+            let operations = split_elements(&tokens[parser.index..]).into_iter().flat_map(alter_operations).collect();
+            Some(TableChange::Alter { table, operations })
+        }
+        "DROP" => match parser.next_word()?.as_str() {
+            "TABLE" | "TABLES" => {
+                parser.skip_if_exists();
+                let mut tables = vec![];
+                while let Some(table) = parser.qualified_name() {
+                    tables.push(table);
+                    if parser.next() != Some(&DdlToken::Symbol(',')) {
+                        break;
+                    }
+                }
+                Some(TableChange::Drop(tables))
+            }
+            "DATABASE" | "SCHEMA" => {
+                parser.skip_if_exists();
+                Some(TableChange::DropDatabase(parser.next()?.name()?.to_string()))
+            }
+            _ => None,
+        },
+        "RENAME" => {
+            let tables = rename_tables(sql, sql_mode);
+            if tables.is_empty() { None } else { Some(TableChange::Rename(tables)) }
+        }
+        _ => None,
+    }
+}
+
+// 按最外层的逗号切分, 用于CREATE TABLE的定义列表和ALTER TABLE的操作列表
+fn split_elements(tokens: &[DdlToken]) -> Vec<&[DdlToken]> {
+    let mut elements = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            DdlToken::Symbol('(') => depth += 1,
+            DdlToken::Symbol(')') => depth -= 1,
+            DdlToken::Symbol(',') if depth == 0 => {
+                elements.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(&tokens[start..]);
+    elements.into_iter().filter(|element| !element.is_empty()).collect()
+}
+
+// 索引, 外键, 约束等不是列的定义
+const NON_COLUMN_KEYWORDS: [&str; 9] =
+    ["KEY", "INDEX", "UNIQUE", "FULLTEXT", "SPATIAL", "FOREIGN", "CHECK", "CONSTRAINT", "PARTITION"];
+
+fn is_non_column(token: &DdlToken) -> bool {
+    NON_COLUMN_KEYWORDS.iter().any(|keyword| token.is_keyword(keyword))
+}
+
+fn create_definitions(tokens: &[DdlToken]) -> Vec<ColumnDefinition> {
+    let mut columns = vec![];
+    let mut primary_key = vec![];
+    for element in split_elements(tokens) {
+        let mut parser = DdlTokens { tokens: element, index: 0 };
+        if let Some(names) = parser.primary_key() {
+            primary_key.extend(names);
+        } else if !element[0].is_keyword("PRIMARY") && !is_non_column(&element[0]) {
+            columns.extend(parser.column_definition());
+        }
+    }
+    for column in columns.iter_mut() {
+        if primary_key.iter().any(|name| name.eq_ignore_ascii_case(column.name())) {
+            column.set_primary_key(true);
+        }
+    }
+    columns
+}
+
+fn alter_operations(tokens: &[DdlToken]) -> Vec<AlterOperation> {
+    let mut parser = DdlTokens { tokens, index: 0 };
+    let operation = match parser.next_word().as_deref() {
+        Some("ADD") => {
+            if let Some(names) = parser.primary_key() {
+                return vec![AlterOperation::AddPrimaryKey(names)];
+            }
+            if parser.peek().is_some_and(is_non_column) {
+                return vec![];
+            }
+            parser.skip_any(&["COLUMN"]);
+            // ADD COLUMN (c1 int, c2 int) 依次加在最后
+            if let Some(group) = parser.group() {
+                return create_definitions(group).into_iter()
+                    .map(|column| AlterOperation::AddColumn(column, ColumnPosition::Last))
+                    .collect();
+            }
+            parser.skip_if_exists();
+            parser.column_definition().map(|column| AlterOperation::AddColumn(column, parser.column_position()))
+        }
+        Some("DROP") => {
+            if parser.peek().is_some_and(|token| token.is_keyword("PRIMARY")) {
+                return vec![AlterOperation::DropPrimaryKey];
+            }
+            if parser.peek().is_some_and(is_non_column) {
+                return vec![];
+            }
+            parser.skip_any(&["COLUMN"]);
+            parser.skip_if_exists();
+            parser.next().and_then(|token| token.name()).map(|name| AlterOperation::DropColumn(name.to_string()))
+        }
+        Some("MODIFY") => {
+            parser.skip_any(&["COLUMN"]);
+            parser.column_definition()
+                .map(|column| AlterOperation::ChangeColumn(column.name().to_string(), column, parser.column_position()))
+        }
+        Some("CHANGE") => {
+            parser.skip_any(&["COLUMN"]);
+            let old = parser.next().and_then(|token| token.name()).map(|name| name.to_string());
+            old.zip(parser.column_definition())
+                .map(|(old, column)| AlterOperation::ChangeColumn(old, column, parser.column_position()))
+        }
+        Some("RENAME") => match parser.peek() {
+            Some(token) if token.is_keyword("COLUMN") => {
+                parser.index += 1;
+                let old = parser.next().and_then(|token| token.name()).map(|name| name.to_string());
+                parser.skip_any(&["TO"]);
+                let new = parser.next().and_then(|token| token.name()).map(|name| name.to_string());
+                old.zip(new).map(|(old, new)| AlterOperation::RenameColumn(old, new))
+            }
+            Some(token) if token.is_keyword("INDEX") || token.is_keyword("KEY") => None,
+            _ => {
+                parser.skip_any(&["TO", "AS"]);
+                parser.qualified_name().map(|(schema, table)| AlterOperation::RenameTable(schema, table))
+            }
+        },
+        _ => None,
+    };
+    operation.into_iter().collect()
+}
+
+// 类型参数的原文, 例如 10,2 或者 'a','b'
+fn render(tokens: &[DdlToken]) -> String {
+    tokens.iter().map(|token| match token {
+        DdlToken::Word(word) | DdlToken::Identifier(word) => word.to_ascii_lowercase(),
+        DdlToken::Literal(text) => format!("'{}'", text.replace('\'', "''")),
+        DdlToken::Symbol(c) => c.to_string(),
+    }).collect()
+}
+
+struct DdlTokens<'a> {
+    tokens: &'a [DdlToken],
+    index: usize,
+}
+
+impl<'a> DdlTokens<'a> {
+    fn next(&mut self) -> Option<&'a DdlToken> {
+        let token = self.tokens.get(self.index)?;
+        self.index += 1;
+        Some(token)
+    }
+
+    fn peek(&self) -> Option<&'a DdlToken> {
+        self.tokens.get(self.index)
+    }
+
+    // 大写的关键字, 下一个不是Word时返回None且不前进
+    fn next_word(&mut self) -> Option<String> {
+        match self.peek()? {
+            DdlToken::Word(word) => {
+                self.index += 1;
+                Some(word.to_ascii_uppercase())
+            }
+            _ => None,
+        }
+    }
+
+    fn skip_any(&mut self, keywords: &[&str]) {
+        while self.peek().is_some_and(|token| keywords.iter().any(|keyword| token.is_keyword(keyword))) {
+            self.index += 1;
+        }
+    }
+
+    // IF [NOT] EXISTS
+    fn skip_if_exists(&mut self) {
+        if self.peek().is_some_and(|token| token.is_keyword("IF")) {
+            self.index += 1;
+            self.skip_any(&["NOT"]);
+            self.skip_any(&["EXISTS"]);
+        }
+    }
+
+    // name 或者 schema.name
+    fn qualified_name(&mut self) -> Option<(String, String)> {
+        let first = self.next()?.name()?.to_string();
+        if self.peek() == Some(&DdlToken::Symbol('.')) {
+            self.index += 1;
+            let second = self.next()?.name()?.to_string();
+            return Some((first, second));
+        }
+        Some((String::new(), first))
+    }
+
+    // (...)中的token并跳过右括号, 下一个不是(时返回None且不前进; 没有闭合时到末尾为止
+    fn group(&mut self) -> Option<&'a [DdlToken]> {
+        if self.peek() != Some(&DdlToken::Symbol('(')) {
+            return None;
+        }
+        let start = self.index + 1;
+        let mut depth = 0;
+        while let Some(token) = self.next() {
+            match token {
+                DdlToken::Symbol('(') => depth += 1,
+                DdlToken::Symbol(')') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(&self.tokens[start..self.index - 1]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(&self.tokens[start..])
+    }
+
+    // [CONSTRAINT [symbol]] PRIMARY KEY [USING BTREE] (c1, c2(10) DESC), 不是主键时返回None且不前进
+    fn primary_key(&mut self) -> Option<Vec<String>> {
+        let start = self.index;
+        if self.peek().is_some_and(|token| token.is_keyword("CONSTRAINT")) {
+            self.index += 1;
+            if !self.peek().is_some_and(|token| token.is_keyword("PRIMARY")) {
+                self.index += 1;
+            }
+        }
+        if !self.peek().is_some_and(|token| token.is_keyword("PRIMARY")) {
+            self.index = start;
+            return None;
+        }
+        self.index += 1;
+        self.skip_any(&["KEY"]);
+        while self.peek().is_some_and(|token| token != &DdlToken::Symbol('(')) {
+            self.index += 1;
+        }
+        let parts = self.group()?;
+        Some(split_elements(parts).into_iter().filter_map(|part| part[0].name().map(|name| name.to_string())).collect())
+    }
+
+    /**
+     * <pre>
+     *  name type[(args)] [UNSIGNED] [NOT NULL] [PRIMARY KEY|KEY] ..., 遇到FIRST/AFTER时停止.
+     *  只识别影响rows event解码的属性, DEFAULT/COMMENT/GENERATED AS (expr)等跳过
+     * </pre>
+     */
+    fn column_definition(&mut self) -> Option<ColumnDefinition> {
+        let name = self.next()?.name()?.to_string();
+        let mut data_type = match self.next()? {
+            DdlToken::Word(word) => word.to_ascii_lowercase(),
+            _ => return None,
+        };
+        if let Some(args) = self.group() {
+            data_type.push_str(&format!("({})", render(args)));
+        }
+        let mut column = ColumnDefinition::new(&name, &data_type);
+        while let Some(token) = self.peek() {
+            if token.is_keyword("FIRST") || token.is_keyword("AFTER") {
+                break;
+            }
+            if token == &DdlToken::Symbol('(') {
+                self.group();
+                continue;
+            }
+            self.index += 1;
+            let next_is = |keyword: &str| self.peek().is_some_and(|token| token.is_keyword(keyword));
+            if token.is_keyword("UNSIGNED") {
+                column.unsigned = true;
+            } else if token.is_keyword("NOT") && next_is("NULL") {
+                column.nullable = false;
+                self.index += 1;
+            } else if token.is_keyword("UNIQUE") {
+                self.skip_any(&["KEY"]);
+            } else if token.is_keyword("PRIMARY") || token.is_keyword("KEY") {
+                // 列定义中单独的KEY等同于PRIMARY KEY
+                self.skip_any(&["KEY"]);
+                column.set_primary_key(true);
+            }
+        }
+        Some(column)
+    }
+
+    fn column_position(&mut self) -> ColumnPosition {
+        match self.next_word().as_deref() {
+            Some("FIRST") => ColumnPosition::First,
+            Some("AFTER") => match self.next().and_then(|token| token.name()) {
+                Some(name) => ColumnPosition::After(name.to_string()),
+                None => ColumnPosition::Last,
+            },
+            _ => ColumnPosition::Last,
+        }
+    }
+
+    fn database(&mut self, mut result: DdlResult, event_type: EventType) -> DdlResult {
+        result.event_type = event_type;
+        self.skip_if_exists();
+        if let Some(name) = self.next().and_then(|token| token.name()) {
+            result.schema_name = name.to_string();
+        }
+        result
+    }
+}
@@ -0,0 +1,282 @@
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::command::event::{DecodeStep, LogContext, RowsLogEvent};
+use crate::config::Properties;
+use crate::filter::RegexFilter;
+use crate::instance::convert::LogEventConvert;
+use crate::instance::EntryPosition;
+use crate::protocol::EventType;
+use crate::sink::mq::flat_message::json_string;
+
+// 最多记录的rows event数, 避免忘记关闭时占满内存
+pub const DEFAULT_MAX_TRACES: usize = 100;
+
+/**
+ * <pre>
+ *  一个rows event的解码过程:
+ *      position                event的起始位点
+ *      rows_length             行数据部分的字节数, steps中的offset相对于行数据的开头
+ *      column_count/columns    rows event中的列数以及before image中出现的列(bitmap, 1为出现)
+ *      steps                   见DecodeStep, 解码失败时最后一步为error
+ *      error                   解码失败的原因
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeTrace {
+    position: EntryPosition,
+    schema_name: String,
+    table_name: String,
+    table_id: u64,
+    event_type: EventType,
+    rows_length: usize,
+    column_count: usize,
+    columns: String,
+    steps: Vec<DecodeStep>,
+    error: Option<String>,
+}
+
+impl DecodeTrace {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(position: EntryPosition, schema_name: &str, table_name: &str, table_id: u64, event_type: EventType,
+               rows_length: usize, columns: &[bool], steps: Vec<DecodeStep>, error: Option<String>) -> DecodeTrace {
+        DecodeTrace {
+            position,
+            schema_name: schema_name.to_string(),
+            table_name: table_name.to_string(),
+            table_id,
+            event_type,
+            rows_length,
+            column_count: columns.len(),
+            columns: columns.iter().map(|present| if *present { '1' } else { '0' }).collect(),
+            steps,
+            error,
+        }
+    }
+
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+    pub fn event_type(&self) -> EventType {
+        self.event_type
+    }
+    pub fn rows_length(&self) -> usize {
+        self.rows_length
+    }
+    pub fn column_count(&self) -> usize {
+        self.column_count
+    }
+    pub fn steps(&self) -> &Vec<DecodeStep> {
+        &self.steps
+    }
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /**
+     * <pre>
+     *  {"logfile":"mysql-bin.000001","offset":4,"schema":"db","table":"t","table_id":70,"type":"INSERT",
+     *   "rows_length":12,"column_count":2,"columns":"11","error":null,
+     *   "steps":[{"offset":0,"length":1,"kind":"null_bitmap","column":null,"detail":"present=2, bitmap=00"},
+     *            {"offset":1,"length":4,"kind":"value","column":0,"type":3,"meta":0,"real_type":3,"real_meta":0,
+     *             "detail":"1"}]}
+     * </pre>
+     */
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"logfile\":{},\"offset\":{},\"schema\":{},\"table\":{},\"table_id\":{},\"type\":{},\
+                             \"rows_length\":{},\"column_count\":{},\"columns\":{},\"error\":{},\"steps\":[",
+                       json_string(self.position.journal_name()), self.position.position(),
+                       json_string(&self.schema_name), json_string(&self.table_name), self.table_id,
+                       json_string(&format!("{:?}", self.event_type).to_ascii_uppercase()), self.rows_length,
+                       self.column_count, json_string(&self.columns),
+                       self.error.as_deref().map_or("null".to_string(), json_string));
+        for (i, step) in self.steps.iter().enumerate() {
+            let _ = write!(out, "{}{{\"offset\":{},\"length\":{},\"kind\":{},\"column\":{}", if i > 0 { "," } else { "" },
+                           step.offset(), step.length(), json_string(step.kind()),
+                           step.column().map_or("null".to_string(), |column| column.to_string()));
+            if let Some((kind, meta, real_type, real_meta)) = step.column_type() {
+                let _ = write!(out, ",\"type\":{},\"meta\":{},\"real_type\":{},\"real_meta\":{}", kind, meta, real_type,
+                               real_meta);
+            }
+            let _ = write!(out, ",\"detail\":{}}}", json_string(step.detail()));
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/**
+ * <pre>
+ *  开发者排查行解码错位时使用的trace模式, 对选中的rows event额外按trace方式解码一次,
+ *  记录每一步读取的位置/长度/类型, 不影响正常的解码和投递:
+ *      from/to         event起始位点的范围(包含两端), 按(文件名, 位置)比较, 不指定时不限制
+ *      tables          RegexFilter格式的表名, 例如shop\\.orders, 不指定时不限制
+ *      max_traces      最多记录的event数, 达到之后不再记录
+ *      output          每记录一个event追加一行json(见DecodeTrace::to_json), 解码失败导致进程退出时也不会丢失
+ *  句柄可以clone, 多个parser/离线解析的worker共享同一份记录.
+ *  配置: master.decode_trace.from=mysql-bin.000003:4, master.decode_trace.to=mysql-bin.000003:1048576,
+ *       master.decode_trace.tables=shop\\.orders, master.decode_trace.max_traces=100,
+ *       master.decode_trace.output=/tmp/decode_trace.jsonl
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct DecodeTracer {
+    from: Option<EntryPosition>,
+    to: Option<EntryPosition>,
+    tables: Option<RegexFilter>,
+    max_traces: usize,
+    output: Option<PathBuf>,
+    traces: Arc<Mutex<Vec<DecodeTrace>>>,
+}
+
+impl Default for DecodeTracer {
+    fn default() -> Self {
+        DecodeTracer::new()
+    }
+}
+
+impl DecodeTracer {
+    pub fn new() -> DecodeTracer {
+        DecodeTracer {
+            from: None,
+            to: None,
+            tables: None,
+            max_traces: DEFAULT_MAX_TRACES,
+            output: None,
+            traces: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    // 没有配置任何master.decode_trace.*时返回None
+    pub fn from_properties(properties: &Properties) -> Result<Option<DecodeTracer>, String> {
+        if !properties.keys().any(|key| key.starts_with("master.decode_trace.")) {
+            return Ok(None);
+        }
+        let position = |key: &str| properties.get(key).map(|value| parse_trace_position(key, value)).transpose();
+        let mut tracer = DecodeTracer::new();
+        tracer.set_range(position("master.decode_trace.from")?, position("master.decode_trace.to")?);
+        if let Some(pattern) = properties.get("master.decode_trace.tables") {
+            tracer.set_tables(Some(RegexFilter::new(pattern)?));
+        }
+        if let Some(value) = properties.get("master.decode_trace.max_traces") {
+            let max_traces = value.trim().parse::<usize>()
+                .map_err(|_| format!("master.decode_trace.max_traces: invalid number {}", value))?;
+            tracer.set_max_traces(max_traces);
+        }
+        if let Some(output) = properties.get("master.decode_trace.output") {
+            tracer.set_output(Some(Path::new(output.trim())));
+        }
+        Ok(Some(tracer))
+    }
+
+    pub fn set_range(&mut self, from: Option<EntryPosition>, to: Option<EntryPosition>) {
+        self.from = from;
+        self.to = to;
+    }
+
+    pub fn set_tables(&mut self, tables: Option<RegexFilter>) {
+        self.tables = tables.filter(|filter| !filter.pattern().is_empty());
+    }
+
+    pub fn set_max_traces(&mut self, max_traces: usize) {
+        self.max_traces = max_traces;
+    }
+
+    pub fn set_output(&mut self, output: Option<&Path>) {
+        self.output = output.map(|output| output.to_path_buf());
+    }
+
+    pub fn from(&self) -> Option<&EntryPosition> {
+        self.from.as_ref()
+    }
+    pub fn to(&self) -> Option<&EntryPosition> {
+        self.to.as_ref()
+    }
+    pub fn tables(&self) -> Option<&RegexFilter> {
+        self.tables.as_ref()
+    }
+    pub fn max_traces(&self) -> usize {
+        self.max_traces
+    }
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    // position为rows event的起始位点
+    pub fn matches(&self, position: &EntryPosition, schema_name: &str, table_name: &str) -> bool {
+        let key = |position: &EntryPosition| (position.journal_name().to_string(), position.position());
+        let in_range = self.from.as_ref().is_none_or(|from| key(from) <= key(position))
+            && self.to.as_ref().is_none_or(|to| key(position) <= key(to));
+        let table_matches = self.tables.as_ref()
+            .is_none_or(|tables| tables.matches(&format!("{}.{}", schema_name, table_name)));
+        in_range && table_matches && self.len() < self.max_traces
+    }
+
+    // 选中的rows event按trace方式解码一次并记录, 在正常解码之前调用, 正常解码失败时trace中已经有失败的位置
+    pub fn trace(&self, convert: &LogEventConvert, rows: &RowsLogEvent, context: &LogContext) -> Result<(), String> {
+        let table = match context.get_table(rows.table_id()) {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        let offset = (rows.header().log_pos() as u64).saturating_sub(rows.header().event_len() as u64);
+        let position = EntryPosition::new(context.log_position().journal_name(), offset);
+        if !self.matches(&position, table.db_name(), table.table_name()) {
+            return Ok(());
+        }
+        match convert.trace_rows(rows, context) {
+            Some(trace) => self.record(trace),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record(&self, trace: DecodeTrace) -> Result<(), String> {
+        if let Some(output) = self.output.as_ref() {
+            let mut file = OpenOptions::new().create(true).append(true).open(output)
+                .map_err(|e| format!("open decode trace {} failure: {}", output.display(), e))?;
+            writeln!(file, "{}", trace.to_json())
+                .map_err(|e| format!("write decode trace {} failure: {}", output.display(), e))?;
+        }
+        if let Ok(mut traces) = self.traces.lock() {
+            traces.push(trace);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.traces.lock().map_or(0, |traces| traces.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn traces(&self) -> Vec<DecodeTrace> {
+        self.traces.lock().map(|traces| traces.clone()).unwrap_or_default()
+    }
+
+    // 所有记录的json数组
+    pub fn to_json(&self) -> String {
+        format!("[{}]", self.traces().iter().map(|trace| trace.to_json()).collect::<Vec<_>>().join(","))
+    }
+}
+
+// mysql-bin.000003:4
+fn parse_trace_position(key: &str, value: &str) -> Result<EntryPosition, String> {
+    let invalid = || format!("{}: invalid position {}, expect for example mysql-bin.000001:4", key, value);
+    let (journal_name, position) = value.trim().rsplit_once(':').ok_or_else(invalid)?;
+    let position = position.parse::<u64>().map_err(|_| invalid())?;
+    Ok(EntryPosition::new(journal_name, position))
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::command::charset;
+use crate::command::event::{LogContext, LogDecoder, LogEvent, TableMapLogEvent};
+use crate::instance::convert::mysql_type;
+use crate::instance::offline::BinlogFileReader;
+
+// 列信息的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataSource {
+    // table map的optional metadata(binlog_row_metadata=FULL)
+    TableMap,
+    // table map中没有, 由解析器推断或者使用默认值
+    Generated,
+}
+
+impl MetadataSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetadataSource::TableMap => "table_map",
+            MetadataSource::Generated => "generated",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnDescription {
+    index: usize,
+    name: String,
+    name_source: MetadataSource,
+    mysql_type: String,
+    charset: String,
+    charset_source: MetadataSource,
+    nullable: bool,
+    pk: bool,
+    visible: bool,
+}
+
+impl ColumnDescription {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn name_source(&self) -> MetadataSource {
+        self.name_source
+    }
+    pub fn mysql_type(&self) -> &str {
+        &self.mysql_type
+    }
+    pub fn charset(&self) -> &str {
+        &self.charset
+    }
+    pub fn charset_source(&self) -> MetadataSource {
+        self.charset_source
+    }
+    pub fn nullable(&self) -> bool {
+        self.nullable
+    }
+    pub fn pk(&self) -> bool {
+        self.pk
+    }
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+}
+
+/**
+ * <pre>
+ *  解析器看到的表结构, 即LogEventConvert输出entry时使用的列名/类型/字符集/主键,
+ *  用于排查"为什么列名是@1, @2"之类的问题: 列名和主键只能来自table map的optional metadata,
+ *  master没有设置binlog_row_metadata=FULL(8.0.1+)时列名由解析器生成, 主键为空
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDescription {
+    schema: String,
+    table: String,
+    table_id: u64,
+    columns: Vec<ColumnDescription>,
+    partial_error: Option<String>,
+}
+
+impl TableDescription {
+    pub fn from_table_map(table_map: &TableMapLogEvent) -> TableDescription {
+        let columns = table_map.column_info().iter().enumerate().map(|(index, info)| {
+            let (name, name_source) = match info.name() {
+                Some(name) => (name.to_string(), MetadataSource::TableMap),
+                None => (format!("@{}", index + 1), MetadataSource::Generated),
+            };
+            let (charset, charset_source) = match info.charset() {
+                Some(collation) => (charset::charset_name(collation as u16).map(|name| name.to_string())
+                                        .unwrap_or_else(|| format!("collation({})", collation)), MetadataSource::TableMap),
+                None if is_character(&mysql_type(info)) => ("utf8".to_string(), MetadataSource::Generated),
+                None => (String::new(), MetadataSource::Generated),
+            };
+            ColumnDescription {
+                index,
+                name,
+                name_source,
+                mysql_type: mysql_type(info),
+                charset,
+                charset_source,
+                nullable: info.nullable(),
+                pk: info.pk(),
+                visible: info.visible(),
+            }
+        }).collect();
+        TableDescription {
+            schema: table_map.db_name().to_string(),
+            table: table_map.table_name().to_string(),
+            table_id: table_map.table_id(),
+            columns,
+            partial_error: table_map.partial_error().map(|e| e.to_string()),
+        }
+    }
+
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+    pub fn columns(&self) -> &Vec<ColumnDescription> {
+        &self.columns
+    }
+    pub fn partial_error(&self) -> Option<&str> {
+        self.partial_error.as_deref()
+    }
+
+    pub fn has_generated_names(&self) -> bool {
+        self.columns.iter().any(|column| column.name_source == MetadataSource::Generated)
+    }
+
+    /**
+     * <pre>
+     *  类似EXPLAIN的文本输出:
+     *  table: db.t (table_id=100, 2 columns)
+     *  +---+------+------+---------+----------+----+-------------+----------------+
+     *  | # | name | type | charset | nullable | pk | name source | charset source |
+     *  +---+------+------+---------+----------+----+-------------+----------------+
+     *  | 1 | id   | int  |         | NO       | PK | table_map   |                |
+     *  ...
+     *  列名是生成的或者table map只解析了一部分时在表格之后输出提示
+     * </pre>
+     */
+    pub fn render(&self) -> String {
+        let headers = ["#", "name", "type", "charset", "nullable", "pk", "name source", "charset source"];
+        let rows: Vec<Vec<String>> = self.columns.iter().map(|column| vec![
+            (column.index + 1).to_string(),
+            if column.visible { column.name.clone() } else { format!("{} (invisible)", column.name) },
+            column.mysql_type.clone(),
+            column.charset.clone(),
+            if column.nullable { "YES" } else { "NO" }.to_string(),
+            if column.pk { "PK" } else { "" }.to_string(),
+            column.name_source.name().to_string(),
+            if column.charset.is_empty() { "" } else { column.charset_source.name() }.to_string(),
+        ]).collect();
+        let widths: Vec<usize> = headers.iter().enumerate()
+            .map(|(i, header)| rows.iter().map(|row| row[i].chars().count()).max().unwrap_or(0).max(header.len()))
+            .collect();
+        let separator = format!("+{}+", widths.iter().map(|width| "-".repeat(width + 2)).collect::<Vec<_>>().join("+"));
+        let line = |cells: Vec<&str>| format!("| {} |", cells.iter().zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>().join(" | "));
+
+        let mut out = vec![format!("table: {}.{} (table_id={}, {} columns)", self.schema, self.table, self.table_id,
+                                   self.columns.len())];
+        out.push(separator.clone());
+        out.push(line(headers.to_vec()));
+        out.push(separator.clone());
+        for row in rows.iter() {
+            out.push(line(row.iter().map(|cell| cell.as_str()).collect()));
+        }
+        out.push(separator);
+        if self.has_generated_names() {
+            out.push("note: the table map has no column names, names are generated as @1, @2... \
+                      set binlog_row_metadata=FULL on the master (mysql 8.0.1+) to get real names and primary keys"
+                .to_string());
+        }
+        if let Some(e) = self.partial_error.as_ref() {
+            out.push(format!("note: the table map is partially decoded: {}", e));
+        }
+        out.join("\n")
+    }
+}
+
+fn is_character(mysql_type: &str) -> bool {
+    ["char", "blob", "enum", "set"].iter().any(|kind| mysql_type.contains(kind))
+}
+
+/**
+ * <pre>
+ *  parser当前缓存的表结构, 以schema.table为key, 可以在其它线程中通过句柄查询(admin接口等).
+ *  只在table_id变化时重新生成描述, 同一个表的table map重复出现时只做一次比较
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TableSchemas {
+    tables: Arc<Mutex<BTreeMap<String, TableDescription>>>,
+}
+
+impl TableSchemas {
+    pub fn new() -> TableSchemas {
+        TableSchemas::default()
+    }
+
+    pub fn update(&self, table_map: &TableMapLogEvent) {
+        let key = format!("{}.{}", table_map.db_name(), table_map.table_name());
+        if let Ok(mut tables) = self.tables.lock() {
+            match tables.get(&key) {
+                Some(description) if description.table_id == table_map.table_id() => {}
+                _ => {
+                    tables.insert(key, TableDescription::from_table_map(table_map));
+                }
+            }
+        }
+    }
+
+    pub fn describe(&self, schema: &str, table: &str) -> Option<TableDescription> {
+        self.tables.lock().ok()?.get(&format!("{}.{}", schema, table)).cloned()
+    }
+
+    // schema.table
+    pub fn names(&self) -> Vec<String> {
+        self.tables.lock().map(|tables| tables.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut tables) = self.tables.lock() {
+            tables.clear();
+        }
+    }
+}
+
+// 从本地的binlog/relay log文件中解析, 返回该表最后一次出现的table map
+pub fn describe_binlog_file(path: &Path, schema: &str, table: &str) -> Result<Option<TableDescription>, String> {
+    let mut reader = BinlogFileReader::open(path)?;
+    let mut decoder = LogDecoder::new();
+    let mut context = LogContext::new();
+    context.set_tolerant(true);
+    let mut description = None;
+    while let Some(event) = reader.next_event()? {
+        if let LogEvent::TableMap(table_map) = decoder.decode(&event, &mut context)? {
+            if table_map.db_name() == schema && table_map.table_name() == table {
+                description = Some(TableDescription::from_table_map(&table_map));
+            }
+        }
+    }
+    Ok(description)
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::instance::running::MysqlEventParser;
+use crate::protocol::Entry;
+use crate::sink::EventSink;
+
+// 阶段之间channel的默认容量, 下游跟不上时上游阻塞
+pub const DEFAULT_STAGE_CAPACITY: usize = 1024;
+
+/**
+ * <pre>
+ *  一个instance的执行层, 每个阶段(fetch/decode/sink)运行在独立的线程上:
+ *  线程名为 <instance>-<stage>, 可以通过set_core把某个阶段绑定到指定的cpu核(目前只支持linux).
+ *  阶段之间通过有界channel连接, stop()只通知最上游的阶段停止,
+ *  下游阶段处理完channel中剩余的数据, 在上游的sender释放后自然退出, 从而保证停止时不丢数据:
+ *      let mut executor = InstanceExecutor::new("example");
+ *      executor.set_core("sink", 3);
+ *      let (sender, receiver) = stage_channel(DEFAULT_STAGE_CAPACITY);
+ *      executor.spawn("decode", move |running| { while running.load(..) { sender.send(entry)... } })?;
+ *      executor.spawn("sink", sink_stage(receiver, sink))?;
+ *      ...
+ *      executor.shutdown()?;
+ * </pre>
+ */
+pub struct InstanceExecutor {
+    name: String,
+    // stage -> cpu核
+    cores: BTreeMap<String, usize>,
+    running: Arc<AtomicBool>,
+    // stop()时需要通知的其它运行状态, 例如MysqlEventParser::running_handle
+    stop_handles: Vec<Arc<AtomicBool>>,
+    // 按启动顺序保存, join时上游先结束
+    stages: Vec<(String, JoinHandle<Result<(), String>>)>,
+}
+
+impl InstanceExecutor {
+    pub fn new(name: &str) -> InstanceExecutor {
+        InstanceExecutor {
+            name: name.to_string(),
+            cores: BTreeMap::new(),
+            running: Arc::new(AtomicBool::new(true)),
+            stop_handles: vec![],
+            stages: vec![],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // 需要在spawn之前设置
+    pub fn set_core(&mut self, stage: &str, core: usize) {
+        self.cores.insert(stage.to_string(), core);
+    }
+
+    pub fn core(&self, stage: &str) -> Option<usize> {
+        self.cores.get(stage).copied()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    // 阶段的执行函数通过该句柄检查是否需要停止
+    pub fn running_handle(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    pub fn stages(&self) -> Vec<&str> {
+        self.stages.iter().map(|(stage, _)| stage.as_str()).collect()
+    }
+
+    pub fn spawn<F>(&mut self, stage: &str, f: F) -> Result<(), String>
+        where F: FnOnce(Arc<AtomicBool>) -> Result<(), String> + Send + 'static {
+        let thread_name = format!("{}-{}", self.name, stage);
+        let core = self.core(stage);
+        let running = self.running.clone();
+        let handle = thread::Builder::new()
+            .name(thread_name.clone())
+            .spawn(move || {
+                if let Some(core) = core {
+                    if let Err(e) = pin_current_thread(core) {
+                        println!("pin thread {} to core {} failure: {}", thread_name, core, e);
+                    }
+                }
+                f(running)
+            })
+            .map_err(|e| format!("spawn stage {} of {} failure: {}", stage, self.name, e))?;
+        self.stages.push((stage.to_string(), handle));
+        Ok(())
+    }
+
+    // 在独立线程中运行parser, stop()时同时停止parser
+    pub fn spawn_parser(&mut self, stage: &str, mut parser: MysqlEventParser) -> Result<(), String> {
+        self.stop_handles.push(parser.running_handle());
+        self.spawn(stage, move |_| parser.start().map_err(String::from))
+    }
+
+    // stop()时需要同时通知的运行状态
+    pub fn add_stop_handle(&mut self, handle: Arc<AtomicBool>) {
+        self.stop_handles.push(handle);
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        for handle in self.stop_handles.iter() {
+            handle.store(false, Ordering::SeqCst);
+        }
+    }
+
+    // 等待所有阶段结束, 返回第一个失败阶段的错误
+    pub fn join(self) -> Result<(), String> {
+        let mut result = Ok(());
+        for (stage, handle) in self.stages {
+            let stage_result = match handle.join() {
+                Ok(stage_result) => stage_result,
+                Err(_) => Err("thread panicked".to_string()),
+            };
+            if let Err(e) = stage_result {
+                println!("stage {} of {} failure: {}", stage, self.name, e);
+                result = result.and(Err(format!("stage {} of {} failure: {}", stage, self.name, e)));
+            }
+        }
+        result
+    }
+
+    pub fn shutdown(self) -> Result<(), String> {
+        self.stop();
+        self.join()
+    }
+}
+
+pub fn stage_channel(capacity: usize) -> (SyncSender<Entry>, Receiver<Entry>) {
+    sync_channel(capacity)
+}
+
+// 消费上游的entry直到上游的sender全部释放, 之后flush sink
+pub fn sink_stage(receiver: Receiver<Entry>, mut sink: Box<dyn EventSink>)
+                  -> impl FnOnce(Arc<AtomicBool>) -> Result<(), String> + Send + 'static {
+    move |_| {
+        for entry in receiver {
+            sink.on_event(&entry)?;
+        }
+        sink.flush()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn pin_current_thread(core: usize) -> Result<(), String> {
+    // SAFETY: cpu_set_t是普通的位图, 全零是合法的初始值; pid为0表示当前线程
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(core, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(_core: usize) -> Result<(), String> {
+    Err("cpu pinning is only supported on linux".to_string())
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::command::gtid::GtidSet;
+use crate::instance::gtid_gap::GtidGapDetector;
+use crate::instance::variables::ServerVariables;
+use crate::instance::EntryPosition;
+
+// 重连之后发现master身份变化时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceChangePolicy {
+    // 与之前的行为一致, 从原来的位点继续dump, 适用于新master的binlog与原来一致的场景(例如VIP切回同一份数据)
+    #[default]
+    Continue,
+    // 从新master当前的位置(show master status)开始, 切换期间的变更由下游根据SourceChanged entry决定如何补齐
+    Latest,
+    // 停止parser, 不再重试, 需要人工确认位点之后修改策略或者位点重新启动
+    Halt,
+}
+
+impl SourceChangePolicy {
+    pub fn from_name(name: &str) -> Result<SourceChangePolicy, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "continue" => Ok(SourceChangePolicy::Continue),
+            "latest" => Ok(SourceChangePolicy::Latest),
+            "halt" => Ok(SourceChangePolicy::Halt),
+            _ => Err(format!("unknown source change policy {}, expect continue/latest/halt", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceChangePolicy::Continue => "continue",
+            SourceChangePolicy::Latest => "latest",
+            SourceChangePolicy::Halt => "halt",
+        }
+    }
+}
+
+// 一次连接时master的身份, 地址只用于记录, 地址变化(VIP/DNS)本身不认为是切换
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceIdentity {
+    address: String,
+    port: u16,
+    server_id: Option<u32>,
+    server_uuid: Option<String>,
+}
+
+impl SourceIdentity {
+    pub fn new(address: &str, port: u16, server_id: Option<u32>, server_uuid: Option<&str>) -> SourceIdentity {
+        SourceIdentity {
+            address: address.to_string(),
+            port,
+            server_id,
+            server_uuid: server_uuid.map(|server_uuid| server_uuid.to_ascii_lowercase()),
+        }
+    }
+
+    // 变量快照中没有server_id/server_uuid时对应的字段为None, 不参与比较
+    pub fn from_variables(address: &str, port: u16, variables: Option<&ServerVariables>) -> SourceIdentity {
+        SourceIdentity::new(address, port, variables.and_then(|variables| variables.server_id()),
+                            variables.and_then(|variables| variables.server_uuid()))
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    pub fn server_id(&self) -> Option<u32> {
+        self.server_id
+    }
+    pub fn server_uuid(&self) -> Option<&str> {
+        self.server_uuid.as_deref()
+    }
+}
+
+impl fmt::Display for SourceIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{} (server_id={}, server_uuid={})", self.address, self.port,
+               self.server_id.map_or("<none>".to_string(), |server_id| server_id.to_string()),
+               self.server_uuid.as_deref().unwrap_or("<none>"))
+    }
+}
+
+/**
+ * <pre>
+ *  比较重连前后master的身份, 返回变化的原因, 没有变化时为空:
+ *      server_id/server_uuid不同       两次连接的不是同一个实例
+ *      GTID回退                        新master的gtid_executed中某个source的最大序号小于已经收到的,
+ *                                      说明已经投递给下游的事务在新master上不存在
+ *  gtid_executed为None(没有开启GTID或者无法查询)时不检查GTID回退, 只检查mysql的uuid:gno
+ * </pre>
+ */
+pub fn source_changes(previous: &SourceIdentity, current: &SourceIdentity, gtid_executed: Option<&GtidSet>,
+                      received: &GtidGapDetector) -> Vec<String> {
+    let mut reasons = vec![];
+    if let (Some(old), Some(new)) = (previous.server_id, current.server_id) {
+        if old != new {
+            reasons.push(format!("server_id {} -> {}", old, new));
+        }
+    }
+    if let (Some(old), Some(new)) = (previous.server_uuid.as_deref(), current.server_uuid.as_deref()) {
+        if old != new {
+            reasons.push(format!("server_uuid {} -> {}", old, new));
+        }
+    }
+    if let Some(gtid_executed) = gtid_executed {
+        for (source, last) in received.sources().filter(|(source, _)| source.len() == 36) {
+            let executed = gtid_executed.uuid_sets().find(|uuid_set| uuid_set.uuid() == source)
+                .and_then(|uuid_set| uuid_set.last_gno());
+            match executed {
+                Some(executed) if executed >= last => {}
+                Some(executed) => {
+                    reasons.push(format!("gtid regression on {}: received {}, master has executed up to {}", source,
+                                         last, executed));
+                }
+                None => reasons.push(format!("gtid regression on {}: received {}, master has not executed any", source,
+                                             last)),
+            }
+        }
+    }
+    reasons
+}
+
+/**
+ * <pre>
+ *  master身份变化以及采取的处理, 作为SourceChanged entry投递给下游用于审计:
+ *      previous/current    重连前后master的身份
+ *      reasons             判断为切换的原因, 见source_changes
+ *      policy              采取的处理方式
+ *      position            切换前准备重新dump的位点
+ *      relocated           实际重新dump的位点, Halt时为None
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceChange {
+    previous: SourceIdentity,
+    current: SourceIdentity,
+    reasons: Vec<String>,
+    policy: SourceChangePolicy,
+    position: EntryPosition,
+    relocated: Option<EntryPosition>,
+}
+
+impl SourceChange {
+    pub fn new(previous: SourceIdentity, current: SourceIdentity, reasons: Vec<String>, policy: SourceChangePolicy,
+               position: EntryPosition, relocated: Option<EntryPosition>) -> SourceChange {
+        SourceChange { previous, current, reasons, policy, position, relocated }
+    }
+
+    pub fn previous(&self) -> &SourceIdentity {
+        &self.previous
+    }
+    pub fn current(&self) -> &SourceIdentity {
+        &self.current
+    }
+    pub fn reasons(&self) -> &Vec<String> {
+        &self.reasons
+    }
+    pub fn policy(&self) -> SourceChangePolicy {
+        self.policy
+    }
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+    pub fn relocated(&self) -> Option<&EntryPosition> {
+        self.relocated.as_ref()
+    }
+
+    // 例如 source changed from a:3306 (...) to b:3306 (...): server_id 1 -> 2, policy latest, relocated from f:p to f:p
+    pub fn message(&self) -> String {
+        let decision = match self.relocated.as_ref() {
+            Some(relocated) if relocated == &self.position => {
+                format!("continue from {}:{}", relocated.journal_name(), relocated.position())
+            }
+            Some(relocated) => format!("relocated from {}:{} to {}:{}", self.position.journal_name(),
+                                       self.position.position(), relocated.journal_name(), relocated.position()),
+            None => format!("halted at {}:{}", self.position.journal_name(), self.position.position()),
+        };
+        format!("source changed from {} to {}: {}, policy {}, {}", self.previous, self.current, self.reasons.join("; "),
+                self.policy.name(), decision)
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+use crate::channel::SocketChannel;
+use crate::command::errno::ServerError;
+use crate::command::msc::{EOF_HEADER, ERROR_HEADER, HEADER_PACKET_LENGTH, MAX_PACKET_LENGTH, OK_HEADER};
+use crate::error::CanalError;
+
+// 超时的类型, 两者的原因和处理方式不同
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchTimeout {
+    // packet读到一半时超过read_timeout没有收到任何数据, 连接已经不可用
+    Network,
+    // 超过heartbeat_timeout没有收到任何event或heartbeat, master可能已经hang住或者heartbeat没有生效
+    Heartbeat,
+}
+
+/**
+ * <pre>
+ *  对应canal中的DirectLogFetcher, 在COM_BINLOG_DUMP之后从channel中读取event,
+ *  每个packet的第一个字节为:
+ *      0x00    后面跟随一个完整的event
+ *      0xfe    EOF, master已没有更多的binlog(非阻塞dump)
+ *      0xff    ErrorPacket
+ *  两个相互独立的超时:
+ *      read_timeout        socket层面的超时, 只在读取packet的过程中生效, 用于发现半开的连接
+ *      heartbeat_timeout   两个packet之间的超时, 需要大于master_heartbeat_period, 否则master空闲时会误判
+ *  都为None时与之前一样一直阻塞. 超时的类型通过last_timeout区分.
+ *  master主动断开dump连接(KILL/shutdown返回的ErrorPacket, 或者两个packet之间连接被关闭/reset)时
+ *  is_killed返回true, 这种情况只需要从当前位点重新连接
+ * </pre>
+ */
+pub struct DirectLogFetcher {
+    buffer: Vec<u8>,
+    // dump过程中master返回的ErrorPacket, 例如1236
+    last_error: Option<ServerError>,
+    last_timeout: Option<FetchTimeout>,
+    killed: bool,
+    read_timeout: Option<Duration>,
+    heartbeat_timeout: Option<Duration>,
+    // socket的超时是否已经设置
+    timeout_applied: bool,
+    last_packet: Instant,
+}
+
+impl Default for DirectLogFetcher {
+    fn default() -> Self {
+        DirectLogFetcher::new()
+    }
+}
+
+impl DirectLogFetcher {
+    pub fn new() -> DirectLogFetcher {
+        DirectLogFetcher {
+            buffer: vec![],
+            last_error: None,
+            last_timeout: None,
+            killed: false,
+            read_timeout: None,
+            heartbeat_timeout: None,
+            timeout_applied: false,
+            last_packet: Instant::now(),
+        }
+    }
+
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout.filter(|timeout| !timeout.is_zero());
+        self.timeout_applied = false;
+    }
+
+    pub fn set_heartbeat_timeout(&mut self, heartbeat_timeout: Option<Duration>) {
+        self.heartbeat_timeout = heartbeat_timeout.filter(|timeout| !timeout.is_zero());
+        self.timeout_applied = false;
+    }
+
+    // sink背压暂停读取之后调用, 暂停的时间不计入heartbeat_timeout
+    pub fn resume(&mut self) {
+        self.last_packet = Instant::now();
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    pub fn heartbeat_timeout(&self) -> Option<Duration> {
+        self.heartbeat_timeout
+    }
+
+    // 读取下一个event, 返回None表示master已经发送了EOF
+    pub fn fetch(&mut self, channel: &mut dyn SocketChannel) -> Result<Option<&[u8]>, CanalError> {
+        let body = self.read_packet(channel)?;
+        match body.first() {
+            Some(&OK_HEADER) => {
+                self.buffer = body;
+                Ok(Some(&self.buffer[1..]))
+            }
+            Some(&EOF_HEADER) if body.len() < 9 => Ok(None),
+            Some(&ERROR_HEADER) => {
+                let error = ServerError::from_packet(&body);
+                let message = error.as_ref().map(|error| error.to_string()).unwrap_or_default();
+                self.killed = error.as_ref().is_some_and(|error| error.errno().is_killed());
+                self.last_error = error.clone();
+                Err(CanalError::Protocol(error, format!("received error packet: {}", message)))
+            }
+            _ => Err(CanalError::Protocol(None, format!("unexpected binlog packet header {:?}", body.first()))),
+        }
+    }
+
+    pub fn last_error(&self) -> Option<&ServerError> {
+        self.last_error.as_ref()
+    }
+
+    pub fn last_timeout(&self) -> Option<FetchTimeout> {
+        self.last_timeout
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed
+    }
+
+    // 读取一个完整的packet body, 超过16M的packet会被拆分为多个连续的packet
+    fn read_packet(&mut self, channel: &mut dyn SocketChannel) -> Result<Vec<u8>, CanalError> {
+        if !self.timeout_applied {
+            // socket超时取两者中较小的一个, 作为检查两种超时的粒度
+            let poll = match (self.read_timeout, self.heartbeat_timeout) {
+                (Some(read), Some(heartbeat)) => Some(read.min(heartbeat)),
+                (read, heartbeat) => read.or(heartbeat),
+            };
+            channel.set_read_timeout(poll)
+                .map_err(|e| CanalError::Io(e.kind(), format!("set socket read timeout failure: {}", e)))?;
+            self.timeout_applied = true;
+            self.last_packet = Instant::now();
+        }
+        let mut header = [0u8; HEADER_PACKET_LENGTH];
+        self.wait_packet(channel, &mut header[..1])?;
+        self.read_fully(channel, &mut header[1..])?;
+        let mut len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let mut body = vec![0u8; len];
+        self.read_fully(channel, &mut body)?;
+        while len == MAX_PACKET_LENGTH {
+            self.read_fully(channel, &mut header)?;
+            len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+            let offset = body.len();
+            body.resize(offset + len, 0);
+            self.read_fully(channel, &mut body[offset..])?;
+        }
+        self.last_packet = Instant::now();
+        Ok(body)
+    }
+
+    // 等待下一个packet的第一个字节, 只检查heartbeat_timeout
+    fn wait_packet(&mut self, channel: &mut dyn SocketChannel, buf: &mut [u8]) -> Result<(), CanalError> {
+        loop {
+            match channel.read(buf) {
+                Ok(0) => {
+                    self.killed = true;
+                    return Err(CanalError::Io(ErrorKind::UnexpectedEof,
+                                          "fetch binlog event failure: connection is closed by master".to_string()));
+                }
+                Ok(_) => return Ok(()),
+                Err(e) if is_timeout(e.kind()) => {
+                    if let Some(timeout) = self.heartbeat_timeout.filter(|timeout| self.last_packet.elapsed() >= *timeout) {
+                        self.last_timeout = Some(FetchTimeout::Heartbeat);
+                        return Err(CanalError::Io(ErrorKind::TimedOut,
+                                                  format!("no event or heartbeat is received in {:?}, check \
+                                                           master_heartbeat_period and the master status", timeout)));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.killed = matches!(e.kind(), ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted);
+                    return Err(CanalError::Io(e.kind(), format!("fetch binlog event failure: {}", e)));
+                }
+            }
+        }
+    }
+
+    // packet的剩余部分, 只检查read_timeout
+    fn read_fully(&mut self, channel: &mut dyn SocketChannel, buf: &mut [u8]) -> Result<(), CanalError> {
+        let mut offset = 0;
+        let mut last_read = Instant::now();
+        while offset < buf.len() {
+            match channel.read(&mut buf[offset..]) {
+                Ok(0) => return Err(CanalError::Io(ErrorKind::UnexpectedEof,
+                                          "fetch binlog event failure: connection is closed by master".to_string())),
+                Ok(size) => {
+                    offset += size;
+                    last_read = Instant::now();
+                }
+                Err(e) if is_timeout(e.kind()) => {
+                    if let Some(timeout) = self.read_timeout.filter(|timeout| last_read.elapsed() >= *timeout) {
+                        self.last_timeout = Some(FetchTimeout::Network);
+                        return Err(CanalError::Io(ErrorKind::TimedOut,
+                                                  format!("fetch binlog event failure: network is silent for {:?} in \
+                                                           the middle of a packet", timeout)));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(CanalError::Io(e.kind(), format!("fetch binlog event failure: {}", e))),
+            }
+        }
+        Ok(())
+    }
+}
+
+// 设置了read timeout的socket超时时返回WouldBlock(unix)或TimedOut(windows)
+fn is_timeout(kind: ErrorKind) -> bool {
+    matches!(kind, ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
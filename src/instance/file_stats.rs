@@ -0,0 +1,234 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::command::event::LogEvent;
+use crate::instance::EntryPosition;
+use crate::sink::mq::flat_message::json_string;
+
+// 保留最近完成的文件数
+pub const DEFAULT_FILE_STATS_HISTORY: usize = 24;
+
+/**
+ * <pre>
+ *  一个binlog文件的解析统计, 在rotate到下一个文件时完成:
+ *      start_position/end_position     本次解析的范围, 从文件中间开始dump时start_position不为4
+ *      events                          event type名字 -> 数量, 不包括heartbeat和fake rotate
+ *      bytes                           event的总字节数
+ *      duration                        第一个event到rotate之间的解析耗时, 包括重连的时间
+ *      decode_errors                   解码失败(包括进入dead letter)的event数
+ *      largest_transaction             最大的事务的起始位点和字节数, 从事务中间开始时只统计收到的部分
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinlogFileStats {
+    journal_name: String,
+    start_position: Option<u64>,
+    end_position: u64,
+    events: BTreeMap<String, u64>,
+    bytes: u64,
+    duration: Duration,
+    decode_errors: u64,
+    largest_transaction: Option<(EntryPosition, u64)>,
+}
+
+impl BinlogFileStats {
+    pub fn new(journal_name: &str) -> BinlogFileStats {
+        BinlogFileStats {
+            journal_name: journal_name.to_string(),
+            start_position: None,
+            end_position: 0,
+            events: BTreeMap::new(),
+            bytes: 0,
+            duration: Duration::ZERO,
+            decode_errors: 0,
+            largest_transaction: None,
+        }
+    }
+
+    pub fn journal_name(&self) -> &str {
+        &self.journal_name
+    }
+    pub fn start_position(&self) -> Option<u64> {
+        self.start_position
+    }
+    pub fn end_position(&self) -> u64 {
+        self.end_position
+    }
+    pub fn events(&self) -> &BTreeMap<String, u64> {
+        &self.events
+    }
+    pub fn event_count(&self) -> u64 {
+        self.events.values().sum()
+    }
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+    pub fn decode_errors(&self) -> u64 {
+        self.decode_errors
+    }
+    pub fn largest_transaction(&self) -> Option<&(EntryPosition, u64)> {
+        self.largest_transaction.as_ref()
+    }
+
+    // 例如 binlog mysql-bin.000001 finished: 4..1024, 12 events, 1020 bytes in 1.2s, 0 decode errors,
+    // largest transaction 512 bytes at mysql-bin.000001:4, events QueryEvent=2, XidEvent=1
+    pub fn message(&self) -> String {
+        let largest = match self.largest_transaction.as_ref() {
+            Some((position, bytes)) => format!("{} bytes at {}:{}", bytes, position.journal_name(), position.position()),
+            None => "none".to_string(),
+        };
+        let events = self.events.iter().map(|(kind, count)| format!("{}={}", kind, count)).collect::<Vec<_>>();
+        format!("binlog {} finished: {}..{}, {} events, {} bytes in {:?}, {} decode errors, largest transaction {}, \
+                 events {}", self.journal_name, self.start_position.unwrap_or(0), self.end_position, self.event_count(),
+                self.bytes, self.duration, self.decode_errors, largest, events.join(", "))
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"logfile\":{},\"start\":{},\"end\":{},\"events\":{{", json_string(&self.journal_name),
+                       self.start_position.unwrap_or(0), self.end_position);
+        for (i, (kind, count)) in self.events.iter().enumerate() {
+            let _ = write!(out, "{}{}:{}", if i > 0 { "," } else { "" }, json_string(kind), count);
+        }
+        let _ = write!(out, "}},\"bytes\":{},\"duration_ms\":{},\"decode_errors\":{},\"largest_transaction\":",
+                       self.bytes, self.duration.as_millis(), self.decode_errors);
+        match self.largest_transaction.as_ref() {
+            Some((position, bytes)) => {
+                let _ = write!(out, "{{\"logfile\":{},\"offset\":{},\"bytes\":{}}}}}", json_string(position.journal_name()),
+                               position.position(), bytes);
+            }
+            None => out.push_str("null}"),
+        }
+        out
+    }
+}
+
+/**
+ * <pre>
+ *  在dump线程中按event累计当前文件的统计, 跨重连保留:
+ *      start()     每次dump开始时调用, 文件名与当前统计的不同时(例如重新定位)先完成当前文件
+ *      observe()   每个event调用一次, 收到真正的ROTATE(文件的最后一个event)或者文件名变化的fake rotate时
+ *                  返回完成的文件
+ *  完成的文件保留最近DEFAULT_FILE_STATS_HISTORY个, 可以通过history句柄在其它线程中读取
+ * </pre>
+ */
+#[derive(Debug)]
+pub struct FileStatsTracker {
+    current: Option<BinlogFileStats>,
+    started_at: Option<Instant>,
+    // 当前事务的起始位点和已经收到的字节数
+    transaction: Option<(EntryPosition, u64)>,
+    history: Arc<Mutex<VecDeque<BinlogFileStats>>>,
+}
+
+impl Default for FileStatsTracker {
+    fn default() -> Self {
+        FileStatsTracker::new()
+    }
+}
+
+impl FileStatsTracker {
+    pub fn new() -> FileStatsTracker {
+        FileStatsTracker { current: None, started_at: None, transaction: None, history: Arc::new(Mutex::new(VecDeque::new())) }
+    }
+
+    pub fn current(&self) -> Option<&BinlogFileStats> {
+        self.current.as_ref()
+    }
+
+    pub fn history(&self) -> Arc<Mutex<VecDeque<BinlogFileStats>>> {
+        self.history.clone()
+    }
+
+    pub fn start(&mut self, journal_name: &str) -> Option<BinlogFileStats> {
+        self.switch(journal_name)
+    }
+
+    // in_transaction为处理完该event之后是否在事务中, 见PositionTracker::in_transaction
+    pub fn observe(&mut self, event: &LogEvent, event_len: usize, in_transaction: bool) -> Option<BinlogFileStats> {
+        let header = event.header();
+        if header.event_type().is_some_and(|kind| kind.is_heartbeat()) {
+            return None;
+        }
+        if let LogEvent::Rotate(rotate) = event {
+            if rotate.is_fake() {
+                return self.switch(rotate.filename());
+            }
+        }
+        let offset = (header.log_pos() as u64).saturating_sub(header.event_len() as u64);
+        let current = self.current.get_or_insert_with(|| BinlogFileStats::new(""));
+        let started_at = self.started_at.get_or_insert_with(Instant::now);
+        current.start_position.get_or_insert(offset);
+        current.end_position = current.end_position.max(header.log_pos() as u64);
+        let kind = match header.event_type() {
+            Some(kind) => format!("{:?}", kind),
+            None => format!("Unknown({})", header.kind()),
+        };
+        *current.events.entry(kind).or_insert(0) += 1;
+        current.bytes += event_len as u64;
+        current.duration = started_at.elapsed();
+        if in_transaction || self.transaction.is_some() {
+            let transaction = self.transaction
+                .get_or_insert_with(|| (EntryPosition::new(&current.journal_name, offset), 0));
+            transaction.1 += event_len as u64;
+        }
+        if !in_transaction {
+            if let Some((position, bytes)) = self.transaction.take() {
+                if current.largest_transaction.as_ref().is_none_or(|(_, largest)| bytes > *largest) {
+                    current.largest_transaction = Some((position, bytes));
+                }
+            }
+        }
+        // 真正的ROTATE是文件的最后一个event
+        match event {
+            LogEvent::Rotate(rotate) => {
+                let finished = self.finish();
+                self.current = Some(BinlogFileStats::new(rotate.filename()));
+                finished
+            }
+            _ => None,
+        }
+    }
+
+    // 解码失败或者进入dead letter的event
+    pub fn record_decode_error(&mut self) {
+        if let Some(current) = self.current.as_mut() {
+            current.decode_errors += 1;
+        }
+    }
+
+    fn switch(&mut self, journal_name: &str) -> Option<BinlogFileStats> {
+        match self.current.as_mut() {
+            Some(current) if current.journal_name == journal_name => None,
+            // 第一次启动时只知道文件名
+            Some(current) if current.journal_name.is_empty() => {
+                current.journal_name = journal_name.to_string();
+                None
+            }
+            _ => {
+                let finished = self.finish();
+                self.current = Some(BinlogFileStats::new(journal_name));
+                finished
+            }
+        }
+    }
+
+    // 没有收到任何event的文件不返回
+    fn finish(&mut self) -> Option<BinlogFileStats> {
+        self.transaction = None;
+        self.started_at = None;
+        let finished = self.current.take().filter(|current| current.event_count() > 0)?;
+        if let Ok(mut history) = self.history.lock() {
+            if history.len() >= DEFAULT_FILE_STATS_HISTORY {
+                history.pop_front();
+            }
+            history.push_back(finished.clone());
+        }
+        Some(finished)
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use crate::command::gtid::GtidSet;
+
+// 发现GTID不连续时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GtidGapPolicy {
+    // 不检查
+    Off,
+    // 投递Incident entry并记录gtid_gaps, 继续dump
+    #[default]
+    Alert,
+    // 投递Incident entry之后停止parser, 不再重试, 等待人工排查
+    Halt,
+}
+
+impl GtidGapPolicy {
+    pub fn from_name(name: &str) -> Result<GtidGapPolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Ok(GtidGapPolicy::Off),
+            "alert" => Ok(GtidGapPolicy::Alert),
+            "halt" => Ok(GtidGapPolicy::Halt),
+            _ => Err(format!("unknown gtid gap policy {}, expect off/alert/halt", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GtidGapPolicy::Off => "off",
+            GtidGapPolicy::Alert => "alert",
+            GtidGapPolicy::Halt => "halt",
+        }
+    }
+}
+
+// 一次不连续: source上(expected, received)之间的事务没有收到
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GtidGap {
+    source: String,
+    expected: u64,
+    received: u64,
+}
+
+impl GtidGap {
+    // mysql为server uuid, MariaDB为domain id
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+    pub fn expected(&self) -> u64 {
+        self.expected
+    }
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+    pub fn missing(&self) -> u64 {
+        self.received - self.expected
+    }
+
+    pub fn message(&self) -> String {
+        if self.missing() == 1 {
+            format!("gtid gap detected on {}: transaction {} is missing, received {}", self.source, self.expected,
+                    self.received)
+        } else {
+            format!("gtid gap detected on {}: transactions {}-{} are missing, received {}", self.source, self.expected,
+                    self.received - 1, self.received)
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  按source跟踪收到的GTID, 发现跳号时(例如主从切换之后新master缺少部分事务)返回GtidGap:
+ *      mysql       uuid:gno, 每个server uuid的gno连续递增
+ *      MariaDB     domain-server_id-seq, 每个domain的seq连续递增
+ *  每个source第一次出现的GTID作为起点, 可以通过seed用已执行的GTID集合初始化.
+ *  重连之后重复收到的GTID(gno不大于已收到的)不认为是跳号
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct GtidGapDetector {
+    last: BTreeMap<String, u64>,
+    gaps: u64,
+}
+
+impl GtidGapDetector {
+    pub fn new() -> GtidGapDetector {
+        GtidGapDetector::default()
+    }
+
+    // 只使用每个uuid的最大gno, gtid_executed中本身的空洞不做检查
+    pub fn seed(&mut self, gtid_set: &GtidSet) {
+        for uuid_set in gtid_set.uuid_sets() {
+            if let Some(gno) = uuid_set.last_gno() {
+                let last = self.last.entry(uuid_set.uuid()).or_insert(gno);
+                *last = (*last).max(gno);
+            }
+        }
+    }
+
+    pub fn last(&self, source: &str) -> Option<u64> {
+        self.last.get(source).copied()
+    }
+
+    // (source, 已收到的最大序号)
+    pub fn sources(&self) -> impl Iterator<Item=(&str, u64)> {
+        self.last.iter().map(|(source, last)| (source.as_str(), *last))
+    }
+
+    pub fn gaps(&self) -> u64 {
+        self.gaps
+    }
+
+    pub fn observe(&mut self, gtid: &str) -> Option<GtidGap> {
+        let (source, gno) = parse_gtid(gtid)?;
+        let last = self.last.entry(source.clone()).or_insert(gno - 1);
+        let expected = *last + 1;
+        *last = (*last).max(gno);
+        if gno <= expected {
+            return None;
+        }
+        self.gaps += 1;
+        Some(GtidGap { source, expected, received: gno })
+    }
+}
+
+// 返回(source, 序号), 带tag的gtid以uuid:tag为source, 无法解析或者序号为0时返回None
+fn parse_gtid(gtid: &str) -> Option<(String, u64)> {
+    if let Some((uuid, gno)) = gtid.rsplit_once(':') {
+        let gno = gno.parse::<u64>().ok().filter(|gno| *gno > 0)?;
+        return Some((uuid.to_ascii_lowercase(), gno));
+    }
+    let mut parts = gtid.splitn(3, '-');
+    let domain = parts.next()?;
+    parts.next()?;
+    let seq = parts.next()?.parse::<u64>().ok().filter(|seq| *seq > 0)?;
+    Some((domain.to_string(), seq))
+}
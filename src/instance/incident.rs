@@ -0,0 +1,26 @@
+// 收到master的INCIDENT event(例如LOST_EVENTS)时的处理方式, 两种策略都会向incident sink投递Incident entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IncidentPolicy {
+    // 继续dump, 由sink告警
+    Alert,
+    // 与mysql replica一致, 停止parser, 不再重试, 需要人工重新同步数据之后从incident之后的位点启动
+    #[default]
+    Halt,
+}
+
+impl IncidentPolicy {
+    pub fn from_name(name: &str) -> Result<IncidentPolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "alert" => Ok(IncidentPolicy::Alert),
+            "halt" => Ok(IncidentPolicy::Halt),
+            _ => Err(format!("unknown incident policy {}, expect alert/halt", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IncidentPolicy::Alert => "alert",
+            IncidentPolicy::Halt => "halt",
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::cmp::Ordering;
+
+use crate::channel::tls::SslMode;
+
+pub mod admin;
+
+pub mod backoff;
+
+pub mod canary;
+pub mod catchup;
+pub mod clock;
+
+pub mod clone;
+
+pub mod containment;
+pub mod control;
+
+pub mod convert;
+
+pub mod cutover;
+
+pub mod ddl;
+
+pub mod decode_trace;
+
+pub mod describe;
+
+pub mod executor;
+
+pub mod failover;
+
+pub mod file_stats;
+
+pub mod fetcher;
+
+pub mod gtid_gap;
+pub mod incident;
+
+pub mod offline;
+pub mod parallel_decode;
+pub mod pipeline;
+
+pub mod position_manager;
+
+pub mod purge;
+
+pub mod redaction;
+
+pub mod relay;
+
+pub mod relay_index;
+
+pub mod running;
+
+pub mod schema_history;
+
+pub mod self_test;
+
+pub mod start;
+
+pub mod status;
+
+pub mod supervisor;
+
+pub mod tracker;
+
+pub mod transient;
+
+pub mod variables;
+
+pub mod watchdog;
+
+// 对应canal中的AuthenticationInfo, 描述如何连接到master
+#[derive(Debug, Clone, Default)]
+pub struct AuthenticationInfo {
+    address: String,
+    port: u16,
+    username: String,
+    password: String,
+    default_database_name: String,
+    ssl_mode: SslMode,
+    ssl_ca: Option<String>,
+}
+
+impl AuthenticationInfo {
+    pub fn new(address: &str, port: u16, username: &str, password: &str) -> AuthenticationInfo {
+        AuthenticationInfo {
+            address: address.to_string(),
+            port,
+            username: username.to_string(),
+            password: password.to_string(),
+            default_database_name: String::new(),
+            ssl_mode: SslMode::Disabled,
+            ssl_ca: None,
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+    pub fn default_database_name(&self) -> &str {
+        &self.default_database_name
+    }
+
+    pub fn ssl_mode(&self) -> SslMode {
+        self.ssl_mode
+    }
+    pub fn ssl_ca(&self) -> Option<&str> {
+        self.ssl_ca.as_deref()
+    }
+
+    pub fn set_default_database_name(&mut self, default_database_name: &str) {
+        self.default_database_name = default_database_name.to_string();
+    }
+
+    pub fn set_ssl_mode(&mut self, ssl_mode: SslMode) {
+        self.ssl_mode = ssl_mode;
+    }
+
+    pub fn set_ssl_ca(&mut self, ssl_ca: Option<&str>) {
+        self.ssl_ca = ssl_ca.map(|ssl_ca| ssl_ca.to_string());
+    }
+}
+
+// binlog中的位点, journal_name为binlog文件名, position为下一个event的起始位置.
+// 位点处于事务中间时, transaction_start记录同一文件内该事务的起始位置
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryPosition {
+    journal_name: String,
+    position: u64,
+    timestamp: i64,
+    server_id: u32,
+    transaction_start: Option<u64>,
+}
+
+impl EntryPosition {
+    pub fn new(journal_name: &str, position: u64) -> EntryPosition {
+        EntryPosition { journal_name: journal_name.to_string(), position, ..EntryPosition::default() }
+    }
+
+    pub fn journal_name(&self) -> &str {
+        &self.journal_name
+    }
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn transaction_start(&self) -> Option<u64> {
+        self.transaction_start
+    }
+
+    pub fn set_journal_name(&mut self, journal_name: &str) {
+        self.journal_name = journal_name.to_string();
+    }
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+    pub fn set_timestamp(&mut self, timestamp: i64) {
+        self.timestamp = timestamp;
+    }
+    pub fn set_server_id(&mut self, server_id: u32) {
+        self.server_id = server_id;
+    }
+    pub fn set_transaction_start(&mut self, transaction_start: Option<u64>) {
+        self.transaction_start = transaction_start;
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction_start.is_some()
+    }
+
+    // 位点处于事务中间时回退到事务开头, 保证不会只投递事务的后半段
+    pub fn transaction_begin(&self) -> EntryPosition {
+        match self.transaction_start {
+            Some(transaction_start) => EntryPosition::new(&self.journal_name, transaction_start),
+            None => self.clone(),
+        }
+    }
+
+    // 先比较binlog文件的序号(mysql-bin.000012), 再比较文件内的offset
+    pub fn compare(&self, other: &EntryPosition) -> Ordering {
+        let sequence = |name: &str| name.rsplit('.').next().and_then(|suffix| suffix.parse::<u64>().ok());
+        let journal = match (sequence(&self.journal_name), sequence(&other.journal_name)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => self.journal_name.cmp(&other.journal_name),
+        };
+        journal.then(self.position.cmp(&other.position))
+    }
+}
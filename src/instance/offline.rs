@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::command::event::{LogContext, LogDecoder, LogEvent, BINLOG_MAGIC, LOG_HEADER_LEN};
+use crate::instance::convert::LogEventConvert;
+use crate::instance::decode_trace::DecodeTracer;
+use crate::instance::transient::TransientTables;
+use crate::protocol::Entry;
+use crate::sink::EventSink;
+
+pub const DEFAULT_OFFLINE_WORKERS: usize = 4;
+// 每个文件解析出但还没有被合并的entry数量上限
+pub const DEFAULT_OFFLINE_CAPACITY: usize = 1024;
+
+// 待解析的文件以及接收该文件entry的channel
+type FileQueue = Mutex<VecDeque<(PathBuf, SyncSender<Result<Entry, String>>)>>;
+
+// 按顺序读取本地binlog/relay log文件中的event, 最后一个不完整的event(正在写入)视为文件结束
+pub struct BinlogFileReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+}
+
+impl BinlogFileReader {
+    pub fn open(path: &Path) -> Result<BinlogFileReader, String> {
+        let file = File::open(path).map_err(|e| format!("open {} failure: {}", path.display(), e))?;
+        let mut reader = BufReader::new(file);
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(|e| format!("read {} failure: {}", path.display(), e))?;
+        if magic != BINLOG_MAGIC {
+            return Err(format!("{} is not a binlog file", path.display()));
+        }
+        Ok(BinlogFileReader { path: path.to_path_buf(), reader })
+    }
+
+    pub fn next_event(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let mut event = vec![0u8; LOG_HEADER_LEN];
+        match self.reader.read_exact(&mut event) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(format!("read {} failure: {}", self.path.display(), e)),
+        }
+        let event_len = u32::from_le_bytes([event[9], event[10], event[11], event[12]]) as usize;
+        if event_len < LOG_HEADER_LEN {
+            return Err(format!("invalid event length {} in {}", event_len, self.path.display()));
+        }
+        event.resize(event_len, 0);
+        if self.reader.read_exact(&mut event[LOG_HEADER_LEN..]).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(event))
+    }
+}
+
+/**
+ * <pre>
+ *  离线解析一批binlog文件, 用于对归档做分析:
+ *  每个文件以format description开头, table map在每个事务中重新出现, 因此文件之间可以独立解析.
+ *  最多workers个线程同时解析, 按文件名中的序号(mysql-bin.000012)排序分配;
+ *  每个文件的entry通过容量为capacity的channel交给调用线程, 调用线程按文件顺序合并后交给sink,
+ *  因此sink收到的entry与顺序解析完全一致, 内存中最多缓存workers * capacity个entry.
+ *  任意一个文件解析失败时停止所有worker并返回错误
+ *      let mut parser = OfflineParser::new();
+ *      parser.set_workers(8);
+ *      let entries = parser.parse(&binlog_files(Path::new("/data/binlog"))?, &mut sink)?;
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct OfflineParser {
+    workers: usize,
+    capacity: usize,
+    tolerant: bool,
+    strict: bool,
+    decode_tracer: Option<DecodeTracer>,
+    // 每个文件的LogEventConvert使用的临时表/中间表处理方式
+    transient_tables: TransientTables,
+}
+
+impl Default for OfflineParser {
+    fn default() -> Self {
+        OfflineParser::new()
+    }
+}
+
+impl OfflineParser {
+    pub fn new() -> OfflineParser {
+        OfflineParser {
+            workers: DEFAULT_OFFLINE_WORKERS,
+            capacity: DEFAULT_OFFLINE_CAPACITY,
+            tolerant: false,
+            strict: false,
+            decode_tracer: None,
+            transient_tables: TransientTables::new(),
+        }
+    }
+
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers.max(1);
+    }
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+    }
+    pub fn set_tolerant(&mut self, tolerant: bool) {
+        self.tolerant = tolerant;
+    }
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+    // 所有worker共享同一个tracer
+    pub fn set_decode_tracer(&mut self, tracer: Option<DecodeTracer>) {
+        self.decode_tracer = tracer;
+    }
+    pub fn set_transient_tables(&mut self, transient_tables: TransientTables) {
+        self.transient_tables = transient_tables;
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // 返回交给sink的entry数量
+    pub fn parse(&self, files: &[PathBuf], sink: &mut dyn EventSink) -> Result<u64, String> {
+        let mut files = files.to_vec();
+        files.sort_by_key(|path| file_sequence(path));
+        let running = Arc::new(AtomicBool::new(true));
+        let mut receivers = vec![];
+        let mut queue = VecDeque::new();
+        for path in files {
+            let (sender, receiver) = sync_channel(self.capacity);
+            queue.push_back((path.clone(), sender));
+            receivers.push((path, receiver));
+        }
+        let queue = Arc::new(Mutex::new(queue));
+        let mut handles = vec![];
+        for index in 0..self.workers.min(receivers.len()) {
+            let (queue, running, settings) = (queue.clone(), running.clone(), self.clone());
+            let handle = thread::Builder::new()
+                .name(format!("offline-parse-{}", index))
+                .spawn(move || settings.work(&queue, &running))
+                .map_err(|e| format!("spawn offline parse worker failure: {}", e))?;
+            handles.push(handle);
+        }
+        let result = merge(receivers, sink);
+        running.store(false, Ordering::SeqCst);
+        for handle in handles {
+            let _ = handle.join();
+        }
+        let count = result?;
+        sink.flush()?;
+        Ok(count)
+    }
+
+    // 按顺序领取文件, 同一个文件只由一个worker解析
+    fn work(&self, queue: &FileQueue, running: &AtomicBool) {
+        while running.load(Ordering::SeqCst) {
+            let next = match queue.lock() {
+                Ok(mut queue) => queue.pop_front(),
+                Err(_) => None,
+            };
+            let (path, sender) = match next {
+                Some(next) => next,
+                None => return,
+            };
+            if let Err(e) = self.parse_file(&path, &sender, running) {
+                let _ = sender.send(Err(e));
+            }
+        }
+    }
+
+    fn parse_file(&self, path: &Path, sender: &SyncSender<Result<Entry, String>>, running: &AtomicBool)
+                  -> Result<(), String> {
+        let mut reader = BinlogFileReader::open(path)?;
+        let mut decoder = LogDecoder::new();
+        let mut convert = LogEventConvert::new();
+        convert.set_transient_tables(self.transient_tables.clone());
+        let mut context = LogContext::new();
+        context.set_tolerant(self.tolerant);
+        context.set_strict(self.strict);
+        let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        context.log_position_mut().set_journal_name(&file_name);
+        while let Some(event) = reader.next_event()? {
+            if !running.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            let log_event = decoder.decode(&event, &mut context)
+                .map_err(|e| format!("parse {} failure: {}", path.display(), e))?;
+            if let (LogEvent::Rows(rows), Some(tracer)) = (&log_event, self.decode_tracer.as_ref()) {
+                tracer.trace(&convert, rows, &context).map_err(|e| format!("parse {} failure: {}", path.display(), e))?;
+            }
+            // 文件从头开始解析, 事务不会被截断
+            let entry = convert.parse(&log_event, &context, true)
+                .map_err(|e| format!("parse {} failure: {}", path.display(), e))?;
+            if let Some(entry) = entry {
+                // 调用线程已经停止合并
+                if sender.send(Ok(entry)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// 按文件顺序读取每个文件的channel, 前一个文件的worker结束(sender释放)之后再读取下一个
+fn merge(receivers: Vec<(PathBuf, Receiver<Result<Entry, String>>)>, sink: &mut dyn EventSink) -> Result<u64, String> {
+    let mut count = 0;
+    for (path, receiver) in receivers {
+        for entry in receiver.iter() {
+            let entry = entry?;
+            sink.on_event(&entry).map_err(|e| format!("sink entry of {} failure: {}", path.display(), e))?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+// 目录下按序号排序的binlog文件(<basename>.<数字>), 忽略index等其它文件
+pub fn binlog_files(directory: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(directory)
+        .map_err(|e| format!("read directory {} failure: {}", directory.display(), e))?;
+    let mut files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && file_sequence(path).is_some())
+        .collect();
+    files.sort_by_key(|path| file_sequence(path));
+    Ok(files)
+}
+
+fn file_sequence(path: &Path) -> Option<u64> {
+    path.extension()?.to_str()?.parse::<u64>().ok()
+}
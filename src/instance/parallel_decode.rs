@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::config::Properties;
+use crate::instance::convert::RowsTask;
+use crate::metrics::rate;
+use crate::metrics::{RateKind, StreamMetrics};
+use crate::protocol::Entry;
+
+// 同时在解码的rows event数的默认上限
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 1024;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParallelDecodeStats {
+    // 交给解码线程的rows event数
+    tasks: u64,
+    // 遇到DDL/FORMAT_DESCRIPTION时还有rows event在解码, 需要等待的次数
+    barriers: u64,
+    // barrier处等待完成的rows event数
+    drained: u64,
+    max_in_flight: usize,
+}
+
+impl ParallelDecodeStats {
+    pub fn tasks(&self) -> u64 {
+        self.tasks
+    }
+    pub fn barriers(&self) -> u64 {
+        self.barriers
+    }
+    pub fn drained(&self) -> u64 {
+        self.drained
+    }
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+}
+
+// master.parallel_decode.threads, master.parallel_decode.max_in_flight, 没有配置threads时返回None
+pub fn parallel_decode_settings(properties: &Properties) -> Result<Option<(usize, usize)>, String> {
+    let number = |key: &str| properties.get(key)
+        .map(|value| value.trim().parse::<usize>().map_err(|_| format!("{}: invalid number {}", key, value)))
+        .transpose();
+    let threads = match number("master.parallel_decode.threads")? {
+        Some(threads) => threads,
+        None => return Ok(None),
+    };
+    let max_in_flight = number("master.parallel_decode.max_in_flight")?.unwrap_or(DEFAULT_MAX_IN_FLIGHT);
+    Ok(Some((threads, max_in_flight.max(1))))
+}
+
+/**
+ * <pre>
+ *  rows event行数据的并行解码:
+ *      submit()    dump线程创建RowsTask(携带当时的table map副本)交给解码线程, 其它entry按顺序排在后面
+ *      barrier()   等待所有在解码的rows event完成并投递, 之后才处理DDL/FORMAT_DESCRIPTION,
+ *                  保证rows event不会按照比它更新的表结构解码, 也不会排到DDL之后
+ *      reset()     dump失败时丢弃还没有投递的结果, 重新dump时会再次收到这些event
+ *  entry始终按照binlog中的顺序投递, 解码失败的rows event在它之前的entry都投递之后返回Err.
+ *  解码线程中的panic转换为Err, 与dump线程中的panic一样使dump失败
+ * </pre>
+ */
+pub struct ParallelDecoder {
+    max_in_flight: usize,
+    sender: Option<Sender<(u64, RowsTask)>>,
+    results: Receiver<(u64, Result<Entry, String>)>,
+    workers: Vec<JoinHandle<()>>,
+    // 队首的序号, 小于该值的结果已经投递或者被reset丢弃
+    head: u64,
+    // 按序号排队等待投递, None为还在解码的rows event
+    queue: VecDeque<Option<Result<Entry, String>>>,
+    in_flight: usize,
+    stats: Arc<Mutex<ParallelDecodeStats>>,
+    // 解码完成时记录rows_emitted
+    metrics: Option<Arc<Mutex<StreamMetrics>>>,
+}
+
+impl ParallelDecoder {
+    pub fn new(threads: usize, max_in_flight: usize) -> Result<ParallelDecoder, String> {
+        let (sender, tasks) = channel::<(u64, RowsTask)>();
+        let (done, results) = channel();
+        let tasks = Arc::new(Mutex::new(tasks));
+        let mut workers = Vec::with_capacity(threads);
+        for index in 0..threads.max(1) {
+            let tasks = tasks.clone();
+            let done = done.clone();
+            let handle = thread::Builder::new()
+                .name(format!("decode-rows-{}", index))
+                .spawn(move || run_worker(tasks, done))
+                .map_err(|e| format!("spawn decode thread {} failure: {}", index, e))?;
+            workers.push(handle);
+        }
+        Ok(ParallelDecoder {
+            max_in_flight: max_in_flight.max(1),
+            sender: Some(sender),
+            results,
+            workers,
+            head: 0,
+            queue: VecDeque::new(),
+            in_flight: 0,
+            stats: Arc::new(Mutex::new(ParallelDecodeStats::default())),
+            metrics: None,
+        })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    // 句柄可以在其它线程中读取, 重连时parser把同一个句柄传给新的decoder
+    pub fn stats(&self) -> Arc<Mutex<ParallelDecodeStats>> {
+        self.stats.clone()
+    }
+
+    pub fn set_stats(&mut self, stats: Arc<Mutex<ParallelDecodeStats>>) {
+        self.stats = stats;
+    }
+
+    pub fn set_metrics(&mut self, metrics: Arc<Mutex<StreamMetrics>>) {
+        self.metrics = Some(metrics);
+    }
+
+    // 在解码的rows event达到max_in_flight时先等待队首完成
+    pub fn submit<F>(&mut self, task: RowsTask, deliver: &mut F) -> Result<(), String>
+        where F: FnMut(Entry) -> Result<(), String> {
+        while self.in_flight >= self.max_in_flight {
+            self.receive(true)?;
+            self.deliver_ready(deliver)?;
+        }
+        let sequence = self.head + self.queue.len() as u64;
+        let sender = self.sender.as_ref().ok_or("parallel decoder is closed")?;
+        sender.send((sequence, task)).map_err(|_| "decode threads exited".to_string())?;
+        self.queue.push_back(None);
+        self.in_flight += 1;
+        let in_flight = self.in_flight;
+        self.update_stats(|stats| {
+            stats.tasks += 1;
+            stats.max_in_flight = stats.max_in_flight.max(in_flight);
+        });
+        self.receive(false)?;
+        self.deliver_ready(deliver)
+    }
+
+    // 不需要解码的entry, 前面没有排队的entry时直接投递
+    pub fn push<F>(&mut self, entry: Entry, deliver: &mut F) -> Result<(), String>
+        where F: FnMut(Entry) -> Result<(), String> {
+        self.receive(false)?;
+        self.deliver_ready(deliver)?;
+        if self.queue.is_empty() {
+            return deliver(entry);
+        }
+        self.queue.push_back(Some(Ok(entry)));
+        Ok(())
+    }
+
+    pub fn barrier<F>(&mut self, deliver: &mut F) -> Result<(), String>
+        where F: FnMut(Entry) -> Result<(), String> {
+        if self.in_flight > 0 {
+            let in_flight = self.in_flight as u64;
+            self.update_stats(|stats| {
+                stats.barriers += 1;
+                stats.drained += in_flight;
+            });
+        }
+        self.drain(deliver)
+    }
+
+    // 等待并投递所有排队的entry, 例如dump正常结束时
+    pub fn drain<F>(&mut self, deliver: &mut F) -> Result<(), String>
+        where F: FnMut(Entry) -> Result<(), String> {
+        loop {
+            self.deliver_ready(deliver)?;
+            if self.queue.is_empty() {
+                return Ok(());
+            }
+            self.receive(true)?;
+        }
+    }
+
+    // 解码线程仍然会完成已经提交的任务, 它们的结果按序号丢弃
+    pub fn reset(&mut self) {
+        self.head += self.queue.len() as u64;
+        self.queue.clear();
+        self.in_flight = 0;
+    }
+
+    // block为true时至少等待一个结果
+    fn receive(&mut self, block: bool) -> Result<(), String> {
+        let mut block = block && self.in_flight > 0;
+        loop {
+            let (sequence, result) = if block {
+                self.results.recv().map_err(|_| "decode threads exited".to_string())?
+            } else {
+                match self.results.try_recv() {
+                    Ok(result) => result,
+                    Err(_) => return Ok(()),
+                }
+            };
+            if sequence < self.head {
+                continue;
+            }
+            if let Some(slot) = self.queue.get_mut((sequence - self.head) as usize) {
+                if let (Ok(entry), Some(metrics)) = (result.as_ref(), self.metrics.as_ref()) {
+                    let rows = entry.row_change().map_or(0, |row_change| row_change.row_datas().len());
+                    rate::mark(metrics, RateKind::RowsEmitted, rows as u64);
+                }
+                *slot = Some(result);
+                self.in_flight -= 1;
+                block = false;
+            }
+        }
+    }
+
+    fn deliver_ready<F>(&mut self, deliver: &mut F) -> Result<(), String>
+        where F: FnMut(Entry) -> Result<(), String> {
+        while let Some(Some(_)) = self.queue.front() {
+            let result = self.queue.pop_front().flatten().expect("front is ready");
+            self.head += 1;
+            deliver(result?)?;
+        }
+        Ok(())
+    }
+
+    fn update_stats<F: FnOnce(&mut ParallelDecodeStats)>(&self, f: F) {
+        if let Ok(mut stats) = self.stats.lock() {
+            f(&mut stats);
+        }
+    }
+}
+
+impl Drop for ParallelDecoder {
+    // 关闭任务队列, 解码线程完成当前任务后退出
+    fn drop(&mut self) {
+        self.sender = None;
+        for handle in self.workers.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run_worker(tasks: Arc<Mutex<Receiver<(u64, RowsTask)>>>, done: Sender<(u64, Result<Entry, String>)>) {
+    loop {
+        let received = match tasks.lock() {
+            Ok(tasks) => tasks.recv(),
+            Err(_) => return,
+        };
+        let (sequence, task) = match received {
+            Ok(received) => received,
+            Err(_) => return,
+        };
+        let position = format!("{}:{}", task.header().log_file_name(), task.header().log_file_offset());
+        let result = catch_unwind(AssertUnwindSafe(|| task.decode()))
+            .unwrap_or_else(|_| Err(format!("panic while decoding rows event at {}", position)));
+        if done.send((sequence, result)).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::command::gtid::GtidSet;
+use crate::filter::EventFilter;
+use crate::instance::executor::{sink_stage, stage_channel, InstanceExecutor, DEFAULT_STAGE_CAPACITY};
+use crate::instance::offline::OfflineParser;
+use crate::instance::position_manager::{FlushPolicy, LogPosition, MemoryPositionManager, PositionManager, PositionSaver};
+use crate::instance::running::MysqlEventParser;
+use crate::instance::EntryPosition;
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+pub const DEFAULT_BATCH_ENTRIES: usize = 1000;
+pub const DEFAULT_BATCH_WAIT: Duration = Duration::from_secs(1);
+
+// fetch + decode阶段, 产生entry
+pub trait EntrySource: Send {
+    // 阻塞直到结束或者被停止, 每个entry交给sink, 结束时调用sink.flush
+    fn run(&mut self, sink: Box<dyn EventSink>) -> Result<(), String>;
+
+    // 其它线程通过store(false)停止, 不支持时返回None
+    fn running_handle(&self) -> Option<Arc<AtomicBool>> {
+        None
+    }
+
+    // 从PositionManager中保存的位点继续
+    fn resume_from(&mut self, position: LogPosition) -> Result<(), String> {
+        let position = position.position();
+        Err(format!("source can not resume from {}:{}", position.journal_name(), position.position()))
+    }
+
+    // 按gtid set dump时source当前的集合, 之后确认的事务的gtid加入该集合一起保存
+    fn gtid_set(&self) -> Option<GtidSet> {
+        None
+    }
+}
+
+impl EntrySource for MysqlEventParser {
+    fn run(&mut self, sink: Box<dyn EventSink>) -> Result<(), String> {
+        self.set_entry_sink(sink);
+        let result = self.start();
+        self.take_entry_sink();
+        result.map_err(String::from)
+    }
+
+    fn running_handle(&self) -> Option<Arc<AtomicBool>> {
+        Some(MysqlEventParser::running_handle(self))
+    }
+
+    // 作为StartMode::Stored的位点, 保存的gtid set覆盖set_gtid_set设置的集合
+    fn resume_from(&mut self, position: LogPosition) -> Result<(), String> {
+        if let (Some(gtid_set), Ok(mut current)) = (position.gtid_set(), self.gtid_set_handle().lock()) {
+            *current = Some(gtid_set.clone());
+        }
+        self.set_entry_position(position.position().clone());
+        Ok(())
+    }
+
+    fn gtid_set(&self) -> Option<GtidSet> {
+        MysqlEventParser::gtid_set(self)
+    }
+}
+
+// 离线解析一批binlog文件, 文件总是从头解析
+pub struct OfflineSource {
+    parser: OfflineParser,
+    files: Vec<PathBuf>,
+}
+
+impl OfflineSource {
+    pub fn new(parser: OfflineParser, files: Vec<PathBuf>) -> OfflineSource {
+        OfflineSource { parser, files }
+    }
+}
+
+impl EntrySource for OfflineSource {
+    fn run(&mut self, mut sink: Box<dyn EventSink>) -> Result<(), String> {
+        self.parser.parse(&self.files, sink.as_mut()).map(|_| ())
+    }
+}
+
+// transform阶段, 返回None时丢弃该entry
+pub trait EntryTransform: Send {
+    fn transform(&mut self, entry: Entry) -> Result<Option<Entry>, String>;
+}
+
+impl<F> EntryTransform for F where F: FnMut(Entry) -> Result<Option<Entry>, String> + Send {
+    fn transform(&mut self, entry: Entry) -> Result<Option<Entry>, String> {
+        self(entry)
+    }
+}
+
+// batch阶段: 投递的entry数达到max_entries或者距离上次flush超过max_wait时flush所有sink并保存位点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchPolicy {
+    max_entries: usize,
+    max_wait: Duration,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        BatchPolicy::new(DEFAULT_BATCH_ENTRIES, DEFAULT_BATCH_WAIT)
+    }
+}
+
+impl BatchPolicy {
+    pub fn new(max_entries: usize, max_wait: Duration) -> BatchPolicy {
+        BatchPolicy { max_entries: max_entries.max(1), max_wait }
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+    pub fn max_wait(&self) -> Duration {
+        self.max_wait
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PipelineStats {
+    received: u64,
+    // 被filter丢弃
+    filtered: u64,
+    // 被transform丢弃
+    dropped: u64,
+    delivered: u64,
+    flushes: u64,
+    // 最近一次保存的位点
+    committed: Option<EntryPosition>,
+}
+
+impl PipelineStats {
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+    pub fn filtered(&self) -> u64 {
+        self.filtered
+    }
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+    pub fn flushes(&self) -> u64 {
+        self.flushes
+    }
+    pub fn committed(&self) -> Option<&EntryPosition> {
+        self.committed.as_ref()
+    }
+}
+
+/**
+ * <pre>
+ *  按显式声明的阶段组装pipeline, 代替MysqlEventParser内部固定的流程:
+ *      source(fetch + decode) -> filter -> transform -> sink... -> batch(flush) -> position manager
+ *  filter全部通过的entry依次经过transform, 之后按注册顺序交给每个sink.
+ *  filter/transform只影响投递, 位点仍然按source的每个事务推进, 被过滤的事务同样会保存位点.
+ *  batch到期或者position manager的FlushPolicy到期时flush所有sink, 之后由PositionSaver保存位点.
+ *      let pipeline = Pipeline::new(parser)
+ *          .filter(RegexFilter::new("shop\\..*")?)
+ *          .transform(|entry| Ok(Some(entry)))
+ *          .batch(BatchPolicy::new(500, Duration::from_millis(200)))
+ *          .sink(kafka)
+ *          .sink(audit)
+ *          .position_manager(FilePositionManager::new(Path::new("/data/example.position")))
+ *          .build()?;
+ *      pipeline.run()?;                    // 在当前线程中运行
+ *      pipeline.spawn(&mut executor)?;     // 或者source和其它阶段分别运行在executor的source/sink线程中
+ * </pre>
+ */
+pub struct Pipeline {
+    source: Box<dyn EntrySource>,
+    stage: ProcessStage,
+    capacity: usize,
+}
+
+impl Pipeline {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<S: EntrySource + 'static>(source: S) -> PipelineBuilder {
+        PipelineBuilder {
+            source: Box::new(source),
+            filters: vec![],
+            transforms: vec![],
+            batch: BatchPolicy::default(),
+            sinks: vec![],
+            position_manager: None,
+            flush_policy: FlushPolicy::default(),
+            capacity: DEFAULT_STAGE_CAPACITY,
+        }
+    }
+
+    pub fn stats(&self) -> Arc<Mutex<PipelineStats>> {
+        self.stage.stats.clone()
+    }
+
+    // 例如 source -> filter(1) -> transform(1) -> sink(2) -> batch(500 entries, 200ms) -> position
+    pub fn describe(&self) -> String {
+        let mut stages = vec!["source".to_string()];
+        if !self.stage.filters.is_empty() {
+            stages.push(format!("filter({})", self.stage.filters.len()));
+        }
+        if !self.stage.transforms.is_empty() {
+            stages.push(format!("transform({})", self.stage.transforms.len()));
+        }
+        stages.push(format!("sink({})", self.stage.sinks.len()));
+        stages.push(format!("batch({} entries, {:?})", self.stage.batch.max_entries, self.stage.batch.max_wait));
+        if self.stage.persistent {
+            stages.push("position".to_string());
+        }
+        stages.join(" -> ")
+    }
+
+    // 在当前线程中运行, source结束之后返回
+    pub fn run(mut self) -> Result<(), String> {
+        self.source.run(Box::new(self.stage))
+    }
+
+    /**
+     * <pre>
+     *  source运行在executor的source线程, 其它阶段运行在sink线程, 之间为容量为capacity的channel.
+     *  executor.stop()同时停止source; sink线程失败时source在下一个entry时停止
+     * </pre>
+     */
+    pub fn spawn(self, executor: &mut InstanceExecutor) -> Result<(), String> {
+        let Pipeline { mut source, stage, capacity } = self;
+        let (sender, receiver) = stage_channel(capacity);
+        let running = source.running_handle();
+        if let Some(running) = running.clone() {
+            executor.add_stop_handle(running);
+        }
+        executor.spawn("source", move |_| source.run(Box::new(ChannelSink { sender, running })))?;
+        executor.spawn("sink", sink_stage(receiver, Box::new(stage)))
+    }
+}
+
+pub struct PipelineBuilder {
+    source: Box<dyn EntrySource>,
+    filters: Vec<Box<dyn EventFilter>>,
+    transforms: Vec<Box<dyn EntryTransform>>,
+    batch: BatchPolicy,
+    sinks: Vec<Box<dyn EventSink>>,
+    position_manager: Option<Box<dyn PositionManager>>,
+    flush_policy: FlushPolicy,
+    capacity: usize,
+}
+
+impl PipelineBuilder {
+    pub fn filter<F: EventFilter + 'static>(mut self, filter: F) -> PipelineBuilder {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    pub fn transform<T: EntryTransform + 'static>(mut self, transform: T) -> PipelineBuilder {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    pub fn batch(mut self, batch: BatchPolicy) -> PipelineBuilder {
+        self.batch = batch;
+        self
+    }
+
+    pub fn sink<S: EventSink + 'static>(mut self, sink: S) -> PipelineBuilder {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    // 已经是Box<dyn EventSink>的sink, 例如SinkRegistry创建的sink
+    pub fn boxed_sink(mut self, sink: Box<dyn EventSink>) -> PipelineBuilder {
+        self.sinks.push(sink);
+        self
+    }
+
+    pub fn position_manager<P: PositionManager + 'static>(mut self, position_manager: P) -> PipelineBuilder {
+        self.position_manager = Some(Box::new(position_manager));
+        self
+    }
+
+    // 保存位点的时机, 默认为FlushPolicy::default(); batch到期时同样保存
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> PipelineBuilder {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    // spawn时source与sink线程之间channel的容量
+    pub fn capacity(mut self, capacity: usize) -> PipelineBuilder {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    // 至少需要一个sink; position manager中有保存的位点时source从该位点继续
+    pub fn build(mut self) -> Result<Pipeline, String> {
+        if self.sinks.is_empty() {
+            return Err("pipeline has no sink".to_string());
+        }
+        // 没有position manager时只在内存中跟踪位点, 用于PipelineStats::committed
+        let persistent = self.position_manager.is_some();
+        let manager = self.position_manager.unwrap_or_else(|| Box::new(MemoryPositionManager::new()));
+        let mut saver = PositionSaver::new(manager, self.flush_policy);
+        let stored = saver.load()?;
+        if let Some(stored) = stored.clone() {
+            let position = stored.position();
+            println!("pipeline resumes from {}:{}, gtid set {:?}", position.journal_name(), position.position(),
+                     stored.gtid_set().map(|gtid_set| gtid_set.to_string()));
+            self.source.resume_from(stored)?;
+        }
+        saver.start_dump(self.source.gtid_set());
+        let committed = stored.map(|stored| stored.position().clone());
+        let stats = PipelineStats { committed, ..PipelineStats::default() };
+        let stage = ProcessStage {
+            filters: self.filters,
+            transforms: self.transforms,
+            sinks: self.sinks,
+            batch: self.batch,
+            saver,
+            persistent,
+            pending: 0,
+            last_flush: Instant::now(),
+            stats: Arc::new(Mutex::new(stats)),
+        };
+        Ok(Pipeline { source: self.source, stage, capacity: self.capacity })
+    }
+}
+
+// filter -> transform -> sinks -> batch -> position manager
+struct ProcessStage {
+    filters: Vec<Box<dyn EventFilter>>,
+    transforms: Vec<Box<dyn EntryTransform>>,
+    sinks: Vec<Box<dyn EventSink>>,
+    batch: BatchPolicy,
+    // 跟踪事务结束之后的位点, flush之后保存
+    saver: PositionSaver,
+    // 是否设置了position manager
+    persistent: bool,
+    // 上次flush之后投递的entry数
+    pending: usize,
+    last_flush: Instant,
+    stats: Arc<Mutex<PipelineStats>>,
+}
+
+impl ProcessStage {
+    fn update_stats<F: FnOnce(&mut PipelineStats)>(&self, f: F) {
+        if let Ok(mut stats) = self.stats.lock() {
+            f(&mut stats);
+        }
+    }
+
+    fn deliver(&mut self, entry: &Entry) -> Result<bool, String> {
+        if !self.filters.iter().all(|filter| filter.filter(entry)) {
+            self.update_stats(|stats| stats.filtered += 1);
+            return Ok(false);
+        }
+        if self.transforms.is_empty() {
+            return self.deliver_to_sinks(entry).map(|_| true);
+        }
+        let mut transformed = entry.clone();
+        for transform in self.transforms.iter_mut() {
+            transformed = match transform.transform(transformed)? {
+                Some(transformed) => transformed,
+                None => {
+                    self.update_stats(|stats| stats.dropped += 1);
+                    return Ok(false);
+                }
+            };
+        }
+        self.deliver_to_sinks(&transformed).map(|_| true)
+    }
+
+    fn deliver_to_sinks(&mut self, entry: &Entry) -> Result<(), String> {
+        for (index, sink) in self.sinks.iter_mut().enumerate() {
+            sink.on_event(entry).map_err(|e| format!("sink {} failure: {}", index, e))?;
+        }
+        Ok(())
+    }
+
+    fn flush_sinks(&mut self) -> Result<(), String> {
+        for (index, sink) in self.sinks.iter_mut().enumerate() {
+            sink.flush().map_err(|e| format!("flush sink {} failure: {}", index, e))?;
+        }
+        self.pending = 0;
+        self.last_flush = Instant::now();
+        self.saver.persist()?;
+        let committed = self.saver.persisted().map(|persisted| persisted.position().clone());
+        self.update_stats(|stats| {
+            stats.flushes += 1;
+            stats.committed = committed;
+        });
+        Ok(())
+    }
+}
+
+impl EventSink for ProcessStage {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.update_stats(|stats| stats.received += 1);
+        if self.deliver(entry)? {
+            self.pending += 1;
+            self.update_stats(|stats| stats.delivered += 1);
+        }
+        let acked = self.saver.ack(entry) && self.persistent;
+        let due = acked || self.pending >= self.batch.max_entries || self.last_flush.elapsed() >= self.batch.max_wait;
+        if due && (self.pending > 0 || self.saver.is_dirty()) {
+            self.flush_sinks()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.flush_sinks()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.sinks.iter().any(|sink| sink.is_backpressured())
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.sinks.iter_mut().try_for_each(|sink| sink.reconfigure(config))
+    }
+}
+
+// source线程中的sink, 把entry交给sink线程; sink线程已经结束时停止source
+struct ChannelSink {
+    sender: SyncSender<Entry>,
+    running: Option<Arc<AtomicBool>>,
+}
+
+impl EventSink for ChannelSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        if self.sender.send(entry.clone()).is_err() {
+            if let Some(running) = self.running.as_ref() {
+                running.store(false, Ordering::SeqCst);
+            }
+            return Err("sink stage of the pipeline is stopped".to_string());
+        }
+        Ok(())
+    }
+}
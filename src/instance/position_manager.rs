@@ -0,0 +1,287 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::command::gtid::GtidSet;
+use crate::config::{get_duration, parse_properties, Properties};
+use crate::instance::EntryPosition;
+use crate::protocol::{Entry, EntryType};
+
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// 已经被entry sink确认的位点, 按gtid set dump时同时记录已经确认的gtid set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogPosition {
+    position: EntryPosition,
+    gtid_set: Option<GtidSet>,
+}
+
+impl LogPosition {
+    pub fn new(position: EntryPosition, gtid_set: Option<GtidSet>) -> LogPosition {
+        LogPosition { position, gtid_set }
+    }
+
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+    pub fn gtid_set(&self) -> Option<&GtidSet> {
+        self.gtid_set.as_ref()
+    }
+}
+
+/**
+ * <pre>
+ *  对应canal中的CanalLogPositionManager, 保存已经投递并被sink确认的位点(包括server_id和gtid set),
+ *  重启之后作为StartMode::Stored的位点继续, 保存的gtid set优先于master.gtid_set.
+ *  位点总是在事务结束之后, 最后一次保存之后确认的entry在重启之后会再次投递(at least once).
+ *  内置memory和file两种实现, 其它存储(zookeeper, 数据库等)实现该trait之后
+ *  通过MysqlEventParser::set_position_manager或者PipelineBuilder::position_manager使用, 由PositionSaver按FlushPolicy调用
+ * </pre>
+ */
+pub trait PositionManager: Send {
+    // 没有保存过时返回None
+    fn load(&mut self) -> Result<Option<LogPosition>, String>;
+
+    fn persist(&mut self, position: &LogPosition) -> Result<(), String>;
+}
+
+// 只保存在内存中, clone之后共享同一个位点, 可以在其它线程中读取, 或者在supervisor重新创建的parser之间传递
+#[derive(Debug, Clone, Default)]
+pub struct MemoryPositionManager {
+    position: Arc<Mutex<Option<LogPosition>>>,
+}
+
+impl MemoryPositionManager {
+    pub fn new() -> MemoryPositionManager {
+        MemoryPositionManager::default()
+    }
+
+    pub fn position(&self) -> Option<LogPosition> {
+        self.position.lock().ok().and_then(|position| position.clone())
+    }
+}
+
+impl PositionManager for MemoryPositionManager {
+    fn load(&mut self) -> Result<Option<LogPosition>, String> {
+        Ok(self.position())
+    }
+
+    fn persist(&mut self, position: &LogPosition) -> Result<(), String> {
+        let mut saved = self.position.lock().map_err(|_| "position is poisoned".to_string())?;
+        *saved = Some(position.clone());
+        Ok(())
+    }
+}
+
+/**
+ * <pre>
+ *  以properties格式保存在文件中, 先写入临时文件再rename, 不会留下写了一半的文件:
+ *      journal_name=mysql-bin.000001
+ *      position=1024
+ *      timestamp=1700000000000
+ *      server_id=1
+ *      gtid_set=3e11fa47-71ca-11e1-9e33-c80aa9429562:1-20     按gtid set dump时才有
+ *  也可以读取旧版本pipeline写入的 journal_name:position 格式
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct FilePositionManager {
+    path: PathBuf,
+}
+
+impl FilePositionManager {
+    pub fn new(path: &Path) -> FilePositionManager {
+        FilePositionManager { path: path.to_path_buf() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl PositionManager for FilePositionManager {
+    fn load(&mut self) -> Result<Option<LogPosition>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&self.path).map_err(|e| format!("read {} failure: {}", self.path.display(), e))?;
+        if !text.contains('=') {
+            let (journal_name, position) = text.trim().rsplit_once(':')
+                .and_then(|(journal_name, position)| Some((journal_name, position.parse::<u64>().ok()?)))
+                .ok_or_else(|| format!("invalid position '{}' in {}", text.trim(), self.path.display()))?;
+            return Ok(Some(LogPosition::new(EntryPosition::new(journal_name, position), None)));
+        }
+        let properties = parse_properties(&text).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        let journal_name = properties.get("journal_name")
+            .ok_or_else(|| format!("{} has no journal_name", self.path.display()))?;
+        let number = |key: &str| match properties.get(key) {
+            Some(value) => value.parse::<u64>().map_err(|_| format!("invalid {} '{}' in {}", key, value, self.path.display())),
+            None => Ok(0),
+        };
+        let mut position = EntryPosition::new(journal_name, number("position")?);
+        position.set_timestamp(number("timestamp")? as i64);
+        position.set_server_id(number("server_id")? as u32);
+        let gtid_set = match properties.get("gtid_set") {
+            Some(gtid_set) => Some(GtidSet::parse(gtid_set).map_err(|e| format!("invalid gtid_set in {}: {}", self.path.display(), e))?),
+            None => None,
+        };
+        Ok(Some(LogPosition::new(position, gtid_set)))
+    }
+
+    fn persist(&mut self, position: &LogPosition) -> Result<(), String> {
+        let entry_position = position.position();
+        let mut text = format!("journal_name={}\nposition={}\ntimestamp={}\nserver_id={}\n", entry_position.journal_name(),
+                               entry_position.position(), entry_position.timestamp(), entry_position.server_id());
+        if let Some(gtid_set) = position.gtid_set() {
+            text.push_str(&format!("gtid_set={}\n", gtid_set));
+        }
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, text).map_err(|e| format!("write {} failure: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &self.path).map_err(|e| format!("rename {} failure: {}", tmp.display(), e))
+    }
+}
+
+/**
+ * <pre>
+ *  何时保存已经确认的位点, 保存之前总是先flush entry sink, 保证sink缓冲中的entry已经写出:
+ *      OnAck       每个事务被sink确认之后立即flush并保存, 重启之后最多重复一个事务
+ *      Periodic    距离上次保存超过interval之后, 在下一个被确认的事务结束时flush并保存
+ *  parser停止时总是flush并保存
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    OnAck,
+    Periodic(Duration),
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy::Periodic(DEFAULT_FLUSH_INTERVAL)
+    }
+}
+
+impl FlushPolicy {
+    // master.position_manager.flush_interval, 0表示OnAck, 没有配置时为默认的1s
+    pub fn from_properties(properties: &Properties) -> Result<FlushPolicy, String> {
+        Ok(match get_duration(properties, "master.position_manager.flush_interval")? {
+            Some(interval) if interval.is_zero() => FlushPolicy::OnAck,
+            Some(interval) => FlushPolicy::Periodic(interval),
+            None => FlushPolicy::default(),
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FlushPolicy::OnAck => "on_ack",
+            FlushPolicy::Periodic(_) => "periodic",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  跟踪sink确认的entry, 按FlushPolicy保存到PositionManager:
+ *  事务结束(TransactionEnd)以及事务外的entry(DDL)之后的位点为可以保存的位点, 同时把该事务的gtid加入gtid set.
+ *  在parser的dump线程或者pipeline的batch阶段中使用, sink返回Ok的entry才调用ack
+ * </pre>
+ */
+pub struct PositionSaver {
+    manager: Box<dyn PositionManager>,
+    policy: FlushPolicy,
+    in_transaction: bool,
+    // 按gtid set dump时已经确认的gtid集合
+    gtid_set: Option<GtidSet>,
+    acked: Option<LogPosition>,
+    persisted: Option<LogPosition>,
+    last_flush: Instant,
+    flushes: u64,
+}
+
+impl PositionSaver {
+    pub fn new(manager: Box<dyn PositionManager>, policy: FlushPolicy) -> PositionSaver {
+        PositionSaver {
+            manager,
+            policy,
+            in_transaction: false,
+            gtid_set: None,
+            acked: None,
+            persisted: None,
+            last_flush: Instant::now(),
+            flushes: 0,
+        }
+    }
+
+    pub fn policy(&self) -> FlushPolicy {
+        self.policy
+    }
+    pub fn acked(&self) -> Option<&LogPosition> {
+        self.acked.as_ref()
+    }
+    pub fn persisted(&self) -> Option<&LogPosition> {
+        self.persisted.as_ref()
+    }
+    pub fn flushes(&self) -> u64 {
+        self.flushes
+    }
+    // 有已经确认但还没有保存的位点
+    pub fn is_dirty(&self) -> bool {
+        self.acked.is_some() && self.acked != self.persisted
+    }
+
+    // 保存的gtid set作为之后确认的事务的基础
+    pub fn load(&mut self) -> Result<Option<LogPosition>, String> {
+        let position = self.manager.load()?;
+        if let Some(gtid_set) = position.as_ref().and_then(|position| position.gtid_set()) {
+            self.gtid_set = Some(gtid_set.clone());
+        }
+        self.persisted = position.clone();
+        Ok(position)
+    }
+
+    // 每次dump开始时调用, 重新dump会从未结束事务的开头再次投递. 按gtid set dump时gtid_set为parser当前的集合
+    pub fn start_dump(&mut self, gtid_set: Option<GtidSet>) {
+        self.in_transaction = false;
+        if self.gtid_set.is_none() {
+            self.gtid_set = gtid_set;
+        }
+    }
+
+    // entry被sink确认之后调用, 返回true时调用方需要flush sink之后调用persist
+    pub fn ack(&mut self, entry: &Entry) -> bool {
+        match entry.entry_type() {
+            EntryType::TransactionBegin => {
+                self.in_transaction = true;
+                return false;
+            }
+            EntryType::TransactionEnd => self.in_transaction = false,
+            EntryType::RowData if !self.in_transaction => {}
+            _ => return false,
+        }
+        let header = entry.header();
+        if let Some(gtid_set) = self.gtid_set.as_mut().filter(|_| !header.gtid().is_empty()) {
+            let _ = gtid_set.add(header.gtid());
+        }
+        let mut position = header.position();
+        position.set_position(header.log_file_offset() + header.event_length() as u64);
+        self.acked = Some(LogPosition::new(position, self.gtid_set.clone()));
+        match self.policy {
+            FlushPolicy::OnAck => true,
+            FlushPolicy::Periodic(interval) => self.last_flush.elapsed() >= interval,
+        }
+    }
+
+    // 保存最近确认的位点, 与上次保存的相同时跳过
+    pub fn persist(&mut self) -> Result<(), String> {
+        self.last_flush = Instant::now();
+        let acked = match self.acked.as_ref() {
+            Some(acked) if self.persisted.as_ref() != Some(acked) => acked,
+            _ => return Ok(()),
+        };
+        self.manager.persist(acked)?;
+        self.persisted = Some(acked.clone());
+        self.flushes += 1;
+        Ok(())
+    }
+}
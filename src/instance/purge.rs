@@ -0,0 +1,64 @@
+use crate::command::gtid::GtidSet;
+use crate::instance::EntryPosition;
+
+/**
+ * <pre>
+ *  请求的binlog已经在master上被purge时(dump返回1236, 且show binary logs中没有该文件)的处理方式.
+ *  除Fail之外, 跳转之前都会向incident sink投递一个Incident entry, 记录丢失的区间:
+ *  Fail        停止parser并返回错误, 不再重试
+ *  Earliest    从master上最早的binlog开头继续, 丢失被purge的部分
+ *  Latest      从show master status的位置继续, 丢失之前的所有变更
+ *  Snapshot    以最新位点调用Snapshotter做全量快照, 之后从快照的位点继续(CLONE快照的位点由clone_status决定)
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PurgedBinlogStrategy {
+    #[default]
+    Fail,
+    Earliest,
+    Latest,
+    Snapshot,
+}
+
+impl PurgedBinlogStrategy {
+    pub fn from_name(name: &str) -> Result<PurgedBinlogStrategy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "fail" => Ok(PurgedBinlogStrategy::Fail),
+            "earliest" => Ok(PurgedBinlogStrategy::Earliest),
+            "latest" => Ok(PurgedBinlogStrategy::Latest),
+            "snapshot" => Ok(PurgedBinlogStrategy::Snapshot),
+            _ => Err(format!("unknown purged binlog strategy {}, expect fail/earliest/latest/snapshot", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PurgedBinlogStrategy::Fail => "fail",
+            PurgedBinlogStrategy::Earliest => "earliest",
+            PurgedBinlogStrategy::Latest => "latest",
+            PurgedBinlogStrategy::Snapshot => "snapshot",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  全量快照, 由使用方实现(例如按主键分段select并写入下游).
+ *  position为快照完成后parser开始dump的位点, 实现需要保证快照包含该位点之前的所有变更,
+ *  例如在FLUSH TABLES WITH READ LOCK或者一致性快照事务中读取, 返回Err时parser停止
+ * </pre>
+ */
+pub trait Snapshotter: Send {
+    fn snapshot(&mut self, position: &EntryPosition) -> Result<(), String>;
+
+    // 快照的位点由快照本身决定时(例如CLONE)返回实际的位点, parser从该位点继续, 默认为传入的position
+    fn snapshot_at(&mut self, position: &EntryPosition) -> Result<EntryPosition, String> {
+        self.snapshot(position)?;
+        Ok(position.clone())
+    }
+
+    // 快照包含的GTID集合, parser用于初始化GTID跳号检查
+    fn gtid_executed(&self) -> Option<GtidSet> {
+        None
+    }
+}
@@ -0,0 +1,103 @@
+use std::ops::Range;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::command::event::rows::ROWS_HEADER_LEN_V1;
+use crate::command::event::{EventType, LogContext, LogHeader, RowsLogBuffer, RowsLogEvent, LOG_HEADER_LEN};
+use crate::command::hex::HexDump;
+use crate::command::log_buffer::LogBuffer;
+use crate::filter::projection::ColumnProjection;
+
+/**
+ * <pre>
+ *  错误日志和DLQ记录中附带event hex时需要遮盖的敏感列, 格式与列投影相同:
+ *      user.account: phone, id_card; pay.card: number
+ *  只处理rows event, 敏感列的值按table map逐行定位, 对应的字节输出为**.
+ *  无法逐列定位时遮盖整个rows部分(post header之后), 宁可多遮盖也不输出明文:
+ *      table map缺失, 没有列名(binlog_row_metadata=MINIMAL), partial update, event本身无法解析
+ *  其它event(包括query event中的sql)原样输出
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SensitiveColumns {
+    tables: ColumnProjection,
+}
+
+impl SensitiveColumns {
+    pub fn new(config: &str) -> Result<SensitiveColumns, String> {
+        let tables = ColumnProjection::new(config).map_err(|e| format!("invalid sensitive columns: {}", e))?;
+        Ok(SensitiveColumns { tables })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn is_sensitive(&self, schema: &str, table: &str, column: &str) -> bool {
+        self.tables.columns(schema, table)
+            .is_some_and(|columns| columns.iter().any(|name| name.eq_ignore_ascii_case(column)))
+    }
+
+    // event中需要遮盖的字节区间
+    pub fn ranges(&self, event: &[u8], context: &LogContext) -> Vec<Range<usize>> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let header = match LogHeader::from_bytes(event, context.checksum_alg()) {
+            Ok(header) if header.event_type().is_some_and(|kind| kind.is_rows()) => header,
+            _ => return vec![],
+        };
+        let post_header_len = context.format_description()
+            .and_then(|description| header.event_type().and_then(|kind| description.post_header_len(kind)))
+            .unwrap_or(ROWS_HEADER_LEN_V1);
+        let end = (LOG_HEADER_LEN + header.data_len()).min(event.len());
+        let whole = (LOG_HEADER_LEN + post_header_len).min(end)..end;
+        // 解码时panic的event同样可能在这里panic
+        match catch_unwind(AssertUnwindSafe(|| self.row_ranges(header, event, context))) {
+            Ok(Ok(Some(ranges))) => ranges,
+            Ok(Ok(None)) => vec![],
+            _ => vec![whole],
+        }
+    }
+
+    // 表没有配置敏感列时返回None
+    fn row_ranges(&self, header: LogHeader, event: &[u8], context: &LogContext)
+                  -> Result<Option<Vec<Range<usize>>>, String> {
+        let description = context.format_description().ok_or("format description is missing")?;
+        let rows_event = RowsLogEvent::from(header, &mut LogBuffer::new(event), description)?;
+        let table = context.get_table(rows_event.table_id())
+            .ok_or_else(|| format!("table map of table id {} is missing", rows_event.table_id()))?;
+        if self.tables.columns(table.db_name(), table.table_name()).is_none() {
+            return Ok(None);
+        }
+        if rows_event.header().event_type() == Some(EventType::PartialUpdateRowsEvent) {
+            return Err("partial update rows can not be redacted by column".to_string());
+        }
+        let column_info = table.column_info();
+        let sensitive = column_info.iter()
+            .map(|info| info.name().map(|name| self.is_sensitive(table.db_name(), table.table_name(), name)))
+            .collect::<Option<Vec<bool>>>()
+            .ok_or_else(|| format!("column names of {}.{} are missing", table.db_name(), table.table_name()))?;
+        // rows在event中的起点
+        let offset = (LOG_HEADER_LEN + rows_event.header().data_len()).saturating_sub(rows_event.rows().len());
+        let mut buffer = RowsLogBuffer::new(rows_event.rows());
+        let mut ranges = vec![];
+        while buffer.has_next() {
+            let mut spans = buffer.next_row_spans(rows_event.columns(), column_info)?;
+            if rows_event.is_update() {
+                spans.extend(buffer.next_row_spans(rows_event.change_columns(), column_info)?);
+            }
+            ranges.extend(spans.into_iter().filter(|(i, _)| sensitive[*i])
+                .map(|(_, span)| offset + span.start..offset + span.end));
+        }
+        Ok(Some(ranges))
+    }
+
+    // 已经遮盖敏感列的hex dump
+    pub fn dump<'a>(&self, event: &'a [u8], context: &LogContext) -> HexDump<'a> {
+        let mut dump = HexDump::new(event);
+        for range in self.ranges(event, context) {
+            dump.redact(range);
+        }
+        dump
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::command::event::{EventType, LogHeader, RotateLogEvent, BINLOG_MAGIC};
+use crate::command::log_buffer::LogBuffer;
+use crate::encryption::{is_encrypted, EncryptedFileReader, FileCipher, KeyProvider, ENCRYPTED_HEADER_LEN, TAG_LEN};
+use crate::instance::relay_index::{IndexEntry, RelayIndexWriter, DEFAULT_INDEX_INTERVAL};
+use crate::instance::EntryPosition;
+use crate::metrics::MemoryGauge;
+
+/**
+ * <pre>
+ *  将master发送的原始event按binlog文件格式写入本地目录, 不做任何解码,
+ *  只解析header用于文件切换和位点记录, 写出的文件可直接被mysqlbinlog读取.
+ *  - fake rotate只用于切换文件, 不会写入
+ *  - 从非起始位置dump时master补发的format description(log_pos=0)不会写入
+ *  - heartbeat不会写入
+ *  设置了KeyProvider时文件按encryption模块的格式加密, 每个event一条记录,
+ *  解密后与未加密的文件内容相同, 位点仍然是binlog中的位置.
+ *  默认同时写入稀疏索引(mysql-bin.000001.idx, 见relay_index), 回放时可以按位点或gtid直接定位
+ * </pre>
+ */
+pub struct RelayLogWriter {
+    directory: PathBuf,
+    file: Option<BufWriter<File>>,
+    position: EntryPosition,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    cipher: Option<FileCipher>,
+    // 下一个记录在文件中的偏移, 未加密时与position相同
+    file_offset: u64,
+    index: Option<RelayIndexWriter>,
+    // None表示不写索引
+    index_interval: Option<u64>,
+    // 写缓冲中还没有写入文件的字节数
+    buffered: usize,
+    memory_gauge: Option<MemoryGauge>,
+}
+
+impl RelayLogWriter {
+    pub fn new(directory: &Path) -> Result<RelayLogWriter, String> {
+        std::fs::create_dir_all(directory)
+            .map_err(|e| format!("create relay directory {} failure: {}", directory.display(), e))?;
+        Ok(RelayLogWriter {
+            directory: directory.to_path_buf(),
+            file: None,
+            position: EntryPosition::default(),
+            key_provider: None,
+            cipher: None,
+            file_offset: 0,
+            index: None,
+            index_interval: Some(DEFAULT_INDEX_INTERVAL),
+            buffered: 0,
+            memory_gauge: None,
+        })
+    }
+
+    // 需要在第一个rotate之前设置
+    pub fn set_key_provider(&mut self, key_provider: Arc<dyn KeyProvider>) {
+        self.key_provider = Some(key_provider);
+    }
+
+    // 需要在第一个rotate之前设置
+    pub fn set_index_interval(&mut self, index_interval: Option<u64>) {
+        self.index_interval = index_interval;
+    }
+
+    pub fn index_interval(&self) -> Option<u64> {
+        self.index_interval
+    }
+
+    // 同步写缓冲的字节数, 例如 MemoryStats::gauge("relay")
+    pub fn set_memory_gauge(&mut self, gauge: MemoryGauge) {
+        if let Some(previous) = self.memory_gauge.as_ref() {
+            previous.sub(self.buffered);
+        }
+        gauge.add(self.buffered);
+        self.memory_gauge = Some(gauge);
+    }
+
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+
+    pub fn write(&mut self, header: &LogHeader, event: &[u8]) -> Result<(), String> {
+        match header.event_type() {
+            Some(EventType::RotateEvent) => {
+                let rotate = RotateLogEvent::from(header.clone(), &mut LogBuffer::new(event), None)?;
+                if !rotate.is_fake() && !header.is_artificial() {
+                    self.append(header, event)?;
+                }
+                if rotate.filename() != self.position.journal_name() {
+                    self.open(rotate.filename())?;
+                }
+                Ok(())
+            }
+            Some(kind) if kind.is_heartbeat() => Ok(()),
+            _ if header.is_artificial() || header.log_pos() == 0 => Ok(()),
+            _ => self.append(header, event),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        if let Some(file) = self.file.as_mut() {
+            file.flush().map_err(|e| format!("flush relay log failure: {}", e))?;
+        }
+        self.sync_memory();
+        match self.index.as_mut() {
+            Some(index) => index.flush(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn close(&mut self) -> Result<(), String> {
+        self.flush()?;
+        self.file = None;
+        self.index = None;
+        self.sync_memory();
+        Ok(())
+    }
+
+    fn append(&mut self, header: &LogHeader, event: &[u8]) -> Result<(), String> {
+        let file = self.file.as_mut()
+            .ok_or_else(|| "relay log is not opened, a rotate event is expected first".to_string())?;
+        let entry = IndexEntry::new(self.position.position(), self.file_offset,
+                                    self.cipher.as_ref().map_or(0, |cipher| cipher.counter()));
+        let record = match self.cipher.as_mut() {
+            Some(cipher) => cipher.encrypt(event)?,
+            None => event.to_vec(),
+        };
+        file.write_all(&record).map_err(|e| format!("write relay log failure: {}", e))?;
+        self.file_offset += record.len() as u64;
+        if let Some(index) = self.index.as_mut() {
+            index.append(entry, header, event)?;
+        }
+        self.position.set_position(header.log_pos() as u64);
+        self.position.set_timestamp(header.when() as i64 * 1000);
+        self.position.set_server_id(header.server_id());
+        self.sync_memory();
+        Ok(())
+    }
+
+    fn sync_memory(&mut self) {
+        let buffered = self.file.as_ref().map_or(0, |file| file.buffer().len());
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.adjust(self.buffered, buffered);
+        }
+        self.buffered = buffered;
+    }
+
+    // 已存在的文件视为断点续传, 直接追加
+    fn open(&mut self, filename: &str) -> Result<(), String> {
+        self.close()?;
+        let path = self.directory.join(filename);
+        if self.key_provider.is_some() {
+            return self.open_encrypted(filename, &path);
+        }
+        if is_encrypted(&path) {
+            return Err(format!("relay log {} is encrypted but no key provider is configured", path.display()));
+        }
+        let length = path.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let exists = length >= BINLOG_MAGIC.len() as u64;
+        let file = OpenOptions::new().create(true).append(true).open(&path)
+            .map_err(|e| format!("open relay log {} failure: {}", path.display(), e))?;
+        let mut file = BufWriter::new(file);
+        if !exists {
+            file.write_all(&BINLOG_MAGIC).map_err(|e| format!("write relay log failure: {}", e))?;
+        }
+        self.file = Some(file);
+        self.position = EntryPosition::new(filename, length.max(BINLOG_MAGIC.len() as u64));
+        self.file_offset = self.position.position();
+        self.open_index(&path)
+    }
+
+    // 续写时解密已有的记录得到binlog中的位置, 并截掉最后一条不完整的记录
+    fn open_encrypted(&mut self, filename: &str, path: &Path) -> Result<(), String> {
+        let key_provider = self.key_provider.clone().ok_or_else(|| "key provider is not configured".to_string())?;
+        let length = path.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let (cipher, valid_length, position) = if length > 0 {
+            if !is_encrypted(path) {
+                return Err(format!("relay log {} exists but is not encrypted", path.display()));
+            }
+            let file = File::open(path).map_err(|e| format!("open relay log {} failure: {}", path.display(), e))?;
+            let mut reader = EncryptedFileReader::new(BufReader::new(file), key_provider.as_ref())?;
+            let (mut valid_length, mut position) = (ENCRYPTED_HEADER_LEN as u64, 0u64);
+            while let Some(record) = reader.next_record()? {
+                valid_length += (4 + record.len() + TAG_LEN) as u64;
+                position += record.len() as u64;
+            }
+            (reader.into_cipher(), valid_length, position)
+        } else {
+            (FileCipher::create(key_provider.as_ref())?, 0, 0)
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("open relay log {} failure: {}", path.display(), e))?;
+        if valid_length > 0 && valid_length < length {
+            file.set_len(valid_length).map_err(|e| format!("truncate relay log {} failure: {}", path.display(), e))?;
+        }
+        let mut file = BufWriter::new(file);
+        self.cipher = Some(cipher);
+        if position == 0 {
+            let cipher = self.cipher.as_mut().ok_or_else(|| "cipher is missing".to_string())?;
+            let mut head = if valid_length == 0 { cipher.header() } else { vec![] };
+            head.extend_from_slice(&cipher.encrypt(&BINLOG_MAGIC)?);
+            file.write_all(&head).map_err(|e| format!("write relay log failure: {}", e))?;
+            self.file_offset = valid_length + head.len() as u64;
+        } else {
+            self.file_offset = valid_length;
+        }
+        self.file = Some(file);
+        self.position = EntryPosition::new(filename, position.max(BINLOG_MAGIC.len() as u64));
+        self.open_index(path)
+    }
+
+    fn open_index(&mut self, path: &Path) -> Result<(), String> {
+        self.index = match self.index_interval {
+            Some(interval) => Some(RelayIndexWriter::open(path, interval)?),
+            None => None,
+        };
+        Ok(())
+    }
+}
+
+impl Drop for RelayLogWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.sub(self.buffered);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::command::event::{LogHeader, BINLOG_MAGIC, LOG_HEADER_LEN};
+use crate::command::gtid::event_gtid;
+use crate::encryption::{is_encrypted, EncryptedFileReader, KeyProvider, ENCRYPTED_HEADER_LEN, TAG_LEN};
+
+pub const RELAY_INDEX_SUFFIX: &str = ".idx";
+// 相邻两个位点索引之间的最小间隔, 字节
+pub const DEFAULT_INDEX_INTERVAL: u64 = 1024 * 1024;
+
+// 索引中的一项, 指向一个event的开头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    // binlog中的位置
+    position: u64,
+    // relay文件中的偏移, 未加密的文件与position相同
+    offset: u64,
+    // 加密文件中该event所在记录的序号, 用于恢复nonce, 未加密的文件为0
+    record: u32,
+}
+
+impl IndexEntry {
+    pub fn new(position: u64, offset: u64, record: u32) -> IndexEntry {
+        IndexEntry { position, offset, record }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    pub fn record(&self) -> u32 {
+        self.record
+    }
+}
+
+pub fn index_path(relay_file: &Path) -> PathBuf {
+    let mut name = relay_file.as_os_str().to_os_string();
+    name.push(RELAY_INDEX_SUFFIX);
+    PathBuf::from(name)
+}
+
+/**
+ * <pre>
+ *  relay文件旁的稀疏索引(mysql-bin.000001.idx), 文本格式, 每行一项:
+ *      P <position> <offset> <record>          位点索引, 同一个文件中至少间隔interval字节
+ *      G <gtid> <position> <offset> <record>   每个gtid event一项
+ *  只追加, 续写relay文件时同样追加. 加密文件截掉不完整的记录之后, offset超出文件长度的项在加载时丢弃
+ * </pre>
+ */
+pub struct RelayIndexWriter {
+    file: BufWriter<File>,
+    interval: u64,
+    last_position: Option<u64>,
+}
+
+impl RelayIndexWriter {
+    pub fn open(relay_file: &Path, interval: u64) -> Result<RelayIndexWriter, String> {
+        let path = index_path(relay_file);
+        let file = OpenOptions::new().create(true).append(true).open(&path)
+            .map_err(|e| format!("open relay index {} failure: {}", path.display(), e))?;
+        Ok(RelayIndexWriter { file: BufWriter::new(file), interval, last_position: None })
+    }
+
+    // event写入relay文件之后调用, event为完整的原始event
+    pub fn append(&mut self, entry: IndexEntry, header: &LogHeader, event: &[u8]) -> Result<(), String> {
+        if let Some(gtid) = event_gtid(header, event) {
+            self.last_position = Some(entry.position);
+            return writeln!(self.file, "G {} {} {} {}", gtid, entry.position, entry.offset, entry.record)
+                .map_err(|e| format!("write relay index failure: {}", e));
+        }
+        if self.last_position.is_some_and(|last| entry.position < last + self.interval) {
+            return Ok(());
+        }
+        self.last_position = Some(entry.position);
+        writeln!(self.file, "P {} {} {}", entry.position, entry.offset, entry.record)
+            .map_err(|e| format!("write relay index failure: {}", e))
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.file.flush().map_err(|e| format!("flush relay index failure: {}", e))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RelayIndex {
+    // 按position排序
+    positions: Vec<IndexEntry>,
+    gtids: BTreeMap<String, IndexEntry>,
+}
+
+impl RelayIndex {
+    // 索引文件不存在时返回空索引, 格式错误的行忽略
+    pub fn load(relay_file: &Path) -> Result<RelayIndex, String> {
+        let path = index_path(relay_file);
+        let mut index = RelayIndex::default();
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => return Ok(index),
+        };
+        let length = relay_file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("read relay index {} failure: {}", path.display(), e))?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (gtid, numbers) = match fields.first() {
+                Some(&"P") if fields.len() == 4 => (None, &fields[1..]),
+                Some(&"G") if fields.len() == 5 => (Some(fields[1]), &fields[2..]),
+                _ => continue,
+            };
+            let entry = match (numbers[0].parse(), numbers[1].parse(), numbers[2].parse()) {
+                (Ok(position), Ok(offset), Ok(record)) => IndexEntry::new(position, offset, record),
+                _ => continue,
+            };
+            if entry.offset >= length {
+                continue;
+            }
+            if let Some(gtid) = gtid {
+                index.gtids.insert(gtid.to_string(), entry);
+            }
+            index.positions.push(entry);
+        }
+        index.positions.sort_by_key(|entry| entry.position);
+        index.positions.dedup_by_key(|entry| entry.position);
+        Ok(index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+    pub fn positions(&self) -> &Vec<IndexEntry> {
+        &self.positions
+    }
+
+    // 不大于position的最后一项
+    pub fn floor(&self, position: u64) -> Option<IndexEntry> {
+        let index = self.positions.partition_point(|entry| entry.position <= position);
+        index.checked_sub(1).map(|index| self.positions[index])
+    }
+
+    pub fn gtid(&self, gtid: &str) -> Option<IndexEntry> {
+        self.gtids.get(gtid).copied()
+    }
+}
+
+enum RelaySource {
+    Plain(BufReader<File>),
+    Encrypted(Box<EncryptedFileReader<BufReader<File>>>),
+}
+
+/**
+ * <pre>
+ *  按event读取RelayLogWriter写出的文件(包括加密文件), 借助索引定位:
+ *      seek_position   跳到索引中不大于position的最近一项, 再向后跳过position之前的event
+ *      seek_gtid       直接跳到gtid event, 索引中没有时返回Err
+ *  没有索引文件时从文件开头扫描. 解码event需要的format description在文件开头, 需要时单独读取
+ * </pre>
+ */
+pub struct RelayLogReader {
+    path: PathBuf,
+    source: RelaySource,
+    index: RelayIndex,
+    // 下一个event在binlog中的位置
+    position: u64,
+}
+
+impl RelayLogReader {
+    pub fn open(path: &Path, key_provider: Option<Arc<dyn KeyProvider>>) -> Result<RelayLogReader, String> {
+        let file = File::open(path).map_err(|e| format!("open relay log {} failure: {}", path.display(), e))?;
+        let source = match (is_encrypted(path), key_provider) {
+            (true, Some(key_provider)) => {
+                let mut reader = EncryptedFileReader::new(BufReader::new(file), key_provider.as_ref())?;
+                // 第一条记录为BINLOG_MAGIC
+                reader.next_record()?;
+                RelaySource::Encrypted(Box::new(reader))
+            }
+            (true, None) => return Err(format!("relay log {} is encrypted but no key provider is configured", path.display())),
+            (false, _) => {
+                let mut reader = BufReader::new(file);
+                reader.seek(SeekFrom::Start(BINLOG_MAGIC.len() as u64))
+                    .map_err(|e| format!("seek relay log {} failure: {}", path.display(), e))?;
+                RelaySource::Plain(reader)
+            }
+        };
+        Ok(RelayLogReader {
+            path: path.to_path_buf(),
+            source,
+            index: RelayIndex::load(path)?,
+            position: BINLOG_MAGIC.len() as u64,
+        })
+    }
+
+    pub fn index(&self) -> &RelayIndex {
+        &self.index
+    }
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn seek_position(&mut self, position: u64) -> Result<(), String> {
+        let start = match self.index.floor(position) {
+            Some(entry) => entry,
+            None => self.first_entry(),
+        };
+        if start.position > self.position || position < self.position {
+            self.seek(start)?;
+        }
+        while self.position < position {
+            let event_len = match self.next_event()? {
+                Some(event) => event.len() as u64,
+                None => return Err(format!("position {} is beyond the end of relay log {}", position, self.path.display())),
+            };
+            if self.position > position {
+                return Err(format!("position {} is inside an event of {} bytes in relay log {}",
+                                   position, event_len, self.path.display()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn seek_gtid(&mut self, gtid: &str) -> Result<(), String> {
+        let entry = self.index.gtid(gtid)
+            .ok_or_else(|| format!("gtid {} is not in the index of relay log {}", gtid, self.path.display()))?;
+        self.seek(entry)
+    }
+
+    // 文件结束或者最后一个event不完整时返回None
+    pub fn next_event(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let event = match &mut self.source {
+            RelaySource::Encrypted(reader) => match reader.next_record()? {
+                Some(event) => event,
+                None => return Ok(None),
+            },
+            RelaySource::Plain(reader) => {
+                let mut event = vec![0u8; LOG_HEADER_LEN];
+                if !read_event_part(reader, &mut event)? {
+                    return Ok(None);
+                }
+                let header = LogHeader::from_bytes(&event, 0)?;
+                let event_len = header.event_len() as usize;
+                if event_len < LOG_HEADER_LEN {
+                    return Err(format!("corrupted event length {} at {} in relay log {}",
+                                       event_len, self.position, self.path.display()));
+                }
+                event.resize(event_len, 0);
+                if !read_event_part(reader, &mut event[LOG_HEADER_LEN..])? {
+                    return Ok(None);
+                }
+                event
+            }
+        };
+        self.position += event.len() as u64;
+        Ok(Some(event))
+    }
+
+    fn first_entry(&self) -> IndexEntry {
+        match self.source {
+            // 第0条记录为BINLOG_MAGIC
+            RelaySource::Encrypted(_) => {
+                IndexEntry::new(BINLOG_MAGIC.len() as u64, (ENCRYPTED_HEADER_LEN + 4 + BINLOG_MAGIC.len() + TAG_LEN) as u64, 1)
+            }
+            RelaySource::Plain(_) => IndexEntry::new(BINLOG_MAGIC.len() as u64, BINLOG_MAGIC.len() as u64, 0),
+        }
+    }
+
+    fn seek(&mut self, entry: IndexEntry) -> Result<(), String> {
+        match &mut self.source {
+            RelaySource::Encrypted(reader) => reader.seek(entry.offset, entry.record)?,
+            RelaySource::Plain(reader) => {
+                reader.seek(SeekFrom::Start(entry.offset))
+                    .map_err(|e| format!("seek relay log {} failure: {}", self.path.display(), e))?;
+            }
+        }
+        self.position = entry.position;
+        Ok(())
+    }
+}
+
+// 读满buf返回true, 在开头就结束返回false, 读到一半结束(写入时进程退出)同样返回false
+fn read_event_part<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, String> {
+    let mut offset = 0;
+    while offset < buf.len() {
+        match reader.read(&mut buf[offset..]) {
+            Ok(0) => return Ok(false),
+            Ok(size) => offset += size,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(format!("read relay log failure: {}", e)),
+        }
+    }
+    Ok(true)
+}
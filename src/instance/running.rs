@@ -0,0 +1,1934 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::channel::mysql_socket::MysqlConnector;
+use crate::channel::DEFAULT_CONNECT_TIMEOUT;
+use crate::channel::tls::SslMode;
+use crate::command::com::{BinlogDumpCommand, BinlogDumpGtidCommand, RegisterSlaveCommand, BINLOG_SEND_ANNOTATE_ROWS_EVENT};
+use crate::command::errno::{ServerErrno, ServerError};
+use crate::command::event::{checksum, EventType, FormatDescriptionLogEvent, LogContext, LogDecoder, LogEvent, LogHeader,
+                            RotateLogEvent, UnsupportedEventPolicy, BINLOG_MAGIC};
+use crate::command::gtid::{event_gtid, GtidSet};
+use crate::command::log_buffer::LogBuffer;
+use crate::command::msc::ERROR_HEADER;
+use crate::config::{get_duration, ConfigChange, Properties};
+use crate::encryption::KeyProvider;
+use crate::error::CanalError;
+use crate::instance::backoff::Backoff;
+use crate::instance::catchup::{CatchUp, CatchUpPolicy, CatchUpStatus};
+use crate::instance::clock::{ClockSkew, ClockSkewMode};
+use crate::instance::containment::{contain, panic_report, PanicContainment};
+use crate::instance::convert::LogEventConvert;
+use crate::instance::ddl::SqlMode;
+use crate::instance::decode_trace::DecodeTracer;
+use crate::instance::describe::TableSchemas;
+use crate::instance::failover::{source_changes, SourceChange, SourceChangePolicy, SourceIdentity};
+use crate::instance::file_stats::{BinlogFileStats, FileStatsTracker};
+use crate::instance::fetcher::{DirectLogFetcher, FetchTimeout};
+use crate::instance::gtid_gap::{GtidGapDetector, GtidGapPolicy};
+use crate::instance::incident::IncidentPolicy;
+use crate::instance::parallel_decode::{parallel_decode_settings, ParallelDecodeStats, ParallelDecoder, DEFAULT_MAX_IN_FLIGHT};
+use crate::instance::position_manager::{FilePositionManager, FlushPolicy, LogPosition, MemoryPositionManager, PositionManager, PositionSaver};
+use crate::instance::purge::{PurgedBinlogStrategy, Snapshotter};
+use crate::instance::redaction::SensitiveColumns;
+use crate::instance::schema_history::SchemaHistory;
+use crate::instance::relay::RelayLogWriter;
+use crate::instance::self_test::self_test;
+use crate::instance::start::{explicit_position, StartMode, StartPolicy, StartPosition};
+use crate::instance::status::{ConnectionState, ParserStatus};
+use crate::instance::tracker::{PartialTransactionPolicy, PositionTracker};
+use crate::instance::variables::ServerVariables;
+use crate::instance::watchdog::{PipelineProgress, ProgressSink, StallReports, Watchdog, WatchdogConfig};
+use crate::instance::{AuthenticationInfo, EntryPosition};
+use crate::metrics::rate;
+use crate::metrics::{ChannelStats, MemoryGauge, MemoryStats, RateKind, StreamMetrics};
+use crate::protocol::{Entry, EntryType, Header};
+use crate::sink::EventSink;
+
+pub const DEFAULT_SLAVE_ID: u32 = 65535;
+
+// 等待重试期间检查running状态的间隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// sink背压期间检查是否可以继续读取的间隔
+const BACKPRESSURE_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+// 没有设置heartbeat_timeout和heartbeat_grace时, 连续错过3个heartbeat认为超时
+const HEARTBEAT_TIMEOUT_FACTOR: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseMode {
+    // 完整解析event
+    Decode,
+    // 不解析event body, 原始event直接写入relay目录, 用于binlog备份
+    Raw(PathBuf),
+}
+
+/**
+ * <pre>
+ *  对应canal中的MysqlEventParser, 负责:
+ *  1. 建立连接并设置dump需要的session变量
+ *  2. 以slave身份注册并发送COM_BINLOG_DUMP, 设置了gtid set时发送COM_BINLOG_DUMP_GTID
+ *  3. 循环读取event, 按照ParseMode进行处理并维护当前位点
+ *  4. dump失败时按照backoff重新连接, 重新握手并注册为slave, Decode模式下从未结束事务的开头重新dump,
+ *     按gtid set dump时从已经结束的事务的gtid set继续, Raw模式下从relay log已写入的位置继续.
+ *     dump连接被master kill(维护, shutdown)时不计入重试次数, 立即更新position_handle之后重新连接.
+ *     只重试CanalError::is_recoverable的错误(网络错误, master返回的错误), 认证失败, 解码失败, sink失败
+ *     以及1236等重试之后仍然失败的错误直接返回
+ * </pre>
+ */
+pub struct MysqlEventParser {
+    authentication_info: AuthenticationInfo,
+    slave_id: u32,
+    mode: ParseMode,
+    // 当前的位点, 首次连接时按start_policy选择, 之后为重新dump的位点
+    position: Option<EntryPosition>,
+    start_policy: StartPolicy,
+    stored_position: Option<EntryPosition>,
+    explicit_position: Option<EntryPosition>,
+    // 首次连接时最终选择的启动位点
+    start: Option<StartPosition>,
+    // 按gtid set dump时已经执行的gtid集合, 每个事务结束时加入该事务的gtid, 重连时从这里继续
+    gtid_set: Arc<Mutex<Option<GtidSet>>>,
+    // 当前未结束事务的gtid
+    pending_gtid: Option<String>,
+    checksum_alg: u8,
+    tolerant: bool,
+    strict: bool,
+    backoff: Backoff,
+    decoder: LogDecoder,
+    convert: LogEventConvert,
+    // 解析器看到的表结构, 用于describe
+    schemas: TableSchemas,
+    // 按位点维护的表结构历史, 补全MINIMAL table map中的列名
+    schema_history: Option<Arc<Mutex<SchemaHistory>>>,
+    metrics: Arc<Mutex<StreamMetrics>>,
+    // 与master之间所有连接的网络统计
+    channel_stats: Arc<Mutex<ChannelStats>>,
+    // 落后较多时的ETA估算, 限速以及只解析关键表, 跨重连保留
+    catch_up: Option<CatchUp>,
+    // 行数据解码线程数, 0时在dump线程中解码
+    parallel_decode_threads: usize,
+    max_decode_in_flight: usize,
+    // 本次dump的并行解码, 只在有entry_sink时创建
+    parallel_decoder: Option<ParallelDecoder>,
+    parallel_decode_stats: Arc<Mutex<ParallelDecodeStats>>,
+    // 开发者排查解码错位时记录选中的rows event的解码过程
+    decode_tracer: Option<DecodeTracer>,
+    // start时先解码内置的fixture, 失败时拒绝启动
+    self_test: bool,
+    // 当前binlog文件的解析统计, rotate时输出并投递Info entry
+    file_stats: FileStatsTracker,
+    // Raw模式下用于加密relay log
+    relay_key_provider: Option<Arc<dyn KeyProvider>>,
+    purged_binlog_strategy: PurgedBinlogStrategy,
+    snapshotter: Option<Box<dyn Snapshotter>>,
+    // 接收Incident/Info等不是来自binlog的entry
+    incident_sink: Option<Box<dyn EventSink>>,
+    // 接收解析出的entry, 没有设置时entry被丢弃, 例如只做relay/校验时
+    entry_sink: Option<Box<dyn EventSink>>,
+    // 保存entry_sink确认的位点, start时从中恢复
+    position_saver: Option<PositionSaver>,
+    // 最近一次连接时master的变量快照
+    server_variables: Arc<Mutex<Option<ServerVariables>>>,
+    // 不需要重试的错误, 例如binlog被purge且策略为Fail
+    fatal: bool,
+    // sink要求停止instance的原因, 例如事务超过限制且策略为Abort, 之后sink返回的Err不再重试
+    abort: Arc<Mutex<Option<String>>>,
+    // 等待应用的热加载, 由dump循环在event之间取出
+    reload: Arc<Mutex<Option<ConfigChange>>>,
+    connect_timeout: Duration,
+    // packet读到一半时的socket超时
+    read_timeout: Option<Duration>,
+    // 通过master_heartbeat_period让master在空闲时发送heartbeat
+    heartbeat_period: Option<Duration>,
+    // 两个event/heartbeat之间的最长间隔, 没有设置时为heartbeat_period + heartbeat_grace
+    heartbeat_timeout: Option<Duration>,
+    heartbeat_grace: Option<Duration>,
+    last_timeout: Option<FetchTimeout>,
+    panic_containment: PanicContainment,
+    // 接收DeadLetter策略下无法解码的event, 没有设置时使用incident_sink
+    dead_letter_sink: Option<Box<dyn EventSink>>,
+    contained_panics: u64,
+    // 错误信息和DLQ记录中event hex需要遮盖的列
+    sensitive_columns: SensitiveColumns,
+    gtid_gap_policy: GtidGapPolicy,
+    partial_transaction_policy: PartialTransactionPolicy,
+    // 按PartialTransactionPolicy::Drop丢弃的event数
+    partial_events_dropped: u64,
+    // master的INCIDENT event的处理方式
+    incident_policy: IncidentPolicy,
+    // 跨重连保留, 重连之后重复收到的GTID不会被当作跳号
+    gtid_gaps: GtidGapDetector,
+    // 重连之后master的身份发生变化时的处理方式
+    source_change_policy: SourceChangePolicy,
+    // 最近一次连接的master身份, 跨重连保留
+    source: Option<SourceIdentity>,
+    source_changes: u64,
+    // dump连接被master kill的次数
+    kills: u64,
+    // 上一次dump是否因为被kill而结束, 重新连接成功之后清除
+    reconnecting_after_kill: bool,
+    // 每次dump结束时立即更新为重新dump的位点
+    position_handle: MemoryPositionManager,
+    // 连接状态, 最近解析的位点和错误, 用于Instance::status_json
+    status: Arc<Mutex<ParserStatus>>,
+    // master与本地时钟的偏差, 修正lag/位点/entry中的时间
+    clock_skew: ClockSkew,
+    // Raw模式下relay log写缓冲的gauge
+    relay_memory: Option<MemoryGauge>,
+    // fetch/decode/sink各阶段的进度, 由watchdog监控
+    progress: PipelineProgress,
+    watchdog: Option<WatchdogConfig>,
+    stalls: StallReports,
+    running: Arc<AtomicBool>,
+}
+
+impl MysqlEventParser {
+    pub fn new(authentication_info: AuthenticationInfo) -> MysqlEventParser {
+        MysqlEventParser {
+            authentication_info,
+            slave_id: DEFAULT_SLAVE_ID,
+            mode: ParseMode::Decode,
+            position: None,
+            start_policy: StartPolicy::default(),
+            stored_position: None,
+            explicit_position: None,
+            start: None,
+            gtid_set: Arc::new(Mutex::new(None)),
+            pending_gtid: None,
+            checksum_alg: checksum::BINLOG_CHECKSUM_ALG_OFF,
+            tolerant: false,
+            strict: false,
+            backoff: Backoff::default(),
+            decoder: LogDecoder::new(),
+            convert: LogEventConvert::new(),
+            schemas: TableSchemas::new(),
+            schema_history: None,
+            metrics: Arc::new(Mutex::new(StreamMetrics::new())),
+            channel_stats: Arc::new(Mutex::new(ChannelStats::new())),
+            catch_up: None,
+            parallel_decode_threads: 0,
+            max_decode_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            parallel_decoder: None,
+            parallel_decode_stats: Arc::new(Mutex::new(ParallelDecodeStats::default())),
+            decode_tracer: None,
+            self_test: false,
+            file_stats: FileStatsTracker::new(),
+            relay_key_provider: None,
+            purged_binlog_strategy: PurgedBinlogStrategy::Fail,
+            snapshotter: None,
+            incident_sink: None,
+            entry_sink: None,
+            position_saver: None,
+            server_variables: Arc::new(Mutex::new(None)),
+            fatal: false,
+            abort: Arc::new(Mutex::new(None)),
+            reload: Arc::new(Mutex::new(None)),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            read_timeout: None,
+            heartbeat_period: None,
+            heartbeat_timeout: None,
+            heartbeat_grace: None,
+            last_timeout: None,
+            panic_containment: PanicContainment::Off,
+            dead_letter_sink: None,
+            contained_panics: 0,
+            sensitive_columns: SensitiveColumns::default(),
+            gtid_gap_policy: GtidGapPolicy::Alert,
+            partial_transaction_policy: PartialTransactionPolicy::default(),
+            partial_events_dropped: 0,
+            incident_policy: IncidentPolicy::Halt,
+            gtid_gaps: GtidGapDetector::new(),
+            source_change_policy: SourceChangePolicy::Continue,
+            source: None,
+            source_changes: 0,
+            kills: 0,
+            reconnecting_after_kill: false,
+            position_handle: MemoryPositionManager::new(),
+            status: Arc::new(Mutex::new(ParserStatus::default())),
+            clock_skew: ClockSkew::default(),
+            relay_memory: None,
+            progress: PipelineProgress::new(),
+            watchdog: None,
+            stalls: Arc::new(Mutex::new(vec![])),
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // 配置指定的启动位点, 即StartMode::Explicit
+    pub fn set_position(&mut self, journal_name: &str, position: u64) {
+        self.explicit_position = Some(EntryPosition::new(journal_name, position));
+    }
+
+    // 使用之前持久化的位点启动(StartMode::Stored), 位点落在事务中间时会回退到事务开头
+    pub fn set_entry_position(&mut self, position: EntryPosition) {
+        self.stored_position = Some(position);
+    }
+
+    /**
+     * <pre>
+     *  按gtid set启动, 格式与gtid_executed相同, 例如 3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:11-18.
+     *  设置之后使用COM_BINLOG_DUMP_GTID, 由master从第一个不在集合中的事务开始发送, 优先于start_policy和位点;
+     *  之后每个事务结束时把它的gtid加入集合, 重连时同样按集合继续. 只支持Decode模式和不带tag的mysql gtid
+     * </pre>
+     */
+    pub fn set_gtid_set(&mut self, gtid_set: &str) -> Result<(), String> {
+        let gtid_set = GtidSet::parse(gtid_set)?;
+        if let Ok(mut current) = self.gtid_set.lock() {
+            *current = Some(gtid_set);
+        }
+        Ok(())
+    }
+
+    // 当前已经执行的gtid集合, 没有按gtid set启动时为None
+    pub fn gtid_set(&self) -> Option<GtidSet> {
+        self.gtid_set.lock().ok().and_then(|gtid_set| gtid_set.clone())
+    }
+
+    pub fn gtid_set_handle(&self) -> Arc<Mutex<Option<GtidSet>>> {
+        self.gtid_set.clone()
+    }
+
+    pub fn set_start_policy(&mut self, start_policy: StartPolicy) {
+        self.start_policy = start_policy;
+    }
+
+    pub fn start_policy(&self) -> &StartPolicy {
+        &self.start_policy
+    }
+
+    // 首次连接之前为None
+    pub fn start_position(&self) -> Option<&StartPosition> {
+        self.start.as_ref()
+    }
+
+    // master.start_mode, master.journal_name, master.position, master.gtid_set
+    pub fn apply_start_mode(&mut self, properties: &Properties) -> Result<(), String> {
+        let start_policy = StartPolicy::from_properties(properties)?;
+        if let Some(position) = explicit_position(properties)? {
+            self.explicit_position = Some(position);
+        }
+        if let Some(gtid_set) = properties.get("master.gtid_set").filter(|gtid_set| !gtid_set.trim().is_empty()) {
+            self.set_gtid_set(gtid_set).map_err(|e| format!("master.gtid_set: {}", e))?;
+        }
+        self.start_policy = start_policy;
+        Ok(())
+    }
+
+    pub fn position(&self) -> Option<&EntryPosition> {
+        self.position.as_ref()
+    }
+
+    pub fn set_slave_id(&mut self, slave_id: u32) {
+        self.slave_id = slave_id;
+    }
+
+    pub fn set_mode(&mut self, mode: ParseMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_relay_directory(&mut self, directory: &Path) {
+        self.mode = ParseMode::Raw(directory.to_path_buf());
+    }
+
+    pub fn set_relay_key_provider(&mut self, key_provider: Arc<dyn KeyProvider>) {
+        self.relay_key_provider = Some(key_provider);
+    }
+
+    pub fn set_purged_binlog_strategy(&mut self, strategy: PurgedBinlogStrategy) {
+        self.purged_binlog_strategy = strategy;
+    }
+
+    pub fn purged_binlog_strategy(&self) -> PurgedBinlogStrategy {
+        self.purged_binlog_strategy
+    }
+
+    // PurgedBinlogStrategy::Snapshot时使用
+    pub fn set_snapshotter(&mut self, snapshotter: Box<dyn Snapshotter>) {
+        self.snapshotter = Some(snapshotter);
+    }
+
+    // 除Incident之外, 每次连接时还会收到包含master变量快照的Info entry
+    pub fn set_incident_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.incident_sink = Some(sink);
+    }
+
+    // sink返回Err时本次dump失败, 按backoff从未结束事务的开头重新dump, 已经投递的entry会再次投递
+    pub fn set_entry_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.entry_sink = Some(Box::new(ProgressSink::new(sink, self.progress.sink().clone())));
+    }
+
+    pub fn take_entry_sink(&mut self) -> Option<Box<dyn EventSink>> {
+        self.entry_sink.take()
+    }
+
+    /**
+     * <pre>
+     *  start时从manager中恢复位点(StartMode::Stored), 保存的gtid set覆盖set_gtid_set设置的集合;
+     *  之后entry_sink确认的事务按policy保存, 保存之前先flush entry_sink. 没有entry_sink时不保存
+     * </pre>
+     */
+    pub fn set_position_manager(&mut self, manager: Box<dyn PositionManager>, policy: FlushPolicy) {
+        self.position_saver = Some(PositionSaver::new(manager, policy));
+    }
+
+    pub fn position_saver(&self) -> Option<&PositionSaver> {
+        self.position_saver.as_ref()
+    }
+
+    // master.position_manager.file, master.position_manager.flush_interval, 没有配置file时保持不变
+    pub fn apply_position_manager(&mut self, properties: &Properties) -> Result<(), String> {
+        let policy = FlushPolicy::from_properties(properties)?;
+        if let Some(file) = properties.get("master.position_manager.file").filter(|file| !file.trim().is_empty()) {
+            self.set_position_manager(Box::new(FilePositionManager::new(Path::new(file.trim()))), policy);
+        }
+        Ok(())
+    }
+
+    pub fn set_panic_containment(&mut self, panic_containment: PanicContainment) {
+        self.panic_containment = panic_containment;
+    }
+
+    pub fn panic_containment(&self) -> PanicContainment {
+        self.panic_containment
+    }
+
+    // 格式与列投影相同, 例如 user.account: phone, id_card
+    pub fn set_sensitive_columns(&mut self, sensitive_columns: SensitiveColumns) {
+        self.sensitive_columns = sensitive_columns;
+    }
+
+    pub fn sensitive_columns(&self) -> &SensitiveColumns {
+        &self.sensitive_columns
+    }
+
+    pub fn set_dead_letter_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.dead_letter_sink = Some(sink);
+    }
+
+    // 解码时被捕获的panic数
+    pub fn contained_panics(&self) -> u64 {
+        self.contained_panics
+    }
+
+    // 没有transaction_start的位点落在事务中间时, 事务后半段的处理方式, 默认丢弃
+    pub fn set_partial_transaction_policy(&mut self, partial_transaction_policy: PartialTransactionPolicy) {
+        self.partial_transaction_policy = partial_transaction_policy;
+    }
+
+    pub fn partial_transaction_policy(&self) -> PartialTransactionPolicy {
+        self.partial_transaction_policy
+    }
+
+    pub fn partial_events_dropped(&self) -> u64 {
+        self.partial_events_dropped
+    }
+
+    pub fn set_incident_policy(&mut self, incident_policy: IncidentPolicy) {
+        self.incident_policy = incident_policy;
+    }
+
+    pub fn incident_policy(&self) -> IncidentPolicy {
+        self.incident_policy
+    }
+
+    // 收到的GTID不连续时的处理方式, 默认Alert
+    pub fn set_gtid_gap_policy(&mut self, gtid_gap_policy: GtidGapPolicy) {
+        self.gtid_gap_policy = gtid_gap_policy;
+    }
+
+    pub fn gtid_gap_policy(&self) -> GtidGapPolicy {
+        self.gtid_gap_policy
+    }
+
+    // 可以通过seed用已执行的GTID集合初始化
+    pub fn gtid_gaps_mut(&mut self) -> &mut GtidGapDetector {
+        &mut self.gtid_gaps
+    }
+
+    pub fn gtid_gaps(&self) -> &GtidGapDetector {
+        &self.gtid_gaps
+    }
+
+    pub fn set_source_change_policy(&mut self, source_change_policy: SourceChangePolicy) {
+        self.source_change_policy = source_change_policy;
+    }
+
+    pub fn source_change_policy(&self) -> SourceChangePolicy {
+        self.source_change_policy
+    }
+
+    // master.source_change_policy=continue/latest/halt
+    pub fn apply_source_change(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.source_change_policy") {
+            let policy = SourceChangePolicy::from_name(value).map_err(|e| format!("master.source_change_policy: {}", e))?;
+            self.set_source_change_policy(policy);
+        }
+        Ok(())
+    }
+
+    // 最近一次连接的master身份, 首次连接之前为None
+    pub fn source(&self) -> Option<&SourceIdentity> {
+        self.source.as_ref()
+    }
+
+    // 发现master身份变化的次数
+    pub fn source_changes(&self) -> u64 {
+        self.source_changes
+    }
+
+    // 交给sink, 写入原因之后dump失败时不再重试, 每次start时清除
+    pub fn abort_handle(&self) -> Arc<Mutex<Option<String>>> {
+        self.abort.clone()
+    }
+
+    fn aborted(&self) -> bool {
+        self.abort.lock().map(|abort| abort.is_some()).unwrap_or(false)
+    }
+
+    // 交给ConfigWatcher所在的线程, 写入的ConfigChange在dump循环处理下一个event之前通过reload应用
+    pub fn reload_handle(&self) -> Arc<Mutex<Option<ConfigChange>>> {
+        self.reload.clone()
+    }
+
+    /**
+     * <pre>
+     *  应用热加载的配置, 所有配置校验通过之后才会生效:
+     *      master.catch_up.*       追赶模式的阈值和限速, 保留当前的追赶状态; 全部删除时关闭追赶模式
+     *      master.decode_trace.*   解码跟踪, 全部删除时关闭
+     *  其它master.*配置需要重启才能生效
+     * </pre>
+     */
+    pub fn reload(&mut self, change: &ConfigChange) -> Result<(), String> {
+        let catch_up = change.touches("master.catch_up.")
+            .then(|| CatchUpPolicy::from_properties(change.properties())).transpose()?;
+        let decode_tracer = change.touches("master.decode_trace.")
+            .then(|| DecodeTracer::from_properties(change.properties())).transpose()?;
+        match (catch_up, self.catch_up.as_mut()) {
+            (Some(Some(policy)), Some(catch_up)) => catch_up.set_policy(policy),
+            (Some(policy), _) => self.set_catch_up_policy(policy),
+            (None, _) => {}
+        }
+        if let Some(decode_tracer) = decode_tracer {
+            self.set_decode_tracer(decode_tracer);
+        }
+        Ok(())
+    }
+
+    fn apply_pending_reload(&mut self) {
+        let change = match self.reload.lock() {
+            Ok(mut reload) => reload.take(),
+            Err(_) => None,
+        };
+        if let Some(change) = change {
+            if let Err(e) = self.reload(&change) {
+                println!("config reload rejected by parser, keep current config: {}", e);
+            }
+        }
+    }
+
+    pub fn kills(&self) -> u64 {
+        self.kills
+    }
+
+    // 重新dump的位点, 用于在其它线程中读取, dump结束(包括被master kill)之后, 重新连接之前更新
+    pub fn position_handle(&self) -> MemoryPositionManager {
+        self.position_handle.clone()
+    }
+
+    // 与其它parser共享句柄, 例如supervisor重启时新的parser继续更新Instance的位点
+    pub fn set_position_handle(&mut self, position_handle: MemoryPositionManager) {
+        self.position_handle = position_handle;
+    }
+
+    pub fn status_handle(&self) -> Arc<Mutex<ParserStatus>> {
+        self.status.clone()
+    }
+
+    pub fn set_status_handle(&mut self, status: Arc<Mutex<ParserStatus>>) {
+        self.status = status;
+    }
+
+    // 最近一次连接时master的变量快照, 句柄可以在其它线程中使用
+    pub fn server_variables(&self) -> Arc<Mutex<Option<ServerVariables>>> {
+        self.server_variables.clone()
+    }
+
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.connect_timeout = connect_timeout;
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    /**
+     * <pre>
+     *  从配置中读取时间类的设置, 值按config::parse_duration解析(30s, 5m), 没有配置的保持不变:
+     *      master.connect_timeout, master.read_timeout, master.heartbeat_period, master.heartbeat_timeout,
+     *      master.heartbeat_grace
+     *  read_timeout/heartbeat_period/heartbeat_timeout为0时关闭
+     * </pre>
+     */
+    pub fn apply_timeouts(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(connect_timeout) = get_duration(properties, "master.connect_timeout")? {
+            self.connect_timeout = connect_timeout;
+        }
+        let optional = |key: &str| get_duration(properties, key)
+            .map(|duration| duration.map(|duration| Some(duration).filter(|duration| !duration.is_zero())));
+        if let Some(read_timeout) = optional("master.read_timeout")? {
+            self.read_timeout = read_timeout;
+        }
+        if let Some(heartbeat_period) = optional("master.heartbeat_period")? {
+            self.heartbeat_period = heartbeat_period;
+        }
+        if let Some(heartbeat_timeout) = optional("master.heartbeat_timeout")? {
+            self.heartbeat_timeout = heartbeat_timeout;
+        }
+        if let Some(heartbeat_grace) = get_duration(properties, "master.heartbeat_grace")? {
+            self.heartbeat_grace = Some(heartbeat_grace);
+        }
+        Ok(())
+    }
+
+    // master.ssl.mode=disabled|preferred|required|verify_ca|verify_identity, master.ssl.ca=ca证书的pem文件
+    pub fn apply_ssl(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.ssl.mode") {
+            let mode = SslMode::from_name(value).map_err(|e| format!("master.ssl.mode: {}", e))?;
+            self.authentication_info.set_ssl_mode(mode);
+        }
+        if let Some(value) = properties.get("master.ssl.ca") {
+            self.authentication_info.set_ssl_ca(Some(value.trim()).filter(|ca| !ca.is_empty()));
+        }
+        let info = &self.authentication_info;
+        if info.ssl_mode().verifies_certificate() && info.ssl_ca().is_none() {
+            return Err(format!("master.ssl.mode {} requires master.ssl.ca", info.ssl_mode().name()));
+        }
+        Ok(())
+    }
+
+    // master.clock_offset=auto/off/+1500ms, 没有配置时保持不变
+    pub fn apply_clock_skew(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.clock_offset") {
+            let mode = ClockSkewMode::from_name(value).map_err(|e| format!("master.clock_offset: {}", e))?;
+            self.set_clock_skew(mode);
+        }
+        Ok(())
+    }
+
+    pub fn set_clock_skew(&mut self, mode: ClockSkewMode) {
+        self.clock_skew = ClockSkew::new(mode);
+        self.update_status(|status| status.set_clock_offset(self.clock_skew.offset()));
+    }
+
+    pub fn clock_skew(&self) -> &ClockSkew {
+        &self.clock_skew
+    }
+
+    pub fn set_read_timeout(&mut self, read_timeout: Option<Duration>) {
+        self.read_timeout = read_timeout;
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    pub fn set_heartbeat_period(&mut self, heartbeat_period: Option<Duration>) {
+        self.heartbeat_period = heartbeat_period;
+    }
+
+    pub fn heartbeat_period(&self) -> Option<Duration> {
+        self.heartbeat_period
+    }
+
+    pub fn set_heartbeat_timeout(&mut self, heartbeat_timeout: Option<Duration>) {
+        self.heartbeat_timeout = heartbeat_timeout;
+    }
+
+    pub fn heartbeat_timeout(&self) -> Option<Duration> {
+        self.heartbeat_timeout.or_else(|| self.heartbeat_period.map(|period| match self.heartbeat_grace {
+            Some(grace) => period + grace,
+            None => period * HEARTBEAT_TIMEOUT_FACTOR,
+        }))
+    }
+
+    /**
+     * <pre>
+     *  dump之前通过set @master_heartbeat_period让master在空闲时每interval发送一个HEARTBEAT_LOG_EVENT,
+     *  超过interval + grace没有收到任何event或heartbeat时认为连接已经失效, 断开并按backoff重新连接.
+     *  interval为0时关闭heartbeat, 之前通过set_heartbeat_timeout设置的超时仍然生效
+     * </pre>
+     */
+    pub fn set_heartbeat_interval(&mut self, interval: Duration, grace: Duration) {
+        self.heartbeat_period = Some(interval).filter(|interval| !interval.is_zero());
+        self.heartbeat_grace = Some(grace);
+    }
+
+    pub fn heartbeat_grace(&self) -> Option<Duration> {
+        self.heartbeat_grace
+    }
+
+    // 最近一次dump失败是否由超时引起, 以及超时的类型
+    pub fn last_timeout(&self) -> Option<FetchTimeout> {
+        self.last_timeout
+    }
+
+    pub fn mode(&self) -> &ParseMode {
+        &self.mode
+    }
+
+    // 开启后table map的metadata解析失败时仍然返回部分信息, 而不是中断整个dump
+    pub fn set_tolerant(&mut self, tolerant: bool) {
+        self.tolerant = tolerant;
+    }
+
+    /**
+     * <pre>
+     *  strict模式下任何导致数据失真的情况都会停止parser并且不再重试, 错误中带有event的位置:
+     *      不认识的status var, 没有映射的字符集, 不完整的table map metadata(忽略tolerant),
+     *      被跳过的unknown/unimplemented event, 没有table map的rows event, DeadLetter策略下跳过的event
+     * </pre>
+     */
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    pub fn backoff(&self) -> &Backoff {
+        &self.backoff
+    }
+
+    // master.reconnect.initial_delay/max_delay/max_retries/jitter, 见Backoff::from_properties, 没有配置时保持不变
+    pub fn apply_reconnect(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(backoff) = Backoff::from_properties(properties, "master.reconnect")? {
+            self.set_backoff(backoff);
+        }
+        Ok(())
+    }
+
+    // 用于配置无法解析的event的处理方式, 以及查看丢弃的event数量
+    pub fn decoder(&self) -> &LogDecoder {
+        &self.decoder
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut LogDecoder {
+        &mut self.decoder
+    }
+
+    // 用于配置table map缺失时的处理方式
+    pub fn convert(&self) -> &LogEventConvert {
+        &self.convert
+    }
+
+    pub fn convert_mut(&mut self) -> &mut LogEventConvert {
+        &mut self.convert
+    }
+
+    // 按schema.table查询当前缓存的表结构, 句柄可以在其它线程中使用
+    pub fn schemas(&self) -> TableSchemas {
+        self.schemas.clone()
+    }
+
+    // 本连接的速率统计, 进程级别的汇总见metrics::rate::global()
+    pub fn metrics(&self) -> Arc<Mutex<StreamMetrics>> {
+        self.metrics.clone()
+    }
+
+    pub fn set_metrics(&mut self, metrics: Arc<Mutex<StreamMetrics>>) {
+        self.metrics = metrics;
+    }
+
+    // 读写字节数, short read以及按IoErrorClass分类的网络错误, 重连之后继续累加
+    pub fn channel_stats(&self) -> Arc<Mutex<ChannelStats>> {
+        self.channel_stats.clone()
+    }
+
+    pub fn set_channel_stats(&mut self, channel_stats: Arc<Mutex<ChannelStats>>) {
+        self.channel_stats = channel_stats;
+    }
+
+    // None时不查询binlog大小, 也不会进入追赶模式
+    pub fn set_catch_up_policy(&mut self, policy: Option<CatchUpPolicy>) {
+        self.catch_up = policy.map(CatchUp::new);
+    }
+
+    pub fn catch_up(&self) -> Option<&CatchUpStatus> {
+        self.catch_up.as_ref().map(|catch_up| catch_up.status())
+    }
+
+    // master.catch_up.*, 没有配置时保持不变
+    pub fn apply_catch_up(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(policy) = CatchUpPolicy::from_properties(properties)? {
+            self.set_catch_up_policy(Some(policy));
+        }
+        Ok(())
+    }
+
+    // threads为0时关闭. DDL和FORMAT_DESCRIPTION之前等待所有在解码的rows event完成, entry仍然按binlog顺序投递
+    pub fn set_parallel_decode(&mut self, threads: usize, max_in_flight: usize) {
+        self.parallel_decode_threads = threads;
+        self.max_decode_in_flight = max_in_flight.max(1);
+    }
+
+    pub fn parallel_decode_threads(&self) -> usize {
+        self.parallel_decode_threads
+    }
+
+    pub fn max_decode_in_flight(&self) -> usize {
+        self.max_decode_in_flight
+    }
+
+    // 跨重连累加
+    pub fn parallel_decode_stats(&self) -> Arc<Mutex<ParallelDecodeStats>> {
+        self.parallel_decode_stats.clone()
+    }
+
+    // master.parallel_decode.threads, master.parallel_decode.max_in_flight
+    pub fn apply_parallel_decode(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some((threads, max_in_flight)) = parallel_decode_settings(properties)? {
+            self.set_parallel_decode(threads, max_in_flight);
+        }
+        Ok(())
+    }
+
+    // 调用方保留tracer的clone, 与parser共享记录, 可以在其它线程中读取或者to_json
+    pub fn set_decode_tracer(&mut self, tracer: Option<DecodeTracer>) {
+        self.decode_tracer = tracer;
+    }
+
+    pub fn decode_tracer(&self) -> Option<&DecodeTracer> {
+        self.decode_tracer.as_ref()
+    }
+
+    // master.decode_trace.*, 没有配置时保持不变
+    pub fn apply_decode_trace(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(tracer) = DecodeTracer::from_properties(properties)? {
+            self.set_decode_tracer(Some(tracer));
+        }
+        Ok(())
+    }
+
+    pub fn set_self_test(&mut self, self_test: bool) {
+        self.self_test = self_test;
+    }
+
+    pub fn self_test(&self) -> bool {
+        self.self_test
+    }
+
+    // master.self_test=true|false
+    pub fn apply_self_test(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.self_test") {
+            let self_test = value.trim().parse::<bool>()
+                .map_err(|_| format!("master.self_test: invalid value {}, expect true/false", value))?;
+            self.set_self_test(self_test);
+        }
+        Ok(())
+    }
+
+    // master.cutover_detection=true|false
+    pub fn apply_cutover_detection(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.cutover_detection") {
+            let enabled = value.trim().parse::<bool>()
+                .map_err(|_| format!("master.cutover_detection: invalid value {}, expect true/false", value))?;
+            self.convert.set_cutover_detection(enabled);
+        }
+        Ok(())
+    }
+
+    // 调用方保留clone, 可以在dump之前通过put设置已有表的结构, 跨重连保留
+    pub fn set_schema_history(&mut self, schema_history: Option<Arc<Mutex<SchemaHistory>>>) {
+        self.schema_history = schema_history;
+    }
+
+    pub fn schema_history(&self) -> Option<Arc<Mutex<SchemaHistory>>> {
+        self.schema_history.clone()
+    }
+
+    // master.schema_history=true|false, 已经开启时保留已有的历史
+    pub fn apply_schema_history(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.schema_history") {
+            let enabled = value.trim().parse::<bool>()
+                .map_err(|_| format!("master.schema_history: invalid value {}, expect true/false", value))?;
+            if !enabled {
+                self.set_schema_history(None);
+            } else if self.schema_history.is_none() {
+                self.set_schema_history(Some(Arc::new(Mutex::new(SchemaHistory::new()))));
+            }
+        }
+        Ok(())
+    }
+
+    // master.temporary_table_policy/ghost_table_policy/ghost_tables,
+    // master.ignorable_event_policy, master.undecoded_rows_event_policy=skip|warn|fail
+    pub fn apply_transient_tables(&mut self, properties: &Properties) -> Result<(), String> {
+        self.convert.transient_tables_mut().apply(properties)?;
+        if let Some(value) = properties.get("master.ignorable_event_policy") {
+            let policy = UnsupportedEventPolicy::from_name(value)
+                .map_err(|e| format!("master.ignorable_event_policy: {}", e))?;
+            self.decoder.set_ignorable_event_policy(policy);
+        }
+        if let Some(value) = properties.get("master.undecoded_rows_event_policy") {
+            let policy = UnsupportedEventPolicy::from_name(value)
+                .map_err(|e| format!("master.undecoded_rows_event_policy: {}", e))?;
+            self.decoder.set_undecoded_rows_event_policy(policy);
+        }
+        Ok(())
+    }
+
+    // 最近完成的binlog文件的统计, 见FileStatsTracker
+    pub fn file_stats_history(&self) -> Arc<Mutex<VecDeque<BinlogFileStats>>> {
+        self.file_stats.history()
+    }
+
+    pub fn progress(&self) -> PipelineProgress {
+        self.progress.clone()
+    }
+
+    pub fn set_watchdog(&mut self, watchdog: Option<WatchdogConfig>) {
+        self.watchdog = watchdog;
+    }
+
+    pub fn watchdog(&self) -> Option<&WatchdogConfig> {
+        self.watchdog.as_ref()
+    }
+
+    // master.watchdog.*, 没有配置时保持不变
+    pub fn apply_watchdog(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(config) = WatchdogConfig::from_properties(properties)? {
+            self.set_watchdog(Some(config));
+        }
+        Ok(())
+    }
+
+    // watchdog最近发现的stall, 跨start保留
+    pub fn stall_reports(&self) -> StallReports {
+        self.stalls.clone()
+    }
+
+    // 把table map缓存和relay log写缓冲的字节数记入stats, 通常为metrics::memory::global()
+    pub fn set_memory_stats(&mut self, stats: &MemoryStats) {
+        self.decoder.set_table_map_cache_gauge(stats.gauge("table_map_cache"));
+        self.relay_memory = Some(stats.gauge("relay"));
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    // 返回运行状态的句柄, 其它线程可以通过store(false)停止parser
+    pub fn running_handle(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    pub fn start(&mut self) -> Result<(), CanalError> {
+        // 自检失败时不连接master, 也不重试
+        if self.self_test {
+            let report = self_test()?;
+            println!("self test passed, {} checks", report.checked());
+        }
+        self.load_stored_position()?;
+        self.running.store(true, Ordering::SeqCst);
+        self.fatal = false;
+        if let Ok(mut abort) = self.abort.lock() {
+            *abort = None;
+        }
+        let mut backoff = self.backoff.clone();
+        backoff.reset();
+        // watchdog判断为stall并停止parser时置为false, 此时返回Err以便supervisor重新启动
+        let healthy = Arc::new(AtomicBool::new(true));
+        let watchdog_running = Arc::new(AtomicBool::new(true));
+        let watchdog = match self.watchdog.clone() {
+            Some(config) => Some(self.spawn_watchdog(config, watchdog_running.clone(), healthy.clone())?),
+            None => None,
+        };
+        let mut tracker: Option<PositionTracker> = None;
+        let result = loop {
+            let kills = self.kills;
+            let result = self.run(&mut tracker);
+            self.progress.settle();
+            // 失败时还没有投递的entry会在重新dump时再次解析
+            self.parallel_decoder = None;
+            if let Some(tracker) = tracker.as_mut() {
+                // 只有结束了事务才重置重试次数, 连接之后反复出现的错误最终会放弃
+                if tracker.commits() > 0 {
+                    backoff.reset();
+                }
+                let restart = tracker.restart();
+                let _ = self.position_handle.persist(&LogPosition::new(restart.clone(), self.gtid_set()));
+                self.position = Some(restart);
+            }
+            let e = match result {
+                Ok(()) => break Ok(()),
+                Err(_) if !self.is_running() => break Ok(()),
+                Err(e) if self.fatal || self.aborted() || !e.is_recoverable() => break Err(e),
+                Err(e) => e,
+            };
+            self.update_status(|status| {
+                status.record_error(e.message());
+                status.set_state(ConnectionState::Reconnecting);
+            });
+            // master维护时kill dump连接是正常情况, 不计入重试次数
+            if self.kills > kills {
+                self.reconnecting_after_kill = true;
+                backoff.reset();
+            }
+            match backoff.next_delay() {
+                Some(delay) if self.kills > kills => {
+                    println!("dump connection is killed by master: {}, reconnect from {} after {:?}", e,
+                             self.resume_point(), delay);
+                    self.sleep(delay);
+                }
+                Some(delay) => {
+                    println!("dump failure ({}): {}, reconnect from {} after {:?}, attempt {}/{}", e.name(), e,
+                             self.resume_point(), delay, backoff.attempts(),
+                             backoff.max_retries().map_or("unlimited".to_string(), |max| max.to_string()));
+                    self.sleep(delay);
+                }
+                None => break Err(e),
+            }
+        };
+        watchdog_running.store(false, Ordering::SeqCst);
+        if let Some(watchdog) = watchdog {
+            let _ = watchdog.join();
+        }
+        let result = match result {
+            Ok(()) if !healthy.load(Ordering::SeqCst) => {
+                let stall = self.stalls.lock().ok().and_then(|stalls| stalls.last().map(|stall| stall.message()));
+                Err(CanalError::State(format!("stopped by watchdog: {}", stall.unwrap_or_default())))
+            }
+            result => result,
+        };
+        self.running.store(false, Ordering::SeqCst);
+        let result = match self.entry_sink.as_mut() {
+            Some(sink) => {
+                let flushed = sink.flush().and_then(|_| match self.position_saver.as_mut() {
+                    Some(saver) => saver.persist(),
+                    None => Ok(()),
+                });
+                result.and(flushed.map_err(CanalError::State))
+            }
+            None => result,
+        };
+        self.update_status(|status| match result.as_ref() {
+            Ok(()) => status.set_state(ConnectionState::Stopped),
+            Err(e) => {
+                status.record_error(e.message());
+                status.set_state(ConnectionState::Failed);
+            }
+        });
+        result
+    }
+
+    // 诊断信息包括parser状态和并行解码的统计, restart时停止parser
+    fn spawn_watchdog(&self, config: WatchdogConfig, running: Arc<AtomicBool>, healthy: Arc<AtomicBool>)
+                      -> Result<JoinHandle<()>, String> {
+        let info = &self.authentication_info;
+        let mut watchdog = Watchdog::new(&format!("{}:{}", info.address(), info.port()), config);
+        watchdog.set_stalls(self.stalls.clone());
+        watchdog.watch_pipeline(&self.progress);
+        let status = self.status.clone();
+        watchdog.add_diagnostics("parser", move || {
+            status.lock().map(|status| format!("{:?}", *status)).unwrap_or_default()
+        });
+        if self.parallel_decode_threads > 0 {
+            let stats = self.parallel_decode_stats.clone();
+            watchdog.add_diagnostics("parallel_decode", move || {
+                stats.lock().map(|stats| format!("{:?}", *stats)).unwrap_or_default()
+            });
+        }
+        watchdog.add_restart_handle(self.running.clone());
+        watchdog.add_restart_handle(healthy);
+        watchdog.spawn(running)
+    }
+
+    fn load_stored_position(&mut self) -> Result<(), CanalError> {
+        let stored = match self.position_saver.as_mut() {
+            Some(saver) => saver.load()?,
+            None => None,
+        };
+        if let Some(stored) = stored {
+            let position = stored.position();
+            println!("stored position {}:{}, gtid set {:?}", position.journal_name(), position.position(),
+                     stored.gtid_set().map(|gtid_set| gtid_set.to_string()));
+            if let (Some(gtid_set), Ok(mut current)) = (stored.gtid_set(), self.gtid_set.lock()) {
+                *current = Some(gtid_set.clone());
+            }
+            self.stored_position = Some(position.clone());
+        }
+        Ok(())
+    }
+
+    // 重新dump的起点, 按gtid set dump时master按gtid set定位
+    fn resume_point(&self) -> String {
+        match (self.gtid_set(), self.position.as_ref()) {
+            (Some(gtid_set), _) => format!("gtid set {}", gtid_set),
+            (None, Some(position)) => format!("{}:{}", position.journal_name(), position.position()),
+            (None, None) => "the start position".to_string(),
+        }
+    }
+
+    fn update_status<F: FnOnce(&mut ParserStatus)>(&self, f: F) {
+        if let Ok(mut status) = self.status.lock() {
+            f(&mut status);
+        }
+    }
+
+    /**
+     * <pre>
+     *  entry_sink背压时不从socket读取下一个event, 直到sink恢复或者parser被stop.
+     *  未读取的数据留在socket缓冲中, master写满缓冲之后阻塞, 超过master的net_write_timeout时master会断开连接,
+     *  此时按正常的重连流程从未结束事务的开头重新dump
+     * </pre>
+     */
+    fn wait_for_sink(&mut self, fetcher: &mut DirectLogFetcher) {
+        let backpressured = |sink: &Option<Box<dyn EventSink>>| sink.as_ref().is_some_and(|sink| sink.is_backpressured());
+        if !backpressured(&self.entry_sink) {
+            return;
+        }
+        let begin = Instant::now();
+        self.update_status(|status| status.set_backpressured(true));
+        while self.is_running() && backpressured(&self.entry_sink) {
+            thread::sleep(BACKPRESSURE_CHECK_INTERVAL);
+        }
+        let paused = begin.elapsed();
+        self.update_status(|status| status.record_backpressure(paused));
+        fetcher.resume();
+    }
+
+    fn sleep(&self, delay: Duration) {
+        let deadline = Instant::now() + delay;
+        while self.is_running() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep(STOP_CHECK_INTERVAL.min(deadline - now));
+        }
+    }
+
+    fn run(&mut self, tracker: &mut Option<PositionTracker>) -> Result<(), CanalError> {
+        let info = &self.authentication_info;
+        let mut connector = MysqlConnector::new(info.address(), info.port(), info.username(),
+                                                info.password(), info.default_database_name());
+        connector.set_connect_timeout(self.connect_timeout);
+        connector.set_stats(self.channel_stats.clone());
+        connector.set_ssl_mode(info.ssl_mode());
+        connector.set_ssl_ca(info.ssl_ca());
+        let master = format!("{}:{}", info.address(), info.port());
+        self.update_status(|status| status.connecting(&master));
+        connector.connect()?;
+        let result = self.dump(&mut connector, tracker);
+        connector.disconnect();
+        result
+    }
+
+    fn dump(&mut self, connector: &mut MysqlConnector, tracker: &mut Option<PositionTracker>)
+            -> Result<(), CanalError> {
+        self.update_settings(connector);
+        self.capture_server_variables(connector)?;
+        self.checksum_alg = self.load_binlog_checksum(connector)?;
+        let source = self.server_variables.lock().ok()
+            .map(|variables| SourceIdentity::from_variables(connector.address(), connector.port(), variables.as_ref()))
+            .unwrap_or_default();
+        let gtid_set = self.gtid_set();
+        if gtid_set.is_some() && self.mode != ParseMode::Decode {
+            self.fatal = true;
+            return Err(CanalError::state("dump by gtid set is only supported in decode mode"));
+        }
+        let position = match self.position.clone() {
+            Some(position) => {
+                let relocated = self.check_source(connector, &source, position.clone())?;
+                // 重新定位之后从新的位点开始跟踪
+                if relocated != position {
+                    *tracker = None;
+                }
+                relocated
+            }
+            // master按gtid set定位, 文件名由之后的fake rotate告知
+            None if gtid_set.is_some() => EntryPosition::new("", BINLOG_MAGIC.len() as u64),
+            None => self.resolve_start_position(connector)?,
+        };
+        self.source = Some(source);
+        let position = match self.mode {
+            ParseMode::Decode => position.transaction_begin(),
+            ParseMode::Raw(_) => position,
+        };
+        self.register_slave(connector)?;
+        let dump_gtid = gtid_set.is_some();
+        match gtid_set {
+            Some(gtid_set) => {
+                println!("dump from gtid set {}", gtid_set);
+                connector.send(&BinlogDumpGtidCommand::builder(self.slave_id).gtid_set(gtid_set).build())?;
+            }
+            None => {
+                let dump = BinlogDumpCommand::builder(self.slave_id)
+                    .binlog_file(position.journal_name())
+                    .binlog_position(position.position() as u32)
+                    .flags(BINLOG_SEND_ANNOTATE_ROWS_EVENT)
+                    .build();
+                connector.send(&dump)?;
+            }
+        }
+        self.pending_gtid = None;
+        let dump_gtid_set = self.gtid_set();
+        if let Some(saver) = self.position_saver.as_mut() {
+            saver.start_dump(dump_gtid_set);
+        }
+        self.update_status(|status| status.set_state(ConnectionState::Dumping));
+        let tracker = tracker.get_or_insert_with(|| match self.mode {
+            ParseMode::Decode => PositionTracker::new(position.clone()),
+            ParseMode::Raw(_) => PositionTracker::raw(position.clone()),
+        });
+        if let Some(finished) = self.file_stats.start(position.journal_name()) {
+            self.binlog_file_finished(&finished)?;
+        }
+        self.position = Some(position);
+
+        let mut relay = match &self.mode {
+            ParseMode::Raw(directory) => {
+                let mut relay = RelayLogWriter::new(directory)?;
+                if let Some(key_provider) = self.relay_key_provider.as_ref() {
+                    relay.set_key_provider(key_provider.clone());
+                }
+                if let Some(gauge) = self.relay_memory.as_ref() {
+                    relay.set_memory_gauge(gauge.clone());
+                }
+                Some(relay)
+            }
+            ParseMode::Decode => None,
+        };
+        let mut fetcher = DirectLogFetcher::new();
+        fetcher.set_read_timeout(self.read_timeout);
+        fetcher.set_heartbeat_timeout(self.heartbeat_timeout());
+        self.last_timeout = None;
+        self.clock_skew.reset();
+        let mut context = LogContext::new();
+        context.set_checksum_alg(self.checksum_alg);
+        context.set_tolerant(self.tolerant);
+        context.set_strict(self.strict);
+        // 追赶模式下查询show binary logs的连接, dump结束时关闭
+        let mut catch_up_connector: Option<MysqlConnector> = None;
+        self.parallel_decoder = None;
+        if self.parallel_decode_threads > 0 && self.entry_sink.is_some() && self.mode == ParseMode::Decode {
+            let mut decoder = ParallelDecoder::new(self.parallel_decode_threads, self.max_decode_in_flight)?;
+            decoder.set_stats(self.parallel_decode_stats.clone());
+            decoder.set_metrics(self.metrics.clone());
+            self.parallel_decoder = Some(decoder);
+        }
+        // 只有master在空闲时也会发送heartbeat, 等待event才说明有待处理的数据
+        let watch_fetch = self.heartbeat_period.is_some();
+        while self.is_running() {
+            self.wait_for_sink(&mut fetcher);
+            if !self.is_running() {
+                break;
+            }
+            if watch_fetch {
+                self.progress.fetch().receive();
+            }
+            let fetched = fetcher.fetch(connector.channel()?);
+            if watch_fetch {
+                self.progress.fetch().complete();
+            }
+            let event = match fetched {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                Err(e) => {
+                    self.last_timeout = fetcher.last_timeout();
+                    if self.last_timeout == Some(FetchTimeout::Heartbeat) {
+                        self.update_status(|status| status.record_heartbeat_timeout());
+                    }
+                    if fetcher.is_killed() {
+                        self.kills += 1;
+                        return Err(e);
+                    }
+                    // 请求的位点一开始就无法读取, 可能已经被purge; 按gtid set dump时没有可以检查的文件
+                    if dump_gtid || tracker.sequence() > 0 || !is_fatal_reading_binlog(fetcher.last_error()) {
+                        return Err(e);
+                    }
+                    let restart = self.recover_purged(connector, tracker.position(), e)?;
+                    *tracker = match self.mode {
+                        ParseMode::Decode => PositionTracker::new(restart.clone()),
+                        ParseMode::Raw(_) => PositionTracker::raw(restart.clone()),
+                    };
+                    // 已经重新定位, 按可以重试的错误返回
+                    return Err(CanalError::Protocol(None, format!("binlog is purged, restart from {}:{}",
+                                                                  restart.journal_name(), restart.position())));
+                }
+            };
+            let event_len = event.len();
+            rate::mark(&self.metrics, RateKind::BytesFetched, event_len as u64);
+            self.progress.decode().receive();
+            let log_event = match relay.as_mut() {
+                Some(relay) => {
+                    let header = LogHeader::from_bytes(event, context.checksum_alg())?;
+                    relay.write(&header, event)?;
+                    raw_event(header, event, &mut context)?
+                }
+                None if self.panic_containment == PanicContainment::Off => {
+                    self.decode_event(event, &mut context, tracker)?
+                }
+                None => match contain(|| self.decode_event(event, &mut context, tracker)) {
+                    Ok(result) => result?,
+                    Err(panic) => {
+                        self.file_stats.record_decode_error();
+                        let message = panic_report(&panic, self.sensitive_columns.dump(event, &context));
+                        if self.panic_containment == PanicContainment::Error {
+                            return Err(CanalError::Decode(message));
+                        }
+                        if self.strict {
+                            self.fatal = true;
+                            return Err(CanalError::Decode(format!("strict mode: {}", message)));
+                        }
+                        self.dead_letter(event, &context, &message)?;
+                        self.progress.decode().complete();
+                        continue;
+                    }
+                },
+            };
+            self.progress.decode().complete();
+            if self.gtid_gap_policy != GtidGapPolicy::Off
+                && log_event.header().event_type().is_some_and(|kind| kind.is_gtid()) {
+                self.check_gtid_gap(log_event.header(), event, &context)?;
+            }
+            tracker.update(&log_event);
+            if dump_gtid {
+                self.track_gtid(log_event.header(), event, tracker.in_transaction());
+            }
+            if let Some(finished) = self.file_stats.observe(&log_event, event_len, tracker.in_transaction()) {
+                self.binlog_file_finished(&finished)?;
+            }
+            let mut position = tracker.position().clone();
+            let now = Utc::now().timestamp_millis();
+            let event_time = match log_event.header().event_type() {
+                // heartbeat没有执行时间, 说明已经追上master
+                Some(kind) if kind.is_heartbeat() => {
+                    self.clock_skew.on_heartbeat();
+                    self.update_status(|status| status.record_heartbeat(now));
+                    now
+                }
+                _ => {
+                    let commit_time = context.commit_timestamps().map(|(_, immediate)| immediate / 1000)
+                        .filter(|commit_time| *commit_time > 0);
+                    self.clock_skew.observe(commit_time.unwrap_or(log_event.header().when() as i64 * 1000), now);
+                    self.clock_skew.correct(log_event.header().when() as i64 * 1000)
+                }
+            };
+            position.set_timestamp(event_time);
+            let clock_offset = self.clock_skew.offset();
+            self.update_status(|status| {
+                status.record_event(position, event_time);
+                status.set_clock_offset(clock_offset);
+            });
+            self.apply_pending_reload();
+            if self.catch_up.is_some() {
+                self.update_catch_up(connector, &mut catch_up_connector, tracker.position(), event_len);
+            }
+        }
+        if let Some(mut catch_up_connector) = catch_up_connector {
+            catch_up_connector.quit();
+        }
+        if let Some(relay) = relay.as_mut() {
+            relay.close()?;
+        }
+        self.drain_decoded(false)
+    }
+
+    // 事务结束之后才把它的gtid加入已执行的集合, 重连时未结束的事务会被master完整地重新发送.
+    // 带tag的gtid不能用GtidSet表示, 不加入集合
+    fn track_gtid(&mut self, header: &LogHeader, event: &[u8], in_transaction: bool) {
+        if header.event_type().is_some_and(|kind| kind.is_gtid()) {
+            self.pending_gtid = event_gtid(header, event);
+        }
+        if in_transaction {
+            return;
+        }
+        if let Some(gtid) = self.pending_gtid.take() {
+            if let Ok(mut gtid_set) = self.gtid_set.lock() {
+                if let Some(gtid_set) = gtid_set.as_mut() {
+                    let _ = gtid_set.add(&gtid);
+                }
+            }
+        }
+    }
+
+    // 一个binlog文件解析完成, 打印统计并向incident sink投递Info entry, 位点为文件中最后一个event的结束位置
+    fn binlog_file_finished(&mut self, stats: &BinlogFileStats) -> Result<(), CanalError> {
+        let message = stats.message();
+        println!("{}", message);
+        if let Some(sink) = self.incident_sink.as_mut() {
+            let mut header = Header::new(stats.journal_name(), stats.end_position());
+            header.set_execute_time(Utc::now().timestamp_millis());
+            sink.on_event(&Entry::info(header, &message))?;
+            sink.flush()?;
+        }
+        Ok(())
+    }
+
+    // 查询binlog大小失败时只打印, 不影响dump, 此时剩余字节数未知并退出追赶模式
+    fn update_catch_up(&mut self, connector: &MysqlConnector, catch_up_connector: &mut Option<MysqlConnector>,
+                       position: &EntryPosition, event_len: usize) {
+        let catch_up = match self.catch_up.as_mut() {
+            Some(catch_up) => catch_up,
+            None => return,
+        };
+        if let Some(delay) = catch_up.on_event(event_len) {
+            thread::sleep(delay);
+        }
+        let now = Instant::now();
+        if !catch_up.refresh_due(now) {
+            return;
+        }
+        let logs = match binary_log_sizes(connector, catch_up_connector) {
+            Ok(logs) => logs,
+            Err(e) => {
+                println!("query binlog sizes for catch up failure: {}", e);
+                *catch_up_connector = None;
+                vec![]
+            }
+        };
+        if catch_up.refresh(&logs, position, now) {
+            let status = catch_up.status();
+            println!("{} catch up mode at {}:{}, remaining {:?} bytes in {} files, eta {:?}",
+                     if status.is_active() { "enter" } else { "leave" }, position.journal_name(), position.position(),
+                     status.remaining_bytes(), status.files_behind(), status.eta());
+        }
+        let status = catch_up.status().clone();
+        self.update_status(|parser_status| parser_status.set_catch_up(status));
+    }
+
+    fn decode_event(&mut self, event: &[u8], context: &mut LogContext, tracker: &PositionTracker)
+                    -> Result<LogEvent, CanalError> {
+        let result = self.convert_event(event, context, tracker);
+        if result.is_err() {
+            self.file_stats.record_decode_error();
+        }
+        if result.is_err() && context.violation().is_some() {
+            self.fatal = true;
+        }
+        result
+    }
+
+    fn convert_event(&mut self, event: &[u8], context: &mut LogContext, tracker: &PositionTracker)
+                     -> Result<LogEvent, CanalError> {
+        let mut event = self.decoder.decode(event, context)?;
+        rate::mark(&self.metrics, RateKind::EventsDecoded, 1);
+        if let Some(history) = self.schema_history.as_ref() {
+            track_schema(history, &mut event, context, self.convert.default_sql_mode());
+        }
+        // 丢弃的中间表不出现在describe中
+        if let LogEvent::TableMap(table_map) = &event {
+            if !self.convert.transient_tables().skip_table(table_map.db_name(), table_map.table_name(), false) {
+                self.schemas.update(table_map);
+            }
+        }
+        if self.parallel_decoder.is_some() && is_decode_barrier(&event) {
+            self.drain_decoded(true)?;
+        }
+        // table map已经记录, 之后的rows event仍然可以解码
+        if self.partial_transaction_policy == PartialTransactionPolicy::Drop && tracker.is_partial(&event) {
+            self.partial_events_dropped += 1;
+            return Ok(event);
+        }
+        let in_transaction = tracker.in_transaction();
+        // 追赶模式下非关键表的rows event不解码行数据
+        if let (LogEvent::Rows(rows), Some(catch_up)) = (&event, self.catch_up.as_mut()) {
+            if context.get_table(rows.table_id()).is_some_and(|table| catch_up.skip(table.db_name(), table.table_name())) {
+                return Ok(event);
+            }
+        }
+        if let (LogEvent::Rows(rows), Some(tracer)) = (&event, self.decode_tracer.as_ref()) {
+            tracer.trace(&self.convert, rows, context)?;
+        }
+        // table map缺失的rows event仍然在dump线程中按照missing_table_meta_policy处理
+        if let (LogEvent::Rows(rows), Some(decoder)) = (&event, self.parallel_decoder.as_mut()) {
+            if let Some(mut task) = self.convert.rows_task(rows, context) {
+                self.clock_skew.correct_header(task.header_mut());
+                let (sink, saver) = (&mut self.entry_sink, &mut self.position_saver);
+                decoder.submit(task, &mut |entry| deliver(sink, saver, &entry))?;
+                return Ok(event);
+            }
+        }
+        // 没有entry_sink时同样转换, 保证table map缺失等问题按照配置处理
+        let mut entry = self.convert.parse(&event, context, in_transaction).map_err(CanalError::Decode)?;
+        if let Some(entry) = entry.as_mut() {
+            self.clock_skew.correct_header(entry.header_mut());
+        }
+        if let LogEvent::Rows(rows) = &event {
+            if context.get_table(rows.table_id()).is_none() {
+                let problem = format!("table map of table_id {} is missing, rows are skipped or emitted without columns",
+                                      rows.table_id());
+                context.fidelity_loss(rows.header(), &problem)?;
+            }
+        }
+        if let Some(rows) = entry.as_ref().and_then(|entry| entry.row_change()).filter(|change| !change.is_ddl()) {
+            rate::mark(&self.metrics, RateKind::RowsEmitted, rows.row_datas().len() as u64);
+        }
+        if let Some(incident) = entry.as_ref().filter(|entry| entry.entry_type() == EntryType::Incident) {
+            self.binlog_incident(incident)?;
+        }
+        match (entry, self.parallel_decoder.as_mut()) {
+            (Some(entry), Some(decoder)) => {
+                let (sink, saver) = (&mut self.entry_sink, &mut self.position_saver);
+                decoder.push(entry, &mut |entry| deliver(sink, saver, &entry))?;
+            }
+            (Some(entry), None) => deliver(&mut self.entry_sink, &mut self.position_saver, &entry)?,
+            (None, _) => {}
+        }
+        Ok(event)
+    }
+
+    // barrier为true时记录等待的次数, 用于DDL/FORMAT_DESCRIPTION之前
+    fn drain_decoded(&mut self, barrier: bool) -> Result<(), CanalError> {
+        let decoder = match self.parallel_decoder.as_mut() {
+            Some(decoder) => decoder,
+            None => return Ok(()),
+        };
+        let (sink, saver) = (&mut self.entry_sink, &mut self.position_saver);
+        let mut deliver = |entry: Entry| deliver(sink, saver, &entry);
+        let result = if barrier { decoder.barrier(&mut deliver) } else { decoder.drain(&mut deliver) };
+        Ok(result?)
+    }
+
+    // master的INCIDENT event投递给incident sink, Halt策略下停止dump并且不再重试, 重新dump的位点在incident之前
+    fn binlog_incident(&mut self, entry: &Entry) -> Result<(), CanalError> {
+        let header = entry.header();
+        let message = format!("{} at {}:{}, policy {}", entry.message().unwrap_or(""), header.log_file_name(),
+                              header.log_file_offset(), self.incident_policy.name());
+        println!("{}", message);
+        if let Some(sink) = self.incident_sink.as_mut() {
+            sink.on_event(entry)?;
+            sink.flush()?;
+        }
+        if self.incident_policy == IncidentPolicy::Halt {
+            self.fatal = true;
+            return Err(CanalError::State(message));
+        }
+        Ok(())
+    }
+
+    // DeadLetter策略下跳过解码时panic的event, 没有可用的sink时返回Err
+    fn dead_letter(&mut self, event: &[u8], context: &LogContext, message: &str) -> Result<(), CanalError> {
+        self.contained_panics += 1;
+        println!("{}, skipped", message);
+        let log_header = LogHeader::from_bytes(event, context.checksum_alg()).ok();
+        let offset = log_header.as_ref()
+            .map_or(0, |log_header| (log_header.log_pos() as u64).saturating_sub(log_header.event_len() as u64));
+        let mut header = Header::new(context.log_position().journal_name(), offset);
+        if let Some(log_header) = log_header {
+            header.set_server_id(log_header.server_id());
+            header.set_event_length(log_header.event_len());
+        }
+        header.set_execute_time(Utc::now().timestamp_millis());
+        let sink = match self.dead_letter_sink.as_mut().or(self.incident_sink.as_mut()) {
+            Some(sink) => sink,
+            None => return Err(CanalError::State(format!("{}, no dead letter sink is configured", message))),
+        };
+        sink.on_event(&Entry::incident(header, message))?;
+        Ok(sink.flush()?)
+    }
+
+    // GTID跳号时投递Incident entry, Halt策略下停止dump并且不再重试
+    fn check_gtid_gap(&mut self, log_header: &LogHeader, event: &[u8], context: &LogContext) -> Result<(), CanalError> {
+        let gap = match event_gtid(log_header, event).and_then(|gtid| self.gtid_gaps.observe(&gtid)) {
+            Some(gap) => gap,
+            None => return Ok(()),
+        };
+        rate::mark(&self.metrics, RateKind::GtidGaps, gap.missing());
+        let offset = (log_header.log_pos() as u64).saturating_sub(log_header.event_len() as u64);
+        let message = format!("{} at {}:{}, policy {}", gap.message(), context.log_position().journal_name(), offset,
+                              self.gtid_gap_policy.name());
+        println!("{}", message);
+        if let Some(sink) = self.incident_sink.as_mut() {
+            let mut header = Header::new(context.log_position().journal_name(), offset);
+            header.set_server_id(log_header.server_id());
+            header.set_execute_time(log_header.when() as i64 * 1000);
+            sink.on_event(&Entry::incident(header, &message))?;
+            sink.flush()?;
+        }
+        if self.gtid_gap_policy == GtidGapPolicy::Halt {
+            self.fatal = true;
+            return Err(CanalError::State(message));
+        }
+        Ok(())
+    }
+
+    // 与canal保持一致, 设置失败时忽略, 不影响后续dump
+    fn update_settings(&self, connector: &mut MysqlConnector) {
+        let mut settings: Vec<String> = [
+            "set wait_timeout=9999999",
+            "set net_write_timeout=7200",
+            "set net_read_timeout=7200",
+            "set names 'binary'",
+            "set @master_binlog_checksum= @@global.binlog_checksum",
+            "set @slave_uuid=uuid()",
+            "SET @mariadb_slave_capability='4'",
+        ].iter().map(|sql| sql.to_string()).collect();
+        // 单位为纳秒
+        if let Some(period) = self.heartbeat_period {
+            settings.push(format!("set @master_heartbeat_period={}", period.as_nanos()));
+        }
+        for sql in settings.iter() {
+            if let Err(e) = connector.update(sql) {
+                println!("update settings failure, sql: {}, error: {}", sql, e);
+            }
+        }
+    }
+
+    /**
+     * <pre>
+     *  记录master的变量快照并投递一个Info entry, 与上一次连接的快照不同时(例如主从切换)附带变化的变量.
+     *  dump连接被kill之后重新连接且变量没有变化时只打印日志, 不投递Info entry.
+     *  查询失败时只打印日志, 不影响dump
+     * </pre>
+     */
+    fn capture_server_variables(&mut self, connector: &mut MysqlConnector) -> Result<(), CanalError> {
+        let variables = match ServerVariables::load(connector) {
+            Ok(variables) => variables,
+            Err(e) => {
+                println!("load server variables failure: {}", e);
+                return Ok(());
+            }
+        };
+        if let Some(sql_mode) = variables.sql_mode() {
+            self.convert.set_default_sql_mode(sql_mode);
+        }
+        let previous = match self.server_variables.lock() {
+            Ok(mut current) => current.replace(variables.clone()),
+            Err(_) => None,
+        };
+        let mut message = format!("connected to {}:{}, {}", connector.address(), connector.port(), variables);
+        let changes = previous.map(|previous| variables.changes(&previous)).unwrap_or_default();
+        if !changes.is_empty() {
+            message.push_str(&format!(", changed since last connection: {}", changes.join("; ")));
+        }
+        println!("{}", message);
+        if std::mem::take(&mut self.reconnecting_after_kill) && changes.is_empty() {
+            return Ok(());
+        }
+        if let Some(sink) = self.incident_sink.as_mut() {
+            let mut header = match self.position.as_ref() {
+                Some(position) => Header::new(position.journal_name(), position.position()),
+                None => Header::default(),
+            };
+            header.set_server_id(variables.server_id().unwrap_or_default());
+            header.set_execute_time(variables.captured_at());
+            sink.on_event(&Entry::info(header, &message))?;
+            sink.flush()?;
+        }
+        Ok(())
+    }
+
+    /**
+     * <pre>
+     *  重连之后master的server_id/server_uuid变化或者GTID回退时, 按照source_change_policy返回重新dump的位点,
+     *  并向incident sink和entry sink投递SourceChanged entry. Halt时停止parser且不再重试,
+     *  此时不更新记录的master身份, 再次启动时仍然会检查
+     * </pre>
+     */
+    fn check_source(&mut self, connector: &mut MysqlConnector, current: &SourceIdentity, position: EntryPosition)
+                    -> Result<EntryPosition, CanalError> {
+        let previous = match self.source.as_ref() {
+            Some(previous) => previous.clone(),
+            None => return Ok(position),
+        };
+        let gtid_executed = match self.gtid_gaps.sources().next() {
+            Some(_) => load_gtid_executed(connector),
+            None => None,
+        };
+        let reasons = source_changes(&previous, current, gtid_executed.as_ref(), &self.gtid_gaps);
+        if reasons.is_empty() {
+            return Ok(position);
+        }
+        let relocated = match self.source_change_policy {
+            SourceChangePolicy::Continue => Some(position.clone()),
+            SourceChangePolicy::Latest => Some(self.find_end_position(connector)?),
+            SourceChangePolicy::Halt => None,
+        };
+        let change = SourceChange::new(previous, current.clone(), reasons, self.source_change_policy, position.clone(),
+                                       relocated.clone());
+        let message = change.message();
+        println!("{}", message);
+        self.source_changes += 1;
+        let mut header = Header::new(position.journal_name(), position.position());
+        header.set_server_id(current.server_id().unwrap_or_default());
+        header.set_execute_time(Utc::now().timestamp_millis());
+        let entry = Entry::source_changed(header, change);
+        if let Some(sink) = self.incident_sink.as_mut() {
+            sink.on_event(&entry)?;
+            sink.flush()?;
+        }
+        // 与数据在同一个流中, 下游可以知道之后的entry来自新的master
+        deliver(&mut self.entry_sink, &mut self.position_saver, &entry)?;
+        match relocated {
+            Some(relocated) => Ok(relocated),
+            None => {
+                self.fatal = true;
+                Err(CanalError::State(message))
+            }
+        }
+    }
+
+    fn load_binlog_checksum(&self, connector: &mut MysqlConnector) -> Result<u8, CanalError> {
+        let result = connector.query("select @@global.binlog_checksum");
+        match result {
+            Ok(result) => Ok(result.field_values().first()
+                .map(|name| checksum::from_name(name))
+                .unwrap_or(checksum::BINLOG_CHECKSUM_ALG_OFF)),
+            // 5.6之前的版本没有binlog_checksum变量
+            Err(_) => Ok(checksum::BINLOG_CHECKSUM_ALG_OFF),
+        }
+    }
+
+    // 没有可用的启动位点是配置错误, 不再重试
+    fn resolve_start_position(&mut self, connector: &mut MysqlConnector) -> Result<EntryPosition, CanalError> {
+        let start = self.start_policy.resolve(self.stored_position.as_ref(), self.explicit_position.as_ref(),
+                                              |mode| match mode {
+                                                  StartMode::Earliest => self.find_earliest_position(connector),
+                                                  _ => self.find_end_position(connector),
+                                              }.map_err(String::from));
+        let start = match start {
+            Ok(start) => start,
+            Err(e) => {
+                self.fatal = true;
+                return Err(CanalError::State(e));
+            }
+        };
+        println!("start from {}:{}, start mode {} ({}): {}", start.position().journal_name(), start.position().position(),
+                 start.mode().name(), self.start_policy.name(), start.reason());
+        let position = start.position().clone();
+        self.start = Some(start);
+        Ok(position)
+    }
+
+    fn find_earliest_position(&self, connector: &mut MysqlConnector) -> Result<EntryPosition, CanalError> {
+        let result = connector.query("show binary logs")?;
+        let earliest = result.rows().filter_map(|row| row.first().cloned()).next()
+            .ok_or_else(|| "command : 'show binary logs' returns no binlog, is log_bin enabled?".to_string())?;
+        Ok(EntryPosition::new(&earliest, BINLOG_MAGIC.len() as u64))
+    }
+
+    fn find_end_position(&self, connector: &mut MysqlConnector) -> Result<EntryPosition, CanalError> {
+        let result = connector.query("show master status")?;
+        let values = result.field_values();
+        if values.len() < 2 {
+            return Err(CanalError::state("command : 'show master status' has an error! pls check. \
+                        you need (at least one of) the SUPER,REPLICATION CLIENT privilege(s) for this operation"));
+        }
+        let position = values[1].parse::<u64>().map_err(|e| CanalError::Protocol(None, e.to_string()))?;
+        Ok(EntryPosition::new(&values[0], position))
+    }
+
+    /**
+     * <pre>
+     *  1236也可能是位点不在event边界等原因, 只有请求的文件不在show binary logs中时才认为已经被purge,
+     *  之后按purged_binlog_strategy返回新的起始位点. dump失败后master可能已经关闭连接, 使用新的连接查询
+     * </pre>
+     */
+    fn recover_purged(&mut self, connector: &MysqlConnector, position: &EntryPosition, error: CanalError)
+                      -> Result<EntryPosition, CanalError> {
+        let mut connector = connector.fork();
+        connector.connect()?;
+        let result = self.purged_restart_position(&mut connector, position, error);
+        connector.disconnect();
+        result
+    }
+
+    fn purged_restart_position(&mut self, connector: &mut MysqlConnector, position: &EntryPosition, error: CanalError)
+                               -> Result<EntryPosition, CanalError> {
+        let result = connector.query("show binary logs")?;
+        let logs: Vec<String> = result.rows().filter_map(|row| row.first().cloned()).collect();
+        let earliest = match logs.first() {
+            Some(earliest) if !logs.iter().any(|name| name == position.journal_name()) => earliest.clone(),
+            _ => return Err(error),
+        };
+        let restart = match self.purged_binlog_strategy {
+            PurgedBinlogStrategy::Fail => {
+                self.fatal = true;
+                let message = format!("binlog {} is purged on the master, the earliest binlog is {}",
+                                      position.journal_name(), earliest);
+                println!("{}, strategy {}: stop", message, self.purged_binlog_strategy.name());
+                self.purged_incident(position, &message)?;
+                return Err(CanalError::Protocol(error.server_error().cloned(), format!("{}, {}", error, message)));
+            }
+            PurgedBinlogStrategy::Earliest => EntryPosition::new(&earliest, BINLOG_MAGIC.len() as u64),
+            PurgedBinlogStrategy::Latest => self.find_end_position(connector)?,
+            PurgedBinlogStrategy::Snapshot => {
+                let restart = self.find_end_position(connector)?;
+                let snapshotter = match self.snapshotter.as_mut() {
+                    Some(snapshotter) => snapshotter,
+                    None => {
+                        self.fatal = true;
+                        return Err(CanalError::State(format!("{}, binlog {} is purged and no snapshotter is \
+                                                              configured", error, position.journal_name())));
+                    }
+                };
+                let restart = match snapshotter.snapshot_at(&restart) {
+                    Ok(restart) => restart,
+                    Err(e) => {
+                        self.fatal = true;
+                        return Err(CanalError::State(format!("snapshot at {}:{} failure: {}", restart.journal_name(),
+                                                             restart.position(), e)));
+                    }
+                };
+                if let Some(gtid_executed) = snapshotter.gtid_executed() {
+                    self.gtid_gaps.seed(&gtid_executed);
+                }
+                restart
+            }
+        };
+        let message = format!("binlog {}:{} is purged on the master, strategy {}: skip to {}:{}",
+                              position.journal_name(), position.position(), self.purged_binlog_strategy.name(),
+                              restart.journal_name(), restart.position());
+        println!("{}", message);
+        self.purged_incident(&restart, &message)?;
+        Ok(restart)
+    }
+
+    // 向incident sink投递Incident entry, 位点为跳转之后的位点, Fail时为被purge的位点
+    fn purged_incident(&mut self, position: &EntryPosition, message: &str) -> Result<(), CanalError> {
+        if let Some(sink) = self.incident_sink.as_mut() {
+            let mut header = Header::new(position.journal_name(), position.position());
+            header.set_execute_time(Utc::now().timestamp_millis());
+            sink.on_event(&Entry::incident(header, message))?;
+            sink.flush()?;
+        }
+        Ok(())
+    }
+
+    fn register_slave(&self, connector: &mut MysqlConnector) -> Result<(), CanalError> {
+        let host = connector.channel()?
+            .get_local_address()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+        let info = &self.authentication_info;
+        let register = RegisterSlaveCommand::builder(self.slave_id)
+            .report_host(&host)
+            .report_port(info.port())
+            .report_user(info.username())
+            .report_password(info.password())
+            .build();
+        connector.send(&register)?;
+        let (_, body) = connector.read_body()?;
+        if body.first() == Some(&ERROR_HEADER) {
+            return Err(connector.server_error(&body).context("register slave failure"));
+        }
+        Ok(())
+    }
+}
+
+// show binary logs的(Log_name, File_size), 连接在多次查询之间复用
+fn binary_log_sizes(connector: &MysqlConnector, catch_up_connector: &mut Option<MysqlConnector>)
+                    -> Result<Vec<(String, u64)>, CanalError> {
+    let query_connector = match catch_up_connector {
+        Some(query_connector) => query_connector,
+        None => {
+            let mut query_connector = connector.fork();
+            query_connector.connect()?;
+            catch_up_connector.insert(query_connector)
+        }
+    };
+    let result = query_connector.query("show binary logs")?;
+    Ok(result.rows().filter_map(|row| Some((row.first()?.clone(), row.get(1)?.parse().ok()?))).collect())
+}
+
+// 没有开启GTID或者无法查询时返回None
+fn load_gtid_executed(connector: &mut MysqlConnector) -> Option<GtidSet> {
+    let result = connector.query("select @@global.gtid_executed").ok()?;
+    let value = result.field_values().first()?.replace(['\n', ' '], "");
+    GtidSet::parse(&value).ok()
+}
+
+// sink确认之后按FlushPolicy保存位点, 保存之前先flush sink
+fn deliver(sink: &mut Option<Box<dyn EventSink>>, saver: &mut Option<PositionSaver>, entry: &Entry)
+           -> Result<(), String> {
+    let sink = match sink.as_mut() {
+        Some(sink) => sink,
+        None => return Ok(()),
+    };
+    sink.on_event(entry)?;
+    let saver = match saver.as_mut() {
+        Some(saver) => saver,
+        None => return Ok(()),
+    };
+    if !saver.ack(entry) {
+        return Ok(());
+    }
+    sink.flush()?;
+    saver.persist()
+}
+
+// 事务边界以外的query(DDL等)和FORMAT_DESCRIPTION, 之后的rows event可能对应新的表结构
+fn is_decode_barrier(event: &LogEvent) -> bool {
+    match event {
+        LogEvent::Query(query) => {
+            let sql = query.query().trim();
+            !sql.eq_ignore_ascii_case("BEGIN") && !sql.eq_ignore_ascii_case("COMMIT")
+        }
+        LogEvent::FormatDescription(_) => true,
+        _ => false,
+    }
+}
+
+// DDL在它的位点生成表结构的新版本, table map按所在位点的版本补全之后替换context中的table map
+fn track_schema(history: &Arc<Mutex<SchemaHistory>>, event: &mut LogEvent, context: &mut LogContext,
+                default_sql_mode: SqlMode) {
+    let mut history = match history.lock() {
+        Ok(history) => history,
+        Err(_) => return,
+    };
+    match event {
+        LogEvent::Query(query) => {
+            let sql = query.query().trim();
+            if sql.eq_ignore_ascii_case("BEGIN") || sql.eq_ignore_ascii_case("COMMIT") {
+                return;
+            }
+            let position = EntryPosition::new(context.log_position().journal_name(), query.header().log_pos() as u64);
+            let sql_mode = if query.has_sql_mode() { SqlMode::new(query.sql_mode()) } else { default_sql_mode };
+            history.apply(&position, query.db_name(), sql, sql_mode);
+        }
+        LogEvent::TableMap(table_map) => {
+            let position = EntryPosition::new(context.log_position().journal_name(), table_map.header().log_pos() as u64);
+            let (schema, table) = (table_map.db_name().to_string(), table_map.table_name().to_string());
+            if history.find(&schema, &table, &position).is_some_and(|definition| definition.enrich(table_map)) {
+                context.put_table(table_map.clone());
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_fatal_reading_binlog(error: Option<&ServerError>) -> bool {
+    error.is_some_and(|error| error.errno() == ServerErrno::MasterFatalReadingBinlog)
+}
+
+// Raw模式下只解析rotate和format description, 后者用于跟踪checksum alg的变化
+fn raw_event(header: LogHeader, event: &[u8], context: &mut LogContext) -> Result<LogEvent, CanalError> {
+    match header.event_type() {
+        Some(EventType::RotateEvent) => Ok(LogEvent::Rotate(RotateLogEvent::from(header, &mut LogBuffer::new(event), context.format_description())?)),
+        Some(EventType::FormatDescriptionEvent) => {
+            let description = FormatDescriptionLogEvent::from(header, &mut LogBuffer::new(event))?;
+            context.set_format_description(description.clone());
+            Ok(LogEvent::FormatDescription(description))
+        }
+        _ => Ok(LogEvent::Unknown(header)),
+    }
+}
@@ -0,0 +1,255 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use crate::command::event::TableMapLogEvent;
+use crate::instance::ddl::{parse_table_change, AlterOperation, ColumnDefinition, ColumnPosition, SqlMode, TableChange};
+use crate::instance::EntryPosition;
+
+// 按位点升序的版本, None表示表被删除或者结构未知
+pub type TableVersions = Vec<(EntryPosition, Option<TableDefinition>)>;
+
+// 某个版本的表结构, 列按表中的顺序
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDefinition {
+    schema_name: String,
+    table_name: String,
+    columns: Vec<ColumnDefinition>,
+}
+
+impl TableDefinition {
+    pub fn new(schema_name: &str, table_name: &str, columns: Vec<ColumnDefinition>) -> TableDefinition {
+        TableDefinition { schema_name: schema_name.to_string(), table_name: table_name.to_string(), columns }
+    }
+
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn columns(&self) -> &Vec<ColumnDefinition> {
+        &self.columns
+    }
+
+    // 列名不区分大小写
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name().eq_ignore_ascii_case(name))
+    }
+
+    fn renamed(mut self, schema_name: &str, table_name: &str) -> TableDefinition {
+        self.schema_name = schema_name.to_string();
+        self.table_name = table_name.to_string();
+        self
+    }
+
+    // 列不存在时返回Err, 调用方不再信任该表的结构
+    fn alter(&mut self, operation: AlterOperation) -> Result<(), String> {
+        match operation {
+            AlterOperation::AddColumn(column, position) => {
+                let index = self.insert_index(&position, self.columns.len())?;
+                self.columns.insert(index, column);
+            }
+            AlterOperation::DropColumn(name) => {
+                let index = self.existing(&name)?;
+                self.columns.remove(index);
+            }
+            // MODIFY/CHANGE不带FIRST/AFTER时位置不变, 主键是索引而不是列属性, 同样保持不变
+            AlterOperation::ChangeColumn(old, mut column, position) => {
+                let index = self.existing(&old)?;
+                let old = self.columns.remove(index);
+                if old.primary_key() {
+                    column.set_primary_key(true);
+                }
+                let index = self.insert_index(&position, index)?;
+                self.columns.insert(index, column);
+            }
+            AlterOperation::RenameColumn(old, new) => {
+                let index = self.existing(&old)?;
+                self.columns[index].set_name(&new);
+            }
+            AlterOperation::AddPrimaryKey(names) => {
+                for name in names {
+                    let index = self.existing(&name)?;
+                    self.columns[index].set_primary_key(true);
+                }
+            }
+            AlterOperation::DropPrimaryKey => self.columns.iter_mut().for_each(|column| column.set_primary_key(false)),
+            AlterOperation::RenameTable(..) => {}
+        }
+        Ok(())
+    }
+
+    fn existing(&self, name: &str) -> Result<usize, String> {
+        self.column_index(name).ok_or_else(|| format!("unknown column {} in {}.{}", name, self.schema_name, self.table_name))
+    }
+
+    fn insert_index(&self, position: &ColumnPosition, last: usize) -> Result<usize, String> {
+        match position {
+            ColumnPosition::Last => Ok(last),
+            ColumnPosition::First => Ok(0),
+            ColumnPosition::After(name) => Ok(self.existing(name)? + 1),
+        }
+    }
+
+    /**
+     * <pre>
+     *  binlog_row_metadata=MINIMAL时table map中没有列名, 用该版本的表结构补全列名, 主键和UNSIGNED,
+     *  rows event按补全后的table map解码. 列数不一致说明DDL解析遗漏了变更, 不补全;
+     *  table map中已有列名(FULL)时以table map为准
+     * </pre>
+     */
+    pub fn enrich(&self, table_map: &mut TableMapLogEvent) -> bool {
+        if table_map.column_count() != self.columns.len() || table_map.column_info().iter().any(|info| info.name().is_some()) {
+            return false;
+        }
+        for (info, column) in table_map.column_info_mut().iter_mut().zip(self.columns.iter()) {
+            info.set_name(column.name());
+            info.set_pk(column.primary_key());
+            info.set_unsigned(info.unsigned() || column.unsigned());
+        }
+        true
+    }
+}
+
+/**
+ * <pre>
+ *  对应canal中的TableMetaTSDB, 按binlog位点保存每张表的历史版本:
+ *  每条改变表结构的DDL(见ddl::parse_table_change)在它的位点(event结束位置)生成受影响的表的新版本,
+ *  None表示表被删除或者无法得到表结构(CREATE TABLE ... SELECT, ALTER了未知的列).
+ *  从旧位点重新dump时, table map按它所在的位点找到当时的版本, 而不是当前的表结构.
+ *  重复应用同一位点的DDL时替换该位点的版本, 因此重连之后重放DDL不会改变结果.
+ *  只保存在内存中, 没有见过CREATE TABLE的表没有版本, 由调用方通过put设置初始的表结构
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SchemaHistory {
+    // (schema, table) -> 版本
+    tables: BTreeMap<(String, String), TableVersions>,
+    ddls: u64,
+}
+
+impl SchemaHistory {
+    pub fn new() -> SchemaHistory {
+        SchemaHistory::default()
+    }
+
+    // 已经应用的DDL数量
+    pub fn ddls(&self) -> u64 {
+        self.ddls
+    }
+
+    // 在位点position设置表的版本, 同一位点已有版本时替换
+    pub fn put(&mut self, position: &EntryPosition, schema_name: &str, table_name: &str,
+               definition: Option<TableDefinition>) {
+        let versions = self.tables.entry((schema_name.to_string(), table_name.to_string())).or_default();
+        let index = versions.partition_point(|(version, _)| version.compare(position) == Ordering::Less);
+        match versions.get_mut(index) {
+            Some((version, existing)) if version.compare(position) == Ordering::Equal => *existing = definition,
+            _ => versions.insert(index, (position.clone(), definition)),
+        }
+    }
+
+    // position时表的结构: 位点不大于position的最后一个版本
+    pub fn find(&self, schema_name: &str, table_name: &str, position: &EntryPosition) -> Option<&TableDefinition> {
+        let versions = self.tables.get(&(schema_name.to_string(), table_name.to_string()))?;
+        let index = versions.partition_point(|(version, _)| version.compare(position) != Ordering::Greater);
+        versions[..index].last()?.1.as_ref()
+    }
+
+    // 版本数量, 包括删除
+    pub fn versions(&self, schema_name: &str, table_name: &str) -> usize {
+        self.tables.get(&(schema_name.to_string(), table_name.to_string())).map_or(0, |versions| versions.len())
+    }
+
+    // 最新版本存在的表, 格式为schema.table
+    pub fn names(&self) -> Vec<String> {
+        self.tables.iter()
+            .filter(|(_, versions)| versions.last().is_some_and(|(_, definition)| definition.is_some()))
+            .map(|((schema, table), _)| format!("{}.{}", schema, table))
+            .collect()
+    }
+
+    /**
+     * <pre>
+     *  应用位点position处的DDL, default_schema为QueryLogEvent的db_name, 不改变表结构的语句返回false.
+     *  新版本基于position之前的版本生成, 同一条语句中先变更的表(RENAME TABLE a TO b, b TO c)对后面可见
+     * </pre>
+     */
+    pub fn apply(&mut self, position: &EntryPosition, default_schema: &str, sql: &str, sql_mode: SqlMode) -> bool {
+        let change = match parse_table_change(sql, sql_mode) {
+            Some(change) => change,
+            None => return false,
+        };
+        let qualify = |(schema, table): (String, String)| {
+            (if schema.is_empty() { default_schema.to_string() } else { schema }, table)
+        };
+        let mut changes: Vec<((String, String), Option<TableDefinition>)> = vec![];
+        match change {
+            TableChange::Create { table, if_not_exists, columns } => {
+                let table = qualify(table);
+                if if_not_exists && self.before(&changes, &table, position).is_some() {
+                    return false;
+                }
+                let definition = Some(columns).filter(|columns| !columns.is_empty())
+                    .map(|columns| TableDefinition::new(&table.0, &table.1, columns));
+                changes.push((table, definition));
+            }
+            TableChange::CreateLike { table, like } => {
+                let table = qualify(table);
+                let definition = self.before(&changes, &qualify(like), position)
+                    .map(|definition| definition.renamed(&table.0, &table.1));
+                changes.push((table, definition));
+            }
+            TableChange::Alter { table, operations } => {
+                let table = qualify(table);
+                let mut target = table.clone();
+                let mut definition = self.before(&changes, &table, position);
+                for operation in operations {
+                    match operation {
+                        AlterOperation::RenameTable(schema, name) => target = qualify((schema, name)),
+                        operation => {
+                            definition = definition.and_then(|mut definition| {
+                                definition.alter(operation).ok().map(|_| definition)
+                            });
+                        }
+                    }
+                }
+                if target != table {
+                    changes.push((table, None));
+                }
+                let definition = definition.map(|definition| definition.renamed(&target.0, &target.1));
+                changes.push((target, definition));
+            }
+            TableChange::Drop(tables) => changes.extend(tables.into_iter().map(|table| (qualify(table), None))),
+            TableChange::Rename(tables) => {
+                for (from, to) in tables {
+                    let (from, to) = (qualify(from), qualify(to));
+                    let definition = self.before(&changes, &from, position)
+                        .map(|definition| definition.renamed(&to.0, &to.1));
+                    changes.push((from, None));
+                    changes.push((to, definition));
+                }
+            }
+            TableChange::DropDatabase(schema) => {
+                let tables: Vec<(String, String)> = self.tables.keys().filter(|(name, _)| *name == schema).cloned().collect();
+                changes.extend(tables.into_iter().map(|table| (table, None)));
+            }
+        }
+        for ((schema, table), definition) in changes {
+            self.put(position, &schema, &table, definition);
+        }
+        self.ddls += 1;
+        true
+    }
+
+    // 同一条语句中已经产生的变更优先, 否则为position之前的最后一个版本
+    fn before(&self, changes: &[((String, String), Option<TableDefinition>)], table: &(String, String),
+              position: &EntryPosition) -> Option<TableDefinition> {
+        if let Some((_, definition)) = changes.iter().rev().find(|(name, _)| name == table) {
+            return definition.clone();
+        }
+        let versions = self.tables.get(table)?;
+        let index = versions.partition_point(|(version, _)| version.compare(position) == Ordering::Less);
+        versions[..index].last()?.1.clone()
+    }
+}
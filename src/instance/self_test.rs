@@ -0,0 +1,198 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::command::event::column_type::*;
+use crate::command::event::{EventType, LogContext, LogDecoder};
+use crate::instance::convert::LogEventConvert;
+use crate::mock::MockBinlog;
+use crate::protocol::{Column, Entry};
+
+const SELF_TEST_FILE: &str = "self-test.000001";
+const SELF_TEST_TABLE_ID: u64 = 1;
+const SELF_TEST_SCHEMA: &str = "self_test";
+const SELF_TEST_TABLE: &str = "all_types";
+
+// 一列: 名字, table map中的类型和metadata, rows中的字节, 期望解码出的文本
+struct SelfTestColumn {
+    name: &'static str,
+    kind: u8,
+    meta: Vec<u8>,
+    value: Vec<u8>,
+    expected: &'static str,
+}
+
+fn column(name: &'static str, kind: u8, meta: &[u8], value: &[u8], expected: &'static str) -> SelfTestColumn {
+    SelfTestColumn { name, kind, meta: meta.to_vec(), value: value.to_vec(), expected }
+}
+
+/**
+ * <pre>
+ *  内置的fixture: 一个覆盖所有支持的列类型的表, 编码方式与MySQL 8.0写入binlog的一致,
+ *  期望值为MySQL客户端看到的文本. timestamp为本地时区相关, 只检查零值
+ * </pre>
+ */
+fn columns() -> Vec<SelfTestColumn> {
+    let date = 29 + 2 * 32 + 2024 * 16 * 32u32;
+    let hms = (12u64 << 12) | (34 << 6) | 56;
+    let datetime = ((((2024 * 13 + 2) << 5) | 29) << 17 | hms) + 0x8000000000;
+    let mut varchar = vec![6];
+    varchar.extend_from_slice("héllo".as_bytes());
+    vec![
+        column("tinyint", MYSQL_TYPE_TINY, &[], &[0xff], "-1"),
+        column("smallint", MYSQL_TYPE_SHORT, &[], &0x1234u16.to_le_bytes(), "4660"),
+        column("mediumint", MYSQL_TYPE_INT24, &[], &[0xfe, 0xff, 0xff], "-2"),
+        column("int", MYSQL_TYPE_LONG, &[], &123456789u32.to_le_bytes(), "123456789"),
+        column("bigint", MYSQL_TYPE_LONGLONG, &[], &(-9000000000i64).to_le_bytes(), "-9000000000"),
+        column("float", MYSQL_TYPE_FLOAT, &[4], &1.5f32.to_bits().to_le_bytes(), "1.5"),
+        column("double", MYSQL_TYPE_DOUBLE, &[8], &2.25f64.to_bits().to_le_bytes(), "2.25"),
+        column("decimal", MYSQL_TYPE_NEWDECIMAL, &[10, 2], &[0x80, 0xbc, 0x61, 0x4e, 0x5a], "12345678.90"),
+        column("negative_decimal", MYSQL_TYPE_NEWDECIMAL, &[4, 2], &[0x7e, 0xcd], "-1.50"),
+        column("year", MYSQL_TYPE_YEAR, &[], &[124], "2024"),
+        column("date", MYSQL_TYPE_DATE, &[], &date.to_le_bytes()[..3], "2024-02-29"),
+        column("time", MYSQL_TYPE_TIME2, &[0], &(hms as u32 + 0x800000).to_be_bytes()[1..], "12:34:56"),
+        column("datetime", MYSQL_TYPE_DATETIME2, &[0], &datetime.to_be_bytes()[3..], "2024-02-29 12:34:56"),
+        column("datetime_fraction", MYSQL_TYPE_DATETIME2, &[3],
+               &[&datetime.to_be_bytes()[3..], &1230u16.to_be_bytes()[..]].concat(), "2024-02-29 12:34:56.123"),
+        column("timestamp", MYSQL_TYPE_TIMESTAMP2, &[0], &[0, 0, 0, 0], "0000-00-00 00:00:00"),
+        column("varchar", MYSQL_TYPE_VARCHAR, &[20, 0], &varchar, "héllo"),
+        column("char", MYSQL_TYPE_STRING, &[MYSQL_TYPE_STRING, 10], &[2, b'a', b'b'], "ab"),
+        column("enum", MYSQL_TYPE_STRING, &[MYSQL_TYPE_ENUM, 1], &[2], "2"),
+        column("set", MYSQL_TYPE_STRING, &[MYSQL_TYPE_SET, 1], &[5], "5"),
+        column("bit", MYSQL_TYPE_BIT, &[2, 1], &[0x02, 0x01], "513"),
+        column("blob", MYSQL_TYPE_BLOB, &[2], &[3, 0, b'x', b'y', b'z'], "xyz"),
+        column("json", MYSQL_TYPE_JSON, &[4],
+               &[&[13, 0, 0, 0][..], &[0x00, 1, 0, 12, 0, 11, 0, 1, 0, 0x05, 1, 0, b'a'][..]].concat(), "{\"a\": 1}"),
+    ]
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelfTestReport {
+    // 检查的列值数
+    checked: usize,
+    failures: Vec<String>,
+}
+
+impl SelfTestReport {
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+    pub fn failures(&self) -> &Vec<String> {
+        &self.failures
+    }
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/**
+ * <pre>
+ *  启动时的自检: 解码内置的fixture(FORMAT_DESCRIPTION(带CRC32), TABLE_MAP, 两行的WRITE_ROWS:
+ *  第一行为每种类型的一个值, 第二行全部为NULL), 逐列与期望值比较.
+ *  用于在上线时拦截错误编译的二进制或者解码的回归, 只在内存中执行, 耗时在毫秒级.
+ *  解码过程中的panic同样记为失败
+ * </pre>
+ */
+pub fn run_self_test() -> SelfTestReport {
+    let columns = columns();
+    let mut report = SelfTestReport::default();
+    match catch_unwind(AssertUnwindSafe(|| decode_fixture(&columns))) {
+        Ok(Ok(entry)) => check_entry(&entry, &columns, &mut report),
+        Ok(Err(e)) => report.failures.push(format!("decode fixture failure: {}", e)),
+        Err(_) => report.failures.push("panic while decoding fixture".to_string()),
+    }
+    report
+}
+
+// 有失败时返回Err, 包含所有失败的列
+pub fn self_test() -> Result<SelfTestReport, String> {
+    let report = run_self_test();
+    if !report.is_ok() {
+        return Err(format!("self test failed, {} of {} checks: {}", report.failures.len(),
+                           report.checked.max(report.failures.len()), report.failures.join("; ")));
+    }
+    Ok(report)
+}
+
+fn decode_fixture(columns: &[SelfTestColumn]) -> Result<Entry, String> {
+    let binlog = MockBinlog::new(1, "8.0.33-self-test", true);
+    let format_description = binlog.format_description(None);
+    let table_map = binlog.event(EventType::TableMapEvent, &table_map_body(columns), 0, 0);
+    let rows = binlog.event(EventType::WriteRowsEvent, &write_rows_body(columns), 0, 0);
+
+    let mut decoder = LogDecoder::new();
+    let mut convert = LogEventConvert::new();
+    let mut context = LogContext::new();
+    context.log_position_mut().set_journal_name(SELF_TEST_FILE);
+    decoder.decode(&format_description, &mut context)?;
+    decoder.decode(&table_map, &mut context)?;
+    let event = decoder.decode(&rows, &mut context)?;
+    convert.parse(&event, &context, true)?.ok_or_else(|| "rows event produced no entry".to_string())
+}
+
+fn check_entry(entry: &Entry, columns: &[SelfTestColumn], report: &mut SelfTestReport) {
+    let header = entry.header();
+    if (header.schema_name(), header.table_name()) != (SELF_TEST_SCHEMA, SELF_TEST_TABLE) {
+        report.failures.push(format!("table is {}.{}", header.schema_name(), header.table_name()));
+    }
+    let rows = match entry.row_change() {
+        Some(row_change) if row_change.row_datas().len() == 2 => row_change.row_datas(),
+        _ => {
+            report.failures.push("expect 2 rows".to_string());
+            return;
+        }
+    };
+    let (values, nulls) = (rows[0].after_columns(), rows[1].after_columns());
+    if values.len() != columns.len() || nulls.len() != columns.len() {
+        report.failures.push(format!("expect {} columns, got {} and {}", columns.len(), values.len(), nulls.len()));
+        return;
+    }
+    for ((expected, value), null) in columns.iter().zip(values).zip(nulls) {
+        report.checked += 2;
+        if value.is_null() || value.value() != expected.expected {
+            report.failures.push(format!("{}: expect {}, got {}", expected.name, expected.expected, display(value)));
+        }
+        if !null.is_null() {
+            report.failures.push(format!("{}: expect NULL, got {}", expected.name, display(null)));
+        }
+    }
+}
+
+fn display(column: &Column) -> String {
+    if column.is_null() { "NULL".to_string() } else { column.value().to_string() }
+}
+
+fn table_map_body(columns: &[SelfTestColumn]) -> Vec<u8> {
+    let mut body = SELF_TEST_TABLE_ID.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    for name in [SELF_TEST_SCHEMA, SELF_TEST_TABLE] {
+        body.push(name.len() as u8);
+        body.extend_from_slice(name.as_bytes());
+        body.push(0);
+    }
+    body.push(columns.len() as u8);
+    body.extend(columns.iter().map(|column| column.kind));
+    let meta: Vec<u8> = columns.iter().flat_map(|column| column.meta.iter().copied()).collect();
+    body.push(meta.len() as u8);
+    body.extend_from_slice(&meta);
+    body.extend(std::iter::repeat_n(0xff, columns.len().div_ceil(8)));
+    body
+}
+
+// v2 WRITE_ROWS, 所有列都出现, 第二行全部为NULL
+fn write_rows_body(columns: &[SelfTestColumn]) -> Vec<u8> {
+    let bitmap_len = columns.len().div_ceil(8);
+    let mut body = SELF_TEST_TABLE_ID.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    body.extend_from_slice(&2u16.to_le_bytes());
+    body.push(columns.len() as u8);
+    body.extend(std::iter::repeat_n(0xff, bitmap_len));
+    body.extend(std::iter::repeat_n(0, bitmap_len));
+    for column in columns {
+        body.extend_from_slice(&column.value);
+    }
+    let mut nulls = vec![0xffu8; bitmap_len];
+    if !columns.len().is_multiple_of(8) {
+        nulls[bitmap_len - 1] = (1 << (columns.len() % 8)) - 1;
+    }
+    body.extend_from_slice(&nulls);
+    body
+}
@@ -0,0 +1,161 @@
+use crate::command::event::BINLOG_MAGIC;
+use crate::config::Properties;
+use crate::instance::EntryPosition;
+
+// 默认的启动位点优先级, 与之前的行为一致: 有位点时使用, 否则从master当前的位置开始
+pub const DEFAULT_START_MODES: [StartMode; 3] = [StartMode::Stored, StartMode::Explicit, StartMode::Latest];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartMode {
+    // master上最早的binlog(show binary logs的第一个文件)的开头
+    Earliest,
+    // master当前的位置(show master status)
+    Latest,
+    // 之前持久化的位点, 见MysqlEventParser::set_entry_position
+    Stored,
+    // 配置指定的位点, master.journal_name + master.position
+    Explicit,
+}
+
+impl StartMode {
+    pub fn from_name(name: &str) -> Result<StartMode, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "earliest" => Ok(StartMode::Earliest),
+            "latest" => Ok(StartMode::Latest),
+            "stored" => Ok(StartMode::Stored),
+            "explicit" => Ok(StartMode::Explicit),
+            _ => Err(format!("unknown start mode {}, expect earliest/latest/stored/explicit", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StartMode::Earliest => "earliest",
+            StartMode::Latest => "latest",
+            StartMode::Stored => "stored",
+            StartMode::Explicit => "explicit",
+        }
+    }
+}
+
+// 最终选择的启动位点以及原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartPosition {
+    position: EntryPosition,
+    mode: StartMode,
+    reason: String,
+}
+
+impl StartPosition {
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+    pub fn mode(&self) -> StartMode {
+        self.mode
+    }
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/**
+ * <pre>
+ *  首次连接时的启动位点, 按优先级依次尝试, 第一个可用的来源生效:
+ *      stored,explicit,latest      默认, 有持久化的位点时继续, 否则使用配置的位点, 都没有时从master当前位置开始
+ *      explicit,earliest           使用配置的位点, 没有配置时从最早的binlog开始
+ *      latest                      总是从master当前位置开始, 忽略持久化的位点
+ *  stored和explicit没有位点时跳过, earliest/latest总是可用, 之后的来源不再尝试.
+ *  配置: master.start_mode=stored,explicit,latest, master.journal_name=mysql-bin.000001, master.position=4.
+ *  只影响首次连接, 之后的重连从已解析的位点继续
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartPolicy {
+    modes: Vec<StartMode>,
+}
+
+impl Default for StartPolicy {
+    fn default() -> StartPolicy {
+        StartPolicy { modes: DEFAULT_START_MODES.to_vec() }
+    }
+}
+
+impl StartPolicy {
+    pub fn new(modes: Vec<StartMode>) -> Result<StartPolicy, String> {
+        if modes.is_empty() {
+            return Err("start mode is empty".to_string());
+        }
+        for (i, mode) in modes.iter().enumerate() {
+            if modes[..i].contains(mode) {
+                return Err(format!("start mode {} is duplicated", mode.name()));
+            }
+        }
+        Ok(StartPolicy { modes })
+    }
+
+    // 逗号分隔, 例如 stored,explicit,latest
+    pub fn from_name(name: &str) -> Result<StartPolicy, String> {
+        StartPolicy::new(name.split(',').filter(|mode| !mode.trim().is_empty())
+            .map(StartMode::from_name).collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // 没有配置master.start_mode时返回默认的优先级
+    pub fn from_properties(properties: &Properties) -> Result<StartPolicy, String> {
+        match properties.get("master.start_mode") {
+            Some(name) => StartPolicy::from_name(name),
+            None => Ok(StartPolicy::default()),
+        }
+    }
+
+    pub fn modes(&self) -> &Vec<StartMode> {
+        &self.modes
+    }
+
+    pub fn name(&self) -> String {
+        self.modes.iter().map(|mode| mode.name()).collect::<Vec<_>>().join(",")
+    }
+
+    /**
+     * <pre>
+     *  按优先级选择启动位点, master(mode)查询earliest/latest的位置.
+     *  所有来源都不可用(例如只配置了stored但没有持久化的位点)时返回Err
+     * </pre>
+     */
+    pub fn resolve<F>(&self, stored: Option<&EntryPosition>, explicit: Option<&EntryPosition>, mut master: F)
+                      -> Result<StartPosition, String>
+        where F: FnMut(StartMode) -> Result<EntryPosition, String> {
+        let mut skipped = vec![];
+        for mode in self.modes.iter().copied() {
+            let (position, reason) = match mode {
+                StartMode::Stored | StartMode::Explicit => {
+                    let position = if mode == StartMode::Stored { stored } else { explicit };
+                    match position {
+                        Some(position) => (position.clone(), format!("{} position is set", mode.name())),
+                        None => {
+                            skipped.push(format!("no {} position", mode.name()));
+                            continue;
+                        }
+                    }
+                }
+                StartMode::Earliest | StartMode::Latest => (master(mode)?, format!("{} position of the master", mode.name())),
+            };
+            let reason = if skipped.is_empty() { reason } else { format!("{}, {}", skipped.join(", "), reason) };
+            return Ok(StartPosition { position, mode, reason });
+        }
+        Err(format!("no start position is available for start mode {}: {}", self.name(), skipped.join(", ")))
+    }
+}
+
+// master.journal_name + master.position, 没有配置journal_name时返回None, position默认为文件开头(4)
+pub fn explicit_position(properties: &Properties) -> Result<Option<EntryPosition>, String> {
+    let journal_name = match properties.get("master.journal_name").map(|name| name.trim()) {
+        Some(journal_name) if !journal_name.is_empty() => journal_name,
+        _ => return Ok(None),
+    };
+    let position = match properties.get("master.position") {
+        Some(position) => position.trim().parse::<u64>()
+            .map_err(|_| format!("invalid master.position {}", position))?,
+        None => BINLOG_MAGIC.len() as u64,
+    };
+    Ok(Some(EntryPosition::new(journal_name, position)))
+}
@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::instance::catchup::CatchUpStatus;
+use crate::instance::position_manager::MemoryPositionManager;
+use crate::instance::running::MysqlEventParser;
+use crate::instance::supervisor::InstanceStatus;
+use crate::instance::EntryPosition;
+use crate::metrics::{ChannelStats, RateKind, StreamMetrics};
+use crate::sink::health::SinkHealth;
+use crate::sink::mq::flat_message::json_string;
+
+// status_json的格式版本, 删除或者修改字段的含义时递增, 只增加字段时不变
+pub const STATUS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionState {
+    // 还没有启动
+    #[default]
+    Idle,
+    Connecting,
+    Dumping,
+    // dump失败之后等待重新连接
+    Reconnecting,
+    // 正常结束或者被stop
+    Stopped,
+    // 不再重试
+    Failed,
+}
+
+impl ConnectionState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConnectionState::Idle => "idle",
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Dumping => "dumping",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Stopped => "stopped",
+            ConnectionState::Failed => "failed",
+        }
+    }
+}
+
+// parser在dump线程中更新, 其它线程通过MysqlEventParser::status_handle读取
+#[derive(Debug, Clone, Default)]
+pub struct ParserStatus {
+    state: ConnectionState,
+    // address:port
+    master: String,
+    connects: u64,
+    // 最近解析的event之后的位点
+    position: Option<EntryPosition>,
+    // 最近解析的event在master上的执行时间, 毫秒, 收到heartbeat时为收到的时间
+    last_event_time: i64,
+    last_error: Option<String>,
+    last_error_at: i64,
+    // master时钟 - 本地时钟, 毫秒, last_event_time已经按它修正
+    clock_offset: i64,
+    // 没有设置追赶策略时为None
+    catch_up: Option<CatchUpStatus>,
+    // entry_sink背压时暂停读取binlog
+    backpressured: bool,
+    backpressure_pauses: u64,
+    // 累计暂停的时间, 毫秒
+    backpressure_millis: u64,
+    // 收到的HEARTBEAT_LOG_EVENT数量以及最近一次收到的本地时间
+    heartbeats: u64,
+    last_heartbeat_at: i64,
+    // 超过heartbeat_timeout没有收到任何数据, 判定连接已经失效的次数
+    heartbeat_timeouts: u64,
+}
+
+impl ParserStatus {
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+    pub fn master(&self) -> &str {
+        &self.master
+    }
+    pub fn connects(&self) -> u64 {
+        self.connects
+    }
+    pub fn position(&self) -> Option<&EntryPosition> {
+        self.position.as_ref()
+    }
+    pub fn last_event_time(&self) -> i64 {
+        self.last_event_time
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    pub fn last_error_at(&self) -> i64 {
+        self.last_error_at
+    }
+    pub fn clock_offset(&self) -> i64 {
+        self.clock_offset
+    }
+    pub fn catch_up(&self) -> Option<&CatchUpStatus> {
+        self.catch_up.as_ref()
+    }
+    pub fn is_backpressured(&self) -> bool {
+        self.backpressured
+    }
+    pub fn backpressure_pauses(&self) -> u64 {
+        self.backpressure_pauses
+    }
+    pub fn backpressure_millis(&self) -> u64 {
+        self.backpressure_millis
+    }
+    pub fn heartbeats(&self) -> u64 {
+        self.heartbeats
+    }
+    pub fn last_heartbeat_at(&self) -> i64 {
+        self.last_heartbeat_at
+    }
+    pub fn heartbeat_timeouts(&self) -> u64 {
+        self.heartbeat_timeouts
+    }
+
+    // 没有收到过event时为None, master时钟快于本地时按0处理
+    pub fn lag_millis(&self) -> Option<i64> {
+        (self.last_event_time > 0).then(|| (Utc::now().timestamp_millis() - self.last_event_time).max(0))
+    }
+
+    pub fn set_state(&mut self, state: ConnectionState) {
+        self.state = state;
+    }
+
+    pub fn connecting(&mut self, master: &str) {
+        self.state = ConnectionState::Connecting;
+        self.master = master.to_string();
+        self.connects += 1;
+    }
+
+    pub fn record_event(&mut self, position: EntryPosition, event_time: i64) {
+        self.position = Some(position);
+        self.last_event_time = event_time;
+    }
+
+    pub fn set_clock_offset(&mut self, clock_offset: i64) {
+        self.clock_offset = clock_offset;
+    }
+
+    pub fn set_catch_up(&mut self, catch_up: CatchUpStatus) {
+        self.catch_up = Some(catch_up);
+    }
+
+    pub fn set_backpressured(&mut self, backpressured: bool) {
+        self.backpressured = backpressured;
+    }
+
+    // 一次背压暂停结束
+    pub fn record_backpressure(&mut self, paused: Duration) {
+        self.backpressured = false;
+        self.backpressure_pauses += 1;
+        self.backpressure_millis += paused.as_millis() as u64;
+    }
+
+    pub fn record_heartbeat(&mut self, received_at: i64) {
+        self.heartbeats += 1;
+        self.last_heartbeat_at = received_at;
+    }
+
+    pub fn record_heartbeat_timeout(&mut self) {
+        self.heartbeat_timeouts += 1;
+    }
+
+    pub fn record_error(&mut self, error: &str) {
+        self.last_error = Some(error.to_string());
+        self.last_error_at = Utc::now().timestamp_millis();
+    }
+}
+
+/**
+ * <pre>
+ *  一个instance的状态句柄, 用于导出给k8s operator/编排脚本的json状态:
+ *      connection  连接状态, master地址, 连接次数
+ *      position    最近解析的位点(parsed)以及重新dump的位点(restart)
+ *      lag_ms      当前时间 - 最近解析的event的执行时间(按clock_offset_ms修正master的时钟偏差)
+ *      catch_up    剩余的binlog字节数以及追上master的ETA, 没有设置追赶策略时为null
+ *      backpressure  sink背压时是否正在暂停读取, 暂停的次数以及累计时间
+ *      heartbeat   收到的heartbeat数量, 最近一次收到的时间, 因为没有收到heartbeat判定连接失效的次数
+ *      throughput  各项速率的累计值以及1分钟速率
+ *      network     与master之间的读写字节数, short read以及按类型统计的网络错误(timeout/reset/eof/refused/unreachable/other)
+ *      errors      parser最近的错误, supervisor记录的最近的错误和panic
+ *      sinks       通过HealthSink记录的每个sink的健康状态
+ *  所有字段都是句柄, Instance可以clone到admin线程中. supervisor每次重启都会创建新的parser, 此时在factory中调用attach:
+ *      let instance = Instance::new("example");
+ *      let attached = instance.clone();
+ *      supervisor.add_parser("example", move || { let mut parser = create_parser(); attached.attach(&mut parser); Ok(parser) })?;
+ *      instance.status_json();
+ * </pre>
+ */
+#[derive(Clone)]
+pub struct Instance {
+    name: String,
+    status: Arc<Mutex<ParserStatus>>,
+    metrics: Arc<Mutex<StreamMetrics>>,
+    network: Arc<Mutex<ChannelStats>>,
+    position: MemoryPositionManager,
+    supervisor: Option<Arc<Mutex<BTreeMap<String, InstanceStatus>>>>,
+    sinks: Vec<(String, Arc<Mutex<SinkHealth>>)>,
+}
+
+impl Instance {
+    pub fn new(name: &str) -> Instance {
+        Instance {
+            name: name.to_string(),
+            status: Arc::new(Mutex::new(ParserStatus::default())),
+            metrics: Arc::new(Mutex::new(StreamMetrics::new())),
+            network: Arc::new(Mutex::new(ChannelStats::new())),
+            position: MemoryPositionManager::new(),
+            supervisor: None,
+            sinks: vec![],
+        }
+    }
+
+    // 使用parser已有的句柄
+    pub fn from_parser(name: &str, parser: &MysqlEventParser) -> Instance {
+        Instance {
+            name: name.to_string(),
+            status: parser.status_handle(),
+            metrics: parser.metrics(),
+            network: parser.channel_stats(),
+            position: parser.position_handle(),
+            supervisor: None,
+            sinks: vec![],
+        }
+    }
+
+    // parser之后的状态更新到该instance的句柄
+    pub fn attach(&self, parser: &mut MysqlEventParser) {
+        parser.set_status_handle(self.status.clone());
+        parser.set_metrics(self.metrics.clone());
+        parser.set_channel_stats(self.network.clone());
+        parser.set_position_handle(self.position.clone());
+    }
+
+    // InstanceSupervisor::status_handle, 按instance的名字查找
+    pub fn set_supervisor(&mut self, statuses: Arc<Mutex<BTreeMap<String, InstanceStatus>>>) {
+        self.supervisor = Some(statuses);
+    }
+
+    // HealthSink::health
+    pub fn add_sink(&mut self, name: &str, health: Arc<Mutex<SinkHealth>>) {
+        self.sinks.push((name.to_string(), health));
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn status(&self) -> ParserStatus {
+        self.status.lock().map(|status| status.clone()).unwrap_or_default()
+    }
+
+    pub fn metrics(&self) -> Arc<Mutex<StreamMetrics>> {
+        self.metrics.clone()
+    }
+
+    pub fn network(&self) -> ChannelStats {
+        self.network.lock().map(|network| network.clone()).unwrap_or_default()
+    }
+
+    /**
+     * <pre>
+     *  版本为STATUS_VERSION的json文档, 时间都是毫秒时间戳, 没有的值为null:
+     *  {"version":1,"name":"example","generated_at":1700000000000,
+     *   "connection":{"state":"dumping","master":"127.0.0.1:3306","connects":1,"supervisor":"running","restarts":0},
+     *   "position":{"parsed":{"journal_name":"mysql-bin.000001","position":1024,"timestamp":1700000000000},"restart":null},
+     *   "lag_ms":12,"clock_offset_ms":0,
+     *   "catch_up":{"active":true,"remaining_bytes":2147483648,"files_behind":2,"fetch_rate":52428800,
+     *               "closing_rate":41943040,"eta_ms":51200,"skipped_events":0},
+     *   "backpressure":{"paused":false,"pauses":0,"paused_ms":0},
+     *   "heartbeat":{"received":3,"last_at":1700000000000,"timeouts":0},
+     *   "throughput":{"bytes_fetched":{"total":1024,"instant":12.0,"rate_1m":10.5,"rate_5m":9.8,"rate_15m":9.6},...},
+     *      instant为最近一个完整的5秒周期内的每秒速率, rate_1m/rate_5m/rate_15m为1/5/15分钟的EWMA
+     *   "network":{"connects":1,"connect_failures":0,"bytes_in":1024,"bytes_out":64,"reads":10,"writes":3,"short_reads":2,
+     *              "errors":{"timeout":0,"reset":0,"eof":0,"refused":0,"unreachable":0,"other":0}},
+     *   "errors":{"last_error":null,"last_error_at":null,"supervisor_error":null,"last_panic":null,"panics":0},
+     *   "sinks":[{"name":"kafka","healthy":true,"delivered":10,"failures":0,"last_error":null,"last_error_at":null}]}
+     * </pre>
+     */
+    pub fn status_json(&self) -> String {
+        let status = self.status();
+        let supervised = self.supervisor.as_ref()
+            .and_then(|statuses| statuses.lock().ok()?.get(&self.name).cloned());
+        let restart = self.position.position();
+        let mut out = String::new();
+        let _ = write!(out, "{{\"version\":{},\"name\":{},\"generated_at\":{}", STATUS_VERSION, json_string(&self.name),
+                       Utc::now().timestamp_millis());
+        let _ = write!(out, ",\"connection\":{{\"state\":{},\"master\":{},\"connects\":{},\"supervisor\":{},\"restarts\":{}}}",
+                       json_string(status.state().name()), json_string(status.master()), status.connects(),
+                       optional_string(supervised.as_ref().map(|supervised| supervised.state().name())),
+                       supervised.as_ref().map_or(0, |supervised| supervised.restarts()));
+        let _ = write!(out, ",\"position\":{{\"parsed\":{},\"restart\":{}}}", position_json(status.position()),
+                       position_json(restart.as_ref().map(|restart| restart.position())));
+        let _ = write!(out, ",\"lag_ms\":{},\"clock_offset_ms\":{}",
+                       status.lag_millis().map_or("null".to_string(), |lag| lag.to_string()), status.clock_offset());
+        let _ = write!(out, ",\"catch_up\":{}", catch_up_json(status.catch_up()));
+        let _ = write!(out, ",\"backpressure\":{{\"paused\":{},\"pauses\":{},\"paused_ms\":{}}}",
+                       status.is_backpressured(), status.backpressure_pauses(), status.backpressure_millis());
+        let _ = write!(out, ",\"heartbeat\":{{\"received\":{},\"last_at\":{},\"timeouts\":{}}}", status.heartbeats(),
+                       Some(status.last_heartbeat_at()).filter(|at| *at > 0).map_or("null".to_string(), |at| at.to_string()),
+                       status.heartbeat_timeouts());
+        out.push_str(",\"throughput\":{");
+        if let Ok(mut metrics) = self.metrics.lock() {
+            for (i, kind) in RateKind::ALL.iter().enumerate() {
+                let snapshot = metrics.snapshot(*kind);
+                let _ = write!(out, "{}\"{}\":{{\"total\":{},\"instant\":{},\"rate_1m\":{},\"rate_5m\":{},\"rate_15m\":{}}}",
+                               if i > 0 { "," } else { "" }, kind.name(), snapshot.count, snapshot.instant,
+                               snapshot.one_minute, snapshot.five_minute, snapshot.fifteen_minute);
+            }
+        }
+        out.push('}');
+        let _ = write!(out, ",\"network\":{}", self.network().to_json());
+        let _ = write!(out, ",\"errors\":{{\"last_error\":{},\"last_error_at\":{},\"supervisor_error\":{},\"last_panic\":{},\"panics\":{}}}",
+                       optional_string(status.last_error()), optional_time(status.last_error_at()),
+                       optional_string(supervised.as_ref().and_then(|supervised| supervised.last_error())),
+                       optional_string(supervised.as_ref().and_then(|supervised| supervised.last_panic())),
+                       supervised.as_ref().map_or(0, |supervised| supervised.panics()));
+        out.push_str(",\"sinks\":[");
+        for (i, (name, health)) in self.sinks.iter().enumerate() {
+            let health = health.lock().map(|health| health.clone()).unwrap_or_default();
+            let _ = write!(out, "{}{{\"name\":{},\"healthy\":{},\"delivered\":{},\"failures\":{},\"last_error\":{},\"last_error_at\":{}}}",
+                           if i > 0 { "," } else { "" }, json_string(name), health.is_healthy(), health.delivered(),
+                           health.failures(), optional_string(health.last_error()), optional_time(health.last_error_at()));
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn position_json(position: Option<&EntryPosition>) -> String {
+    match position {
+        Some(position) => format!("{{\"journal_name\":{},\"position\":{},\"timestamp\":{}}}",
+                                  json_string(position.journal_name()), position.position(), position.timestamp()),
+        None => "null".to_string(),
+    }
+}
+
+fn catch_up_json(catch_up: Option<&CatchUpStatus>) -> String {
+    match catch_up {
+        Some(catch_up) => format!("{{\"active\":{},\"remaining_bytes\":{},\"files_behind\":{},\"fetch_rate\":{},\
+                                   \"closing_rate\":{},\"eta_ms\":{},\"skipped_events\":{}}}",
+                                  catch_up.is_active(),
+                                  catch_up.remaining_bytes().map_or("null".to_string(), |bytes| bytes.to_string()),
+                                  catch_up.files_behind(), catch_up.fetch_rate(), catch_up.closing_rate(),
+                                  catch_up.eta().map_or("null".to_string(), |eta| eta.as_millis().to_string()),
+                                  catch_up.skipped_events()),
+        None => "null".to_string(),
+    }
+}
+
+fn optional_string(value: Option<&str>) -> String {
+    value.map_or("null".to_string(), json_string)
+}
+
+// 0表示没有发生
+fn optional_time(time: i64) -> String {
+    if time > 0 { time.to_string() } else { "null".to_string() }
+}
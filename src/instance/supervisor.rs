@@ -0,0 +1,349 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::instance::backoff::Backoff;
+use crate::instance::containment::panic_message;
+use crate::instance::running::MysqlEventParser;
+
+pub const DEFAULT_RESTART_INITIAL_DELAY: Duration = Duration::from_secs(1);
+pub const DEFAULT_RESTART_MAX_DELAY: Duration = Duration::from_secs(60);
+// 运行超过该时间之后退避重新从initial_delay开始
+pub const DEFAULT_STABLE_AFTER: Duration = Duration::from_secs(300);
+pub const DEFAULT_MAX_RESTARTS: usize = 10;
+pub const DEFAULT_RESTART_WINDOW: Duration = Duration::from_secs(600);
+// 等待期间检查running状态的间隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceState {
+    Running,
+    // 失败之后等待重启
+    Restarting,
+    // 正常返回或者被stop
+    Stopped,
+    // restart_window内重启次数超过max_restarts, 不再重启
+    GaveUp,
+}
+
+impl InstanceState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstanceState::Running => "running",
+            InstanceState::Restarting => "restarting",
+            InstanceState::Stopped => "stopped",
+            InstanceState::GaveUp => "gave_up",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstanceStatus {
+    name: String,
+    state: InstanceState,
+    restarts: u64,
+    panics: u64,
+    last_error: Option<String>,
+    last_panic: Option<String>,
+    // 最近一次(重新)启动的时间, 毫秒
+    started_at: i64,
+}
+
+impl InstanceStatus {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn state(&self) -> InstanceState {
+        self.state
+    }
+    pub fn restarts(&self) -> u64 {
+        self.restarts
+    }
+    pub fn panics(&self) -> u64 {
+        self.panics
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    pub fn last_panic(&self) -> Option<&str> {
+        self.last_panic.as_deref()
+    }
+    pub fn started_at(&self) -> i64 {
+        self.started_at
+    }
+}
+
+impl fmt::Display for InstanceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} restarts={} panics={}", self.name, self.state.name(), self.restarts, self.panics)?;
+        if let Some(last_error) = self.last_error.as_ref() {
+            write!(f, " last_error={}", last_error)?;
+        }
+        if let Some(last_panic) = self.last_panic.as_ref() {
+            write!(f, " last_panic={}", last_panic)?;
+        }
+        Ok(())
+    }
+}
+
+type InstanceRun = Box<dyn FnMut(Arc<AtomicBool>) -> Result<(), String> + Send>;
+
+/**
+ * <pre>
+ *  管理多个instance, 每个instance运行在独立的线程(<name>-supervisor)上:
+ *      返回Ok          视为正常结束(例如被stop), 不再重启
+ *      返回Err/panic   记录错误并在退避之后重新调用, 退避间隔按Backoff翻倍并加上最多jitter比例的随机值,
+ *                      一次运行超过stable_after之后退避重新计数
+ *  restart_window内重启超过max_restarts次时认为是重启风暴, 停止重启并进入GaveUp, 需要人工介入.
+ *  instance通过参数中的running判断是否需要停止, 每次重启使用新的实例:
+ *      let mut supervisor = InstanceSupervisor::new();
+ *      supervisor.add_parser("example", move || Ok(create_parser()))?;
+ *      supervisor.status();
+ *      supervisor.shutdown()?;
+ * </pre>
+ */
+pub struct InstanceSupervisor {
+    running: Arc<AtomicBool>,
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    stable_after: Duration,
+    max_restarts: usize,
+    restart_window: Duration,
+    statuses: Arc<Mutex<BTreeMap<String, InstanceStatus>>>,
+    instances: Vec<(String, JoinHandle<()>)>,
+}
+
+impl Default for InstanceSupervisor {
+    fn default() -> Self {
+        InstanceSupervisor::new()
+    }
+}
+
+impl InstanceSupervisor {
+    pub fn new() -> InstanceSupervisor {
+        InstanceSupervisor {
+            running: Arc::new(AtomicBool::new(true)),
+            initial_delay: DEFAULT_RESTART_INITIAL_DELAY,
+            max_delay: DEFAULT_RESTART_MAX_DELAY,
+            jitter: 0.2,
+            stable_after: DEFAULT_STABLE_AFTER,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            restart_window: DEFAULT_RESTART_WINDOW,
+            statuses: Arc::new(Mutex::new(BTreeMap::new())),
+            instances: vec![],
+        }
+    }
+
+    // 以下设置只影响之后add的instance
+    pub fn set_backoff(&mut self, initial_delay: Duration, max_delay: Duration) {
+        self.initial_delay = initial_delay;
+        self.max_delay = max_delay;
+    }
+
+    // 0表示不加随机值
+    pub fn set_jitter(&mut self, jitter: f64) {
+        self.jitter = jitter.clamp(0.0, 1.0);
+    }
+
+    pub fn set_stable_after(&mut self, stable_after: Duration) {
+        self.stable_after = stable_after;
+    }
+
+    pub fn set_restart_limit(&mut self, max_restarts: usize, restart_window: Duration) {
+        self.max_restarts = max_restarts;
+        self.restart_window = restart_window;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> Vec<InstanceStatus> {
+        self.statuses.lock().map(|statuses| statuses.values().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn instance_status(&self, name: &str) -> Option<InstanceStatus> {
+        self.statuses.lock().ok()?.get(name).cloned()
+    }
+
+    // 状态的句柄, 用于在其它线程中导出
+    pub fn status_handle(&self) -> Arc<Mutex<BTreeMap<String, InstanceStatus>>> {
+        self.statuses.clone()
+    }
+
+    pub fn add<F>(&mut self, name: &str, run: F) -> Result<(), String>
+        where F: FnMut(Arc<AtomicBool>) -> Result<(), String> + Send + 'static {
+        if self.instances.iter().any(|(instance, _)| instance == name) {
+            return Err(format!("instance {} is already supervised", name));
+        }
+        let status = InstanceStatus {
+            name: name.to_string(),
+            state: InstanceState::Running,
+            restarts: 0,
+            panics: 0,
+            last_error: None,
+            last_panic: None,
+            started_at: Utc::now().timestamp_millis(),
+        };
+        if let Ok(mut statuses) = self.statuses.lock() {
+            statuses.insert(name.to_string(), status);
+        }
+        let mut backoff = Backoff::new(self.initial_delay, self.max_delay);
+        backoff.set_max_retries(None);
+        let supervised = Supervised {
+            name: name.to_string(),
+            run: Box::new(run),
+            running: self.running.clone(),
+            backoff,
+            jitter: self.jitter,
+            stable_after: self.stable_after,
+            max_restarts: self.max_restarts,
+            restart_window: self.restart_window,
+            restarts: VecDeque::new(),
+            statuses: self.statuses.clone(),
+        };
+        let handle = thread::Builder::new()
+            .name(format!("{}-supervisor", name))
+            .spawn(move || supervised.supervise())
+            .map_err(|e| format!("spawn supervisor of {} failure: {}", name, e))?;
+        self.instances.push((name.to_string(), handle));
+        Ok(())
+    }
+
+    // 每次(重新)启动时通过factory创建新的parser, supervisor停止时同时停止parser
+    pub fn add_parser<F>(&mut self, name: &str, mut factory: F) -> Result<(), String>
+        where F: FnMut() -> Result<MysqlEventParser, String> + Send + 'static {
+        self.add(name, move |running| {
+            let mut parser = factory()?;
+            let handle = parser.running_handle();
+            let done = Arc::new(AtomicBool::new(false));
+            let watcher_done = done.clone();
+            // parser.start会把running重置为true, 因此停止之前需要一直同步
+            let watcher = thread::spawn(move || {
+                while !watcher_done.load(Ordering::SeqCst) {
+                    if !running.load(Ordering::SeqCst) {
+                        handle.store(false, Ordering::SeqCst);
+                    }
+                    thread::sleep(STOP_CHECK_INTERVAL);
+                }
+            });
+            let result = parser.start();
+            done.store(true, Ordering::SeqCst);
+            let _ = watcher.join();
+            result.map_err(String::from)
+        })
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    pub fn join(self) -> Result<(), String> {
+        for (name, handle) in self.instances {
+            if handle.join().is_err() {
+                return Err(format!("supervisor of {} panicked", name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn shutdown(self) -> Result<(), String> {
+        self.stop();
+        self.join()
+    }
+}
+
+struct Supervised {
+    name: String,
+    run: InstanceRun,
+    running: Arc<AtomicBool>,
+    backoff: Backoff,
+    jitter: f64,
+    stable_after: Duration,
+    max_restarts: usize,
+    restart_window: Duration,
+    // restart_window内的重启时间
+    restarts: VecDeque<Instant>,
+    statuses: Arc<Mutex<BTreeMap<String, InstanceStatus>>>,
+}
+
+impl Supervised {
+    fn supervise(mut self) {
+        while self.running.load(Ordering::SeqCst) {
+            let started = Instant::now();
+            self.update(|status| {
+                status.state = InstanceState::Running;
+                status.started_at = Utc::now().timestamp_millis();
+            });
+            let running = self.running.clone();
+            let result = catch_unwind(AssertUnwindSafe(|| (self.run)(running)));
+            if !self.running.load(Ordering::SeqCst) {
+                break;
+            }
+            match result {
+                Ok(Ok(())) => break,
+                Ok(Err(e)) => {
+                    println!("instance {} failure: {}", self.name, e);
+                    self.update(|status| status.last_error = Some(e));
+                }
+                Err(payload) => {
+                    let message = panic_message(payload.as_ref());
+                    println!("instance {} panicked: {}", self.name, message);
+                    self.update(|status| {
+                        status.panics += 1;
+                        status.last_panic = Some(message);
+                    });
+                }
+            }
+            if started.elapsed() >= self.stable_after {
+                self.backoff.reset();
+            }
+            let now = Instant::now();
+            while self.restarts.front().is_some_and(|restart| now.duration_since(*restart) > self.restart_window) {
+                self.restarts.pop_front();
+            }
+            if self.restarts.len() >= self.max_restarts {
+                println!("instance {} restarted {} times within {:?}, give up", self.name, self.restarts.len(),
+                         self.restart_window);
+                self.update(|status| status.state = InstanceState::GaveUp);
+                return;
+            }
+            self.restarts.push_back(now);
+            self.update(|status| {
+                status.state = InstanceState::Restarting;
+                status.restarts += 1;
+            });
+            let delay = self.next_delay();
+            println!("restart instance {} after {:?}", self.name, delay);
+            let deadline = Instant::now() + delay;
+            while self.running.load(Ordering::SeqCst) && Instant::now() < deadline {
+                thread::sleep(STOP_CHECK_INTERVAL.min(deadline - Instant::now()));
+            }
+        }
+        self.update(|status| status.state = InstanceState::Stopped);
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.backoff.next_delay().unwrap_or_default();
+        // RandomState每次使用不同的随机种子
+        let random = RandomState::new().hash_one(self.restarts.len()) as f64 / u64::MAX as f64;
+        delay + delay.mul_f64(self.jitter * random)
+    }
+
+    fn update<F: FnOnce(&mut InstanceStatus)>(&self, f: F) {
+        if let Ok(mut statuses) = self.statuses.lock() {
+            if let Some(status) = statuses.get_mut(&self.name) {
+                f(status);
+            }
+        }
+    }
+}
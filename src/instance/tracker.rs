@@ -0,0 +1,214 @@
+use crate::command::event::{EventType, LogEvent};
+use crate::instance::EntryPosition;
+
+/**
+ * <pre>
+ *  跟踪dump过程中的位点, 用于断线后重新发起COM_BINLOG_DUMP:
+ *  position            最后一个已处理event的结束位置
+ *  transaction_start   当前未结束事务的起始位置(GTID或BEGIN之前)
+ *  重连时从restart_position开始dump, 事务中断时会回到事务开头重新接收整个事务,
+ *  保证多次重连后也不会只收到事务的后半段.
+ *  fake rotate(log_pos=0)只是master告知当前文件名, 不推进位点;
+ *  sequence为本次dump收到的event数量, commits为本次dump中结束的事务数量(包括DDL和切换到下一个文件),
+ *  dump开始时的fake rotate和format description只计入sequence. 两者在每次重新dump时归零.
+ *  重连时只有commits大于0才说明有进展, 可以重置重试次数.
+ *  Raw模式下event已经原样写入relay log, 重连时只能从最后一个event之后继续, 不跟踪事务.
+ *  position中带有transaction_start, 持久化之后再次启动时会先回退到事务开头;
+ *  没有该信息且起始位置落在事务中间时(没有GTID/BEGIN就收到了rows/XID),
+ *  在事务结束之前in_partial_transaction()返回true, parser按PartialTransactionPolicy处理这部分event
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct PositionTracker {
+    position: EntryPosition,
+    transaction_start: Option<EntryPosition>,
+    // 是否收到了BEGIN, 此时事务内的query不会结束事务
+    explicit: bool,
+    // 启动位置落在事务中间, 当前收到的是没有事务头的后半段
+    partial: bool,
+    transactional: bool,
+    sequence: u64,
+    commits: u64,
+}
+
+impl PositionTracker {
+    pub fn new(position: EntryPosition) -> PositionTracker {
+        PositionTracker {
+            position: position.transaction_begin(),
+            transaction_start: None,
+            explicit: false,
+            partial: false,
+            transactional: true,
+            sequence: 0,
+            commits: 0,
+        }
+    }
+
+    // raw模式下relay log中已经写入了事务的前半段, 不能回退
+    pub fn raw(mut position: EntryPosition) -> PositionTracker {
+        position.set_transaction_start(None);
+        PositionTracker { position, transactional: false, ..PositionTracker::new(EntryPosition::default()) }
+    }
+
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+
+    pub fn transaction_start(&self) -> Option<&EntryPosition> {
+        self.transaction_start.as_ref()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction_start.is_some()
+    }
+
+    pub fn in_partial_transaction(&self) -> bool {
+        self.partial
+    }
+
+    // 在update之前判断event是否属于没有事务头的后半段, 包括结束该事务的XID/COMMIT
+    pub fn is_partial(&self, event: &LogEvent) -> bool {
+        let header = event.header();
+        if !self.transactional || header.log_pos() == 0 || header.is_artificial() {
+            return false;
+        }
+        match event {
+            LogEvent::Query(query) => {
+                let sql = query.query().trim();
+                self.partial && (sql.eq_ignore_ascii_case("COMMIT") || sql.eq_ignore_ascii_case("ROLLBACK"))
+            }
+            _ => match header.event_type() {
+                Some(kind) if kind.is_gtid() => false,
+                Some(kind) if kind.is_rows() || kind == EventType::TableMapEvent => self.partial || !self.in_transaction(),
+                _ => self.partial,
+            },
+        }
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn commits(&self) -> u64 {
+        self.commits
+    }
+
+    // 下一次dump的起始位置
+    pub fn restart_position(&self) -> EntryPosition {
+        self.position.transaction_begin()
+    }
+
+    // 重新dump之前调用, 位点回退到事务开头, 未结束的事务会被完整地重新接收
+    pub fn restart(&mut self) -> EntryPosition {
+        self.position = self.restart_position();
+        self.end_transaction();
+        self.partial = false;
+        self.sequence = 0;
+        self.commits = 0;
+        self.position.clone()
+    }
+
+    pub fn update(&mut self, event: &LogEvent) {
+        self.sequence += 1;
+        let header = event.header();
+        if let LogEvent::Rotate(rotate) = event {
+            // fake rotate的文件名与当前一致时说明是dump开始时的通知, 位点保持不变
+            if rotate.is_fake() && rotate.filename() == self.position.journal_name() {
+                return;
+            }
+            self.position = EntryPosition::new(rotate.filename(), rotate.position());
+            self.end_transaction();
+            self.commits += 1;
+            return;
+        }
+        if header.log_pos() == 0 || header.is_artificial() {
+            return;
+        }
+        if self.transactional {
+            self.update_transaction(event);
+        } else if !matches!(event, LogEvent::FormatDescription(_)) {
+            // raw模式下从最后一个event之后继续, 每个event都是进展
+            self.commits += 1;
+        }
+        self.position.set_transaction_start(self.transaction_start.as_ref().map(|start| start.position()));
+        self.position.set_position(header.log_pos() as u64);
+        self.position.set_timestamp(header.when() as i64 * 1000);
+        self.position.set_server_id(header.server_id());
+    }
+
+    fn update_transaction(&mut self, event: &LogEvent) {
+        let mut start = self.position.clone();
+        start.set_transaction_start(None);
+        match event {
+            LogEvent::Query(query) => {
+                let sql = query.query().trim();
+                let commit = sql.eq_ignore_ascii_case("COMMIT") || sql.eq_ignore_ascii_case("ROLLBACK");
+                if sql.eq_ignore_ascii_case("BEGIN") {
+                    self.transaction_start.get_or_insert(start);
+                    self.explicit = true;
+                    self.partial = false;
+                } else if commit && !self.in_transaction() {
+                    self.partial = false;
+                    self.commits += 1;
+                } else if !self.explicit || commit {
+                    // 没有BEGIN的query(DDL)是隐式提交的, 本身就构成一个完整的事务
+                    self.end_transaction();
+                    self.commits += 1;
+                }
+            }
+            _ => match event.header().event_type() {
+                Some(kind) if kind.is_gtid() => {
+                    self.transaction_start = Some(start);
+                    self.explicit = false;
+                    self.partial = false;
+                }
+                Some(EventType::XidEvent) | Some(EventType::XaPrepareLogEvent) => {
+                    self.partial = false;
+                    self.end_transaction();
+                    self.commits += 1;
+                }
+                Some(kind) if (kind.is_rows() || kind == EventType::TableMapEvent) && !self.in_transaction() => {
+                    if !self.partial {
+                        println!("position {}:{} is inside a transaction without its begin, \
+                                  events until the next commit are a partial transaction",
+                                 self.position.journal_name(), self.position.position());
+                    }
+                    self.partial = true;
+                }
+                _ => {}
+            },
+        }
+    }
+
+    fn end_transaction(&mut self) {
+        self.transaction_start = None;
+        self.explicit = false;
+    }
+}
+
+// 启动位置落在事务中间时, 事务后半段的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialTransactionPolicy {
+    // 不投递, 下游不会收到缺少前半段的事务
+    #[default]
+    Drop,
+    // 照常投递, 由下游处理没有TransactionBegin的事务
+    Deliver,
+}
+
+impl PartialTransactionPolicy {
+    pub fn from_name(name: &str) -> Result<PartialTransactionPolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "drop" => Ok(PartialTransactionPolicy::Drop),
+            "deliver" => Ok(PartialTransactionPolicy::Deliver),
+            _ => Err(format!("unknown partial transaction policy {}, expect drop/deliver", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PartialTransactionPolicy::Drop => "drop",
+            PartialTransactionPolicy::Deliver => "deliver",
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::config::Properties;
+use crate::filter::RegexFilter;
+
+// gh-ost的_t_gho/_t_ghc/_t_del, pt-osc的_t_new/_t_old, mysql ALTER/OPTIMIZE时的#sql-*/#sql2-*
+pub const DEFAULT_GHOST_TABLES: &str = r".*\._.+_(gho|ghc|del|new|old),.*\.#sql.*";
+
+// 临时表以及online DDL中间表的变更的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransientTablePolicy {
+    // 与之前的行为一致, 和普通表一样投递
+    #[default]
+    Emit,
+    // 丢弃, 不产生entry, 也不记入describe的表结构
+    Skip,
+}
+
+impl TransientTablePolicy {
+    pub fn from_name(name: &str) -> Result<TransientTablePolicy, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "emit" => Ok(TransientTablePolicy::Emit),
+            "skip" => Ok(TransientTablePolicy::Skip),
+            _ => Err(format!("unknown transient table policy {}, expect emit/skip", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransientTablePolicy::Emit => "emit",
+            TransientTablePolicy::Skip => "skip",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  识别不属于业务数据的表变更:
+ *      temporary       CREATE/DROP TEMPORARY TABLE, 只存在于一个会话中, 下游无法也不需要同步
+ *      ghost           online DDL工具的中间表(见DEFAULT_GHOST_TABLES), 包括建表/改表以及复制数据产生的rows event,
+ *                      切换时的RENAME TABLE第一个对象为原表, 不会被丢弃
+ *  ghost_tables为RegexFilter格式的schema.table, 按照实际使用的工具配置.
+ *  配置: master.temporary_table_policy=emit|skip, master.ghost_table_policy=emit|skip,
+ *       master.ghost_tables=.*\\._.+_(gho|ghc|del)
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct TransientTables {
+    temporary_policy: TransientTablePolicy,
+    ghost_policy: TransientTablePolicy,
+    ghost_tables: RegexFilter,
+    // 丢弃的entry数
+    skipped: u64,
+}
+
+impl Default for TransientTables {
+    fn default() -> Self {
+        TransientTables::new()
+    }
+}
+
+impl TransientTables {
+    pub fn new() -> TransientTables {
+        TransientTables {
+            temporary_policy: TransientTablePolicy::Emit,
+            ghost_policy: TransientTablePolicy::Emit,
+            ghost_tables: RegexFilter::new(DEFAULT_GHOST_TABLES).expect("default ghost tables pattern"),
+            skipped: 0,
+        }
+    }
+
+    // 没有配置时对应的字段保持不变
+    pub fn apply(&mut self, properties: &Properties) -> Result<(), String> {
+        if let Some(value) = properties.get("master.temporary_table_policy") {
+            self.temporary_policy = TransientTablePolicy::from_name(value)
+                .map_err(|e| format!("master.temporary_table_policy: {}", e))?;
+        }
+        if let Some(value) = properties.get("master.ghost_table_policy") {
+            self.ghost_policy = TransientTablePolicy::from_name(value)
+                .map_err(|e| format!("master.ghost_table_policy: {}", e))?;
+        }
+        if let Some(pattern) = properties.get("master.ghost_tables") {
+            self.set_ghost_tables(RegexFilter::new(pattern)?);
+        }
+        Ok(())
+    }
+
+    pub fn temporary_policy(&self) -> TransientTablePolicy {
+        self.temporary_policy
+    }
+    pub fn ghost_policy(&self) -> TransientTablePolicy {
+        self.ghost_policy
+    }
+    pub fn ghost_tables(&self) -> &RegexFilter {
+        &self.ghost_tables
+    }
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    pub fn set_temporary_policy(&mut self, policy: TransientTablePolicy) {
+        self.temporary_policy = policy;
+    }
+    pub fn set_ghost_policy(&mut self, policy: TransientTablePolicy) {
+        self.ghost_policy = policy;
+    }
+    // pattern为空的RegexFilter匹配所有表, 这里视为不识别任何中间表
+    pub fn set_ghost_tables(&mut self, ghost_tables: RegexFilter) {
+        self.ghost_tables = ghost_tables;
+    }
+
+    pub fn is_ghost(&self, schema_name: &str, table_name: &str) -> bool {
+        !table_name.is_empty() && !self.ghost_tables.pattern().is_empty()
+            && self.ghost_tables.matches(&format!("{}.{}", schema_name, table_name))
+    }
+
+    // 表的变更(DDL/rows)是否丢弃, temporary只对DDL有意义, rows event不会出现临时表
+    pub fn skip_table(&self, schema_name: &str, table_name: &str, temporary: bool) -> bool {
+        (temporary && self.temporary_policy == TransientTablePolicy::Skip)
+            || (self.ghost_policy == TransientTablePolicy::Skip && self.is_ghost(schema_name, table_name))
+    }
+
+    // 与skip_table相同, 丢弃时计数
+    pub fn skip(&mut self, schema_name: &str, table_name: &str, temporary: bool) -> bool {
+        let skip = self.skip_table(schema_name, table_name, temporary);
+        if skip {
+            self.skipped += 1;
+        }
+        skip
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::Utc;
+
+use crate::channel::mysql_socket::MysqlConnector;
+use crate::instance::ddl::SqlMode;
+
+// 影响解析结果的master变量
+pub const SNAPSHOT_VARIABLES: [&str; 10] = [
+    "version",
+    "server_id",
+    "binlog_format",
+    "binlog_row_image",
+    "gtid_mode",
+    "lower_case_table_names",
+    "character_set_server",
+    "time_zone",
+    "sql_mode",
+    "server_uuid",
+];
+
+/**
+ * <pre>
+ *  每次(重新)连接master时记录的变量快照, 用于排查主从切换之后解析结果不一致的问题,
+ *  例如新master的binlog_row_image=MINIMAL导致before image缺列, 或者time_zone不同导致timestamp偏移.
+ *  使用show variables查询, master上不存在的变量(例如5.5/MariaDB的gtid_mode)不出现在快照中
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerVariables {
+    variables: BTreeMap<String, String>,
+    // 采集时间, 毫秒
+    captured_at: i64,
+}
+
+impl ServerVariables {
+    pub fn load(connector: &mut MysqlConnector) -> Result<ServerVariables, String> {
+        let names = SNAPSHOT_VARIABLES.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", ");
+        let result = connector.query(&format!("show variables where Variable_name in ({})", names))?;
+        let mut variables: BTreeMap<String, String> = result.rows()
+            .filter(|row| row.len() >= 2)
+            .map(|row| (row[0].to_lowercase(), row[1].clone()))
+            .collect();
+        // 没有权限或者被代理拦截时, 至少保留握手包中的版本
+        if !variables.contains_key("version") && !connector.server_version().is_empty() {
+            variables.insert("version".to_string(), connector.server_version().to_string());
+        }
+        Ok(ServerVariables { variables, captured_at: Utc::now().timestamp_millis() })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(|value| value.as_str())
+    }
+
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
+
+    pub fn captured_at(&self) -> i64 {
+        self.captured_at
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.get("version")
+    }
+
+    pub fn server_id(&self) -> Option<u32> {
+        self.get("server_id").and_then(|server_id| server_id.parse().ok())
+    }
+
+    pub fn binlog_format(&self) -> Option<&str> {
+        self.get("binlog_format")
+    }
+
+    pub fn binlog_row_image(&self) -> Option<&str> {
+        self.get("binlog_row_image")
+    }
+
+    pub fn gtid_mode(&self) -> Option<&str> {
+        self.get("gtid_mode")
+    }
+
+    pub fn lower_case_table_names(&self) -> Option<&str> {
+        self.get("lower_case_table_names")
+    }
+
+    pub fn character_set_server(&self) -> Option<&str> {
+        self.get("character_set_server")
+    }
+
+    pub fn time_zone(&self) -> Option<&str> {
+        self.get("time_zone")
+    }
+
+    pub fn sql_mode(&self) -> Option<SqlMode> {
+        self.get("sql_mode").map(SqlMode::from_names)
+    }
+
+    pub fn server_uuid(&self) -> Option<&str> {
+        self.get("server_uuid")
+    }
+
+    // 与上一次连接时的快照比较, 返回 name: old -> new
+    pub fn changes(&self, previous: &ServerVariables) -> Vec<String> {
+        SNAPSHOT_VARIABLES.iter().filter_map(|name| {
+            let (old, new) = (previous.get(name), self.get(name));
+            if old == new {
+                return None;
+            }
+            Some(format!("{}: {} -> {}", name, old.unwrap_or("<none>"), new.unwrap_or("<none>")))
+        }).collect()
+    }
+}
+
+// 按SNAPSHOT_VARIABLES的顺序输出 name=value, 不存在的变量输出<none>
+impl fmt::Display for ServerVariables {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = SNAPSHOT_VARIABLES.iter()
+            .map(|name| format!("{}={}", name, self.get(name).unwrap_or("<none>")))
+            .collect::<Vec<_>>();
+        write!(f, "{}", values.join(", "))
+    }
+}
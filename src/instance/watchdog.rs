@@ -0,0 +1,425 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config::{get_duration, Properties};
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// 保留最近的stall报告数
+const MAX_STALL_REPORTS: usize = 16;
+// 等待期间检查running状态的间隔
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/**
+ * <pre>
+ *  一个阶段的进度计数, 由阶段所在的线程更新, watchdog线程读取:
+ *      received    交给该阶段的工作数, 在开始处理之前(或者上游交付时)增加
+ *      completed   处理完成的工作数, 失败时同样增加
+ *  received > completed表示该阶段有待处理的数据
+ * </pre>
+ */
+#[derive(Debug, Default)]
+pub struct StageProgress {
+    received: AtomicU64,
+    completed: AtomicU64,
+}
+
+impl StageProgress {
+    pub fn receive(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn complete(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn pending(&self) -> u64 {
+        self.received().saturating_sub(self.completed())
+    }
+
+    // 阶段异常退出(例如?提前返回)之后丢弃没有完成的工作, 避免重连期间被误判为stall
+    pub fn settle(&self) {
+        self.received.store(self.completed(), Ordering::Relaxed);
+    }
+}
+
+// MysqlEventParser各阶段的进度, clone之后共享同一组计数
+#[derive(Debug, Clone, Default)]
+pub struct PipelineProgress {
+    fetch: Arc<StageProgress>,
+    decode: Arc<StageProgress>,
+    sink: Arc<StageProgress>,
+}
+
+impl PipelineProgress {
+    pub fn new() -> PipelineProgress {
+        PipelineProgress::default()
+    }
+
+    pub fn fetch(&self) -> &Arc<StageProgress> {
+        &self.fetch
+    }
+    pub fn decode(&self) -> &Arc<StageProgress> {
+        &self.decode
+    }
+    pub fn sink(&self) -> &Arc<StageProgress> {
+        &self.sink
+    }
+
+    pub fn stages(&self) -> Vec<(&'static str, Arc<StageProgress>)> {
+        vec![("fetch", self.fetch.clone()), ("decode", self.decode.clone()), ("sink", self.sink.clone())]
+    }
+
+    pub fn settle(&self) {
+        self.fetch.settle();
+        self.decode.settle();
+        self.sink.settle();
+    }
+}
+
+// 记录sink阶段的进度, on_event阻塞时该阶段保持有待处理的数据
+pub struct ProgressSink {
+    inner: Box<dyn EventSink>,
+    progress: Arc<StageProgress>,
+}
+
+impl ProgressSink {
+    pub fn new(inner: Box<dyn EventSink>, progress: Arc<StageProgress>) -> ProgressSink {
+        ProgressSink { inner, progress }
+    }
+}
+
+impl EventSink for ProgressSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.progress.receive();
+        let result = self.inner.on_event(entry);
+        self.progress.complete();
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.progress.receive();
+        let result = self.inner.flush();
+        self.progress.complete();
+        result
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
+
+/**
+ * <pre>
+ *  watchdog的配置, 从配置中读取时以master.watchdog.stall_timeout开启:
+ *      master.watchdog.stall_timeout   有待处理的数据但是completed没有变化超过该时间时判断为stall
+ *      master.watchdog.check_interval  检查间隔, 默认1s
+ *      master.watchdog.restart         stall时是否停止instance, 由supervisor重新启动, 默认false只输出诊断信息
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchdogConfig {
+    stall_timeout: Duration,
+    check_interval: Duration,
+    restart: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig::new(DEFAULT_STALL_TIMEOUT)
+    }
+}
+
+impl WatchdogConfig {
+    pub fn new(stall_timeout: Duration) -> WatchdogConfig {
+        WatchdogConfig { stall_timeout, check_interval: DEFAULT_CHECK_INTERVAL, restart: false }
+    }
+
+    pub fn from_properties(properties: &Properties) -> Result<Option<WatchdogConfig>, String> {
+        let stall_timeout = match get_duration(properties, "master.watchdog.stall_timeout")? {
+            Some(stall_timeout) if !stall_timeout.is_zero() => stall_timeout,
+            _ => return Ok(None),
+        };
+        let mut config = WatchdogConfig::new(stall_timeout);
+        if let Some(check_interval) = get_duration(properties, "master.watchdog.check_interval")? {
+            config.set_check_interval(check_interval);
+        }
+        if let Some(value) = properties.get("master.watchdog.restart") {
+            let restart = value.trim().parse::<bool>()
+                .map_err(|_| format!("master.watchdog.restart: invalid value {}, expect true/false", value))?;
+            config.set_restart(restart);
+        }
+        Ok(Some(config))
+    }
+
+    pub fn stall_timeout(&self) -> Duration {
+        self.stall_timeout
+    }
+    pub fn check_interval(&self) -> Duration {
+        self.check_interval
+    }
+    pub fn restart(&self) -> bool {
+        self.restart
+    }
+
+    pub fn set_stall_timeout(&mut self, stall_timeout: Duration) {
+        self.stall_timeout = stall_timeout;
+    }
+    pub fn set_check_interval(&mut self, check_interval: Duration) {
+        self.check_interval = check_interval.max(Duration::from_millis(10));
+    }
+    pub fn set_restart(&mut self, restart: bool) {
+        self.restart = restart;
+    }
+}
+
+// 一次stall, 同一个阶段恢复进度之前只报告一次
+#[derive(Debug, Clone)]
+pub struct StallReport {
+    instance: String,
+    stage: String,
+    received: u64,
+    completed: u64,
+    stalled_for: Duration,
+    diagnostics: Vec<String>,
+}
+
+impl StallReport {
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+    pub fn stage(&self) -> &str {
+        &self.stage
+    }
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+    pub fn completed(&self) -> u64 {
+        self.completed
+    }
+    pub fn stalled_for(&self) -> Duration {
+        self.stalled_for
+    }
+    pub fn diagnostics(&self) -> &Vec<String> {
+        &self.diagnostics
+    }
+
+    pub fn message(&self) -> String {
+        format!("stage {} of {} made no progress for {:?} with {} pending (received={}, completed={})",
+                self.stage, self.instance, self.stalled_for, self.received.saturating_sub(self.completed),
+                self.received, self.completed)
+    }
+}
+
+pub type StallReports = Arc<Mutex<Vec<StallReport>>>;
+
+type Diagnostics = Box<dyn Fn() -> String + Send>;
+
+struct WatchedStage {
+    name: String,
+    progress: Arc<StageProgress>,
+    last_completed: u64,
+    // 最近一次有进度或者没有待处理数据的时间
+    since: Instant,
+    reported: bool,
+}
+
+/**
+ * <pre>
+ *  监控各个阶段的进度, 用于发现并发pipeline中的死锁以及阻塞的下游:
+ *  阶段有待处理的数据(received > completed), 但completed超过stall_timeout没有变化时判断为stall,
+ *  输出各阶段的计数, 注册的状态(例如ParserStatus)以及进程中每个线程的状态(linux下为/proc中的state和wchan),
+ *  restart时通过restart handle停止instance. 卡在不检查running的阻塞调用中的线程无法被停止, 此时只有诊断信息.
+ *      let mut watchdog = Watchdog::new("example", WatchdogConfig::new(Duration::from_secs(30)));
+ *      watchdog.watch_pipeline(&parser.progress());
+ *      watchdog.add_diagnostics("parser", move || format!("{:?}", status.lock()));
+ *      let handle = watchdog.spawn(running)?;
+ * </pre>
+ */
+pub struct Watchdog {
+    name: String,
+    config: WatchdogConfig,
+    stages: Vec<WatchedStage>,
+    diagnostics: Vec<(String, Diagnostics)>,
+    restart_handles: Vec<Arc<AtomicBool>>,
+    stalls: StallReports,
+}
+
+impl Watchdog {
+    pub fn new(name: &str, config: WatchdogConfig) -> Watchdog {
+        Watchdog {
+            name: name.to_string(),
+            config,
+            stages: vec![],
+            diagnostics: vec![],
+            restart_handles: vec![],
+            stalls: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    pub fn watch(&mut self, stage: &str, progress: Arc<StageProgress>) {
+        let last_completed = progress.completed();
+        self.stages.push(WatchedStage { name: stage.to_string(), progress, last_completed, since: Instant::now(),
+                                        reported: false });
+    }
+
+    pub fn watch_pipeline(&mut self, progress: &PipelineProgress) {
+        for (stage, progress) in progress.stages() {
+            self.watch(stage, progress);
+        }
+    }
+
+    pub fn add_diagnostics<F: Fn() -> String + Send + 'static>(&mut self, name: &str, f: F) {
+        self.diagnostics.push((name.to_string(), Box::new(f)));
+    }
+
+    // restart时store(false)
+    pub fn add_restart_handle(&mut self, handle: Arc<AtomicBool>) {
+        self.restart_handles.push(handle);
+    }
+
+    // 最近的stall报告
+    pub fn stalls(&self) -> StallReports {
+        self.stalls.clone()
+    }
+
+    pub fn set_stalls(&mut self, stalls: StallReports) {
+        self.stalls = stalls;
+    }
+
+    // 检查一次, 返回本次新发现的stall
+    pub fn check(&mut self) -> Vec<StallReport> {
+        let now = Instant::now();
+        let mut stalled = vec![];
+        for stage in self.stages.iter_mut() {
+            let completed = stage.progress.completed();
+            if completed != stage.last_completed || stage.progress.pending() == 0 {
+                stage.last_completed = completed;
+                stage.since = now;
+                stage.reported = false;
+                continue;
+            }
+            let stalled_for = now.duration_since(stage.since);
+            if stage.reported || stalled_for < self.config.stall_timeout {
+                continue;
+            }
+            stage.reported = true;
+            stalled.push(StallReport {
+                instance: self.name.clone(),
+                stage: stage.name.clone(),
+                received: stage.progress.received(),
+                completed,
+                stalled_for,
+                diagnostics: vec![],
+            });
+        }
+        if stalled.is_empty() {
+            return stalled;
+        }
+        let diagnostics = self.diagnose();
+        for report in stalled.iter_mut() {
+            report.diagnostics = diagnostics.clone();
+            println!("watchdog: {}", report.message());
+        }
+        for line in diagnostics.iter() {
+            println!("watchdog: {}", line);
+        }
+        if let Ok(mut stalls) = self.stalls.lock() {
+            stalls.extend(stalled.iter().cloned());
+            let overflow = stalls.len().saturating_sub(MAX_STALL_REPORTS);
+            stalls.drain(..overflow);
+        }
+        if self.config.restart {
+            println!("watchdog: stop {} for restart", self.name);
+            for handle in self.restart_handles.iter() {
+                handle.store(false, Ordering::SeqCst);
+            }
+        }
+        stalled
+    }
+
+    // 在<name>-watchdog线程中按check_interval检查, running为false时结束
+    pub fn spawn(mut self, running: Arc<AtomicBool>) -> Result<JoinHandle<()>, String> {
+        let name = format!("{}-watchdog", self.name);
+        thread::Builder::new()
+            .name(name.clone())
+            .spawn(move || {
+                let mut next_check = Instant::now() + self.config.check_interval;
+                while running.load(Ordering::SeqCst) {
+                    let now = Instant::now();
+                    if now >= next_check {
+                        self.check();
+                        next_check = now + self.config.check_interval;
+                        continue;
+                    }
+                    thread::sleep(STOP_CHECK_INTERVAL.min(next_check - now));
+                }
+            })
+            .map_err(|e| format!("spawn {} failure: {}", name, e))
+    }
+
+    fn diagnose(&self) -> Vec<String> {
+        let mut lines = vec![];
+        for stage in self.stages.iter() {
+            lines.push(format!("stage {} received={} completed={}", stage.name, stage.progress.received(),
+                               stage.progress.completed()));
+        }
+        for (name, f) in self.diagnostics.iter() {
+            lines.push(format!("{}: {}", name, f()));
+        }
+        lines.extend(thread_states());
+        lines
+    }
+}
+
+// 进程中每个线程的名字, 状态(R/S/D...)以及阻塞所在的内核函数
+#[cfg(target_os = "linux")]
+fn thread_states() -> Vec<String> {
+    use std::fs;
+
+    let read = |path: std::path::PathBuf| fs::read_to_string(path).map(|value| value.trim().to_string()).ok();
+    let mut states = vec![];
+    let tasks = match fs::read_dir("/proc/self/task") {
+        Ok(tasks) => tasks,
+        Err(e) => return vec![format!("read /proc/self/task failure: {}", e)],
+    };
+    for task in tasks.flatten() {
+        let path = task.path();
+        let comm = read(path.join("comm")).unwrap_or_default();
+        // pid (comm) state ..., comm中可能有空格和括号
+        let state = read(path.join("stat"))
+            .and_then(|stat| stat.rsplit_once(')').and_then(|(_, rest)| rest.split_whitespace().next().map(str::to_string)))
+            .unwrap_or_else(|| "?".to_string());
+        let wchan = read(path.join("wchan")).filter(|wchan| !wchan.is_empty() && wchan != "0")
+            .unwrap_or_else(|| "-".to_string());
+        states.push(format!("thread {} ({}) state={} wchan={}", comm, task.file_name().to_string_lossy(), state, wchan));
+    }
+    states.sort();
+    states
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_states() -> Vec<String> {
+    vec![]
+}
@@ -4,6 +4,25 @@ pub mod channel;
 
 pub mod command;
 
+pub mod config;
+
+pub mod encryption;
+
+pub mod error;
+
+pub mod filter;
+
 pub mod instance;
 
+pub mod metrics;
+
+pub mod mock;
+
+pub mod protocol;
+
+pub mod sink;
+
+pub mod store;
+
+pub mod verify;
 
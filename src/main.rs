@@ -1,12 +1,218 @@
-mod channel;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::process;
+
+use mysql_binlog_parse::channel::mysql_socket::MysqlConnector;
+use mysql_binlog_parse::channel::tls::SslMode;
+use mysql_binlog_parse::instance::describe::describe_binlog_file;
+use mysql_binlog_parse::instance::offline::{binlog_files, OfflineParser};
+use mysql_binlog_parse::instance::self_test::run_self_test;
+use mysql_binlog_parse::sink::logger::LoggerSink;
+use mysql_binlog_parse::verify::dialect::{IdentifierCase, IdentifierQuote, SqlDialect, SqlTarget, NO_BACKSLASH_ESCAPES_SQL};
+use mysql_binlog_parse::verify::{TableVerifier, DEFAULT_CHUNK_SIZE};
+
+#[cfg(feature = "heap-stats")]
+#[global_allocator]
+static ALLOCATOR: mysql_binlog_parse::metrics::memory::CountingAllocator =
+    mysql_binlog_parse::metrics::memory::CountingAllocator;
+
+const USAGE: &str = "usage:
+    mini-canal verify --source user:password@host:port --target user:password@host:port
+                      --table schema.table [--chunk-size 1000] [--repair-sql]
+                      [--dialect mysql|mariadb|postgres] [--identifier-quote backtick|double-quote|none]
+                      [--identifier-case preserve|lower|upper] [--no-schema-prefix]
+                      [--ssl-mode disabled|preferred|required|verify_ca|verify_identity] [--ssl-ca path/to/ca.pem]
+    mini-canal describe --binlog path/to/mysql-bin.000001 --table schema.table
+    mini-canal parse --binlog-dir path/to/binlogs [--workers 4] [--verbose] [--self-test]
+    mini-canal self-test
+    mini-canal capture --upstream host:port --listen 127.0.0.1:3307 --output path/to/server.fixture
+                       (requires feature capture)";
 
-use crate::channel::SocketChannel;
-use crate::channel::TcpChannel;
 fn main() {
-    let mut channel = TcpChannel::new("127.0.0.1", 50001);
-    let mut buf = [0u8; 10];
-    channel.read(&mut buf);
-    println!("{}",String::from_utf8_lossy(&buf).to_string());
-    let size = channel.write("xie shu ju".as_bytes());
-    println!("{}",size.unwrap())
-}
\ No newline at end of file
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(|command| command.as_str()) {
+        Some("verify") => verify(&args[1..]),
+        Some("describe") => describe(&args[1..]),
+        Some("parse") => parse(&args[1..]),
+        Some("capture") => capture(&args[1..]),
+        Some("self-test") => self_test(),
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
+}
+
+// 比对一致返回0, 不一致返回1, 参数或执行错误返回2
+fn verify(args: &[String]) -> Result<i32, String> {
+    let options = parse_options(args)?;
+    let required = |name: &str| options.get(name).cloned().flatten()
+        .ok_or_else(|| format!("missing --{}\n{}", name, USAGE));
+    let mut source = connector(&required("source")?)?;
+    let mut target = connector(&required("target")?)?;
+    if let Some(ssl_mode) = options.get("ssl-mode").cloned().flatten() {
+        let ssl_mode = SslMode::from_name(&ssl_mode)?;
+        let ssl_ca = options.get("ssl-ca").cloned().flatten();
+        if ssl_mode.verifies_certificate() && ssl_ca.is_none() {
+            return Err(format!("--ssl-mode {} requires --ssl-ca", ssl_mode.name()));
+        }
+        for connector in [&mut source, &mut target] {
+            connector.set_ssl_mode(ssl_mode);
+            connector.set_ssl_ca(ssl_ca.as_deref());
+        }
+    }
+    let table = required("table")?;
+    let (schema, table) = table.split_once('.')
+        .ok_or_else(|| format!("--table must be schema.table, got {}", table))?;
+
+    let mut verifier = TableVerifier::new(source, target);
+    if let Some(chunk_size) = options.get("chunk-size").cloned().flatten() {
+        verifier.set_chunk_size(chunk_size.parse().map_err(|_| format!("invalid --chunk-size {}", chunk_size))?);
+    } else {
+        verifier.set_chunk_size(DEFAULT_CHUNK_SIZE);
+    }
+    verifier.set_repair(options.contains_key("repair-sql"));
+    let dialect = dialect(&options)?;
+    let target_mysql = dialect.target() != SqlTarget::Postgres;
+    verifier.set_dialect(dialect);
+
+    let report = verifier.verify(schema, table)?;
+    println!("table {}: {} chunks, source {} rows, target {} rows, {} mismatched chunks",
+             report.table(), report.chunks(), report.source_rows(), report.target_rows(), report.mismatches().len());
+    // 修复sql中的字符串按NO_BACKSLASH_ESCAPES转义, 需要先在目标库的会话中设置
+    if target_mysql && report.mismatches().iter().any(|mismatch| !mismatch.repair_sql().is_empty()) {
+        println!("{};", NO_BACKSLASH_ESCAPES_SQL);
+    }
+    for mismatch in report.mismatches() {
+        println!("mismatch range ({:?}, {:?}]: source {} rows, target {} rows",
+                 mismatch.lower(), mismatch.upper(), mismatch.source_rows(), mismatch.target_rows());
+        for sql in mismatch.repair_sql() {
+            println!("{}", sql);
+        }
+    }
+    Ok(if report.is_consistent() { 0 } else { 1 })
+}
+
+// 修复sql的方言, 先按--dialect取默认值再覆盖单独指定的选项
+fn dialect(options: &HashMap<String, Option<String>>) -> Result<SqlDialect, String> {
+    let option = |name: &str| options.get(name).cloned().flatten();
+    let mut dialect = match option("dialect") {
+        Some(target) => SqlDialect::new(SqlTarget::from_name(&target)?),
+        None => SqlDialect::default(),
+    };
+    if let Some(quote) = option("identifier-quote") {
+        dialect.set_quote(IdentifierQuote::from_name(&quote)?);
+    }
+    if let Some(case) = option("identifier-case") {
+        dialect.set_case(IdentifierCase::from_name(&case)?);
+    }
+    if options.contains_key("no-schema-prefix") {
+        dialect.set_schema_prefix(false);
+    }
+    Ok(dialect)
+}
+
+// 输出解析器从binlog文件中看到的表结构, 找不到该表的table map时返回1
+fn describe(args: &[String]) -> Result<i32, String> {
+    let options = parse_options(args)?;
+    let required = |name: &str| options.get(name).cloned().flatten()
+        .ok_or_else(|| format!("missing --{}\n{}", name, USAGE));
+    let binlog = required("binlog")?;
+    let table = required("table")?;
+    let (schema, table) = table.split_once('.')
+        .ok_or_else(|| format!("--table must be schema.table, got {}", table))?;
+    match describe_binlog_file(Path::new(&binlog), schema, table)? {
+        Some(description) => {
+            println!("{}", description.render());
+            Ok(0)
+        }
+        None => {
+            println!("no table map of {}.{} in {}", schema, table, binlog);
+            Ok(1)
+        }
+    }
+}
+
+// 并行解析目录下的所有binlog文件, 按文件顺序打印entry
+fn parse(args: &[String]) -> Result<i32, String> {
+    let options = parse_options(args)?;
+    let directory = options.get("binlog-dir").cloned().flatten()
+        .ok_or_else(|| format!("missing --binlog-dir\n{}", USAGE))?;
+    if options.contains_key("self-test") && self_test()? != 0 {
+        return Ok(1);
+    }
+    let mut parser = OfflineParser::new();
+    if let Some(workers) = options.get("workers").cloned().flatten() {
+        parser.set_workers(workers.parse().map_err(|_| format!("invalid --workers {}", workers))?);
+    }
+    let files = binlog_files(Path::new(&directory))?;
+    let mut sink = LoggerSink::new(options.contains_key("verbose"));
+    let entries = parser.parse(&files, &mut sink)?;
+    println!("parsed {} files, {} entries", files.len(), entries);
+    Ok(0)
+}
+
+// 解码内置的fixture并与期望值比较, 全部通过返回0, 否则返回1
+fn self_test() -> Result<i32, String> {
+    let report = run_self_test();
+    for failure in report.failures() {
+        println!("self test failure: {}", failure);
+    }
+    if !report.is_ok() {
+        return Ok(1);
+    }
+    println!("self test passed, {} checks", report.checked());
+    Ok(0)
+}
+
+// 录制下一个client连接与upstream之间的协议交互, 写入fixture文件
+#[cfg(feature = "capture")]
+fn capture(args: &[String]) -> Result<i32, String> {
+    use mysql_binlog_parse::mock::capture::CaptureProxy;
+
+    let options = parse_options(args)?;
+    let required = |name: &str| options.get(name).cloned().flatten()
+        .ok_or_else(|| format!("missing --{}\n{}", name, USAGE));
+    let mut proxy = CaptureProxy::new(&required("upstream")?);
+    let address = proxy.bind(&required("listen")?)?;
+    let output = required("output")?;
+    println!("capture proxy is listening on {}, connect the client to it", address);
+    let fixture = proxy.capture_one()?;
+    fixture.save(Path::new(&output))?;
+    println!("captured {} packets into {}", fixture.records().len(), output);
+    Ok(0)
+}
+
+#[cfg(not(feature = "capture"))]
+fn capture(_args: &[String]) -> Result<i32, String> {
+    Err("capture requires building with --features capture".to_string())
+}
+
+// --name value 或者不带值的 --flag
+fn parse_options(args: &[String]) -> Result<HashMap<String, Option<String>>, String> {
+    let mut options = HashMap::new();
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        let name = arg.strip_prefix("--").ok_or_else(|| format!("unexpected argument {}\n{}", arg, USAGE))?;
+        let value = match iter.peek() {
+            Some(next) if !next.starts_with("--") => iter.next().cloned(),
+            _ => None,
+        };
+        options.insert(name.to_string(), value);
+    }
+    Ok(options)
+}
+
+// user:password@host:port
+fn connector(spec: &str) -> Result<MysqlConnector, String> {
+    let (credential, address) = spec.rsplit_once('@').ok_or_else(|| format!("invalid connection {}", spec))?;
+    let (username, password) = credential.split_once(':').unwrap_or((credential, ""));
+    let (host, port) = address.rsplit_once(':').unwrap_or((address, "3306"));
+    let port = port.parse().map_err(|_| format!("invalid port in {}", spec))?;
+    Ok(MysqlConnector::new(host, port, username, password, ""))
+}
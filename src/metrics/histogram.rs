@@ -0,0 +1,123 @@
+use std::fmt::Write;
+
+use crate::instance::EntryPosition;
+
+// 桶的上界, 毫秒
+pub const DEFAULT_BUCKETS: [u64; 14] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000, 60000];
+
+// 桶内最近一次观测值及其位点, 用于从延迟定位到具体的binlog位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exemplar {
+    value: u64,
+    position: EntryPosition,
+}
+
+impl Exemplar {
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+}
+
+/**
+ * <pre>
+ *  累计型直方图, 记录从master提交(event header中的timestamp)到sink确认的延迟, 单位毫秒.
+ *  counts[i]为落在(bounds[i-1], bounds[i]]内的数量, 最后一个为+Inf,
+ *  每个桶保留一个exemplar. 导出格式为OpenMetrics, exemplar以journal/position作为label
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    bounds: Vec<u64>,
+    counts: Vec<u64>,
+    exemplars: Vec<Option<Exemplar>>,
+    sum: u64,
+    count: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram::new(DEFAULT_BUCKETS.to_vec())
+    }
+}
+
+impl LatencyHistogram {
+    pub fn new(mut bounds: Vec<u64>) -> LatencyHistogram {
+        bounds.sort_unstable();
+        bounds.dedup();
+        let buckets = bounds.len() + 1;
+        LatencyHistogram { bounds, counts: vec![0; buckets], exemplars: vec![None; buckets], sum: 0, count: 0 }
+    }
+
+    pub fn record(&mut self, latency: u64, position: &EntryPosition) {
+        let index = self.bounds.partition_point(|bound| *bound < latency);
+        self.counts[index] += 1;
+        self.exemplars[index] = Some(Exemplar { value: latency, position: position.clone() });
+        self.sum += latency;
+        self.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+    pub fn bounds(&self) -> &Vec<u64> {
+        &self.bounds
+    }
+    pub fn counts(&self) -> &Vec<u64> {
+        &self.counts
+    }
+    pub fn exemplars(&self) -> &Vec<Option<Exemplar>> {
+        &self.exemplars
+    }
+
+    // 返回分位数所在桶的上界, 落在+Inf桶时返回该桶exemplar的值
+    pub fn percentile(&self, quantile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((self.count as f64) * quantile.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return match self.bounds.get(index) {
+                    Some(bound) => Some(*bound),
+                    None => self.exemplars[index].as_ref().map(|exemplar| exemplar.value),
+                };
+            }
+        }
+        None
+    }
+
+    pub fn reset(&mut self) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.exemplars.iter_mut().for_each(|exemplar| *exemplar = None);
+        self.sum = 0;
+        self.count = 0;
+    }
+
+    pub fn to_open_metrics(&self, name: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let _ = writeln!(out, "# UNIT {} milliseconds", name);
+        let mut cumulative = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            let le = self.bounds.get(index).map(|bound| bound.to_string()).unwrap_or_else(|| "+Inf".to_string());
+            let _ = write!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+            if let Some(exemplar) = &self.exemplars[index] {
+                let _ = write!(out, " # {{journal=\"{}\",position=\"{}\"}} {}",
+                               exemplar.position.journal_name(), exemplar.position.position(), exemplar.value);
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+        out
+    }
+}
@@ -0,0 +1,201 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::sink::mq::flat_message::json_string;
+
+pub const DEFAULT_MEMORY_METRIC_PREFIX: &str = "mini_canal_memory";
+
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/**
+ * <pre>
+ *  统计已分配字节数的全局分配器, 实际的分配交给System. 需要在binary中安装:
+ *      #[global_allocator]
+ *      static ALLOCATOR: CountingAllocator = CountingAllocator;
+ *  mini-canal在feature heap-stats下安装, 每次分配多一次原子加减
+ * </pre>
+ */
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+            record_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+fn record_alloc(size: usize) {
+    ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    if !INSTALLED.load(Ordering::Relaxed) {
+        INSTALLED.store(true, Ordering::Relaxed);
+    }
+}
+
+// 当前已分配(未释放)的堆内存, 没有安装CountingAllocator时返回None
+pub fn allocated_bytes() -> Option<u64> {
+    INSTALLED.load(Ordering::Relaxed).then(|| ALLOCATED.load(Ordering::Relaxed))
+}
+
+// 进程的常驻内存(RSS), 读取/proc/self/statm, 其它平台返回None
+#[cfg(target_os = "linux")]
+pub fn resident_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    (page_size > 0).then(|| pages * page_size as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn resident_bytes() -> Option<u64> {
+    None
+}
+
+/**
+ * <pre>
+ *  一个子系统持有的内存字节数(估计值), 由子系统在增加和释放数据时自己维护.
+ *  同名的gauge是同一个计数, 多个instance的同一个子系统累加在一起
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct MemoryGauge {
+    bytes: Arc<AtomicU64>,
+}
+
+impl MemoryGauge {
+    pub fn new() -> MemoryGauge {
+        MemoryGauge::default()
+    }
+
+    pub fn get(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn add(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    // 不会小于0
+    pub fn sub(&self, bytes: usize) {
+        let _ = self.bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed,
+                                        |value| Some(value.saturating_sub(bytes as u64)));
+    }
+
+    // 子系统的字节数从from变为to
+    pub fn adjust(&self, from: usize, to: usize) {
+        if to > from {
+            self.add(to - from);
+        } else {
+            self.sub(from - to);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemorySnapshot {
+    pub resident: Option<u64>,
+    pub allocated: Option<u64>,
+    // 子系统名 -> 字节数
+    pub subsystems: BTreeMap<String, u64>,
+}
+
+/**
+ * <pre>
+ *  进程内存的统计, 用于在OOM之前发现长时间运行时缓慢的泄漏:
+ *      resident        进程的RSS
+ *      allocated       CountingAllocator统计的堆内存, 没有安装时为null
+ *      subsystems      各子系统自己维护的gauge, 例如store(EntryStore中的entry), relay(relay log的写缓冲),
+ *                      table_map_cache(解析过的table map), replay_cache(未确认的批次)
+ *  allocated持续增长而subsystems不变时, 说明泄漏不在这些缓存中.
+ *  admin的GET /memory返回to_json(), to_open_metrics()用于prometheus
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStats {
+    gauges: Arc<Mutex<BTreeMap<String, MemoryGauge>>>,
+}
+
+impl MemoryStats {
+    pub fn new() -> MemoryStats {
+        MemoryStats::default()
+    }
+
+    // 没有时创建
+    pub fn gauge(&self, name: &str) -> MemoryGauge {
+        match self.gauges.lock() {
+            Ok(mut gauges) => gauges.entry(name.to_string()).or_default().clone(),
+            Err(_) => MemoryGauge::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> MemorySnapshot {
+        let subsystems = match self.gauges.lock() {
+            Ok(gauges) => gauges.iter().map(|(name, gauge)| (name.clone(), gauge.get())).collect(),
+            Err(_) => BTreeMap::new(),
+        };
+        MemorySnapshot { resident: resident_bytes(), allocated: allocated_bytes(), subsystems }
+    }
+
+    pub fn to_json(&self) -> String {
+        let snapshot = self.snapshot();
+        let optional = |value: Option<u64>| value.map_or("null".to_string(), |value| value.to_string());
+        let mut out = String::new();
+        let _ = write!(out, "{{\"resident_bytes\":{},\"allocated_bytes\":{},\"subsystems\":{{",
+                       optional(snapshot.resident), optional(snapshot.allocated));
+        for (i, (name, bytes)) in snapshot.subsystems.iter().enumerate() {
+            let _ = write!(out, "{}{}:{}", if i > 0 { "," } else { "" }, json_string(name), bytes);
+        }
+        out.push_str("}}");
+        out
+    }
+
+    pub fn to_open_metrics(&self, prefix: &str) -> String {
+        let snapshot = self.snapshot();
+        let mut out = String::new();
+        for (name, value) in [("resident", snapshot.resident), ("allocated", snapshot.allocated)] {
+            if let Some(value) = value {
+                let _ = writeln!(out, "# TYPE {}_{}_bytes gauge", prefix, name);
+                let _ = writeln!(out, "{}_{}_bytes {}", prefix, name, value);
+            }
+        }
+        let _ = writeln!(out, "# TYPE {}_subsystem_bytes gauge", prefix);
+        for (name, bytes) in snapshot.subsystems.iter() {
+            let _ = writeln!(out, "{}_subsystem_bytes{{subsystem=\"{}\"}} {}", prefix, name, bytes);
+        }
+        out
+    }
+}
+
+// 进程内所有instance共享的统计
+pub fn global() -> &'static MemoryStats {
+    static GLOBAL: OnceLock<MemoryStats> = OnceLock::new();
+    GLOBAL.get_or_init(MemoryStats::new)
+}
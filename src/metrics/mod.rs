@@ -0,0 +1,12 @@
+pub mod histogram;
+
+pub mod memory;
+
+pub mod network;
+
+pub mod rate;
+
+pub use histogram::LatencyHistogram;
+pub use memory::{MemoryGauge, MemorySnapshot, MemoryStats};
+pub use network::{ChannelStats, IoErrorClass};
+pub use rate::{RateKind, RateMeter, StreamMetrics};
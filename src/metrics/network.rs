@@ -0,0 +1,182 @@
+use std::fmt::Write;
+use std::io::{Error, ErrorKind};
+
+use crate::sink::mq::flat_message::json_string;
+
+// 网络错误的分类, 用于区分"网络抖动"的具体表现
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoErrorClass {
+    // 读写超时, 包括socket read timeout返回的WouldBlock
+    Timeout,
+    // 连接被对端或者中间设备重置
+    Reset,
+    // 对端关闭连接, 读到的数据不完整
+    Eof,
+    // 连接被拒绝, 通常是master没有启动或者端口错误
+    Refused,
+    // 地址解析失败或者网络不可达
+    Unreachable,
+    Other,
+}
+
+impl IoErrorClass {
+    pub const ALL: [IoErrorClass; 6] = [IoErrorClass::Timeout, IoErrorClass::Reset, IoErrorClass::Eof, IoErrorClass::Refused,
+                                        IoErrorClass::Unreachable, IoErrorClass::Other];
+
+    pub fn classify(error: &Error) -> IoErrorClass {
+        match error.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => IoErrorClass::Timeout,
+            ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe
+            | ErrorKind::NotConnected => IoErrorClass::Reset,
+            ErrorKind::UnexpectedEof | ErrorKind::WriteZero => IoErrorClass::Eof,
+            ErrorKind::ConnectionRefused => IoErrorClass::Refused,
+            ErrorKind::NotFound | ErrorKind::AddrNotAvailable | ErrorKind::HostUnreachable
+            | ErrorKind::NetworkUnreachable | ErrorKind::NetworkDown => IoErrorClass::Unreachable,
+            _ => IoErrorClass::Other,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<IoErrorClass> {
+        IoErrorClass::ALL.into_iter().find(|class| class.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IoErrorClass::Timeout => "timeout",
+            IoErrorClass::Reset => "reset",
+            IoErrorClass::Eof => "eof",
+            IoErrorClass::Refused => "refused",
+            IoErrorClass::Unreachable => "unreachable",
+            IoErrorClass::Other => "other",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  TcpChannel的读写统计, 用于判断延迟是否由网络引起:
+ *      bytes_in/bytes_out      读写的字节数
+ *      reads/writes            系统调用次数
+ *      short_reads             返回的字节数少于请求的读, 大量出现说明数据是分多次到达的
+ *      errors                  按IoErrorClass分类的读写以及连接错误, timeouts/resets分别是其中的timeout/reset
+ *      connects                成功建立的连接数, connect_failures为失败的连接数
+ *  对端正常关闭(read返回0)记为eof. MysqlConnector在每次连接时把句柄传给新的TcpChannel,
+ *  因此重连之后继续累加, fork出来的connector使用同一个句柄
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    connects: u64,
+    connect_failures: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    reads: u64,
+    writes: u64,
+    short_reads: u64,
+    errors: [u64; 6],
+}
+
+impl ChannelStats {
+    pub fn new() -> ChannelStats {
+        ChannelStats::default()
+    }
+
+    pub fn connects(&self) -> u64 {
+        self.connects
+    }
+    pub fn connect_failures(&self) -> u64 {
+        self.connect_failures
+    }
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in
+    }
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out
+    }
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+    pub fn writes(&self) -> u64 {
+        self.writes
+    }
+    pub fn short_reads(&self) -> u64 {
+        self.short_reads
+    }
+    pub fn errors(&self, class: IoErrorClass) -> u64 {
+        self.errors[class as usize]
+    }
+    pub fn timeouts(&self) -> u64 {
+        self.errors(IoErrorClass::Timeout)
+    }
+    pub fn resets(&self) -> u64 {
+        self.errors(IoErrorClass::Reset)
+    }
+    pub fn total_errors(&self) -> u64 {
+        self.errors.iter().sum()
+    }
+
+    pub fn record_connect(&mut self) {
+        self.connects += 1;
+    }
+
+    pub fn record_connect_failure(&mut self, error: &Error) -> IoErrorClass {
+        self.connect_failures += 1;
+        self.record_error(error)
+    }
+
+    // requested为buf的长度, size为read的返回值
+    pub fn record_read(&mut self, requested: usize, size: usize) {
+        self.reads += 1;
+        self.bytes_in += size as u64;
+        if size == 0 && requested > 0 {
+            self.errors[IoErrorClass::Eof as usize] += 1;
+        } else if size < requested {
+            self.short_reads += 1;
+        }
+    }
+
+    pub fn record_write(&mut self, size: usize) {
+        self.writes += 1;
+        self.bytes_out += size as u64;
+    }
+
+    pub fn record_error(&mut self, error: &Error) -> IoErrorClass {
+        let class = IoErrorClass::classify(error);
+        self.errors[class as usize] += 1;
+        class
+    }
+
+    /**
+     * <pre>
+     *  {"connects":1,"connect_failures":0,"bytes_in":1024,"bytes_out":64,"reads":10,"writes":3,"short_reads":2,
+     *   "errors":{"timeout":0,"reset":0,"eof":0,"refused":0,"unreachable":0,"other":0}}
+     * </pre>
+     */
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"connects\":{},\"connect_failures\":{},\"bytes_in\":{},\"bytes_out\":{},\"reads\":{},\
+                             \"writes\":{},\"short_reads\":{},\"errors\":{{",
+                       self.connects, self.connect_failures, self.bytes_in, self.bytes_out, self.reads, self.writes,
+                       self.short_reads);
+        for (i, class) in IoErrorClass::ALL.iter().enumerate() {
+            let _ = write!(out, "{}{}:{}", if i > 0 { "," } else { "" }, json_string(class.name()), self.errors(*class));
+        }
+        out.push_str("}}");
+        out
+    }
+
+    pub fn to_open_metrics(&self, prefix: &str) -> String {
+        let mut out = String::new();
+        for (name, value) in [("connects", self.connects), ("connect_failures", self.connect_failures),
+                              ("bytes_in", self.bytes_in), ("bytes_out", self.bytes_out), ("reads", self.reads),
+                              ("writes", self.writes), ("short_reads", self.short_reads)] {
+            let _ = writeln!(out, "# TYPE {}_{} counter", prefix, name);
+            let _ = writeln!(out, "{}_{}_total {}", prefix, name, value);
+        }
+        let _ = writeln!(out, "# TYPE {}_io_errors counter", prefix);
+        for class in IoErrorClass::ALL {
+            let _ = writeln!(out, "{}_io_errors_total{{class=\"{}\"}} {}", prefix, class.name(), self.errors(class));
+        }
+        out
+    }
+}
@@ -0,0 +1,199 @@
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// EWMA的更新周期
+pub const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+struct Ewma {
+    alpha: f64,
+    rate: Option<f64>,
+}
+
+impl Ewma {
+    // window秒的指数加权移动平均, 每个TICK_INTERVAL更新一次
+    fn new(window: Duration) -> Ewma {
+        Ewma { alpha: 1.0 - (-TICK_INTERVAL.as_secs_f64() / window.as_secs_f64()).exp(), rate: None }
+    }
+
+    fn update(&mut self, instant: f64) {
+        self.rate = Some(match self.rate {
+            Some(rate) => rate + self.alpha * (instant - rate),
+            None => instant,
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateSnapshot {
+    pub count: u64,
+    // 最近一个完整周期内的速率, 每秒
+    pub instant: f64,
+    pub one_minute: f64,
+    pub five_minute: f64,
+    pub fifteen_minute: f64,
+}
+
+/**
+ * <pre>
+ *  与dropwizard Meter相同的速率统计:
+ *  mark()只累加计数, 读取或者下一次mark时按TICK_INTERVAL补齐经过的周期,
+ *  每个周期把周期内的计数换算为每秒速率, 分别更新1/5/15分钟的EWMA
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct RateMeter {
+    count: u64,
+    uncounted: u64,
+    last_tick: Instant,
+    instant: f64,
+    ewmas: [Ewma; 3],
+}
+
+impl Default for RateMeter {
+    fn default() -> Self {
+        RateMeter::new(Instant::now())
+    }
+}
+
+impl RateMeter {
+    pub fn new(now: Instant) -> RateMeter {
+        RateMeter {
+            count: 0,
+            uncounted: 0,
+            last_tick: now,
+            instant: 0.0,
+            ewmas: [
+                Ewma::new(Duration::from_secs(60)),
+                Ewma::new(Duration::from_secs(5 * 60)),
+                Ewma::new(Duration::from_secs(15 * 60)),
+            ],
+        }
+    }
+
+    pub fn mark(&mut self, n: u64) {
+        self.mark_at(n, Instant::now());
+    }
+
+    pub fn mark_at(&mut self, n: u64, now: Instant) {
+        self.tick_if_necessary(now);
+        self.count += n;
+        self.uncounted += n;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn snapshot(&mut self) -> RateSnapshot {
+        self.snapshot_at(Instant::now())
+    }
+
+    pub fn snapshot_at(&mut self, now: Instant) -> RateSnapshot {
+        self.tick_if_necessary(now);
+        RateSnapshot {
+            count: self.count,
+            instant: self.instant,
+            one_minute: self.ewmas[0].rate.unwrap_or(0.0),
+            five_minute: self.ewmas[1].rate.unwrap_or(0.0),
+            fifteen_minute: self.ewmas[2].rate.unwrap_or(0.0),
+        }
+    }
+
+    fn tick_if_necessary(&mut self, now: Instant) {
+        while now.saturating_duration_since(self.last_tick) >= TICK_INTERVAL {
+            self.instant = self.uncounted as f64 / TICK_INTERVAL.as_secs_f64();
+            self.uncounted = 0;
+            for ewma in self.ewmas.iter_mut() {
+                ewma.update(self.instant);
+            }
+            self.last_tick += TICK_INTERVAL;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateKind {
+    BytesFetched,
+    EventsDecoded,
+    RowsEmitted,
+    Acks,
+    // GTID跳号缺失的事务数
+    GtidGaps,
+    // DedupSink丢弃的重复entry数
+    Duplicates,
+}
+
+impl RateKind {
+    pub const ALL: [RateKind; 6] = [RateKind::BytesFetched, RateKind::EventsDecoded, RateKind::RowsEmitted, RateKind::Acks,
+                                   RateKind::GtidGaps, RateKind::Duplicates];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RateKind::BytesFetched => "bytes_fetched",
+            RateKind::EventsDecoded => "events_decoded",
+            RateKind::RowsEmitted => "rows_emitted",
+            RateKind::Acks => "acks",
+            RateKind::GtidGaps => "gtid_gaps",
+            RateKind::Duplicates => "duplicates",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  一条binlog流的速率: 拉取的字节数, 解析的event数, 产生的行数, sink确认数, GTID跳号缺失的事务数以及去重丢弃的entry数.
+ *  每个parser持有自己的StreamMetrics, 同时累加到进程级别的global()中
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct StreamMetrics {
+    meters: [RateMeter; 6],
+}
+
+impl StreamMetrics {
+    pub fn new() -> StreamMetrics {
+        StreamMetrics::default()
+    }
+
+    pub fn mark(&mut self, kind: RateKind, n: u64) {
+        self.meters[kind as usize].mark(n);
+    }
+
+    pub fn snapshot(&mut self, kind: RateKind) -> RateSnapshot {
+        self.meters[kind as usize].snapshot()
+    }
+
+    pub fn to_open_metrics(&mut self, prefix: &str) -> String {
+        let mut out = String::new();
+        for kind in RateKind::ALL {
+            let name = format!("{}_{}", prefix, kind.name());
+            let snapshot = self.snapshot(kind);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{}_total {}", name, snapshot.count);
+            let _ = writeln!(out, "# TYPE {}_rate gauge", name);
+            let _ = writeln!(out, "{}_rate{{window=\"instant\"}} {}", name, snapshot.instant);
+            let _ = writeln!(out, "{}_rate{{window=\"1m\"}} {}", name, snapshot.one_minute);
+            let _ = writeln!(out, "{}_rate{{window=\"5m\"}} {}", name, snapshot.five_minute);
+            let _ = writeln!(out, "{}_rate{{window=\"15m\"}} {}", name, snapshot.fifteen_minute);
+        }
+        out
+    }
+}
+
+// 进程内所有binlog流的汇总
+pub fn global() -> &'static Mutex<StreamMetrics> {
+    static GLOBAL: OnceLock<Mutex<StreamMetrics>> = OnceLock::new();
+    GLOBAL.get_or_init(|| Mutex::new(StreamMetrics::new()))
+}
+
+// 同时记录到某条流以及全局
+pub fn mark(metrics: &Mutex<StreamMetrics>, kind: RateKind, n: u64) {
+    if let Ok(mut metrics) = metrics.lock() {
+        metrics.mark(kind, n);
+    }
+    if let Ok(mut metrics) = global().lock() {
+        metrics.mark(kind, n);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::command::event::column_type::MYSQL_TYPE_LONG;
+use crate::command::event::event_type::ENUM_END_EVENT;
+use crate::command::event::{checksum, event_flag, EventType, LOG_HEADER_LEN};
+
+pub const MOCK_BINLOG_FILE: &str = "mysql-bin.000001";
+pub const MOCK_SCHEMA: &str = "mock";
+pub const MOCK_TABLE: &str = "t";
+pub const MOCK_TABLE_ID: u64 = 100;
+
+// binlog文件开头magic number之后的位置
+const FIRST_EVENT_POSITION: u64 = 4;
+
+/**
+ * <pre>
+ *  MockMaster使用的确定性binlog, 文件内容只由参数决定, 任意一次dump都能得到相同的event:
+ *      4               FORMAT_DESCRIPTION_EVENT
+ *      ...             事务0, 事务1, ...
+ *  每个事务都是 BEGIN, TABLE_MAP(mock.t, 一个int列), WRITE_ROWS(id = 序号 + 1), XID,
+ *  设置了server_uuid时事务开头还有GTID_LOG_EVENT(server_uuid:序号 + 1), 设置gtid_gap之后的事务gno向后跳.
+ *  长度固定, 因此可以由位点直接算出事务的序号
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct MockBinlog {
+    server_id: u32,
+    server_version: String,
+    with_checksum: bool,
+    timestamp: u32,
+    server_uuid: Option<[u8; 16]>,
+    // (事务序号, 跳过的gno数)
+    gtid_gap: Option<(u64, u64)>,
+}
+
+impl MockBinlog {
+    pub fn new(server_id: u32, server_version: &str, with_checksum: bool) -> MockBinlog {
+        MockBinlog {
+            server_id,
+            server_version: server_version.to_string(),
+            with_checksum,
+            timestamp: 1_700_000_000,
+            server_uuid: None,
+            gtid_gap: None,
+        }
+    }
+
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+
+    pub fn server_version(&self) -> &str {
+        &self.server_version
+    }
+
+    pub fn with_checksum(&self) -> bool {
+        self.with_checksum
+    }
+
+    pub fn server_uuid(&self) -> Option<&[u8; 16]> {
+        self.server_uuid.as_ref()
+    }
+
+    // 开启gtid_mode, 第index个事务的gtid为server_uuid:index + 1
+    pub fn set_server_uuid(&mut self, server_uuid: Option<[u8; 16]>) {
+        self.server_uuid = server_uuid;
+    }
+
+    // 模拟主从切换丢失事务: 从第index个事务开始gno多加missing, 中间的gno不会出现在binlog中
+    pub fn set_gtid_gap(&mut self, index: u64, missing: u64) {
+        self.gtid_gap = Some((index, missing));
+    }
+
+    // 第index个事务的gno
+    pub fn gno(&self, index: u64) -> u64 {
+        match self.gtid_gap {
+            Some((gap, missing)) if index >= gap => index + 1 + missing,
+            _ => index + 1,
+        }
+    }
+
+    // log_pos为0时client不会据此更新位点, 从文件中间开始dump时使用
+    pub fn format_description(&self, log_pos: Option<u32>) -> Vec<u8> {
+        let body = self.format_description_body();
+        let end = FIRST_EVENT_POSITION as u32 + self.event_len(&body) as u32;
+        self.event(EventType::FormatDescriptionEvent, &body, log_pos.unwrap_or(end), 0)
+    }
+
+    // dump开始时master伪造的rotate
+    pub fn fake_rotate(&self, position: u64) -> Vec<u8> {
+        let mut body = position.to_le_bytes().to_vec();
+        body.extend_from_slice(MOCK_BINLOG_FILE.as_bytes());
+        self.event(EventType::RotateEvent, &body, 0, event_flag::LOG_EVENT_ARTIFICIAL_F)
+    }
+
+    pub fn first_transaction_position(&self) -> u64 {
+        FIRST_EVENT_POSITION + self.event_len(&self.format_description_body()) as u64
+    }
+
+    pub fn transaction_len(&self) -> u64 {
+        self.transaction_bodies(0).iter().map(|(_, body)| self.event_len(body) as u64).sum()
+    }
+
+    pub fn transaction_position(&self, index: u64) -> u64 {
+        self.first_transaction_position() + index * self.transaction_len()
+    }
+
+    // 位点不在事务边界上时返回None
+    pub fn transaction_index(&self, position: u64) -> Option<u64> {
+        let offset = position.checked_sub(self.first_transaction_position())?;
+        if offset % self.transaction_len() != 0 {
+            return None;
+        }
+        Some(offset / self.transaction_len())
+    }
+
+    // 第index个事务的所有event, log_pos为各个event在文件中的结束位置
+    pub fn transaction(&self, index: u64) -> Vec<Vec<u8>> {
+        let mut position = self.transaction_position(index) as u32;
+        let mut events = vec![];
+        for (kind, body) in self.transaction_bodies(index) {
+            position += self.event_len(&body) as u32;
+            events.push(self.event(kind, &body, position, 0));
+        }
+        events
+    }
+
+    pub fn event(&self, kind: EventType, body: &[u8], log_pos: u32, flags: u16) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&self.timestamp.to_le_bytes());
+        buf.push(kind.code());
+        buf.extend_from_slice(&self.server_id.to_le_bytes());
+        buf.extend_from_slice(&(self.event_len(body) as u32).to_le_bytes());
+        buf.extend_from_slice(&log_pos.to_le_bytes());
+        buf.extend_from_slice(&flags.to_le_bytes());
+        buf.extend_from_slice(body);
+        if self.with_checksum {
+            let crc = crc32(&buf);
+            buf.extend_from_slice(&crc.to_le_bytes());
+        }
+        buf
+    }
+
+    fn event_len(&self, body: &[u8]) -> usize {
+        LOG_HEADER_LEN + body.len() + if self.with_checksum { checksum::BINLOG_CHECKSUM_LEN } else { 0 }
+    }
+
+    fn format_description_body(&self) -> Vec<u8> {
+        let mut body = 4u16.to_le_bytes().to_vec();
+        let mut version = self.server_version.as_bytes().to_vec();
+        version.resize(50, 0);
+        body.extend_from_slice(&version);
+        body.extend_from_slice(&self.timestamp.to_le_bytes());
+        body.push(LOG_HEADER_LEN as u8);
+        let mut post_header_len = vec![0u8; ENUM_END_EVENT as usize - 1];
+        post_header_len[EventType::QueryEvent as usize - 1] = 13;
+        post_header_len[EventType::RotateEvent as usize - 1] = 8;
+        post_header_len[EventType::TableMapEvent as usize - 1] = 8;
+        post_header_len[EventType::WriteRowsEvent as usize - 1] = 10;
+        post_header_len[EventType::UpdateRowsEvent as usize - 1] = 10;
+        post_header_len[EventType::DeleteRowsEvent as usize - 1] = 10;
+        post_header_len[EventType::GtidLogEvent as usize - 1] = 42;
+        body.extend_from_slice(&post_header_len);
+        body.push(if self.with_checksum { checksum::BINLOG_CHECKSUM_ALG_CRC32 } else { checksum::BINLOG_CHECKSUM_ALG_OFF });
+        body
+    }
+
+    fn transaction_bodies(&self, index: u64) -> Vec<(EventType, Vec<u8>)> {
+        let mut bodies = vec![];
+        if let Some(server_uuid) = self.server_uuid.as_ref() {
+            bodies.push((EventType::GtidLogEvent, gtid_body(server_uuid, self.gno(index))));
+        }
+        bodies.extend([
+            (EventType::QueryEvent, query_body(MOCK_SCHEMA, "BEGIN")),
+            (EventType::TableMapEvent, table_map_body()),
+            (EventType::WriteRowsEvent, write_rows_body(index as u32 + 1)),
+            (EventType::XidEvent, (index + 1).to_le_bytes().to_vec()),
+        ]);
+        bodies
+    }
+}
+
+// 5.7格式的post header, 没有commit timestamp
+fn gtid_body(server_uuid: &[u8; 16], gno: u64) -> Vec<u8> {
+    // flags: commit flag
+    let mut body = vec![1];
+    body.extend_from_slice(server_uuid);
+    body.extend_from_slice(&gno.to_le_bytes());
+    // lt_type, last_committed, sequence_number
+    body.push(2);
+    body.extend_from_slice(&(gno as i64 - 1).to_le_bytes());
+    body.extend_from_slice(&(gno as i64).to_le_bytes());
+    body
+}
+
+fn query_body(db: &str, query: &str) -> Vec<u8> {
+    let mut body = vec![];
+    // thread id, exec time, db len, error code, status vars len
+    body.extend_from_slice(&1u32.to_le_bytes());
+    body.extend_from_slice(&0u32.to_le_bytes());
+    body.push(db.len() as u8);
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body.extend_from_slice(db.as_bytes());
+    body.push(0);
+    body.extend_from_slice(query.as_bytes());
+    body
+}
+
+fn table_map_body() -> Vec<u8> {
+    let mut body = MOCK_TABLE_ID.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    for name in [MOCK_SCHEMA, MOCK_TABLE] {
+        body.push(name.len() as u8);
+        body.extend_from_slice(name.as_bytes());
+        body.push(0);
+    }
+    // column count, types, metadata length, null bitmap
+    body.extend_from_slice(&[1, MYSQL_TYPE_LONG, 0, 0]);
+    body
+}
+
+// v2 WRITE_ROWS, 一行一列
+fn write_rows_body(id: u32) -> Vec<u8> {
+    let mut body = MOCK_TABLE_ID.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    // extra data length, column count, present bitmap, null bitmap
+    body.extend_from_slice(&2u16.to_le_bytes());
+    body.extend_from_slice(&[1, 0xff, 0]);
+    body.extend_from_slice(&id.to_le_bytes());
+    body
+}
+
+// binlog checksum使用的CRC32(IEEE)
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,90 @@
+use std::io::Write;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::mock::fixture::{read_raw_packet, Direction, Fixture, Record};
+
+/**
+ * <pre>
+ *  录制client与真实server之间的协议交互, 生成ReplayServer使用的fixture(需要feature capture):
+ *  监听本地地址, 接受一个client连接之后连接upstream, 两个方向按packet转发并按顺序记录,
+ *  任意一端关闭连接时结束. 用于为每个支持的server版本生成tests/fixtures下的文件:
+ *      let mut proxy = CaptureProxy::new("10.0.0.1:3306");
+ *      let address = proxy.bind("127.0.0.1:0")?;
+ *      // client连接address, 执行握手/查询/dump之后断开
+ *      proxy.capture_one()?.save(Path::new("tests/fixtures/mysql-8.0.36.fixture"))?;
+ *  fixture中包含完整的packet, 录制时使用专门的测试账号, 不要使用生产环境的密码
+ * </pre>
+ */
+pub struct CaptureProxy {
+    upstream: String,
+    listener: Option<TcpListener>,
+}
+
+impl CaptureProxy {
+    pub fn new(upstream: &str) -> CaptureProxy {
+        CaptureProxy { upstream: upstream.to_string(), listener: None }
+    }
+
+    pub fn upstream(&self) -> &str {
+        &self.upstream
+    }
+
+    pub fn bind(&mut self, address: &str) -> Result<SocketAddr, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("bind capture proxy {} failure: {}", address, e))?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+        self.listener = Some(listener);
+        Ok(address)
+    }
+
+    // 录制下一个连接, 连接结束之后返回
+    pub fn capture_one(&self) -> Result<Fixture, String> {
+        let listener = self.listener.as_ref().ok_or_else(|| "capture proxy is not bound".to_string())?;
+        let (client, _) = listener.accept().map_err(|e| format!("accept capture client failure: {}", e))?;
+        let server = TcpStream::connect(&self.upstream)
+            .map_err(|e| format!("connect upstream {} failure: {}", self.upstream, e))?;
+        let records = Arc::new(Mutex::new(vec![]));
+        let upstream = pump(server.try_clone().map_err(|e| e.to_string())?,
+                            client.try_clone().map_err(|e| e.to_string())?, Direction::Server, records.clone())?;
+        let downstream = pump(client, server, Direction::Client, records.clone())?;
+        let _ = upstream.join();
+        let _ = downstream.join();
+        let mut fixture = Fixture::new();
+        fixture.set_metadata("upstream", &self.upstream);
+        for record in records.lock().map_err(|_| "capture records are poisoned".to_string())?.drain(..) {
+            fixture.push(record);
+        }
+        if let Some(version) = fixture.records().first().and_then(|record| server_version(record.body())) {
+            fixture.set_metadata("server_version", &version);
+        }
+        Ok(fixture)
+    }
+}
+
+// 从from读取packet转发到to, 结束时关闭两端, 让另一个方向也结束
+fn pump(mut from: TcpStream, mut to: TcpStream, direction: Direction, records: Arc<Mutex<Vec<Record>>>)
+        -> Result<thread::JoinHandle<()>, String> {
+    thread::Builder::new()
+        .name(format!("capture-{}", direction.name()))
+        .spawn(move || {
+            while let Ok(Some(packet)) = read_raw_packet(&mut from) {
+                // 先记录再转发, 保证对端的响应记录在请求之后
+                if let Ok(mut records) = records.lock() {
+                    records.push(Record::new(direction, packet.clone()));
+                }
+                if to.write_all(&packet).is_err() {
+                    break;
+                }
+            }
+            let _ = from.shutdown(Shutdown::Both);
+            let _ = to.shutdown(Shutdown::Both);
+        })
+        .map_err(|e| format!("spawn capture pump failure: {}", e))
+}
+
+// 握手包: 1字节protocol version + null结尾的server version
+fn server_version(handshake: &[u8]) -> Option<String> {
+    let end = handshake.iter().skip(1).position(|byte| *byte == 0)? + 1;
+    Some(String::from_utf8_lossy(&handshake[1..end]).to_string())
+}
@@ -0,0 +1,345 @@
+use std::io::{self, Error, ErrorKind};
+use std::net::SocketAddrV4;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::channel::SocketChannel;
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+// splitmix64, 相同的seed产生相同的序列, 不依赖rand
+#[derive(Debug, Clone)]
+pub struct ChaosRng {
+    state: u64,
+}
+
+impl ChaosRng {
+    pub fn new(seed: u64) -> ChaosRng {
+        ChaosRng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // [0, bound), bound为0时返回0
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 { 0 } else { self.next_u64() % bound }
+    }
+
+    // probability不大于0时不消耗随机数, 关闭的故障不影响其它故障的序列
+    pub fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+/**
+ * <pre>
+ *  故障注入的配置, 概率均为每个entry(sink)或者每次读写(channel)独立判断, 0表示关闭:
+ *      seed                    随机种子, 配置和seed相同时注入的故障完全相同
+ *      delay_probability       延迟的概率, 延迟时间在[0, max_delay)中均匀分布
+ *      duplicate_probability   重复投递的概率(只对sink)
+ *      reorder_probability     乱序的概率(只对sink), 见ChaosSink
+ *      reorder_window          最多同时暂存的entry数
+ *      disconnect_probability  断开的概率, sink返回Err, channel之后的读写都返回ConnectionReset
+ *      disconnect_after        确定性的断开, sink为第n个entry, channel为读写n字节之后, 与概率同时生效
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosConfig {
+    seed: u64,
+    delay_probability: f64,
+    max_delay: Duration,
+    duplicate_probability: f64,
+    reorder_probability: f64,
+    reorder_window: usize,
+    disconnect_probability: f64,
+    disconnect_after: Option<u64>,
+}
+
+impl ChaosConfig {
+    pub fn new(seed: u64) -> ChaosConfig {
+        ChaosConfig {
+            seed,
+            delay_probability: 0.0,
+            max_delay: Duration::ZERO,
+            duplicate_probability: 0.0,
+            reorder_probability: 0.0,
+            reorder_window: 1,
+            disconnect_probability: 0.0,
+            disconnect_after: None,
+        }
+    }
+
+    pub fn set_delay(&mut self, probability: f64, max_delay: Duration) {
+        self.delay_probability = probability;
+        self.max_delay = max_delay;
+    }
+    pub fn set_duplicate_probability(&mut self, probability: f64) {
+        self.duplicate_probability = probability;
+    }
+    pub fn set_reorder(&mut self, probability: f64, window: usize) {
+        self.reorder_probability = probability;
+        self.reorder_window = window.max(1);
+    }
+    pub fn set_disconnect_probability(&mut self, probability: f64) {
+        self.disconnect_probability = probability;
+    }
+    pub fn set_disconnect_after(&mut self, disconnect_after: Option<u64>) {
+        self.disconnect_after = disconnect_after;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn delay_probability(&self) -> f64 {
+        self.delay_probability
+    }
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+    pub fn duplicate_probability(&self) -> f64 {
+        self.duplicate_probability
+    }
+    pub fn reorder_probability(&self) -> f64 {
+        self.reorder_probability
+    }
+    pub fn reorder_window(&self) -> usize {
+        self.reorder_window
+    }
+    pub fn disconnect_probability(&self) -> f64 {
+        self.disconnect_probability
+    }
+    pub fn disconnect_after(&self) -> Option<u64> {
+        self.disconnect_after
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChaosFault {
+    Delay(Duration),
+    Duplicate,
+    // 被推迟投递
+    Reorder,
+    Disconnect,
+}
+
+// 注入的故障, sequence为sink收到的第几个entry(从1开始)或者channel的第几次读写
+pub type ChaosFaults = Arc<Mutex<Vec<(u64, ChaosFault)>>>;
+
+fn record(faults: &ChaosFaults, sequence: u64, fault: ChaosFault) {
+    if let Ok(mut faults) = faults.lock() {
+        faults.push((sequence, fault));
+    }
+}
+
+fn delay(rng: &mut ChaosRng, config: &ChaosConfig) -> Option<Duration> {
+    if !rng.chance(config.delay_probability) {
+        return None;
+    }
+    Some(Duration::from_micros(rng.below(config.max_delay.as_micros() as u64)))
+}
+
+/**
+ * <pre>
+ *  只用于测试的sink包装, 按照ChaosConfig向下游注入故障, 用于验证下游对exactly-once/顺序的假设:
+ *      delay       投递之前sleep
+ *      duplicate   同一个entry投递两次
+ *      reorder     RowData被暂存(最多reorder_window个), 在下一个没有被暂存的RowData之后按原来的顺序投递;
+ *                  不会越过事务边界, 非RowData的entry之前先投递暂存的
+ *      disconnect  丢弃暂存的entry并返回Err, 与下游连接断开时一样, parser从事务开头重新投递
+ *  故障记录在faults句柄中, 可以在测试中断言:
+ *      let mut config = ChaosConfig::new(42);
+ *      config.set_duplicate_probability(0.1);
+ *      let sink = ChaosSink::new(Box::new(consumer), config);
+ *      let faults = sink.faults();
+ * </pre>
+ */
+pub struct ChaosSink {
+    inner: Box<dyn EventSink>,
+    config: ChaosConfig,
+    rng: ChaosRng,
+    held: Vec<Entry>,
+    sequence: u64,
+    faults: ChaosFaults,
+}
+
+impl ChaosSink {
+    pub fn new(inner: Box<dyn EventSink>, config: ChaosConfig) -> ChaosSink {
+        ChaosSink {
+            inner,
+            rng: ChaosRng::new(config.seed),
+            config,
+            held: vec![],
+            sequence: 0,
+            faults: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    pub fn faults(&self) -> ChaosFaults {
+        self.faults.clone()
+    }
+
+    fn deliver(&mut self, entry: &Entry) -> Result<(), String> {
+        if let Some(delay) = delay(&mut self.rng, &self.config) {
+            record(&self.faults, self.sequence, ChaosFault::Delay(delay));
+            thread::sleep(delay);
+        }
+        self.inner.on_event(entry)?;
+        if self.rng.chance(self.config.duplicate_probability) {
+            record(&self.faults, self.sequence, ChaosFault::Duplicate);
+            self.inner.on_event(entry)?;
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<(), String> {
+        for entry in std::mem::take(&mut self.held) {
+            self.deliver(&entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for ChaosSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.sequence += 1;
+        if self.config.disconnect_after == Some(self.sequence) || self.rng.chance(self.config.disconnect_probability) {
+            record(&self.faults, self.sequence, ChaosFault::Disconnect);
+            self.held.clear();
+            return Err(format!("chaos: injected disconnect at entry {}", self.sequence));
+        }
+        if entry.entry_type() != EntryType::RowData {
+            self.release()?;
+            return self.deliver(entry);
+        }
+        if self.held.len() < self.config.reorder_window && self.rng.chance(self.config.reorder_probability) {
+            record(&self.faults, self.sequence, ChaosFault::Reorder);
+            self.held.push(entry.clone());
+            return Ok(());
+        }
+        self.deliver(entry)?;
+        self.release()
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.release()?;
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
+
+/**
+ * <pre>
+ *  只用于测试的channel包装, 在每次读写之前按照ChaosConfig注入延迟和断开(duplicate/reorder对字节流没有意义, 忽略).
+ *  断开之后关闭底层channel, 之后的读写都返回ConnectionReset, is_connected为false.
+ *  disconnect_after按照读写的累计字节数判断, 一次读写跨过该值时只完成到该值为止的部分
+ * </pre>
+ */
+pub struct ChaosChannel {
+    inner: Box<dyn SocketChannel>,
+    config: ChaosConfig,
+    rng: ChaosRng,
+    transferred: u64,
+    sequence: u64,
+    disconnected: bool,
+    faults: ChaosFaults,
+}
+
+impl ChaosChannel {
+    pub fn new(inner: Box<dyn SocketChannel>, config: ChaosConfig) -> ChaosChannel {
+        ChaosChannel {
+            inner,
+            rng: ChaosRng::new(config.seed),
+            config,
+            transferred: 0,
+            sequence: 0,
+            disconnected: false,
+            faults: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn faults(&self) -> ChaosFaults {
+        self.faults.clone()
+    }
+
+    // 返回本次最多可以读写的字节数
+    fn before_io(&mut self, len: usize) -> io::Result<usize> {
+        if self.disconnected {
+            return Err(Error::from(ErrorKind::ConnectionReset));
+        }
+        self.sequence += 1;
+        let remaining = self.config.disconnect_after.map(|limit| limit.saturating_sub(self.transferred));
+        if remaining == Some(0) || self.rng.chance(self.config.disconnect_probability) {
+            record(&self.faults, self.sequence, ChaosFault::Disconnect);
+            self.disconnected = true;
+            let _ = self.inner.close();
+            return Err(Error::new(ErrorKind::ConnectionReset, "chaos: injected disconnect"));
+        }
+        if let Some(delay) = delay(&mut self.rng, &self.config) {
+            record(&self.faults, self.sequence, ChaosFault::Delay(delay));
+            thread::sleep(delay);
+        }
+        Ok(remaining.map_or(len, |remaining| len.min(remaining as usize)))
+    }
+}
+
+impl SocketChannel for ChaosChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.before_io(buf.len())?;
+        let size = self.inner.write(&buf[..len])?;
+        self.transferred += size as u64;
+        Ok(size)
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.before_io(buf.len())?;
+        let size = self.inner.read(&mut buf[..len])?;
+        self.transferred += size as u64;
+        Ok(size)
+    }
+
+    fn read_with_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let len = self.before_io(buf.len())?;
+        let size = self.inner.read_with_timeout(&mut buf[..len], timeout)?;
+        self.transferred += size as u64;
+        Ok(size)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    fn is_connected(&self) -> bool {
+        !self.disconnected && self.inner.is_connected()
+    }
+
+    fn get_remote_address(&self) -> Option<SocketAddrV4> {
+        self.inner.get_remote_address()
+    }
+
+    fn get_local_address(&self) -> Option<SocketAddrV4> {
+        self.inner.get_local_address()
+    }
+
+    fn close(&self) -> io::Result<()> {
+        self.inner.close()
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::command::hex::{decode_hex, encode_hex};
+
+// packet header: 3字节长度 + 1字节sequence
+const PACKET_HEADER_LEN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // server -> client
+    Server,
+    // client -> server
+    Client,
+}
+
+impl Direction {
+    pub fn from_name(name: &str) -> Result<Direction, String> {
+        match name {
+            "S" => Ok(Direction::Server),
+            "C" => Ok(Direction::Client),
+            _ => Err(format!("unknown fixture direction {}, expect S/C", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Server => "S",
+            Direction::Client => "C",
+        }
+    }
+}
+
+// 一个完整的packet, 包括header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    direction: Direction,
+    packet: Vec<u8>,
+}
+
+impl Record {
+    pub fn new(direction: Direction, packet: Vec<u8>) -> Record {
+        Record { direction, packet }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+    pub fn packet(&self) -> &Vec<u8> {
+        &self.packet
+    }
+    pub fn sequence(&self) -> Option<u8> {
+        self.packet.get(3).copied()
+    }
+    // 不包括header
+    pub fn body(&self) -> &[u8] {
+        self.packet.get(PACKET_HEADER_LEN..).unwrap_or_default()
+    }
+}
+
+/**
+ * <pre>
+ *  一次连接中client和server之间按顺序交换的packet, 由CaptureProxy(feature capture)从真实的server录制:
+ *      # server_version: 8.0.33
+ *      S 4a0000000a382e302e3333...
+ *      C 3f00000185a2bf01...
+ *  每一行是方向(S: server -> client, C: client -> server)和packet(包括header)的hex,
+ *  以#开头的 "key: value" 行是元数据, 例如server_version, binlog_file, binlog_position以及回放时的期望结果
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Fixture {
+    metadata: BTreeMap<String, String>,
+    records: Vec<Record>,
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        Fixture::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Fixture, String> {
+        let mut fixture = Fixture::new();
+        for (number, line) in text.lines().enumerate().map(|(number, line)| (number + 1, line.trim())) {
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some((key, value)) = comment.split_once(':') {
+                    fixture.metadata.insert(key.trim().to_string(), value.trim().to_string());
+                }
+                continue;
+            }
+            let (direction, hex) = line.split_once(' ')
+                .ok_or_else(|| format!("invalid fixture line {}: {}", number, line))?;
+            let packet = decode_hex(hex.trim()).map_err(|e| format!("invalid fixture line {}: {}", number, e))?;
+            if packet.len() < PACKET_HEADER_LEN {
+                return Err(format!("invalid fixture line {}: packet is too short", number));
+            }
+            fixture.records.push(Record::new(Direction::from_name(direction)?, packet));
+        }
+        Ok(fixture)
+    }
+
+    pub fn load(path: &Path) -> Result<Fixture, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("read {} failure: {}", path.display(), e))?;
+        Fixture::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("write {} failure: {}", path.display(), e))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (key, value) in self.metadata.iter() {
+            text.push_str(&format!("# {}: {}\n", key, value));
+        }
+        for record in self.records.iter() {
+            text.push_str(&format!("{} {}\n", record.direction.name(), encode_hex(&record.packet)));
+        }
+        text
+    }
+
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata.get(key).map(|value| value.as_str())
+    }
+    pub fn set_metadata(&mut self, key: &str, value: &str) {
+        self.metadata.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn records(&self) -> &Vec<Record> {
+        &self.records
+    }
+    pub fn push(&mut self, record: Record) {
+        self.records.push(record);
+    }
+}
+
+/**
+ * <pre>
+ *  按fixture回放server端, 用于在没有mysql的环境中锁定与各个server版本的协议兼容性:
+ *  每个连接从头回放, S记录原样发送, C记录读取client的一个packet并与录制的比较:
+ *  sequence以及command byte(body的第一个字节)必须一致, 其余字节包含随机值(例如client的地址), 不做比较.
+ *  不一致时记录到mismatches并关闭连接, 回放结束后关闭连接
+ * </pre>
+ */
+pub struct ReplayServer {
+    fixture: Arc<Fixture>,
+    running: Arc<AtomicBool>,
+    address: Option<SocketAddr>,
+    connections: Arc<AtomicU64>,
+    mismatches: Arc<Mutex<Vec<String>>>,
+}
+
+impl ReplayServer {
+    pub fn new(fixture: Fixture) -> ReplayServer {
+        ReplayServer {
+            fixture: Arc::new(fixture),
+            running: Arc::new(AtomicBool::new(false)),
+            address: None,
+            connections: Arc::new(AtomicU64::new(0)),
+            mismatches: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.address.map(|address| address.port()).unwrap_or(0)
+    }
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::SeqCst)
+    }
+    pub fn mismatches(&self) -> Vec<String> {
+        self.mismatches.lock().map(|mismatches| mismatches.clone()).unwrap_or_default()
+    }
+
+    // 监听127.0.0.1的随机端口
+    pub fn start(&mut self) -> Result<(), String> {
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| format!("bind replay server failure: {}", e))?;
+        self.address = Some(listener.local_addr().map_err(|e| e.to_string())?);
+        self.running.store(true, Ordering::SeqCst);
+        let (fixture, running) = (self.fixture.clone(), self.running.clone());
+        let (connections, mismatches) = (self.connections.clone(), self.mismatches.clone());
+        thread::Builder::new()
+            .name("replay-server".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if !running.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let mut stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+                    let connection = connections.fetch_add(1, Ordering::SeqCst) + 1;
+                    let (fixture, mismatches) = (fixture.clone(), mismatches.clone());
+                    thread::spawn(move || {
+                        if let Err(e) = replay(&fixture, &mut stream) {
+                            if let Ok(mut mismatches) = mismatches.lock() {
+                                mismatches.push(format!("connection {}: {}", connection, e));
+                            }
+                        }
+                        // 先记录mismatch再关闭, client看到连接关闭时mismatches已经可以读取
+                        let _ = stream.shutdown(Shutdown::Both);
+                    });
+                }
+            })
+            .map_err(|e| format!("spawn replay server failure: {}", e))?;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        // 唤醒阻塞在accept上的线程
+        if let Some(address) = self.address {
+            let _ = TcpStream::connect(address);
+        }
+    }
+}
+
+impl Drop for ReplayServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn replay(fixture: &Fixture, stream: &mut TcpStream) -> Result<(), String> {
+    for (index, record) in fixture.records().iter().enumerate() {
+        match record.direction() {
+            Direction::Server => {
+                if stream.write_all(record.packet()).is_err() {
+                    // client已经断开, 例如dump被client停止
+                    return Ok(());
+                }
+            }
+            Direction::Client => {
+                let packet = read_raw_packet(stream).map_err(|e| format!("read record {} failure: {}", index, e))?
+                    .ok_or_else(|| format!("client closed before record {}", index))?;
+                let actual = Record::new(Direction::Client, packet);
+                if actual.sequence() != record.sequence() || actual.body().first() != record.body().first() {
+                    return Err(format!("record {} mismatch, expect {}, got {}", index, encode_hex(record.packet()),
+                                       encode_hex(actual.packet())));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// 读取一个完整的packet(包括header), 连接在packet边界上关闭时返回None
+pub fn read_raw_packet(stream: &mut TcpStream) -> Result<Option<Vec<u8>>, String> {
+    let mut packet = vec![0u8; PACKET_HEADER_LEN];
+    match stream.read_exact(&mut packet) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof || e.kind() == ErrorKind::ConnectionReset => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    }
+    let len = u32::from_le_bytes([packet[0], packet[1], packet[2], 0]) as usize;
+    packet.resize(PACKET_HEADER_LEN + len, 0);
+    stream.read_exact(&mut packet[PACKET_HEADER_LEN..]).map_err(|e| e.to_string())?;
+    Ok(Some(packet))
+}
+
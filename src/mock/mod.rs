@@ -0,0 +1,433 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::channel::{SocketChannel, TcpChannel};
+use crate::command::capability::{CLIENT_CONNECT_WITH_DB, CLIENT_LONG_FLAG, CLIENT_LONG_PASSWORD, CLIENT_PLUGIN_AUTH,
+                                 CLIENT_PROTOCOL_41, CLIENT_SECURE_CONNECTION, CLIENT_TRANSACTIONS};
+use crate::command::com::{BinlogDumpCommand, BinlogDumpGtidCommand, Command, QueryCommand};
+use crate::command::command_type::{COM_BINLOG_DUMP, COM_BINLOG_DUMP_GTID, COM_PING, COM_QUERY, COM_QUIT, COM_REGISTER_SLAVE};
+use crate::command::msc::{DEFAULT_CHARSET_NUMBER, DEFAULT_PROTOCOL_VERSION, EOF_HEADER, ERROR_HEADER, OK_HEADER};
+use crate::command::gtid::GtidSet;
+use crate::command::packet_utils::{read_packet, write_pkg};
+use crate::command::password::MYSQL_NATIVE_PASSWORD;
+
+pub mod binlog;
+
+pub mod chaos;
+
+#[cfg(feature = "capture")]
+pub mod capture;
+
+pub mod fixture;
+
+pub use binlog::{MockBinlog, MOCK_BINLOG_FILE};
+pub use fixture::{Fixture, ReplayServer};
+
+const SERVER_STATUS_AUTOCOMMIT: u16 = 0x0002;
+const ER_MASTER_FATAL_ERROR_READING_BINLOG: u16 = 1236;
+const ER_UNKNOWN_COM_ERROR: u16 = 1047;
+
+#[derive(Debug, Default)]
+struct MockStats {
+    connections: AtomicU64,
+    events_sent: AtomicU64,
+    disconnects: AtomicU64,
+    corruptions: AtomicU64,
+}
+
+#[derive(Debug, Clone)]
+struct MockConfig {
+    binlog: MockBinlog,
+    transactions: Option<u64>,
+    events_per_second: Option<u32>,
+    disconnect_after: Option<u64>,
+    corrupt_every: Option<u64>,
+}
+
+/**
+ * <pre>
+ *  模拟master的服务端, 用于在没有mysql的CI中做压测和故障测试:
+ *  1. 握手, 接受任意用户名密码(mysql_native_password)
+ *  2. COM_QUERY: select @@global.binlog_checksum, show master status, show binary logs 与 show variables 返回结果集,
+ *     其它sql返回OK
+ *  3. COM_REGISTER_SLAVE 返回OK, COM_BINLOG_DUMP 从指定位点开始发送MockBinlog中的event,
+ *     位点不在事务边界上时返回1236. MockBinlog设置了server_uuid时支持COM_BINLOG_DUMP_GTID,
+ *     从文件开头发送并跳过gtid set中已经执行的事务
+ *  可以配置:
+ *  transactions        生成的事务数量, 发送完之后返回EOF(相当于非阻塞dump), 默认一直生成
+ *  events_per_second   发送速率, 默认不限速
+ *  disconnect_after    每个连接发送n个event之后断开, 用于测试重连和从事务开头恢复
+ *  corrupt_every       每发送n个event损坏一个(event_len比实际长度大), 用于测试解析失败后的恢复
+ *      let mut master = MockMaster::new();
+ *      master.set_transactions(Some(1000));
+ *      master.set_disconnect_after(Some(50));
+ *      master.start()?;
+ *      let parser = MysqlEventParser::new(AuthenticationInfo::new("127.0.0.1", master.port(), "canal", "canal"));
+ * </pre>
+ */
+pub struct MockMaster {
+    config: MockConfig,
+    address: Option<SocketAddr>,
+    running: Arc<AtomicBool>,
+    stats: Arc<MockStats>,
+}
+
+impl Default for MockMaster {
+    fn default() -> Self {
+        MockMaster::new()
+    }
+}
+
+impl MockMaster {
+    pub fn new() -> MockMaster {
+        MockMaster {
+            config: MockConfig {
+                binlog: MockBinlog::new(1, "8.0.33-mock", true),
+                transactions: None,
+                events_per_second: None,
+                disconnect_after: None,
+                corrupt_every: None,
+            },
+            address: None,
+            running: Arc::new(AtomicBool::new(false)),
+            stats: Arc::new(MockStats::default()),
+        }
+    }
+
+    pub fn set_binlog(&mut self, binlog: MockBinlog) {
+        self.config.binlog = binlog;
+    }
+    pub fn set_transactions(&mut self, transactions: Option<u64>) {
+        self.config.transactions = transactions;
+    }
+    pub fn set_events_per_second(&mut self, events_per_second: Option<u32>) {
+        self.config.events_per_second = events_per_second;
+    }
+    pub fn set_disconnect_after(&mut self, disconnect_after: Option<u64>) {
+        self.config.disconnect_after = disconnect_after;
+    }
+    pub fn set_corrupt_every(&mut self, corrupt_every: Option<u64>) {
+        self.config.corrupt_every = corrupt_every.filter(|n| *n > 0);
+    }
+
+    pub fn binlog(&self) -> &MockBinlog {
+        &self.config.binlog
+    }
+
+    // 所有事务之后的位点, 一直生成时为None
+    pub fn end_position(&self) -> Option<u64> {
+        self.config.transactions.map(|transactions| self.config.binlog.transaction_position(transactions))
+    }
+
+    pub fn port(&self) -> u16 {
+        self.address.map(|address| address.port()).unwrap_or(0)
+    }
+
+    pub fn connections(&self) -> u64 {
+        self.stats.connections.load(Ordering::SeqCst)
+    }
+    pub fn events_sent(&self) -> u64 {
+        self.stats.events_sent.load(Ordering::SeqCst)
+    }
+    pub fn disconnects(&self) -> u64 {
+        self.stats.disconnects.load(Ordering::SeqCst)
+    }
+    pub fn corruptions(&self) -> u64 {
+        self.stats.corruptions.load(Ordering::SeqCst)
+    }
+
+    // 监听127.0.0.1的随机端口
+    pub fn start(&mut self) -> Result<(), String> {
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| format!("bind mock master failure: {}", e))?;
+        self.address = Some(listener.local_addr().map_err(|e| e.to_string())?);
+        self.running.store(true, Ordering::SeqCst);
+        let config = self.config.clone();
+        let running = self.running.clone();
+        let stats = self.stats.clone();
+        thread::Builder::new()
+            .name("mock-master".to_string())
+            .spawn(move || accept(listener, config, running, stats))
+            .map_err(|e| format!("spawn mock master failure: {}", e))?;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        // 唤醒阻塞在accept上的线程
+        if let Some(address) = self.address {
+            let _ = TcpStream::connect(address);
+        }
+    }
+}
+
+impl Drop for MockMaster {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept(listener: TcpListener, config: MockConfig, running: Arc<AtomicBool>, stats: Arc<MockStats>) {
+    for stream in listener.incoming() {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let connection_id = stats.connections.fetch_add(1, Ordering::SeqCst) + 1;
+        let session = Session { config: config.clone(), running: running.clone(), stats: stats.clone(), connection_id };
+        thread::spawn(move || {
+            if let Err(e) = session.serve(stream) {
+                println!("mock master connection {} closed: {}", connection_id, e);
+            }
+        });
+    }
+}
+
+struct Session {
+    config: MockConfig,
+    running: Arc<AtomicBool>,
+    stats: Arc<MockStats>,
+    connection_id: u64,
+}
+
+impl Session {
+    fn serve(&self, stream: TcpStream) -> Result<(), String> {
+        let mut channel = TcpChannel::from_stream(stream).map_err(|e| e.to_string())?;
+        write_pkg(&mut channel, 0, &self.handshake()).map_err(|e| e.to_string())?;
+        let (header, _) = read_packet(&mut channel).map_err(|e| e.to_string())?;
+        write_pkg(&mut channel, header.get_packet_sequence_number().wrapping_add(1), &ok_packet())
+            .map_err(|e| e.to_string())?;
+        loop {
+            let body = match read_packet(&mut channel) {
+                Ok((_, body)) => body,
+                // client断开连接
+                Err(_) => return Ok(()),
+            };
+            match body.first().copied() {
+                Some(COM_QUIT) | None => return Ok(()),
+                Some(COM_QUERY) => self.query(&mut channel, QueryCommand::decode(&body)?.sql())?,
+                Some(COM_REGISTER_SLAVE) | Some(COM_PING) => write(&mut channel, 1, &ok_packet())?,
+                Some(COM_BINLOG_DUMP) => return self.dump(&mut channel, &body),
+                Some(COM_BINLOG_DUMP_GTID) => return self.dump_gtid(&mut channel, &body),
+                Some(command) => {
+                    let message = format!("Unknown command {}", command);
+                    write(&mut channel, 1, &error_packet(ER_UNKNOWN_COM_ERROR, "08S01", &message))?
+                }
+            }
+        }
+    }
+
+    fn handshake(&self) -> Vec<u8> {
+        let capabilities = (CLIENT_LONG_PASSWORD | CLIENT_LONG_FLAG | CLIENT_CONNECT_WITH_DB | CLIENT_PROTOCOL_41
+            | CLIENT_TRANSACTIONS | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH) as u32;
+        let seed: Vec<u8> = (0..20u8).map(|i| b'a' + i).collect();
+        let mut body = vec![DEFAULT_PROTOCOL_VERSION];
+        body.extend_from_slice(b"8.0.33-mock");
+        body.push(0);
+        body.extend_from_slice(&(self.connection_id as u32).to_le_bytes());
+        body.extend_from_slice(&seed[..8]);
+        body.push(0);
+        body.extend_from_slice(&(capabilities as u16).to_le_bytes());
+        body.push(DEFAULT_CHARSET_NUMBER);
+        body.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+        body.extend_from_slice(&((capabilities >> 16) as u16).to_le_bytes());
+        body.push(seed.len() as u8 + 1);
+        body.extend_from_slice(&[0u8; 10]);
+        body.extend_from_slice(&seed[8..]);
+        body.push(0);
+        body.extend_from_slice(MYSQL_NATIVE_PASSWORD.as_bytes());
+        body.push(0);
+        body
+    }
+
+    fn query(&self, channel: &mut dyn SocketChannel, sql: &str) -> Result<(), String> {
+        let sql = sql.trim().to_lowercase();
+        let binlog = &self.config.binlog;
+        if sql.starts_with("select") && sql.contains("binlog_checksum") {
+            let checksum = if binlog.with_checksum() { "CRC32" } else { "NONE" };
+            return result_set(channel, &["@@global.binlog_checksum"], &[checksum]);
+        }
+        if sql == "show master status" {
+            let position = binlog.transaction_position(self.config.transactions.unwrap_or(0)).to_string();
+            return result_set(channel, &["File", "Position"], &[MOCK_BINLOG_FILE, &position]);
+        }
+        if sql.starts_with("show variables") {
+            let server_id = binlog.server_id().to_string();
+            let values = ["binlog_format", "ROW", "binlog_row_image", "FULL", "server_id", &server_id,
+                          "version", binlog.server_version()];
+            return result_set(channel, &["Variable_name", "Value"], &values);
+        }
+        if sql == "show binary logs" {
+            let size = binlog.transaction_position(self.config.transactions.unwrap_or(0)).to_string();
+            return result_set(channel, &["Log_name", "File_size"], &[MOCK_BINLOG_FILE, &size]);
+        }
+        write(channel, 1, &ok_packet())
+    }
+
+    fn dump(&self, channel: &mut dyn SocketChannel, body: &[u8]) -> Result<(), String> {
+        let command = BinlogDumpCommand::decode(body)?;
+        let position = command.binlog_position() as u64;
+        let file = command.binlog_file().to_string();
+        let binlog = &self.config.binlog;
+        let start = if position <= 4 { Some(0) } else { binlog.transaction_index(position) };
+        let start = match start {
+            Some(start) if file == MOCK_BINLOG_FILE => start,
+            _ => {
+                let message = format!("Could not find first log event by binlog position {}:{}", file, position);
+                return write(channel, 1, &error_packet(ER_MASTER_FATAL_ERROR_READING_BINLOG, "HY000", &message));
+            }
+        };
+        self.send_binlog(channel, position, start, None)
+    }
+
+    fn dump_gtid(&self, channel: &mut dyn SocketChannel, body: &[u8]) -> Result<(), String> {
+        let command = BinlogDumpGtidCommand::decode(body)?;
+        if self.config.binlog.server_uuid().is_none() {
+            let message = "The slave is connecting using CHANGE MASTER TO MASTER_AUTO_POSITION = 1, \
+                           but the master has GTID_MODE = OFF";
+            return write(channel, 1, &error_packet(ER_MASTER_FATAL_ERROR_READING_BINLOG, "HY000", message));
+        }
+        let executed = command.gtid_set().cloned().unwrap_or_default();
+        self.send_binlog(channel, 4, 0, Some(executed))
+    }
+
+    // executed不为None时跳过其中已经执行的事务
+    fn send_binlog(&self, channel: &mut dyn SocketChannel, position: u64, start: u64, executed: Option<GtidSet>)
+                   -> Result<(), String> {
+        let binlog = &self.config.binlog;
+        let mut sequence = 1u8;
+        let log_pos = if position <= 4 { None } else { Some(0) };
+        for event in [binlog.fake_rotate(position.max(4)), binlog.format_description(log_pos)] {
+            send_event(channel, &mut sequence, &event)?;
+        }
+        let mut sent = 0u64;
+        let mut index = start;
+        while self.running.load(Ordering::SeqCst) {
+            if self.config.transactions.is_some_and(|transactions| index >= transactions) {
+                return write(channel, sequence, &[EOF_HEADER, 0, 0, 0, 0]);
+            }
+            let skip = match (executed.as_ref(), binlog.server_uuid()) {
+                (Some(executed), Some(server_uuid)) => executed.contains(server_uuid, binlog.gno(index)),
+                _ => false,
+            };
+            if skip {
+                index += 1;
+                continue;
+            }
+            for mut event in binlog.transaction(index) {
+                if self.config.disconnect_after.is_some_and(|limit| sent >= limit) {
+                    self.stats.disconnects.fetch_add(1, Ordering::SeqCst);
+                    let _ = channel.close();
+                    return Ok(());
+                }
+                if let Some(rate) = self.config.events_per_second.filter(|rate| *rate > 0) {
+                    thread::sleep(Duration::from_secs_f64(1.0 / rate as f64));
+                }
+                let total = self.stats.events_sent.fetch_add(1, Ordering::SeqCst) + 1;
+                if self.config.corrupt_every.is_some_and(|every| total.is_multiple_of(every)) {
+                    corrupt(&mut event);
+                    self.stats.corruptions.fetch_add(1, Ordering::SeqCst);
+                }
+                send_event(channel, &mut sequence, &event)?;
+                sent += 1;
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+// header中的event_len比实际长度大1, client会认为event被截断
+fn corrupt(event: &mut [u8]) {
+    let event_len = u32::from_le_bytes([event[9], event[10], event[11], event[12]]) + 1;
+    event[9..13].copy_from_slice(&event_len.to_le_bytes());
+}
+
+fn send_event(channel: &mut dyn SocketChannel, sequence: &mut u8, event: &[u8]) -> Result<(), String> {
+    let mut body = Vec::with_capacity(event.len() + 1);
+    body.push(OK_HEADER);
+    body.extend_from_slice(event);
+    write(channel, *sequence, &body)?;
+    *sequence = sequence.wrapping_add(1);
+    Ok(())
+}
+
+fn write(channel: &mut dyn SocketChannel, sequence: u8, body: &[u8]) -> Result<(), String> {
+    write_pkg(channel, sequence, body).map_err(|e| e.to_string())
+}
+
+fn ok_packet() -> Vec<u8> {
+    let mut body = vec![OK_HEADER, 0, 0];
+    body.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body
+}
+
+fn error_packet(code: u16, sql_state: &str, message: &str) -> Vec<u8> {
+    let mut body = vec![ERROR_HEADER];
+    body.extend_from_slice(&code.to_le_bytes());
+    body.push(b'#');
+    body.extend_from_slice(sql_state.as_bytes());
+    body.extend_from_slice(message.as_bytes());
+    body
+}
+
+fn eof_packet() -> Vec<u8> {
+    let mut body = vec![EOF_HEADER, 0, 0];
+    body.extend_from_slice(&SERVER_STATUS_AUTOCOMMIT.to_le_bytes());
+    body
+}
+
+// 只有一行的文本协议结果集
+fn result_set(channel: &mut dyn SocketChannel, names: &[&str], values: &[&str]) -> Result<(), String> {
+    let mut sequence = 1u8;
+    let mut packets = vec![vec![names.len() as u8]];
+    for name in names {
+        let mut field = vec![];
+        for value in ["def", "", "", "", name, name] {
+            put_length_coded_string(&mut field, value);
+        }
+        // 0x0c, charset, column length, type(VAR_STRING), flags, decimals, filler
+        field.push(0x0c);
+        field.extend_from_slice(&(DEFAULT_CHARSET_NUMBER as u16).to_le_bytes());
+        field.extend_from_slice(&255u32.to_le_bytes());
+        field.push(0xfd);
+        field.extend_from_slice(&0u16.to_le_bytes());
+        field.push(0);
+        field.extend_from_slice(&0u16.to_le_bytes());
+        packets.push(field);
+    }
+    packets.push(eof_packet());
+    // values按行平铺
+    for values in values.chunks(names.len().max(1)) {
+        let mut row = vec![];
+        for value in values {
+            put_length_coded_string(&mut row, value);
+        }
+        packets.push(row);
+    }
+    packets.push(eof_packet());
+    for packet in packets {
+        write(channel, sequence, &packet)?;
+        sequence = sequence.wrapping_add(1);
+    }
+    Ok(())
+}
+
+fn put_length_coded_string(buf: &mut Vec<u8>, value: &str) {
+    let len = value.len();
+    if len < 251 {
+        buf.push(len as u8);
+    } else {
+        buf.push(0xfc);
+        buf.extend_from_slice(&(len as u16).to_le_bytes());
+    }
+    buf.extend_from_slice(value.as_bytes());
+}
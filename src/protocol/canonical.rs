@@ -0,0 +1,125 @@
+use sha1::{Digest, Sha1};
+
+use crate::command::hex::encode_hex;
+use crate::protocol::{Column, RowData};
+
+// 列之间的分隔符(ASCII unit separator)以及NULL的表示
+pub const FIELD_SEPARATOR: char = '\u{1f}';
+pub const NULL_VALUE: &str = "\\N";
+
+/**
+ * <pre>
+ *  行数据的规范化序列化, 用于源端与目标端的数据比对:
+ *  1. 按列的index排序, 与列在entry中的顺序无关
+ *  2. 按mysql_type规范化取值:
+ *      decimal             去掉多余的前导0和小数末尾的0, -0 => 0
+ *      float/double        按最短可还原的形式输出
+ *      datetime/timestamp/time     去掉小数秒末尾的0
+ *  3. 列之间以0x1f分隔, NULL输出为\N, 值中的\和0x1f会被转义
+ * </pre>
+ */
+pub fn canonical_columns(columns: &[Column]) -> String {
+    let mut sorted: Vec<&Column> = columns.iter().collect();
+    sorted.sort_by_key(|column| column.index());
+    let mut out = String::new();
+    for (i, column) in sorted.iter().enumerate() {
+        if i > 0 {
+            out.push(FIELD_SEPARATOR);
+        }
+        if column.is_null() {
+            out.push_str(NULL_VALUE);
+        } else {
+            escape_into(&canonical_value(column.mysql_type(), column.value()), &mut out);
+        }
+    }
+    out
+}
+
+// update取after, delete取before
+pub fn canonical_row(row_data: &RowData) -> String {
+    if row_data.after_columns().is_empty() {
+        canonical_columns(row_data.before_columns())
+    } else {
+        canonical_columns(row_data.after_columns())
+    }
+}
+
+// 规范化结果的sha1(hex), 与mysql中SHA1()的输出格式一致
+pub fn row_checksum(columns: &[Column]) -> String {
+    let digest = Sha1::digest(canonical_columns(columns).as_bytes());
+    encode_hex(&digest)
+}
+
+// 表结构的版本, 按index排序之后 name:mysql_type 的sha1前16位, 列增减或者类型变化时改变
+pub fn table_version(columns: &[Column]) -> String {
+    let mut sorted: Vec<&Column> = columns.iter().collect();
+    sorted.sort_by_key(|column| column.index());
+    let mut hasher = Sha1::new();
+    for column in sorted {
+        hasher.update(column.name().as_bytes());
+        hasher.update(b":");
+        hasher.update(column.mysql_type().as_bytes());
+        hasher.update([FIELD_SEPARATOR as u8]);
+    }
+    encode_hex(&hasher.finalize()[..8])
+}
+
+pub fn canonical_value(mysql_type: &str, value: &str) -> String {
+    let base = mysql_type.split(['(', ' ']).next().unwrap_or("").to_ascii_lowercase();
+    match base.as_str() {
+        "decimal" | "numeric" => normalize_decimal(value),
+        "float" | "double" | "real" => value.parse::<f64>().map(|v| if v == 0.0 { 0.0 } else { v }.to_string())
+            .unwrap_or_else(|_| value.to_string()),
+        "datetime" | "timestamp" | "time" => normalize_fraction(value),
+        _ => value.to_string(),
+    }
+}
+
+pub fn normalize_decimal(value: &str) -> String {
+    let value = value.trim();
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    if !int_part.chars().chain(frac_part.chars()).all(|c| c.is_ascii_digit()) {
+        return value.to_string();
+    }
+    let int_part = int_part.trim_start_matches('0');
+    let frac_part = frac_part.trim_end_matches('0');
+    let mut out = String::new();
+    if negative && !(int_part.is_empty() && frac_part.is_empty()) {
+        out.push('-');
+    }
+    out.push_str(if int_part.is_empty() { "0" } else { int_part });
+    if !frac_part.is_empty() {
+        out.push('.');
+        out.push_str(frac_part);
+    }
+    out
+}
+
+// 2024-01-01 10:00:00.120000 => 2024-01-01 10:00:00.12, 10:00:00.000 => 10:00:00
+pub fn normalize_fraction(value: &str) -> String {
+    match value.rsplit_once('.') {
+        Some((seconds, fraction)) if !fraction.is_empty() && fraction.chars().all(|c| c.is_ascii_digit()) => {
+            let fraction = fraction.trim_end_matches('0');
+            if fraction.is_empty() {
+                seconds.to_string()
+            } else {
+                format!("{}.{}", seconds, fraction)
+            }
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn escape_into(value: &str, out: &mut String) {
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            FIELD_SEPARATOR => out.push_str("\\u001f"),
+            _ => out.push(c),
+        }
+    }
+}
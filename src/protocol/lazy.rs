@@ -0,0 +1,74 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use crate::command::charset;
+
+/**
+ * <pre>
+ *  延迟解码的BLOB/TEXT/GEOMETRY列值:
+ *  解析rows event时只记录列值在event中的偏移和长度, 与同一个event的所有lazy列共享rows的内存,
+ *  第一次读取value时才按collation解码并缓存, 之后的读取直接返回缓存.
+ *  被filter丢弃的行/列不会被解码, 也不会产生拷贝
+ * </pre>
+ */
+#[derive(Clone)]
+pub struct LazyValue {
+    rows: Arc<Vec<u8>>,
+    offset: usize,
+    len: usize,
+    collation: u16,
+    value: OnceLock<String>,
+}
+
+impl LazyValue {
+    pub fn new(rows: Arc<Vec<u8>>, offset: usize, len: usize, collation: u16) -> Result<LazyValue, String> {
+        if offset + len > rows.len() {
+            return Err(format!("lazy value [{}, {}) is out of rows range {}", offset, offset + len, rows.len()));
+        }
+        Ok(LazyValue { rows, offset, len, collation, value: OnceLock::new() })
+    }
+
+    // 原始字节数, 不触发解码
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn collation(&self) -> u16 {
+        self.collation
+    }
+
+    pub fn raw(&self) -> &[u8] {
+        &self.rows[self.offset..self.offset + self.len]
+    }
+
+    pub fn is_materialized(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    pub fn get(&self) -> &str {
+        self.value.get_or_init(|| charset::decode(self.raw(), self.collation))
+    }
+}
+
+impl PartialEq for LazyValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.collation == other.collation && self.raw() == other.raw()
+    }
+}
+
+impl Eq for LazyValue {}
+
+// 不输出列值, 避免打印大字段
+impl fmt::Debug for LazyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyValue")
+            .field("len", &self.len)
+            .field("collation", &self.collation)
+            .field("materialized", &self.is_materialized())
+            .finish()
+    }
+}
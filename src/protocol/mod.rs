@@ -0,0 +1,430 @@
+use std::sync::Arc;
+
+use crate::instance::failover::SourceChange;
+use crate::instance::EntryPosition;
+
+pub mod canonical;
+
+pub mod lazy;
+
+pub mod row;
+
+pub mod temporal;
+
+pub mod wire;
+
+pub use lazy::LazyValue;
+pub use row::{Row, RowSchema};
+pub use temporal::{TemporalFormat, ZeroDatePolicy};
+
+// 对应canal中CanalEntry.EntryType
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntryType {
+    TransactionBegin,
+    RowData,
+    TransactionEnd,
+    Heartbeat,
+    GtidLog,
+    // 不是binlog中的数据, 而是解析过程中需要通知下游的异常, 例如binlog被purge后跳过了一段位点
+    Incident,
+    // 同样不是binlog中的数据, 只用于诊断, 例如连接master时的变量快照
+    Info,
+    // 重连之后master的身份发生变化(主从切换), source_change中为切换前后的身份和采取的处理
+    SourceChanged,
+}
+
+// 对应canal中CanalEntry.EventType
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventType {
+    Insert,
+    Update,
+    Delete,
+    Create,
+    Alter,
+    Erase,
+    Query,
+    Truncate,
+    Rename,
+    CIndex,
+    DIndex,
+    Gtid,
+    XaCommit,
+    XaRollback,
+    MHeartbeat,
+}
+
+impl EventType {
+    pub fn is_dml(&self) -> bool {
+        matches!(self, EventType::Insert | EventType::Update | EventType::Delete)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Header {
+    log_file_name: String,
+    log_file_offset: u64,
+    server_id: u32,
+    // master执行的时间, 毫秒
+    execute_time: i64,
+    schema_name: String,
+    table_name: String,
+    event_type: Option<EventType>,
+    event_length: u32,
+    gtid: String,
+    // mysql 8.0.1+ GTID event中的commit timestamp, 微秒, 比execute_time(秒)精确
+    original_commit_timestamp: Option<i64>,
+    immediate_commit_timestamp: Option<i64>,
+    // 产生该entry的原始语句, 来自mysql的ROWS_QUERY或者MariaDB的ANNOTATE_ROWS, 没有时为空
+    statement: String,
+    // SizeLimitSink拆分出的第几部分, 从0开始, 同一个rows event的各部分共享其余的header字段, 没有拆分时为None
+    part: Option<u32>,
+}
+
+impl Header {
+    pub fn new(log_file_name: &str, log_file_offset: u64) -> Header {
+        Header { log_file_name: log_file_name.to_string(), log_file_offset, ..Header::default() }
+    }
+
+    pub fn log_file_name(&self) -> &str {
+        &self.log_file_name
+    }
+    pub fn log_file_offset(&self) -> u64 {
+        self.log_file_offset
+    }
+    pub fn server_id(&self) -> u32 {
+        self.server_id
+    }
+    pub fn execute_time(&self) -> i64 {
+        self.execute_time
+    }
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+    pub fn event_type(&self) -> Option<EventType> {
+        self.event_type
+    }
+    pub fn event_length(&self) -> u32 {
+        self.event_length
+    }
+    pub fn gtid(&self) -> &str {
+        &self.gtid
+    }
+    // 事务在最初的master上提交的时间, 级联复制时也不会变化
+    pub fn original_commit_timestamp(&self) -> Option<i64> {
+        self.original_commit_timestamp
+    }
+    // 事务在当前连接的server上提交的时间
+    pub fn immediate_commit_timestamp(&self) -> Option<i64> {
+        self.immediate_commit_timestamp
+    }
+    pub fn statement(&self) -> &str {
+        &self.statement
+    }
+    pub fn part(&self) -> Option<u32> {
+        self.part
+    }
+
+    pub fn set_server_id(&mut self, server_id: u32) {
+        self.server_id = server_id;
+    }
+    pub fn set_execute_time(&mut self, execute_time: i64) {
+        self.execute_time = execute_time;
+    }
+    pub fn set_schema_name(&mut self, schema_name: &str) {
+        self.schema_name = schema_name.to_string();
+    }
+    pub fn set_table_name(&mut self, table_name: &str) {
+        self.table_name = table_name.to_string();
+    }
+    pub fn set_event_type(&mut self, event_type: EventType) {
+        self.event_type = Some(event_type);
+    }
+    pub fn set_event_length(&mut self, event_length: u32) {
+        self.event_length = event_length;
+    }
+    pub fn set_gtid(&mut self, gtid: &str) {
+        self.gtid = gtid.to_string();
+    }
+    pub fn set_commit_timestamps(&mut self, original_commit_timestamp: i64, immediate_commit_timestamp: i64) {
+        self.original_commit_timestamp = Some(original_commit_timestamp);
+        self.immediate_commit_timestamp = Some(immediate_commit_timestamp);
+    }
+    pub fn set_statement(&mut self, statement: &str) {
+        self.statement = statement.to_string();
+    }
+    pub fn set_part(&mut self, part: u32) {
+        self.part = Some(part);
+    }
+
+    pub fn position(&self) -> EntryPosition {
+        let mut position = EntryPosition::new(&self.log_file_name, self.log_file_offset);
+        position.set_timestamp(self.execute_time);
+        position.set_server_id(self.server_id);
+        position
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Column {
+    index: usize,
+    // java.sql.Types
+    sql_type: i32,
+    name: String,
+    is_key: bool,
+    updated: bool,
+    is_null: bool,
+    value: String,
+    mysql_type: String,
+    // 延迟解码的大字段, 不为None时value()从这里读取
+    lazy: Option<LazyValue>,
+}
+
+impl Column {
+    pub fn new(index: usize, name: &str) -> Column {
+        Column { index, name: name.to_string(), ..Column::default() }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn sql_type(&self) -> i32 {
+        self.sql_type
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn is_key(&self) -> bool {
+        self.is_key
+    }
+    pub fn updated(&self) -> bool {
+        self.updated
+    }
+    pub fn is_null(&self) -> bool {
+        self.is_null
+    }
+    // lazy列第一次读取时解码
+    pub fn value(&self) -> &str {
+        match &self.lazy {
+            Some(lazy) => lazy.get(),
+            None => &self.value,
+        }
+    }
+    pub fn mysql_type(&self) -> &str {
+        &self.mysql_type
+    }
+    pub fn lazy_value(&self) -> Option<&LazyValue> {
+        self.lazy.as_ref()
+    }
+    // 列值的字节数, lazy列返回原始字节数, 不触发解码
+    pub fn value_len(&self) -> usize {
+        match &self.lazy {
+            Some(lazy) => lazy.len(),
+            None => self.value.len(),
+        }
+    }
+
+    pub fn set_sql_type(&mut self, sql_type: i32) {
+        self.sql_type = sql_type;
+    }
+    pub fn set_is_key(&mut self, is_key: bool) {
+        self.is_key = is_key;
+    }
+    pub fn set_updated(&mut self, updated: bool) {
+        self.updated = updated;
+    }
+    pub fn set_is_null(&mut self, is_null: bool) {
+        self.is_null = is_null;
+    }
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.to_string();
+        self.lazy = None;
+    }
+    pub fn set_lazy_value(&mut self, lazy: LazyValue) {
+        self.value.clear();
+        self.lazy = Some(lazy);
+    }
+    pub fn set_mysql_type(&mut self, mysql_type: &str) {
+        self.mysql_type = mysql_type.to_string();
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowData {
+    before_columns: Vec<Column>,
+    after_columns: Vec<Column>,
+}
+
+impl RowData {
+    pub fn new(before_columns: Vec<Column>, after_columns: Vec<Column>) -> RowData {
+        RowData { before_columns, after_columns }
+    }
+
+    pub fn before_columns(&self) -> &Vec<Column> {
+        &self.before_columns
+    }
+    pub fn after_columns(&self) -> &Vec<Column> {
+        &self.after_columns
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowChange {
+    table_id: u64,
+    event_type: EventType,
+    is_ddl: bool,
+    sql: String,
+    row_datas: Vec<RowData>,
+    ddl_schema_name: String,
+    // 列名索引, DDL以及没有table map的entry为None
+    schema: Option<Arc<RowSchema>>,
+}
+
+impl RowChange {
+    pub fn new(event_type: EventType) -> RowChange {
+        RowChange {
+            table_id: 0,
+            event_type,
+            is_ddl: false,
+            sql: String::new(),
+            row_datas: vec![],
+            ddl_schema_name: String::new(),
+            schema: None,
+        }
+    }
+
+    pub fn table_id(&self) -> u64 {
+        self.table_id
+    }
+    pub fn event_type(&self) -> EventType {
+        self.event_type
+    }
+    pub fn is_ddl(&self) -> bool {
+        self.is_ddl
+    }
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+    pub fn row_datas(&self) -> &Vec<RowData> {
+        &self.row_datas
+    }
+    pub fn ddl_schema_name(&self) -> &str {
+        &self.ddl_schema_name
+    }
+    pub fn schema(&self) -> Option<&Arc<RowSchema>> {
+        self.schema.as_ref()
+    }
+
+    pub fn before_rows(&self) -> impl Iterator<Item=Row<'_>> {
+        self.row_datas.iter().map(|row_data| Row::new(self.schema.as_deref(), row_data.before_columns()))
+    }
+    pub fn after_rows(&self) -> impl Iterator<Item=Row<'_>> {
+        self.row_datas.iter().map(|row_data| Row::new(self.schema.as_deref(), row_data.after_columns()))
+    }
+    // delete返回before image, insert/update返回after image
+    pub fn rows(&self) -> impl Iterator<Item=Row<'_>> {
+        let delete = self.event_type == EventType::Delete;
+        self.row_datas.iter().map(move |row_data| {
+            let columns = if delete { row_data.before_columns() } else { row_data.after_columns() };
+            Row::new(self.schema.as_deref(), columns)
+        })
+    }
+
+    pub fn set_table_id(&mut self, table_id: u64) {
+        self.table_id = table_id;
+    }
+    pub fn set_is_ddl(&mut self, is_ddl: bool) {
+        self.is_ddl = is_ddl;
+    }
+    pub fn set_sql(&mut self, sql: &str) {
+        self.sql = sql.to_string();
+    }
+    pub fn set_ddl_schema_name(&mut self, ddl_schema_name: &str) {
+        self.ddl_schema_name = ddl_schema_name.to_string();
+    }
+    pub fn add_row_data(&mut self, row_data: RowData) {
+        self.row_datas.push(row_data);
+    }
+    pub fn set_row_datas(&mut self, row_datas: Vec<RowData>) {
+        self.row_datas = row_datas;
+    }
+    pub fn set_schema(&mut self, schema: Arc<RowSchema>) {
+        self.schema = Some(schema);
+    }
+}
+
+/**
+ * <pre>
+ *  对应canal中的CanalEntry.Entry, 解析后交给sink的最小单元:
+ *  TransactionBegin/TransactionEnd   事务边界, TransactionEnd携带xid
+ *  RowData                           一个rows event或者一条DDL
+ *  SourceChanged                     重连之后master发生切换, 见instance::failover::SourceChange
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    header: Header,
+    entry_type: EntryType,
+    row_change: Option<RowChange>,
+    transaction_id: Option<u64>,
+    // Incident/Info/SourceChanged的描述
+    message: Option<String>,
+    source_change: Option<Box<SourceChange>>,
+}
+
+impl Entry {
+    pub fn new(header: Header, entry_type: EntryType) -> Entry {
+        Entry { header, entry_type, row_change: None, transaction_id: None, message: None, source_change: None }
+    }
+
+    pub fn row_data(header: Header, row_change: RowChange) -> Entry {
+        Entry { row_change: Some(row_change), ..Entry::new(header, EntryType::RowData) }
+    }
+
+    pub fn incident(header: Header, message: &str) -> Entry {
+        Entry { message: Some(message.to_string()), ..Entry::new(header, EntryType::Incident) }
+    }
+
+    pub fn info(header: Header, message: &str) -> Entry {
+        Entry { message: Some(message.to_string()), ..Entry::new(header, EntryType::Info) }
+    }
+
+    pub fn source_changed(header: Header, source_change: SourceChange) -> Entry {
+        Entry {
+            message: Some(source_change.message()),
+            source_change: Some(Box::new(source_change)),
+            ..Entry::new(header, EntryType::SourceChanged)
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+    pub fn entry_type(&self) -> EntryType {
+        self.entry_type
+    }
+    pub fn row_change(&self) -> Option<&RowChange> {
+        self.row_change.as_ref()
+    }
+    pub fn transaction_id(&self) -> Option<u64> {
+        self.transaction_id
+    }
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+    pub fn source_change(&self) -> Option<&SourceChange> {
+        self.source_change.as_deref()
+    }
+
+    pub fn set_row_change(&mut self, row_change: RowChange) {
+        self.row_change = Some(row_change);
+    }
+    pub fn set_transaction_id(&mut self, transaction_id: u64) {
+        self.transaction_id = Some(transaction_id);
+    }
+}
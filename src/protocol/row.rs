@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::protocol::Column;
+
+/**
+ * <pre>
+ *  一个表的列名到列序号的索引, 由LogEventConvert按table map生成,
+ *  同一个RowChange的所有行共享, 按列名访问时不需要遍历列
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowSchema {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+}
+
+impl RowSchema {
+    pub fn new(names: Vec<String>) -> RowSchema {
+        let index = names.iter().enumerate().map(|(i, name)| (name.clone(), i)).collect();
+        RowSchema { names, index }
+    }
+
+    pub fn names(&self) -> &Vec<String> {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    // 列在表中的序号
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+}
+
+/**
+ * <pre>
+ *  RowData中before或after image的只读视图, 不拷贝列值:
+ *      get(index)          按列在表中的序号访问
+ *      get_by_name(name)   按列名访问, 有schema时为O(1)
+ *      iter()              按顺序返回(name, value, changed), NULL的value为None
+ *  binlog_row_image=MINIMAL时image中只有部分列, 不在image中的列返回None.
+ *  序列化为{name: value}, NULL输出null
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'a> {
+    schema: Option<&'a RowSchema>,
+    columns: &'a [Column],
+}
+
+impl<'a> Row<'a> {
+    pub fn new(schema: Option<&'a RowSchema>, columns: &'a [Column]) -> Row<'a> {
+        Row { schema, columns }
+    }
+
+    pub fn columns(&self) -> &'a [Column] {
+        self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&'a Column> {
+        // 完整的image中列的位置与序号相同, 否则列按序号升序排列
+        match self.columns.get(index) {
+            Some(column) if column.index() == index => Some(column),
+            _ => self.columns.binary_search_by_key(&index, |column| column.index()).ok().map(|i| &self.columns[i]),
+        }
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&'a Column> {
+        match self.schema {
+            Some(schema) => self.get(schema.index_of(name)?),
+            None => self.columns.iter().find(|column| column.name() == name),
+        }
+    }
+
+    // 列不存在或者为NULL时返回None
+    pub fn value(&self, index: usize) -> Option<&'a str> {
+        self.get(index).filter(|column| !column.is_null()).map(|column| column.value())
+    }
+
+    pub fn value_by_name(&self, name: &str) -> Option<&'a str> {
+        self.get_by_name(name).filter(|column| !column.is_null()).map(|column| column.value())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get_by_name(name).is_some()
+    }
+
+    // (name, value, changed), changed只在update的after image中有意义
+    pub fn iter(&self) -> impl Iterator<Item=(&'a str, Option<&'a str>, bool)> + 'a {
+        self.columns.iter().map(|column| {
+            let value = if column.is_null() { None } else { Some(column.value()) };
+            (column.name(), value, column.updated())
+        })
+    }
+
+    pub fn changed(&self) -> impl Iterator<Item=&'a Column> + 'a {
+        self.columns.iter().filter(|column| column.updated())
+    }
+}
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (name, value, _) in self.iter() {
+            map.serialize_entry(name, &value)?;
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,207 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+
+// Sentinel策略默认使用的值, date列取前10个字符
+pub const DEFAULT_ZERO_DATE_SENTINEL: &str = "1970-01-01 00:00:00";
+
+/**
+ * <pre>
+ *  MySQL允许的非法日期(0000-00-00, 2020-00-10, ALLOW_INVALID_DATES下的2020-02-31)的处理方式.
+ *  Postgres/ES等下游无法写入这些值, 需要显式选择:
+ *      Keep        原样输出MySQL的文本, 与之前的行为一致
+ *      Sentinel    替换为一个固定的值, 例如1970-01-01 00:00:00
+ *      Null        输出为NULL
+ *      Error       返回错误, 由调用方停止或者跳过
+ *  只作用于date/datetime/timestamp, time和year不受影响
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ZeroDatePolicy {
+    #[default]
+    Keep,
+    Sentinel(String),
+    Null,
+    Error,
+}
+
+impl ZeroDatePolicy {
+    // keep/null/error/sentinel, sentinel没有指定值时使用DEFAULT_ZERO_DATE_SENTINEL
+    pub fn from_name(name: &str, sentinel: Option<&str>) -> Result<ZeroDatePolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "keep" => Ok(ZeroDatePolicy::Keep),
+            "sentinel" => Ok(ZeroDatePolicy::Sentinel(sentinel.unwrap_or(DEFAULT_ZERO_DATE_SENTINEL).to_string())),
+            "null" => Ok(ZeroDatePolicy::Null),
+            "error" => Ok(ZeroDatePolicy::Error),
+            _ => Err(format!("unknown zero date policy {}, expect keep/sentinel/null/error", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZeroDatePolicy::Keep => "keep",
+            ZeroDatePolicy::Sentinel(_) => "sentinel",
+            ZeroDatePolicy::Null => "null",
+            ZeroDatePolicy::Error => "error",
+        }
+    }
+
+    // value为date/datetime/timestamp的文本, 返回None表示输出NULL
+    pub fn apply(&self, value: &str) -> Result<Option<String>, String> {
+        if !is_invalid_date(value) {
+            return Ok(Some(value.to_string()));
+        }
+        match self {
+            ZeroDatePolicy::Keep => Ok(Some(value.to_string())),
+            ZeroDatePolicy::Sentinel(sentinel) if value.len() == 10 && sentinel.len() > 10 => {
+                Ok(Some(sentinel[..10].to_string()))
+            }
+            ZeroDatePolicy::Sentinel(sentinel) => Ok(Some(sentinel.clone())),
+            ZeroDatePolicy::Null => Ok(None),
+            ZeroDatePolicy::Error => Err(format!("invalid date value {}", value)),
+        }
+    }
+}
+
+// mysql_type为Column中的类型, 例如datetime(3)
+pub fn is_date_type(mysql_type: &str) -> bool {
+    matches!(base_type(mysql_type).as_str(), "date" | "datetime" | "timestamp")
+}
+
+// yyyy-MM-dd开头且不是合法日期, 包括零值和月/日为0的部分零值
+pub fn is_invalid_date(value: &str) -> bool {
+    let date = match value.get(..10) {
+        Some(date) if date.as_bytes()[4] == b'-' && date.as_bytes()[7] == b'-' => date,
+        _ => return false,
+    };
+    let parts = (date[..4].parse::<i32>(), date[5..7].parse::<u32>(), date[8..10].parse::<u32>());
+    match parts {
+        (Ok(year), Ok(month), Ok(day)) => NaiveDate::from_ymd_opt(year, month, day).is_none(),
+        _ => false,
+    }
+}
+
+/**
+ * <pre>
+ *  date/time/datetime/timestamp列输出的格式, 由sink选择, 不影响Entry中的值:
+ *      Text        RowsLogBuffer解码的文本, 与之前的行为一致:
+ *                  2020-01-02, 2020-01-02 03:04:05.123, 12:34:56, timestamp为进程所在时区的本地时间
+ *      Iso8601     datetime/timestamp输出为带时区偏移的ISO8601, 例如2020-01-02T03:04:05.123+08:00,
+ *                  date/time保持不变
+ *      EpochMillis 距离1970-01-01T00:00:00Z的毫秒数, time列为距离00:00:00的毫秒数(可以为负数或者超过一天)
+ *      EpochMicros 同EpochMillis, 单位为微秒
+ *  MySQL的date/datetime没有时区, 按offset(例如sink配置的temporal_utc_offset)解释;
+ *  timestamp是绝对时间, 文本按本地时区还原之后再按offset输出, 夏令时回拨的一小时内取较早的时刻.
+ *  非法日期(见ZeroDatePolicy)以及无法解析的文本原样输出, year列不受影响
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TemporalFormat {
+    #[default]
+    Text,
+    Iso8601,
+    EpochMillis,
+    EpochMicros,
+}
+
+impl TemporalFormat {
+    pub fn from_name(name: &str) -> Result<TemporalFormat, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(TemporalFormat::Text),
+            "iso8601" => Ok(TemporalFormat::Iso8601),
+            "epoch_millis" => Ok(TemporalFormat::EpochMillis),
+            "epoch_micros" => Ok(TemporalFormat::EpochMicros),
+            _ => Err(format!("unknown temporal format {}, expect text/iso8601/epoch_millis/epoch_micros", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemporalFormat::Text => "text",
+            TemporalFormat::Iso8601 => "iso8601",
+            TemporalFormat::EpochMillis => "epoch_millis",
+            TemporalFormat::EpochMicros => "epoch_micros",
+        }
+    }
+
+    // value为RowsLogBuffer解码的文本, mysql_type为Column中的类型, 不是时间类型时原样返回
+    pub fn format(&self, value: &str, mysql_type: &str, offset: &FixedOffset) -> String {
+        if *self == TemporalFormat::Text || is_invalid_date(value) {
+            return value.to_string();
+        }
+        let converted = match base_type(mysql_type).as_str() {
+            "date" => self.format_date(value, offset),
+            "datetime" => parse_datetime(value)
+                .and_then(|(datetime, fraction)| offset.from_local_datetime(&datetime).single().map(|time| (time, fraction)))
+                .map(|(time, fraction)| self.format_instant(&time, fraction)),
+            "timestamp" => parse_datetime(value)
+                .and_then(|(datetime, fraction)| Local.from_local_datetime(&datetime).earliest().map(|time| (time, fraction)))
+                .map(|(time, fraction)| self.format_instant(&time.with_timezone(offset), fraction)),
+            "time" => self.format_time(value),
+            _ => None,
+        };
+        converted.unwrap_or_else(|| value.to_string())
+    }
+
+    fn format_date(&self, value: &str, offset: &FixedOffset) -> Option<String> {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+        match self {
+            TemporalFormat::Text | TemporalFormat::Iso8601 => Some(value.to_string()),
+            _ => {
+                let time = offset.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).single()?;
+                Some(self.format_instant(&time, ""))
+            }
+        }
+    }
+
+    // fraction为原文本中秒的小数部分(含'.'), ISO8601保持原来的精度
+    fn format_instant(&self, time: &DateTime<FixedOffset>, fraction: &str) -> String {
+        match self {
+            TemporalFormat::Text => time.format("%Y-%m-%d %H:%M:%S").to_string() + fraction,
+            TemporalFormat::Iso8601 => format!("{}{}{}", time.format("%Y-%m-%dT%H:%M:%S"), fraction, time.format("%:z")),
+            TemporalFormat::EpochMillis => time.timestamp_millis().to_string(),
+            TemporalFormat::EpochMicros => (time.timestamp() * 1_000_000 + time.timestamp_subsec_micros() as i64).to_string(),
+        }
+    }
+
+    // [-]HHH:MM:SS[.ffffff], 小时可以超过24
+    fn format_time(&self, value: &str) -> Option<String> {
+        let (negative, rest) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (hms, fraction) = rest.split_once('.').unwrap_or((rest, ""));
+        let mut parts = hms.split(':').map(|part| part.parse::<i64>().ok());
+        let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+        if parts.next().is_some() || fraction.len() > 6 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let micros = format!("{:0<6}", fraction).parse::<i64>().ok()?;
+        let total = ((hours * 3600 + minutes * 60 + seconds) * 1_000_000 + micros) * if negative { -1 } else { 1 };
+        match self {
+            TemporalFormat::Text | TemporalFormat::Iso8601 => Some(value.to_string()),
+            TemporalFormat::EpochMillis => Some((total / 1000).to_string()),
+            TemporalFormat::EpochMicros => Some(total.to_string()),
+        }
+    }
+}
+
+// datetime(3) -> datetime
+fn base_type(mysql_type: &str) -> String {
+    mysql_type.split('(').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+// yyyy-MM-dd HH:mm:ss[.ffffff], 返回时间和小数部分的原文本
+fn parse_datetime(value: &str) -> Option<(NaiveDateTime, &str)> {
+    let (seconds, fraction) = match value.find('.') {
+        Some(index) => (&value[..index], &value[index..]),
+        None => (value, ""),
+    };
+    let datetime = NaiveDateTime::parse_from_str(seconds, "%Y-%m-%d %H:%M:%S").ok()?;
+    let micros = match fraction.len() {
+        0 | 1 => 0,
+        len if len <= 7 && fraction[1..].chars().all(|c| c.is_ascii_digit()) => {
+            format!("{:0<6}", &fraction[1..]).parse::<u32>().ok()?
+        }
+        _ => return None,
+    };
+    Some((datetime.with_nanosecond(micros * 1000)?, fraction))
+}
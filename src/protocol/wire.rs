@@ -0,0 +1,116 @@
+use mini_canal_types as types;
+
+use crate::protocol::{Column, Entry, EntryType, EventType, Header, RowChange, RowData};
+
+/**
+ * <pre>
+ *  转换为mini-canal-types中的对外类型, 下游服务只依赖mini-canal-types即可反序列化:
+ *      Entry       source_change只保留message
+ *      Header      位点信息放在Position中, commit timestamp/statement不输出
+ *      RowChange   lazy列在转换时解码
+ * </pre>
+ */
+impl From<&Entry> for types::Entry {
+    fn from(entry: &Entry) -> types::Entry {
+        let mut wire = types::Entry::new(entry_type(entry.entry_type()), entry.header().into());
+        if let Some(row_change) = entry.row_change() {
+            wire.set_row_change(row_change.into());
+        }
+        wire.set_transaction_id(entry.transaction_id());
+        wire.set_message(entry.message());
+        wire
+    }
+}
+
+impl From<&Header> for types::Position {
+    fn from(header: &Header) -> types::Position {
+        let mut position = types::Position::new(header.log_file_name(), header.log_file_offset());
+        position.set_timestamp(header.execute_time());
+        position.set_server_id(header.server_id());
+        position.set_gtid(header.gtid());
+        position
+    }
+}
+
+impl From<&Header> for types::Header {
+    fn from(header: &Header) -> types::Header {
+        let mut wire = types::Header::new(header.into(), header.schema_name(), header.table_name());
+        wire.set_event_type(header.event_type().map_or(types::EventType::Unknown, event_type));
+        wire.set_event_length(header.event_length());
+        wire.set_part(header.part());
+        wire
+    }
+}
+
+impl From<&RowChange> for types::RowChange {
+    fn from(row_change: &RowChange) -> types::RowChange {
+        let mut wire = types::RowChange::new(event_type(row_change.event_type()), row_change.table_id());
+        if row_change.is_ddl() {
+            wire.set_ddl(row_change.sql(), row_change.ddl_schema_name());
+        }
+        for row_data in row_change.row_datas() {
+            wire.add_row(row_data.into());
+        }
+        wire
+    }
+}
+
+impl From<&RowData> for types::RowData {
+    fn from(row_data: &RowData) -> types::RowData {
+        types::RowData::new(row_data.before_columns().iter().map(types::ColumnValue::from).collect(),
+                            row_data.after_columns().iter().map(types::ColumnValue::from).collect())
+    }
+}
+
+impl From<&Column> for types::ColumnValue {
+    fn from(column: &Column) -> types::ColumnValue {
+        let mut wire = types::ColumnValue::new(column.index() as u32, column.name(), column.mysql_type(),
+                                               column.sql_type());
+        wire.set_is_key(column.is_key());
+        wire.set_updated(column.updated());
+        wire.set_value((!column.is_null()).then(|| column.value()));
+        wire
+    }
+}
+
+pub fn entry_type(entry_type: EntryType) -> types::EntryType {
+    match entry_type {
+        EntryType::TransactionBegin => types::EntryType::TransactionBegin,
+        EntryType::RowData => types::EntryType::RowData,
+        EntryType::TransactionEnd => types::EntryType::TransactionEnd,
+        EntryType::Heartbeat => types::EntryType::Heartbeat,
+        EntryType::GtidLog => types::EntryType::GtidLog,
+        EntryType::Incident => types::EntryType::Incident,
+        EntryType::Info => types::EntryType::Info,
+        EntryType::SourceChanged => types::EntryType::SourceChanged,
+    }
+}
+
+pub fn event_type(event_type: EventType) -> types::EventType {
+    match event_type {
+        EventType::Insert => types::EventType::Insert,
+        EventType::Update => types::EventType::Update,
+        EventType::Delete => types::EventType::Delete,
+        EventType::Create => types::EventType::Create,
+        EventType::Alter => types::EventType::Alter,
+        EventType::Erase => types::EventType::Erase,
+        EventType::Query => types::EventType::Query,
+        EventType::Truncate => types::EventType::Truncate,
+        EventType::Rename => types::EventType::Rename,
+        EventType::CIndex => types::EventType::CIndex,
+        EventType::DIndex => types::EventType::DIndex,
+        EventType::Gtid => types::EventType::Gtid,
+        EventType::XaCommit => types::EventType::XaCommit,
+        EventType::XaRollback => types::EventType::XaRollback,
+        EventType::MHeartbeat => types::EventType::MHeartbeat,
+    }
+}
+
+// 对外的表结构, 取entry中第一行的列, DDL以及没有行的entry返回None
+pub fn table_schema(entry: &Entry) -> Option<types::TableSchema> {
+    let row_data = entry.row_change().filter(|row_change| !row_change.is_ddl())?.row_datas().first()?;
+    let wire = types::RowData::from(row_data);
+    let columns = if wire.after().is_empty() { wire.before() } else { wire.after() };
+    Some(types::TableSchema::new(entry.header().schema_name(), entry.header().table_name(),
+                                 columns.iter().map(types::ColumnSchema::from).collect()))
+}
@@ -0,0 +1,197 @@
+use crate::channel::mysql_socket::MysqlConnector;
+use crate::protocol::{Column, Entry, EventType, RowChange};
+use crate::sink::parallel::{ApplierFactory, RowApplier};
+use crate::verify::dialect::{SqlDialect, SqlTarget, NO_BACKSLASH_ESCAPES_SQL};
+
+/**
+ * <pre>
+ *  执行SqlApplier生成的sql, 不返回结果集.
+ *  mysql/mariadb由MysqlConnector实现; postgres没有内置的客户端, 由使用方用自己的驱动实现
+ * </pre>
+ */
+pub trait SqlExecutor: Send {
+    fn execute(&mut self, sql: &str) -> Result<(), String>;
+}
+
+impl SqlExecutor for MysqlConnector {
+    // 断开之后重新连接, 未提交的事务由目标库回滚, lane失败后从上次确认的位点重新dump.
+    // 每次连接之后设置NO_BACKSLASH_ESCAPES, 与SqlDialect::quote_value的转义方式一致
+    fn execute(&mut self, sql: &str) -> Result<(), String> {
+        if !self.is_connected() {
+            self.connect().map_err(|e| e.to_string())?;
+            self.update(NO_BACKSLASH_ESCAPES_SQL).map_err(|e| e.to_string())?;
+        }
+        self.update(sql).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+/**
+ * <pre>
+ *  按SqlDialect把行变更转换为sql在目标库中执行, ParallelApplySink中每个lane一个实例:
+ *      insert      按主键upsert整行(REPLACE INTO / ON CONFLICT DO UPDATE), 重新dump后重复执行结果不变;
+ *                  没有主键的表为普通的INSERT
+ *      update      UPDATE ... SET after image中的列 WHERE before image的主键,
+ *                  binlog_row_image=MINIMAL时after image只有变化的列, 同样适用
+ *      delete      DELETE ... WHERE before image的主键
+ *  没有主键的表按before image的所有列定位行, NULL值使用IS NULL.
+ *  第一个行变更之前执行BEGIN, flush时COMMIT.
+ *  DDL先提交当前的事务再执行, mysql/mariadb中先USE到DDL所在的库; 其它目标库不执行mysql的DDL, 只打印日志
+ * </pre>
+ */
+pub struct SqlApplier {
+    executor: Box<dyn SqlExecutor>,
+    dialect: SqlDialect,
+    in_transaction: bool,
+    statements: u64,
+    commits: u64,
+}
+
+impl SqlApplier {
+    pub fn new(executor: Box<dyn SqlExecutor>, dialect: SqlDialect) -> SqlApplier {
+        SqlApplier { executor, dialect, in_transaction: false, statements: 0, commits: 0 }
+    }
+
+    pub fn dialect(&self) -> &SqlDialect {
+        &self.dialect
+    }
+    // 执行的行变更和DDL语句数, 不包括BEGIN/COMMIT
+    pub fn statements(&self) -> u64 {
+        self.statements
+    }
+    pub fn commits(&self) -> u64 {
+        self.commits
+    }
+
+    // entry对应的行变更语句, DDL返回空
+    pub fn row_statements(&self, entry: &Entry) -> Vec<String> {
+        let row_change = match entry.row_change() {
+            Some(row_change) if !row_change.is_ddl() => row_change,
+            _ => return vec![],
+        };
+        let (schema, table) = (entry.header().schema_name(), entry.header().table_name());
+        let table_name = self.dialect.table_name(schema, table);
+        row_change.row_datas().iter().filter_map(|row| {
+            match row_change.event_type() {
+                EventType::Insert => Some(self.insert(schema, table, row.after_columns())),
+                EventType::Update => {
+                    let values = row.after_columns().iter()
+                        .map(|column| format!("{} = {}", self.dialect.quote_identifier(column.name()), self.value(column)))
+                        .collect::<Vec<String>>();
+                    Some(format!("UPDATE {} SET {} WHERE {};", table_name, values.join(", "),
+                                 self.condition(row.before_columns())))
+                }
+                EventType::Delete => Some(format!("DELETE FROM {} WHERE {};", table_name, self.condition(row.before_columns()))),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    fn insert(&self, schema: &str, table: &str, columns: &[Column]) -> String {
+        let names: Vec<String> = columns.iter().map(|column| column.name().to_string()).collect();
+        let values: Vec<String> = columns.iter().map(|column| self.value(column)).collect();
+        let keys: Vec<String> = columns.iter().filter(|column| column.is_key()).map(|column| column.name().to_string()).collect();
+        if keys.is_empty() {
+            return format!("INSERT INTO {} ({}) VALUES ({});", self.dialect.table_name(schema, table),
+                           self.dialect.column_list(&names), values.join(", "));
+        }
+        self.dialect.upsert(schema, table, &names, &keys, &values)
+    }
+
+    // 有主键时按主键, 否则按所有列
+    fn condition(&self, columns: &[Column]) -> String {
+        let keys: Vec<&Column> = columns.iter().filter(|column| column.is_key()).collect();
+        let columns: Vec<&Column> = if keys.is_empty() { columns.iter().collect() } else { keys };
+        columns.iter().map(|column| {
+            let name = self.dialect.quote_identifier(column.name());
+            if column.is_null() {
+                format!("{} IS NULL", name)
+            } else {
+                format!("{} = {}", name, self.dialect.quote_value(column.value()))
+            }
+        }).collect::<Vec<String>>().join(" AND ")
+    }
+
+    fn value(&self, column: &Column) -> String {
+        if column.is_null() {
+            "NULL".to_string()
+        } else {
+            self.dialect.quote_value(column.value())
+        }
+    }
+
+    fn execute(&mut self, sql: &str) -> Result<(), String> {
+        self.executor.execute(sql)
+    }
+
+    fn apply_ddl(&mut self, entry: &Entry, row_change: &RowChange) -> Result<(), String> {
+        self.flush()?;
+        if self.dialect.target() == SqlTarget::Postgres {
+            println!("skip ddl at {}:{} for {} target: {}", entry.header().log_file_name(),
+                     entry.header().log_file_offset(), self.dialect.target().name(), row_change.sql());
+            return Ok(());
+        }
+        if !row_change.ddl_schema_name().is_empty() {
+            let sql = format!("USE {}", self.dialect.quote_identifier(row_change.ddl_schema_name()));
+            self.execute(&sql)?;
+        }
+        self.execute(row_change.sql())?;
+        self.statements += 1;
+        Ok(())
+    }
+}
+
+impl RowApplier for SqlApplier {
+    fn apply(&mut self, entry: &Entry) -> Result<(), String> {
+        let row_change = match entry.row_change() {
+            Some(row_change) => row_change,
+            None => return Ok(()),
+        };
+        if row_change.is_ddl() {
+            return self.apply_ddl(entry, row_change);
+        }
+        let statements = self.row_statements(entry);
+        if statements.is_empty() {
+            return Ok(());
+        }
+        if !self.in_transaction {
+            self.execute("BEGIN")?;
+            self.in_transaction = true;
+        }
+        for sql in statements {
+            self.execute(&sql)?;
+            self.statements += 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if self.in_transaction {
+            self.execute("COMMIT")?;
+            self.in_transaction = false;
+            self.commits += 1;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * <pre>
+ *  每个lane调用connect(lane)得到自己的连接, 例如postgres:
+ *      let factory = sql_applier_factory(|_| Ok(Box::new(PgExecutor::connect(url)?)), SqlDialect::new(SqlTarget::Postgres));
+ *      let sink = ParallelApplySink::from_config(factory, &config)?;
+ * </pre>
+ */
+pub fn sql_applier_factory<F>(connect: F, dialect: SqlDialect) -> ApplierFactory
+    where F: Fn(usize) -> Result<Box<dyn SqlExecutor>, String> + Send + 'static {
+    Box::new(move |lane| Ok(Box::new(SqlApplier::new(connect(lane)?, dialect.clone())) as Box<dyn RowApplier>))
+}
+
+// mysql/mariadb目标库, 每个lane使用connector.fork()的连接
+pub fn mysql_applier_factory(connector: MysqlConnector, dialect: SqlDialect) -> ApplierFactory {
+    sql_applier_factory(move |lane| {
+        let mut connector = connector.fork();
+        connector.connect().map_err(|e| format!("connect apply lane {} failure: {}", lane, e))?;
+        connector.update(NO_BACKSLASH_ESCAPES_SQL).map_err(|e| format!("set sql_mode on apply lane {} failure: {}", lane, e))?;
+        Ok(Box::new(connector) as Box<dyn SqlExecutor>)
+    }, dialect)
+}
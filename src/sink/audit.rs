@@ -0,0 +1,231 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use crate::instance::EntryPosition;
+use crate::protocol::{Column, Entry, EntryType, EventType};
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    // 位点回退
+    PositionRegression,
+    // BEGIN之前的事务没有COMMIT/Xid
+    MissingTransactionEnd,
+    // COMMIT/Xid之前没有BEGIN
+    MissingTransactionBegin,
+    // 事务外出现DML
+    RowDataOutsideTransaction,
+    // 同一事务中同一张表出现重复主键
+    DuplicatePrimaryKey,
+}
+
+#[derive(Debug, Clone)]
+pub struct Violation {
+    kind: ViolationKind,
+    position: EntryPosition,
+    message: String,
+}
+
+impl Violation {
+    pub fn kind(&self) -> &ViolationKind {
+        &self.kind
+    }
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} at {}:{}: {}", self.kind, self.position.journal_name(), self.position.position(), self.message)
+    }
+}
+
+/**
+ * <pre>
+ *  校验投递顺序的sink, 不改变数据, 只记录并打印违反约束的entry:
+ *  1. 位点单调递增
+ *  2. 每个BEGIN都有对应的COMMIT/Xid, DML必须在事务内
+ *  3. 同一事务内, 同一张表的主键不会重复插入
+ *  可以包装一个下游sink, 校验后继续投递
+ * </pre>
+ */
+pub struct AuditSink {
+    inner: Option<Box<dyn EventSink>>,
+    last_position: Option<EntryPosition>,
+    in_transaction: bool,
+    // schema.table -> 当前事务内存在的主键
+    keys: HashMap<String, HashSet<Vec<String>>>,
+    violations: Vec<Violation>,
+    max_violations: usize,
+}
+
+impl Default for AuditSink {
+    fn default() -> Self {
+        AuditSink::new()
+    }
+}
+
+impl AuditSink {
+    pub fn new() -> AuditSink {
+        AuditSink {
+            inner: None,
+            last_position: None,
+            in_transaction: false,
+            keys: HashMap::new(),
+            violations: vec![],
+            max_violations: 1024,
+        }
+    }
+
+    pub fn wrap(inner: Box<dyn EventSink>) -> AuditSink {
+        AuditSink { inner: Some(inner), ..AuditSink::new() }
+    }
+
+    // 最多保留的violation数量, 超过后只打印不保存
+    pub fn set_max_violations(&mut self, max_violations: usize) {
+        self.max_violations = max_violations;
+    }
+
+    pub fn violations(&self) -> &Vec<Violation> {
+        &self.violations
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn audit(&mut self, entry: &Entry) {
+        let position = entry.header().position();
+        if let Some(last) = &self.last_position {
+            if position.compare(last).is_lt() {
+                let message = format!("position went back from {}:{}", last.journal_name(), last.position());
+                self.report(ViolationKind::PositionRegression, &position, message);
+            }
+        }
+        self.last_position = Some(position.clone());
+
+        match entry.entry_type() {
+            EntryType::TransactionBegin => {
+                if self.in_transaction {
+                    self.report(ViolationKind::MissingTransactionEnd, &position, "BEGIN inside an open transaction".to_string());
+                }
+                self.begin();
+            }
+            EntryType::TransactionEnd => {
+                if !self.in_transaction {
+                    self.report(ViolationKind::MissingTransactionBegin, &position, "COMMIT without BEGIN".to_string());
+                }
+                self.in_transaction = false;
+                self.keys.clear();
+            }
+            EntryType::RowData => self.audit_rows(entry, &position),
+            _ => {}
+        }
+    }
+
+    fn begin(&mut self) {
+        self.in_transaction = true;
+        self.keys.clear();
+    }
+
+    fn audit_rows(&mut self, entry: &Entry, position: &EntryPosition) {
+        let row_change = match entry.row_change() {
+            Some(row_change) => row_change,
+            None => return,
+        };
+        if row_change.is_ddl() || !row_change.event_type().is_dml() {
+            return;
+        }
+        if !self.in_transaction {
+            self.report(ViolationKind::RowDataOutsideTransaction, position,
+                        format!("{:?} on {} outside transaction", row_change.event_type(), table(entry)));
+            self.begin();
+        }
+        let table = table(entry);
+        for row_data in row_change.row_datas() {
+            let before = primary_key(row_data.before_columns());
+            let after = primary_key(row_data.after_columns());
+            let keys = self.keys.entry(table.clone()).or_default();
+            let duplicate = match row_change.event_type() {
+                EventType::Insert => after.filter(|key| !keys.insert(key.clone())),
+                EventType::Update => {
+                    match (before, after) {
+                        (Some(before), Some(after)) if before != after => {
+                            keys.remove(&before);
+                            Some(after).filter(|key| !keys.insert(key.clone()))
+                        }
+                        (_, Some(after)) => {
+                            keys.insert(after);
+                            None
+                        }
+                        _ => None,
+                    }
+                }
+                _ => {
+                    if let Some(before) = before {
+                        keys.remove(&before);
+                    }
+                    None
+                }
+            };
+            if let Some(key) = duplicate {
+                self.report(ViolationKind::DuplicatePrimaryKey, position,
+                            format!("duplicate primary key {:?} on {}", key, table));
+            }
+        }
+    }
+
+    fn report(&mut self, kind: ViolationKind, position: &EntryPosition, message: String) {
+        let violation = Violation { kind, position: position.clone(), message };
+        eprintln!("audit violation: {}", violation);
+        if self.violations.len() < self.max_violations {
+            self.violations.push(violation);
+        }
+    }
+}
+
+impl EventSink for AuditSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.audit(entry);
+        match self.inner.as_mut() {
+            Some(inner) => inner.on_event(entry),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.as_ref().is_some_and(|inner| inner.is_backpressured())
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.reconfigure(config),
+            None => Ok(()),
+        }
+    }
+}
+
+fn table(entry: &Entry) -> String {
+    format!("{}.{}", entry.header().schema_name(), entry.header().table_name())
+}
+
+// 没有主键的表返回None, 不参与校验
+fn primary_key(columns: &[Column]) -> Option<Vec<String>> {
+    let key: Vec<String> = columns.iter()
+        .filter(|column| column.is_key())
+        .map(|column| column.value().to_string())
+        .collect();
+    if key.is_empty() { None } else { Some(key) }
+}
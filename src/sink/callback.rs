@@ -0,0 +1,84 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::protocol::Entry;
+use crate::sink::EventSink;
+
+/**
+ * <pre>
+ *  消费者和parser之间共享的背压开关, 可以clone到消费者的线程中:
+ *  消费者处理不过来时调用pause, parser读完当前event之后停止从socket读取, 直到消费者调用resume.
+ *  暂停期间未读取的数据留在socket缓冲中, 暂停超过master的net_write_timeout时master会断开连接,
+ *  parser恢复后按正常的重连流程从上次投递的位点继续
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Backpressure {
+    paused: Arc<AtomicBool>,
+}
+
+impl Backpressure {
+    pub fn new() -> Backpressure {
+        Backpressure::default()
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+/**
+ * <pre>
+ *  把闭包适配为EventSink, 嵌入到其它程序中时不需要为消费逻辑单独定义类型:
+ *      let backpressure = Backpressure::new();
+ *      let sink = CallbackSink::new(move |entry| queue.push(entry.clone()))
+ *          .with_backpressure(backpressure.clone());
+ *      parser.set_entry_sink(Box::new(sink));
+ *  回调按binlog顺序在parser线程中执行, 返回Err时parser停止投递.
+ *  回调中阻塞同样会阻塞parser, 需要异步处理时由回调把entry交给其它线程, 再通过Backpressure控制读取速度
+ * </pre>
+ */
+pub struct CallbackSink<F>
+    where F: FnMut(&Entry) -> Result<(), String> + Send {
+    callback: F,
+    backpressure: Backpressure,
+}
+
+impl<F> CallbackSink<F>
+    where F: FnMut(&Entry) -> Result<(), String> + Send {
+    pub fn new(callback: F) -> CallbackSink<F> {
+        CallbackSink { callback, backpressure: Backpressure::default() }
+    }
+
+    pub fn with_backpressure(mut self, backpressure: Backpressure) -> CallbackSink<F> {
+        self.backpressure = backpressure;
+        self
+    }
+
+    pub fn set_backpressure(&mut self, backpressure: Backpressure) {
+        self.backpressure = backpressure;
+    }
+
+    pub fn backpressure(&self) -> Backpressure {
+        self.backpressure.clone()
+    }
+}
+
+impl<F> EventSink for CallbackSink<F>
+    where F: FnMut(&Entry) -> Result<(), String> + Send {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        (self.callback)(entry)
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.backpressure.is_paused()
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+
+use crate::instance::canary::{canary_timestamps, DEFAULT_CANARY_SCHEMA, DEFAULT_CANARY_TABLE};
+use crate::metrics::LatencyHistogram;
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+pub const END_TO_END_LAG_METRIC: &str = "canal_end_to_end_lag";
+pub const CANARY_AGE_METRIC: &str = "canal_canary_ack_age";
+
+// 一个sink的端到端延迟
+#[derive(Debug, Clone, Default)]
+pub struct SinkLag {
+    // 最近一次确认的canary的延迟, 毫秒
+    last: u64,
+    // 最近一次确认的时间, 毫秒
+    acked_at: i64,
+    histogram: LatencyHistogram,
+}
+
+impl SinkLag {
+    pub fn last(&self) -> u64 {
+        self.last
+    }
+    pub fn acked_at(&self) -> i64 {
+        self.acked_at
+    }
+    pub fn histogram(&self) -> &LatencyHistogram {
+        &self.histogram
+    }
+}
+
+/**
+ * <pre>
+ *  各个sink的canary延迟, 以sink名字为key, 可以在其它线程中通过句柄导出.
+ *  canary停止流动(parser卡住或者sink阻塞)时last不再变化, 此时需要结合ack_age(距离上次确认的时间)判断:
+ *  ack_age明显大于写入间隔时, ack_age减去写入间隔为当前延迟的下界
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct CanaryLag {
+    sinks: Arc<Mutex<BTreeMap<String, SinkLag>>>,
+}
+
+impl CanaryLag {
+    pub fn new() -> CanaryLag {
+        CanaryLag::default()
+    }
+
+    pub fn record(&self, sink: &str, latency: u64, entry: &Entry) {
+        if let Ok(mut sinks) = self.sinks.lock() {
+            let lag = sinks.entry(sink.to_string()).or_default();
+            lag.last = latency;
+            lag.acked_at = Utc::now().timestamp_millis();
+            lag.histogram.record(latency, &entry.header().position());
+        }
+    }
+
+    pub fn get(&self, sink: &str) -> Option<SinkLag> {
+        self.sinks.lock().ok()?.get(sink).cloned()
+    }
+
+    pub fn sinks(&self) -> Vec<String> {
+        self.sinks.lock().map(|sinks| sinks.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /**
+     * <pre>
+     *  OpenMetrics格式:
+     *  canal_end_to_end_lag{sink="kafka"} 35
+     *  canal_canary_ack_age{sink="kafka"} 420
+     * </pre>
+     */
+    pub fn to_open_metrics(&self) -> String {
+        let sinks = match self.sinks.lock() {
+            Ok(sinks) => sinks.clone(),
+            Err(_) => return String::new(),
+        };
+        let now = Utc::now().timestamp_millis();
+        let mut out = String::new();
+        for (name, value) in [(END_TO_END_LAG_METRIC, None), (CANARY_AGE_METRIC, Some(now))] {
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "# UNIT {} milliseconds", name);
+            for (sink, lag) in sinks.iter() {
+                let metric = match value {
+                    Some(now) => (now - lag.acked_at).max(0) as u64,
+                    None => lag.last,
+                };
+                let _ = writeln!(out, "{}{{sink=\"{}\"}} {}", name, sink, metric);
+            }
+        }
+        out
+    }
+}
+
+/**
+ * <pre>
+ *  包装下游sink, 下游确认canary表的行之后记录 当前时间 - 写入时间 作为该sink的端到端延迟.
+ *  canary行同样交给下游, 确认的语义与业务数据相同, 下游不需要canary数据时自行忽略canary表.
+ *  需要与CanaryWriter使用相同的canary表
+ * </pre>
+ */
+pub struct CanarySink {
+    name: String,
+    inner: Box<dyn EventSink>,
+    lag: CanaryLag,
+    schema: String,
+    table: String,
+}
+
+impl CanarySink {
+    pub fn new(name: &str, inner: Box<dyn EventSink>, lag: CanaryLag) -> CanarySink {
+        CanarySink {
+            name: name.to_string(),
+            inner,
+            lag,
+            schema: DEFAULT_CANARY_SCHEMA.to_string(),
+            table: DEFAULT_CANARY_TABLE.to_string(),
+        }
+    }
+
+    pub fn set_table(&mut self, schema: &str, table: &str) {
+        self.schema = schema.to_string();
+        self.table = table.to_string();
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn lag(&self) -> CanaryLag {
+        self.lag.clone()
+    }
+}
+
+impl EventSink for CanarySink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.inner.on_event(entry)?;
+        let timestamps = canary_timestamps(entry, &self.schema, &self.table);
+        if !timestamps.is_empty() {
+            let now = Utc::now().timestamp_millis();
+            for ts in timestamps {
+                self.lag.record(&self.name, (now - ts).max(0) as u64, entry);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::instance::control::{control_requests, ControlCommand, ControlRequest, TableSnapshotter, DEFAULT_CONTROL_SCHEMA,
+                               DEFAULT_CONTROL_TABLE};
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+// 暂停期间最多缓存的entry数, 超过之后返回Err
+pub const DEFAULT_MAX_PAUSED_ENTRIES: usize = 100_000;
+
+#[derive(Debug, Clone, Default)]
+pub struct ControlState {
+    paused: bool,
+    // 暂停期间缓存的entry数
+    buffered: usize,
+    applied: u64,
+    // 无法识别或者执行失败的命令数
+    rejected: u64,
+    // 最近处理的命令, 例如 resnapshot shop.orders (id=12) at mysql-bin.000001:1024
+    last_command: Option<String>,
+    last_error: Option<String>,
+    // 已处理的最大id, 重新dump时重复收到的行不再执行
+    last_id: Option<u64>,
+}
+
+impl ControlState {
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+    pub fn applied(&self) -> u64 {
+        self.applied
+    }
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+    pub fn last_command(&self) -> Option<&str> {
+        self.last_command.as_deref()
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    pub fn last_id(&self) -> Option<u64> {
+        self.last_id
+    }
+}
+
+/**
+ * <pre>
+ *  包装下游sink, 把源库control表(见instance::control::control_requests)的行解释为命令:
+ *      pause       之后的entry缓存在本sink中, 不再交给下游; 超过max_paused_entries时返回Err, 由调用方决定重试或退出
+ *      resume      按顺序投递缓存的entry, 之后正常投递
+ *      resnapshot  调用TableSnapshotter对该表做全量快照, 暂停期间同样立即执行
+ *  暂停期间control表的行仍然会被处理, 因此可以通过同一张表恢复. control表的行不交给下游.
+ *  id不大于已处理的最大id的行被忽略, 避免重新dump时重复执行; 进程重启之后该状态不保留
+ * </pre>
+ */
+pub struct ControlSink {
+    instance: String,
+    inner: Box<dyn EventSink>,
+    schema: String,
+    table: String,
+    snapshotter: Option<Box<dyn TableSnapshotter>>,
+    paused: VecDeque<Entry>,
+    max_paused_entries: usize,
+    state: Arc<Mutex<ControlState>>,
+}
+
+impl ControlSink {
+    pub fn new(instance: &str, inner: Box<dyn EventSink>) -> ControlSink {
+        ControlSink {
+            instance: instance.to_string(),
+            inner,
+            schema: DEFAULT_CONTROL_SCHEMA.to_string(),
+            table: DEFAULT_CONTROL_TABLE.to_string(),
+            snapshotter: None,
+            paused: VecDeque::new(),
+            max_paused_entries: DEFAULT_MAX_PAUSED_ENTRIES,
+            state: Arc::new(Mutex::new(ControlState::default())),
+        }
+    }
+
+    pub fn set_table(&mut self, schema: &str, table: &str) {
+        self.schema = schema.to_string();
+        self.table = table.to_string();
+    }
+
+    pub fn set_snapshotter(&mut self, snapshotter: Box<dyn TableSnapshotter>) {
+        self.snapshotter = Some(snapshotter);
+    }
+
+    pub fn set_max_paused_entries(&mut self, max_paused_entries: usize) {
+        self.max_paused_entries = max_paused_entries;
+    }
+
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+    pub fn max_paused_entries(&self) -> usize {
+        self.max_paused_entries
+    }
+
+    // 句柄可以在其它线程中读取
+    pub fn state(&self) -> Arc<Mutex<ControlState>> {
+        self.state.clone()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().map(|state| state.paused).unwrap_or(false)
+    }
+
+    fn update_state<F: FnOnce(&mut ControlState)>(&self, f: F) {
+        if let Ok(mut state) = self.state.lock() {
+            f(&mut state);
+        }
+    }
+
+    fn apply(&mut self, request: &ControlRequest) -> Result<(), String> {
+        let id = request.id().parse::<u64>().ok();
+        if let (Some(id), Some(last_id)) = (id, self.state.lock().ok().and_then(|state| state.last_id)) {
+            if id <= last_id {
+                return Ok(());
+            }
+        }
+        let position = request.position();
+        let is_resume = request.command() == Ok(&ControlCommand::Resume);
+        let result = match request.command() {
+            Ok(ControlCommand::Pause) => {
+                self.update_state(|state| state.paused = true);
+                Ok(())
+            }
+            Ok(ControlCommand::Resume) => self.resume(),
+            Ok(ControlCommand::Resnapshot { schema, table }) => match self.snapshotter.as_mut() {
+                Some(snapshotter) => snapshotter.snapshot_table(schema, table, position),
+                None => Err(format!("resnapshot {}.{} is not supported, no table snapshotter is configured", schema, table)),
+            },
+            Err(e) => Err(e.to_string()),
+        };
+        let command = match request.command() {
+            Ok(ControlCommand::Resnapshot { schema, table }) => format!("resnapshot {}.{}", schema, table),
+            Ok(command) => command.name().to_string(),
+            Err(_) => "unknown".to_string(),
+        };
+        let description = format!("{} (id={}) at {}:{}", command, request.id(), position.journal_name(), position.position());
+        match &result {
+            Ok(()) => println!("control command {} of {} is applied", description, self.instance),
+            Err(e) => println!("control command {} of {} is rejected: {}", description, self.instance, e),
+        }
+        self.update_state(|state| {
+            match &result {
+                Ok(()) => state.applied += 1,
+                Err(e) => {
+                    state.rejected += 1;
+                    state.last_error = Some(format!("{}: {}", description, e));
+                }
+            }
+            state.last_command = Some(description);
+            // 下游失败的resume在重新投递时可以再次执行
+            if id.is_some() && !(is_resume && result.is_err()) {
+                state.last_id = id;
+            }
+        });
+        // 命令本身的错误不影响数据的投递, 下游的错误需要返回
+        if is_resume { result } else { Ok(()) }
+    }
+
+    fn resume(&mut self) -> Result<(), String> {
+        while let Some(entry) = self.paused.front() {
+            self.inner.on_event(entry)?;
+            self.paused.pop_front();
+        }
+        self.update_state(|state| {
+            state.paused = false;
+            state.buffered = 0;
+        });
+        Ok(())
+    }
+}
+
+impl EventSink for ControlSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        let header = entry.header();
+        if header.schema_name() == self.schema && header.table_name() == self.table {
+            for request in control_requests(entry, &self.schema, &self.table, &self.instance) {
+                self.apply(&request)?;
+            }
+            return Ok(());
+        }
+        if !self.is_paused() {
+            return self.inner.on_event(entry);
+        }
+        if self.paused.len() >= self.max_paused_entries {
+            return Err(format!("{} is paused by control table {}.{} and {} entries are buffered, resume it to continue",
+                               self.instance, self.schema, self.table, self.paused.len()));
+        }
+        self.paused.push_back(entry.clone());
+        let buffered = self.paused.len();
+        self.update_state(|state| state.buffered = buffered);
+        Ok(())
+    }
+
+    // 暂停期间缓存的entry保留到resume
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
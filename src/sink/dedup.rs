@@ -0,0 +1,131 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::metrics::rate;
+use crate::metrics::{RateKind, StreamMetrics};
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::EventSink;
+
+// 最近投递的entry数量, 需要大于重连时回退的最大事务的entry数
+pub const DEFAULT_DEDUP_CAPACITY: usize = 65536;
+
+/**
+ * <pre>
+ *  entry的幂等key: <binlog文件>:<offset>:<entry类型>, 同一个event重新dump之后key不变.
+ *  SizeLimitSink拆分出的各部分共享同一个header, key加上:<part>区分, 重新拆分时part不变.
+ *  heartbeat以及不是来自binlog的entry(没有位点的Incident/Info)返回None
+ * </pre>
+ */
+pub fn idempotency_key(entry: &Entry) -> Option<String> {
+    let header = entry.header();
+    if entry.entry_type() == EntryType::Heartbeat || header.log_file_name().is_empty() || header.log_file_offset() == 0 {
+        return None;
+    }
+    let key = format!("{}:{}:{:?}", header.log_file_name(), header.log_file_offset(), entry.entry_type());
+    Some(match header.part() {
+        Some(part) => format!("{}:{}", key, part),
+        None => key,
+    })
+}
+
+/**
+ * <pre>
+ *  包装没有幂等能力的下游sink, 丢弃最近capacity个entry中已经投递过的entry:
+ *  重连或者事务回滚后parser从事务开始的位点重新dump, 已经交给下游的entry会再次出现.
+ *  只记录下游返回Ok的entry, 下游失败的entry重试时仍然会投递.
+ *  超过capacity时淘汰最早的key, 回退超过该范围的重复entry不能被识别.
+ *  设置了metrics时丢弃的entry计入该流的duplicates
+ *  配置: dedup_capacity=65536
+ * </pre>
+ */
+pub struct DedupSink {
+    inner: Box<dyn EventSink>,
+    capacity: usize,
+    keys: HashSet<String>,
+    // 按投递顺序, 用于淘汰
+    order: VecDeque<String>,
+    suppressed: u64,
+    metrics: Option<Arc<Mutex<StreamMetrics>>>,
+}
+
+impl DedupSink {
+    pub fn new(inner: Box<dyn EventSink>, capacity: usize) -> DedupSink {
+        DedupSink {
+            inner,
+            capacity: capacity.max(1),
+            keys: HashSet::new(),
+            order: VecDeque::new(),
+            suppressed: 0,
+            metrics: None,
+        }
+    }
+
+    pub fn from_config(inner: Box<dyn EventSink>, config: &SinkConfig) -> Result<DedupSink, String> {
+        let capacity = parse_or(config, "dedup_capacity", DEFAULT_DEDUP_CAPACITY)?;
+        if capacity == 0 {
+            return Err("dedup_capacity must be greater than 0".to_string());
+        }
+        Ok(DedupSink::new(inner, capacity))
+    }
+
+    // 通常为MysqlEventParser::metrics()
+    pub fn set_metrics(&mut self, metrics: Arc<Mutex<StreamMetrics>>) {
+        self.metrics = Some(metrics);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+    // 当前记录的key数量
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn remember(&mut self, key: String) {
+        if self.order.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        self.keys.insert(key.clone());
+        self.order.push_back(key);
+    }
+}
+
+impl EventSink for DedupSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        let key = match idempotency_key(entry) {
+            Some(key) => key,
+            None => return self.inner.on_event(entry),
+        };
+        if self.keys.contains(&key) {
+            self.suppressed += 1;
+            if let Some(metrics) = self.metrics.as_ref() {
+                rate::mark(metrics, RateKind::Duplicates, 1);
+            }
+            return Ok(());
+        }
+        self.inner.on_event(entry)?;
+        self.remember(key);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
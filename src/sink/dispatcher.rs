@@ -0,0 +1,239 @@
+use crate::config::{ConfigChange, Properties};
+use std::path::Path;
+
+use crate::filter::{ColumnProjection, EventFilter, RegexFilter, ReplicationFilter};
+use crate::instance::describe::TableSchemas;
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+// destination.<name>.filter, destination.<name>.replicate_file, destination.<name>.projection
+pub const FILTER_KEY_PREFIX: &str = "destination.";
+// destination.<name>.sink.<key>, 去掉前缀之后作为sink的SinkConfig
+pub const SINK_KEY_INFIX: &str = ".sink.";
+
+// 一个消费方, 拥有独立的订阅filter, 列投影和sink
+pub struct Destination {
+    name: String,
+    filter: Option<Box<dyn EventFilter>>,
+    projection: Option<ColumnProjection>,
+    sink: Box<dyn EventSink>,
+    delivered: u64,
+    filtered: u64,
+}
+
+impl Destination {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+    pub fn filtered(&self) -> u64 {
+        self.filtered
+    }
+    pub fn projection(&self) -> Option<&ColumnProjection> {
+        self.projection.as_ref()
+    }
+
+    fn accept(&self, entry: &Entry) -> bool {
+        self.filter.as_ref().map(|filter| filter.filter(entry)).unwrap_or(true)
+    }
+}
+
+/**
+ * <pre>
+ *  多个destination共享同一个解析结果:
+ *  binlog只解析一次, 每个entry按各个destination自己的filter独立判断,
+ *  通过的entry以引用的方式交给对应的sink, 序列化等工作由各个sink自己完成.
+ *  配置了列投影的destination收到的是裁剪之后的拷贝, 只有命中投影的DML才会拷贝.
+ *  destination按注册顺序投递, 某个sink返回Err时停止本次投递并带上destination名字返回
+ * </pre>
+ */
+#[derive(Default)]
+pub struct DestinationDispatcher {
+    destinations: Vec<Destination>,
+}
+
+impl DestinationDispatcher {
+    pub fn new() -> DestinationDispatcher {
+        DestinationDispatcher { destinations: vec![] }
+    }
+
+    pub fn register(&mut self, name: &str, filter: Option<Box<dyn EventFilter>>, sink: Box<dyn EventSink>)
+                    -> Result<(), String> {
+        if self.get(name).is_some() {
+            return Err(format!("destination {} is already registered", name));
+        }
+        self.destinations.push(Destination {
+            name: name.to_string(),
+            filter,
+            projection: None,
+            sink,
+            delivered: 0,
+            filtered: 0,
+        });
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> Option<Box<dyn EventSink>> {
+        let index = self.destinations.iter().position(|destination| destination.name == name)?;
+        Some(self.destinations.remove(index).sink)
+    }
+
+    pub fn set_filter(&mut self, name: &str, filter: Option<Box<dyn EventFilter>>) -> Result<(), String> {
+        let destination = self.destinations.iter_mut().find(|destination| destination.name == name)
+            .ok_or_else(|| format!("destination {} is not registered", name))?;
+        destination.filter = filter;
+        Ok(())
+    }
+
+    pub fn set_projection(&mut self, name: &str, projection: Option<ColumnProjection>) -> Result<(), String> {
+        let destination = self.destinations.iter_mut().find(|destination| destination.name == name)
+            .ok_or_else(|| format!("destination {} is not registered", name))?;
+        destination.projection = projection.filter(|projection| !projection.is_empty());
+        Ok(())
+    }
+
+    /**
+     * <pre>
+     *  热加载时按 destination.<name>.filter 和 destination.<name>.projection 更新各个destination的filter和列投影,
+     *  配置被删除时取消. 只更新自己的配置被添加, 修改或者删除的destination, 其它destination的filter
+     *  (包括通过register/set_filter在代码中设置的)保持不变. 所有配置都校验通过之后才会生效.
+     *  destination.<name>.replicate_file 指向replica的my.cnf, 按其中的replicate-*规则过滤(ReplicationFilter),
+     *  不能与filter同时配置
+     * </pre>
+     */
+    pub fn reload_filters(&mut self, change: &ConfigChange) -> Result<(), String> {
+        if !change.touches(FILTER_KEY_PREFIX) {
+            return Ok(());
+        }
+        let properties = change.properties();
+        let mut filters = vec![];
+        let mut projections = vec![];
+        for (index, destination) in self.destinations.iter().enumerate() {
+            let key = format!("{}{}.filter", FILTER_KEY_PREFIX, destination.name);
+            let replicate_key = format!("{}{}.replicate_file", FILTER_KEY_PREFIX, destination.name);
+            if change.touches_key(&key) || change.touches_key(&replicate_key) {
+                let filter = match (properties.get(&key), properties.get(&replicate_key)) {
+                    (Some(_), Some(_)) => return Err(format!("{} and {} can not be used together", key, replicate_key)),
+                    (Some(pattern), None) => Some(Box::new(RegexFilter::new(pattern)?) as Box<dyn EventFilter>),
+                    (None, Some(path)) => Some(Box::new(ReplicationFilter::from_file(Path::new(path))?) as Box<dyn EventFilter>),
+                    (None, None) => None,
+                };
+                filters.push((index, filter));
+            }
+            let key = format!("{}{}.projection", FILTER_KEY_PREFIX, destination.name);
+            if change.touches_key(&key) {
+                let projection = match properties.get(&key) {
+                    Some(config) => Some(ColumnProjection::new(config)?).filter(|projection| !projection.is_empty()),
+                    None => None,
+                };
+                projections.push((index, projection));
+            }
+        }
+        for (index, filter) in filters {
+            self.destinations[index].filter = filter;
+        }
+        for (index, projection) in projections {
+            self.destinations[index].projection = projection;
+        }
+        Ok(())
+    }
+
+    /**
+     * <pre>
+     *  热加载时把 destination.<name>.sink.<key> 交给该destination的sink(EventSink::reconfigure),
+     *  例如 destination.orders.sink.batch_size=500. 只通知自己的配置有变化的destination,
+     *  传入的是去掉前缀之后的完整配置. 某个sink拒绝时返回Err, 已经通知过的destination
+     *  (包括拒绝的那个)按加载之前的配置重新reconfigure, 所有destination保持原来的配置
+     * </pre>
+     */
+    pub fn reload_sinks(&mut self, change: &ConfigChange) -> Result<(), String> {
+        let mut touched = vec![];
+        let mut failure = None;
+        for (index, destination) in self.destinations.iter_mut().enumerate() {
+            let prefix = sink_prefix(&destination.name);
+            if !change.touches(&prefix) {
+                continue;
+            }
+            touched.push(index);
+            if let Err(e) = destination.sink.reconfigure(&sink_config(change.properties(), &prefix)) {
+                failure = Some(format!("destination {} failure: {}", destination.name, e));
+                break;
+            }
+        }
+        let failure = match failure {
+            Some(failure) => failure,
+            None => return Ok(()),
+        };
+        for index in touched {
+            let destination = &mut self.destinations[index];
+            let config = sink_config(change.old_properties(), &sink_prefix(&destination.name));
+            destination.sink.reconfigure(&config)
+                .map_err(|e| format!("{}; rollback destination {} failure: {}", failure, destination.name, e))?;
+        }
+        Err(failure)
+    }
+
+    // 按parser当前的表结构检查所有destination的列投影, 错误信息带上destination名字
+    pub fn validate_projections(&self, schemas: &TableSchemas) -> Result<(), String> {
+        let errors: Vec<String> = self.destinations.iter()
+            .filter_map(|destination| {
+                let projection = destination.projection.as_ref()?;
+                projection.validate(schemas).err().map(|e| format!("destination {}: {}", destination.name, e))
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Destination> {
+        self.destinations.iter().find(|destination| destination.name == name)
+    }
+
+    pub fn destinations(&self) -> &Vec<Destination> {
+        &self.destinations
+    }
+}
+
+fn sink_prefix(name: &str) -> String {
+    format!("{}{}{}", FILTER_KEY_PREFIX, name, SINK_KEY_INFIX)
+}
+
+// 去掉destination.<name>.sink.前缀之后的配置
+fn sink_config(properties: &Properties, prefix: &str) -> SinkConfig {
+    properties.iter()
+        .filter_map(|(key, value)| key.strip_prefix(prefix).map(|key| (key.to_string(), value.clone())))
+        .collect()
+}
+
+impl EventSink for DestinationDispatcher {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        for destination in self.destinations.iter_mut() {
+            if !destination.accept(entry) {
+                destination.filtered += 1;
+                continue;
+            }
+            let projected = destination.projection.as_ref().and_then(|projection| projection.apply(entry));
+            destination.sink.on_event(projected.as_ref().unwrap_or(entry))
+                .map_err(|e| format!("destination {} failure: {}", destination.name, e))?;
+            destination.delivered += 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        for destination in self.destinations.iter_mut() {
+            destination.sink.flush().map_err(|e| format!("destination {} failure: {}", destination.name, e))?;
+        }
+        Ok(())
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.destinations.iter().any(|destination| destination.sink.is_backpressured())
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use chrono::Utc;
+use regex::Regex;
+
+use crate::instance::describe::{MetadataSource, TableDescription, TableSchemas};
+use crate::instance::EntryPosition;
+use crate::protocol::{Column, Entry, EntryType, EventType, Header};
+use crate::sink::registry::SinkConfig;
+use crate::sink::sample::SampleMode;
+use crate::sink::EventSink;
+
+pub const DRIFT_ALERTS_METRIC: &str = "canal_schema_drift_alerts";
+// 等待检查的抽样行, 超出时丢弃
+pub const DEFAULT_DRIFT_QUEUE: usize = 1024;
+// DriftAlerts中保留的最近的告警数
+pub const MAX_RECENT_ALERTS: usize = 100;
+
+/**
+ * <pre>
+ *  对一行的一个image(before或after)做的检查, table为解析这一行时parser跟踪的表结构.
+ *  key_image表示该image中应当包含主键: insert的after, update和delete的before.
+ *  返回发现的问题, 每一项是一条告警信息
+ * </pre>
+ */
+pub trait DriftCheck: Send {
+    fn name(&self) -> &str;
+
+    fn check(&self, table: &TableDescription, image: &[Column], key_image: bool) -> Vec<String>;
+}
+
+// 列的序号/列名/类型与表结构一致, NOT NULL的列没有NULL
+pub struct SchemaCheck;
+
+impl DriftCheck for SchemaCheck {
+    fn name(&self) -> &str {
+        "schema"
+    }
+
+    fn check(&self, table: &TableDescription, image: &[Column], _key_image: bool) -> Vec<String> {
+        let mut problems = vec![];
+        for column in image {
+            let description = match table.columns().get(column.index()) {
+                Some(description) => description,
+                None => {
+                    problems.push(format!("column {} is out of the schema ({} columns)", column.index(),
+                                          table.columns().len()));
+                    continue;
+                }
+            };
+            // 生成的列名(@1)不比较
+            if description.name_source() == MetadataSource::TableMap && column.name() != description.name() {
+                problems.push(format!("column {} is named {}, expect {}", column.index(), column.name(),
+                                      description.name()));
+            }
+            if column.mysql_type() != description.mysql_type() {
+                problems.push(format!("column {} has type {}, expect {}", description.name(), column.mysql_type(),
+                                      description.mysql_type()));
+            }
+            if column.is_null() && !description.nullable() {
+                problems.push(format!("column {} is NOT NULL but the value is NULL", description.name()));
+            }
+        }
+        problems
+    }
+}
+
+// 值在列类型的范围内, 例如tinyint unsigned在[0, 255], decimal(5,2)最多3位整数和2位小数
+pub struct TypeRangeCheck {
+    date: Regex,
+    datetime: Regex,
+    time: Regex,
+    decimal: Regex,
+}
+
+impl TypeRangeCheck {
+    pub fn new() -> TypeRangeCheck {
+        let regex = |pattern: &str| Regex::new(pattern).unwrap_or_else(|e| panic!("invalid pattern {}: {}", pattern, e));
+        TypeRangeCheck {
+            date: regex(r"^\d{4}-\d{2}-\d{2}$"),
+            datetime: regex(r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(\.\d{1,6})?$"),
+            time: regex(r"^-?\d{2,3}:\d{2}:\d{2}(\.\d{1,6})?$"),
+            decimal: regex(r"^-?(\d+)(\.(\d+))?$"),
+        }
+    }
+
+    fn check_value(&self, mysql_type: &str, value: &str) -> Result<(), String> {
+        let unsigned = mysql_type.ends_with(" unsigned");
+        let integer = |bits: u32| -> Result<(), String> {
+            let in_range = if unsigned {
+                value.parse::<u64>().is_ok_and(|value| bits == 64 || value < 1 << bits)
+            } else {
+                value.parse::<i64>().is_ok_and(|value| bits == 64 || (-(1 << (bits - 1))..1 << (bits - 1)).contains(&value))
+            };
+            if in_range { Ok(()) } else { Err(format!("{} is out of range of {}", value, mysql_type)) }
+        };
+        let pattern = |regex: &Regex| -> Result<(), String> {
+            if regex.is_match(value) { Ok(()) } else { Err(format!("{} is not a valid {}", value, mysql_type)) }
+        };
+        match mysql_type.split([' ', '(']).next().unwrap_or("") {
+            "tinyint" => integer(8),
+            "smallint" => integer(16),
+            "mediumint" => integer(24),
+            "int" => integer(32),
+            "bigint" => integer(64),
+            "float" | "double" => value.parse::<f64>().map(|_| ()).map_err(|_| format!("{} is not a valid {}", value, mysql_type)),
+            "year" => match value.parse::<u16>() {
+                Ok(0) | Ok(1901..=2155) => Ok(()),
+                _ => Err(format!("{} is out of range of year", value)),
+            },
+            "date" => pattern(&self.date),
+            "datetime" | "timestamp" => pattern(&self.datetime),
+            "time" => pattern(&self.time),
+            "decimal" => self.check_decimal(mysql_type, value),
+            _ => Ok(()),
+        }
+    }
+
+    // decimal(precision,scale)
+    fn check_decimal(&self, mysql_type: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("{} is out of range of {}", value, mysql_type);
+        let captures = self.decimal.captures(value).ok_or_else(invalid)?;
+        let definition = mysql_type.trim_start_matches("decimal(").split(')').next().unwrap_or("");
+        let (precision, scale) = match definition.split_once(',') {
+            Some((precision, scale)) => (precision.parse::<usize>().unwrap_or(65), scale.parse::<usize>().unwrap_or(30)),
+            None => return Ok(()),
+        };
+        let integer = captures.get(1).map_or("", |m| m.as_str()).trim_start_matches('0');
+        let fraction = captures.get(3).map_or(0, |m| m.as_str().len());
+        if integer.len() > precision.saturating_sub(scale) || fraction > scale {
+            return Err(invalid());
+        }
+        Ok(())
+    }
+}
+
+impl Default for TypeRangeCheck {
+    fn default() -> TypeRangeCheck {
+        TypeRangeCheck::new()
+    }
+}
+
+impl DriftCheck for TypeRangeCheck {
+    fn name(&self) -> &str {
+        "type_range"
+    }
+
+    fn check(&self, table: &TableDescription, image: &[Column], _key_image: bool) -> Vec<String> {
+        image.iter()
+            // 延迟解码的大字段不在这里解码
+            .filter(|column| !column.is_null() && column.lazy_value().is_none())
+            .filter_map(|column| {
+                let description = table.columns().get(column.index())?;
+                self.check_value(description.mysql_type(), column.value()).err()
+                    .map(|e| format!("column {}: {}", description.name(), e))
+            })
+            .collect()
+    }
+}
+
+// 字符列按charset解码之后没有替换字符(U+FFFD), 出现时通常是charset对应错误
+pub struct CharsetCheck;
+
+impl DriftCheck for CharsetCheck {
+    fn name(&self) -> &str {
+        "charset"
+    }
+
+    fn check(&self, table: &TableDescription, image: &[Column], _key_image: bool) -> Vec<String> {
+        image.iter()
+            .filter(|column| !column.is_null() && column.lazy_value().is_none())
+            .filter_map(|column| {
+                let description = table.columns().get(column.index())?;
+                let character = !description.charset().is_empty() && description.charset() != "binary";
+                (character && column.value().contains('\u{FFFD}'))
+                    .then(|| format!("column {} ({}) has invalid characters", description.name(), description.charset()))
+            })
+            .collect()
+    }
+}
+
+// 表结构中有主键时, key image中的主键列都存在, 不为NULL并且标记为key
+pub struct PrimaryKeyCheck;
+
+impl DriftCheck for PrimaryKeyCheck {
+    fn name(&self) -> &str {
+        "primary_key"
+    }
+
+    fn check(&self, table: &TableDescription, image: &[Column], key_image: bool) -> Vec<String> {
+        if !key_image {
+            return vec![];
+        }
+        table.columns().iter().filter(|description| description.pk())
+            .filter_map(|description| match image.iter().find(|column| column.index() == description.index()) {
+                None => Some(format!("primary key column {} is missing", description.name())),
+                Some(column) if column.is_null() => Some(format!("primary key column {} is NULL", description.name())),
+                Some(column) if !column.is_key() => Some(format!("column {} is not marked as key", description.name())),
+                Some(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriftAlert {
+    // schema.table
+    table: String,
+    check: String,
+    message: String,
+    position: EntryPosition,
+}
+
+impl DriftAlert {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+    pub fn check(&self) -> &str {
+        &self.check
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    pub fn position(&self) -> &EntryPosition {
+        &self.position
+    }
+}
+
+#[derive(Debug, Default)]
+struct DriftState {
+    rows_checked: u64,
+    // 队列满时丢弃的抽样行
+    rows_dropped: u64,
+    // parser没有跟踪到表结构而跳过的抽样行
+    rows_untracked: u64,
+    // (schema.table, check) -> 告警数
+    alerts: BTreeMap<(String, String), u64>,
+    recent: VecDeque<DriftAlert>,
+}
+
+/**
+ * <pre>
+ *  DriftValidator的统计和告警, 可以在其它线程中通过句柄查询.
+ *  OpenMetrics格式:
+ *  canal_schema_drift_alerts{table="db.t",check="type_range"} 3
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct DriftAlerts {
+    state: Arc<Mutex<DriftState>>,
+}
+
+impl DriftAlerts {
+    pub fn new() -> DriftAlerts {
+        DriftAlerts::default()
+    }
+
+    pub fn rows_checked(&self) -> u64 {
+        self.state.lock().map(|state| state.rows_checked).unwrap_or(0)
+    }
+
+    pub fn rows_dropped(&self) -> u64 {
+        self.state.lock().map(|state| state.rows_dropped).unwrap_or(0)
+    }
+
+    pub fn rows_untracked(&self) -> u64 {
+        self.state.lock().map(|state| state.rows_untracked).unwrap_or(0)
+    }
+
+    pub fn count(&self, table: &str, check: &str) -> u64 {
+        self.state.lock().ok()
+            .and_then(|state| state.alerts.get(&(table.to_string(), check.to_string())).copied())
+            .unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.state.lock().map(|state| state.alerts.values().sum()).unwrap_or(0)
+    }
+
+    // 最近的告警, 最早的在前
+    pub fn recent(&self) -> Vec<DriftAlert> {
+        self.state.lock().map(|state| state.recent.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    pub fn to_open_metrics(&self) -> String {
+        let alerts = match self.state.lock() {
+            Ok(state) => state.alerts.clone(),
+            Err(_) => return String::new(),
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE {} counter", DRIFT_ALERTS_METRIC);
+        for ((table, check), count) in alerts.iter() {
+            let _ = writeln!(out, "{}{{table=\"{}\",check=\"{}\"}} {}", DRIFT_ALERTS_METRIC, table, check, count);
+        }
+        out
+    }
+
+    fn dropped(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.rows_dropped += 1;
+        }
+    }
+
+    fn untracked(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.rows_untracked += 1;
+        }
+    }
+
+    // 返回该表的该项检查是否是第一次告警
+    fn record(&self, alerts: &[DriftAlert]) -> Vec<bool> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return vec![],
+        };
+        state.rows_checked += 1;
+        let mut first = vec![];
+        for alert in alerts {
+            let count = state.alerts.entry((alert.table.clone(), alert.check.clone())).or_insert(0);
+            *count += 1;
+            first.push(*count == 1);
+            if state.recent.len() >= MAX_RECENT_ALERTS {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(alert.clone());
+        }
+        first
+    }
+}
+
+/**
+ * <pre>
+ *  把解码之后的行与parser跟踪的表结构(TableSchemas, 来自table map)比较, 用于尽早发现解码器的问题:
+ *  解码输出不再符合表结构(值超出类型范围, 字符乱码, 主键缺失等)时, 下游通常不会报错, 数据却已经错了.
+ *  检查是可插拔的, new()包含SchemaCheck/TypeRangeCheck/CharsetCheck/PrimaryKeyCheck, 可以add_check增加.
+ *  每个表的每项检查第一次告警时打印, 所有告警记录在DriftAlerts中, 设置了alert_sink时同时投递Incident entry
+ * </pre>
+ */
+pub struct DriftValidator {
+    checks: Vec<Box<dyn DriftCheck>>,
+    alerts: DriftAlerts,
+    alert_sink: Option<Box<dyn EventSink>>,
+}
+
+impl DriftValidator {
+    pub fn new() -> DriftValidator {
+        let mut validator = DriftValidator::empty();
+        validator.add_check(Box::new(SchemaCheck));
+        validator.add_check(Box::new(TypeRangeCheck::new()));
+        validator.add_check(Box::new(CharsetCheck));
+        validator.add_check(Box::new(PrimaryKeyCheck));
+        validator
+    }
+
+    // 没有任何检查
+    pub fn empty() -> DriftValidator {
+        DriftValidator { checks: vec![], alerts: DriftAlerts::new(), alert_sink: None }
+    }
+
+    pub fn add_check(&mut self, check: Box<dyn DriftCheck>) {
+        self.checks.push(check);
+    }
+
+    pub fn checks(&self) -> Vec<&str> {
+        self.checks.iter().map(|check| check.name()).collect()
+    }
+
+    pub fn set_alert_sink(&mut self, sink: Box<dyn EventSink>) {
+        self.alert_sink = Some(sink);
+    }
+
+    pub fn alerts(&self) -> DriftAlerts {
+        self.alerts.clone()
+    }
+
+    // 检查一个rows entry的所有行, table为None时(没有跟踪到表结构)跳过
+    pub fn validate(&mut self, entry: &Entry, table: Option<&TableDescription>) -> Vec<DriftAlert> {
+        let row_change = match entry.row_change() {
+            Some(row_change) if entry.entry_type() == EntryType::RowData && !row_change.is_ddl() => row_change,
+            _ => return vec![],
+        };
+        let table = match table {
+            Some(table) => table,
+            None => {
+                self.alerts.untracked();
+                return vec![];
+            }
+        };
+        let name = format!("{}.{}", table.schema(), table.table());
+        let key_after = row_change.event_type() == EventType::Insert;
+        let mut alerts = vec![];
+        for row_data in row_change.row_datas() {
+            for (image, key_image) in [(row_data.before_columns(), !key_after), (row_data.after_columns(), key_after)] {
+                if image.is_empty() {
+                    continue;
+                }
+                for check in self.checks.iter() {
+                    alerts.extend(check.check(table, image, key_image).into_iter().map(|message| DriftAlert {
+                        table: name.clone(),
+                        check: check.name().to_string(),
+                        message,
+                        position: entry.header().position(),
+                    }));
+                }
+            }
+        }
+        let first = self.alerts.record(&alerts);
+        for (alert, first) in alerts.iter().zip(first) {
+            if first {
+                println!("schema drift on {} ({}) at {}:{}: {}", alert.table, alert.check,
+                         alert.position.journal_name(), alert.position.position(), alert.message);
+            }
+        }
+        if !alerts.is_empty() {
+            self.deliver(entry, &alerts);
+        }
+        alerts
+    }
+
+    fn deliver(&mut self, entry: &Entry, alerts: &[DriftAlert]) {
+        let sink = match self.alert_sink.as_mut() {
+            Some(sink) => sink,
+            None => return,
+        };
+        let source = entry.header();
+        let mut header = Header::new(source.log_file_name(), source.log_file_offset());
+        header.set_execute_time(Utc::now().timestamp_millis());
+        let messages: Vec<String> = alerts.iter().map(|alert| format!("{}: {}", alert.check, alert.message)).collect();
+        let message = format!("schema drift on {}: {}", alerts[0].table, messages.join("; "));
+        if let Err(e) = sink.on_event(&Entry::incident(header, &message)).and_then(|_| sink.flush()) {
+            println!("schema drift alert sink failure: {}", e);
+        }
+    }
+}
+
+impl Default for DriftValidator {
+    fn default() -> DriftValidator {
+        DriftValidator::new()
+    }
+}
+
+/**
+ * <pre>
+ *  在后台线程中检查抽样的行, 不影响投递:
+ *  entry先交给inner, 成功之后rows entry按SampleMode抽样, 连同当时跟踪的表结构放入队列,
+ *  由validator线程检查. 队列满时丢弃抽样行(计入rows_dropped), 不阻塞parser.
+ *  表结构在抽样时取出, DDL之后的行按新的表结构检查
+ * </pre>
+ */
+pub struct DriftSink {
+    inner: Box<dyn EventSink>,
+    schemas: TableSchemas,
+    mode: SampleMode,
+    alerts: DriftAlerts,
+    sender: Option<SyncSender<(Entry, Option<TableDescription>)>>,
+    handle: Option<JoinHandle<()>>,
+    rows_seen: u64,
+    rows_sampled: u64,
+}
+
+impl DriftSink {
+    pub fn new(inner: Box<dyn EventSink>, schemas: TableSchemas, validator: DriftValidator, mode: SampleMode,
+               capacity: usize) -> Result<DriftSink, String> {
+        let alerts = validator.alerts();
+        let (sender, receiver) = sync_channel(capacity.max(1));
+        let handle = thread::Builder::new()
+            .name("drift-validator".to_string())
+            .spawn(move || run_validator(validator, receiver))
+            .map_err(|e| format!("spawn drift validator failure: {}", e))?;
+        Ok(DriftSink {
+            inner,
+            schemas,
+            mode,
+            alerts,
+            sender: Some(sender),
+            handle: Some(handle),
+            rows_seen: 0,
+            rows_sampled: 0,
+        })
+    }
+
+    pub fn mode(&self) -> SampleMode {
+        self.mode
+    }
+    pub fn alerts(&self) -> DriftAlerts {
+        self.alerts.clone()
+    }
+    pub fn rows_seen(&self) -> u64 {
+        self.rows_seen
+    }
+    pub fn rows_sampled(&self) -> u64 {
+        self.rows_sampled
+    }
+}
+
+impl EventSink for DriftSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.inner.on_event(entry)?;
+        let rows = entry.entry_type() == EntryType::RowData
+            && entry.row_change().is_some_and(|row_change| !row_change.is_ddl());
+        if !rows {
+            return Ok(());
+        }
+        self.rows_seen += 1;
+        if !self.mode.sample(self.rows_seen, entry) {
+            return Ok(());
+        }
+        self.rows_sampled += 1;
+        let table = self.schemas.describe(entry.header().schema_name(), entry.header().table_name());
+        if let Some(sender) = self.sender.as_ref() {
+            if let Err(TrySendError::Full(_)) = sender.try_send((entry.clone(), table)) {
+                self.alerts.dropped();
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
+
+impl Drop for DriftSink {
+    // 关闭队列, validator检查完剩余的行后退出
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run_validator(mut validator: DriftValidator, receiver: Receiver<(Entry, Option<TableDescription>)>) {
+    for (entry, table) in receiver {
+        validator.validate(&entry, table.as_ref());
+    }
+}
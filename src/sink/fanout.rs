@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::instance::backoff::Backoff;
+use crate::instance::EntryPosition;
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+// 每个branch最多缓存的失败entry数
+pub const DEFAULT_MAX_BACKLOG: usize = 10000;
+
+const RETRY_INITIAL_DELAY: Duration = Duration::from_millis(100);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchMode {
+    // 参与源位点的计算, 没有确认的entry不会被跳过
+    Required,
+    // 不参与源位点的计算, backlog满时丢弃最早的entry
+    BestEffort,
+}
+
+impl BranchMode {
+    pub fn from_name(name: &str) -> Result<BranchMode, String> {
+        match name {
+            "required" => Ok(BranchMode::Required),
+            "best_effort" => Ok(BranchMode::BestEffort),
+            _ => Err(format!("unknown branch mode {}, expect required/best_effort", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BranchMode::Required => "required",
+            BranchMode::BestEffort => "best_effort",
+        }
+    }
+}
+
+// 接收同一个流的一个sink, 失败时在自己的backlog中缓存并按退避时间重试
+pub struct Branch {
+    name: String,
+    mode: BranchMode,
+    sink: Box<dyn EventSink>,
+    // backlog满时接收最早的entry, 没有设置时Required返回Err, BestEffort丢弃
+    dead_letter: Option<Box<dyn EventSink>>,
+    // (序号, entry), 投递失败之后按顺序缓存
+    backlog: VecDeque<(u64, Entry)>,
+    max_backlog: usize,
+    backoff: Backoff,
+    retry_at: Option<Instant>,
+    delivered: u64,
+    failures: u64,
+    dead_lettered: u64,
+    dropped: u64,
+    last_error: Option<String>,
+}
+
+impl Branch {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn mode(&self) -> BranchMode {
+        self.mode
+    }
+    pub fn backlog(&self) -> usize {
+        self.backlog.len()
+    }
+    pub fn max_backlog(&self) -> usize {
+        self.max_backlog
+    }
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+    pub fn dead_lettered(&self) -> u64 {
+        self.dead_lettered
+    }
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    pub fn is_healthy(&self) -> bool {
+        self.backlog.is_empty()
+    }
+
+    // 最早的没有确认的entry的序号
+    fn oldest_pending(&self) -> Option<u64> {
+        self.backlog.front().map(|(sequence, _)| *sequence)
+    }
+
+    fn fail(&mut self, e: String) {
+        if self.last_error.is_none() || self.backlog.is_empty() {
+            println!("fan-out branch {} failure, buffering: {}", self.name, e);
+        }
+        self.failures += 1;
+        self.last_error = Some(e);
+        let delay = self.backoff.next_delay().unwrap_or(RETRY_MAX_DELAY);
+        self.retry_at = Some(Instant::now() + delay);
+    }
+
+    // 到了重试时间时按顺序重新投递backlog, 全部成功后恢复直接投递
+    fn retry(&mut self) {
+        if self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return;
+        }
+        while let Some((_, entry)) = self.backlog.front() {
+            if let Err(e) = self.sink.on_event(entry) {
+                self.fail(e);
+                return;
+            }
+            self.backlog.pop_front();
+            self.delivered += 1;
+        }
+        if self.retry_at.take().is_some() {
+            println!("fan-out branch {} recovers", self.name);
+        }
+        self.backoff.reset();
+    }
+
+    fn deliver(&mut self, sequence: u64, entry: &Entry) -> Result<(), String> {
+        if !self.backlog.is_empty() {
+            self.retry();
+        }
+        if self.backlog.is_empty() {
+            match self.sink.on_event(entry) {
+                Ok(()) => {
+                    self.delivered += 1;
+                    return Ok(());
+                }
+                Err(e) => self.fail(e),
+            }
+        }
+        if self.backlog.len() >= self.max_backlog {
+            self.overflow()?;
+        }
+        self.backlog.push_back((sequence, entry.clone()));
+        Ok(())
+    }
+
+    // backlog已满, 把最早的entry移出
+    fn overflow(&mut self) -> Result<(), String> {
+        let dead_letter = match (self.dead_letter.as_mut(), self.mode) {
+            (Some(dead_letter), _) => dead_letter,
+            (None, BranchMode::BestEffort) => {
+                self.backlog.pop_front();
+                self.dropped += 1;
+                return Ok(());
+            }
+            (None, BranchMode::Required) => {
+                return Err(format!("fan-out branch {} backlog is full ({} entries), last error: {}", self.name,
+                                   self.backlog.len(), self.last_error.as_deref().unwrap_or("")));
+            }
+        };
+        if let Some((_, entry)) = self.backlog.front() {
+            if let Err(e) = dead_letter.on_event(entry) {
+                if self.mode == BranchMode::Required {
+                    return Err(format!("fan-out branch {} dead letter failure: {}", self.name, e));
+                }
+                self.dropped += 1;
+            } else {
+                self.dead_lettered += 1;
+            }
+            self.backlog.pop_front();
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if !self.backlog.is_empty() {
+            self.retry();
+        }
+        if let Some(dead_letter) = self.dead_letter.as_mut() {
+            dead_letter.flush().map_err(|e| format!("fan-out branch {} dead letter failure: {}", self.name, e))?;
+        }
+        if !self.backlog.is_empty() {
+            return Ok(());
+        }
+        self.sink.flush().map_err(|e| format!("fan-out branch {} failure: {}", self.name, e))
+    }
+}
+
+/**
+ * <pre>
+ *  同一个流同时交给多个sink, 并隔离各个sink的失败:
+ *  某个branch返回Err时entry进入该branch自己的backlog, 之后按退避时间(100ms起翻倍, 最多30s)顺序重试,
+ *  其它branch照常投递. backlog超过max_backlog时最早的entry交给该branch的dead letter sink,
+ *  没有dead letter时BestEffort丢弃, Required返回Err(此时需要停止源端, 不能跳过数据).
+ *  共享的源位点committed_position()只推进到所有Required branch都确认过的entry,
+ *  BestEffort branch不参与, 重启之后可能丢失其backlog中的entry.
+ *  flush时重试backlog, backlog为空的branch执行flush, 只有Required branch的flush失败会返回Err
+ * </pre>
+ */
+#[derive(Default)]
+pub struct FanoutSink {
+    branches: Vec<Branch>,
+    sequence: u64,
+    // 还没有提交的entry的(序号, 位点)
+    uncommitted: VecDeque<(u64, EntryPosition)>,
+    committed: Option<EntryPosition>,
+}
+
+impl FanoutSink {
+    pub fn new() -> FanoutSink {
+        FanoutSink::default()
+    }
+
+    pub fn add_branch(&mut self, name: &str, sink: Box<dyn EventSink>, mode: BranchMode) -> Result<(), String> {
+        if self.branch(name).is_some() {
+            return Err(format!("fan-out branch {} is already added", name));
+        }
+        let mut backoff = Backoff::new(RETRY_INITIAL_DELAY, RETRY_MAX_DELAY);
+        backoff.set_max_retries(None);
+        self.branches.push(Branch {
+            name: name.to_string(),
+            mode,
+            sink,
+            dead_letter: None,
+            backlog: VecDeque::new(),
+            max_backlog: DEFAULT_MAX_BACKLOG,
+            backoff,
+            retry_at: None,
+            delivered: 0,
+            failures: 0,
+            dead_lettered: 0,
+            dropped: 0,
+            last_error: None,
+        });
+        Ok(())
+    }
+
+    pub fn set_dead_letter(&mut self, name: &str, sink: Box<dyn EventSink>) -> Result<(), String> {
+        self.branch_mut(name)?.dead_letter = Some(sink);
+        Ok(())
+    }
+
+    pub fn set_max_backlog(&mut self, name: &str, max_backlog: usize) -> Result<(), String> {
+        self.branch_mut(name)?.max_backlog = max_backlog.max(1);
+        Ok(())
+    }
+
+    pub fn branch(&self, name: &str) -> Option<&Branch> {
+        self.branches.iter().find(|branch| branch.name == name)
+    }
+
+    pub fn branches(&self) -> &Vec<Branch> {
+        &self.branches
+    }
+
+    // 所有Required branch都确认过的最后一个entry的位点
+    pub fn committed_position(&self) -> Option<&EntryPosition> {
+        self.committed.as_ref()
+    }
+
+    fn branch_mut(&mut self, name: &str) -> Result<&mut Branch, String> {
+        self.branches.iter_mut().find(|branch| branch.name == name)
+            .ok_or_else(|| format!("fan-out branch {} is not added", name))
+    }
+
+    fn advance(&mut self) {
+        let oldest_pending = self.branches.iter()
+            .filter(|branch| branch.mode == BranchMode::Required)
+            .filter_map(|branch| branch.oldest_pending())
+            .min();
+        while let Some((sequence, _)) = self.uncommitted.front() {
+            if oldest_pending.is_some_and(|oldest| *sequence >= oldest) {
+                break;
+            }
+            self.committed = self.uncommitted.pop_front().map(|(_, position)| position);
+        }
+    }
+}
+
+impl EventSink for FanoutSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.sequence += 1;
+        self.uncommitted.push_back((self.sequence, entry.header().position()));
+        let mut result = Ok(());
+        for branch in self.branches.iter_mut() {
+            if let Err(e) = branch.deliver(self.sequence, entry) {
+                result = result.and(Err(e));
+            }
+        }
+        self.advance();
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        for branch in self.branches.iter_mut() {
+            if let Err(e) = branch.flush() {
+                if branch.mode == BranchMode::Required {
+                    result = result.and(Err(e));
+                } else {
+                    branch.failures += 1;
+                    branch.last_error = Some(e);
+                }
+            }
+        }
+        self.advance();
+        result
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.branches.iter().any(|branch| branch.sink.is_backpressured())
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.branches.iter_mut().try_for_each(|branch| branch.sink.reconfigure(config))
+    }
+}
@@ -0,0 +1,321 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::protocol::Entry;
+use crate::sink::mq::{Compression, FlatMessageSerializer};
+use crate::sink::registry::{duration_or, parse_or, SinkConfig};
+use crate::sink::segment::TimeSegmenter;
+use crate::sink::EventSink;
+
+pub const DEFAULT_FILE_PREFIX: &str = "entries";
+// 单个文件压缩后的大小上限
+pub const DEFAULT_ROTATE_BYTES: u64 = 256 * 1024 * 1024;
+// 缓冲的json超过该大小时压缩为一个frame写入文件
+pub const DEFAULT_CHUNK_BYTES: usize = 1024 * 1024;
+
+// 写入文件之后何时fsync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FsyncPolicy {
+    // 交给操作系统, 进程崩溃不丢数据, 机器掉电可能丢失
+    Never,
+    // 每次flush
+    #[default]
+    Flush,
+    // 只在文件切换时
+    Rotate,
+}
+
+impl FsyncPolicy {
+    pub fn from_name(name: &str) -> Result<FsyncPolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "never" => Ok(FsyncPolicy::Never),
+            "flush" => Ok(FsyncPolicy::Flush),
+            "rotate" => Ok(FsyncPolicy::Rotate),
+            _ => Err(format!("unknown fsync policy {}, expect never/flush/rotate", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FsyncPolicy::Never => "never",
+            FsyncPolicy::Flush => "flush",
+            FsyncPolicy::Rotate => "rotate",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  把RowData entry按FlatMessage的json逐行写入本地文件:
+ *      <directory>/<prefix>.000001.jsonl.zst
+ *  压缩(compression=none|gzip|zstd)以chunk为单位: flush或者缓冲超过chunk_bytes时把缓冲的行压缩为一个完整的
+ *  gzip member/zstd frame追加到文件, 多个frame拼接之后仍然是合法的.gz/.zst文件, 进程崩溃时最多丢失未flush的部分,
+ *  文件末尾不会出现半个frame导致之前的数据无法解压.
+ *  文件压缩后超过rotate_bytes或者打开超过rotate_interval时切换到下一个序号, 时间只在写入时检查.
+ *  启动时从目录中已有的最大序号之后继续, 不会追加到已有的文件.
+ *  配置了segment时按event的时间分段, 每段的文件名为<prefix>-<segment>.000001.jsonl, 例如entries-20260101,
+ *  entry所在的段变化时切换文件, 重放历史区间时写入该段已有的最大序号之后的新文件.
+ *  配置: directory, file_prefix, compression, rotate_bytes, rotate_interval(例如1h), chunk_bytes,
+ *        fsync=never|flush|rotate, segment=none|hourly|daily, segment_utc_offset, 以及FlatMessageSerializer的配置
+ * </pre>
+ */
+pub struct FileSink {
+    directory: PathBuf,
+    prefix: String,
+    serializer: FlatMessageSerializer,
+    compression: Compression,
+    rotate_bytes: Option<u64>,
+    rotate_interval: Option<Duration>,
+    chunk_bytes: usize,
+    fsync: FsyncPolicy,
+    buffer: Vec<u8>,
+    file: Option<File>,
+    file_path: Option<PathBuf>,
+    // 当前文件已写入的字节数(压缩后)
+    file_bytes: u64,
+    opened_at: Instant,
+    sequence: u64,
+    lines: u64,
+    segmenter: Option<TimeSegmenter>,
+    // 当前的段, 第一个有时间的entry之前为None
+    segment: Option<String>,
+}
+
+impl FileSink {
+    pub fn new(directory: &Path, serializer: FlatMessageSerializer) -> Result<FileSink, String> {
+        let mut sink = FileSink {
+            directory: directory.to_path_buf(),
+            prefix: DEFAULT_FILE_PREFIX.to_string(),
+            serializer,
+            compression: Compression::None,
+            rotate_bytes: Some(DEFAULT_ROTATE_BYTES),
+            rotate_interval: None,
+            chunk_bytes: DEFAULT_CHUNK_BYTES,
+            fsync: FsyncPolicy::Flush,
+            buffer: vec![],
+            file: None,
+            file_path: None,
+            file_bytes: 0,
+            opened_at: Instant::now(),
+            sequence: 0,
+            lines: 0,
+            segmenter: None,
+            segment: None,
+        };
+        sink.set_prefix(DEFAULT_FILE_PREFIX)?;
+        Ok(sink)
+    }
+
+    pub fn from_config(config: &SinkConfig) -> Result<FileSink, String> {
+        let directory = config.get("directory").ok_or_else(|| "file sink config is missing 'directory'".to_string())?;
+        let mut sink = FileSink::new(Path::new(directory), FlatMessageSerializer::from_config(config)?)?;
+        if let Some(prefix) = config.get("file_prefix") {
+            sink.set_prefix(prefix)?;
+        }
+        if let Some(name) = config.get("compression") {
+            sink.set_compression(Compression::from_name(name)?)?;
+        }
+        let rotate_bytes = parse_or(config, "rotate_bytes", DEFAULT_ROTATE_BYTES)?;
+        sink.set_rotate_bytes(Some(rotate_bytes).filter(|bytes| *bytes > 0));
+        let rotate_interval = duration_or(config, "rotate_interval", Duration::ZERO)?;
+        sink.set_rotate_interval(Some(rotate_interval).filter(|interval| !interval.is_zero()));
+        sink.set_chunk_bytes(parse_or(config, "chunk_bytes", DEFAULT_CHUNK_BYTES)?);
+        if let Some(name) = config.get("fsync") {
+            sink.set_fsync(FsyncPolicy::from_name(name)?);
+        }
+        sink.set_segmenter(TimeSegmenter::from_config(config)?);
+        Ok(sink)
+    }
+
+    // 修改前缀之后从该前缀已有的最大序号之后继续
+    pub fn set_prefix(&mut self, prefix: &str) -> Result<(), String> {
+        self.prefix = prefix.to_string();
+        self.sequence = last_sequence(&self.directory, &self.segment_prefix())?;
+        Ok(())
+    }
+
+    pub fn set_segmenter(&mut self, segmenter: Option<TimeSegmenter>) {
+        self.segmenter = segmenter;
+    }
+
+    // lz4没有通用的文件格式, 不支持
+    pub fn set_compression(&mut self, compression: Compression) -> Result<(), String> {
+        if compression == Compression::Lz4 {
+            return Err("file sink supports compression none/gzip/zstd".to_string());
+        }
+        self.compression = compression;
+        Ok(())
+    }
+
+    pub fn set_rotate_bytes(&mut self, rotate_bytes: Option<u64>) {
+        self.rotate_bytes = rotate_bytes;
+    }
+    pub fn set_rotate_interval(&mut self, rotate_interval: Option<Duration>) {
+        self.rotate_interval = rotate_interval;
+    }
+    pub fn set_chunk_bytes(&mut self, chunk_bytes: usize) {
+        self.chunk_bytes = chunk_bytes.max(1);
+    }
+    pub fn set_fsync(&mut self, fsync: FsyncPolicy) {
+        self.fsync = fsync;
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+    pub fn fsync(&self) -> FsyncPolicy {
+        self.fsync
+    }
+    // 当前写入的文件, 第一次写入之前为None
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+    pub fn segmenter(&self) -> Option<&TimeSegmenter> {
+        self.segmenter.as_ref()
+    }
+    pub fn segment(&self) -> Option<&str> {
+        self.segment.as_deref()
+    }
+
+    pub fn file_name(&self, sequence: u64) -> String {
+        let extension = match self.compression {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+            _ => "",
+        };
+        format!("{}.{:06}.jsonl{}", self.segment_prefix(), sequence, extension)
+    }
+
+    // 当前段的文件名前缀
+    fn segment_prefix(&self) -> String {
+        match self.segment.as_ref() {
+            Some(segment) => format!("{}-{}", self.prefix, segment),
+            None => self.prefix.clone(),
+        }
+    }
+
+    // entry属于另一个段时关闭当前文件, 之后从新段已有的最大序号之后继续
+    fn switch_segment(&mut self, entry: &Entry) -> Result<(), String> {
+        let segment = match self.segmenter.as_ref().and_then(|segmenter| segmenter.segment(entry)) {
+            Some(segment) => segment,
+            None => return Ok(()),
+        };
+        if self.segment.as_ref() == Some(&segment) {
+            return Ok(());
+        }
+        self.rotate()?;
+        self.segment = Some(segment);
+        self.sequence = last_sequence(&self.directory, &self.segment_prefix())?;
+        Ok(())
+    }
+
+    // 关闭当前文件, 下一次写入时打开新的文件
+    pub fn rotate(&mut self) -> Result<(), String> {
+        self.write_chunk()?;
+        if let Some(file) = self.file.take() {
+            if self.fsync != FsyncPolicy::Never {
+                file.sync_all().map_err(|e| format!("sync {:?} failure: {}", self.file_path, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn should_rotate(&self) -> bool {
+        self.file.is_some()
+            && (self.rotate_bytes.is_some_and(|bytes| self.file_bytes >= bytes)
+            || self.rotate_interval.is_some_and(|interval| self.opened_at.elapsed() >= interval))
+    }
+
+    fn open(&mut self) -> Result<&mut File, String> {
+        if self.file.is_none() {
+            fs::create_dir_all(&self.directory)
+                .map_err(|e| format!("create directory {} failure: {}", self.directory.display(), e))?;
+            self.sequence += 1;
+            let path = self.directory.join(self.file_name(self.sequence));
+            let file = OpenOptions::new().create_new(true).write(true).open(&path)
+                .map_err(|e| format!("open {} failure: {}", path.display(), e))?;
+            self.file = Some(file);
+            self.file_path = Some(path);
+            self.file_bytes = 0;
+            self.opened_at = Instant::now();
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    // 缓冲的行压缩为一个完整的frame写入文件
+    fn write_chunk(&mut self) -> Result<(), String> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = self.compression.compress(&self.buffer)?;
+        let file = self.open()?;
+        file.write_all(&chunk).map_err(|e| format!("write file sink failure: {}", e))?;
+        self.file_bytes += chunk.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl EventSink for FileSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        let line = match self.serializer.to_json(entry)? {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        self.switch_segment(entry)?;
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        self.buffer.extend_from_slice(line.as_bytes());
+        self.buffer.push(b'\n');
+        self.lines += 1;
+        if self.buffer.len() >= self.chunk_bytes {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.write_chunk()?;
+        if self.fsync == FsyncPolicy::Flush {
+            if let Some(file) = self.file.as_ref() {
+                file.sync_data().map_err(|e| format!("sync {:?} failure: {}", self.file_path, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.rotate() {
+            println!("close file sink failure: {}", e);
+        }
+    }
+}
+
+// 目录中<prefix>.<sequence>.jsonl*的最大序号, 没有时为0
+fn last_sequence(directory: &Path, prefix: &str) -> Result<u64, String> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("read directory {} failure: {}", directory.display(), e)),
+    };
+    let head = format!("{}.", prefix);
+    Ok(entries.filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (sequence, extension) = name.strip_prefix(&head)?.split_once('.')?;
+            extension.starts_with("jsonl").then(|| sequence.parse::<u64>().ok()).flatten()
+        })
+        .max()
+        .unwrap_or(0))
+}
@@ -0,0 +1,112 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+#[derive(Debug, Clone, Default)]
+pub struct SinkHealth {
+    delivered: u64,
+    failures: u64,
+    consecutive_failures: u64,
+    last_error: Option<String>,
+    // 毫秒, 没有发生时为0
+    last_error_at: i64,
+    last_success_at: i64,
+}
+
+impl SinkHealth {
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+    pub fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures
+    }
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+    pub fn last_error_at(&self) -> i64 {
+        self.last_error_at
+    }
+    pub fn last_success_at(&self) -> i64 {
+        self.last_success_at
+    }
+
+    // 最近一次投递或者flush成功
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures == 0
+    }
+
+    pub fn record(&mut self, result: &Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.consecutive_failures = 0;
+                self.last_success_at = Utc::now().timestamp_millis();
+            }
+            Err(e) => {
+                self.failures += 1;
+                self.consecutive_failures += 1;
+                self.last_error = Some(e.clone());
+                self.last_error_at = Utc::now().timestamp_millis();
+            }
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  包装下游sink, 记录投递成功/失败的次数以及最近的错误, 用于Instance::status_json中的sink健康状态.
+ *  on_event和flush都计入, 只有on_event成功时计入delivered
+ * </pre>
+ */
+pub struct HealthSink {
+    inner: Box<dyn EventSink>,
+    health: Arc<Mutex<SinkHealth>>,
+}
+
+impl HealthSink {
+    pub fn new(inner: Box<dyn EventSink>) -> HealthSink {
+        HealthSink { inner, health: Arc::new(Mutex::new(SinkHealth::default())) }
+    }
+
+    // 句柄可以在其它线程中读取
+    pub fn health(&self) -> Arc<Mutex<SinkHealth>> {
+        self.health.clone()
+    }
+
+    fn record(&self, result: &Result<(), String>, delivered: bool) {
+        if let Ok(mut health) = self.health.lock() {
+            health.record(result);
+            if delivered && result.is_ok() {
+                health.delivered += 1;
+            }
+        }
+    }
+}
+
+impl EventSink for HealthSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        let result = self.inner.on_event(entry);
+        self.record(&result, true);
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let result = self.inner.flush();
+        self.record(&result, false);
+        result
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
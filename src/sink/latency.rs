@@ -0,0 +1,87 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+
+use crate::metrics::rate;
+use crate::metrics::{LatencyHistogram, RateKind, StreamMetrics};
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+pub const DEFAULT_METRIC_NAME: &str = "canal_entry_latency";
+
+/**
+ * <pre>
+ *  包装下游sink, 下游on_event返回Ok(即确认)后记录该entry的延迟:
+ *  当前时间 - 事务在最初的master上的提交时间(header.original_commit_timestamp, 微秒),
+ *  master不是mysql 8时没有commit timestamp, 使用秒级的header.execute_time.
+ *  heartbeat以及没有执行时间的entry不计入, 下游时钟早于master时按0处理.
+ *  histogram可以通过handle在其它线程中导出.
+ *  设置了metrics时每次确认同时计入该流的ack速率
+ * </pre>
+ */
+pub struct LatencySink {
+    inner: Box<dyn EventSink>,
+    histogram: Arc<Mutex<LatencyHistogram>>,
+    metrics: Option<Arc<Mutex<StreamMetrics>>>,
+}
+
+impl LatencySink {
+    pub fn new(inner: Box<dyn EventSink>) -> LatencySink {
+        LatencySink::with_histogram(inner, LatencyHistogram::default())
+    }
+
+    pub fn with_histogram(inner: Box<dyn EventSink>, histogram: LatencyHistogram) -> LatencySink {
+        LatencySink { inner, histogram: Arc::new(Mutex::new(histogram)), metrics: None }
+    }
+
+    // 通常为MysqlEventParser::metrics()
+    pub fn set_metrics(&mut self, metrics: Arc<Mutex<StreamMetrics>>) {
+        self.metrics = Some(metrics);
+    }
+
+    pub fn histogram(&self) -> Arc<Mutex<LatencyHistogram>> {
+        self.histogram.clone()
+    }
+
+    pub fn export(&self) -> String {
+        match self.histogram.lock() {
+            Ok(histogram) => histogram.to_open_metrics(DEFAULT_METRIC_NAME),
+            Err(_) => String::new(),
+        }
+    }
+}
+
+impl EventSink for LatencySink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.inner.on_event(entry)?;
+        if let Some(metrics) = self.metrics.as_ref() {
+            rate::mark(metrics, RateKind::Acks, 1);
+        }
+        let header = entry.header();
+        if entry.entry_type() == EntryType::Heartbeat {
+            return Ok(());
+        }
+        let latency = match header.original_commit_timestamp().filter(|timestamp| *timestamp > 0) {
+            Some(timestamp) => (Utc::now().timestamp_millis() - timestamp / 1000).max(0) as u64,
+            None if header.execute_time() > 0 => (Utc::now().timestamp_millis() - header.execute_time()).max(0) as u64,
+            None => return Ok(()),
+        };
+        if let Ok(mut histogram) = self.histogram.lock() {
+            histogram.record(latency, &header.position());
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::EventSink;
+
+/**
+ * <pre>
+ *  对应canal-adapter中的logger, 把entry打印到标准输出, 用于调试和验证订阅配置
+ * </pre>
+ */
+#[derive(Debug, Default)]
+pub struct LoggerSink {
+    // 是否打印每一行的列值
+    verbose: bool,
+}
+
+impl LoggerSink {
+    pub fn new(verbose: bool) -> LoggerSink {
+        LoggerSink { verbose }
+    }
+}
+
+impl EventSink for LoggerSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        let header = entry.header();
+        let position = format!("{}:{}", header.log_file_name(), header.log_file_offset());
+        match (entry.entry_type(), entry.row_change()) {
+            (EntryType::RowData, Some(row_change)) if row_change.is_ddl() => {
+                println!("[{}] DDL {}.{}: {}", position, header.schema_name(), header.table_name(), row_change.sql());
+            }
+            (EntryType::RowData, Some(row_change)) => {
+                println!("[{}] {:?} {}.{} rows={}", position, row_change.event_type(), header.schema_name(),
+                         header.table_name(), row_change.row_datas().len());
+                if self.verbose {
+                    for row_data in row_change.row_datas() {
+                        println!("    before={:?}", row_data.before_columns().iter()
+                            .map(|column| (column.name(), column.value())).collect::<Vec<_>>());
+                        println!("    after={:?}", row_data.after_columns().iter()
+                            .map(|column| (column.name(), column.value())).collect::<Vec<_>>());
+                    }
+                }
+            }
+            (EntryType::Incident, _) => println!("[{}] INCIDENT {}", position, entry.message().unwrap_or("")),
+            (EntryType::Info, _) => println!("[{}] INFO {}", position, entry.message().unwrap_or("")),
+            (EntryType::SourceChanged, _) => println!("[{}] SOURCE CHANGED {}", position, entry.message().unwrap_or("")),
+            (entry_type, _) => println!("[{}] {:?}", position, entry_type),
+        }
+        Ok(())
+    }
+
+    // verbose可以热加载, 用于临时打开列值的输出
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.verbose = parse_or(config, "verbose", false)?;
+        Ok(())
+    }
+}
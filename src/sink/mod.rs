@@ -0,0 +1,76 @@
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+
+pub mod apply;
+
+pub mod audit;
+
+pub mod callback;
+
+pub mod canary;
+
+pub mod control;
+
+pub mod dedup;
+
+pub mod dispatcher;
+
+pub mod drift;
+
+pub mod fanout;
+
+pub mod file;
+
+pub mod health;
+
+pub mod latency;
+
+pub mod logger;
+
+pub mod mq;
+
+pub mod parallel;
+
+pub mod registry;
+
+pub mod replay;
+
+pub mod router;
+
+pub mod sample;
+
+pub mod segment;
+
+pub mod size_limit;
+
+pub mod transaction;
+
+pub mod transaction_limit;
+
+/**
+ * <pre>
+ *  解析后的entry的消费者, 按binlog顺序逐条回调.
+ *  返回Err时parser会停止投递, 由调用方决定重试或退出.
+ *  trait是object safe的, sink统一以Box<dyn EventSink>的形式使用, 可以通过SinkRegistry按名字创建
+ * </pre>
+ */
+pub trait EventSink: Send {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String>;
+
+    // 批量投递结束或parser停止时调用, 用于刷新缓冲
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    // 消费者处理不过来时返回true, parser在读取下一个event之前等待直到返回false.
+    // 包装其它sink的sink需要转发内层sink的状态
+    fn is_backpressured(&self) -> bool {
+        false
+    }
+
+    // 热加载时更新可以在运行时修改的配置(例如批次大小), 只处理自己认识的key, config中没有的key恢复默认值.
+    // 包装其它sink的sink需要转发给内层sink
+    fn reconfigure(&mut self, _config: &SinkConfig) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use crate::protocol::{Column, Entry, EntryType, EventType};
+use crate::sink::mq::EntrySerializer;
+
+pub const AVRO_CONTENT_TYPE: &str = "avro/binary";
+
+// 每个批次的object container file使用相同的sync marker, 相同的批次序列化结果相同
+const SYNC_MARKER: &[u8; 16] = b"mini-canal-sync0";
+
+// 与FlatMessage的字段对应, 列值统一为文本, NULL为null
+pub const ENTRY_SCHEMA: &str = concat!(
+    r#"{"type":"record","name":"Entry","namespace":"mini_canal","fields":["#,
+    r#"{"name":"database","type":"string"},"#,
+    r#"{"name":"table","type":"string"},"#,
+    r#"{"name":"type","type":"string"},"#,
+    r#"{"name":"isDdl","type":"boolean"},"#,
+    r#"{"name":"es","type":"long"},"#,
+    r#"{"name":"sql","type":"string"},"#,
+    r#"{"name":"pkNames","type":{"type":"array","items":"string"}},"#,
+    r#"{"name":"data","type":{"type":"array","items":{"type":"map","values":["null","string"]}}},"#,
+    r#"{"name":"old","type":{"type":"array","items":{"type":"map","values":["null","string"]}}},"#,
+    r#"{"name":"logfile","type":"string"},"#,
+    r#"{"name":"offset","type":"long"}"#,
+    r#"]}"#);
+
+/**
+ * <pre>
+ *  每个批次输出为一个Avro object container file(codec为null), schema见ENTRY_SCHEMA,
+ *  消费方用任意Avro库读取, 不需要schema registry.
+ *  与FlatMessage一样只输出rows event和DDL: delete的data为before image, update的old为被修改的列的原值
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct AvroSerializer;
+
+impl AvroSerializer {
+    pub fn new() -> AvroSerializer {
+        AvroSerializer
+    }
+
+    fn write_entry(&self, out: &mut Vec<u8>, entry: &Entry) -> bool {
+        let row_change = match (entry.entry_type(), entry.row_change()) {
+            (EntryType::RowData, Some(row_change)) => row_change,
+            _ => return false,
+        };
+        let header = entry.header();
+        write_string(out, header.schema_name());
+        write_string(out, header.table_name());
+        write_string(out, &format!("{:?}", row_change.event_type()).to_ascii_uppercase());
+        out.push(row_change.is_ddl() as u8);
+        write_long(out, header.execute_time());
+        write_string(out, row_change.sql());
+
+        let columns = row_change.row_datas().first()
+            .map(|row_data| if row_data.after_columns().is_empty() { row_data.before_columns() } else { row_data.after_columns() });
+        let pk_names: Vec<&Column> = columns.iter().flat_map(|columns| columns.iter()).filter(|column| column.is_key()).collect();
+        write_array(out, &pk_names, |out, column| write_string(out, column.name()));
+
+        let mut data: Vec<Vec<&Column>> = vec![];
+        let mut old: Vec<Vec<&Column>> = vec![];
+        for row_data in row_change.row_datas().iter().filter(|_| !row_change.is_ddl()) {
+            if row_change.event_type() == EventType::Delete {
+                data.push(row_data.before_columns().iter().collect());
+            } else {
+                data.push(row_data.after_columns().iter().collect());
+            }
+            if row_change.event_type() == EventType::Update {
+                let updated: Vec<&str> = row_data.after_columns().iter()
+                    .filter(|column| column.updated())
+                    .map(|column| column.name())
+                    .collect();
+                old.push(row_data.before_columns().iter().filter(|column| updated.contains(&column.name())).collect());
+            }
+        }
+        write_array(out, &data, |out, columns| write_columns(out, columns));
+        write_array(out, &old, |out, columns| write_columns(out, columns));
+        write_string(out, header.log_file_name());
+        write_long(out, header.log_file_offset() as i64);
+        true
+    }
+}
+
+impl EntrySerializer for AvroSerializer {
+    fn serialize(&self, entries: &[Entry]) -> Result<Vec<u8>, String> {
+        let mut out = b"Obj\x01".to_vec();
+        // 文件头的metadata, map<bytes>
+        write_long(&mut out, 2);
+        write_string(&mut out, "avro.schema");
+        write_string(&mut out, ENTRY_SCHEMA);
+        write_string(&mut out, "avro.codec");
+        write_string(&mut out, "null");
+        write_long(&mut out, 0);
+        out.extend_from_slice(SYNC_MARKER);
+
+        let mut block = vec![];
+        let count = entries.iter().filter(|entry| self.write_entry(&mut block, entry)).count();
+        if count > 0 {
+            write_long(&mut out, count as i64);
+            write_long(&mut out, block.len() as i64);
+            out.extend_from_slice(&block);
+            out.extend_from_slice(SYNC_MARKER);
+        }
+        Ok(out)
+    }
+
+    fn content_type(&self) -> &str {
+        AVRO_CONTENT_TYPE
+    }
+}
+
+// zigzag编码的变长整数
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_long(out, value.len() as i64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+// 只有一个block, 空数组只写结束标记0
+fn write_array<T>(out: &mut Vec<u8>, items: &[T], write: impl Fn(&mut Vec<u8>, &T)) {
+    if !items.is_empty() {
+        write_long(out, items.len() as i64);
+        for item in items {
+            write(out, item);
+        }
+    }
+    write_long(out, 0);
+}
+
+// map<["null","string"]>, union的分支序号0为null, 1为string
+fn write_columns(out: &mut Vec<u8>, columns: &[&Column]) {
+    write_array(out, columns, |out, column| {
+        write_string(out, column.name());
+        if column.is_null() {
+            write_long(out, 0);
+        } else {
+            write_long(out, 1);
+            write_string(out, column.value());
+        }
+    });
+}
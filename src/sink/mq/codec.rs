@@ -0,0 +1,88 @@
+use std::io::{Read, Write};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+// 消息header中标记payload压缩方式的key, 取值与http的Content-Encoding一致
+pub const CONTENT_ENCODING_HEADER: &str = "content-encoding";
+
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/**
+ * <pre>
+ *  序列化后的批次在投递之前的压缩方式, 每个sink单独配置(compression=none|lz4|zstd|gzip).
+ *  压缩方式写入消息的content-encoding header, 消费端据此选择解压方式:
+ *      none    identity
+ *      lz4     lz4, 带原始长度前缀的block格式
+ *      zstd    zstd
+ *      gzip    gzip
+ *  json的flat message重复的字段名很多, zstd/gzip一般可以压缩到原来的1/5以下
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Result<Compression, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" | "identity" => Ok(Compression::None),
+            "lz4" => Ok(Compression::Lz4),
+            "zstd" => Ok(Compression::Zstd),
+            "gzip" => Ok(Compression::Gzip),
+            _ => Err(format!("unknown compression {}, expect none/lz4/zstd/gzip", name)),
+        }
+    }
+
+    // content-encoding header的取值
+    pub fn content_encoding(&self) -> &'static str {
+        match self {
+            Compression::None => "identity",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd",
+            Compression::Gzip => "gzip",
+        }
+    }
+
+    pub fn compress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(payload)),
+            Compression::Zstd => zstd::encode_all(payload, DEFAULT_ZSTD_LEVEL)
+                .map_err(|e| format!("zstd compress failure: {}", e)),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(payload).and_then(|_| encoder.finish())
+                    .map_err(|e| format!("gzip compress failure: {}", e))
+            }
+        }
+    }
+
+    pub fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Lz4 => lz4_flex::decompress_size_prepended(payload)
+                .map_err(|e| format!("lz4 decompress failure: {}", e)),
+            Compression::Zstd => zstd::decode_all(payload).map_err(|e| format!("zstd decompress failure: {}", e)),
+            // 兼容多个member拼接的payload, 例如file sink按chunk写入的文件
+            Compression::Gzip => {
+                let mut out = vec![];
+                MultiGzDecoder::new(payload).read_to_end(&mut out).map_err(|e| format!("gzip decompress failure: {}", e))?;
+                Ok(out)
+            }
+        }
+    }
+}
+
+// 消费端按content-encoding header解压, 没有header时视为未压缩
+pub fn decode_payload(content_encoding: Option<&str>, payload: &[u8]) -> Result<Vec<u8>, String> {
+    match content_encoding {
+        Some(name) => Compression::from_name(name)?.decompress(payload),
+        None => Ok(payload.to_vec()),
+    }
+}
@@ -0,0 +1,363 @@
+use crate::protocol::canonical::table_version;
+use chrono::FixedOffset;
+
+use crate::protocol::temporal::is_date_type;
+use crate::protocol::{Column, Entry, EntryType, EventType, TemporalFormat, ZeroDatePolicy};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::segment::{parse_utc_offset, utc};
+
+pub const FLAT_MESSAGE_CONTENT_TYPE: &str = "application/json";
+
+/**
+ * <pre>
+ *  批次的序列化方式, 输出作为一条MQ消息的payload
+ * </pre>
+ */
+pub trait EntrySerializer: Send {
+    fn serialize(&self, entries: &[Entry]) -> Result<Vec<u8>, String>;
+
+    // 写入消息的content-type header
+    fn content_type(&self) -> &str;
+}
+
+// 顶层字段名的风格, 不影响data/old中的列名
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldNaming {
+    // 与canal一致: isDdl, pkNames, mysqlType, sqlType
+    #[default]
+    CamelCase,
+    // is_ddl, pk_names, mysql_type, sql_type
+    SnakeCase,
+}
+
+impl FieldNaming {
+    pub fn from_name(name: &str) -> Result<FieldNaming, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "camel" | "camelcase" | "camel_case" => Ok(FieldNaming::CamelCase),
+            "snake" | "snakecase" | "snake_case" => Ok(FieldNaming::SnakeCase),
+            _ => Err(format!("unknown field naming {}, expect camel_case/snake_case", name)),
+        }
+    }
+
+    // 字段名以camelCase定义
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            FieldNaming::CamelCase => name.to_string(),
+            FieldNaming::SnakeCase => {
+                let mut out = String::with_capacity(name.len() + 4);
+                for c in name.chars() {
+                    if c.is_ascii_uppercase() {
+                        out.push('_');
+                        out.push(c.to_ascii_lowercase());
+                    } else {
+                        out.push(c);
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  对应canal中的FlatMessage, 一个批次序列化为json数组, 每个RowData entry一个元素:
+ *      {"database":"db","table":"t","type":"UPDATE","isDdl":false,"es":1700000000000,
+ *       "pkNames":["id"],"sql":"","mysqlType":{"id":"int","name":"varchar(20)"},"sqlType":{"id":4,"name":12},
+ *       "data":[{"id":"1","name":"b"}],"old":[{"name":"a"}],"logfile":"mysql-bin.000001","offset":4}
+ *  data: insert/update取after, delete取before; old: update中变更列的before值.
+ *  列值统一输出为字符串, NULL输出为null. 事务边界等非RowData的entry不输出.
+ *  为了与原有的consumer兼容, 可以配置:
+ *      field_naming    camel_case(默认)/snake_case
+ *      include_types   是否输出mysqlType/sqlType, 默认true
+ *      emit_nulls      是否输出值为NULL的列, 默认true, false时data/old中省略这些列
+ *      zero_date       date/datetime/timestamp中非法日期的处理, keep(默认)/sentinel/null/error,
+ *                      sentinel的值由zero_date_sentinel指定, error时整个批次序列化失败
+ *      temporal_format date/time/datetime/timestamp的格式, text(默认)/iso8601/epoch_millis/epoch_micros,
+ *                      见TemporalFormat, 在zero_date之后处理, epoch同样输出为字符串
+ *      temporal_utc_offset iso8601/epoch时date/datetime所在的时区以及timestamp输出的时区, 默认+00:00
+ *      include_lineage 是否输出lineage块, 默认false, 用于下游的数据治理追溯每条记录的来源:
+ *          "lineage":{"host":"10.0.0.1","port":3306,"serverUuid":"...","logfile":"mysql-bin.000001",
+ *                     "offset":4,"gtid":"uuid:10","tableVersion":"3f2a..."}
+ *                      host/port/serverUuid由source_host/source_port/server_uuid指定, 没有GTID时gtid为null,
+ *                      tableVersion为列名和mysqlType的hash, 表结构变化之后改变
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct FlatMessageSerializer {
+    field_naming: FieldNaming,
+    include_types: bool,
+    emit_nulls: bool,
+    zero_date: ZeroDatePolicy,
+    temporal_format: TemporalFormat,
+    temporal_offset: FixedOffset,
+    include_lineage: bool,
+    lineage_source: LineageSource,
+}
+
+impl Default for FlatMessageSerializer {
+    fn default() -> Self {
+        FlatMessageSerializer::new()
+    }
+}
+
+impl FlatMessageSerializer {
+    pub fn new() -> FlatMessageSerializer {
+        FlatMessageSerializer {
+            field_naming: FieldNaming::CamelCase,
+            include_types: true,
+            emit_nulls: true,
+            zero_date: ZeroDatePolicy::Keep,
+            temporal_format: TemporalFormat::Text,
+            temporal_offset: utc(),
+            include_lineage: false,
+            lineage_source: LineageSource::default(),
+        }
+    }
+
+    pub fn from_config(config: &SinkConfig) -> Result<FlatMessageSerializer, String> {
+        let mut serializer = FlatMessageSerializer::new();
+        if let Some(name) = config.get("field_naming") {
+            serializer.set_field_naming(FieldNaming::from_name(name)?);
+        }
+        serializer.set_include_types(parse_or(config, "include_types", true)?);
+        serializer.set_emit_nulls(parse_or(config, "emit_nulls", true)?);
+        if let Some(name) = config.get("zero_date") {
+            let sentinel = config.get("zero_date_sentinel").map(|sentinel| sentinel.as_str());
+            serializer.set_zero_date(ZeroDatePolicy::from_name(name, sentinel)?);
+        }
+        if let Some(name) = config.get("temporal_format") {
+            serializer.set_temporal_format(TemporalFormat::from_name(name)?);
+        }
+        if let Some(offset) = config.get("temporal_utc_offset") {
+            serializer.set_temporal_offset(parse_utc_offset(offset).map_err(|e| format!("temporal_utc_offset: {}", e))?);
+        }
+        serializer.set_include_lineage(parse_or(config, "include_lineage", false)?);
+        let host = config.get("source_host").map(|host| host.as_str()).unwrap_or("");
+        let server_uuid = config.get("server_uuid").map(|server_uuid| server_uuid.as_str()).unwrap_or("");
+        serializer.set_lineage_source(LineageSource::new(host, parse_or(config, "source_port", 0)?, server_uuid));
+        Ok(serializer)
+    }
+
+    pub fn field_naming(&self) -> FieldNaming {
+        self.field_naming
+    }
+    pub fn include_types(&self) -> bool {
+        self.include_types
+    }
+    pub fn emit_nulls(&self) -> bool {
+        self.emit_nulls
+    }
+    pub fn zero_date(&self) -> &ZeroDatePolicy {
+        &self.zero_date
+    }
+    pub fn temporal_format(&self) -> TemporalFormat {
+        self.temporal_format
+    }
+    pub fn temporal_offset(&self) -> &FixedOffset {
+        &self.temporal_offset
+    }
+    pub fn include_lineage(&self) -> bool {
+        self.include_lineage
+    }
+    pub fn lineage_source(&self) -> &LineageSource {
+        &self.lineage_source
+    }
+
+    pub fn set_field_naming(&mut self, field_naming: FieldNaming) {
+        self.field_naming = field_naming;
+    }
+    pub fn set_include_types(&mut self, include_types: bool) {
+        self.include_types = include_types;
+    }
+    pub fn set_emit_nulls(&mut self, emit_nulls: bool) {
+        self.emit_nulls = emit_nulls;
+    }
+    pub fn set_zero_date(&mut self, zero_date: ZeroDatePolicy) {
+        self.zero_date = zero_date;
+    }
+    pub fn set_temporal_format(&mut self, temporal_format: TemporalFormat) {
+        self.temporal_format = temporal_format;
+    }
+    pub fn set_temporal_offset(&mut self, temporal_offset: FixedOffset) {
+        self.temporal_offset = temporal_offset;
+    }
+    pub fn set_include_lineage(&mut self, include_lineage: bool) {
+        self.include_lineage = include_lineage;
+    }
+    pub fn set_lineage_source(&mut self, lineage_source: LineageSource) {
+        self.lineage_source = lineage_source;
+    }
+
+    // 不是RowData的entry返回None
+    pub fn to_json(&self, entry: &Entry) -> Result<Option<String>, String> {
+        let row_change = match (entry.entry_type(), entry.row_change()) {
+            (EntryType::RowData, Some(row_change)) => row_change,
+            _ => return Ok(None),
+        };
+        let header = entry.header();
+        let key = |name: &str| json_string(&self.field_naming.apply(name));
+        let mut out = String::from("{");
+        out.push_str(&format!("{}:{},", key("database"), json_string(header.schema_name())));
+        out.push_str(&format!("{}:{},", key("table"), json_string(header.table_name())));
+        out.push_str(&format!("{}:{},", key("type"), json_string(&event_type_name(row_change.event_type()))));
+        out.push_str(&format!("{}:{},", key("isDdl"), row_change.is_ddl()));
+        out.push_str(&format!("{}:{},", key("es"), header.execute_time()));
+        out.push_str(&format!("{}:{},", key("sql"), json_string(row_change.sql())));
+
+        // 表结构取第一行的列
+        let columns = row_change.row_datas().first()
+            .map(|row_data| if row_data.after_columns().is_empty() { row_data.before_columns() } else { row_data.after_columns() });
+        let pk_names: Vec<String> = columns.iter().flat_map(|columns| columns.iter())
+            .filter(|column| column.is_key())
+            .map(|column| json_string(column.name()))
+            .collect();
+        out.push_str(&format!("{}:[{}],", key("pkNames"), pk_names.join(",")));
+        if self.include_types {
+            let mysql_type: Vec<String> = columns.iter().flat_map(|columns| columns.iter())
+                .map(|column| format!("{}:{}", json_string(column.name()), json_string(column.mysql_type())))
+                .collect();
+            out.push_str(&format!("{}:{{{}}},", key("mysqlType"), mysql_type.join(",")));
+            let sql_type: Vec<String> = columns.iter().flat_map(|columns| columns.iter())
+                .map(|column| format!("{}:{}", json_string(column.name()), column.sql_type()))
+                .collect();
+            out.push_str(&format!("{}:{{{}}},", key("sqlType"), sql_type.join(",")));
+        }
+
+        let mut data = vec![];
+        let mut old = vec![];
+        for row_data in row_change.row_datas() {
+            if row_change.event_type() == EventType::Delete {
+                data.push(self.json_columns(row_data.before_columns().iter())?);
+            } else {
+                data.push(self.json_columns(row_data.after_columns().iter())?);
+            }
+            if row_change.event_type() == EventType::Update {
+                let updated: Vec<&str> = row_data.after_columns().iter()
+                    .filter(|column| column.updated())
+                    .map(|column| column.name())
+                    .collect();
+                old.push(self.json_columns(row_data.before_columns().iter().filter(|column| updated.contains(&column.name())))?);
+            }
+        }
+        if row_change.is_ddl() {
+            out.push_str(&format!("{}:null,{}:null,", key("data"), key("old")));
+        } else {
+            out.push_str(&format!("{}:[{}],", key("data"), data.join(",")));
+            if old.is_empty() {
+                out.push_str(&format!("{}:null,", key("old")));
+            } else {
+                out.push_str(&format!("{}:[{}],", key("old"), old.join(",")));
+            }
+        }
+        out.push_str(&format!("{}:{},", key("logfile"), json_string(header.log_file_name())));
+        out.push_str(&format!("{}:{}", key("offset"), header.log_file_offset()));
+        if self.include_lineage {
+            let source = &self.lineage_source;
+            let gtid = if header.gtid().is_empty() { "null".to_string() } else { json_string(header.gtid()) };
+            out.push_str(&format!(",{}:{{{}:{},{}:{},{}:{},{}:{},{}:{},{}:{},{}:{}}}", key("lineage"),
+                                  key("host"), json_string(source.host()),
+                                  key("port"), source.port(),
+                                  key("serverUuid"), json_string(source.server_uuid()),
+                                  key("logfile"), json_string(header.log_file_name()),
+                                  key("offset"), header.log_file_offset(),
+                                  key("gtid"), gtid,
+                                  key("tableVersion"), json_string(&table_version(columns.map(|columns| columns.as_slice()).unwrap_or(&[])))));
+        }
+        out.push('}');
+        Ok(Some(out))
+    }
+
+    fn json_columns<'a>(&self, columns: impl Iterator<Item = &'a Column>) -> Result<String, String> {
+        let mut fields = vec![];
+        for column in columns {
+            let value = if column.is_null() {
+                None
+            } else if self.zero_date != ZeroDatePolicy::Keep && is_date_type(column.mysql_type()) {
+                self.zero_date.apply(column.value())
+                    .map_err(|e| format!("serialize column {} failure: {}", column.name(), e))?
+            } else {
+                Some(column.value().to_string())
+            };
+            let value = match value {
+                Some(value) if self.temporal_format != TemporalFormat::Text => {
+                    Some(self.temporal_format.format(&value, column.mysql_type(), &self.temporal_offset))
+                }
+                value => value,
+            };
+            if value.is_none() && !self.emit_nulls {
+                continue;
+            }
+            let value = value.map(|value| json_string(&value)).unwrap_or_else(|| "null".to_string());
+            fields.push(format!("{}:{}", json_string(column.name()), value));
+        }
+        Ok(format!("{{{}}}", fields.join(",")))
+    }
+}
+
+impl EntrySerializer for FlatMessageSerializer {
+    fn serialize(&self, entries: &[Entry]) -> Result<Vec<u8>, String> {
+        let mut messages = vec![];
+        for entry in entries {
+            if let Some(message) = self.to_json(entry)? {
+                messages.push(message);
+            }
+        }
+        Ok(format!("[{}]", messages.join(",")).into_bytes())
+    }
+
+    fn content_type(&self) -> &str {
+        FLAT_MESSAGE_CONTENT_TYPE
+    }
+}
+
+// INSERT, UPDATE, ...
+fn event_type_name(event_type: EventType) -> String {
+    format!("{:?}", event_type).to_ascii_uppercase()
+}
+
+/**
+ * <pre>
+ *  lineage块中entry本身没有的源端信息, 由部署方配置, 或者取连接master时的变量快照(server_uuid)
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineageSource {
+    host: String,
+    port: u16,
+    server_uuid: String,
+}
+
+impl LineageSource {
+    pub fn new(host: &str, port: u16, server_uuid: &str) -> LineageSource {
+        LineageSource { host: host.to_string(), port, server_uuid: server_uuid.to_string() }
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    pub fn server_uuid(&self) -> &str {
+        &self.server_uuid
+    }
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
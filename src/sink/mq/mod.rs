@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+
+use crate::protocol::Entry;
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::segment::TimeSegmenter;
+use crate::sink::size_limit::{entry_size, DEFAULT_MAX_ENTRY_SIZE};
+use crate::sink::EventSink;
+
+pub mod avro;
+
+pub mod codec;
+
+pub mod flat_message;
+
+pub mod protobuf;
+
+pub use avro::AvroSerializer;
+pub use codec::{decode_payload, Compression, CONTENT_ENCODING_HEADER};
+pub use flat_message::{EntrySerializer, FieldNaming, FlatMessageSerializer, LineageSource};
+pub use protobuf::ProtobufSerializer;
+
+pub const CONTENT_TYPE_HEADER: &str = "content-type";
+// 消息的目标topic, producer按该header发送, 没有时使用producer自己的默认topic
+pub const TOPIC_HEADER: &str = "topic";
+// 按event时间分段时消息所属的段, 例如20260101
+pub const SEGMENT_HEADER: &str = "segment";
+
+pub const DEFAULT_MQ_BATCH_SIZE: usize = 100;
+// 与kafka默认的max.message.bytes(1MB)保持一致
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = DEFAULT_MAX_ENTRY_SIZE;
+
+// 投递给broker的一条消息
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    headers: BTreeMap<String, String>,
+    payload: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(payload: Vec<u8>) -> Message {
+        Message { headers: BTreeMap::new(), payload }
+    }
+
+    pub fn headers(&self) -> &BTreeMap<String, String> {
+        &self.headers
+    }
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.get(key).map(|value| value.as_str())
+    }
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn set_header(&mut self, key: &str, value: &str) {
+        self.headers.insert(key.to_string(), value.to_string());
+    }
+
+    // 按content-encoding header解压后的payload
+    pub fn decoded_payload(&self) -> Result<Vec<u8>, String> {
+        decode_payload(self.header(CONTENT_ENCODING_HEADER), &self.payload)
+    }
+}
+
+/**
+ * <pre>
+ *  MQ的发送端(kafka, rocketmq, pulsar...)由使用方实现, send返回Ok表示broker已经确认
+ * </pre>
+ */
+pub trait MessageProducer: Send {
+    fn send(&mut self, message: Message) -> Result<(), String>;
+}
+
+/**
+ * <pre>
+ *  按批次投递到MQ的sink: 累积batch_size个entry(或者flush时)序列化为一条消息,
+ *  批次按entry_size估算的大小加上下一个entry会超过max_message_bytes时提前发送, 单个entry超过限制时单独发送,
+ *  需要限制单个entry时在前面包装SizeLimitSink.
+ *  发送失败时丢弃当前批次并返回Err: parser从事务开始的位点重新dump, 批次中的entry会被重新投递,
+ *  保留批次会导致重复发送.
+ *  按配置的压缩方式压缩payload, 并写入header:
+ *      content-type        序列化方式, 例如application/json
+ *      content-encoding    压缩方式, identity/lz4/zstd/gzip
+ *      topic               配置了topic时写入, 分段时为<topic>_<segment>, 例如binlog_20260101
+ *      segment             配置了segment时写入
+ *  分段按event的时间(见TimeSegmenter), 一条消息中的entry总是属于同一个段, entry的段变化时先发送之前的批次.
+ *  配置: batch_size=100, max_message_bytes=1048576, compression=none|lz4|zstd|gzip, topic, segment=none|hourly|daily, segment_utc_offset,
+ *        以及FlatMessageSerializer的field_naming, include_types, emit_nulls, temporal_format, temporal_utc_offset
+ * </pre>
+ */
+pub struct MqSink {
+    producer: Box<dyn MessageProducer>,
+    serializer: Box<dyn EntrySerializer>,
+    compression: Compression,
+    batch_size: usize,
+    batch: Vec<Entry>,
+    // 当前批次按entry_size估算的大小
+    batch_bytes: usize,
+    max_message_bytes: usize,
+    messages: u64,
+    // 压缩前后的字节数
+    raw_bytes: u64,
+    compressed_bytes: u64,
+    topic: Option<String>,
+    segmenter: Option<TimeSegmenter>,
+    // 当前批次所属的段
+    segment: Option<String>,
+}
+
+impl MqSink {
+    pub fn new(producer: Box<dyn MessageProducer>, serializer: Box<dyn EntrySerializer>) -> MqSink {
+        MqSink {
+            producer,
+            serializer,
+            compression: Compression::None,
+            batch_size: DEFAULT_MQ_BATCH_SIZE,
+            batch: vec![],
+            batch_bytes: 0,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            messages: 0,
+            raw_bytes: 0,
+            compressed_bytes: 0,
+            topic: None,
+            segmenter: None,
+            segment: None,
+        }
+    }
+
+    pub fn from_config(producer: Box<dyn MessageProducer>, config: &SinkConfig) -> Result<MqSink, String> {
+        let mut sink = MqSink::new(producer, Box::new(FlatMessageSerializer::from_config(config)?));
+        sink.set_batch_size(parse_or(config, "batch_size", DEFAULT_MQ_BATCH_SIZE)?);
+        sink.set_max_message_bytes(parse_or(config, "max_message_bytes", DEFAULT_MAX_MESSAGE_BYTES)?);
+        if let Some(name) = config.get("compression") {
+            sink.set_compression(Compression::from_name(name)?);
+        }
+        sink.set_topic(config.get("topic").map(|topic| topic.as_str()));
+        sink.set_segmenter(TimeSegmenter::from_config(config)?);
+        Ok(sink)
+    }
+
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    pub fn set_max_message_bytes(&mut self, max_message_bytes: usize) {
+        self.max_message_bytes = max_message_bytes.max(1);
+    }
+
+    pub fn set_topic(&mut self, topic: Option<&str>) {
+        self.topic = topic.map(|topic| topic.to_string());
+    }
+
+    pub fn set_segmenter(&mut self, segmenter: Option<TimeSegmenter>) {
+        self.segmenter = segmenter;
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+    pub fn max_message_bytes(&self) -> usize {
+        self.max_message_bytes
+    }
+    pub fn topic(&self) -> Option<&str> {
+        self.topic.as_deref()
+    }
+    pub fn segmenter(&self) -> Option<&TimeSegmenter> {
+        self.segmenter.as_ref()
+    }
+    // 当前批次所属的段
+    pub fn segment(&self) -> Option<&str> {
+        self.segment.as_deref()
+    }
+
+    // 当前批次发送到的topic
+    pub fn segment_topic(&self) -> Option<String> {
+        match (self.topic.as_ref(), self.segment.as_ref()) {
+            (Some(topic), Some(segment)) => Some(format!("{}_{}", topic, segment)),
+            (Some(topic), None) => Some(topic.clone()),
+            (None, _) => None,
+        }
+    }
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+    pub fn raw_bytes(&self) -> u64 {
+        self.raw_bytes
+    }
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed_bytes
+    }
+
+    // 压缩后与压缩前的字节数之比
+    pub fn compression_ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 1.0;
+        }
+        self.compressed_bytes as f64 / self.raw_bytes as f64
+    }
+
+    fn publish(&mut self) -> Result<(), String> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        // 批次已经从sink中取出, 无论发送是否成功都不会再次发送
+        let batch = std::mem::take(&mut self.batch);
+        self.batch_bytes = 0;
+        let raw = self.serializer.serialize(&batch)?;
+        let payload = self.compression.compress(&raw)?;
+        let (raw_len, payload_len) = (raw.len() as u64, payload.len() as u64);
+        let mut message = Message::new(payload);
+        message.set_header(CONTENT_TYPE_HEADER, self.serializer.content_type());
+        message.set_header(CONTENT_ENCODING_HEADER, self.compression.content_encoding());
+        if let Some(topic) = self.segment_topic() {
+            message.set_header(TOPIC_HEADER, &topic);
+        }
+        if let Some(segment) = self.segment.as_ref() {
+            message.set_header(SEGMENT_HEADER, segment);
+        }
+        self.producer.send(message)?;
+        self.messages += 1;
+        self.raw_bytes += raw_len;
+        self.compressed_bytes += payload_len;
+        Ok(())
+    }
+}
+
+impl EventSink for MqSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        // 没有时间的entry跟随当前的段
+        if let Some(segment) = self.segmenter.as_ref().and_then(|segmenter| segmenter.segment(entry)) {
+            if self.segment.as_ref() != Some(&segment) {
+                self.publish()?;
+                self.segment = Some(segment);
+            }
+        }
+        let size = entry_size(entry);
+        if !self.batch.is_empty() && self.batch_bytes + size > self.max_message_bytes {
+            self.publish()?;
+        }
+        self.batch.push(entry.clone());
+        self.batch_bytes += size;
+        if self.batch.len() >= self.batch_size {
+            self.publish()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.publish()
+    }
+
+    // batch_size和max_message_bytes可以热加载, 从下一个entry开始生效
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        let batch_size = parse_or(config, "batch_size", DEFAULT_MQ_BATCH_SIZE)?;
+        let max_message_bytes = parse_or(config, "max_message_bytes", DEFAULT_MAX_MESSAGE_BYTES)?;
+        self.set_batch_size(batch_size);
+        self.set_max_message_bytes(max_message_bytes);
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use mini_canal_types as types;
+use mini_canal_types::Message;
+
+use crate::protocol::{Entry, EntryType};
+use crate::sink::mq::EntrySerializer;
+
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/**
+ * <pre>
+ *  按mini-canal-types中的Entry(prost)序列化, 批次中的entry依次以length-delimited的方式写入,
+ *  消费方循环调用 types::Entry::decode_length_delimited 直到payload结束. heartbeat不输出
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ProtobufSerializer;
+
+impl ProtobufSerializer {
+    pub fn new() -> ProtobufSerializer {
+        ProtobufSerializer
+    }
+}
+
+impl EntrySerializer for ProtobufSerializer {
+    fn serialize(&self, entries: &[Entry]) -> Result<Vec<u8>, String> {
+        let mut out = vec![];
+        for entry in entries.iter().filter(|entry| entry.entry_type() != EntryType::Heartbeat) {
+            types::Entry::from(entry).encode_length_delimited(&mut out)
+                .map_err(|e| format!("encode entry at {}:{} failure: {}", entry.header().log_file_name(),
+                                     entry.header().log_file_offset(), e))?;
+        }
+        Ok(out)
+    }
+
+    fn content_type(&self) -> &str {
+        PROTOBUF_CONTENT_TYPE
+    }
+}
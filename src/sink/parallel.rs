@@ -0,0 +1,370 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::protocol::{Column, Entry, EntryType, RowData};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::EventSink;
+
+pub const DEFAULT_APPLY_LANES: usize = 4;
+// 每个lane的队列长度, 满了之后on_event阻塞
+pub const DEFAULT_LANE_CAPACITY: usize = 1024;
+
+/**
+ * <pre>
+ *  在目标库中执行行变更, 每个lane一个实例(一个连接), mysql/postgres见apply::SqlApplier.
+ *  apply收到的entry只包含同一个lane的行; flush需要提交已经执行的变更,
+ *  之后其它lane的连接可以看到这些变更
+ * </pre>
+ */
+pub trait RowApplier: Send {
+    fn apply(&mut self, entry: &Entry) -> Result<(), String>;
+
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// 参数为lane的序号
+pub type ApplierFactory = Box<dyn Fn(usize) -> Result<Box<dyn RowApplier>, String> + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaneMode {
+    // 同一个表的行在同一个lane
+    Table,
+    // 按表和主键的hash分配lane, 没有主键的表按Table
+    PrimaryKey,
+}
+
+impl LaneMode {
+    pub fn from_name(name: &str) -> Result<LaneMode, String> {
+        match name {
+            "table" => Ok(LaneMode::Table),
+            "pk" => Ok(LaneMode::PrimaryKey),
+            _ => Err(format!("unknown apply mode {}, expect table/pk", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LaneMode::Table => "table",
+            LaneMode::PrimaryKey => "pk",
+        }
+    }
+}
+
+enum LaneTask {
+    Apply(Box<Entry>),
+    // 之前的任务执行完并且flush之后回复
+    Flush(SyncSender<Result<(), String>>),
+}
+
+struct Lane {
+    sender: SyncSender<LaneTask>,
+    handle: JoinHandle<()>,
+}
+
+/**
+ * <pre>
+ *  多个lane并行执行行变更, 用于单线程apply跟不上源端提交速度的场景:
+ *  行按LaneMode分配到lane, 同一个主键(或者同一个表)的变更总是在同一个lane中按binlog顺序执行.
+ *  跨lane的依赖:
+ *      1. 修改主键的update, before和after的主键可能在不同的lane, 先等待before所在的lane执行完并flush,
+ *         之后在after所在的lane执行, 保证与前后的变更的顺序
+ *      2. DDL是barrier, 等待所有lane执行完并flush, 之后在lane 0中执行并flush
+ *  binlog_row_image=MINIMAL时update的after image只有变化的列, 其中没有的主键列沿用before image的值,
+ *  因此没有修改主键的update与同一主键的其它变更在同一个lane.
+ *  binlog中没有唯一索引的信息, 有唯一索引(非主键)的表需要通过add_unique_key_table(apply_unique_key_tables)声明,
+ *  这些表的行按Table分配: 不同主键的行可能先后占用同一个唯一键的值(先delete再insert), 分到不同lane会冲突.
+ *  不同lane之间不保证源端事务的原子性, 目标库可能短暂看到事务的一部分; 需要原子性时使用单个lane.
+ *  TransactionBegin/TransactionEnd/heartbeat等不投递, flush等待所有lane执行完并flush.
+ *  lane失败之后不再执行后续的变更, 错误通过之后的on_event/flush返回.
+ *  配置: apply_lanes=4, apply_mode=pk, lane_capacity=1024, apply_unique_key_tables=db.users,db.orders
+ * </pre>
+ */
+pub struct ParallelApplySink {
+    lanes: Vec<Lane>,
+    mode: LaneMode,
+    // 有唯一索引的表, 格式为schema.table
+    unique_key_tables: HashSet<String>,
+    error: Arc<Mutex<Option<String>>>,
+    applied: u64,
+    // DDL barrier的次数
+    barriers: u64,
+    // 跨lane等待的次数(不包括DDL)
+    waits: u64,
+}
+
+impl ParallelApplySink {
+    pub fn new(factory: ApplierFactory, lanes: usize, mode: LaneMode, capacity: usize) -> Result<ParallelApplySink, String> {
+        if lanes == 0 {
+            return Err("apply_lanes must be greater than 0".to_string());
+        }
+        let error = Arc::new(Mutex::new(None));
+        let mut sink = ParallelApplySink {
+            lanes: vec![],
+            mode,
+            unique_key_tables: HashSet::new(),
+            error: error.clone(),
+            applied: 0,
+            barriers: 0,
+            waits: 0,
+        };
+        for index in 0..lanes {
+            let applier = factory(index)?;
+            let (sender, receiver) = sync_channel(capacity.max(1));
+            let error = error.clone();
+            let handle = thread::Builder::new()
+                .name(format!("apply-lane-{}", index))
+                .spawn(move || run_lane(applier, receiver, error))
+                .map_err(|e| format!("spawn apply lane {} failure: {}", index, e))?;
+            sink.lanes.push(Lane { sender, handle });
+        }
+        Ok(sink)
+    }
+
+    pub fn from_config(factory: ApplierFactory, config: &SinkConfig) -> Result<ParallelApplySink, String> {
+        let mode = match config.get("apply_mode") {
+            Some(name) => LaneMode::from_name(name)?,
+            None => LaneMode::PrimaryKey,
+        };
+        let mut sink = ParallelApplySink::new(factory, parse_or(config, "apply_lanes", DEFAULT_APPLY_LANES)?, mode,
+                                              parse_or(config, "lane_capacity", DEFAULT_LANE_CAPACITY)?)?;
+        for name in config.get("apply_unique_key_tables").map(|names| names.split(',')).into_iter().flatten() {
+            match name.trim().split_once('.') {
+                Some((schema, table)) => sink.add_unique_key_table(schema, table),
+                None if name.trim().is_empty() => {}
+                None => return Err(format!("invalid apply_unique_key_tables entry {}, expect schema.table", name)),
+            }
+        }
+        Ok(sink)
+    }
+
+    // 声明有唯一索引(非主键)的表, 该表的行总是在同一个lane
+    pub fn add_unique_key_table(&mut self, schema: &str, table: &str) {
+        self.unique_key_tables.insert(format!("{}.{}", schema, table));
+    }
+
+    pub fn lanes(&self) -> usize {
+        self.lanes.len()
+    }
+    pub fn mode(&self) -> LaneMode {
+        self.mode
+    }
+    pub fn unique_key_tables(&self) -> &HashSet<String> {
+        &self.unique_key_tables
+    }
+    // 投递到lane的entry数, 一个entry拆分到多个lane时分别计数
+    pub fn applied(&self) -> u64 {
+        self.applied
+    }
+    pub fn barriers(&self) -> u64 {
+        self.barriers
+    }
+    pub fn waits(&self) -> u64 {
+        self.waits
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match self.error.lock().map_err(|_| "apply lane error is poisoned".to_string())?.as_ref() {
+            Some(e) => Err(e.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn send(&mut self, lane: usize, entry: Entry) -> Result<(), String> {
+        self.lanes[lane].sender.send(LaneTask::Apply(Box::new(entry)))
+            .map_err(|_| format!("apply lane {} is stopped", lane))?;
+        self.applied += 1;
+        Ok(())
+    }
+
+    // 等待lane之前的变更执行完并提交
+    fn wait(&self, lanes: &[usize]) -> Result<(), String> {
+        let mut acks = vec![];
+        for lane in lanes {
+            let (sender, receiver) = sync_channel(1);
+            self.lanes[*lane].sender.send(LaneTask::Flush(sender))
+                .map_err(|_| format!("apply lane {} is stopped", lane))?;
+            acks.push((*lane, receiver));
+        }
+        for (lane, receiver) in acks {
+            receiver.recv().map_err(|_| format!("apply lane {} is stopped", lane))??;
+        }
+        Ok(())
+    }
+
+    fn lane_of<T: Hash>(&self, key: &T) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.lanes.len() as u64) as usize
+    }
+
+    // 一行涉及的lane: before和after的主键所在的lane, 最后一个是执行的lane
+    fn row_lanes(&self, schema: &str, table: &str, row: &RowData) -> Vec<usize> {
+        let table_lane = self.lane_of(&(schema, table));
+        if self.mode == LaneMode::Table
+            || (!self.unique_key_tables.is_empty() && self.unique_key_tables.contains(&format!("{}.{}", schema, table))) {
+            return vec![table_lane];
+        }
+        let (before, after) = (row.before_columns(), row.after_columns());
+        let mut lanes = vec![];
+        for (columns, key) in [(before, primary_key(before)), (after, after_key(before, after))] {
+            let lane = match key {
+                Some(key) => self.lane_of(&(schema, table, key)),
+                None if columns.is_empty() => continue,
+                None => table_lane,
+            };
+            if !lanes.contains(&lane) {
+                lanes.push(lane);
+            }
+        }
+        if lanes.is_empty() {
+            lanes.push(table_lane);
+        }
+        lanes
+    }
+
+    fn dispatch_rows(&mut self, entry: &Entry) -> Result<(), String> {
+        let row_change = match entry.row_change() {
+            Some(row_change) => row_change,
+            None => return Ok(()),
+        };
+        let (schema, table) = (entry.header().schema_name(), entry.header().table_name());
+        // 按lane分组, 保持每个lane中行的顺序
+        let mut pending: Vec<Vec<RowData>> = vec![vec![]; self.lanes.len()];
+        for row in row_change.row_datas() {
+            let lanes = self.row_lanes(schema, table, row);
+            let target = lanes[lanes.len() - 1];
+            if lanes.len() == 1 {
+                pending[target].push(row.clone());
+                continue;
+            }
+            self.send_pending(entry, &mut pending)?;
+            self.waits += 1;
+            self.wait(&lanes[..lanes.len() - 1])?;
+            self.send(target, with_rows(entry, vec![row.clone()]))?;
+        }
+        self.send_pending(entry, &mut pending)
+    }
+
+    fn send_pending(&mut self, entry: &Entry, pending: &mut [Vec<RowData>]) -> Result<(), String> {
+        for (lane, rows) in pending.iter_mut().enumerate() {
+            if !rows.is_empty() {
+                let rows = std::mem::take(rows);
+                self.send(lane, with_rows(entry, rows))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn barrier(&mut self, entry: &Entry) -> Result<(), String> {
+        self.barriers += 1;
+        let all: Vec<usize> = (0..self.lanes.len()).collect();
+        self.wait(&all)?;
+        self.send(0, entry.clone())?;
+        self.wait(&[0])
+    }
+}
+
+impl EventSink for ParallelApplySink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.check()?;
+        if entry.entry_type() != EntryType::RowData {
+            return Ok(());
+        }
+        match entry.row_change() {
+            Some(row_change) if row_change.is_ddl() => self.barrier(entry),
+            Some(_) => self.dispatch_rows(entry),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let all: Vec<usize> = (0..self.lanes.len()).collect();
+        self.wait(&all)?;
+        self.check()
+    }
+}
+
+impl Drop for ParallelApplySink {
+    // 关闭队列, lane执行完剩余的任务后退出
+    fn drop(&mut self) {
+        let handles: Vec<JoinHandle<()>> = self.lanes.drain(..).map(|lane| lane.handle).collect();
+        for handle in handles {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn run_lane(mut applier: Box<dyn RowApplier>, receiver: Receiver<LaneTask>, error: Arc<Mutex<Option<String>>>) {
+    let mut failure: Option<String> = None;
+    for task in receiver {
+        match task {
+            LaneTask::Apply(entry) => {
+                if failure.is_some() {
+                    continue;
+                }
+                if let Err(e) = applier.apply(&entry) {
+                    let e = format!("apply {}:{} failure: {}", entry.header().log_file_name(),
+                                    entry.header().log_file_offset(), e);
+                    if let Ok(mut error) = error.lock() {
+                        error.get_or_insert_with(|| e.clone());
+                    }
+                    failure = Some(e);
+                }
+            }
+            LaneTask::Flush(ack) => {
+                let result = match failure.as_ref() {
+                    Some(e) => Err(e.clone()),
+                    None => applier.flush(),
+                };
+                if let Err(e) = result.as_ref() {
+                    if let Ok(mut error) = error.lock() {
+                        error.get_or_insert_with(|| e.clone());
+                    }
+                    failure.get_or_insert_with(|| e.clone());
+                }
+                let _ = ack.send(result);
+            }
+        }
+    }
+}
+
+// 主键列的值, 没有主键列时返回None
+fn primary_key(columns: &[Column]) -> Option<Vec<Option<&str>>> {
+    let key: Vec<Option<&str>> = columns.iter()
+        .filter(|column| column.is_key())
+        .map(|column| if column.is_null() { None } else { Some(column.value()) })
+        .collect();
+    if key.is_empty() {
+        None
+    } else {
+        Some(key)
+    }
+}
+
+// after image的主键, 其中没有的主键列(MINIMAL)沿用before image的值, 按列的序号对应
+fn after_key<'a>(before: &'a [Column], after: &'a [Column]) -> Option<Vec<Option<&'a str>>> {
+    if after.is_empty() || !before.iter().any(|column| column.is_key()) {
+        return primary_key(after);
+    }
+    let key = before.iter().filter(|column| column.is_key())
+        .map(|key| after.iter().find(|column| column.index() == key.index()).unwrap_or(key))
+        .map(|column| if column.is_null() { None } else { Some(column.value()) })
+        .collect();
+    Some(key)
+}
+
+fn with_rows(entry: &Entry, rows: Vec<RowData>) -> Entry {
+    let mut entry = entry.clone();
+    if let Some(row_change) = entry.row_change() {
+        let mut row_change = row_change.clone();
+        row_change.set_row_datas(rows);
+        entry.set_row_change(row_change);
+    }
+    entry
+}
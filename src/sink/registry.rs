@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::parse_duration;
+use crate::sink::audit::AuditSink;
+use crate::sink::file::FileSink;
+use crate::sink::logger::LoggerSink;
+use crate::sink::EventSink;
+
+// sink的配置, 例如 type=audit, max_violations=100
+pub type SinkConfig = HashMap<String, String>;
+
+pub type SinkFactory = Box<dyn Fn(&SinkConfig) -> Result<Box<dyn EventSink>, String> + Send + Sync>;
+
+// 配置中指定sink名字的key
+pub const SINK_TYPE_KEY: &str = "type";
+
+/**
+ * <pre>
+ *  sink名字到构造函数的映射, 下游crate可以注册自己的sink,
+ *  之后只通过配置(type=名字)选择, 不需要修改mini-canal:
+ *      let mut registry = SinkRegistry::with_builtins();
+ *      registry.register("kafka", Box::new(|config| Ok(Box::new(KafkaSink::new(config)?))))?;
+ *      let sink = registry.create_from_config(&config)?;
+ *  内置的sink: logger, audit, file
+ * </pre>
+ */
+#[derive(Default)]
+pub struct SinkRegistry {
+    factories: HashMap<String, SinkFactory>,
+}
+
+impl SinkRegistry {
+    pub fn new() -> SinkRegistry {
+        SinkRegistry { factories: HashMap::new() }
+    }
+
+    pub fn with_builtins() -> SinkRegistry {
+        let mut registry = SinkRegistry::new();
+        registry.factories.insert("logger".to_string(), Box::new(|config: &SinkConfig| {
+            let verbose = parse_or(config, "verbose", false)?;
+            Ok(Box::new(LoggerSink::new(verbose)) as Box<dyn EventSink>)
+        }));
+        registry.factories.insert("audit".to_string(), Box::new(|config: &SinkConfig| {
+            let mut sink = AuditSink::new();
+            sink.set_max_violations(parse_or(config, "max_violations", 1024)?);
+            Ok(Box::new(sink) as Box<dyn EventSink>)
+        }));
+        registry.factories.insert("file".to_string(), Box::new(|config: &SinkConfig| {
+            Ok(Box::new(FileSink::from_config(config)?) as Box<dyn EventSink>)
+        }));
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, factory: SinkFactory) -> Result<(), String> {
+        if self.factories.contains_key(name) {
+            return Err(format!("sink {} is already registered", name));
+        }
+        self.factories.insert(name.to_string(), factory);
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn create(&self, name: &str, config: &SinkConfig) -> Result<Box<dyn EventSink>, String> {
+        let factory = self.factories.get(name)
+            .ok_or_else(|| format!("unknown sink {}, registered: {:?}", name, self.names()))?;
+        factory(config).map_err(|e| format!("create sink {} failure: {}", name, e))
+    }
+
+    pub fn create_from_config(&self, config: &SinkConfig) -> Result<Box<dyn EventSink>, String> {
+        let name = config.get(SINK_TYPE_KEY)
+            .ok_or_else(|| format!("sink config is missing '{}'", SINK_TYPE_KEY))?;
+        self.create(name, config)
+    }
+}
+
+pub fn parse_or<T: std::str::FromStr>(config: &SinkConfig, key: &str, default: T) -> Result<T, String> {
+    match config.get(key) {
+        Some(value) => value.parse().map_err(|_| format!("invalid value for {}: {}", key, value)),
+        None => Ok(default),
+    }
+}
+
+// 时间类的配置按parse_duration解析, 例如 flush_interval=500ms
+pub fn duration_or(config: &SinkConfig, key: &str, default: Duration) -> Result<Duration, String> {
+    match config.get(key) {
+        Some(value) => parse_duration(value).map_err(|e| format!("invalid value for {}: {}", key, e)),
+        None => Ok(default),
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::metrics::MemoryGauge;
+use crate::protocol::Entry;
+use crate::sink::registry::{duration_or, parse_or, SinkConfig};
+use crate::sink::size_limit::entry_size;
+use crate::sink::EventSink;
+
+pub const DEFAULT_REPLAY_BATCHES: usize = 64;
+pub const DEFAULT_REPLAY_BYTES: usize = 64 * 1024 * 1024;
+pub const DEFAULT_REPLAY_BATCH_SIZE: usize = 1000;
+// 未确认的批次达到max_in_flight之后, 等待ack的最长时间
+pub const DEFAULT_IN_FLIGHT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// 等待ack时检查cache的间隔
+const IN_FLIGHT_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone)]
+pub struct ReplayBatch {
+    batch_id: u64,
+    entries: Vec<Entry>,
+    // 按entry_size估算的内存占用
+    size: usize,
+}
+
+impl ReplayBatch {
+    pub fn batch_id(&self) -> u64 {
+        self.batch_id
+    }
+    pub fn entries(&self) -> &Vec<Entry> {
+        &self.entries
+    }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/**
+ * <pre>
+ *  最近投递的批次, 对应canal client协议中get/ack/rollback的语义:
+ *      put         保存一个已投递的批次, 分配递增的batch id
+ *      get         按batch id取回批次重新投递(rollback之后的get again, 或者sink重试), 不需要重新从master拉取
+ *      ack         确认batch id及之前的批次, 释放内存
+ *      unacked     未确认的批次, rollback时按顺序重新投递
+ *  cache中只有未确认的批次, 不会淘汰: 批次数达到max_batches或者内存会超过max_bytes时put返回Err,
+ *  调用方等待ack之后再放入(见ReplaySink). cache为空时总是接受, 单个批次可以超过max_bytes.
+ *  已确认的批次get时返回None
+ * </pre>
+ */
+#[derive(Debug)]
+pub struct ReplayCache {
+    max_batches: usize,
+    max_bytes: usize,
+    batches: VecDeque<ReplayBatch>,
+    next_batch_id: u64,
+    used_bytes: usize,
+    hits: u64,
+    misses: u64,
+    // cache已满而拒绝的put次数
+    rejections: u64,
+    memory_gauge: Option<MemoryGauge>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        ReplayCache::new(DEFAULT_REPLAY_BATCHES, DEFAULT_REPLAY_BYTES)
+    }
+}
+
+impl ReplayCache {
+    pub fn new(max_batches: usize, max_bytes: usize) -> ReplayCache {
+        ReplayCache {
+            max_batches: max_batches.max(1),
+            max_bytes,
+            batches: VecDeque::new(),
+            next_batch_id: 1,
+            used_bytes: 0,
+            hits: 0,
+            misses: 0,
+            rejections: 0,
+            memory_gauge: None,
+        }
+    }
+
+    // 同步used_bytes, 例如 MemoryStats::gauge("replay_cache")
+    pub fn set_memory_gauge(&mut self, gauge: MemoryGauge) {
+        if let Some(previous) = self.memory_gauge.as_ref() {
+            previous.sub(self.used_bytes);
+        }
+        gauge.add(self.used_bytes);
+        self.memory_gauge = Some(gauge);
+    }
+
+    // 是否还能放入size字节的批次
+    pub fn fits(&self, size: usize) -> bool {
+        self.batches.is_empty() || (self.batches.len() < self.max_batches && self.used_bytes + size <= self.max_bytes)
+    }
+
+    // 保存批次并返回batch id, cache已满时返回Err, 批次没有放入
+    pub fn put(&mut self, entries: Vec<Entry>) -> Result<u64, String> {
+        let size = entries.iter().map(entry_size).sum();
+        if !self.fits(size) {
+            self.rejections += 1;
+            return Err(format!("replay cache is full of unacked batches ({} batches, {} bytes, batch of {} bytes), ack before put",
+                               self.batches.len(), self.used_bytes, size));
+        }
+        let used_bytes = self.used_bytes;
+        let batch_id = self.next_batch_id;
+        self.next_batch_id += 1;
+        self.used_bytes += size;
+        self.batches.push_back(ReplayBatch { batch_id, entries, size });
+        self.sync_memory(used_bytes);
+        Ok(batch_id)
+    }
+
+    pub fn get(&mut self, batch_id: u64) -> Option<&ReplayBatch> {
+        match self.batches.iter().position(|batch| batch.batch_id == batch_id) {
+            Some(index) => {
+                self.hits += 1;
+                self.batches.get(index)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    // 确认batch_id及之前的批次, 返回释放的批次数
+    pub fn ack(&mut self, batch_id: u64) -> usize {
+        let used_bytes = self.used_bytes;
+        let mut released = 0;
+        while let Some(batch) = self.batches.front() {
+            if batch.batch_id > batch_id {
+                break;
+            }
+            self.used_bytes -= batch.size;
+            self.batches.pop_front();
+            released += 1;
+        }
+        self.sync_memory(used_bytes);
+        released
+    }
+
+    pub fn unacked(&self) -> Vec<u64> {
+        self.batches.iter().map(|batch| batch.batch_id).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.batches.clear();
+        let used_bytes = std::mem::take(&mut self.used_bytes);
+        self.sync_memory(used_bytes);
+    }
+
+    fn sync_memory(&self, previous: usize) {
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.adjust(previous, self.used_bytes);
+        }
+    }
+
+    pub fn max_batches(&self) -> usize {
+        self.max_batches
+    }
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+    pub fn len(&self) -> usize {
+        self.batches.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+    pub fn rejections(&self) -> u64 {
+        self.rejections
+    }
+}
+
+impl Drop for ReplayCache {
+    fn drop(&mut self) {
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.sub(self.used_bytes);
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  包装下游sink, 按batch_size累积entry, 批次满或者flush时先放入ReplayCache再投递给下游.
+ *  下游返回Err时批次仍然在cache中, 调用方可以通过redeliver(batch_id)重试而不需要重新dump.
+ *  下游确认后调用方通过cache()的句柄ack.
+ *  未确认的批次达到max_in_flight, 或者cache放不下新的批次(replay_batches/replay_bytes)时不再投递新的批次,
+ *  on_event/flush阻塞等待ack(反压到parser), 超过in_flight_timeout仍然没有ack时返回Err, 批次保留在sink中.
+ *  没有设置max_in_flight时只受cache的限制.
+ *  小的max_in_flight降低重放和内存的代价, 大的max_in_flight允许下游异步确认以提高吞吐.
+ *  max_in_flight不能大于replay_batches, 否则cache的限制先生效.
+ *  配置: replay_batch_size=1000, replay_batches=64, replay_bytes=67108864, max_in_flight=8, in_flight_timeout=30s
+ * </pre>
+ */
+pub struct ReplaySink {
+    inner: Box<dyn EventSink>,
+    cache: Arc<Mutex<ReplayCache>>,
+    batch_size: usize,
+    batch: Vec<Entry>,
+    redelivered: u64,
+    // None表示不限制
+    max_in_flight: Option<usize>,
+    in_flight_timeout: Duration,
+    // 因为未确认的批次过多而等待的次数
+    throttled: u64,
+}
+
+impl ReplaySink {
+    pub fn new(inner: Box<dyn EventSink>, cache: ReplayCache, batch_size: usize) -> ReplaySink {
+        ReplaySink {
+            inner,
+            cache: Arc::new(Mutex::new(cache)),
+            batch_size: batch_size.max(1),
+            batch: vec![],
+            redelivered: 0,
+            max_in_flight: None,
+            in_flight_timeout: DEFAULT_IN_FLIGHT_TIMEOUT,
+            throttled: 0,
+        }
+    }
+
+    pub fn from_config(inner: Box<dyn EventSink>, config: &SinkConfig) -> Result<ReplaySink, String> {
+        let cache = ReplayCache::new(parse_or(config, "replay_batches", DEFAULT_REPLAY_BATCHES)?,
+                                     parse_or(config, "replay_bytes", DEFAULT_REPLAY_BYTES)?);
+        let mut sink = ReplaySink::new(inner, cache, parse_or(config, "replay_batch_size", DEFAULT_REPLAY_BATCH_SIZE)?);
+        if let Some(value) = config.get("max_in_flight") {
+            let max_in_flight = value.parse().map_err(|_| format!("invalid value for max_in_flight: {}", value))?;
+            sink.set_max_in_flight(Some(max_in_flight))?;
+        }
+        sink.set_in_flight_timeout(duration_or(config, "in_flight_timeout", DEFAULT_IN_FLIGHT_TIMEOUT)?);
+        Ok(sink)
+    }
+
+    pub fn set_max_in_flight(&mut self, max_in_flight: Option<usize>) -> Result<(), String> {
+        if let Some(max_in_flight) = max_in_flight {
+            let max_batches = self.cache.lock().map_err(|_| "replay cache lock is poisoned".to_string())?.max_batches();
+            if max_in_flight == 0 || max_in_flight > max_batches {
+                return Err(format!("max_in_flight must be in [1, {}] (replay_batches), got {}", max_batches, max_in_flight));
+            }
+        }
+        self.max_in_flight = max_in_flight;
+        Ok(())
+    }
+
+    pub fn set_in_flight_timeout(&mut self, in_flight_timeout: Duration) {
+        self.in_flight_timeout = in_flight_timeout;
+    }
+
+    pub fn max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+    pub fn in_flight_timeout(&self) -> Duration {
+        self.in_flight_timeout
+    }
+    pub fn throttled(&self) -> u64 {
+        self.throttled
+    }
+
+    // 已投递还没有确认的批次数
+    pub fn in_flight(&self) -> Result<usize, String> {
+        Ok(self.cache.lock().map_err(|_| "replay cache lock is poisoned".to_string())?.len())
+    }
+
+    pub fn cache(&self) -> Arc<Mutex<ReplayCache>> {
+        self.cache.clone()
+    }
+
+    pub fn redelivered(&self) -> u64 {
+        self.redelivered
+    }
+
+    // 重新投递cache中的批次, 批次已经确认时返回Err
+    pub fn redeliver(&mut self, batch_id: u64) -> Result<(), String> {
+        let entries = {
+            let mut cache = self.cache.lock().map_err(|_| "replay cache lock is poisoned".to_string())?;
+            match cache.get(batch_id) {
+                Some(batch) => batch.entries().clone(),
+                None => return Err(format!("batch {} is not in replay cache, it is acked or unknown", batch_id)),
+            }
+        };
+        self.deliver(&entries)?;
+        self.redelivered += 1;
+        Ok(())
+    }
+
+    // rollback: 按顺序重新投递所有未确认的批次
+    pub fn rollback(&mut self) -> Result<(), String> {
+        let unacked = self.cache.lock().map_err(|_| "replay cache lock is poisoned".to_string())?.unacked();
+        for batch_id in unacked {
+            self.redeliver(batch_id)?;
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self) -> Result<(), String> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let size = self.batch.iter().map(entry_size).sum();
+        self.wait_in_flight(size)?;
+        self.cache.lock().map_err(|_| "replay cache lock is poisoned".to_string())?.put(self.batch.clone())?;
+        let entries = std::mem::take(&mut self.batch);
+        self.deliver(&entries)
+    }
+
+    // 等待未确认的批次少于max_in_flight并且cache能放下size字节的批次, 超时返回Err, 批次保留在self.batch中
+    fn wait_in_flight(&mut self, size: usize) -> Result<(), String> {
+        let deadline = Instant::now() + self.in_flight_timeout;
+        let mut waited = false;
+        loop {
+            let (in_flight, fits) = {
+                let cache = self.cache.lock().map_err(|_| "replay cache lock is poisoned".to_string())?;
+                (cache.len(), cache.fits(size))
+            };
+            if fits && self.max_in_flight.is_none_or(|max_in_flight| in_flight < max_in_flight) {
+                return Ok(());
+            }
+            if !waited {
+                waited = true;
+                self.throttled += 1;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!("replay sink has {} unacked batches (max_in_flight {:?}), no ack within {:?}",
+                                   in_flight, self.max_in_flight, self.in_flight_timeout));
+            }
+            thread::sleep(IN_FLIGHT_CHECK_INTERVAL.min(deadline - now));
+        }
+    }
+
+    fn deliver(&mut self, entries: &[Entry]) -> Result<(), String> {
+        for entry in entries {
+            self.inner.on_event(entry)?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl EventSink for ReplaySink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.batch.push(entry.clone());
+        if self.batch.len() >= self.batch_size {
+            self.publish()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.publish()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    // replay_batch_size, max_in_flight和in_flight_timeout可以热加载, cache的大小不变
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        let batch_size = parse_or(config, "replay_batch_size", DEFAULT_REPLAY_BATCH_SIZE)?;
+        let max_in_flight = config.get("max_in_flight")
+            .map(|value| value.parse().map_err(|_| format!("invalid value for max_in_flight: {}", value)))
+            .transpose()?;
+        let in_flight_timeout = duration_or(config, "in_flight_timeout", DEFAULT_IN_FLIGHT_TIMEOUT)?;
+        self.inner.reconfigure(config)?;
+        self.set_max_in_flight(max_in_flight)?;
+        self.batch_size = batch_size.max(1);
+        self.in_flight_timeout = in_flight_timeout;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use crate::filter::RegexFilter;
+use crate::instance::EntryPosition;
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::SinkConfig;
+use crate::sink::EventSink;
+
+// 一条路由规则, 匹配的entry攒批后交给自己的sink
+pub struct Route {
+    name: String,
+    filter: RegexFilter,
+    sink: Box<dyn EventSink>,
+    batch_size: usize,
+    // (序号, entry), 还没有被sink确认
+    pending: Vec<(u64, Entry)>,
+    delivered: u64,
+}
+
+impl Route {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn pattern(&self) -> &str {
+        self.filter.pattern()
+    }
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    // 投递整批并flush, flush返回Ok视为该批被确认
+    fn ack(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        for (_, entry) in self.pending.iter() {
+            self.sink.on_event(entry).map_err(|e| format!("route {} failure: {}", self.name, e))?;
+        }
+        self.sink.flush().map_err(|e| format!("route {} failure: {}", self.name, e))?;
+        self.delivered += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/**
+ * <pre>
+ *  按schema.table把同一个instance的数据路由到不同的sink, 例如:
+ *      orders\\..*     -> kafka topic A, batch_size=500
+ *      audit\\..*      -> 文件归档, batch_size=1
+ *  RowData按添加顺序匹配第一条规则, 没有匹配的entry被丢弃;
+ *  事务头尾以及heartbeat不交给sink, 只参与位点的推进.
+ *  每个route独立攒批, 攒满batch_size或者flush()时投递并确认.
+ *  所有route共享同一个源位点: committed_position()只在事务结束(TransactionEnd)以及事务外的entry(DDL)之后推进,
+ *  并且之前的entry都已经被所有route确认, 与ProcessStage相同, 位点为该entry的结束位置.
+ *  重启时从该位点开始不会丢数据, 也不会从事务中间开始, 已经确认过的route可能收到重复的数据
+ * </pre>
+ */
+#[derive(Default)]
+pub struct RouteSink {
+    routes: Vec<Route>,
+    sequence: u64,
+    // 还没有提交的事务结束点的(序号, 位点)
+    uncommitted: VecDeque<(u64, EntryPosition)>,
+    committed: Option<EntryPosition>,
+    in_transaction: bool,
+    unrouted: u64,
+}
+
+impl RouteSink {
+    pub fn new() -> RouteSink {
+        RouteSink::default()
+    }
+
+    pub fn add_route(&mut self, name: &str, pattern: &str, sink: Box<dyn EventSink>, batch_size: usize)
+                     -> Result<(), String> {
+        if self.routes.iter().any(|route| route.name == name) {
+            return Err(format!("route {} is already added", name));
+        }
+        self.routes.push(Route {
+            name: name.to_string(),
+            filter: RegexFilter::new(pattern)?,
+            sink,
+            batch_size: batch_size.max(1),
+            pending: vec![],
+            delivered: 0,
+        });
+        Ok(())
+    }
+
+    pub fn routes(&self) -> &Vec<Route> {
+        &self.routes
+    }
+
+    // 所有route都确认过的最后一个事务结束之后的位点
+    pub fn committed_position(&self) -> Option<&EntryPosition> {
+        self.committed.as_ref()
+    }
+
+    // 没有匹配任何规则而丢弃的RowData数量
+    pub fn unrouted(&self) -> u64 {
+        self.unrouted
+    }
+
+    // 事务结束以及事务外的entry(例如DDL)之后的位点
+    fn commit_point(&mut self, entry: &Entry) -> Option<EntryPosition> {
+        match entry.entry_type() {
+            EntryType::TransactionBegin => {
+                self.in_transaction = true;
+                return None;
+            }
+            EntryType::TransactionEnd => self.in_transaction = false,
+            EntryType::RowData if !self.in_transaction => {}
+            _ => return None,
+        }
+        let header = entry.header();
+        let mut position = header.position();
+        position.set_position(header.log_file_offset() + header.event_length() as u64);
+        Some(position)
+    }
+
+    fn advance(&mut self) {
+        let oldest_pending = self.routes.iter()
+            .filter_map(|route| route.pending.first().map(|(sequence, _)| *sequence))
+            .min();
+        while let Some((sequence, _)) = self.uncommitted.front() {
+            if oldest_pending.is_some_and(|oldest| *sequence >= oldest) {
+                break;
+            }
+            self.committed = self.uncommitted.pop_front().map(|(_, position)| position);
+        }
+    }
+}
+
+impl EventSink for RouteSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.sequence += 1;
+        if let Some(position) = self.commit_point(entry) {
+            self.uncommitted.push_back((self.sequence, position));
+        }
+        if entry.entry_type() == EntryType::RowData {
+            let header = entry.header();
+            let name = format!("{}.{}", header.schema_name(), header.table_name());
+            match self.routes.iter_mut().find(|route| route.filter.matches(&name)) {
+                Some(route) => {
+                    route.pending.push((self.sequence, entry.clone()));
+                    if route.pending.len() >= route.batch_size {
+                        route.ack()?;
+                    }
+                }
+                None => self.unrouted += 1,
+            }
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let mut result = Ok(());
+        // 某个route失败时其它route仍然确认, 位点停在失败的route上
+        for route in self.routes.iter_mut() {
+            if let Err(e) = route.ack() {
+                result = result.and(Err(e));
+            }
+        }
+        self.advance();
+        result
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.routes.iter().any(|route| route.sink.is_backpressured())
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.routes.iter_mut().try_for_each(|route| route.sink.reconfigure(config))
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::EventSink;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleMode {
+    // 每n个rows entry取一个
+    Every(u64),
+    // 按位点的hash以rate的概率抽样, 同一个位点重新dump后的抽样结果不变
+    Rate(f64),
+}
+
+impl SampleMode {
+    // sample_every=100 或者 sample_rate=0.01, 都没有配置时返回None
+    pub fn from_config(config: &SinkConfig) -> Result<Option<SampleMode>, String> {
+        match (config.get("sample_every"), config.get("sample_rate")) {
+            (Some(_), Some(_)) => Err("sample_every and sample_rate can not be used together".to_string()),
+            (Some(_), None) => {
+                let every: u64 = parse_or(config, "sample_every", 1)?;
+                if every == 0 {
+                    return Err("sample_every must be greater than 0".to_string());
+                }
+                Ok(Some(SampleMode::Every(every)))
+            }
+            (None, Some(_)) => {
+                let rate: f64 = parse_or(config, "sample_rate", 1.0)?;
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(format!("sample_rate must be in [0, 1], got {}", rate));
+                }
+                Ok(Some(SampleMode::Rate(rate)))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    // seen为包括entry在内已经看到的rows entry数, 从1开始
+    pub fn sample(&self, seen: u64, entry: &Entry) -> bool {
+        match *self {
+            SampleMode::Every(every) => seen.saturating_sub(1).is_multiple_of(every),
+            SampleMode::Rate(rate) => {
+                let header = entry.header();
+                let mut hasher = DefaultHasher::new();
+                header.log_file_name().hash(&mut hasher);
+                header.log_file_offset().hash(&mut hasher);
+                (hasher.finish() as f64 / u64::MAX as f64) < rate
+            }
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  全量投递的同时输出一个低流量的抽样流, 用于dashboard和异常检测:
+ *  entry先交给inner(全量), 成功之后rows entry(不包括DDL)按SampleMode抽样交给sampled.
+ *  抽样流只用于观测, sampled返回Err时只计数并打印第一次的错误, 不影响全量投递
+ * </pre>
+ */
+pub struct SamplingSink {
+    inner: Box<dyn EventSink>,
+    sampled: Box<dyn EventSink>,
+    mode: SampleMode,
+    rows_seen: u64,
+    rows_sampled: u64,
+    errors: u64,
+}
+
+impl SamplingSink {
+    pub fn new(inner: Box<dyn EventSink>, sampled: Box<dyn EventSink>, mode: SampleMode) -> SamplingSink {
+        SamplingSink { inner, sampled, mode, rows_seen: 0, rows_sampled: 0, errors: 0 }
+    }
+
+    pub fn mode(&self) -> SampleMode {
+        self.mode
+    }
+    pub fn rows_seen(&self) -> u64 {
+        self.rows_seen
+    }
+    pub fn rows_sampled(&self) -> u64 {
+        self.rows_sampled
+    }
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    fn record_error(&mut self, e: String) {
+        self.errors += 1;
+        if self.errors == 1 {
+            println!("sampled sink failure, sampling continues: {}", e);
+        }
+    }
+}
+
+impl EventSink for SamplingSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        self.inner.on_event(entry)?;
+        let rows = entry.entry_type() == EntryType::RowData
+            && entry.row_change().is_some_and(|row_change| !row_change.is_ddl());
+        if !rows {
+            return Ok(());
+        }
+        self.rows_seen += 1;
+        if self.mode.sample(self.rows_seen, entry) {
+            self.rows_sampled += 1;
+            if let Err(e) = self.sampled.on_event(entry) {
+                self.record_error(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()?;
+        if let Err(e) = self.sampled.flush() {
+            self.record_error(e);
+        }
+        Ok(())
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured() || self.sampled.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{FixedOffset, TimeZone};
+
+use crate::protocol::Entry;
+use crate::sink::registry::SinkConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentPeriod {
+    Hourly,
+    Daily,
+}
+
+impl SegmentPeriod {
+    pub fn from_name(name: &str) -> Result<SegmentPeriod, String> {
+        match name {
+            "hourly" => Ok(SegmentPeriod::Hourly),
+            "daily" => Ok(SegmentPeriod::Daily),
+            _ => Err(format!("unknown segment period {}, expect none/hourly/daily", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SegmentPeriod::Hourly => "hourly",
+            SegmentPeriod::Daily => "daily",
+        }
+    }
+
+    fn format(&self) -> &'static str {
+        match self {
+            SegmentPeriod::Hourly => "%Y%m%d%H",
+            SegmentPeriod::Daily => "%Y%m%d",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  按event的时间(header的execute_time, 即binlog中的时间)而不是本地时钟划分输出的段,
+ *  重放历史区间时entry仍然落在它原本的日期/小时中:
+ *      daily   20260101
+ *      hourly  2026010115
+ *  日期按固定的时区计算, 默认UTC, 不受本地时区和夏令时影响.
+ *  没有时间的entry(execute_time为0, 例如incident)返回None, 由sink写入当前的段.
+ *  配置: segment=none|hourly|daily, segment_utc_offset=+08:00
+ * </pre>
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSegmenter {
+    period: SegmentPeriod,
+    offset: FixedOffset,
+}
+
+impl TimeSegmenter {
+    pub fn new(period: SegmentPeriod, offset: FixedOffset) -> TimeSegmenter {
+        TimeSegmenter { period, offset }
+    }
+
+    // segment没有配置或者为none时返回None
+    pub fn from_config(config: &SinkConfig) -> Result<Option<TimeSegmenter>, String> {
+        let period = match config.get("segment").map(|name| name.as_str()) {
+            None | Some("none") => return Ok(None),
+            Some(name) => SegmentPeriod::from_name(name)?,
+        };
+        let offset = match config.get("segment_utc_offset") {
+            Some(value) => parse_utc_offset(value).map_err(|e| format!("segment_utc_offset: {}", e))?,
+            None => utc(),
+        };
+        Ok(Some(TimeSegmenter::new(period, offset)))
+    }
+
+    pub fn period(&self) -> SegmentPeriod {
+        self.period
+    }
+    pub fn offset(&self) -> FixedOffset {
+        self.offset
+    }
+
+    // timestamp为毫秒
+    pub fn segment_of(&self, timestamp: i64) -> Option<String> {
+        if timestamp <= 0 {
+            return None;
+        }
+        let time = self.offset.timestamp_millis_opt(timestamp).single()?;
+        Some(time.format(self.period.format()).to_string())
+    }
+
+    pub fn segment(&self, entry: &Entry) -> Option<String> {
+        self.segment_of(entry.header().execute_time())
+    }
+}
+
+pub fn utc() -> FixedOffset {
+    FixedOffset::east_opt(0).unwrap()
+}
+
+// +08:00, -05:30, +0800, Z
+pub fn parse_utc_offset(value: &str) -> Result<FixedOffset, String> {
+    let value = value.trim();
+    if value == "Z" || value == "0" {
+        return Ok(utc());
+    }
+    let invalid = || format!("invalid utc offset {}, expect for example +08:00", value);
+    let (sign, rest) = match value.as_bytes().first() {
+        Some(b'+') => (1, &value[1..]),
+        Some(b'-') => (-1, &value[1..]),
+        _ => return Err(invalid()),
+    };
+    let digits: String = rest.chars().filter(|c| *c != ':').collect();
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse().map_err(|_| invalid())?;
+    let minutes: i32 = digits[2..].parse().map_err(|_| invalid())?;
+    if minutes >= 60 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
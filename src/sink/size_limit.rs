@@ -0,0 +1,272 @@
+use crate::protocol::{Column, Entry, Header, RowData};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::EventSink;
+
+// 与kafka默认的max.message.bytes(1MB)保持一致
+pub const DEFAULT_MAX_ENTRY_SIZE: usize = 1024 * 1024;
+
+// 截断后的列值以该标记结尾
+pub const TRUNCATED_MARKER: &str = "...[truncated]";
+
+// 序列化时每个header/column/entry的固定开销(字段tag, 长度, 数值类型的字段), 按canal protobuf估算
+const HEADER_OVERHEAD: usize = 48;
+const COLUMN_OVERHEAD: usize = 16;
+const ENTRY_OVERHEAD: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizePolicy {
+    // 按行拆分为多个entry, 单行仍然超过限制时截断该行的大字段
+    Split,
+    // 截断blob/text/json等大字段, 保留前缀并加上TRUNCATED_MARKER
+    Truncate,
+    // 整个entry交给dead letter sink
+    DeadLetter,
+}
+
+impl OversizePolicy {
+    pub fn from_name(name: &str) -> Result<OversizePolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "split" => Ok(OversizePolicy::Split),
+            "truncate" => Ok(OversizePolicy::Truncate),
+            "dlq" | "dead_letter" => Ok(OversizePolicy::DeadLetter),
+            _ => Err(format!("unknown oversize policy {}, expect split/truncate/dlq", name)),
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  限制交给下游sink的entry大小, 避免下游对单条消息有大小限制(例如kafka的max.message.bytes)时
+ *  投递一直失败而卡住整个pipeline:
+ *  Split       rows event按行拆分为多个entry, 除part(从0开始的序号)外共享同一个header, 按原来的顺序投递.
+ *              各部分逐个交给下游, 后面的部分失败时前面的部分已经投递, 重新dump后整个entry再次拆分投递,
+ *              因此是at-least-once, 下游按(位点, part)去重
+ *  Truncate    从最长的大字段开始截断, 直到entry不超过限制
+ *  DeadLetter  交给dead letter sink, 继续处理后面的entry
+ *  Split/Truncate之后仍然超过限制(例如超长的DDL或者非大字段)时同样交给dead letter sink,
+ *  没有设置dead letter sink时返回Err
+ *  配置: max_entry_size=1048576, oversize_policy=split|truncate|dlq
+ * </pre>
+ */
+pub struct SizeLimitSink {
+    inner: Box<dyn EventSink>,
+    dead_letter: Option<Box<dyn EventSink>>,
+    max_entry_size: usize,
+    policy: OversizePolicy,
+    split: u64,
+    truncated: u64,
+    dead_lettered: u64,
+}
+
+impl SizeLimitSink {
+    pub fn new(inner: Box<dyn EventSink>, max_entry_size: usize, policy: OversizePolicy) -> SizeLimitSink {
+        SizeLimitSink { inner, dead_letter: None, max_entry_size, policy, split: 0, truncated: 0, dead_lettered: 0 }
+    }
+
+    pub fn from_config(inner: Box<dyn EventSink>, config: &SinkConfig) -> Result<SizeLimitSink, String> {
+        let (max_entry_size, policy) = limits(config)?;
+        Ok(SizeLimitSink::new(inner, max_entry_size, policy))
+    }
+
+    pub fn set_dead_letter(&mut self, dead_letter: Box<dyn EventSink>) {
+        self.dead_letter = Some(dead_letter);
+    }
+
+    pub fn max_entry_size(&self) -> usize {
+        self.max_entry_size
+    }
+    pub fn policy(&self) -> OversizePolicy {
+        self.policy
+    }
+    // 被拆分的entry数
+    pub fn split(&self) -> u64 {
+        self.split
+    }
+    // 截断过大字段的entry数
+    pub fn truncated(&self) -> u64 {
+        self.truncated
+    }
+    pub fn dead_lettered(&self) -> u64 {
+        self.dead_lettered
+    }
+
+    fn split_rows(&mut self, entry: &Entry) -> Result<(), String> {
+        let row_change = match entry.row_change() {
+            Some(row_change) if row_change.row_datas().len() > 1 => row_change,
+            _ => return self.truncate(entry.clone()),
+        };
+        let empty = with_rows(entry, vec![]);
+        let base = entry_size(&empty);
+        let mut chunks: Vec<Vec<RowData>> = vec![];
+        let mut size = base;
+        for row_data in row_change.row_datas() {
+            let row_size = row_size(row_data);
+            match chunks.last_mut() {
+                Some(chunk) if size + row_size <= self.max_entry_size => chunk.push(row_data.clone()),
+                _ => {
+                    chunks.push(vec![row_data.clone()]);
+                    size = base;
+                }
+            }
+            size += row_size;
+        }
+        self.split += 1;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let mut part = with_rows(&empty, chunk);
+            part.header_mut().set_part(i as u32);
+            if entry_size(&part) > self.max_entry_size {
+                self.truncate(part)?;
+            } else {
+                self.inner.on_event(&part)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn truncate(&mut self, mut entry: Entry) -> Result<(), String> {
+        let excess = entry_size(&entry).saturating_sub(self.max_entry_size);
+        if truncate_columns(&mut entry, excess) {
+            self.truncated += 1;
+        }
+        if entry_size(&entry) > self.max_entry_size {
+            return self.dead_letter(&entry);
+        }
+        self.inner.on_event(&entry)
+    }
+
+    fn dead_letter(&mut self, entry: &Entry) -> Result<(), String> {
+        let header = entry.header();
+        let size = entry_size(entry);
+        let dead_letter = self.dead_letter.as_mut()
+            .ok_or_else(|| format!("entry of {}.{} at {}:{} is {} bytes, exceeds max entry size {} and no dead letter sink",
+                                   header.schema_name(), header.table_name(), header.log_file_name(),
+                                   header.log_file_offset(), size, self.max_entry_size))?;
+        println!("entry of {}.{} at {}:{} is {} bytes, route to dead letter sink",
+                 header.schema_name(), header.table_name(), header.log_file_name(), header.log_file_offset(), size);
+        dead_letter.on_event(entry)?;
+        self.dead_lettered += 1;
+        Ok(())
+    }
+}
+
+impl EventSink for SizeLimitSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        if entry_size(entry) <= self.max_entry_size {
+            return self.inner.on_event(entry);
+        }
+        match self.policy {
+            OversizePolicy::Split => self.split_rows(entry),
+            OversizePolicy::Truncate => self.truncate(entry.clone()),
+            OversizePolicy::DeadLetter => self.dead_letter(entry),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if let Some(dead_letter) = self.dead_letter.as_mut() {
+            dead_letter.flush()?;
+        }
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    // max_entry_size和oversize_policy可以热加载
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        let (max_entry_size, policy) = limits(config)?;
+        self.inner.reconfigure(config)?;
+        self.max_entry_size = max_entry_size;
+        self.policy = policy;
+        Ok(())
+    }
+}
+
+fn limits(config: &SinkConfig) -> Result<(usize, OversizePolicy), String> {
+    let max_entry_size = parse_or(config, "max_entry_size", DEFAULT_MAX_ENTRY_SIZE)?;
+    let policy = match config.get("oversize_policy") {
+        Some(name) => OversizePolicy::from_name(name)?,
+        None => OversizePolicy::Split,
+    };
+    Ok((max_entry_size, policy))
+}
+
+// entry序列化后的估算大小
+pub fn entry_size(entry: &Entry) -> usize {
+    let rows = entry.row_change()
+        .map(|row_change| row_change.sql().len() + row_change.row_datas().iter().map(row_size).sum::<usize>())
+        .unwrap_or(0);
+    ENTRY_OVERHEAD + header_size(entry.header()) + rows
+}
+
+fn header_size(header: &Header) -> usize {
+    HEADER_OVERHEAD + header.log_file_name().len() + header.schema_name().len() + header.table_name().len() + header.gtid().len()
+}
+
+fn row_size(row_data: &RowData) -> usize {
+    row_data.before_columns().iter().chain(row_data.after_columns().iter()).map(column_size).sum()
+}
+
+fn column_size(column: &Column) -> usize {
+    COLUMN_OVERHEAD + column.name().len() + column.value_len() + column.mysql_type().len()
+}
+
+fn with_rows(entry: &Entry, row_datas: Vec<RowData>) -> Entry {
+    let mut part = entry.clone();
+    if let Some(mut row_change) = entry.row_change().cloned() {
+        row_change.set_row_datas(row_datas);
+        part.set_row_change(row_change);
+    }
+    part
+}
+
+// binlog中text与blob的类型相同, 都输出为blob
+fn is_large_column(column: &Column) -> bool {
+    ["blob", "text", "json", "geometry"].iter().any(|kind| column.mysql_type().contains(kind))
+}
+
+// 从最长的大字段开始截断, 直到减少excess字节, 有列被截断时返回true
+fn truncate_columns(entry: &mut Entry, mut excess: usize) -> bool {
+    let mut row_change = match entry.row_change() {
+        Some(row_change) if excess > 0 => row_change.clone(),
+        _ => return false,
+    };
+    let mut row_datas: Vec<(Vec<Column>, Vec<Column>)> = row_change.row_datas().iter()
+        .map(|row_data| (row_data.before_columns().clone(), row_data.after_columns().clone()))
+        .collect();
+    // (row, after, column, value length)
+    let mut candidates = vec![];
+    for (i, (before, after)) in row_datas.iter().enumerate() {
+        for (is_after, columns) in [(false, before), (true, after)] {
+            for (j, column) in columns.iter().enumerate() {
+                if is_large_column(column) && column.value_len() > TRUNCATED_MARKER.len() {
+                    candidates.push((i, is_after, j, column.value_len()));
+                }
+            }
+        }
+    }
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.3));
+
+    let mut truncated = false;
+    for (i, is_after, j, _) in candidates {
+        if excess == 0 {
+            break;
+        }
+        let column = if is_after { &mut row_datas[i].1[j] } else { &mut row_datas[i].0[j] };
+        // lazy列的原始字节数与解码后的长度可能不同, 按解码后的值截断
+        let len = column.value().len();
+        let mut keep = len.saturating_sub(excess + TRUNCATED_MARKER.len());
+        while !column.value().is_char_boundary(keep) {
+            keep -= 1;
+        }
+        let value = format!("{}{}", &column.value()[..keep], TRUNCATED_MARKER);
+        excess = excess.saturating_sub(len - value.len());
+        column.set_value(&value);
+        truncated = true;
+    }
+    if truncated {
+        row_change.set_row_datas(row_datas.into_iter().map(|(before, after)| RowData::new(before, after)).collect());
+        entry.set_row_change(row_change);
+    }
+    truncated
+}
@@ -0,0 +1,209 @@
+use crate::protocol::{Entry, EntryType};
+use crate::sink::registry::{parse_or, SinkConfig};
+use crate::sink::size_limit::entry_size;
+use crate::sink::EventSink;
+
+// 单个batch的上限, 超过时事务拆分为多个chunk投递
+pub const DEFAULT_MAX_BATCH_ENTRIES: usize = 10000;
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 64 * 1024 * 1024;
+
+/**
+ * <pre>
+ *  一个源端事务(从TransactionBegin到TransactionEnd)或者其中的一段:
+ *  事务没有超过上限时只有一个chunk, is_first和is_last都为true;
+ *  超过上限时按顺序拆分为多个chunk(spill), sink在第一个chunk开启事务, 在最后一个chunk提交.
+ *  不在事务中的entry(例如DDL)作为单独的一个完整batch
+ * </pre>
+ */
+#[derive(Debug, Clone, Default)]
+pub struct TransactionBatch {
+    entries: Vec<Entry>,
+    chunk: u32,
+    last: bool,
+    bytes: usize,
+}
+
+impl TransactionBatch {
+    pub fn entries(&self) -> &Vec<Entry> {
+        &self.entries
+    }
+    // 事务中的第几个chunk, 从0开始
+    pub fn chunk(&self) -> u32 {
+        self.chunk
+    }
+    pub fn is_first(&self) -> bool {
+        self.chunk == 0
+    }
+    pub fn is_last(&self) -> bool {
+        self.last
+    }
+    // 整个事务在一个batch中
+    pub fn is_complete(&self) -> bool {
+        self.is_first() && self.is_last()
+    }
+    // entry序列化后的估算大小之和
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/**
+ * <pre>
+ *  以源端事务为单位接收entry的sink, 例如在目标库中以同样的原子性提交的apply sink.
+ *  通过TransactionBatchingSink接入EventSink的pipeline
+ * </pre>
+ */
+pub trait TransactionSink: Send {
+    fn on_transaction(&mut self, batch: &TransactionBatch) -> Result<(), String>;
+
+    // 已经投递了部分chunk的事务不会再有后续的chunk(例如重连之后从事务开头重新投递), sink需要回滚
+    fn on_rollback(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/**
+ * <pre>
+ *  把逐条的entry按源端事务组装为TransactionBatch交给TransactionSink, 每个sink不需要自己重新组装事务:
+ *  缓存TransactionBegin之后的entry, 收到TransactionEnd时作为一个batch投递.
+ *  缓存的entry数超过max_batch_entries或者大小超过max_batch_bytes时先投递已缓存的部分(spill),
+ *  内存中最多保留一个chunk. 事务中途收到新的TransactionBegin时(重连后从事务开头重新dump)丢弃缓存,
+ *  已经投递过chunk时调用on_rollback. heartbeat不投递.
+ *  配置: max_batch_entries=10000, max_batch_bytes=67108864
+ * </pre>
+ */
+pub struct TransactionBatchingSink {
+    inner: Box<dyn TransactionSink>,
+    max_entries: usize,
+    max_bytes: usize,
+    batch: TransactionBatch,
+    in_transaction: bool,
+    transactions: u64,
+    // 被拆分为多个chunk的事务数
+    spilled: u64,
+    rollbacks: u64,
+}
+
+impl TransactionBatchingSink {
+    pub fn new(inner: Box<dyn TransactionSink>) -> TransactionBatchingSink {
+        TransactionBatchingSink {
+            inner,
+            max_entries: DEFAULT_MAX_BATCH_ENTRIES,
+            max_bytes: DEFAULT_MAX_BATCH_BYTES,
+            batch: TransactionBatch::default(),
+            in_transaction: false,
+            transactions: 0,
+            spilled: 0,
+            rollbacks: 0,
+        }
+    }
+
+    pub fn from_config(inner: Box<dyn TransactionSink>, config: &SinkConfig) -> Result<TransactionBatchingSink, String> {
+        let mut sink = TransactionBatchingSink::new(inner);
+        sink.set_max_entries(parse_or(config, "max_batch_entries", DEFAULT_MAX_BATCH_ENTRIES)?);
+        sink.set_max_bytes(parse_or(config, "max_batch_bytes", DEFAULT_MAX_BATCH_BYTES)?);
+        Ok(sink)
+    }
+
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries.max(1);
+    }
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes.max(1);
+    }
+
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+    pub fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+    // 投递完成的事务数(包括不在事务中的单个entry)
+    pub fn transactions(&self) -> u64 {
+        self.transactions
+    }
+    pub fn spilled(&self) -> u64 {
+        self.spilled
+    }
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    fn push(&mut self, entry: &Entry) {
+        self.batch.bytes += entry_size(entry);
+        self.batch.entries.push(entry.clone());
+    }
+
+    fn deliver(&mut self, last: bool) -> Result<(), String> {
+        self.batch.last = last;
+        self.inner.on_transaction(&self.batch)?;
+        if last {
+            self.transactions += 1;
+            self.batch = TransactionBatch::default();
+            self.in_transaction = false;
+        } else {
+            if self.batch.chunk == 0 {
+                self.spilled += 1;
+            }
+            self.batch.chunk += 1;
+            self.batch.entries.clear();
+            self.batch.bytes = 0;
+        }
+        Ok(())
+    }
+
+    // 丢弃没有结束的事务
+    fn abandon(&mut self) -> Result<(), String> {
+        let delivered = self.batch.chunk > 0;
+        self.batch = TransactionBatch::default();
+        self.in_transaction = false;
+        if delivered {
+            self.rollbacks += 1;
+            self.inner.on_rollback()?;
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for TransactionBatchingSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        match entry.entry_type() {
+            EntryType::Heartbeat => return Ok(()),
+            EntryType::TransactionBegin => {
+                if self.in_transaction {
+                    self.abandon()?;
+                }
+                self.in_transaction = true;
+                self.push(entry);
+            }
+            EntryType::TransactionEnd if self.in_transaction => {
+                self.push(entry);
+                return self.deliver(true);
+            }
+            _ if self.in_transaction => self.push(entry),
+            _ => {
+                self.push(entry);
+                return self.deliver(true);
+            }
+        }
+        if self.batch.entries.len() >= self.max_entries || self.batch.bytes >= self.max_bytes {
+            self.deliver(false)?;
+        }
+        Ok(())
+    }
+
+    // 没有结束的事务继续缓存, 只刷新下游
+    fn flush(&mut self) -> Result<(), String> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::instance::EntryPosition;
+use crate::protocol::{Entry, EntryType, Header};
+use crate::sink::registry::{duration_or, parse_or, SinkConfig};
+use crate::sink::size_limit::entry_size;
+use crate::sink::EventSink;
+
+// 超过限制时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransactionLimitPolicy {
+    // 只报告, entry照常投递
+    #[default]
+    Warn,
+    // 在超过限制的entry之前插入TransactionEnd, Info(说明拆分), TransactionBegin, 事务被拆分为多个部分
+    Split,
+    // 报告之后返回Err并通过abort句柄停止instance, 不再重试
+    Abort,
+}
+
+impl TransactionLimitPolicy {
+    pub fn from_name(name: &str) -> Result<TransactionLimitPolicy, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "warn" => Ok(TransactionLimitPolicy::Warn),
+            "split" => Ok(TransactionLimitPolicy::Split),
+            "abort" => Ok(TransactionLimitPolicy::Abort),
+            _ => Err(format!("unknown transaction limit policy {}, expect warn/split/abort", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionLimitPolicy::Warn => "warn",
+            TransactionLimitPolicy::Split => "split",
+            TransactionLimitPolicy::Abort => "abort",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  超过限制的事务的报告:
+ *      begin/end       事务的位点范围, end为报告时最后一个entry的位点
+ *      limit           超过的限制, 例如 max entries 10001 > 10000
+ *      tables          事务中每张表的行数
+ *  entries/bytes/duration为报告时整个事务的统计, duration按照entry的执行时间计算, 与sink处理的快慢无关
+ * </pre>
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionReport {
+    begin: EntryPosition,
+    end: EntryPosition,
+    gtid: String,
+    entries: usize,
+    bytes: usize,
+    duration: Duration,
+    tables: BTreeMap<String, usize>,
+    limit: String,
+}
+
+impl TransactionReport {
+    pub fn begin(&self) -> &EntryPosition {
+        &self.begin
+    }
+    pub fn end(&self) -> &EntryPosition {
+        &self.end
+    }
+    pub fn gtid(&self) -> &str {
+        &self.gtid
+    }
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+    pub fn tables(&self) -> &BTreeMap<String, usize> {
+        &self.tables
+    }
+    pub fn limit(&self) -> &str {
+        &self.limit
+    }
+
+    // 例如 transaction at mysql-bin.000001:4-mysql-bin.000001:1024 exceeds max entries 10001 > 10000
+    // (10001 entries, 2097152 bytes, 3s), tables: shop.orders=9000, shop.items=1000
+    pub fn message(&self) -> String {
+        let tables = self.tables.iter().map(|(table, rows)| format!("{}={}", table, rows)).collect::<Vec<_>>().join(", ");
+        let gtid = if self.gtid.is_empty() { String::new() } else { format!(" (gtid {})", self.gtid) };
+        format!("transaction at {}:{}-{}:{}{} exceeds {} ({} entries, {} bytes, {:?}), tables: {}",
+                self.begin.journal_name(), self.begin.position(), self.end.journal_name(), self.end.position(), gtid,
+                self.limit, self.entries, self.bytes, self.duration, if tables.is_empty() { "<none>" } else { &tables })
+    }
+}
+
+// 当前事务的统计, chunk_*为拆分之后当前部分的统计
+#[derive(Debug, Clone, Default)]
+struct Transaction {
+    report: TransactionReport,
+    begin_time: i64,
+    chunk_entries: usize,
+    chunk_bytes: usize,
+    chunk_begin_time: i64,
+    parts: u32,
+    reported: bool,
+}
+
+/**
+ * <pre>
+ *  限制单个源端事务的entry数, 大小和持续时间, 保护有严格消息大小限制的下游(例如把整个事务作为一条消息的MQ):
+ *      max_entries     事务中的entry数, 包括TransactionBegin/TransactionEnd以及Split时插入的TransactionBegin
+ *      max_bytes       entry序列化后的估算大小之和, 见size_limit::entry_size
+ *      max_duration    事务中最后一个entry与TransactionBegin的执行时间之差
+ *  超过任意一个限制时按policy处理, 并打印TransactionReport, 设置了report sink时同时投递Incident entry.
+ *  Warn/Abort每个事务只报告一次; Split时限制作用于拆分后的每一部分, 每次拆分都会报告, 无法拆分时按Warn处理.
+ *  entry逐条投递给下游, Abort之前事务已经投递的部分不会撤回. 不在事务中的entry不受限制.
+ *  配置: max_transaction_entries=100000, max_transaction_bytes=67108864, max_transaction_duration=10m,
+ *       transaction_limit_policy=warn|split|abort, 没有配置的限制不检查
+ * </pre>
+ */
+pub struct TransactionLimitSink {
+    inner: Box<dyn EventSink>,
+    report_sink: Option<Box<dyn EventSink>>,
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>,
+    max_duration: Option<Duration>,
+    policy: TransactionLimitPolicy,
+    // 通常为MysqlEventParser::abort_handle, Abort时写入原因
+    abort: Option<Arc<Mutex<Option<String>>>>,
+    transaction: Option<Transaction>,
+    exceeded: u64,
+    split: u64,
+    last_report: Option<TransactionReport>,
+}
+
+impl TransactionLimitSink {
+    pub fn new(inner: Box<dyn EventSink>, policy: TransactionLimitPolicy) -> TransactionLimitSink {
+        TransactionLimitSink {
+            inner,
+            report_sink: None,
+            max_entries: None,
+            max_bytes: None,
+            max_duration: None,
+            policy,
+            abort: None,
+            transaction: None,
+            exceeded: 0,
+            split: 0,
+            last_report: None,
+        }
+    }
+
+    pub fn from_config(inner: Box<dyn EventSink>, config: &SinkConfig) -> Result<TransactionLimitSink, String> {
+        let policy = match config.get("transaction_limit_policy") {
+            Some(name) => TransactionLimitPolicy::from_name(name)?,
+            None => TransactionLimitPolicy::Warn,
+        };
+        let mut sink = TransactionLimitSink::new(inner, policy);
+        sink.set_max_entries(Some(parse_or(config, "max_transaction_entries", 0)?));
+        sink.set_max_bytes(Some(parse_or(config, "max_transaction_bytes", 0)?));
+        sink.set_max_duration(Some(duration_or(config, "max_transaction_duration", Duration::ZERO)?));
+        Ok(sink)
+    }
+
+    // 0表示不限制
+    pub fn set_max_entries(&mut self, max_entries: Option<usize>) {
+        self.max_entries = max_entries.filter(|max_entries| *max_entries > 0);
+    }
+    pub fn set_max_bytes(&mut self, max_bytes: Option<usize>) {
+        self.max_bytes = max_bytes.filter(|max_bytes| *max_bytes > 0);
+    }
+    pub fn set_max_duration(&mut self, max_duration: Option<Duration>) {
+        self.max_duration = max_duration.filter(|max_duration| !max_duration.is_zero());
+    }
+
+    pub fn set_report_sink(&mut self, report_sink: Box<dyn EventSink>) {
+        self.report_sink = Some(report_sink);
+    }
+
+    pub fn set_abort_handle(&mut self, abort: Arc<Mutex<Option<String>>>) {
+        self.abort = Some(abort);
+    }
+
+    pub fn max_entries(&self) -> Option<usize> {
+        self.max_entries
+    }
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration
+    }
+    pub fn policy(&self) -> TransactionLimitPolicy {
+        self.policy
+    }
+    // 超过限制的次数, Split时每次拆分计一次
+    pub fn exceeded(&self) -> u64 {
+        self.exceeded
+    }
+    // 插入的拆分标记数
+    pub fn split(&self) -> u64 {
+        self.split
+    }
+    pub fn last_report(&self) -> Option<&TransactionReport> {
+        self.last_report.as_ref()
+    }
+
+    // 加入entry之后当前部分超过的限制
+    fn exceeded_limit(&self, transaction: &Transaction, entry: &Entry, size: usize) -> Option<String> {
+        let entries = transaction.chunk_entries + 1;
+        let bytes = transaction.chunk_bytes + size;
+        let duration = Duration::from_millis((entry.header().execute_time() - transaction.chunk_begin_time).max(0) as u64);
+        if let Some(max_entries) = self.max_entries.filter(|max_entries| entries > *max_entries) {
+            return Some(format!("max entries {} > {}", entries, max_entries));
+        }
+        if let Some(max_bytes) = self.max_bytes.filter(|max_bytes| bytes > *max_bytes) {
+            return Some(format!("max bytes {} > {}", bytes, max_bytes));
+        }
+        if let Some(max_duration) = self.max_duration.filter(|max_duration| duration > *max_duration) {
+            return Some(format!("max duration {:?} > {:?}", duration, max_duration));
+        }
+        None
+    }
+
+    fn report(&mut self, report: TransactionReport) -> Result<String, String> {
+        let message = format!("{}, policy {}", report.message(), self.policy.name());
+        println!("{}", message);
+        self.exceeded += 1;
+        if let Some(report_sink) = self.report_sink.as_mut() {
+            let mut header = Header::new(report.begin.journal_name(), report.begin.position());
+            header.set_execute_time(report.end.timestamp());
+            report_sink.on_event(&Entry::incident(header, &message))?;
+            report_sink.flush()?;
+        }
+        self.last_report = Some(report);
+        Ok(message)
+    }
+
+    // 在entry之前结束当前部分并开始新的部分, 标记entry与触发拆分的entry位点相同
+    fn split_before(&mut self, entry: &Entry, part: u32, message: &str) -> Result<(), String> {
+        let mut header = entry.header().clone();
+        header.set_event_length(0);
+        self.inner.on_event(&Entry::new(header.clone(), EntryType::TransactionEnd))?;
+        self.inner.on_event(&Entry::info(header.clone(), &format!("split transaction, part {} starts: {}", part, message)))?;
+        self.inner.on_event(&Entry::new(header, EntryType::TransactionBegin))?;
+        self.split += 1;
+        Ok(())
+    }
+
+    fn track(&mut self, entry: &Entry) -> Result<(), String> {
+        let mut transaction = match self.transaction.take() {
+            Some(transaction) => transaction,
+            None => return Ok(()),
+        };
+        let size = entry_size(entry);
+        let header = entry.header();
+        // Split时TransactionEnd总是留在当前部分
+        let exceeded = match entry.entry_type() {
+            EntryType::TransactionEnd if self.policy == TransactionLimitPolicy::Split => None,
+            _ => self.exceeded_limit(&transaction, entry, size),
+        };
+        {
+            let report = &mut transaction.report;
+            report.entries += 1;
+            report.bytes += size;
+            report.duration = Duration::from_millis((header.execute_time() - transaction.begin_time).max(0) as u64);
+            report.end = header.position();
+            if report.gtid.is_empty() {
+                report.gtid = header.gtid().to_string();
+            }
+            if let Some(row_change) = entry.row_change().filter(|row_change| !row_change.is_ddl()) {
+                *report.tables.entry(format!("{}.{}", header.schema_name(), header.table_name())).or_insert(0) +=
+                    row_change.row_datas().len();
+            }
+        }
+        transaction.chunk_entries += 1;
+        transaction.chunk_bytes += size;
+        // 当前部分只有TransactionBegin时拆分没有意义, 按Warn报告
+        let can_split = self.policy == TransactionLimitPolicy::Split && transaction.chunk_entries > 2;
+        let result = match exceeded {
+            Some(limit) if can_split => {
+                let mut report = transaction.report.clone();
+                report.limit = limit;
+                let message = self.report(report)?;
+                transaction.parts += 1;
+                // 新的部分从插入的TransactionBegin开始
+                transaction.chunk_entries = 2;
+                transaction.chunk_bytes = size;
+                transaction.chunk_begin_time = header.execute_time();
+                self.split_before(entry, transaction.parts + 1, &message)
+            }
+            Some(limit) if !transaction.reported => {
+                transaction.reported = true;
+                let mut report = transaction.report.clone();
+                report.limit = limit;
+                let message = self.report(report)?;
+                if self.policy == TransactionLimitPolicy::Abort {
+                    if let Some(abort) = self.abort.as_ref() {
+                        if let Ok(mut abort) = abort.lock() {
+                            *abort = Some(message.clone());
+                        }
+                    }
+                    Err(message)
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        };
+        if entry.entry_type() != EntryType::TransactionEnd {
+            self.transaction = Some(transaction);
+        }
+        result
+    }
+}
+
+impl EventSink for TransactionLimitSink {
+    fn on_event(&mut self, entry: &Entry) -> Result<(), String> {
+        match entry.entry_type() {
+            EntryType::Heartbeat => return self.inner.on_event(entry),
+            // 重连之后从事务开头重新投递时, 没有结束的事务重新开始统计
+            EntryType::TransactionBegin => {
+                let header = entry.header();
+                let report = TransactionReport {
+                    begin: header.position(),
+                    ..TransactionReport::default()
+                };
+                self.transaction = Some(Transaction {
+                    report,
+                    begin_time: header.execute_time(),
+                    chunk_begin_time: header.execute_time(),
+                    ..Transaction::default()
+                });
+            }
+            _ => {}
+        }
+        self.track(entry)?;
+        self.inner.on_event(entry)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if let Some(report_sink) = self.report_sink.as_mut() {
+            report_sink.flush()?;
+        }
+        self.inner.flush()
+    }
+
+    fn is_backpressured(&self) -> bool {
+        self.inner.is_backpressured()
+    }
+
+    fn reconfigure(&mut self, config: &SinkConfig) -> Result<(), String> {
+        self.inner.reconfigure(config)
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::ConfigChange;
+use crate::sink::mq::{AvroSerializer, EntrySerializer, FlatMessageSerializer, ProtobufSerializer};
+use crate::store::quota::{Quota, QuotaLimiter, QUOTA_KEY_PREFIX};
+use crate::store::EntryStore;
+
+// 每种格式最多缓存的批次数
+pub const DEFAULT_PAYLOAD_CACHE_BATCHES: usize = 256;
+
+// (格式, 起始序号, 结束序号, filter)
+type CacheKey = (PayloadFormat, u64, u64, String);
+
+// 消费方订阅时选择的payload格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PayloadFormat {
+    // mini-canal-types的protobuf Entry, 见ProtobufSerializer
+    Entry,
+    // FlatMessage json
+    Flat,
+    // Avro object container file, 见AvroSerializer
+    Avro,
+}
+
+impl PayloadFormat {
+    pub fn from_name(name: &str) -> Result<PayloadFormat, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "entry" | "protobuf" => Ok(PayloadFormat::Entry),
+            "flat" | "json" => Ok(PayloadFormat::Flat),
+            "avro" => Ok(PayloadFormat::Avro),
+            _ => Err(format!("unknown payload format {}, expect entry/flat/avro", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PayloadFormat::Entry => "entry",
+            PayloadFormat::Flat => "flat",
+            PayloadFormat::Avro => "avro",
+        }
+    }
+}
+
+// get返回的一批已序列化的entry
+#[derive(Debug, Clone)]
+pub struct Payload {
+    // 批次的起始序号
+    sequence: u64,
+    // 批次的结束序号, 确认时ack这个序号. 有filter时批次中可能有被跳过的entry, 不等于sequence + count
+    next_sequence: u64,
+    count: usize,
+    format: PayloadFormat,
+    content_type: String,
+    body: Arc<Vec<u8>>,
+}
+
+impl Payload {
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    pub fn format(&self) -> PayloadFormat {
+        self.format
+    }
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+    pub fn body(&self) -> &Arc<Vec<u8>> {
+        &self.body
+    }
+}
+
+// get的结果, 对应http的状态码
+#[derive(Debug, Clone)]
+pub enum Fetch {
+    // 200
+    Batch(Payload),
+    // 204, 没有新的entry
+    Empty,
+    // 429, 超过了destination的配额, 等待retry_after之后重试
+    Throttled(Duration),
+}
+
+impl Fetch {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            Fetch::Batch(_) => 200,
+            Fetch::Empty => 204,
+            Fetch::Throttled(_) => 429,
+        }
+    }
+
+    pub fn payload(&self) -> Option<&Payload> {
+        match self {
+            Fetch::Batch(payload) => Some(payload),
+            _ => None,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Fetch::Throttled(retry_after) => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  server端按消费方协商的格式投递EntryStore中的entry:
+ *      subscribe(name, format)     订阅时选择格式, 格式没有注册serializer时返回错误
+ *      subscribe_with_filter       带filter订阅, 已订阅时替换格式和filter, 保留游标(见EntryStore)
+ *      get                         返回序列化之后的批次, 超过destination的配额(QuotaLimiter)时返回Throttled
+ *      ack/rollback                与EntryStore一致
+ *  序列化结果按(格式, 批次的序号区间, filter)缓存, 多个消费方使用相同格式/filter和batch size时只序列化一次,
+ *  store淘汰entry之后对应的缓存一起释放. 默认注册了entry/flat/avro, 可以通过register替换(例如调整flat的选项).
+ * </pre>
+ */
+pub struct EntryDelivery {
+    store: EntryStore,
+    serializers: BTreeMap<PayloadFormat, Box<dyn EntrySerializer>>,
+    formats: BTreeMap<String, PayloadFormat>,
+    cache: BTreeMap<CacheKey, Arc<Vec<u8>>>,
+    cache_batches: usize,
+    hits: u64,
+    misses: u64,
+    // destination -> 配额, 避免单个消费方占满instance的带宽
+    quotas: BTreeMap<String, QuotaLimiter>,
+}
+
+impl EntryDelivery {
+    pub fn new(store: EntryStore) -> EntryDelivery {
+        let mut serializers: BTreeMap<PayloadFormat, Box<dyn EntrySerializer>> = BTreeMap::new();
+        serializers.insert(PayloadFormat::Entry, Box::new(ProtobufSerializer::new()));
+        serializers.insert(PayloadFormat::Flat, Box::new(FlatMessageSerializer::new()));
+        serializers.insert(PayloadFormat::Avro, Box::new(AvroSerializer::new()));
+        EntryDelivery {
+            store,
+            serializers,
+            formats: BTreeMap::new(),
+            cache: BTreeMap::new(),
+            cache_batches: DEFAULT_PAYLOAD_CACHE_BATCHES,
+            hits: 0,
+            misses: 0,
+            quotas: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, format: PayloadFormat, serializer: Box<dyn EntrySerializer>) {
+        self.serializers.insert(format, serializer);
+        self.cache.retain(|(cached, _, _, _), _| *cached != format);
+    }
+
+    pub fn set_cache_batches(&mut self, cache_batches: usize) {
+        self.cache_batches = cache_batches;
+    }
+
+    pub fn store(&self) -> &EntryStore {
+        &self.store
+    }
+    pub fn store_mut(&mut self) -> &mut EntryStore {
+        &mut self.store
+    }
+    pub fn format(&self, name: &str) -> Option<PayloadFormat> {
+        self.formats.get(name).copied()
+    }
+    pub fn formats(&self) -> Vec<PayloadFormat> {
+        self.serializers.keys().copied().collect()
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+    pub fn cached(&self) -> usize {
+        self.cache.len()
+    }
+
+    // None表示取消配额, 重新设置时令牌桶重新开始
+    pub fn set_quota(&mut self, name: &str, quota: Option<Quota>) {
+        self.set_quota_at(name, quota, Instant::now());
+    }
+
+    // 令牌桶从now开始计时, 与get_at一起使用
+    pub fn set_quota_at(&mut self, name: &str, quota: Option<Quota>, now: Instant) {
+        match quota {
+            Some(quota) => self.quotas.insert(name.to_string(), QuotaLimiter::new_at(quota, now)),
+            None => self.quotas.remove(name),
+        };
+    }
+
+    pub fn quota(&self, name: &str) -> Option<&QuotaLimiter> {
+        self.quotas.get(name)
+    }
+
+    /**
+     * <pre>
+     *  热加载时按 destination.<name>.max_entries_per_sec 和 destination.<name>.max_bytes_per_sec
+     *  更新已订阅的destination的配额, 配置被删除时取消, 没有变化的配额保持当前的令牌.
+     *  所有配置都校验通过之后才会生效
+     * </pre>
+     */
+    pub fn reload_quotas(&mut self, change: &ConfigChange) -> Result<(), String> {
+        if !change.touches(QUOTA_KEY_PREFIX) {
+            return Ok(());
+        }
+        let mut quotas = vec![];
+        for name in self.formats.keys() {
+            quotas.push((name.clone(), Quota::from_properties(change.properties(), name)?));
+        }
+        for (name, quota) in quotas {
+            if self.quota(&name).map(|limiter| limiter.quota()) != quota {
+                self.set_quota(&name, quota);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn subscribe(&mut self, name: &str, format: PayloadFormat) -> Result<(), String> {
+        self.check_format(format)?;
+        self.store.subscribe(name)?;
+        self.formats.insert(name.to_string(), format);
+        Ok(())
+    }
+
+    pub fn subscribe_with_filter(&mut self, name: &str, format: PayloadFormat, filter: &str) -> Result<(), String> {
+        self.check_format(format)?;
+        self.store.subscribe_with_filter(name, filter)?;
+        self.formats.insert(name.to_string(), format);
+        Ok(())
+    }
+
+    fn check_format(&self, format: PayloadFormat) -> Result<(), String> {
+        if !self.serializers.contains_key(&format) {
+            let available = self.formats().iter().map(|format| format.name()).collect::<Vec<_>>();
+            return Err(format!("payload format {} is not available, expect {}", format.name(), available.join("/")));
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, name: &str) {
+        self.formats.remove(name);
+        self.store.unsubscribe(name);
+        self.trim();
+    }
+
+    pub fn get(&mut self, name: &str, batch_size: usize) -> Result<Fetch, String> {
+        self.get_at(name, batch_size, Instant::now())
+    }
+
+    // now用于检查配额, Throttled时不读取store, 游标保持不变
+    pub fn get_at(&mut self, name: &str, batch_size: usize, now: Instant) -> Result<Fetch, String> {
+        let format = self.format(name).ok_or_else(|| format!("destination {} is not subscribed", name))?;
+        let batch_size = match self.quotas.get_mut(name).map(|limiter| limiter.acquire_at(batch_size, now)) {
+            Some(Ok(batch_size)) => batch_size,
+            Some(Err(retry_after)) => return Ok(Fetch::Throttled(retry_after)),
+            None => batch_size,
+        };
+        let (sequence, next_sequence, entries) = self.store.get(name, batch_size)?;
+        if entries.is_empty() {
+            return Ok(Fetch::Empty);
+        }
+        let serializer = self.serializers.get(&format)
+            .ok_or_else(|| format!("payload format {} is not available", format.name()))?;
+        let content_type = serializer.content_type().to_string();
+        let filter = self.store.consumer(name).map(|cursor| cursor.filter().to_string()).unwrap_or_default();
+        let key = (format, sequence, next_sequence, filter);
+        let body = match self.cache.get(&key) {
+            Some(body) => {
+                self.hits += 1;
+                body.clone()
+            }
+            None => {
+                let body = Arc::new(serializer.serialize(&entries)?);
+                self.misses += 1;
+                if self.cache_batches > 0 {
+                    self.cache.insert(key, body.clone());
+                    self.trim();
+                }
+                body
+            }
+        };
+        if let Some(limiter) = self.quotas.get_mut(name) {
+            limiter.consume(entries.len(), body.len());
+        }
+        Ok(Fetch::Batch(Payload { sequence, next_sequence, count: entries.len(), format, content_type, body }))
+    }
+
+    pub fn ack(&mut self, name: &str, sequence: u64) -> Result<(), String> {
+        self.store.ack(name, sequence)?;
+        self.trim();
+        Ok(())
+    }
+
+    pub fn rollback(&mut self, name: &str) -> Result<(), String> {
+        self.store.rollback(name)
+    }
+
+    // 释放已被store淘汰的批次, 每种格式超过cache_batches时从最早的批次开始释放
+    fn trim(&mut self) {
+        let first_sequence = self.store.first_sequence();
+        self.cache.retain(|(_, sequence, _, _), _| *sequence >= first_sequence);
+        for format in self.formats() {
+            let cached: Vec<CacheKey> = self.cache.keys().filter(|key| key.0 == format).cloned().collect();
+            for key in cached.iter().take(cached.len().saturating_sub(self.cache_batches)) {
+                self.cache.remove(key);
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::sink::mq::flat_message::json_string;
+use crate::store::{ConsumerState, EntryStore};
+
+#[derive(Debug, Clone)]
+pub struct CursorSnapshot {
+    name: String,
+    state: ConsumerState,
+    acked: u64,
+    fetched: u64,
+    // store中还没有get的entry数
+    pending: u64,
+    unacked_batches: usize,
+    idle: Duration,
+    filter: String,
+}
+
+impl CursorSnapshot {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn state(&self) -> ConsumerState {
+        self.state
+    }
+    pub fn acked(&self) -> u64 {
+        self.acked
+    }
+    pub fn fetched(&self) -> u64 {
+        self.fetched
+    }
+    // 已get未ack的entry数
+    pub fn unacked(&self) -> u64 {
+        self.fetched.saturating_sub(self.acked)
+    }
+    pub fn pending(&self) -> u64 {
+        self.pending
+    }
+    pub fn unacked_batches(&self) -> usize {
+        self.unacked_batches
+    }
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+}
+
+// 最早的未确认entry, 通常就是卡住的消费方正在处理的位置
+#[derive(Debug, Clone)]
+pub struct UnackedEntry {
+    sequence: u64,
+    destination: String,
+    log_file_name: String,
+    log_file_offset: u64,
+    execute_time: i64,
+}
+
+impl UnackedEntry {
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+    pub fn log_file_name(&self) -> &str {
+        &self.log_file_name
+    }
+    pub fn log_file_offset(&self) -> u64 {
+        self.log_file_offset
+    }
+    pub fn execute_time(&self) -> i64 {
+        self.execute_time
+    }
+}
+
+/**
+ * <pre>
+ *  EntryStore的只读快照, 用于排查消费方卡住的问题(admin接口/日志输出):
+ *  inspect只读取状态, 不会刷新消费方的活跃时间, 也不会触发淘汰, 不影响正常的投递.
+ *  oldest unacked的age按entry的execute_time(binlog中的时间)计算
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct StoreSnapshot {
+    captured_at: i64,
+    capacity: usize,
+    size: usize,
+    first_sequence: u64,
+    next_sequence: u64,
+    evicted: u64,
+    oldest_unacked: Option<UnackedEntry>,
+    cursors: Vec<CursorSnapshot>,
+}
+
+impl StoreSnapshot {
+    pub fn captured_at(&self) -> i64 {
+        self.captured_at
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    pub fn first_sequence(&self) -> u64 {
+        self.first_sequence
+    }
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+    pub fn oldest_unacked(&self) -> Option<&UnackedEntry> {
+        self.oldest_unacked.as_ref()
+    }
+    pub fn cursors(&self) -> &Vec<CursorSnapshot> {
+        &self.cursors
+    }
+
+    pub fn unacked_batches(&self) -> usize {
+        self.cursors.iter().map(|cursor| cursor.unacked_batches).sum()
+    }
+
+    // 最早的未确认entry距离captured_at的时间, 毫秒
+    pub fn oldest_unacked_age(&self) -> Option<i64> {
+        self.oldest_unacked.as_ref().map(|entry| (self.captured_at - entry.execute_time).max(0))
+    }
+
+    /**
+     * <pre>
+     *  文本输出:
+     *  store: 10/16384 entries, sequence [0, 10), evicted 0, unacked batches 1
+     *  oldest unacked: sequence 3 of destination a at mysql-bin.000001:120, age 1500ms
+     *  destination a: active acked=3 fetched=5 unacked=2 batches=1 pending=5 idle=120ms filter=db\\..*
+     *  没有filter的destination不输出filter
+     * </pre>
+     */
+    pub fn render(&self) -> String {
+        let mut out = vec![format!("store: {}/{} entries, sequence [{}, {}), evicted {}, unacked batches {}",
+                                   self.size, self.capacity, self.first_sequence, self.next_sequence, self.evicted,
+                                   self.unacked_batches())];
+        match self.oldest_unacked.as_ref() {
+            Some(entry) => out.push(format!("oldest unacked: sequence {} of destination {} at {}:{}, age {}ms",
+                                            entry.sequence, entry.destination, entry.log_file_name,
+                                            entry.log_file_offset, self.oldest_unacked_age().unwrap_or(0))),
+            None => out.push("oldest unacked: none".to_string()),
+        }
+        for cursor in self.cursors.iter() {
+            let mut line = format!("destination {}: {} acked={} fetched={} unacked={} batches={} pending={} idle={}ms",
+                                   cursor.name, cursor.state.name(), cursor.acked, cursor.fetched, cursor.unacked(),
+                                   cursor.unacked_batches, cursor.pending, cursor.idle.as_millis());
+            if !cursor.filter.is_empty() {
+                line.push_str(&format!(" filter={}", cursor.filter));
+            }
+            out.push(line);
+        }
+        out.join("\n")
+    }
+
+    /**
+     * <pre>
+     *  admin接口(GET /store/<name>)的json:
+     *  {"captured_at":..,"capacity":..,"size":..,"first_sequence":..,"next_sequence":..,"evicted":..,
+     *   "unacked_batches":1,
+     *   "oldest_unacked":{"sequence":3,"destination":"a","journal_name":"mysql-bin.000001","position":120,
+     *                     "execute_time":..,"age_ms":1500},
+     *   "cursors":[{"name":"a","state":"active","acked":3,"fetched":5,"unacked":2,"unacked_batches":1,
+     *               "pending":5,"idle_ms":120,"filter":null}]}
+     *  没有未确认的entry时oldest_unacked为null, 没有filter时filter为null
+     * </pre>
+     */
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "{{\"captured_at\":{},\"capacity\":{},\"size\":{},\"first_sequence\":{},\"next_sequence\":{},\"evicted\":{},\"unacked_batches\":{}",
+                       self.captured_at, self.capacity, self.size, self.first_sequence, self.next_sequence,
+                       self.evicted, self.unacked_batches());
+        match self.oldest_unacked.as_ref() {
+            Some(entry) => {
+                let _ = write!(out, ",\"oldest_unacked\":{{\"sequence\":{},\"destination\":{},\"journal_name\":{},\"position\":{},\"execute_time\":{},\"age_ms\":{}}}",
+                               entry.sequence, json_string(&entry.destination), json_string(&entry.log_file_name),
+                               entry.log_file_offset, entry.execute_time, self.oldest_unacked_age().unwrap_or(0));
+            }
+            None => out.push_str(",\"oldest_unacked\":null"),
+        }
+        out.push_str(",\"cursors\":[");
+        for (i, cursor) in self.cursors.iter().enumerate() {
+            let filter = Some(cursor.filter.as_str()).filter(|filter| !filter.is_empty())
+                .map_or("null".to_string(), json_string);
+            let _ = write!(out, "{}{{\"name\":{},\"state\":{},\"acked\":{},\"fetched\":{},\"unacked\":{},\"unacked_batches\":{},\"pending\":{},\"idle_ms\":{},\"filter\":{}}}",
+                           if i > 0 { "," } else { "" }, json_string(&cursor.name), json_string(cursor.state.name()),
+                           cursor.acked, cursor.fetched, cursor.unacked(), cursor.unacked_batches, cursor.pending,
+                           cursor.idle.as_millis(), filter);
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+impl EntryStore {
+    pub fn inspect(&self) -> StoreSnapshot {
+        let next_sequence = self.next_sequence();
+        let cursors = self.consumers.values().map(|cursor| CursorSnapshot {
+            name: cursor.name.clone(),
+            state: cursor.state,
+            acked: cursor.acked,
+            fetched: cursor.fetched,
+            pending: next_sequence.saturating_sub(cursor.fetched),
+            unacked_batches: cursor.batches.len(),
+            idle: cursor.last_fetch.elapsed(),
+            filter: cursor.filter.pattern().to_string(),
+        }).collect();
+        // 已经被淘汰的序号没有对应的entry
+        let oldest_unacked = self.consumers.values()
+            .filter(|cursor| cursor.acked < next_sequence)
+            .min_by_key(|cursor| cursor.acked)
+            .and_then(|cursor| {
+                let sequence = cursor.acked.max(self.first_sequence);
+                let header = self.entries.get((sequence - self.first_sequence) as usize)?.header();
+                Some(UnackedEntry {
+                    sequence,
+                    destination: cursor.name.clone(),
+                    log_file_name: header.log_file_name().to_string(),
+                    log_file_offset: header.log_file_offset(),
+                    execute_time: header.execute_time(),
+                })
+            });
+        StoreSnapshot {
+            captured_at: Utc::now().timestamp_millis(),
+            capacity: self.capacity,
+            size: self.entries.len(),
+            first_sequence: self.first_sequence,
+            next_sequence,
+            evicted: self.evicted,
+            oldest_unacked,
+            cursors,
+        }
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use crate::filter::{EventFilter, RegexFilter};
+use crate::metrics::MemoryGauge;
+use crate::protocol::{Entry, Header};
+use crate::sink::size_limit::entry_size;
+use crate::sink::EventSink;
+
+pub mod delivery;
+
+pub mod inspect;
+
+pub mod quota;
+
+pub use delivery::{EntryDelivery, Fetch, Payload, PayloadFormat};
+pub use inspect::{CursorSnapshot, StoreSnapshot, UnackedEntry};
+pub use quota::{Quota, QuotaLimiter};
+
+pub const DEFAULT_STORE_CAPACITY: usize = 16 * 1024;
+
+// 消费方超过idle_ttl没有get时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdlePolicy {
+    // 不再阻止store淘汰其未确认的entry, 恢复get之后如果有entry被淘汰则返回错误
+    #[default]
+    Pause,
+    // 直接移除该消费方
+    Expire,
+}
+
+impl IdlePolicy {
+    pub fn from_name(name: &str) -> Result<IdlePolicy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "pause" => Ok(IdlePolicy::Pause),
+            "expire" => Ok(IdlePolicy::Expire),
+            _ => Err(format!("unknown idle policy {}, expect pause/expire", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdlePolicy::Pause => "pause",
+            IdlePolicy::Expire => "expire",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsumerState {
+    Active,
+    // 超过idle_ttl, 不再参与retention的计算
+    Paused,
+}
+
+impl ConsumerState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConsumerState::Active => "active",
+            ConsumerState::Paused => "paused",
+        }
+    }
+}
+
+// 一个消费方(destination)的游标, sequence为entry在store中的全局序号
+#[derive(Debug, Clone)]
+pub struct ConsumerCursor {
+    name: String,
+    // 下一个需要ack的序号, 之前的entry都已经确认
+    acked: u64,
+    // 下一个get返回的序号
+    fetched: u64,
+    last_fetch: Instant,
+    state: ConsumerState,
+    // 已get未ack的批次, [start, end)
+    batches: VecDeque<(u64, u64)>,
+    // 订阅的schema.table, 不匹配的entry在get时跳过
+    filter: RegexFilter,
+}
+
+impl ConsumerCursor {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn acked(&self) -> u64 {
+        self.acked
+    }
+    pub fn fetched(&self) -> u64 {
+        self.fetched
+    }
+    pub fn idle(&self) -> Duration {
+        self.last_fetch.elapsed()
+    }
+    pub fn state(&self) -> ConsumerState {
+        self.state
+    }
+    pub fn unacked_batches(&self) -> usize {
+        self.batches.len()
+    }
+    // 为空时订阅所有表
+    pub fn filter(&self) -> &str {
+        self.filter.pattern()
+    }
+}
+
+/**
+ * <pre>
+ *  对应canal中的CanalEventStore, 扩展为多个消费方共享一份entry:
+ *      put         parser写入, store满时返回Err, 由调用方等待后重试(背压)
+ *      get         按消费方的游标取出下一批entry, 同时刷新其最近活跃时间
+ *      ack         确认序号之前的entry
+ *      rollback    游标回退到最近一次ack的位置, 下一次get重新返回未确认的entry
+ *  消费方可以带filter(schema.table的正则, 见RegexFilter)订阅, get时跳过不匹配的entry,
+ *  批次的序号区间包括跳过的entry. 与canal的subscribe一致, 已订阅的消费方重新订阅时只替换filter,
+ *  游标保持不变: 新加入的表从游标当前的位置开始投递, 不会重放游标之前的历史.
+ *  store只能淘汰所有Active消费方都已确认的entry, 因此一个不再消费的destination会占满整个retention.
+ *  设置idle_ttl之后, put发现store已满时检查超过ttl没有get的消费方:
+ *      Pause       标记为Paused, 不再阻止淘汰, 重新get时如果未确认的entry已被淘汰则返回错误, 需要reset
+ *      Expire      移除该消费方
+ *  两种情况都会向notifier投递一个Incident entry
+ * </pre>
+ */
+pub struct EntryStore {
+    capacity: usize,
+    entries: VecDeque<Entry>,
+    // entries[0]的序号
+    first_sequence: u64,
+    consumers: BTreeMap<String, ConsumerCursor>,
+    idle_ttl: Option<Duration>,
+    idle_policy: IdlePolicy,
+    notifier: Option<Box<dyn EventSink>>,
+    evicted: u64,
+    // entries的估计字节数
+    bytes: usize,
+    memory_gauge: Option<MemoryGauge>,
+}
+
+impl Default for EntryStore {
+    fn default() -> Self {
+        EntryStore::new(DEFAULT_STORE_CAPACITY)
+    }
+}
+
+impl EntryStore {
+    pub fn new(capacity: usize) -> EntryStore {
+        EntryStore {
+            capacity: capacity.max(1),
+            entries: VecDeque::new(),
+            first_sequence: 0,
+            consumers: BTreeMap::new(),
+            idle_ttl: None,
+            idle_policy: IdlePolicy::Pause,
+            notifier: None,
+            evicted: 0,
+            bytes: 0,
+            memory_gauge: None,
+        }
+    }
+
+    pub fn set_idle_ttl(&mut self, idle_ttl: Option<Duration>) {
+        self.idle_ttl = idle_ttl;
+    }
+
+    pub fn set_idle_policy(&mut self, idle_policy: IdlePolicy) {
+        self.idle_policy = idle_policy;
+    }
+
+    // 接收消费方被暂停或者移除的通知
+    pub fn set_notifier(&mut self, notifier: Box<dyn EventSink>) {
+        self.notifier = Some(notifier);
+    }
+
+    // 同步entries的字节数, 例如 MemoryStats::gauge("store")
+    pub fn set_memory_gauge(&mut self, gauge: MemoryGauge) {
+        if let Some(previous) = self.memory_gauge.as_ref() {
+            previous.sub(self.bytes);
+        }
+        gauge.add(self.bytes);
+        self.memory_gauge = Some(gauge);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    // entries的估计字节数, 见size_limit::entry_size
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    // 下一个put的序号
+    pub fn next_sequence(&self) -> u64 {
+        self.first_sequence + self.entries.len() as u64
+    }
+    pub fn first_sequence(&self) -> u64 {
+        self.first_sequence
+    }
+    // 在Paused消费方确认之前被淘汰的entry数
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+    pub fn consumer(&self, name: &str) -> Option<&ConsumerCursor> {
+        self.consumers.get(name)
+    }
+    pub fn consumers(&self) -> Vec<&ConsumerCursor> {
+        self.consumers.values().collect()
+    }
+
+    // 新的消费方从下一个put的entry开始消费
+    pub fn subscribe(&mut self, name: &str) -> Result<(), String> {
+        if self.consumers.contains_key(name) {
+            return Err(format!("destination {} is already subscribed", name));
+        }
+        self.add_consumer(name, RegexFilter::new("")?);
+        Ok(())
+    }
+
+    /**
+     * <pre>
+     *  按filter订阅, 已订阅时替换filter并保留游标(包括已get未ack的批次):
+     *  之后的get按新的filter从fetched开始过滤, 新加入的表不会从历史的位置重放,
+     *  已经get的批次仍然是按旧的filter过滤的结果, rollback之后按新的filter重新get
+     * </pre>
+     */
+    pub fn subscribe_with_filter(&mut self, name: &str, filter: &str) -> Result<(), String> {
+        let filter = RegexFilter::new(filter)?;
+        match self.consumers.get_mut(name) {
+            Some(cursor) => {
+                if cursor.filter.pattern() != filter.pattern() {
+                    println!("destination {} changes filter from [{}] to [{}] at sequence {}", name,
+                             cursor.filter.pattern(), filter.pattern(), cursor.fetched);
+                    cursor.filter = filter;
+                }
+            }
+            None => self.add_consumer(name, filter),
+        }
+        Ok(())
+    }
+
+    fn add_consumer(&mut self, name: &str, filter: RegexFilter) {
+        let sequence = self.next_sequence();
+        self.consumers.insert(name.to_string(), ConsumerCursor {
+            name: name.to_string(),
+            acked: sequence,
+            fetched: sequence,
+            last_fetch: Instant::now(),
+            state: ConsumerState::Active,
+            batches: VecDeque::new(),
+            filter,
+        });
+    }
+
+    pub fn unsubscribe(&mut self, name: &str) -> Option<ConsumerCursor> {
+        let cursor = self.consumers.remove(name);
+        self.trim();
+        cursor
+    }
+
+    pub fn put(&mut self, entry: Entry) -> Result<u64, String> {
+        if self.entries.len() >= self.capacity {
+            self.trim();
+        }
+        if self.entries.len() >= self.capacity {
+            self.check_idle()?;
+        }
+        if self.entries.len() >= self.capacity {
+            let pinned = self.consumers.values()
+                .filter(|cursor| cursor.state == ConsumerState::Active && cursor.acked == self.first_sequence)
+                .map(|cursor| cursor.name.as_str())
+                .collect::<Vec<_>>();
+            return Err(format!("entry store is full, waiting for destination {} to ack", pinned.join(", ")));
+        }
+        let sequence = self.next_sequence();
+        let size = entry_size(&entry);
+        self.bytes += size;
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.add(size);
+        }
+        self.entries.push_back(entry);
+        Ok(sequence)
+    }
+
+    /**
+     * <pre>
+     *  返回(批次的起始序号, 结束序号, entries), 确认整个批次时ack结束序号.
+     *  有filter时entries只包含匹配的entry, 少于结束序号 - 起始序号, 没有新的entry时为空.
+     *  扫描到的entry都被过滤并且没有未确认的批次时, 直接确认这些entry
+     * </pre>
+     */
+    pub fn get(&mut self, name: &str, batch_size: usize) -> Result<(u64, u64, Vec<Entry>), String> {
+        let first_sequence = self.first_sequence;
+        let cursor = self.consumers.get_mut(name).ok_or_else(|| format!("destination {} is not subscribed", name))?;
+        cursor.last_fetch = Instant::now();
+        if cursor.acked < first_sequence {
+            return Err(format!("destination {} lost entries [{}, {}) while it was idle, reset to continue",
+                               name, cursor.acked, first_sequence));
+        }
+        if cursor.state == ConsumerState::Paused {
+            println!("destination {} resumes at sequence {}", name, cursor.acked);
+            cursor.state = ConsumerState::Active;
+        }
+        let start = cursor.fetched;
+        let offset = (start - first_sequence) as usize;
+        let mut entries = vec![];
+        let mut end = start;
+        for entry in self.entries.iter().skip(offset) {
+            if entries.len() >= batch_size {
+                break;
+            }
+            end += 1;
+            if cursor.filter.filter(entry) {
+                entries.push(entry.clone());
+            }
+        }
+        cursor.fetched = end;
+        if !entries.is_empty() {
+            cursor.batches.push_back((start, end));
+        } else if end > start && cursor.batches.is_empty() {
+            cursor.acked = end;
+            self.trim();
+        }
+        Ok((start, end, entries))
+    }
+
+    // 确认sequence之前(不包括sequence)的entry
+    pub fn ack(&mut self, name: &str, sequence: u64) -> Result<(), String> {
+        let cursor = self.consumers.get_mut(name).ok_or_else(|| format!("destination {} is not subscribed", name))?;
+        if sequence > cursor.fetched {
+            return Err(format!("destination {} acks sequence {} beyond fetched {}", name, sequence, cursor.fetched));
+        }
+        cursor.acked = cursor.acked.max(sequence);
+        while cursor.batches.front().is_some_and(|(_, end)| *end <= cursor.acked) {
+            cursor.batches.pop_front();
+        }
+        // 最后一个批次之后被过滤的entry一起确认
+        if cursor.batches.is_empty() {
+            cursor.acked = cursor.fetched;
+        }
+        self.trim();
+        Ok(())
+    }
+
+    pub fn rollback(&mut self, name: &str) -> Result<(), String> {
+        let cursor = self.consumers.get_mut(name).ok_or_else(|| format!("destination {} is not subscribed", name))?;
+        cursor.fetched = cursor.acked;
+        cursor.batches.clear();
+        Ok(())
+    }
+
+    // 丢失entry之后从store中最早的entry继续
+    pub fn reset(&mut self, name: &str) -> Result<(), String> {
+        let first_sequence = self.first_sequence;
+        let cursor = self.consumers.get_mut(name).ok_or_else(|| format!("destination {} is not subscribed", name))?;
+        cursor.acked = cursor.acked.max(first_sequence);
+        cursor.fetched = cursor.fetched.max(first_sequence);
+        cursor.batches.retain(|(_, end)| *end > first_sequence);
+        cursor.state = ConsumerState::Active;
+        cursor.last_fetch = Instant::now();
+        Ok(())
+    }
+
+    // 淘汰所有消费方都已确认的entry, store满时按需淘汰Paused消费方未确认的entry
+    fn trim(&mut self) {
+        let min_acked = |state: ConsumerState| self.consumers.values()
+            .filter(|cursor| cursor.state == state)
+            .map(|cursor| cursor.acked)
+            .min();
+        let active = min_acked(ConsumerState::Active).unwrap_or_else(|| self.next_sequence());
+        let retained = min_acked(ConsumerState::Paused).map_or(active, |paused| paused.min(active));
+        while self.first_sequence < retained && self.pop_front() {}
+        // 每次只腾出一个位置, 尽量为Paused消费方多保留
+        while self.entries.len() >= self.capacity && self.first_sequence < active && self.pop_front() {
+            self.evicted += 1;
+        }
+    }
+
+    fn pop_front(&mut self) -> bool {
+        match self.entries.pop_front() {
+            Some(entry) => {
+                let size = entry_size(&entry);
+                self.bytes -= size;
+                if let Some(gauge) = self.memory_gauge.as_ref() {
+                    gauge.sub(size);
+                }
+                self.first_sequence += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // put发现store已满时自动调用, 也可以由定时任务调用
+    pub fn check_idle(&mut self) -> Result<(), String> {
+        let idle_ttl = match self.idle_ttl {
+            Some(idle_ttl) => idle_ttl,
+            None => return Ok(()),
+        };
+        let idle: Vec<String> = self.consumers.values()
+            .filter(|cursor| cursor.state == ConsumerState::Active && cursor.last_fetch.elapsed() >= idle_ttl)
+            .map(|cursor| cursor.name.clone())
+            .collect();
+        for name in idle {
+            let message = format!("destination {} has not fetched for more than {:?}, {} it", name, idle_ttl,
+                                  self.idle_policy.name());
+            println!("{}", message);
+            match self.idle_policy {
+                IdlePolicy::Pause => {
+                    if let Some(cursor) = self.consumers.get_mut(&name) {
+                        cursor.state = ConsumerState::Paused;
+                    }
+                }
+                IdlePolicy::Expire => {
+                    self.consumers.remove(&name);
+                }
+            }
+            if let Some(notifier) = self.notifier.as_mut() {
+                let mut header = Header::default();
+                header.set_execute_time(Utc::now().timestamp_millis());
+                notifier.on_event(&Entry::incident(header, &message))?;
+                notifier.flush()?;
+            }
+        }
+        self.trim();
+        Ok(())
+    }
+}
+
+impl Drop for EntryStore {
+    fn drop(&mut self) {
+        if let Some(gauge) = self.memory_gauge.as_ref() {
+            gauge.sub(self.bytes);
+        }
+    }
+}
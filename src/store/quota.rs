@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use crate::config::Properties;
+
+pub const QUOTA_KEY_PREFIX: &str = "destination.";
+
+// 每秒的配额, None表示不限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quota {
+    max_entries_per_sec: Option<u64>,
+    max_bytes_per_sec: Option<u64>,
+}
+
+impl Quota {
+    pub fn new(max_entries_per_sec: Option<u64>, max_bytes_per_sec: Option<u64>) -> Quota {
+        Quota { max_entries_per_sec, max_bytes_per_sec }
+    }
+
+    /**
+     * <pre>
+     *  destination.<name>.max_entries_per_sec 和 destination.<name>.max_bytes_per_sec, 都没有配置时返回None,
+     *  值必须大于0
+     * </pre>
+     */
+    pub fn from_properties(properties: &Properties, destination: &str) -> Result<Option<Quota>, String> {
+        let get = |suffix: &str| -> Result<Option<u64>, String> {
+            let key = format!("{}{}.{}", QUOTA_KEY_PREFIX, destination, suffix);
+            match properties.get(&key) {
+                Some(value) => match value.trim().parse::<u64>() {
+                    Ok(limit) if limit > 0 => Ok(Some(limit)),
+                    _ => Err(format!("invalid value for {}: {}, expect a positive integer", key, value)),
+                },
+                None => Ok(None),
+            }
+        };
+        let quota = Quota::new(get("max_entries_per_sec")?, get("max_bytes_per_sec")?);
+        Ok(Some(quota).filter(|quota| !quota.is_unlimited()))
+    }
+
+    pub fn max_entries_per_sec(&self) -> Option<u64> {
+        self.max_entries_per_sec
+    }
+    pub fn max_bytes_per_sec(&self) -> Option<u64> {
+        self.max_bytes_per_sec
+    }
+    pub fn is_unlimited(&self) -> bool {
+        self.max_entries_per_sec.is_none() && self.max_bytes_per_sec.is_none()
+    }
+}
+
+// 容量为1秒配额的令牌桶, 允许透支, 透支之后等待补足
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> TokenBucket {
+        TokenBucket { rate: rate as f64, tokens: rate as f64, updated: now }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated = now;
+    }
+
+    // 令牌不少于1个之前需要等待的时间
+    fn wait(&self) -> Option<Duration> {
+        (self.tokens < 1.0).then(|| Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+}
+
+/**
+ * <pre>
+ *  一个destination的配额执行状态, EntryDelivery::get时检查:
+ *  令牌不足时不读取store, 返回需要等待的时间(Fetch::Throttled, 对应http的429 + Retry-After),
+ *  否则batch size不超过剩余的entry令牌, 投递之后按实际的条数和payload字节数扣除.
+ *  字节数在序列化之后才知道, 一个批次可以透支字节令牌, 之后的get等待透支补足.
+ *  每个桶的容量为1秒的配额, 空闲的消费方最多积攒1秒的突发
+ * </pre>
+ */
+#[derive(Debug, Clone)]
+pub struct QuotaLimiter {
+    quota: Quota,
+    entries: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    throttled: u64,
+}
+
+impl QuotaLimiter {
+    pub fn new(quota: Quota) -> QuotaLimiter {
+        QuotaLimiter::new_at(quota, Instant::now())
+    }
+
+    // now为令牌桶开始计时的时间, 桶在now时是满的
+    pub fn new_at(quota: Quota, now: Instant) -> QuotaLimiter {
+        QuotaLimiter {
+            quota,
+            entries: quota.max_entries_per_sec.map(|rate| TokenBucket::new(rate, now)),
+            bytes: quota.max_bytes_per_sec.map(|rate| TokenBucket::new(rate, now)),
+            throttled: 0,
+        }
+    }
+
+    pub fn quota(&self) -> Quota {
+        self.quota
+    }
+    // 被拒绝的get次数
+    pub fn throttled(&self) -> u64 {
+        self.throttled
+    }
+
+    // 可以投递时返回允许的batch size, 否则返回需要等待的时间
+    pub fn acquire(&mut self, batch_size: usize) -> Result<usize, Duration> {
+        self.acquire_at(batch_size, Instant::now())
+    }
+
+    // now早于上一次acquire时不补充令牌
+    pub fn acquire_at(&mut self, batch_size: usize, now: Instant) -> Result<usize, Duration> {
+        for bucket in self.entries.iter_mut().chain(self.bytes.iter_mut()) {
+            bucket.refill(now);
+        }
+        let wait = self.entries.iter().chain(self.bytes.iter()).filter_map(|bucket| bucket.wait()).max();
+        if let Some(wait) = wait {
+            self.throttled += 1;
+            return Err(wait);
+        }
+        Ok(match self.entries.as_ref() {
+            Some(bucket) => batch_size.min(bucket.tokens as usize).max(1),
+            None => batch_size,
+        })
+    }
+
+    pub fn consume(&mut self, entries: usize, bytes: usize) {
+        if let Some(bucket) = self.entries.as_mut() {
+            bucket.tokens -= entries as f64;
+        }
+        if let Some(bucket) = self.bytes.as_mut() {
+            bucket.tokens -= bytes as f64;
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// 执行生成的sql之前在mysql/mariadb连接上设置, 反斜杠不再是转义字符, 见SqlDialect::quote_value
+pub const NO_BACKSLASH_ESCAPES_SQL: &str =
+    "SET SESSION sql_mode = CONCAT_WS(',', NULLIF(@@SESSION.sql_mode, ''), 'NO_BACKSLASH_ESCAPES')";
+
+// 生成的sql所在的目标库类型, 决定upsert语法和字符串转义
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlTarget {
+    Mysql,
+    Mariadb,
+    Postgres,
+}
+
+impl SqlTarget {
+    pub fn from_name(name: &str) -> Result<SqlTarget, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "mysql" => Ok(SqlTarget::Mysql),
+            "mariadb" => Ok(SqlTarget::Mariadb),
+            "postgres" | "postgresql" => Ok(SqlTarget::Postgres),
+            _ => Err(format!("unknown sql dialect {}, expect mysql/mariadb/postgres", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SqlTarget::Mysql => "mysql",
+            SqlTarget::Mariadb => "mariadb",
+            SqlTarget::Postgres => "postgres",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentifierQuote {
+    // `name`, mysql/mariadb
+    Backtick,
+    // "name", postgres以及ANSI_QUOTES模式下的mysql
+    DoubleQuote,
+    // 不加引号, 只适用于不含关键字和特殊字符的名字
+    None,
+}
+
+impl IdentifierQuote {
+    pub fn from_name(name: &str) -> Result<IdentifierQuote, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "backtick" => Ok(IdentifierQuote::Backtick),
+            "double-quote" | "double_quote" => Ok(IdentifierQuote::DoubleQuote),
+            "none" => Ok(IdentifierQuote::None),
+            _ => Err(format!("unknown identifier quote {}, expect backtick/double-quote/none", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdentifierQuote::Backtick => "backtick",
+            IdentifierQuote::DoubleQuote => "double-quote",
+            IdentifierQuote::None => "none",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentifierCase {
+    Preserve,
+    Lower,
+    Upper,
+}
+
+impl IdentifierCase {
+    pub fn from_name(name: &str) -> Result<IdentifierCase, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "preserve" => Ok(IdentifierCase::Preserve),
+            "lower" => Ok(IdentifierCase::Lower),
+            "upper" => Ok(IdentifierCase::Upper),
+            _ => Err(format!("unknown identifier case {}, expect preserve/lower/upper", name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdentifierCase::Preserve => "preserve",
+            IdentifierCase::Lower => "lower",
+            IdentifierCase::Upper => "upper",
+        }
+    }
+}
+
+/**
+ * <pre>
+ *  生成sql时的方言, 默认值由目标库决定, 可以单独覆盖:
+ *                  quote           case        schema_prefix   upsert
+ *      mysql       backtick        preserve    true            REPLACE INTO
+ *      mariadb     backtick        preserve    true            REPLACE INTO
+ *      postgres    double-quote    lower       true            INSERT ... ON CONFLICT (pk) DO UPDATE
+ *  postgres中没有引号的名字被折叠为小写, 加引号之后大小写敏感, 因此默认转为小写以匹配按mysql表名建的表.
+ *  schema_prefix为false时只输出表名, 由连接的默认库/search_path决定schema
+ * </pre>
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlDialect {
+    target: SqlTarget,
+    quote: IdentifierQuote,
+    case: IdentifierCase,
+    schema_prefix: bool,
+}
+
+impl Default for SqlDialect {
+    fn default() -> Self {
+        SqlDialect::new(SqlTarget::Mysql)
+    }
+}
+
+impl SqlDialect {
+    pub fn new(target: SqlTarget) -> SqlDialect {
+        let (quote, case) = match target {
+            SqlTarget::Mysql | SqlTarget::Mariadb => (IdentifierQuote::Backtick, IdentifierCase::Preserve),
+            SqlTarget::Postgres => (IdentifierQuote::DoubleQuote, IdentifierCase::Lower),
+        };
+        SqlDialect { target, quote, case, schema_prefix: true }
+    }
+
+    pub fn target(&self) -> SqlTarget {
+        self.target
+    }
+    pub fn quote(&self) -> IdentifierQuote {
+        self.quote
+    }
+    pub fn case(&self) -> IdentifierCase {
+        self.case
+    }
+    pub fn schema_prefix(&self) -> bool {
+        self.schema_prefix
+    }
+
+    pub fn set_quote(&mut self, quote: IdentifierQuote) {
+        self.quote = quote;
+    }
+    pub fn set_case(&mut self, case: IdentifierCase) {
+        self.case = case;
+    }
+    pub fn set_schema_prefix(&mut self, schema_prefix: bool) {
+        self.schema_prefix = schema_prefix;
+    }
+
+    pub fn quote_identifier(&self, name: &str) -> String {
+        let name = match self.case {
+            IdentifierCase::Preserve => name.to_string(),
+            IdentifierCase::Lower => name.to_lowercase(),
+            IdentifierCase::Upper => name.to_uppercase(),
+        };
+        match self.quote {
+            IdentifierQuote::Backtick => format!("`{}`", name.replace('`', "``")),
+            IdentifierQuote::DoubleQuote => format!("\"{}\"", name.replace('"', "\"\"")),
+            IdentifierQuote::None => name,
+        }
+    }
+
+    // 单引号写两次, 反斜杠原样输出: postgres默认standard_conforming_strings=on,
+    // mysql/mariadb需要在NO_BACKSLASH_ESCAPES下执行(NO_BACKSLASH_ESCAPES_SQL)
+    pub fn quote_value(&self, value: &str) -> String {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+
+    // binary/blob列的值, hex为HEX()的结果
+    pub fn quote_binary(&self, hex: &str) -> String {
+        match self.target {
+            SqlTarget::Mysql | SqlTarget::Mariadb => format!("X'{}'", hex),
+            SqlTarget::Postgres => format!("'\\x{}'", hex),
+        }
+    }
+
+    pub fn table_name(&self, schema: &str, table: &str) -> String {
+        if self.schema_prefix {
+            format!("{}.{}", self.quote_identifier(schema), self.quote_identifier(table))
+        } else {
+            self.quote_identifier(table)
+        }
+    }
+
+    pub fn column_list(&self, names: &[String]) -> String {
+        names.iter().map(|name| self.quote_identifier(name)).collect::<Vec<String>>().join(", ")
+    }
+
+    // 按主键覆盖整行, values为已经quote过的值(或者NULL)
+    pub fn upsert(&self, schema: &str, table: &str, names: &[String], keys: &[String], values: &[String]) -> String {
+        match self.target {
+            SqlTarget::Mysql | SqlTarget::Mariadb => {
+                format!("REPLACE INTO {} ({}) VALUES ({});",
+                        self.table_name(schema, table), self.column_list(names), values.join(", "))
+            }
+            SqlTarget::Postgres => {
+                let updates = names.iter().filter(|name| !keys.contains(name))
+                    .map(|name| format!("{} = EXCLUDED.{}", self.quote_identifier(name), self.quote_identifier(name)))
+                    .collect::<Vec<String>>();
+                let action = if updates.is_empty() {
+                    "DO NOTHING".to_string()
+                } else {
+                    format!("DO UPDATE SET {}", updates.join(", "))
+                };
+                format!("INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) {};",
+                        self.table_name(schema, table), self.column_list(names), values.join(", "),
+                        self.column_list(keys), action)
+            }
+        }
+    }
+
+    // keys与values一一对应, values为已经quote过的值
+    pub fn delete(&self, schema: &str, table: &str, keys: &[String], values: &[String]) -> String {
+        let condition = keys.iter().zip(values.iter())
+            .map(|(key, value)| format!("{} = {}", self.quote_identifier(key), value))
+            .collect::<Vec<String>>();
+        format!("DELETE FROM {} WHERE {};", self.table_name(schema, table), condition.join(" AND "))
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+
+use sha1::{Digest, Sha1};
+
+use crate::channel::mysql_socket::MysqlConnector;
+use crate::command::hex::encode_hex;
+use crate::protocol::canonical;
+use crate::protocol::Column;
+use crate::verify::dialect::{SqlDialect, NO_BACKSLASH_ESCAPES_SQL};
+
+pub mod dialect;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+// 一个主键范围内的比对结果, lower不包含, upper包含, None表示无边界. binary主键为HEX()的结果
+#[derive(Debug, Clone, Default)]
+pub struct ChunkMismatch {
+    lower: Option<Vec<String>>,
+    upper: Option<Vec<String>>,
+    source_rows: usize,
+    target_rows: usize,
+    repair_sql: Vec<String>,
+}
+
+impl ChunkMismatch {
+    pub fn lower(&self) -> Option<&Vec<String>> {
+        self.lower.as_ref()
+    }
+    pub fn upper(&self) -> Option<&Vec<String>> {
+        self.upper.as_ref()
+    }
+    pub fn source_rows(&self) -> usize {
+        self.source_rows
+    }
+    pub fn target_rows(&self) -> usize {
+        self.target_rows
+    }
+    pub fn repair_sql(&self) -> &Vec<String> {
+        &self.repair_sql
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    table: String,
+    chunks: usize,
+    source_rows: usize,
+    target_rows: usize,
+    mismatches: Vec<ChunkMismatch>,
+}
+
+impl VerifyReport {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+    pub fn chunks(&self) -> usize {
+        self.chunks
+    }
+    pub fn source_rows(&self) -> usize {
+        self.source_rows
+    }
+    pub fn target_rows(&self) -> usize {
+        self.target_rows
+    }
+    pub fn mismatches(&self) -> &Vec<ChunkMismatch> {
+        &self.mismatches
+    }
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+// 表结构: 按ORDINAL_POSITION排序的列名/列类型, 以及主键列
+struct TableMeta {
+    columns: Vec<(String, String)>,
+    keys: Vec<String>,
+}
+
+impl TableMeta {
+    fn is_binary(&self, name: &str) -> bool {
+        self.columns.iter().any(|(column, column_type)| column == name && is_binary_type(column_type))
+    }
+
+    // 主键值按列类型quote, 用于范围条件
+    fn quote_keys(&self, values: &[String]) -> Vec<String> {
+        self.keys.iter().zip(values.iter()).map(|(key, value)| quote_value(value, self.is_binary(key))).collect()
+    }
+}
+
+// 一行数据: 主键值以及规范化之后的checksum, 生成修复sql时需要完整的列值. binary/blob列的值为HEX()的结果
+struct VerifyRow {
+    key: Vec<String>,
+    checksum: String,
+    values: Vec<Option<String>>,
+}
+
+/**
+ * <pre>
+ *  源库与目标库的表数据比对:
+ *  1. 在源库上按主键顺序切分chunk, 每个chunk最多chunk_size行
+ *      SELECT pk FROM t WHERE (pk) > (lower) ORDER BY pk LIMIT 1 OFFSET chunk_size - 1
+ *  2. 两边分别查询同一主键范围的数据, 每一行用canonical::row_checksum计算checksum,
+ *     因此decimal精度, 小数秒等格式上的差异不会被认为是不一致
+ *  3. chunk的checksum不一致时逐行比对, 需要时生成修复sql(upsert / DELETE)
+ *  最后一个chunk没有上边界, 目标库中多出来的行也能被发现.
+ *  比对时的查询总是mysql语法, 连接上设置NO_BACKSLASH_ESCAPES, 字符串中的单引号写两次,
+ *  binary/blob列按HEX()查询并以X'..'比较, 不会因为字符集转换而改变; 修复sql按照dialect生成, 见SqlDialect
+ * </pre>
+ */
+pub struct TableVerifier {
+    source: MysqlConnector,
+    target: MysqlConnector,
+    chunk_size: usize,
+    repair: bool,
+    dialect: SqlDialect,
+}
+
+impl TableVerifier {
+    pub fn new(source: MysqlConnector, target: MysqlConnector) -> TableVerifier {
+        TableVerifier { source, target, chunk_size: DEFAULT_CHUNK_SIZE, repair: false, dialect: SqlDialect::default() }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+    pub fn repair(&self) -> bool {
+        self.repair
+    }
+    pub fn dialect(&self) -> &SqlDialect {
+        &self.dialect
+    }
+
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size.max(1);
+    }
+    pub fn set_repair(&mut self, repair: bool) {
+        self.repair = repair;
+    }
+    pub fn set_dialect(&mut self, dialect: SqlDialect) {
+        self.dialect = dialect;
+    }
+
+    pub fn verify(&mut self, schema: &str, table: &str) -> Result<VerifyReport, String> {
+        for connector in [&mut self.source, &mut self.target] {
+            connector.connect()?;
+            connector.update(NO_BACKSLASH_ESCAPES_SQL)?;
+        }
+        let source_meta = load_meta(&mut self.source, schema, table)?;
+        let target_meta = load_meta(&mut self.target, schema, table)?;
+        if source_meta.keys.is_empty() {
+            return Err(format!("table {}.{} has no primary key, can't be chunked", schema, table));
+        }
+        if source_meta.keys != target_meta.keys {
+            return Err(format!("primary key of {}.{} differs: source {:?}, target {:?}",
+                               schema, table, source_meta.keys, target_meta.keys));
+        }
+        let names: Vec<&String> = source_meta.columns.iter().map(|(name, _)| name).collect();
+        let target_names: Vec<&String> = target_meta.columns.iter().map(|(name, _)| name).collect();
+        if names != target_names {
+            return Err(format!("columns of {}.{} differ: source {:?}, target {:?}",
+                               schema, table, names, target_names));
+        }
+
+        let mut report = VerifyReport { table: format!("{}.{}", schema, table), ..VerifyReport::default() };
+        let mut lower: Option<Vec<String>> = None;
+        loop {
+            let upper = self.next_boundary(schema, table, &source_meta, lower.as_ref())?;
+            let source_rows = fetch_rows(&mut self.source, schema, table, &source_meta, lower.as_ref(), upper.as_ref())?;
+            let target_rows = fetch_rows(&mut self.target, schema, table, &target_meta, lower.as_ref(), upper.as_ref())?;
+            report.chunks += 1;
+            report.source_rows += source_rows.len();
+            report.target_rows += target_rows.len();
+            if chunk_checksum(&source_rows) != chunk_checksum(&target_rows) {
+                let repair_sql = if self.repair {
+                    repair_sql(&self.dialect, schema, table, &source_meta, &source_rows, &target_rows)
+                } else {
+                    vec![]
+                };
+                report.mismatches.push(ChunkMismatch {
+                    lower: lower.clone(),
+                    upper: upper.clone(),
+                    source_rows: source_rows.len(),
+                    target_rows: target_rows.len(),
+                    repair_sql,
+                });
+            }
+            match upper {
+                Some(upper) => lower = Some(upper),
+                None => break,
+            }
+        }
+        Ok(report)
+    }
+
+    // 源库上当前chunk的上边界, 剩余行数不足chunk_size时返回None
+    fn next_boundary(&mut self, schema: &str, table: &str, meta: &TableMeta, lower: Option<&Vec<String>>)
+                     -> Result<Option<Vec<String>>, String> {
+        let keys = select_list(meta, &meta.keys);
+        let sql = format!("SELECT {} FROM {} {} ORDER BY {} LIMIT 1 OFFSET {}",
+                          keys, table_name(schema, table), range_condition(meta, lower, None),
+                          column_list(&meta.keys), self.chunk_size - 1);
+        let result = self.source.query(&sql)?;
+        let boundary = result.rows().next().filter(|row| !row.is_empty()).map(|row| row.to_vec());
+        Ok(boundary)
+    }
+}
+
+fn load_meta(connector: &mut MysqlConnector, schema: &str, table: &str) -> Result<TableMeta, String> {
+    let columns = connector.query(&format!(
+        "SELECT COLUMN_NAME, COLUMN_TYPE FROM information_schema.COLUMNS \
+         WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} ORDER BY ORDINAL_POSITION",
+        quote_value(schema, false), quote_value(table, false)))?;
+    let columns: Vec<(String, String)> = columns.rows()
+        .filter(|row| row.len() == 2)
+        .map(|row| (row[0].clone(), row[1].clone()))
+        .collect();
+    if columns.is_empty() {
+        return Err(format!("table {}.{} doesn't exist on {}:{}", schema, table, connector.address(), connector.port()));
+    }
+    let keys = connector.query(&format!(
+        "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+         WHERE TABLE_SCHEMA = {} AND TABLE_NAME = {} AND CONSTRAINT_NAME = 'PRIMARY' ORDER BY ORDINAL_POSITION",
+        quote_value(schema, false), quote_value(table, false)))?;
+    let keys = keys.rows().filter_map(|row| row.first().cloned()).collect();
+    Ok(TableMeta { columns, keys })
+}
+
+// 文本协议中NULL和空字符串无法区分, 额外查询一列ISNULL的标记
+fn fetch_rows(connector: &mut MysqlConnector, schema: &str, table: &str, meta: &TableMeta,
+              lower: Option<&Vec<String>>, upper: Option<&Vec<String>>) -> Result<Vec<VerifyRow>, String> {
+    let names: Vec<String> = meta.columns.iter().map(|(name, _)| name.clone()).collect();
+    let null_flags = names.iter().map(|name| format!("ISNULL({})", quote_identifier(name))).collect::<Vec<String>>();
+    let sql = format!("SELECT {}, CONCAT({}) FROM {} {} ORDER BY {}",
+                      select_list(meta, &names), null_flags.join(", "), table_name(schema, table),
+                      range_condition(meta, lower, upper), column_list(&meta.keys));
+    let result = connector.query(&sql)?;
+    let key_indexes: Vec<usize> = meta.keys.iter()
+        .filter_map(|key| names.iter().position(|name| name == key))
+        .collect();
+    let mut rows = vec![];
+    for row in result.rows() {
+        if row.len() != names.len() + 1 {
+            return Err(format!("unexpected column count {} for sql: {}", row.len(), sql));
+        }
+        let flags = row[names.len()].as_bytes();
+        let mut columns = Vec::with_capacity(names.len());
+        let mut values = Vec::with_capacity(names.len());
+        for (i, (name, mysql_type)) in meta.columns.iter().enumerate() {
+            let is_null = flags.get(i) == Some(&b'1');
+            let mut column = Column::new(i, name);
+            column.set_mysql_type(mysql_type);
+            column.set_is_null(is_null);
+            column.set_value(&row[i]);
+            columns.push(column);
+            values.push(if is_null { None } else { Some(row[i].clone()) });
+        }
+        rows.push(VerifyRow {
+            key: key_indexes.iter().map(|i| row[*i].clone()).collect(),
+            checksum: canonical::row_checksum(&columns),
+            values,
+        });
+    }
+    Ok(rows)
+}
+
+fn chunk_checksum(rows: &[VerifyRow]) -> String {
+    let mut digest = Sha1::new();
+    for row in rows {
+        digest.update(row.checksum.as_bytes());
+    }
+    encode_hex(&digest.finalize())
+}
+
+// 以源库为准: 源库有而目标库没有或者不一致的行REPLACE, 目标库多出来的行DELETE
+fn repair_sql(dialect: &SqlDialect, schema: &str, table: &str, meta: &TableMeta, source: &[VerifyRow],
+              target: &[VerifyRow]) -> Vec<String> {
+    let names: Vec<String> = meta.columns.iter().map(|(name, _)| name.clone()).collect();
+    let target_checksums: HashMap<&Vec<String>, &String> = target.iter().map(|row| (&row.key, &row.checksum)).collect();
+    let source_keys: HashMap<&Vec<String>, ()> = source.iter().map(|row| (&row.key, ())).collect();
+    let mut sql = vec![];
+    for row in source {
+        if target_checksums.get(&row.key) == Some(&&row.checksum) {
+            continue;
+        }
+        let values = names.iter().zip(row.values.iter())
+            .map(|(name, value)| match value {
+                Some(value) => dialect_value(dialect, value, meta.is_binary(name)),
+                None => "NULL".to_string(),
+            })
+            .collect::<Vec<String>>();
+        sql.push(dialect.upsert(schema, table, &names, &meta.keys, &values));
+    }
+    for row in target.iter().filter(|row| !source_keys.contains_key(&row.key)) {
+        let keys = meta.keys.iter().zip(row.key.iter())
+            .map(|(key, value)| dialect_value(dialect, value, meta.is_binary(key)))
+            .collect::<Vec<String>>();
+        sql.push(dialect.delete(schema, table, &meta.keys, &keys));
+    }
+    sql
+}
+
+fn dialect_value(dialect: &SqlDialect, value: &str, binary: bool) -> String {
+    if binary {
+        dialect.quote_binary(value)
+    } else {
+        dialect.quote_value(value)
+    }
+}
+
+// 多列主键使用行构造器比较, 例如 WHERE (`a`, `b`) > ('1', X'02')
+fn range_condition(meta: &TableMeta, lower: Option<&Vec<String>>, upper: Option<&Vec<String>>) -> String {
+    let tuple = |values: &Vec<String>| meta.quote_keys(values).join(", ");
+    let mut conditions = vec![];
+    if let Some(lower) = lower {
+        conditions.push(format!("({}) > ({})", column_list(&meta.keys), tuple(lower)));
+    }
+    if let Some(upper) = upper {
+        conditions.push(format!("({}) <= ({})", column_list(&meta.keys), tuple(upper)));
+    }
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    }
+}
+
+fn table_name(schema: &str, table: &str) -> String {
+    format!("{}.{}", quote_identifier(schema), quote_identifier(table))
+}
+
+fn column_list(names: &[String]) -> String {
+    names.iter().map(|name| quote_identifier(name)).collect::<Vec<String>>().join(", ")
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+// binary/blob列查询HEX(), 其它列原样查询
+fn select_list(meta: &TableMeta, names: &[String]) -> String {
+    names.iter().map(|name| if meta.is_binary(name) {
+        format!("HEX({})", quote_identifier(name))
+    } else {
+        quote_identifier(name)
+    }).collect::<Vec<String>>().join(", ")
+}
+
+// 连接上设置了NO_BACKSLASH_ESCAPES, 反斜杠不是转义字符; binary值为HEX()的结果
+fn quote_value(value: &str, binary: bool) -> String {
+    if binary {
+        format!("X'{}'", value)
+    } else {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+}
+
+// binary(n), varbinary(n), tinyblob/blob/mediumblob/longblob
+fn is_binary_type(column_type: &str) -> bool {
+    let column_type = column_type.to_ascii_lowercase();
+    column_type.starts_with("binary") || column_type.starts_with("varbinary") || column_type.ends_with("blob")
+}
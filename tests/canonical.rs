@@ -0,0 +1,81 @@
+use mysql_binlog_parse::protocol::canonical::{canonical_columns, canonical_row, canonical_value, row_checksum,
+                                               table_version, FIELD_SEPARATOR};
+use mysql_binlog_parse::protocol::{Column, RowData};
+
+fn column(index: usize, name: &str, mysql_type: &str, value: Option<&str>) -> Column {
+    let mut column = Column::new(index, name);
+    column.set_mysql_type(mysql_type);
+    match value {
+        Some(value) => column.set_value(value),
+        None => column.set_is_null(true),
+    }
+    column
+}
+
+fn columns() -> Vec<Column> {
+    vec![column(0, "id", "bigint(20)", Some("7")), column(1, "amount", "decimal(10,2)", Some("12.50")),
+         column(2, "created", "datetime(6)", Some("2024-01-01 10:00:00.120000")), column(3, "note", "varchar(32)", None)]
+}
+
+#[test]
+fn checksum_is_stable_across_column_order() {
+    let ordered = columns();
+    let mut reversed = columns();
+    reversed.reverse();
+    assert_eq!(canonical_columns(&ordered), canonical_columns(&reversed));
+    assert_eq!(row_checksum(&ordered), row_checksum(&reversed));
+    assert_eq!(canonical_columns(&ordered),
+               ["7", "12.5", "2024-01-01 10:00:00.12", "\\N"].join(&FIELD_SEPARATOR.to_string()));
+    // 与mysql中SHA1()的输出一致, 40位小写hex
+    let checksum = row_checksum(&ordered);
+    assert_eq!(checksum.len(), 40);
+    assert!(checksum.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+
+    // 值改变时checksum改变
+    let mut changed = columns();
+    changed[1] = column(1, "amount", "decimal(10,2)", Some("12.51"));
+    assert_ne!(row_checksum(&ordered), row_checksum(&changed));
+}
+
+#[test]
+fn values_are_normalized_by_type() {
+    assert_eq!(canonical_value("decimal(10,4)", "0012.3400"), "12.34");
+    assert_eq!(canonical_value("DECIMAL(10,2)", "-0.00"), "0");
+    assert_eq!(canonical_value("double", "1.50"), "1.5");
+    assert_eq!(canonical_value("float", "-0"), "0");
+    assert_eq!(canonical_value("timestamp(3)", "2024-01-01 10:00:00.000"), "2024-01-01 10:00:00");
+    assert_eq!(canonical_value("time(6)", "10:00:00.500000"), "10:00:00.5");
+    assert_eq!(canonical_value("varchar(10)", "0012.3400"), "0012.3400");
+}
+
+#[test]
+fn null_and_separator_are_unambiguous() {
+    let null = vec![column(0, "note", "varchar(32)", None)];
+    let literal = vec![column(0, "note", "varchar(32)", Some("\\N"))];
+    assert_ne!(canonical_columns(&null), canonical_columns(&literal));
+
+    // 值中带分隔符时不能与两列混淆
+    let one = vec![column(0, "a", "varchar(32)", Some(&format!("x{}y", FIELD_SEPARATOR)))];
+    let two = vec![column(0, "a", "varchar(32)", Some("x")), column(1, "b", "varchar(32)", Some("y"))];
+    assert_ne!(row_checksum(&one), row_checksum(&two));
+}
+
+#[test]
+fn row_uses_after_image_unless_deleted() {
+    let before = vec![column(0, "id", "int", Some("1"))];
+    let after = vec![column(0, "id", "int", Some("2"))];
+    assert_eq!(canonical_row(&RowData::new(before.clone(), after.clone())), "2");
+    assert_eq!(canonical_row(&RowData::new(before, vec![])), "1");
+}
+
+#[test]
+fn table_version_changes_with_schema_only() {
+    let mut reversed = columns();
+    reversed.reverse();
+    assert_eq!(table_version(&columns()), table_version(&reversed));
+    assert_eq!(table_version(&columns()).len(), 16);
+
+    let mut retyped = columns();
+    retyped[0] = column(0, "id", "int(11)", Some("7"));
+    assert_ne!(table_version(&columns()), table_version(&retyped));
+}
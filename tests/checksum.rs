@@ -0,0 +1,96 @@
+mod common;
+
+use common::{event, format_description_body, query_body, rotate_body, BinlogFile};
+use mysql_binlog_parse::command::event::{checksum, EventType, LogContext, LogDecoder, LogEvent};
+
+fn decode_all(events: &[Vec<u8>], checksum_alg: u8) -> (Vec<LogEvent>, LogContext) {
+    let mut context = LogContext::new();
+    context.set_checksum_alg(checksum_alg);
+    let mut decoder = LogDecoder::new();
+    let events = events.iter().map(|event| decoder.decode(event, &mut context).unwrap()).collect();
+    (events, context)
+}
+
+fn query(event: &LogEvent) -> &str {
+    match event {
+        LogEvent::Query(query) => query.query(),
+        other => panic!("expect query event, got {:?}", other),
+    }
+}
+
+fn rotate_filename(event: &LogEvent) -> &str {
+    match event {
+        LogEvent::Rotate(rotate) => rotate.filename(),
+        other => panic!("expect rotate event, got {:?}", other),
+    }
+}
+
+// 5.6.1+的format description总是带有checksum alg和4字节的checksum, alg为OFF时checksum为0
+fn format_description_without_checksum(server_version: &str) -> Vec<u8> {
+    let mut body = format_description_body(server_version, true);
+    *body.last_mut().unwrap() = checksum::BINLOG_CHECKSUM_ALG_OFF;
+    let mut event = event(EventType::FormatDescriptionEvent, &body, 0, 0, true);
+    let len = event.len();
+    event[len - checksum::BINLOG_CHECKSUM_LEN..].fill(0);
+    event
+}
+
+#[test]
+fn upgrade_from_server_without_checksum() {
+    // mysql-bin.000001由5.5写入, 没有checksum, 之后升级到8.0并开启CRC32
+    let mut old = BinlogFile::new(false);
+    let mut new = BinlogFile::new(true);
+    let events = vec![
+        old.append(EventType::FormatDescriptionEvent, &format_description_body("5.5.62-log", false)),
+        old.append(EventType::QueryEvent, &query_body("test", &[], b"BEGIN")),
+        old.append(EventType::RotateEvent, &rotate_body(4, "mysql-bin.000002")),
+        new.append(EventType::FormatDescriptionEvent, &format_description_body("8.0.33", true)),
+        new.append(EventType::QueryEvent, &query_body("test", &[], b"COMMIT")),
+    ];
+    // 连接的是升级之后的master, 初始的checksum alg取自@@global.binlog_checksum
+    let (decoded, context) = decode_all(&events, checksum::BINLOG_CHECKSUM_ALG_CRC32);
+    assert_eq!(query(&decoded[1]), "BEGIN");
+    assert_eq!(decoded[1].header().data_len(), events[1].len() - 19);
+    assert_eq!(rotate_filename(&decoded[2]), "mysql-bin.000002");
+    assert_eq!(query(&decoded[4]), "COMMIT");
+    assert_eq!(decoded[4].header().data_len(), events[4].len() - 19 - checksum::BINLOG_CHECKSUM_LEN);
+    assert_eq!(context.checksum_alg(), checksum::BINLOG_CHECKSUM_ALG_CRC32);
+}
+
+#[test]
+fn checksum_turned_off_between_files() {
+    let mut first = BinlogFile::new(true);
+    let mut second = BinlogFile::new(false);
+    let description = format_description_without_checksum("8.0.33");
+    let events = vec![
+        first.append(EventType::FormatDescriptionEvent, &format_description_body("8.0.33", true)),
+        first.append(EventType::QueryEvent, &query_body("test", &[], b"BEGIN")),
+        first.append(EventType::RotateEvent, &rotate_body(4, "mysql-bin.000002")),
+        description,
+        second.append(EventType::QueryEvent, &query_body("test", &[], b"COMMIT")),
+        second.append(EventType::RotateEvent, &rotate_body(4, "mysql-bin.000003")),
+    ];
+    let (decoded, context) = decode_all(&events, checksum::BINLOG_CHECKSUM_ALG_CRC32);
+    assert_eq!(query(&decoded[1]), "BEGIN");
+    assert_eq!(rotate_filename(&decoded[2]), "mysql-bin.000002");
+    assert_eq!(query(&decoded[4]), "COMMIT");
+    assert_eq!(rotate_filename(&decoded[5]), "mysql-bin.000003");
+    assert_eq!(context.checksum_alg(), checksum::BINLOG_CHECKSUM_ALG_OFF);
+}
+
+#[test]
+fn checksum_turned_on_between_files() {
+    let mut first = BinlogFile::new(false);
+    let mut second = BinlogFile::new(true);
+    let events = vec![
+        format_description_without_checksum("8.0.33"),
+        first.append(EventType::QueryEvent, &query_body("test", &[], b"BEGIN")),
+        first.append(EventType::RotateEvent, &rotate_body(4, "mysql-bin.000002")),
+        second.append(EventType::FormatDescriptionEvent, &format_description_body("8.0.33", true)),
+        second.append(EventType::QueryEvent, &query_body("test", &[], b"COMMIT")),
+    ];
+    let (decoded, _) = decode_all(&events, checksum::BINLOG_CHECKSUM_ALG_OFF);
+    assert_eq!(query(&decoded[1]), "BEGIN");
+    assert_eq!(rotate_filename(&decoded[2]), "mysql-bin.000002");
+    assert_eq!(query(&decoded[4]), "COMMIT");
+}
@@ -0,0 +1,68 @@
+use mysql_binlog_parse::command::com::{BinlogDumpCommand, Command, PingCommand, QueryCommand, QuitCommand,
+                                       RegisterSlaveCommand, SemiAckCommand, BINLOG_DUMP_NON_BLOCK};
+use mysql_binlog_parse::command::command_type::{COM_BINLOG_DUMP, COM_PING, COM_QUERY, COM_QUIT, COM_REGISTER_SLAVE};
+
+#[test]
+fn quit_and_ping() {
+    assert_eq!(QuitCommand.encode(), vec![COM_QUIT]);
+    assert_eq!(PingCommand.encode(), vec![COM_PING]);
+    assert_eq!(QuitCommand::decode(&[COM_QUIT]).unwrap(), QuitCommand);
+    assert_eq!(PingCommand::decode(&[COM_PING]).unwrap(), PingCommand);
+    assert!(QuitCommand::decode(&[COM_PING]).is_err());
+    assert!(PingCommand::decode(&[]).is_err());
+}
+
+#[test]
+fn query() {
+    let command = QueryCommand::new("show master status");
+    let bytes = command.encode();
+    assert_eq!(bytes[0], COM_QUERY);
+    assert_eq!(&bytes[1..], b"show master status");
+    assert_eq!(QueryCommand::decode(&bytes).unwrap(), command);
+}
+
+#[test]
+fn register_slave() {
+    let command = RegisterSlaveCommand::builder(3)
+        .report_host("h")
+        .report_user("u")
+        .report_password("pw")
+        .report_port(3306)
+        .build();
+    let bytes = command.encode();
+    let mut expected = vec![COM_REGISTER_SLAVE, 3, 0, 0, 0, 1, b'h', 1, b'u', 2, b'p', b'w'];
+    expected.extend_from_slice(&3306u16.to_le_bytes());
+    expected.extend_from_slice(&[0; 8]);
+    assert_eq!(bytes, expected);
+    assert_eq!(RegisterSlaveCommand::decode(&bytes).unwrap(), command);
+    assert!(RegisterSlaveCommand::decode(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn binlog_dump() {
+    let command = BinlogDumpCommand::builder(65535)
+        .binlog_file("mysql-bin.000001")
+        .binlog_position(4)
+        .flags(BINLOG_DUMP_NON_BLOCK)
+        .build();
+    let bytes = command.encode();
+    let mut expected = vec![COM_BINLOG_DUMP, 4, 0, 0, 0, 1, 0, 0xff, 0xff, 0, 0];
+    expected.extend_from_slice(b"mysql-bin.000001");
+    assert_eq!(bytes, expected);
+    let decoded = BinlogDumpCommand::decode(&bytes).unwrap();
+    assert_eq!(decoded, command);
+    assert_eq!(decoded.binlog_file(), "mysql-bin.000001");
+    assert!(BinlogDumpCommand::decode(&bytes[..10]).is_err());
+}
+
+#[test]
+fn semi_ack() {
+    let command = SemiAckCommand::new("mysql-bin.000002", 1024);
+    let bytes = command.encode();
+    let mut expected = vec![0xef];
+    expected.extend_from_slice(&1024u64.to_le_bytes());
+    expected.extend_from_slice(b"mysql-bin.000002");
+    assert_eq!(bytes, expected);
+    assert_eq!(SemiAckCommand::decode(&bytes).unwrap(), command);
+    assert!(SemiAckCommand::decode(&bytes[..5]).is_err());
+}
@@ -0,0 +1,140 @@
+#![allow(dead_code)]
+
+use mysql_binlog_parse::command::event::event_type::ENUM_END_EVENT;
+use mysql_binlog_parse::command::event::{checksum, EventType, LOG_HEADER_LEN};
+
+pub const SERVER_ID: u32 = 1;
+pub const TIMESTAMP: u32 = 1_700_000_000;
+
+pub fn event(kind: EventType, body: &[u8], log_pos: u32, flags: u16, with_checksum: bool) -> Vec<u8> {
+    let crc_len = if with_checksum { checksum::BINLOG_CHECKSUM_LEN } else { 0 };
+    let mut buf = vec![];
+    buf.extend_from_slice(&TIMESTAMP.to_le_bytes());
+    buf.push(kind.code());
+    buf.extend_from_slice(&SERVER_ID.to_le_bytes());
+    buf.extend_from_slice(&((LOG_HEADER_LEN + body.len() + crc_len) as u32).to_le_bytes());
+    buf.extend_from_slice(&log_pos.to_le_bytes());
+    buf.extend_from_slice(&flags.to_le_bytes());
+    buf.extend_from_slice(body);
+    buf.extend(std::iter::repeat_n(0xaa, crc_len));
+    buf
+}
+
+pub fn format_description_body(server_version: &str, with_checksum: bool) -> Vec<u8> {
+    let mut body = vec![];
+    body.extend_from_slice(&4u16.to_le_bytes());
+    let mut version = server_version.as_bytes().to_vec();
+    version.resize(50, 0);
+    body.extend_from_slice(&version);
+    body.extend_from_slice(&0u32.to_le_bytes());
+    body.push(LOG_HEADER_LEN as u8);
+    let mut post_header_len = vec![0u8; ENUM_END_EVENT as usize - 1];
+    post_header_len[EventType::QueryEvent as usize - 1] = 13;
+    post_header_len[EventType::RotateEvent as usize - 1] = 8;
+    post_header_len[EventType::TableMapEvent as usize - 1] = 8;
+    post_header_len[EventType::WriteRowsEvent as usize - 1] = 10;
+    post_header_len[EventType::UpdateRowsEvent as usize - 1] = 10;
+    post_header_len[EventType::DeleteRowsEvent as usize - 1] = 10;
+    post_header_len[EventType::GtidLogEvent as usize - 1] = 42;
+    body.extend_from_slice(&post_header_len);
+    if with_checksum {
+        body.push(checksum::BINLOG_CHECKSUM_ALG_CRC32);
+    }
+    body
+}
+
+pub fn query_body(db: &str, status_vars: &[u8], query: &[u8]) -> Vec<u8> {
+    let mut body = vec![];
+    body.extend_from_slice(&77u32.to_le_bytes());
+    body.extend_from_slice(&3u32.to_le_bytes());
+    body.push(db.len() as u8);
+    body.extend_from_slice(&0u16.to_le_bytes());
+    body.extend_from_slice(&(status_vars.len() as u16).to_le_bytes());
+    body.extend_from_slice(status_vars);
+    body.extend_from_slice(db.as_bytes());
+    body.push(0);
+    body.extend_from_slice(query);
+    body
+}
+
+pub fn rotate_body(position: u64, filename: &str) -> Vec<u8> {
+    let mut body = position.to_le_bytes().to_vec();
+    body.extend_from_slice(filename.as_bytes());
+    body
+}
+
+// length encoded integer, 251以上的值(例如宽表的列数)需要多个字节
+pub fn packed_long(value: u64) -> Vec<u8> {
+    match value {
+        0..=250 => vec![value as u8],
+        251..=0xffff => [vec![252], (value as u16).to_le_bytes().to_vec()].concat(),
+        0x10000..=0xffffff => [vec![253], value.to_le_bytes()[..3].to_vec()].concat(),
+        _ => [vec![254], value.to_le_bytes().to_vec()].concat(),
+    }
+}
+
+// 低位在前, 每个bit对应一列, 用于present/null bitmap
+pub fn bitmap(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            bytes[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bytes
+}
+
+pub fn table_map_body(table_id: u64, db: &str, table: &str, types: &[u8], metadata: &[u8]) -> Vec<u8> {
+    let mut body = table_id.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    body.push(db.len() as u8);
+    body.extend_from_slice(db.as_bytes());
+    body.push(0);
+    body.push(table.len() as u8);
+    body.extend_from_slice(table.as_bytes());
+    body.push(0);
+    body.extend(packed_long(types.len() as u64));
+    body.extend_from_slice(types);
+    body.extend(packed_long(metadata.len() as u64));
+    body.extend_from_slice(metadata);
+    body.extend(std::iter::repeat_n(0u8, types.len().div_ceil(8)));
+    body
+}
+
+// v2 rows event, 所有列都出现且不为NULL, rows中每一项是一行按列顺序编码好的值
+pub fn write_rows_body(table_id: u64, column_count: usize, rows: &[&[u8]]) -> Vec<u8> {
+    let mut body = table_id.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    body.extend_from_slice(&2u16.to_le_bytes());
+    body.extend(packed_long(column_count as u64));
+    body.extend(std::iter::repeat_n(0xffu8, column_count.div_ceil(8)));
+    for row in rows {
+        body.extend(std::iter::repeat_n(0u8, column_count.div_ceil(8)));
+        body.extend_from_slice(row);
+    }
+    body
+}
+
+/**
+ * 按binlog文件的顺序生成event, log_pos为event在文件中的结束位置
+ */
+pub struct BinlogFile {
+    position: u32,
+    with_checksum: bool,
+}
+
+impl BinlogFile {
+    pub fn new(with_checksum: bool) -> BinlogFile {
+        BinlogFile { position: 4, with_checksum }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position as u64
+    }
+
+    pub fn append(&mut self, kind: EventType, body: &[u8]) -> Vec<u8> {
+        let crc_len = if self.with_checksum { checksum::BINLOG_CHECKSUM_LEN } else { 0 };
+        self.position += (LOG_HEADER_LEN + body.len() + crc_len) as u32;
+        event(kind, body, self.position, 0, self.with_checksum)
+    }
+}
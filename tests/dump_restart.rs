@@ -0,0 +1,350 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::{event, format_description_body, packed_long, query_body, rotate_body, table_map_body, write_rows_body,
+             BinlogFile};
+use mysql_binlog_parse::command::event::{checksum, event_flag, EventType, LogContext, LogDecoder, LogEvent,
+                                          UnsupportedEventPolicy};
+use mysql_binlog_parse::config::parse_properties;
+use mysql_binlog_parse::instance::backoff::Backoff;
+use mysql_binlog_parse::instance::purge::PurgedBinlogStrategy;
+use mysql_binlog_parse::instance::running::MysqlEventParser;
+use mysql_binlog_parse::instance::tracker::PositionTracker;
+use mysql_binlog_parse::instance::{AuthenticationInfo, EntryPosition};
+use mysql_binlog_parse::mock::{MockMaster, MOCK_BINLOG_FILE};
+use mysql_binlog_parse::protocol::{Entry, EntryType};
+use mysql_binlog_parse::sink::callback::CallbackSink;
+
+const FILE: &str = "mysql-bin.000001";
+
+struct Binlog {
+    events: Vec<(u64, Vec<u8>)>,
+    format_description: Vec<u8>,
+}
+
+impl Binlog {
+    fn new() -> Binlog {
+        let mut file = BinlogFile::new(true);
+        let description = format_description_body("8.0.33", true);
+        let mut events = vec![];
+        let mut append = |file: &mut BinlogFile, kind: EventType, body: &[u8]| {
+            let start = file.position();
+            events.push((start, file.append(kind, body)));
+        };
+        append(&mut file, EventType::FormatDescriptionEvent, &description);
+        for id in [1u32, 2] {
+            append(&mut file, EventType::GtidLogEvent, &[0u8; 42]);
+            append(&mut file, EventType::QueryEvent, &query_body("test", &[], b"BEGIN"));
+            append(&mut file, EventType::TableMapEvent, &table_map_body(10, "test", "t", &[3], &[]));
+            append(&mut file, EventType::WriteRowsEvent, &write_rows_body(10, 1, &[&id.to_le_bytes()]));
+            append(&mut file, EventType::XidEvent, &7u64.to_le_bytes());
+        }
+        append(&mut file, EventType::GtidLogEvent, &[0u8; 42]);
+        append(&mut file, EventType::QueryEvent, &query_body("test", &[], b"CREATE TABLE t2 (id int)"));
+        Binlog { events, format_description: description }
+    }
+
+    fn start_of(&self, index: usize) -> u64 {
+        self.events[index].0
+    }
+
+    fn end(&self) -> u64 {
+        let (start, last) = self.events.last().unwrap();
+        start + last.len() as u64
+    }
+
+    // 模拟一次dump: fake rotate + format description + 从start开始的event, 收到limit个binlog中的event之后断开.
+    // 返回每个event在update之前is_partial的结果
+    fn dump(&self, tracker: &mut PositionTracker, limit: Option<usize>) -> Vec<bool> {
+        let start = tracker.position().clone();
+        let mut context = LogContext::new();
+        context.set_checksum_alg(checksum::BINLOG_CHECKSUM_ALG_CRC32);
+        let mut decoder = LogDecoder::new();
+        let mut session = vec![
+            event(EventType::RotateEvent, &rotate_body(start.position(), start.journal_name()), 0,
+                  event_flag::LOG_EVENT_ARTIFICIAL_F, true),
+        ];
+        if start.position() > 4 {
+            session.push(event(EventType::FormatDescriptionEvent, &self.format_description, 0, 0, true));
+        }
+        let limit = limit.map(|limit| limit + session.len()).unwrap_or(usize::MAX);
+        session.extend(self.events.iter()
+            .filter(|(position, _)| *position >= start.position())
+            .map(|(_, event)| event.clone()));
+        let mut partial = vec![];
+        for event in session.iter().take(limit) {
+            let event = decoder.decode(event, &mut context).unwrap();
+            partial.push(tracker.is_partial(&event));
+            tracker.update(&event);
+        }
+        partial
+    }
+}
+
+#[test]
+fn fake_rotate_does_not_advance_position() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, binlog.start_of(1)));
+    binlog.dump(&mut tracker, Some(0));
+    assert_eq!(tracker.position(), &EntryPosition::new(FILE, binlog.start_of(1)));
+    assert_eq!(tracker.sequence(), 2);
+    assert!(!tracker.in_transaction());
+}
+
+#[test]
+fn reconnect_mid_transaction_restarts_from_transaction_start() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, 4));
+    // format description, gtid, begin, table map, rows, 在xid之前断开
+    binlog.dump(&mut tracker, Some(5));
+    assert!(tracker.in_transaction());
+    assert_eq!(tracker.position().position(), binlog.start_of(5));
+    assert_eq!(tracker.restart_position().position(), binlog.start_of(1));
+
+    let restart = tracker.restart();
+    assert_eq!(restart.journal_name(), FILE);
+    assert_eq!(restart.position(), binlog.start_of(1));
+    assert_eq!(tracker.sequence(), 0);
+    assert!(!tracker.in_transaction());
+}
+
+#[test]
+fn repeated_reconnects_mid_transaction_are_idempotent() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, 4));
+    // 第一个事务的begin之后断开
+    binlog.dump(&mut tracker, Some(3));
+    tracker.restart();
+    binlog.dump(&mut tracker, Some(3));
+    tracker.restart();
+    // 第一个事务完成, 第二个事务的table map之后断开
+    binlog.dump(&mut tracker, Some(8));
+    let second_transaction = binlog.start_of(6);
+    for limit in [1, 2, 3, 4] {
+        let restart = tracker.restart();
+        assert_eq!((restart.journal_name(), restart.position()), (FILE, second_transaction));
+        binlog.dump(&mut tracker, Some(limit));
+        assert_eq!(tracker.restart_position().position(), second_transaction, "limit {}", limit);
+    }
+
+    tracker.restart();
+    binlog.dump(&mut tracker, None);
+    assert!(!tracker.in_transaction());
+    assert_eq!(tracker.restart().position(), binlog.end());
+}
+
+#[test]
+fn start_mid_transaction_marks_tail_as_partial() {
+    let binlog = Binlog::new();
+    // 位点没有transaction_start, 落在第一个事务的table map
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, binlog.start_of(3)));
+    let partial = binlog.dump(&mut tracker, Some(5));
+    // fake rotate, format description, table map, rows, xid, 第二个事务的gtid, begin
+    assert_eq!(partial, vec![false, false, true, true, true, false, false]);
+    assert!(!tracker.in_partial_transaction());
+    assert!(tracker.in_transaction());
+}
+
+#[test]
+fn ddl_without_begin_completes_transaction() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, 4));
+    binlog.dump(&mut tracker, None);
+    assert!(!tracker.in_transaction());
+    assert_eq!(tracker.restart_position().position(), binlog.end());
+
+    // ddl的gtid之后断开, 需要从gtid重新开始
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, binlog.start_of(11)));
+    binlog.dump(&mut tracker, Some(1));
+    assert_eq!(tracker.restart_position().position(), binlog.start_of(11));
+}
+
+#[test]
+fn fake_rotate_to_another_file_switches_journal() {
+    let mut tracker = PositionTracker::new(EntryPosition::new("", 0));
+    let mut context = LogContext::new();
+    let rotate = event(EventType::RotateEvent, &rotate_body(4, "mysql-bin.000003"), 0,
+                       event_flag::LOG_EVENT_ARTIFICIAL_F, false);
+    tracker.update(&LogDecoder::new().decode(&rotate, &mut context).unwrap());
+    assert_eq!(tracker.position(), &EntryPosition::new("mysql-bin.000003", 4));
+}
+
+#[test]
+fn real_rotate_moves_to_next_file() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, 4));
+    binlog.dump(&mut tracker, None);
+    let mut context = LogContext::new();
+    let rotate = event(EventType::RotateEvent, &rotate_body(4, "mysql-bin.000002"), binlog.end() as u32 + 50, 0, false);
+    tracker.update(&LogDecoder::new().decode(&rotate, &mut context).unwrap());
+    assert_eq!(tracker.restart(), EntryPosition::new("mysql-bin.000002", 4));
+}
+
+#[test]
+fn raw_tracker_resumes_after_last_event() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::raw(EntryPosition::new(FILE, 4));
+    binlog.dump(&mut tracker, Some(5));
+    assert!(!tracker.in_transaction());
+    assert_eq!(tracker.restart().position(), binlog.start_of(5));
+}
+
+#[test]
+fn backoff_doubles_until_max_delay() {
+    let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500));
+    backoff.set_max_retries(Some(5));
+    let delays: Vec<Option<Duration>> = (0..6).map(|_| backoff.next_delay()).collect();
+    assert_eq!(delays, vec![
+        Some(Duration::from_millis(100)),
+        Some(Duration::from_millis(200)),
+        Some(Duration::from_millis(400)),
+        Some(Duration::from_millis(500)),
+        Some(Duration::from_millis(500)),
+        None,
+    ]);
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Some(Duration::from_millis(100)));
+}
+
+#[test]
+fn unlimited_backoff_never_gives_up() {
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
+    backoff.set_max_retries(None);
+    for _ in 0..100 {
+        assert!(backoff.next_delay().unwrap() <= Duration::from_secs(30));
+    }
+    assert_eq!(backoff.attempts(), 100);
+}
+
+#[test]
+fn backoff_from_properties() {
+    assert_eq!(Backoff::from_properties(&parse_properties("").unwrap(), "master.reconnect").unwrap(), None);
+
+    let properties = parse_properties("master.reconnect.initial_delay=200ms\nmaster.reconnect.max_retries=unlimited\n\
+                                       master.reconnect.jitter=0.5").unwrap();
+    let mut backoff = Backoff::from_properties(&properties, "master.reconnect").unwrap().unwrap();
+    assert_eq!(backoff.initial_delay(), Duration::from_millis(200));
+    assert_eq!(backoff.max_delay(), Duration::from_secs(30));
+    assert_eq!(backoff.max_retries(), None);
+    let delay = backoff.next_delay().unwrap();
+    assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(300), "{:?}", delay);
+
+    for invalid in ["master.reconnect.max_retries=-1", "master.reconnect.jitter=2",
+                    "master.reconnect.initial_delay=10s\nmaster.reconnect.max_delay=1s"] {
+        assert!(Backoff::from_properties(&parse_properties(invalid).unwrap(), "master.reconnect").is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn heartbeat_does_not_advance_position() {
+    let binlog = Binlog::new();
+    let mut tracker = PositionTracker::new(EntryPosition::new(FILE, binlog.start_of(1)));
+    binlog.dump(&mut tracker, Some(5));
+    let position = tracker.position().clone();
+
+    let mut context = LogContext::new();
+    context.set_checksum_alg(checksum::BINLOG_CHECKSUM_ALG_CRC32);
+    let mut decoder = LogDecoder::new();
+    decoder.decode(&event(EventType::FormatDescriptionEvent, &binlog.format_description, 0, 0, true), &mut context)
+        .unwrap();
+    let heartbeat = event(EventType::HeartbeatLogEvent, FILE.as_bytes(), binlog.end() as u32,
+                          event_flag::LOG_EVENT_ARTIFICIAL_F, true);
+    let decoded = decoder.decode(&heartbeat, &mut context).unwrap();
+    match &decoded {
+        LogEvent::Heartbeat(heartbeat) => {
+            assert_eq!(heartbeat.filename(), FILE);
+            assert_eq!(heartbeat.position(), binlog.end());
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+    tracker.update(&decoded);
+    assert_eq!(tracker.position(), &position);
+
+    // V2的位置不受log_pos的4G限制
+    let mut body = vec![1];
+    body.extend(packed_long(FILE.len() as u64));
+    body.extend(FILE.as_bytes());
+    body.push(2);
+    body.extend(packed_long(9));
+    body.extend(packed_long(5_000_000_000));
+    body.push(0);
+    let heartbeat = event(EventType::HeartbeatLogEventV2, &body, 0, event_flag::LOG_EVENT_ARTIFICIAL_F, true);
+    match decoder.decode(&heartbeat, &mut context).unwrap() {
+        LogEvent::Heartbeat(heartbeat) => {
+            assert_eq!(heartbeat.filename(), FILE);
+            assert_eq!(heartbeat.position(), 5_000_000_000);
+        }
+        other => panic!("unexpected event {:?}", other),
+    }
+}
+
+#[test]
+fn undecoded_rows_events_fail_by_default() {
+    let binlog = Binlog::new();
+    let mut context = LogContext::new();
+    context.set_checksum_alg(checksum::BINLOG_CHECKSUM_ALG_CRC32);
+    let mut decoder = LogDecoder::new();
+    decoder.decode(&event(EventType::FormatDescriptionEvent, &binlog.format_description, 0, 0, true), &mut context)
+        .unwrap();
+    // 带有LOG_EVENT_IGNORABLE_F时同样不能按ignorable跳过
+    for kind in [EventType::TransactionPayloadEvent, EventType::PartialUpdateRowsEvent] {
+        let payload = event(kind, &[0; 16], binlog.end() as u32, event_flag::LOG_EVENT_IGNORABLE_F, true);
+        let e = decoder.decode(&payload, &mut context).unwrap_err();
+        assert_eq!(e.name(), "decode", "{}", e);
+    }
+
+    decoder.set_undecoded_rows_event_policy(UnsupportedEventPolicy::Warn);
+    let payload = event(EventType::TransactionPayloadEvent, &[0; 16], binlog.end() as u32, 0, true);
+    assert!(matches!(decoder.decode(&payload, &mut context).unwrap(), LogEvent::Unknown(_)));
+    assert_eq!(decoder.unsupported_counts().get(&(EventType::TransactionPayloadEvent as u8)), Some(&2));
+}
+
+#[test]
+fn recurring_failure_after_fake_rotate_gives_up() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(5));
+    // 每个连接在fake rotate和format description之后的第一个event就被截断, 没有进展
+    master.set_corrupt_every(Some(1));
+    master.start().unwrap();
+
+    let mut parser = MysqlEventParser::new(AuthenticationInfo::new("127.0.0.1", master.port(), "canal", "canal"));
+    parser.set_position(MOCK_BINLOG_FILE, 4);
+    let properties = parse_properties("master.reconnect.initial_delay=1ms\nmaster.reconnect.max_delay=1ms\n\
+                                       master.reconnect.max_retries=3").unwrap();
+    parser.apply_reconnect(&properties).unwrap();
+    let e = parser.start().unwrap_err();
+    assert!(e.message().contains("event truncated"), "{}", e);
+    assert!(e.is_recoverable(), "{:?}", e);
+    assert_eq!(master.connections(), 4);
+}
+
+#[test]
+fn purged_binlog_with_fail_strategy_delivers_incident() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(5));
+    master.start().unwrap();
+
+    let mut parser = MysqlEventParser::new(AuthenticationInfo::new("127.0.0.1", master.port(), "canal", "canal"));
+    // show binary logs中只有MOCK_BINLOG_FILE, 更早的文件已经被purge
+    parser.set_position("mysql-bin.000000", 4);
+    parser.set_purged_binlog_strategy(PurgedBinlogStrategy::Fail);
+    let incidents = Arc::new(Mutex::new(vec![]));
+    let received = incidents.clone();
+    parser.set_incident_sink(Box::new(CallbackSink::new(move |entry: &Entry| {
+        received.lock().unwrap().push(entry.clone());
+        Ok(())
+    })));
+    let e = parser.start().unwrap_err();
+    assert!(e.message().contains("is purged"), "{}", e);
+
+    // 连接时的Info entry之外只有一个Incident
+    let incidents: Vec<Entry> = incidents.lock().unwrap().iter()
+        .filter(|entry| entry.entry_type() == EntryType::Incident)
+        .cloned()
+        .collect();
+    assert_eq!(incidents.len(), 1);
+    assert_eq!(incidents[0].header().log_file_name(), "mysql-bin.000000");
+    assert!(incidents[0].message().unwrap_or("").contains(MOCK_BINLOG_FILE));
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use mysql_binlog_parse::command::event::{checksum, LogHeader, BINLOG_MAGIC};
+use mysql_binlog_parse::config::parse_properties;
+use mysql_binlog_parse::encryption::{decrypt_file, is_encrypted, EncryptedFileReader, FileCipher, KeyProvider,
+                                     StaticKeyProvider, ENCRYPTED_HEADER_LEN, KEY_LEN, TAG_LEN};
+use mysql_binlog_parse::instance::relay::RelayLogWriter;
+use mysql_binlog_parse::mock::binlog::MockBinlog;
+use mysql_binlog_parse::mock::MOCK_BINLOG_FILE;
+
+fn relay_directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mini-canal-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    path
+}
+
+// 写入fake rotate, format description以及指定的事务, 返回写入文件的原始内容(不包括BINLOG_MAGIC)
+fn write_relay(writer: &mut RelayLogWriter, binlog: &MockBinlog, transactions: &[u64], head: bool) -> Vec<u8> {
+    let mut events = vec![binlog.fake_rotate(4)];
+    if head {
+        events.push(binlog.format_description(None));
+    }
+    for index in transactions {
+        events.extend(binlog.transaction(*index));
+    }
+    let mut written = vec![];
+    for event in events {
+        let header = LogHeader::from_bytes(&event, checksum::BINLOG_CHECKSUM_ALG_CRC32).unwrap();
+        writer.write(&header, &event).unwrap();
+        if !header.is_artificial() {
+            written.extend_from_slice(&event);
+        }
+    }
+    writer.close().unwrap();
+    written
+}
+
+#[test]
+fn cipher_round_trip() {
+    let provider = StaticKeyProvider::new(1, [7u8; KEY_LEN]);
+    let mut cipher = FileCipher::create(&provider).unwrap();
+    let header = cipher.header();
+    assert_eq!(header.len(), ENCRYPTED_HEADER_LEN);
+    let records: Vec<Vec<u8>> = [b"first".as_slice(), b"second".as_slice()].iter()
+        .map(|plaintext| cipher.encrypt(plaintext).unwrap())
+        .collect();
+    assert_eq!(records[0].len(), 4 + 5 + TAG_LEN);
+
+    let content = [header.clone(), records.concat()].concat();
+    let mut reader = EncryptedFileReader::new(content.as_slice(), &provider).unwrap();
+    assert_eq!(reader.key_id(), 1);
+    assert_eq!(reader.next_record().unwrap(), Some(b"first".to_vec()));
+    assert_eq!(reader.next_record().unwrap(), Some(b"second".to_vec()));
+    assert_eq!(reader.next_record().unwrap(), None);
+
+    // 调换记录的顺序或者使用错误的密钥时解密失败
+    let swapped = [header.clone(), records[1].clone(), records[0].clone()].concat();
+    assert!(EncryptedFileReader::new(swapped.as_slice(), &provider).unwrap().next_record().is_err());
+    let mut wrong = StaticKeyProvider::new(1, [8u8; KEY_LEN]);
+    wrong.add_key(2, [7u8; KEY_LEN]);
+    assert!(EncryptedFileReader::new(content.as_slice(), &wrong).unwrap().next_record().is_err());
+}
+
+#[test]
+fn key_provider_from_properties() {
+    let properties = parse_properties(&format!("encryption.key.1={}\nencryption.key.2={}\n",
+                                               "00".repeat(KEY_LEN), "ff".repeat(KEY_LEN))).unwrap();
+    let provider = StaticKeyProvider::from_properties(&properties).unwrap();
+    assert_eq!(provider.current_key().unwrap(), (2, [0xffu8; KEY_LEN]));
+    assert_eq!(provider.key(1).unwrap(), [0u8; KEY_LEN]);
+    assert!(provider.key(3).is_err());
+
+    let properties = parse_properties(&format!("encryption.key.1={}\nencryption.key.current=1\n",
+                                               "00".repeat(KEY_LEN))).unwrap();
+    assert_eq!(StaticKeyProvider::from_properties(&properties).unwrap().current_key().unwrap().0, 1);
+    let properties = parse_properties("encryption.key.1=abc").unwrap();
+    assert!(StaticKeyProvider::from_properties(&properties).is_err());
+}
+
+#[test]
+fn encrypted_relay_decrypts_to_binlog() {
+    let directory = relay_directory("encrypted-relay");
+    let binlog = MockBinlog::new(1, "8.0.33-mock", true);
+    let mut writer = RelayLogWriter::new(&directory).unwrap();
+    writer.set_key_provider(Arc::new(StaticKeyProvider::new(1, [7u8; KEY_LEN])));
+    let mut expected = BINLOG_MAGIC.to_vec();
+    expected.extend(write_relay(&mut writer, &binlog, &[0, 1], true));
+
+    let path = directory.join(MOCK_BINLOG_FILE);
+    assert!(is_encrypted(&path));
+    let provider = StaticKeyProvider::new(1, [7u8; KEY_LEN]);
+    assert_eq!(decrypt_file(&path, &provider).unwrap(), expected);
+    assert_eq!(writer.position().position(), expected.len() as u64);
+
+    // 没有KeyProvider时不能续写加密的文件
+    let mut plain = RelayLogWriter::new(&directory).unwrap();
+    let rotate = binlog.fake_rotate(4);
+    let header = LogHeader::from_bytes(&rotate, checksum::BINLOG_CHECKSUM_ALG_CRC32).unwrap();
+    assert!(plain.write(&header, &rotate).is_err());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn rotated_key_keeps_existing_file_key() {
+    let directory = relay_directory("rotated-key");
+    let binlog = MockBinlog::new(1, "8.0.33-mock", true);
+    let mut writer = RelayLogWriter::new(&directory).unwrap();
+    writer.set_key_provider(Arc::new(StaticKeyProvider::new(1, [7u8; KEY_LEN])));
+    let mut expected = BINLOG_MAGIC.to_vec();
+    expected.extend(write_relay(&mut writer, &binlog, &[0], true));
+
+    // 切换到key 2之后续写, 已有的文件仍然使用文件头中的key 1
+    let mut rotated = StaticKeyProvider::new(1, [7u8; KEY_LEN]);
+    rotated.add_key(2, [9u8; KEY_LEN]);
+    rotated.set_current(2);
+    let mut writer = RelayLogWriter::new(&directory).unwrap();
+    writer.set_key_provider(Arc::new(rotated.clone()));
+    expected.extend(write_relay(&mut writer, &binlog, &[1], false));
+
+    let path = directory.join(MOCK_BINLOG_FILE);
+    let reader = EncryptedFileReader::new(fs::File::open(&path).unwrap(), &rotated).unwrap();
+    assert_eq!(reader.key_id(), 1);
+    assert_eq!(decrypt_file(&path, &rotated).unwrap(), expected);
+
+    // 新文件使用当前的key
+    let directory2 = relay_directory("rotated-key-new");
+    let mut writer = RelayLogWriter::new(&directory2).unwrap();
+    writer.set_key_provider(Arc::new(rotated.clone()));
+    write_relay(&mut writer, &binlog, &[0], true);
+    let reader = EncryptedFileReader::new(fs::File::open(directory2.join(MOCK_BINLOG_FILE)).unwrap(), &rotated).unwrap();
+    assert_eq!(reader.key_id(), 2);
+    fs::remove_dir_all(&directory).unwrap();
+    fs::remove_dir_all(&directory2).unwrap();
+}
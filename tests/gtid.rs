@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use mysql_binlog_parse::command::com::{BinlogDumpGtidCommand, Command, BINLOG_THROUGH_GTID};
+use mysql_binlog_parse::command::command_type::COM_BINLOG_DUMP_GTID;
+use mysql_binlog_parse::command::gtid::{parse_uuid, GtidSet, Interval};
+use mysql_binlog_parse::instance::backoff::Backoff;
+use mysql_binlog_parse::instance::position_manager::{FlushPolicy, MemoryPositionManager};
+use mysql_binlog_parse::instance::running::MysqlEventParser;
+use mysql_binlog_parse::instance::AuthenticationInfo;
+use mysql_binlog_parse::metrics::RateKind;
+use mysql_binlog_parse::mock::binlog::MockBinlog;
+use mysql_binlog_parse::mock::MockMaster;
+use mysql_binlog_parse::sink::callback::CallbackSink;
+
+const UUID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+const OTHER_UUID: &str = "8a94f357-aab4-11df-86ab-c80aa9429563";
+
+#[test]
+fn parse_and_display() {
+    let set = GtidSet::parse(" 3E11FA47-71CA-11E1-9E33-C80AA9429562:1-5:11-18,\n8a94f357-aab4-11df-86ab-c80aa9429563:7 ")
+        .unwrap();
+    let uuid_set = set.get(UUID).unwrap();
+    assert_eq!(uuid_set.intervals(), &vec![Interval::new(1, 6), Interval::new(11, 19)]);
+    assert_eq!(uuid_set.last_gno(), Some(18));
+    assert!(set.contains(&parse_uuid(UUID).unwrap(), 5));
+    assert!(!set.contains(&parse_uuid(UUID).unwrap(), 6));
+    assert_eq!(set.to_string(), format!("{}:1-5:11-18,{}:7", UUID, OTHER_UUID));
+    assert_eq!(set.to_string().parse::<GtidSet>().unwrap(), set);
+    assert!(GtidSet::parse("").unwrap().is_empty());
+
+    for invalid in [UUID.to_string(), format!("{}:0", UUID), format!("{}:5-3", UUID), "not-a-uuid:1".to_string(),
+                    format!("{}:1-18446744073709551615", UUID), format!("{}:18446744073709551615", UUID)] {
+        assert!(GtidSet::parse(&invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn merge_overlapping_and_adjacent_intervals() {
+    let mut set = GtidSet::parse(&format!("{}:1-5:11-18", UUID)).unwrap();
+    set.merge(&GtidSet::parse(&format!("{}:4-10,{}:1-3", UUID, OTHER_UUID)).unwrap());
+    assert_eq!(set.to_string(), format!("{}:1-18,{}:1-3", UUID, OTHER_UUID));
+
+    set.add(&format!("{}:4", OTHER_UUID)).unwrap();
+    set.add(&format!("{}:20", UUID)).unwrap();
+    assert_eq!(set.to_string(), format!("{}:1-18:20,{}:1-4", UUID, OTHER_UUID));
+    assert!(set.add("missing-gno").is_err());
+    assert!(set.add(&format!("{}:{}", UUID, u64::MAX)).is_err());
+    set.add(&format!("{}:{}", UUID, u64::MAX - 1)).unwrap();
+    assert_eq!(set.get(UUID).unwrap().last_gno(), Some(u64::MAX - 1));
+}
+
+#[test]
+fn encode_round_trip() {
+    let set = GtidSet::parse(&format!("{}:1-5:11-18,{}:7", UUID, OTHER_UUID)).unwrap();
+    let encoded = set.encode();
+    assert_eq!(encoded.len(), set.encoded_length());
+    // n_sids + (sid + n_intervals + 2个区间) + (sid + n_intervals + 1个区间)
+    assert_eq!(encoded.len(), 8 + (16 + 8 + 2 * 16) + (16 + 8 + 16));
+    assert_eq!(&encoded[..8], &2u64.to_le_bytes());
+    assert_eq!(&encoded[8..24], &parse_uuid(UUID).unwrap());
+    assert_eq!(GtidSet::decode(&encoded).unwrap(), set);
+    assert!(GtidSet::decode(&encoded[..encoded.len() - 1]).is_err());
+    assert_eq!(GtidSet::new().encode(), 0u64.to_le_bytes().to_vec());
+}
+
+#[test]
+fn binlog_dump_gtid_command() {
+    let set = GtidSet::parse(&format!("{}:1-100", UUID)).unwrap();
+    let command = BinlogDumpGtidCommand::builder(65535).gtid_set(set.clone()).build();
+    assert_eq!(command.flags() & BINLOG_THROUGH_GTID, BINLOG_THROUGH_GTID);
+    let bytes = command.encode();
+    assert_eq!(bytes[0], COM_BINLOG_DUMP_GTID);
+    assert_eq!(&bytes[3..7], &65535u32.to_le_bytes());
+    // 空的文件名, 位点4, 之后是gtid set的长度和数据
+    assert_eq!(&bytes[7..11], &0u32.to_le_bytes());
+    assert_eq!(&bytes[11..19], &4u64.to_le_bytes());
+    assert_eq!(&bytes[19..23], &(set.encoded_length() as u32).to_le_bytes());
+    assert_eq!(&bytes[23..], set.encode().as_slice());
+    assert_eq!(BinlogDumpGtidCommand::decode(&bytes).unwrap(), command);
+
+    // 没有gtid set时不带BINLOG_THROUGH_GTID和gtid set
+    let command = BinlogDumpGtidCommand::builder(1).binlog_file("mysql-bin.000001").binlog_position(120).build();
+    assert_eq!(command.flags() & BINLOG_THROUGH_GTID, 0);
+    let bytes = command.encode();
+    assert_eq!(bytes.len(), 1 + 2 + 4 + 4 + "mysql-bin.000001".len() + 8);
+    assert_eq!(BinlogDumpGtidCommand::decode(&bytes).unwrap(), command);
+}
+
+fn gtid_master(transactions: u64) -> MockMaster {
+    let mut binlog = MockBinlog::new(1, "8.0.33-mock", true);
+    binlog.set_server_uuid(Some(parse_uuid(UUID).unwrap()));
+    let mut master = MockMaster::new();
+    master.set_binlog(binlog);
+    master.set_transactions(Some(transactions));
+    master.start().unwrap();
+    master
+}
+
+fn parser(master: &MockMaster) -> MysqlEventParser {
+    let mut parser = MysqlEventParser::new(AuthenticationInfo::new("127.0.0.1", master.port(), "canal", "canal"));
+    let mut backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(10));
+    backoff.set_max_retries(Some(10));
+    parser.set_backoff(backoff);
+    parser
+}
+
+#[test]
+fn dump_from_gtid_set_skips_executed_transactions() {
+    let master = gtid_master(5);
+    let mut parser = parser(&master);
+    parser.set_gtid_set(&format!("{}:1-2", UUID)).unwrap();
+    parser.start().unwrap();
+    assert_eq!(parser.metrics().lock().unwrap().snapshot(RateKind::RowsEmitted).count, 3);
+    assert_eq!(parser.gtid_set().unwrap().to_string(), format!("{}:1-5", UUID));
+}
+
+#[test]
+fn resume_from_stored_gtid_set() {
+    let master = gtid_master(5);
+    let manager = MemoryPositionManager::new();
+    let mut parser = parser(&master);
+    parser.set_gtid_set(&format!("{}:1", UUID)).unwrap();
+    parser.set_entry_sink(Box::new(CallbackSink::new(|_| Ok(()))));
+    parser.set_position_manager(Box::new(manager.clone()), FlushPolicy::OnAck);
+    parser.start().unwrap();
+    let stored = manager.position().unwrap();
+    assert_eq!(stored.gtid_set().unwrap().to_string(), format!("{}:1-5", UUID));
+    assert_eq!(stored.position().server_id(), 1);
+
+    // 保存的gtid set优先于set_gtid_set, 所有事务都已经执行过
+    let mut parser = self::parser(&master);
+    parser.set_gtid_set(&format!("{}:1", UUID)).unwrap();
+    parser.set_entry_sink(Box::new(CallbackSink::new(|_| Ok(()))));
+    parser.set_position_manager(Box::new(manager.clone()), FlushPolicy::OnAck);
+    parser.start().unwrap();
+    assert_eq!(parser.metrics().lock().unwrap().snapshot(RateKind::RowsEmitted).count, 0);
+    assert_eq!(parser.gtid_set().unwrap().to_string(), format!("{}:1-5", UUID));
+}
@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use mysql_binlog_parse::instance::status::Instance;
+use mysql_binlog_parse::metrics::rate::TICK_INTERVAL;
+use mysql_binlog_parse::metrics::{RateKind, RateMeter};
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "expect {}, got {}", expected, actual);
+}
+
+#[test]
+fn instant_rate_covers_last_complete_tick() {
+    let start = Instant::now();
+    let mut meter = RateMeter::new(start);
+    meter.mark_at(50, start);
+    // 周期没有结束之前没有速率
+    let snapshot = meter.snapshot_at(start + Duration::from_secs(4));
+    assert_eq!(snapshot.count, 50);
+    assert_close(snapshot.instant, 0.0);
+
+    // 第一个周期直接作为EWMA的初始值
+    let snapshot = meter.snapshot_at(start + TICK_INTERVAL);
+    assert_close(snapshot.instant, 10.0);
+    assert_close(snapshot.one_minute, 10.0);
+    assert_close(snapshot.five_minute, 10.0);
+    assert_close(snapshot.fifteen_minute, 10.0);
+
+    // 空闲的周期速率为0, 计数保持不变
+    let snapshot = meter.snapshot_at(start + TICK_INTERVAL * 2);
+    assert_eq!(snapshot.count, 50);
+    assert_close(snapshot.instant, 0.0);
+}
+
+#[test]
+fn ewma_converges_to_steady_rate() {
+    let start = Instant::now();
+    let mut meter = RateMeter::new(start);
+    meter.mark_at(50, start);
+    let mut now = start + TICK_INTERVAL;
+    meter.snapshot_at(now);
+
+    // 从10/s变为20/s, 经过一个窗口的时间后还剩下1/e的差距
+    let mut tick = |meter: &mut RateMeter, ticks: u32| {
+        for _ in 0..ticks {
+            meter.mark_at(100, now);
+            now += TICK_INTERVAL;
+        }
+        meter.snapshot_at(now)
+    };
+    let snapshot = tick(&mut meter, 12);
+    assert_close(snapshot.instant, 20.0);
+    assert_close(snapshot.one_minute, 20.0 - 10.0 * (-1.0f64).exp());
+    assert_close(snapshot.five_minute, 20.0 - 10.0 * (-0.2f64).exp());
+    assert_close(snapshot.fifteen_minute, 20.0 - 10.0 * (-1.0f64 / 15.0).exp());
+
+    let snapshot = tick(&mut meter, 12 * 60);
+    assert_close(snapshot.fifteen_minute, 20.0 - 10.0 * (-61.0f64 / 15.0).exp());
+    assert!((snapshot.one_minute - 20.0).abs() < 1e-9);
+    assert!(snapshot.one_minute > snapshot.five_minute && snapshot.five_minute > snapshot.fifteen_minute);
+    assert_eq!(snapshot.count, 50 + 100 * 12 * 61);
+}
+
+#[test]
+fn status_json_reports_every_rate() {
+    let instance = Instance::new("example");
+    instance.metrics().lock().unwrap().mark(RateKind::RowsEmitted, 3);
+    let json = instance.status_json();
+    assert!(json.contains("\"rows_emitted\":{\"total\":3,\"instant\":0,\"rate_1m\":0,\"rate_5m\":0,\"rate_15m\":0}"),
+            "{}", json);
+}
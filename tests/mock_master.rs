@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mysql_binlog_parse::instance::backoff::Backoff;
+use mysql_binlog_parse::instance::ddl::ColumnDefinition;
+use mysql_binlog_parse::instance::gtid_gap::GtidGapPolicy;
+use mysql_binlog_parse::instance::running::MysqlEventParser;
+use mysql_binlog_parse::instance::schema_history::{SchemaHistory, TableDefinition};
+use mysql_binlog_parse::instance::{AuthenticationInfo, EntryPosition};
+use mysql_binlog_parse::metrics::RateKind;
+use mysql_binlog_parse::mock::binlog::{MockBinlog, MOCK_SCHEMA, MOCK_TABLE};
+use mysql_binlog_parse::mock::{MockMaster, MOCK_BINLOG_FILE};
+use mysql_binlog_parse::protocol::EntryType;
+use mysql_binlog_parse::sink::callback::CallbackSink;
+
+fn parser(master: &MockMaster) -> MysqlEventParser {
+    let mut parser = MysqlEventParser::new(AuthenticationInfo::new("127.0.0.1", master.port(), "canal", "canal"));
+    parser.set_position(MOCK_BINLOG_FILE, 4);
+    let mut backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(10));
+    backoff.set_max_retries(Some(100));
+    parser.set_backoff(backoff);
+    parser
+}
+
+#[test]
+fn dump_all_transactions() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(20));
+    master.start().unwrap();
+
+    let mut parser = parser(&master);
+    parser.start().unwrap();
+    assert_eq!(parser.position().unwrap().position(), master.end_position().unwrap());
+    assert_eq!(parser.metrics().lock().unwrap().snapshot(RateKind::RowsEmitted).count, 20);
+    assert_eq!(master.connections(), 1);
+}
+
+#[test]
+fn recover_from_disconnects() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(20));
+    // 每个连接发送10个event, 第三个事务发送到一半时断开
+    master.set_disconnect_after(Some(10));
+    master.start().unwrap();
+
+    let mut parser = parser(&master);
+    parser.start().unwrap();
+    assert_eq!(parser.position().unwrap().position(), master.end_position().unwrap());
+    assert!(master.disconnects() >= 9);
+}
+
+#[test]
+fn recover_from_corrupted_events() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(20));
+    master.set_corrupt_every(Some(7));
+    master.start().unwrap();
+
+    // 同一个事务可能连续多次被破坏, 这些重试没有进展, 会累计重试次数
+    let mut parser = parser(&master);
+    let mut backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(1));
+    backoff.set_max_retries(None);
+    parser.set_backoff(backoff);
+    parser.start().unwrap();
+    assert_eq!(parser.position().unwrap().position(), master.end_position().unwrap());
+    assert!(master.corruptions() > 0);
+}
+
+#[test]
+fn reject_position_inside_transaction() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(1));
+    master.start().unwrap();
+
+    let mut parser = parser(&master);
+    parser.set_position(MOCK_BINLOG_FILE, master.binlog().transaction_position(0) + 1);
+    parser.set_backoff(Backoff::new(Duration::from_millis(1), Duration::from_millis(1)));
+    let e = parser.start().unwrap_err();
+    assert!(e.message().contains("1236"), "{}", e);
+    assert!(!e.is_recoverable(), "{:?}", e);
+    // 无法恢复的错误不重连, 第二个连接用于检查binlog是否被purge
+    assert_eq!(master.connections(), 2);
+}
+
+#[test]
+fn schema_history_names_minimal_table_map_columns() {
+    let mut master = MockMaster::new();
+    master.set_transactions(Some(3));
+    master.start().unwrap();
+
+    let mut id = ColumnDefinition::new("id", "int");
+    id.set_primary_key(true);
+    let mut history = SchemaHistory::new();
+    history.put(&EntryPosition::new(MOCK_BINLOG_FILE, 4), MOCK_SCHEMA, MOCK_TABLE,
+                Some(TableDefinition::new(MOCK_SCHEMA, MOCK_TABLE, vec![id])));
+    let columns = Arc::new(Mutex::new(vec![]));
+    let received = columns.clone();
+    let mut parser = parser(&master);
+    parser.set_schema_history(Some(Arc::new(Mutex::new(history))));
+    parser.set_entry_sink(Box::new(CallbackSink::new(move |entry| {
+        for row_data in entry.row_change().map(|row_change| row_change.row_datas().as_slice()).unwrap_or_default() {
+            let mut received = received.lock().unwrap();
+            received.extend(row_data.after_columns().iter().map(|column| (column.name().to_string(), column.is_key())));
+        }
+        Ok(())
+    })));
+    parser.start().unwrap();
+    assert_eq!(*columns.lock().unwrap(), vec![("id".to_string(), true); 3]);
+}
+
+// 第5个事务之后丢失了gno 6-8
+fn gtid_gap_master() -> MockMaster {
+    let mut binlog = MockBinlog::new(1, "8.0.33-mock", true);
+    binlog.set_server_uuid(Some([0x11; 16]));
+    binlog.set_gtid_gap(5, 3);
+    let mut master = MockMaster::new();
+    master.set_binlog(binlog);
+    master.set_transactions(Some(10));
+    master.start().unwrap();
+    master
+}
+
+#[test]
+fn gtid_gap_policies() {
+    for policy in [GtidGapPolicy::Off, GtidGapPolicy::Alert, GtidGapPolicy::Halt] {
+        let master = gtid_gap_master();
+        let incidents = Arc::new(Mutex::new(vec![]));
+        let received = incidents.clone();
+        let mut parser = parser(&master);
+        parser.set_gtid_gap_policy(policy);
+        parser.set_incident_sink(Box::new(CallbackSink::new(move |entry| {
+            if entry.entry_type() == EntryType::Incident {
+                received.lock().unwrap().push(entry.message().unwrap_or_default().to_string());
+            }
+            Ok(())
+        })));
+        let result = parser.start();
+        let incidents = incidents.lock().unwrap().clone();
+        let rows = parser.metrics().lock().unwrap().snapshot(RateKind::RowsEmitted).count;
+        let missing = parser.metrics().lock().unwrap().snapshot(RateKind::GtidGaps).count;
+        let message = "gtid gap detected on 11111111-1111-1111-1111-111111111111: transactions 6-8 are missing, received 9";
+        match policy {
+            GtidGapPolicy::Off => {
+                result.unwrap();
+                assert!(incidents.is_empty(), "{:?}", incidents);
+                assert_eq!((rows, missing, parser.gtid_gaps().gaps()), (10, 0, 0));
+            }
+            GtidGapPolicy::Alert => {
+                // 投递Incident之后继续dump
+                result.unwrap();
+                assert_eq!(incidents.len(), 1, "{:?}", incidents);
+                assert!(incidents[0].starts_with(message) && incidents[0].ends_with("policy alert"), "{}", incidents[0]);
+                assert_eq!((rows, missing, parser.gtid_gaps().gaps()), (10, 3, 1));
+                assert_eq!(parser.position().unwrap().position(), master.end_position().unwrap());
+            }
+            GtidGapPolicy::Halt => {
+                // 停在跳号的事务之前, 不再重连
+                let e = result.unwrap_err();
+                assert!(e.message().starts_with(message), "{}", e);
+                assert_eq!(incidents.len(), 1, "{:?}", incidents);
+                assert!(incidents[0].ends_with("policy halt"), "{}", incidents[0]);
+                assert_eq!((rows, missing), (5, 3));
+                assert_eq!(parser.position().unwrap().position(), master.binlog().transaction_position(5));
+                assert_eq!(master.connections(), 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use mysql_binlog_parse::command::gtid::GtidSet;
+use mysql_binlog_parse::config::parse_properties;
+use mysql_binlog_parse::instance::position_manager::{FilePositionManager, FlushPolicy, LogPosition,
+                                                     MemoryPositionManager, PositionManager, PositionSaver};
+use mysql_binlog_parse::instance::EntryPosition;
+use mysql_binlog_parse::protocol::{Entry, EntryType, Header};
+
+const FILE: &str = "mysql-bin.000001";
+const UUID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+
+fn position_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mini-canal-{}-{}.position", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn entry(offset: u64, entry_type: EntryType, gtid: &str) -> Entry {
+    let mut header = Header::new(FILE, offset);
+    header.set_event_length(10);
+    header.set_server_id(7);
+    header.set_gtid(gtid);
+    Entry::new(header, entry_type)
+}
+
+// 一个事务: begin, row, end, 结束之后的位点为offset + 30
+fn transaction(offset: u64, gno: u64) -> Vec<Entry> {
+    let gtid = format!("{}:{}", UUID, gno);
+    vec![entry(offset, EntryType::TransactionBegin, &gtid), entry(offset + 10, EntryType::RowData, &gtid),
+         entry(offset + 20, EntryType::TransactionEnd, &gtid)]
+}
+
+#[test]
+fn file_position_manager_round_trip() {
+    let path = position_file("round-trip");
+    let mut manager = FilePositionManager::new(&path);
+    assert_eq!(manager.load().unwrap(), None);
+
+    let mut position = EntryPosition::new(FILE, 1024);
+    position.set_timestamp(1_700_000_000_000);
+    position.set_server_id(3);
+    let saved = LogPosition::new(position, Some(GtidSet::parse(&format!("{}:1-20", UUID)).unwrap()));
+    manager.persist(&saved).unwrap();
+    assert_eq!(FilePositionManager::new(&path).load().unwrap(), Some(saved));
+    assert!(fs::read_to_string(&path).unwrap().contains(&format!("gtid_set={}:1-20", UUID)));
+
+    // 没有gtid set时不写gtid_set
+    let saved = LogPosition::new(EntryPosition::new(FILE, 2048), None);
+    manager.persist(&saved).unwrap();
+    assert_eq!(manager.load().unwrap(), Some(saved));
+    assert!(!path.with_extension("tmp").exists());
+
+    // 旧版本pipeline写入的格式
+    fs::write(&path, format!("{}:4096\n", FILE)).unwrap();
+    assert_eq!(manager.load().unwrap(), Some(LogPosition::new(EntryPosition::new(FILE, 4096), None)));
+
+    fs::write(&path, "position=12\n").unwrap();
+    assert!(manager.load().is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn memory_position_manager_is_shared_by_clones() {
+    let manager = MemoryPositionManager::new();
+    let mut other = manager.clone();
+    let saved = LogPosition::new(EntryPosition::new(FILE, 4), None);
+    other.persist(&saved).unwrap();
+    assert_eq!(manager.position(), Some(saved));
+}
+
+#[test]
+fn saver_on_ack_persists_each_transaction() {
+    let manager = MemoryPositionManager::new();
+    let mut saver = PositionSaver::new(Box::new(manager.clone()), FlushPolicy::OnAck);
+    saver.start_dump(Some(GtidSet::new()));
+    let entries = transaction(100, 1);
+    // 事务中间不保存
+    assert!(!saver.ack(&entries[0]));
+    assert!(!saver.ack(&entries[1]));
+    assert!(saver.ack(&entries[2]));
+    assert!(saver.is_dirty());
+    saver.persist().unwrap();
+    assert!(!saver.is_dirty());
+
+    let stored = manager.position().unwrap();
+    assert_eq!(stored.position().position(), 130);
+    assert_eq!(stored.position().server_id(), 7);
+    assert_eq!(stored.gtid_set().unwrap().to_string(), format!("{}:1", UUID));
+
+    // 与上次保存的相同时跳过
+    saver.persist().unwrap();
+    assert_eq!(saver.flushes(), 1);
+}
+
+#[test]
+fn saver_periodic_persists_after_interval() {
+    let manager = MemoryPositionManager::new();
+    let mut saver = PositionSaver::new(Box::new(manager.clone()), FlushPolicy::Periodic(Duration::from_millis(50)));
+    let acked: Vec<bool> = transaction(100, 1).iter().map(|entry| saver.ack(entry)).collect();
+    assert_eq!(acked, vec![false, false, false]);
+    assert!(saver.is_dirty());
+    assert_eq!(manager.position(), None);
+
+    std::thread::sleep(Duration::from_millis(60));
+    let acked: Vec<bool> = transaction(130, 2).iter().map(|entry| saver.ack(entry)).collect();
+    assert_eq!(acked, vec![false, false, true]);
+    saver.persist().unwrap();
+    assert_eq!(manager.position().unwrap().position().position(), 160);
+    // 没有按gtid set dump时不保存gtid set
+    assert_eq!(manager.position().unwrap().gtid_set(), None);
+}
+
+#[test]
+fn saver_load_continues_stored_gtid_set() {
+    let mut manager = MemoryPositionManager::new();
+    let gtid_set = GtidSet::parse(&format!("{}:1-5", UUID)).unwrap();
+    manager.persist(&LogPosition::new(EntryPosition::new(FILE, 100), Some(gtid_set))).unwrap();
+
+    let mut saver = PositionSaver::new(Box::new(manager.clone()), FlushPolicy::OnAck);
+    assert!(saver.load().unwrap().is_some());
+    assert!(!saver.is_dirty());
+    saver.start_dump(None);
+    for entry in transaction(100, 6) {
+        saver.ack(&entry);
+    }
+    saver.persist().unwrap();
+    assert_eq!(manager.position().unwrap().gtid_set().unwrap().to_string(), format!("{}:1-6", UUID));
+}
+
+#[test]
+fn flush_policy_from_properties() {
+    let policy = |text: &str| FlushPolicy::from_properties(&parse_properties(text).unwrap());
+    assert_eq!(policy("").unwrap(), FlushPolicy::default());
+    assert_eq!(policy("master.position_manager.flush_interval=0s").unwrap(), FlushPolicy::OnAck);
+    assert_eq!(policy("master.position_manager.flush_interval=200ms").unwrap(),
+               FlushPolicy::Periodic(Duration::from_millis(200)));
+    assert!(policy("master.position_manager.flush_interval=soon").is_err());
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use mysql_binlog_parse::instance::backoff::Backoff;
+use mysql_binlog_parse::instance::running::MysqlEventParser;
+use mysql_binlog_parse::instance::AuthenticationInfo;
+use mysql_binlog_parse::metrics::RateKind;
+use mysql_binlog_parse::mock::fixture::{Direction, Record};
+use mysql_binlog_parse::mock::{Fixture, ReplayServer};
+
+// 录制时使用的账号, 握手响应中的scramble由它和fixture中的seed决定
+const USERNAME: &str = "canal";
+const PASSWORD: &str = "canal";
+
+fn fixtures() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut files: Vec<PathBuf> = fs::read_dir(directory).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "fixture"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn parser(server: &ReplayServer, fixture: &Fixture) -> MysqlEventParser {
+    let mut parser = MysqlEventParser::new(AuthenticationInfo::new("127.0.0.1", server.port(), USERNAME, PASSWORD));
+    parser.set_position(fixture.metadata("binlog_file").unwrap(), fixture.metadata("binlog_position").unwrap().parse().unwrap());
+    let mut backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(10));
+    backoff.set_max_retries(Some(0));
+    parser.set_backoff(backoff);
+    parser
+}
+
+#[test]
+fn replay_recorded_dumps() {
+    let files = fixtures();
+    assert!(!files.is_empty());
+    for path in files {
+        let fixture = Fixture::load(&path).unwrap();
+        let mut server = ReplayServer::new(fixture.clone());
+        server.start().unwrap();
+
+        let mut parser = parser(&server, &fixture);
+        parser.start().unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(server.mismatches().is_empty(), "{}: {:?}", path.display(), server.mismatches());
+        let end_position: u64 = fixture.metadata("end_position").unwrap().parse().unwrap();
+        assert_eq!(parser.position().unwrap().position(), end_position, "{}", path.display());
+        let rows: u64 = fixture.metadata("rows_emitted").unwrap().parse().unwrap();
+        assert_eq!(parser.metrics().lock().unwrap().snapshot(RateKind::RowsEmitted).count, rows, "{}", path.display());
+    }
+}
+
+#[test]
+fn detect_changed_client_command() {
+    let path = fixtures().into_iter().next().unwrap();
+    let recorded = Fixture::load(&path).unwrap();
+    // 把第一个COM_QUERY换成COM_PING, client实际发送的命令与录制的不一致
+    let mut fixture = Fixture::new();
+    let mut replaced = false;
+    for record in recorded.records() {
+        let mut packet = record.packet().clone();
+        if !replaced && record.direction() == Direction::Client && record.sequence() == Some(0) {
+            packet[4] = 0x0e;
+            replaced = true;
+        }
+        fixture.push(Record::new(record.direction(), packet));
+    }
+    let mut server = ReplayServer::new(fixture);
+    server.start().unwrap();
+
+    let mut parser = parser(&server, &recorded);
+    assert!(parser.start().is_err());
+    let mismatches = server.mismatches();
+    assert_eq!(mismatches.len(), 1);
+    assert!(mismatches[0].contains("mismatch"), "{}", mismatches[0]);
+}
+
+#[test]
+fn fixture_text_round_trip() {
+    let path = fixtures().into_iter().next().unwrap();
+    let fixture = Fixture::load(&path).unwrap();
+    let parsed = Fixture::parse(&fixture.to_text()).unwrap();
+    assert_eq!(parsed.records(), fixture.records());
+    assert_eq!(parsed.metadata("server_version"), fixture.metadata("server_version"));
+    assert_eq!(fixture.records().first().unwrap().direction(), Direction::Server);
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::{event, format_description_body, query_body};
+use mysql_binlog_parse::command::event::query::status_var;
+use mysql_binlog_parse::command::event::{EventType, LogContext, LogDecoder, LogEvent, QueryLogEvent};
+use mysql_binlog_parse::instance::ddl::SqlMode;
+use mysql_binlog_parse::instance::schema_history::SchemaHistory;
+use mysql_binlog_parse::instance::EntryPosition;
+
+fn format_description(server_version: &str, with_checksum: bool) -> Vec<u8> {
+    event(EventType::FormatDescriptionEvent, &format_description_body(server_version, with_checksum), 0, 0, with_checksum)
+}
+
+fn query_event(db: &str, status_vars: &[u8], query: &[u8], with_checksum: bool) -> Vec<u8> {
+    event(EventType::QueryEvent, &query_body(db, status_vars, query), 4096, 0, with_checksum)
+}
+
+fn charset_var(client_charset: u16) -> Vec<u8> {
+    let mut vars = vec![status_var::Q_CHARSET_CODE];
+    vars.extend_from_slice(&client_charset.to_le_bytes());
+    vars.extend_from_slice(&client_charset.to_le_bytes());
+    vars.extend_from_slice(&8u16.to_le_bytes());
+    vars
+}
+
+fn decode_query(server_version: &str, with_checksum: bool, query: &[u8]) -> QueryLogEvent {
+    let mut context = LogContext::new();
+    let mut decoder = LogDecoder::new();
+    decoder.decode(&format_description(server_version, with_checksum), &mut context).unwrap();
+    match decoder.decode(query, &mut context).unwrap() {
+        LogEvent::Query(event) => event,
+        other => panic!("expect query event, got {:?}", other),
+    }
+}
+
+#[test]
+fn ddl_with_full_status_vars() {
+    let mut vars = vec![status_var::Q_FLAGS2_CODE];
+    vars.extend_from_slice(&0u32.to_le_bytes());
+    vars.push(status_var::Q_SQL_MODE_CODE);
+    vars.extend_from_slice(&0x1234u64.to_le_bytes());
+    vars.push(status_var::Q_CATALOG_NZ_CODE);
+    vars.push(3);
+    vars.extend_from_slice(b"std");
+    vars.extend(charset_var(45));
+    vars.push(status_var::Q_UPDATED_DB_NAMES);
+    vars.push(1);
+    vars.extend_from_slice(b"shop\0");
+    vars.push(status_var::Q_DDL_LOGGED_WITH_XID);
+    vars.extend_from_slice(&99u64.to_le_bytes());
+
+    let sql = "CREATE TABLE `orders` (`id` bigint NOT NULL, PRIMARY KEY (`id`))";
+    let event = decode_query("8.0.33", true, &query_event("shop", &vars, sql.as_bytes(), true));
+    assert_eq!(event.query(), sql);
+    assert_eq!(event.db_name(), "shop");
+    assert_eq!(event.thread_id(), 77);
+    assert_eq!(event.exec_time(), 3);
+    assert_eq!(event.sql_mode(), 0x1234);
+    assert_eq!(event.catalog(), "std");
+    assert_eq!(event.client_charset(), Some(45));
+    assert_eq!(event.updated_db_names(), &vec!["shop".to_string()]);
+    assert_eq!(event.ddl_xid(), Some(99));
+}
+
+#[test]
+fn ddl_without_checksum() {
+    let sql = "ALTER TABLE t ADD COLUMN c int";
+    let event = decode_query("5.5.62", false, &query_event("test", &[], sql.as_bytes(), false));
+    assert_eq!(event.query(), sql);
+    assert_eq!(event.db_name(), "test");
+    assert_eq!(event.client_charset(), None);
+}
+
+#[test]
+fn multi_byte_utf8mb4_statement() {
+    let sql = "INSERT INTO `用户` VALUES (1, '中文😀')";
+    let event = decode_query("8.0.33", true, &query_event("库", &charset_var(45), sql.as_bytes(), true));
+    assert_eq!(event.query(), sql);
+    assert_eq!(event.db_name(), "库");
+}
+
+#[test]
+fn latin1_statement_is_decoded_with_client_charset() {
+    let query = b"UPDATE t SET name = 'caf\xe9 \x80'";
+    let event = decode_query("8.0.33", true, &query_event("test", &charset_var(8), query, true));
+    assert_eq!(event.query(), "UPDATE t SET name = 'café €'");
+}
+
+#[test]
+fn unknown_status_var_does_not_lose_query() {
+    let mut vars = charset_var(33);
+    vars.extend_from_slice(&[200, 1, 2, 3]);
+    let event = decode_query("8.0.33", true, &query_event("test", &vars, b"DROP TABLE t", true));
+    assert_eq!(event.query(), "DROP TABLE t");
+    assert_eq!(event.client_charset(), Some(33));
+}
+
+#[test]
+fn empty_db_and_begin() {
+    let event = decode_query("8.0.33", true, &query_event("", &[], b"BEGIN", true));
+    assert_eq!(event.query(), "BEGIN");
+    assert_eq!(event.db_name(), "");
+}
+
+#[test]
+fn schema_history_follows_ddl_by_position() {
+    let position = |offset: u64| EntryPosition::new("mysql-bin.000001", offset);
+    let sql_mode = SqlMode::default();
+    let mut history = SchemaHistory::new();
+    assert!(history.apply(&position(100), "test", "CREATE TABLE t (id int unsigned PRIMARY KEY, a int)", sql_mode));
+    assert!(history.apply(&position(200), "test", "ALTER TABLE t ADD COLUMN b varchar(10) AFTER id, DROP INDEX i", sql_mode));
+    assert!(history.apply(&position(300), "test", "RENAME TABLE t TO u, u TO v", sql_mode));
+    assert!(!history.apply(&position(400), "test", "INSERT INTO v VALUES (1, 'b', 2)", sql_mode));
+    // 重连之后重放同一位点的DDL不产生新的版本
+    assert!(history.apply(&position(200), "test", "ALTER TABLE t ADD COLUMN b varchar(10) AFTER id, DROP INDEX i", sql_mode));
+
+    let columns = |table: &str, offset: u64| history.find("test", table, &position(offset))
+        .map(|definition| definition.columns().iter().map(|column| column.name().to_string()).collect::<Vec<_>>());
+    assert_eq!(columns("t", 50), None);
+    assert_eq!(columns("t", 150), Some(vec!["id".to_string(), "a".to_string()]));
+    assert_eq!(columns("t", 250), Some(vec!["id".to_string(), "b".to_string(), "a".to_string()]));
+    assert_eq!(columns("t", 350), None);
+    assert_eq!(columns("u", 350), None);
+    assert_eq!(columns("v", 350).map(|names| names.len()), Some(3));
+    assert_eq!(history.versions("test", "t"), 3);
+    assert_eq!(history.names(), vec!["test.v"]);
+    let id = &history.find("test", "v", &position(350)).unwrap().columns()[0];
+    assert!(id.unsigned() && id.primary_key() && !id.nullable());
+}
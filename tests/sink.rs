@@ -0,0 +1,490 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mysql_binlog_parse::config::{ConfigChange, Properties};
+use mysql_binlog_parse::filter::RegexFilter;
+use mysql_binlog_parse::protocol::{Column, Entry, EntryType, EventType, Header, RowChange, RowData};
+use mysql_binlog_parse::sink::apply::{sql_applier_factory, SqlApplier, SqlExecutor};
+use mysql_binlog_parse::sink::audit::{AuditSink, ViolationKind};
+use mysql_binlog_parse::sink::callback::CallbackSink;
+use mysql_binlog_parse::sink::dedup::DedupSink;
+use mysql_binlog_parse::sink::dispatcher::DestinationDispatcher;
+use mysql_binlog_parse::sink::mq::avro::ENTRY_SCHEMA;
+use mysql_binlog_parse::sink::mq::{AvroSerializer, EntrySerializer, Message, MessageProducer, MqSink, ProtobufSerializer};
+use mysql_binlog_parse::sink::parallel::{LaneMode, ParallelApplySink, RowApplier};
+use mysql_binlog_parse::sink::replay::{ReplayCache, ReplaySink};
+use mysql_binlog_parse::sink::router::RouteSink;
+use mysql_binlog_parse::sink::size_limit::{entry_size, OversizePolicy, SizeLimitSink};
+use mysql_binlog_parse::sink::EventSink;
+use mysql_binlog_parse::verify::dialect::{SqlDialect, SqlTarget};
+
+const FILE: &str = "mysql-bin.000001";
+
+fn header(offset: u64, table: &str) -> Header {
+    let mut header = Header::new(FILE, offset);
+    header.set_event_length(10);
+    header.set_schema_name("db");
+    header.set_table_name(table);
+    header
+}
+
+fn row(offset: u64, table: &str, id: &str) -> Entry {
+    rows(offset, table, &[id])
+}
+
+fn rows(offset: u64, table: &str, ids: &[&str]) -> Entry {
+    let mut row_change = RowChange::new(EventType::Insert);
+    for id in ids {
+        let mut column = Column::new(0, "id");
+        column.set_value(id);
+        column.set_is_key(true);
+        row_change.add_row_data(RowData::new(vec![], vec![column]));
+    }
+    let mut header = header(offset, table);
+    header.set_event_type(EventType::Insert);
+    Entry::row_data(header, row_change)
+}
+
+fn marker(offset: u64, entry_type: EntryType) -> Entry {
+    Entry::new(header(offset, ""), entry_type)
+}
+
+// 收到的entry的位点
+fn recording_sink() -> (Box<dyn EventSink>, Arc<Mutex<Vec<u64>>>) {
+    let received = Arc::new(Mutex::new(vec![]));
+    let offsets = received.clone();
+    let sink = CallbackSink::new(move |entry: &Entry| {
+        offsets.lock().unwrap().push(entry.header().log_file_offset());
+        Ok(())
+    });
+    (Box::new(sink), received)
+}
+
+#[test]
+fn route_commits_only_at_transaction_end() {
+    let mut sink = RouteSink::new();
+    let (orders, received) = recording_sink();
+    sink.add_route("orders", "db\\.orders", orders, 2).unwrap();
+
+    sink.on_event(&marker(100, EntryType::TransactionBegin)).unwrap();
+    sink.on_event(&row(110, "orders", "1")).unwrap();
+    // 攒满一批被确认, 但事务还没有结束, 不能从rows event重新开始
+    sink.on_event(&row(120, "orders", "2")).unwrap();
+    assert_eq!(*received.lock().unwrap(), vec![110, 120]);
+    assert_eq!(sink.committed_position(), None);
+
+    sink.on_event(&marker(130, EntryType::TransactionEnd)).unwrap();
+    assert_eq!(sink.committed_position().map(|position| position.position()), Some(140));
+
+    // 下一个事务的rows还没有确认, 位点停在上一个事务结束
+    sink.on_event(&marker(140, EntryType::TransactionBegin)).unwrap();
+    sink.on_event(&row(150, "orders", "3")).unwrap();
+    sink.on_event(&marker(160, EntryType::TransactionEnd)).unwrap();
+    assert_eq!(sink.committed_position().map(|position| position.position()), Some(140));
+    sink.flush().unwrap();
+    assert_eq!(sink.committed_position().map(|position| position.position()), Some(170));
+}
+
+fn audit(entries: &[Entry]) -> Vec<(ViolationKind, u64)> {
+    let mut sink = AuditSink::new();
+    for entry in entries {
+        sink.on_event(entry).unwrap();
+    }
+    sink.violations().iter().map(|violation| (violation.kind().clone(), violation.position().position())).collect()
+}
+
+#[test]
+fn audit_accepts_ordered_transactions() {
+    let (inner, received) = recording_sink();
+    let mut sink = AuditSink::wrap(inner);
+    for entry in [marker(100, EntryType::TransactionBegin), rows(110, "t", &["1", "2"]),
+                  marker(120, EntryType::TransactionEnd), marker(130, EntryType::TransactionBegin),
+                  row(140, "t", "1"), marker(150, EntryType::TransactionEnd)] {
+        sink.on_event(&entry).unwrap();
+    }
+    assert!(sink.is_clean());
+    // 校验后继续投递给下游
+    assert_eq!(received.lock().unwrap().len(), 6);
+}
+
+#[test]
+fn audit_reports_position_regression() {
+    let violations = audit(&[marker(100, EntryType::TransactionBegin), row(110, "t", "1"),
+                             marker(120, EntryType::TransactionEnd), marker(90, EntryType::TransactionBegin),
+                             marker(130, EntryType::TransactionEnd)]);
+    assert_eq!(violations, vec![(ViolationKind::PositionRegression, 90)]);
+}
+
+#[test]
+fn audit_reports_unbalanced_transactions() {
+    let violations = audit(&[marker(100, EntryType::TransactionBegin), row(110, "t", "1"),
+                             marker(120, EntryType::TransactionBegin), marker(130, EntryType::TransactionEnd),
+                             marker(140, EntryType::TransactionEnd), row(150, "t", "2")]);
+    assert_eq!(violations, vec![(ViolationKind::MissingTransactionEnd, 120),
+                                (ViolationKind::MissingTransactionBegin, 140),
+                                (ViolationKind::RowDataOutsideTransaction, 150)]);
+}
+
+#[test]
+fn audit_reports_duplicate_primary_key() {
+    let violations = audit(&[marker(100, EntryType::TransactionBegin), rows(110, "t", &["1", "2"]),
+                             row(120, "other", "1"), row(130, "t", "2"), marker(140, EntryType::TransactionEnd)]);
+    assert_eq!(violations, vec![(ViolationKind::DuplicatePrimaryKey, 130)]);
+
+    // 超过max_violations后只打印不保存
+    let mut sink = AuditSink::new();
+    sink.set_max_violations(1);
+    for entry in [marker(100, EntryType::TransactionBegin), row(110, "t", "1"), row(120, "t", "1"),
+                  row(130, "t", "1"), marker(140, EntryType::TransactionEnd)] {
+        sink.on_event(&entry).unwrap();
+    }
+    assert_eq!(sink.violations().len(), 1);
+}
+
+#[test]
+fn dedup_keeps_split_parts() {
+    let received = Arc::new(Mutex::new(vec![]));
+    let parts = received.clone();
+    let recorder = CallbackSink::new(move |entry: &Entry| {
+        let ids: Vec<String> = entry.row_change().unwrap().row_datas().iter()
+            .map(|row_data| row_data.after_columns()[0].value().to_string())
+            .collect();
+        parts.lock().unwrap().push((entry.header().part(), ids));
+        Ok(())
+    });
+    let dedup = DedupSink::new(Box::new(recorder), 16);
+    // 每个部分只能放下一行
+    let max_entry_size = entry_size(&row(110, "orders", "1"));
+    let mut sink = SizeLimitSink::new(Box::new(dedup), max_entry_size, OversizePolicy::Split);
+
+    let entry = rows(110, "orders", &["1", "2", "3"]);
+    sink.on_event(&entry).unwrap();
+    let expected = vec![
+        (Some(0), vec!["1".to_string()]),
+        (Some(1), vec!["2".to_string()]),
+        (Some(2), vec!["3".to_string()]),
+    ];
+    assert_eq!(*received.lock().unwrap(), expected);
+
+    // 重新dump后再次拆分, 各部分都是重复的
+    sink.on_event(&entry).unwrap();
+    assert_eq!(*received.lock().unwrap(), expected);
+}
+
+// payload为逗号分隔的位点
+struct OffsetSerializer;
+
+impl EntrySerializer for OffsetSerializer {
+    fn serialize(&self, entries: &[Entry]) -> Result<Vec<u8>, String> {
+        let offsets: Vec<String> = entries.iter().map(|entry| entry.header().log_file_offset().to_string()).collect();
+        Ok(offsets.join(",").into_bytes())
+    }
+
+    fn content_type(&self) -> &str {
+        "text/plain"
+    }
+}
+
+// failing为true时发送失败
+struct RecordingProducer {
+    sent: Arc<Mutex<Vec<String>>>,
+    failing: Arc<Mutex<bool>>,
+}
+
+impl MessageProducer for RecordingProducer {
+    fn send(&mut self, message: Message) -> Result<(), String> {
+        if *self.failing.lock().unwrap() {
+            return Err("broker unavailable".to_string());
+        }
+        self.sent.lock().unwrap().push(String::from_utf8(message.payload().to_vec()).unwrap());
+        Ok(())
+    }
+}
+
+type Shared<T> = Arc<Mutex<T>>;
+
+fn mq_sink() -> (MqSink, Shared<Vec<String>>, Shared<bool>) {
+    let sent = Arc::new(Mutex::new(vec![]));
+    let failing = Arc::new(Mutex::new(false));
+    let producer = RecordingProducer { sent: sent.clone(), failing: failing.clone() };
+    (MqSink::new(Box::new(producer), Box::new(OffsetSerializer)), sent, failing)
+}
+
+#[test]
+fn mq_publishes_before_exceeding_max_message_bytes() {
+    let (mut sink, sent, _) = mq_sink();
+    sink.set_max_message_bytes(entry_size(&row(110, "orders", "1")) * 2);
+
+    for offset in [110, 120, 130, 140, 150] {
+        sink.on_event(&row(offset, "orders", "1")).unwrap();
+    }
+    assert_eq!(*sent.lock().unwrap(), vec!["110,120", "130,140"]);
+    sink.flush().unwrap();
+    assert_eq!(*sent.lock().unwrap(), vec!["110,120", "130,140", "150"]);
+
+    // 单个entry超过限制时单独发送
+    sink.on_event(&rows(160, "orders", &["1", "2", "3"])).unwrap();
+    sink.on_event(&row(170, "orders", "1")).unwrap();
+    sink.flush().unwrap();
+    assert_eq!(*sent.lock().unwrap(), vec!["110,120", "130,140", "150", "160", "170"]);
+}
+
+#[test]
+fn mq_drops_batch_when_send_fails() {
+    let (mut sink, sent, failing) = mq_sink();
+    sink.set_batch_size(2);
+
+    sink.on_event(&row(110, "orders", "1")).unwrap();
+    *failing.lock().unwrap() = true;
+    assert!(sink.on_event(&row(120, "orders", "2")).is_err());
+
+    // parser重新dump, 同样的entry再次投递, 每个entry只发送一次
+    *failing.lock().unwrap() = false;
+    sink.on_event(&row(110, "orders", "1")).unwrap();
+    sink.on_event(&row(120, "orders", "2")).unwrap();
+    sink.flush().unwrap();
+    assert_eq!(*sent.lock().unwrap(), vec!["110,120"]);
+}
+
+#[test]
+fn replay_cache_rejects_put_when_full() {
+    let size = entry_size(&row(110, "orders", "1"));
+    let mut cache = ReplayCache::new(2, size * 10);
+    let first = cache.put(vec![row(110, "orders", "1")]).unwrap();
+    cache.put(vec![row(120, "orders", "2")]).unwrap();
+    // 批次数已满, 未确认的批次不能被淘汰
+    assert!(cache.put(vec![row(130, "orders", "3")]).is_err());
+    assert_eq!(cache.unacked().len(), 2);
+    assert_eq!(cache.ack(first), 1);
+    cache.put(vec![row(130, "orders", "3")]).unwrap();
+
+    // 内存限制同样生效, cache为空时总是接受
+    let mut cache = ReplayCache::new(10, size * 2);
+    cache.put(vec![row(110, "orders", "1"), row(120, "orders", "2"), row(130, "orders", "3")]).unwrap();
+    assert!(cache.put(vec![row(140, "orders", "4")]).is_err());
+    assert_eq!(cache.rejections(), 1);
+    assert_eq!(cache.used_bytes(), size * 3);
+}
+
+#[test]
+fn replay_sink_waits_for_ack_when_cache_is_full() {
+    let (inner, received) = recording_sink();
+    let mut sink = ReplaySink::new(inner, ReplayCache::new(1, usize::MAX), 1);
+    sink.set_in_flight_timeout(Duration::from_millis(20));
+    sink.on_event(&row(110, "orders", "1")).unwrap();
+    // 没有ack, 超时后返回Err, 批次没有投递也没有丢失
+    assert!(sink.on_event(&row(120, "orders", "2")).is_err());
+    assert_eq!(*received.lock().unwrap(), vec![110]);
+    assert_eq!(sink.throttled(), 1);
+
+    let cache = sink.cache();
+    let batch_id = cache.lock().unwrap().unacked()[0];
+    cache.lock().unwrap().ack(batch_id);
+    sink.flush().unwrap();
+    assert_eq!(*received.lock().unwrap(), vec![110, 120]);
+}
+
+fn column(index: usize, name: &str, value: &str, is_key: bool) -> Column {
+    let mut column = Column::new(index, name);
+    column.set_value(value);
+    column.set_is_key(is_key);
+    column
+}
+
+fn change(offset: u64, table: &str, event_type: EventType, row_datas: Vec<RowData>) -> Entry {
+    let mut row_change = RowChange::new(event_type);
+    row_change.set_row_datas(row_datas);
+    let mut header = header(offset, table);
+    header.set_event_type(event_type);
+    Entry::row_data(header, row_change)
+}
+
+// 执行的sql
+struct RecordingExecutor(Shared<Vec<String>>);
+
+impl SqlExecutor for RecordingExecutor {
+    fn execute(&mut self, sql: &str) -> Result<(), String> {
+        self.0.lock().unwrap().push(sql.to_string());
+        Ok(())
+    }
+}
+
+// 每个lane收到的entry的位点, (lane, offset)
+fn recording_lanes(lanes: usize, mode: LaneMode) -> (ParallelApplySink, Shared<Vec<(usize, u64)>>) {
+    struct Recorder(usize, Shared<Vec<(usize, u64)>>);
+    impl RowApplier for Recorder {
+        fn apply(&mut self, entry: &Entry) -> Result<(), String> {
+            self.1.lock().unwrap().push((self.0, entry.header().log_file_offset()));
+            Ok(())
+        }
+    }
+    let applied = Arc::new(Mutex::new(vec![]));
+    let shared = applied.clone();
+    let factory = Box::new(move |lane| Ok(Box::new(Recorder(lane, shared.clone())) as Box<dyn RowApplier>));
+    (ParallelApplySink::new(factory, lanes, mode, 16).unwrap(), applied)
+}
+
+#[test]
+fn minimal_update_stays_in_primary_key_lane() {
+    let (mut sink, applied) = recording_lanes(8, LaneMode::PrimaryKey);
+    for id in 0..16 {
+        let id = id.to_string();
+        let insert = RowData::new(vec![], vec![column(0, "id", &id, true), column(1, "name", "a", false)]);
+        sink.on_event(&change(100, "orders", EventType::Insert, vec![insert])).unwrap();
+        // MINIMAL: before image只有主键, after image只有变化的列
+        let update = RowData::new(vec![column(0, "id", &id, true)], vec![column(1, "name", "b", false)]);
+        sink.on_event(&change(200, "orders", EventType::Update, vec![update])).unwrap();
+        sink.flush().unwrap();
+        let lanes: Vec<usize> = applied.lock().unwrap().drain(..).map(|(lane, _)| lane).collect();
+        assert_eq!(lanes.len(), 2);
+        assert_eq!(lanes[0], lanes[1]);
+    }
+    assert_eq!(sink.waits(), 0);
+}
+
+#[test]
+fn unique_key_tables_use_one_lane() {
+    let (mut sink, applied) = recording_lanes(8, LaneMode::PrimaryKey);
+    sink.add_unique_key_table("db", "users");
+    let rows = (0..16).map(|id| RowData::new(vec![], vec![column(0, "id", &id.to_string(), true)])).collect();
+    sink.on_event(&change(100, "users", EventType::Insert, rows)).unwrap();
+    sink.flush().unwrap();
+    let applied = applied.lock().unwrap();
+    assert_eq!(applied.len(), 1);
+    assert_eq!(applied[0].1, 100);
+}
+
+#[test]
+fn sql_applier_generates_statements() {
+    let executed = Arc::new(Mutex::new(vec![]));
+    let mut applier = SqlApplier::new(Box::new(RecordingExecutor(executed.clone())), SqlDialect::new(SqlTarget::Mysql));
+    let insert = RowData::new(vec![], vec![column(0, "id", "1", true), column(1, "name", "a'b", false)]);
+    applier.apply(&change(100, "orders", EventType::Insert, vec![insert])).unwrap();
+    let update = RowData::new(vec![column(0, "id", "1", true)], vec![column(1, "name", "c", false)]);
+    applier.apply(&change(110, "orders", EventType::Update, vec![update])).unwrap();
+    let delete = RowData::new(vec![column(0, "id", "1", true)], vec![]);
+    applier.apply(&change(120, "orders", EventType::Delete, vec![delete])).unwrap();
+    applier.flush().unwrap();
+    assert_eq!(*executed.lock().unwrap(), vec![
+        "BEGIN",
+        "REPLACE INTO `db`.`orders` (`id`, `name`) VALUES ('1', 'a''b');",
+        "UPDATE `db`.`orders` SET `name` = 'c' WHERE `id` = '1';",
+        "DELETE FROM `db`.`orders` WHERE `id` = '1';",
+        "COMMIT",
+    ]);
+    assert_eq!((applier.statements(), applier.commits()), (3, 1));
+}
+
+#[test]
+fn sql_applier_factory_applies_in_lanes() {
+    let executed = Arc::new(Mutex::new(vec![]));
+    let shared = executed.clone();
+    let factory = sql_applier_factory(move |_| Ok(Box::new(RecordingExecutor(shared.clone())) as Box<dyn SqlExecutor>),
+                                      SqlDialect::new(SqlTarget::Postgres));
+    let mut sink = ParallelApplySink::new(factory, 2, LaneMode::Table, 16).unwrap();
+    let insert = RowData::new(vec![], vec![column(0, "id", "1", true), column(1, "name", "a", false)]);
+    sink.on_event(&change(100, "orders", EventType::Insert, vec![insert])).unwrap();
+    sink.flush().unwrap();
+    let executed = executed.lock().unwrap();
+    assert_eq!(executed[1], "INSERT INTO \"db\".\"orders\" (\"id\", \"name\") VALUES ('1', 'a') \
+                             ON CONFLICT (\"id\") DO UPDATE SET \"name\" = EXCLUDED.\"name\";");
+    assert_eq!(executed.iter().filter(|sql| *sql == "COMMIT").count(), 1);
+}
+
+fn properties(pairs: &[(&str, &str)]) -> Properties {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+#[test]
+fn reload_only_touches_changed_destinations() {
+    let mut dispatcher = DestinationDispatcher::new();
+    let (orders, orders_received) = recording_sink();
+    let (audit, audit_received) = recording_sink();
+    dispatcher.register("orders", Some(Box::new(RegexFilter::new("db\\.orders").unwrap())), orders).unwrap();
+    dispatcher.register("audit", None, audit).unwrap();
+
+    // 只有audit的filter变化, 代码中设置的orders的filter保持不变
+    let old = properties(&[]);
+    let new = properties(&[("destination.audit.filter", "db\\.users")]);
+    dispatcher.reload_filters(&ConfigChange::diff(&old, &new)).unwrap();
+    dispatcher.on_event(&row(110, "users", "1")).unwrap();
+    dispatcher.on_event(&row(120, "orders", "2")).unwrap();
+    assert_eq!(*orders_received.lock().unwrap(), vec![120]);
+    assert_eq!(*audit_received.lock().unwrap(), vec![110]);
+
+    // 删除audit的filter之后不再过滤
+    dispatcher.reload_filters(&ConfigChange::diff(&new, &old)).unwrap();
+    dispatcher.on_event(&row(130, "orders", "3")).unwrap();
+    assert_eq!(*orders_received.lock().unwrap(), vec![120, 130]);
+    assert_eq!(*audit_received.lock().unwrap(), vec![110, 130]);
+}
+
+#[test]
+fn reload_sink_batch_size() {
+    let (mq, sent, _) = mq_sink();
+    let mut dispatcher = DestinationDispatcher::new();
+    dispatcher.register("mq", None, Box::new(mq)).unwrap();
+    let old = properties(&[]);
+    let new = properties(&[("destination.mq.sink.batch_size", "2"), ("destination.other.sink.batch_size", "x")]);
+    dispatcher.reload_sinks(&ConfigChange::diff(&old, &new)).unwrap();
+    for offset in [110, 120, 130] {
+        dispatcher.on_event(&row(offset, "orders", "1")).unwrap();
+    }
+    assert_eq!(*sent.lock().unwrap(), vec!["110,120"]);
+
+    let invalid = properties(&[("destination.mq.sink.batch_size", "x")]);
+    assert!(dispatcher.reload_sinks(&ConfigChange::diff(&new, &invalid)).is_err());
+}
+
+#[test]
+fn rejected_sink_reload_keeps_every_destination() {
+    let (first, first_sent, _) = mq_sink();
+    let (second, second_sent, _) = mq_sink();
+    let mut dispatcher = DestinationDispatcher::new();
+    dispatcher.register("first", None, Box::new(first)).unwrap();
+    dispatcher.register("second", None, Box::new(second)).unwrap();
+    let old = properties(&[("destination.first.sink.batch_size", "2"), ("destination.second.sink.batch_size", "2")]);
+    dispatcher.reload_sinks(&ConfigChange::diff(&properties(&[]), &old)).unwrap();
+
+    // first的新配置有效, second的无效: first回滚到batch_size=2
+    let new = properties(&[("destination.first.sink.batch_size", "3"), ("destination.second.sink.batch_size", "x")]);
+    let error = dispatcher.reload_sinks(&ConfigChange::diff(&old, &new)).unwrap_err();
+    assert!(error.contains("destination second"), "{}", error);
+    for offset in [110, 120] {
+        dispatcher.on_event(&row(offset, "orders", "1")).unwrap();
+    }
+    assert_eq!(*first_sent.lock().unwrap(), vec!["110,120"]);
+    assert_eq!(*second_sent.lock().unwrap(), vec!["110,120"]);
+}
+
+#[test]
+fn protobuf_payload_round_trip() {
+    use mini_canal_types::Message as _;
+
+    let entries = vec![row(110, "orders", "1"), marker(120, EntryType::Heartbeat), row(130, "users", "2")];
+    let payload = ProtobufSerializer::new().serialize(&entries).unwrap();
+    let mut buf = payload.as_slice();
+    let mut tables = vec![];
+    while !buf.is_empty() {
+        let entry = mini_canal_types::Entry::decode_length_delimited(&mut buf).unwrap();
+        tables.push(entry.header().unwrap().table_name().to_string());
+    }
+    assert_eq!(tables, vec!["orders", "users"]);
+}
+
+#[test]
+fn avro_payload_is_object_container_file() {
+    let entries = vec![row(110, "orders", "1"), marker(120, EntryType::Heartbeat), row(130, "users", "2")];
+    let payload = AvroSerializer::new().serialize(&entries).unwrap();
+    assert!(payload.starts_with(b"Obj\x01"));
+    let sync = b"mini-canal-sync0";
+    let header_end = payload.windows(sync.len()).position(|window| window == sync).unwrap() + sync.len();
+    assert!(payload[..header_end].windows(ENTRY_SCHEMA.len()).any(|window| window == ENTRY_SCHEMA.as_bytes()));
+    // block: 记录数(zigzag) 2, 之后是字节数, 数据和sync marker
+    assert_eq!(payload[header_end], 4);
+    assert!(payload.ends_with(sync));
+    // 第一条记录的database
+    let record = &payload[header_end + 1..];
+    let record = &record[record.iter().position(|byte| byte & 0x80 == 0).unwrap() + 1..];
+    assert_eq!(&record[..3], &[4, b'd', b'b']);
+}
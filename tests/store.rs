@@ -0,0 +1,146 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mysql_binlog_parse::instance::admin::AdminServer;
+use mysql_binlog_parse::protocol::{Entry, EntryType, Header};
+use mysql_binlog_parse::store::quota::{Quota, QuotaLimiter};
+use mysql_binlog_parse::store::{EntryDelivery, EntryStore, Fetch, PayloadFormat};
+
+const FILE: &str = "mysql-bin.000001";
+
+fn entry(offset: u64) -> Entry {
+    let mut header = Header::new(FILE, offset);
+    header.set_execute_time(1_700_000_000_000 + offset as i64);
+    Entry::new(header, EntryType::RowData)
+}
+
+// (acked, fetched, idle)
+fn cursor(store: &Mutex<EntryStore>, name: &str) -> (u64, u64, Duration) {
+    let store = store.lock().unwrap();
+    let cursor = store.consumer(name).unwrap();
+    (cursor.acked(), cursor.fetched(), cursor.idle())
+}
+
+#[test]
+fn admin_store_route_does_not_move_cursors() {
+    let mut store = EntryStore::new(16);
+    store.subscribe("a").unwrap();
+    store.subscribe("b").unwrap();
+    for offset in [120, 240, 360, 480] {
+        store.put(entry(offset)).unwrap();
+    }
+    // a取了一批没有确认, b一直没有get
+    assert_eq!(store.get("a", 2).unwrap().0, 0);
+    let store = Arc::new(Mutex::new(store));
+
+    let admin = AdminServer::new();
+    let inspected = store.clone();
+    admin.add_store("example", move || inspected.lock().ok().map(|store| store.inspect()));
+    let before = (cursor(&store, "a"), cursor(&store, "b"));
+    thread::sleep(Duration::from_millis(20));
+
+    for _ in 0..2 {
+        let (status, body) = admin.handle("/store/example");
+        assert_eq!(status, 200, "{}", body);
+        assert!(body.starts_with("{\"version\":1,\"name\":\"example\",\"store\":{"), "{}", body);
+        assert!(body.contains("\"size\":4,\"first_sequence\":0,\"next_sequence\":4,\"evicted\":0,\"unacked_batches\":1"),
+                "{}", body);
+        assert!(body.contains("\"oldest_unacked\":{\"sequence\":0,\"destination\":\"a\",\"journal_name\":\"mysql-bin.000001\",\"position\":120,\"execute_time\":1700000000120,"),
+                "{}", body);
+        assert!(body.contains("{\"name\":\"a\",\"state\":\"active\",\"acked\":0,\"fetched\":2,\"unacked\":2,\"unacked_batches\":1,\"pending\":2,"),
+                "{}", body);
+        assert!(body.contains("{\"name\":\"b\",\"state\":\"active\",\"acked\":0,\"fetched\":0,\"unacked\":0,\"unacked_batches\":0,\"pending\":4,"),
+                "{}", body);
+    }
+
+    // 游标和活跃时间都没有变化
+    let after = (cursor(&store, "a"), cursor(&store, "b"));
+    assert_eq!((after.0 .0, after.0 .1), (before.0 .0, before.0 .1));
+    assert_eq!((after.1 .0, after.1 .1), (before.1 .0, before.1 .1));
+    assert!(after.0 .2 >= before.0 .2 + Duration::from_millis(20));
+    assert!(after.1 .2 >= before.1 .2 + Duration::from_millis(20));
+
+    // a的下一批从上一次get的位置继续
+    let (start, end, entries) = store.lock().unwrap().get("a", 2).unwrap();
+    assert_eq!((start, end), (2, 4));
+    assert_eq!(entries[0].header().log_file_offset(), 360);
+
+    assert_eq!(admin.handle("/store/unknown").0, 404);
+    admin.remove_store("example");
+    assert_eq!(admin.handle("/store/example").0, 404);
+}
+
+fn millis(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+#[test]
+fn entry_quota_allows_one_second_burst_then_refills() {
+    let start = Instant::now();
+    let mut limiter = QuotaLimiter::new_at(Quota::new(Some(10), None), start);
+    // 桶的容量为1秒的配额
+    assert_eq!(limiter.acquire_at(100, start), Ok(10));
+    limiter.consume(10, 0);
+    assert_eq!(limiter.acquire_at(100, start), Err(millis(100)));
+    assert_eq!(limiter.throttled(), 1);
+
+    // 250ms补充2.5个令牌, batch size按整数个令牌截断
+    assert_eq!(limiter.acquire_at(100, start + millis(250)), Ok(2));
+    limiter.consume(2, 0);
+    assert_eq!(limiter.acquire_at(100, start + millis(250)), Err(millis(50)));
+    assert_eq!(limiter.acquire_at(1, start + millis(300)), Ok(1));
+
+    // 长时间空闲之后最多积攒1秒的突发
+    assert_eq!(limiter.acquire_at(100, start + Duration::from_secs(60)), Ok(10));
+    assert_eq!(limiter.throttled(), 2);
+}
+
+#[test]
+fn byte_quota_waits_for_overdraft() {
+    let start = Instant::now();
+    let mut limiter = QuotaLimiter::new_at(Quota::new(None, Some(1000)), start);
+    // 只限制字节时不限制batch size, 一个批次可以透支
+    assert_eq!(limiter.acquire_at(64, start), Ok(64));
+    limiter.consume(64, 1500);
+    assert_eq!(limiter.acquire_at(64, start), Err(millis(501)));
+    assert_eq!(limiter.acquire_at(64, start + millis(250)), Err(millis(251)));
+    assert_eq!(limiter.acquire_at(64, start + millis(600)), Ok(64));
+    assert_eq!(limiter.throttled(), 2);
+}
+
+#[test]
+fn throttled_fetch_keeps_cursor() {
+    let start = Instant::now();
+    let mut delivery = EntryDelivery::new(EntryStore::new(16));
+    delivery.subscribe("a", PayloadFormat::Flat).unwrap();
+    for offset in [120, 240, 360, 480, 600] {
+        delivery.store_mut().put(entry(offset)).unwrap();
+    }
+    delivery.set_quota_at("a", Some(Quota::new(Some(2), None)), start);
+
+    let payload = delivery.get_at("a", 10, start).unwrap().payload().cloned().unwrap();
+    assert_eq!((payload.sequence(), payload.next_sequence(), payload.count()), (0, 2, 2));
+    delivery.ack("a", payload.next_sequence()).unwrap();
+
+    // 令牌用完之后返回429, 不读取store
+    let fetch = delivery.get_at("a", 10, start + millis(100)).unwrap();
+    assert!(matches!(fetch, Fetch::Throttled(_)), "{:?}", fetch);
+    assert_eq!(fetch.status_code(), 429);
+    assert_eq!(fetch.retry_after(), Some(millis(400)));
+    assert_eq!(delivery.quota("a").unwrap().throttled(), 1);
+    let cursor = delivery.store().consumer("a").unwrap();
+    assert_eq!((cursor.acked(), cursor.fetched(), cursor.unacked_batches()), (2, 2, 0));
+
+    // 等待retry_after之后从游标的位置继续, batch size不超过补充的令牌
+    let payload = delivery.get_at("a", 10, start + millis(500)).unwrap().payload().cloned().unwrap();
+    assert_eq!((payload.sequence(), payload.next_sequence()), (2, 3));
+    let payload = delivery.get_at("a", 10, start + Duration::from_secs(5)).unwrap().payload().cloned().unwrap();
+    assert_eq!((payload.sequence(), payload.next_sequence()), (3, 5));
+
+    // 没有配额的destination不受影响
+    delivery.set_quota_at("a", None, start);
+    delivery.rollback("a").unwrap();
+    let payload = delivery.get_at("a", 10, start).unwrap().payload().cloned().unwrap();
+    assert_eq!((payload.sequence(), payload.next_sequence()), (2, 5));
+}
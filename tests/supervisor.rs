@@ -0,0 +1,84 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mysql_binlog_parse::instance::supervisor::{InstanceState, InstanceSupervisor};
+
+const INITIAL_DELAY: Duration = Duration::from_millis(40);
+
+fn supervisor() -> InstanceSupervisor {
+    let mut supervisor = InstanceSupervisor::new();
+    supervisor.set_backoff(INITIAL_DELAY, Duration::from_secs(1));
+    supervisor.set_jitter(0.0);
+    supervisor
+}
+
+#[test]
+fn restart_after_panic_with_backoff() {
+    let mut supervisor = supervisor();
+    let starts = Arc::new(Mutex::new(vec![]));
+    let recorded = starts.clone();
+    supervisor.add("example", move |_| {
+        let mut starts = recorded.lock().unwrap();
+        starts.push(Instant::now());
+        match starts.len() {
+            1 => {
+                drop(starts);
+                panic!("decode failure")
+            }
+            2 => Err("connection reset".to_string()),
+            _ => Ok(()),
+        }
+    }).unwrap();
+    let statuses = supervisor.status_handle();
+    supervisor.join().unwrap();
+
+    // 每次重启之前的等待按initial_delay翻倍
+    let starts = starts.lock().unwrap();
+    assert_eq!(starts.len(), 3);
+    assert!(starts[1] - starts[0] >= INITIAL_DELAY, "{:?}", starts[1] - starts[0]);
+    assert!(starts[2] - starts[1] >= INITIAL_DELAY * 2, "{:?}", starts[2] - starts[1]);
+
+    let status = statuses.lock().unwrap().get("example").cloned().unwrap();
+    assert_eq!(status.state(), InstanceState::Stopped);
+    assert_eq!(status.restarts(), 2);
+    assert_eq!(status.panics(), 1);
+    assert_eq!(status.last_panic(), Some("decode failure"));
+    assert_eq!(status.last_error(), Some("connection reset"));
+}
+
+#[test]
+fn give_up_after_restart_storm() {
+    let mut supervisor = InstanceSupervisor::new();
+    supervisor.set_backoff(Duration::from_millis(1), Duration::from_millis(1));
+    supervisor.set_restart_limit(2, Duration::from_secs(60));
+    let runs = Arc::new(Mutex::new(0));
+    let counted = runs.clone();
+    supervisor.add("example", move |_| {
+        *counted.lock().unwrap() += 1;
+        Err("access denied".to_string())
+    }).unwrap();
+    assert!(supervisor.add("example", |_| Ok(())).is_err());
+    let statuses = supervisor.status_handle();
+    supervisor.join().unwrap();
+
+    assert_eq!(*runs.lock().unwrap(), 3);
+    let status = statuses.lock().unwrap().get("example").cloned().unwrap();
+    assert_eq!(status.state(), InstanceState::GaveUp);
+    assert_eq!(status.restarts(), 2);
+    assert_eq!(status.last_error(), Some("access denied"));
+}
+
+#[test]
+fn shutdown_interrupts_backoff() {
+    let mut supervisor = InstanceSupervisor::new();
+    supervisor.set_backoff(Duration::from_secs(60), Duration::from_secs(60));
+    supervisor.add("example", |_| Err("connection refused".to_string())).unwrap();
+    while supervisor.instance_status("example").unwrap().state() != InstanceState::Restarting {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let started = Instant::now();
+    let statuses = supervisor.status_handle();
+    supervisor.shutdown().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(statuses.lock().unwrap().get("example").unwrap().state(), InstanceState::Stopped);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::{format_description_body, table_map_body, BinlogFile};
+use mysql_binlog_parse::command::event::column_type::{MYSQL_TYPE_LONG, MYSQL_TYPE_LONGLONG};
+use mysql_binlog_parse::command::event::{EventType, LogContext, LogDecoder, LogEvent, TableMapLogEvent};
+
+const TABLE_ID: u64 = 42;
+
+fn table_map(event: LogEvent) -> TableMapLogEvent {
+    match event {
+        LogEvent::TableMap(table_map) => table_map,
+        other => panic!("expect table map event, got {:?}", other),
+    }
+}
+
+fn decoder() -> (LogDecoder, LogContext, BinlogFile) {
+    let mut decoder = LogDecoder::new();
+    let mut context = LogContext::new();
+    let mut file = BinlogFile::new(false);
+    let description = file.append(EventType::FormatDescriptionEvent, &format_description_body("8.0.33", false));
+    decoder.decode(&description, &mut context).unwrap();
+    (decoder, context, file)
+}
+
+#[test]
+fn identical_table_map_hits_cache() {
+    let (mut decoder, mut context, mut file) = decoder();
+    let body = table_map_body(TABLE_ID, "test", "orders", &[MYSQL_TYPE_LONG], &[]);
+    let first = file.append(EventType::TableMapEvent, &body);
+    let second = file.append(EventType::TableMapEvent, &body);
+
+    let decoded = table_map(decoder.decode(&first, &mut context).unwrap());
+    let cached = table_map(decoder.decode(&second, &mut context).unwrap());
+    assert_eq!(decoder.table_map_cache().misses(), 1);
+    assert_eq!(decoder.table_map_cache().hits(), 1);
+    assert_eq!(decoder.table_map_cache().len(), 1);
+    // 命中时使用新event的header
+    assert_eq!(cached.header().log_pos(), file.position() as u32);
+    assert_ne!(cached.header().log_pos(), decoded.header().log_pos());
+    assert_eq!(cached.column_count(), 1);
+    assert_eq!(context.get_table(TABLE_ID).unwrap().header().log_pos(), file.position() as u32);
+}
+
+#[test]
+fn changed_table_map_is_decoded_again() {
+    let (mut decoder, mut context, mut file) = decoder();
+    let before = file.append(EventType::TableMapEvent, &table_map_body(TABLE_ID, "test", "orders", &[MYSQL_TYPE_LONG], &[]));
+    decoder.decode(&before, &mut context).unwrap();
+
+    // 同一个table_id在ALTER之后多了一列, 不能复用之前的解析结果
+    let altered = table_map_body(TABLE_ID, "test", "orders", &[MYSQL_TYPE_LONG, MYSQL_TYPE_LONGLONG], &[]);
+    let after = file.append(EventType::TableMapEvent, &altered);
+    let decoded = table_map(decoder.decode(&after, &mut context).unwrap());
+    assert_eq!(decoded.column_count(), 2);
+    assert_eq!(context.get_table(TABLE_ID).unwrap().column_count(), 2);
+    assert_eq!(decoder.table_map_cache().misses(), 2);
+    assert_eq!(decoder.table_map_cache().hits(), 0);
+
+    // 换了表名同样不命中
+    let renamed = file.append(EventType::TableMapEvent, &table_map_body(TABLE_ID, "test", "orders_new", &[MYSQL_TYPE_LONG], &[]));
+    assert_eq!(table_map(decoder.decode(&renamed, &mut context).unwrap()).table_name(), "orders_new");
+    assert_eq!(decoder.table_map_cache().hits(), 0);
+}
+
+#[test]
+fn format_description_clears_cache() {
+    let (mut decoder, mut context, mut file) = decoder();
+    let body = table_map_body(TABLE_ID, "test", "orders", &[MYSQL_TYPE_LONG], &[]);
+    decoder.decode(&file.append(EventType::TableMapEvent, &body), &mut context).unwrap();
+    assert_eq!(decoder.table_map_cache().len(), 1);
+    assert!(decoder.table_map_cache().bytes() > 0);
+
+    let description = file.append(EventType::FormatDescriptionEvent, &format_description_body("8.0.33", false));
+    decoder.decode(&description, &mut context).unwrap();
+    assert!(decoder.table_map_cache().is_empty());
+    assert_eq!(decoder.table_map_cache().bytes(), 0);
+    decoder.decode(&file.append(EventType::TableMapEvent, &body), &mut context).unwrap();
+    assert_eq!(decoder.table_map_cache().misses(), 2);
+}
+
+#[test]
+fn zero_capacity_disables_cache() {
+    let (mut decoder, mut context, mut file) = decoder();
+    decoder.set_table_map_cache_capacity(0);
+    let body = table_map_body(TABLE_ID, "test", "orders", &[MYSQL_TYPE_LONG], &[]);
+    for _ in 0..2 {
+        decoder.decode(&file.append(EventType::TableMapEvent, &body), &mut context).unwrap();
+    }
+    assert!(decoder.table_map_cache().is_empty());
+    assert_eq!(decoder.table_map_cache().hits(), 0);
+}
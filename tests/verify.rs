@@ -0,0 +1,21 @@
+use mysql_binlog_parse::verify::dialect::{SqlDialect, SqlTarget};
+
+#[test]
+fn dialect_quotes_binary_values_as_hex() {
+    let mysql = SqlDialect::new(SqlTarget::Mysql);
+    assert_eq!(mysql.quote_binary("00FF27"), "X'00FF27'");
+    // 反斜杠原样输出, 与NO_BACKSLASH_ESCAPES一致
+    assert_eq!(mysql.quote_value("it's"), "'it''s'");
+    assert_eq!(mysql.quote_value("a\\'b\\"), "'a\\''b\\'");
+    let postgres = SqlDialect::new(SqlTarget::Postgres);
+    assert_eq!(postgres.quote_binary("00FF27"), "'\\x00FF27'");
+    assert_eq!(postgres.quote_value("it's"), "'it''s'");
+}
+
+#[test]
+fn dialect_delete_uses_quoted_values() {
+    let dialect = SqlDialect::new(SqlTarget::Mysql);
+    let keys = vec!["id".to_string(), "hash".to_string()];
+    let values = vec![dialect.quote_value("1"), dialect.quote_binary("0A0B")];
+    assert_eq!(dialect.delete("db", "t", &keys, &values), "DELETE FROM `db`.`t` WHERE `id` = '1' AND `hash` = X'0A0B';");
+}
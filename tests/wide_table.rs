@@ -0,0 +1,285 @@
+mod common;
+
+use common::{bitmap, event, format_description_body, packed_long};
+use mysql_binlog_parse::command::event::column_type::{MYSQL_TYPE_LONG, MYSQL_TYPE_LONGLONG, MYSQL_TYPE_VARCHAR};
+use mysql_binlog_parse::command::event::{EventType, LogContext, LogDecoder, LogEvent, TableMapLogEvent};
+use mysql_binlog_parse::instance::convert::LogEventConvert;
+use mysql_binlog_parse::protocol::{Column, Entry};
+
+const COLUMNS: usize = 300;
+const TABLE_ID: u64 = 42;
+const VARCHAR_LEN: u8 = 64;
+// 主键, 257在optional metadata中需要多个字节的packed编码
+const PRIMARY_KEY: [usize; 2] = [0, 257];
+
+// 三种类型循环: int, varchar(64), bigint
+fn column_type(index: usize) -> u8 {
+    match index % 3 {
+        0 => MYSQL_TYPE_LONG,
+        1 => MYSQL_TYPE_VARCHAR,
+        _ => MYSQL_TYPE_LONGLONG,
+    }
+}
+
+fn nullable(index: usize) -> bool {
+    index % 2 == 1
+}
+
+// 按数值列中的序号(不是列下标)标记unsigned
+fn unsigned(numeric_index: usize) -> bool {
+    numeric_index.is_multiple_of(5)
+}
+
+fn numeric_columns() -> Vec<usize> {
+    (0..COLUMNS).filter(|i| column_type(*i) != MYSQL_TYPE_VARCHAR).collect()
+}
+
+fn character_columns() -> Vec<usize> {
+    (0..COLUMNS).filter(|i| column_type(*i) == MYSQL_TYPE_VARCHAR).collect()
+}
+
+// 高位在前的bitmap, optional metadata中的SIGNEDNESS/COLUMN_VISIBILITY
+fn msb_bitmap(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    bytes
+}
+
+fn optional_field(kind: u8, value: &[u8]) -> Vec<u8> {
+    [vec![kind], packed_long(value.len() as u64), value.to_vec()].concat()
+}
+
+// binlog_row_metadata=FULL的300列table map
+fn table_map_body(columns: usize) -> Vec<u8> {
+    let mut body = TABLE_ID.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    for name in ["test", "wide"] {
+        body.push(name.len() as u8);
+        body.extend_from_slice(name.as_bytes());
+        body.push(0);
+    }
+    body.extend(packed_long(columns as u64));
+    body.extend((0..columns).map(column_type));
+    let metadata: Vec<u8> = (0..columns)
+        .filter(|i| column_type(*i) == MYSQL_TYPE_VARCHAR)
+        .flat_map(|_| (VARCHAR_LEN as u16).to_le_bytes())
+        .collect();
+    body.extend(packed_long(metadata.len() as u64));
+    body.extend(metadata);
+    body.extend(bitmap(&(0..columns).map(nullable).collect::<Vec<_>>()));
+
+    let signedness: Vec<bool> = (0..numeric_columns().len()).map(unsigned).collect();
+    body.extend(optional_field(1, &msb_bitmap(&signedness)));
+    // 默认utf8mb4, 第90个字符列为binary
+    let charset = [packed_long(255), packed_long(90), packed_long(63)].concat();
+    body.extend(optional_field(2, &charset));
+    let names: Vec<u8> = (0..columns)
+        .flat_map(|i| [packed_long(format!("c{}", i).len() as u64), format!("c{}", i).into_bytes()].concat())
+        .collect();
+    body.extend(optional_field(4, &names));
+    let primary_key: Vec<u8> = PRIMARY_KEY.iter().flat_map(|i| packed_long(*i as u64)).collect();
+    body.extend(optional_field(8, &primary_key));
+    let visibility: Vec<bool> = (0..columns).map(|i| i != COLUMNS - 1).collect();
+    body.extend(optional_field(12, &msb_bitmap(&visibility)));
+    body
+}
+
+fn value(index: usize, row: u32) -> String {
+    match column_type(index) {
+        MYSQL_TYPE_VARCHAR => format!("r{}c{}", row, index),
+        _ => (index as u64 * 1000 + row as u64).to_string(),
+    }
+}
+
+// present中的列按顺序编码, nulls为NULL的列
+fn image(present: &[bool], nulls: &[usize], row: u32) -> Vec<u8> {
+    let columns: Vec<usize> = (0..present.len()).filter(|i| present[*i]).collect();
+    let mut out = bitmap(&columns.iter().map(|i| nulls.contains(i)).collect::<Vec<_>>());
+    for index in columns.iter().filter(|i| !nulls.contains(i)) {
+        let value = value(*index, row);
+        match column_type(*index) {
+            MYSQL_TYPE_LONG => out.extend_from_slice(&value.parse::<u32>().unwrap().to_le_bytes()),
+            MYSQL_TYPE_LONGLONG => out.extend_from_slice(&value.parse::<u64>().unwrap().to_le_bytes()),
+            _ => {
+                out.push(value.len() as u8);
+                out.extend_from_slice(value.as_bytes());
+            }
+        }
+    }
+    out
+}
+
+fn rows_body(column_count: usize, present: &[bool], change_present: Option<&[bool]>, images: &[Vec<u8>]) -> Vec<u8> {
+    let mut body = TABLE_ID.to_le_bytes()[..6].to_vec();
+    body.extend_from_slice(&1u16.to_le_bytes());
+    body.extend_from_slice(&2u16.to_le_bytes());
+    body.extend(packed_long(column_count as u64));
+    body.extend(bitmap(present));
+    if let Some(change_present) = change_present {
+        body.extend(bitmap(change_present));
+    }
+    for image in images {
+        body.extend_from_slice(image);
+    }
+    body
+}
+
+fn present(columns: &[usize]) -> Vec<bool> {
+    (0..COLUMNS).map(|i| columns.contains(&i)).collect()
+}
+
+struct Session {
+    context: LogContext,
+    decoder: LogDecoder,
+    convert: LogEventConvert,
+}
+
+impl Session {
+    fn new() -> Session {
+        let mut session = Session { context: LogContext::new(), decoder: LogDecoder::new(), convert: LogEventConvert::new() };
+        let description = event(EventType::FormatDescriptionEvent, &format_description_body("8.0.33", false), 0, 0, false);
+        session.decoder.decode(&description, &mut session.context).unwrap();
+        session
+    }
+
+    fn table_map(&mut self, columns: usize) -> TableMapLogEvent {
+        let table_map = event(EventType::TableMapEvent, &table_map_body(columns), 4096, 0, false);
+        match self.decoder.decode(&table_map, &mut self.context).unwrap() {
+            LogEvent::TableMap(table_map) => table_map,
+            other => panic!("expect table map event, got {:?}", other),
+        }
+    }
+
+    fn rows(&mut self, kind: EventType, body: &[u8]) -> Result<Entry, String> {
+        let rows = event(kind, body, 8192, 0, false);
+        let event = self.decoder.decode(&rows, &mut self.context)?;
+        Ok(self.convert.parse(&event, &self.context, true)?.expect("rows entry"))
+    }
+}
+
+fn assert_column(column: &Column, index: usize, row: u32, nulls: &[usize]) {
+    assert_eq!(column.index(), index);
+    assert_eq!(column.name(), format!("c{}", index));
+    assert_eq!(column.is_key(), PRIMARY_KEY.contains(&index), "column {}", index);
+    if nulls.contains(&index) {
+        assert!(column.is_null(), "column {} should be NULL", index);
+    } else {
+        assert!(!column.is_null(), "column {} should not be NULL", index);
+        assert_eq!(column.value(), value(index, row), "column {}", index);
+    }
+}
+
+#[test]
+fn table_map_with_300_columns_and_full_metadata() {
+    let table_map = Session::new().table_map(COLUMNS);
+    assert_eq!(table_map.column_count(), COLUMNS);
+    assert!(!table_map.is_partial());
+    let numeric = numeric_columns();
+    let character = character_columns();
+    for (index, info) in table_map.column_info().iter().enumerate() {
+        assert_eq!(info.kind(), column_type(index));
+        assert_eq!(info.nullable(), nullable(index), "nullable of column {}", index);
+        assert_eq!(info.name(), Some(format!("c{}", index).as_str()));
+        assert_eq!(info.pk(), PRIMARY_KEY.contains(&index), "pk of column {}", index);
+        assert_eq!(info.visible(), index != COLUMNS - 1, "visibility of column {}", index);
+        match numeric.iter().position(|i| *i == index) {
+            Some(numeric_index) => assert_eq!(info.unsigned(), unsigned(numeric_index), "signedness of column {}", index),
+            None => assert!(!info.unsigned()),
+        }
+        match character.iter().position(|i| *i == index) {
+            Some(90) => assert_eq!(info.charset(), Some(63)),
+            Some(_) => assert_eq!(info.charset(), Some(255)),
+            None => assert_eq!(info.charset(), None),
+        }
+        if info.kind() == MYSQL_TYPE_VARCHAR {
+            assert_eq!(info.meta(), VARCHAR_LEN as u16);
+        }
+    }
+}
+
+#[test]
+fn insert_into_300_columns_with_nulls_across_bitmap_bytes() {
+    let mut session = Session::new();
+    session.table_map(COLUMNS);
+    let all = vec![true; COLUMNS];
+    let nulls = [7, 63, 65, 127, 129, 255, 257, 299];
+    let images = vec![image(&all, &nulls, 1), image(&all, &[], 2)];
+    let entry = session.rows(EventType::WriteRowsEvent, &rows_body(COLUMNS, &all, None, &images)).unwrap();
+    let rows = entry.row_change().unwrap().row_datas();
+    assert_eq!(rows.len(), 2);
+    for (row, expect_nulls) in [(1u32, &nulls[..]), (2, &[])] {
+        let columns = rows[row as usize - 1].after_columns();
+        assert_eq!(columns.len(), COLUMNS);
+        for (index, column) in columns.iter().enumerate() {
+            assert_column(column, index, row, expect_nulls);
+        }
+    }
+}
+
+#[test]
+fn update_with_sparse_before_and_after_images() {
+    let mut session = Session::new();
+    session.table_map(COLUMNS);
+    // binlog_row_image=MINIMAL: before只有主键, after只有变化的列, 两个bitmap不同
+    let before = present(&[0, 257]);
+    let after = present(&[1, 64, 130, 257, 298, 299]);
+    let mut images = vec![image(&before, &[], 1), image(&after, &[299], 2)];
+    images.push(image(&before, &[], 3));
+    images.push(image(&after, &[], 3));
+    let body = rows_body(COLUMNS, &before, Some(&after), &images);
+    let entry = session.rows(EventType::UpdateRowsEvent, &body).unwrap();
+    let rows = entry.row_change().unwrap().row_datas();
+    assert_eq!(rows.len(), 2);
+
+    let indexes = |columns: &Vec<Column>| columns.iter().map(|column| column.index()).collect::<Vec<_>>();
+    assert_eq!(indexes(rows[0].before_columns()), vec![0, 257]);
+    assert_eq!(indexes(rows[0].after_columns()), vec![1, 64, 130, 257, 298, 299]);
+    for column in rows[0].before_columns() {
+        assert_column(column, column.index(), 1, &[]);
+    }
+    for column in rows[0].after_columns() {
+        assert_column(column, column.index(), 2, &[299]);
+        // 257在before中的值不同, 其它列在before中不存在
+        assert!(column.updated(), "column {}", column.index());
+    }
+    // before与after中的257相同时不算变化
+    let unchanged = rows[1].after_columns().iter().find(|column| column.index() == 257).unwrap();
+    assert!(!unchanged.updated());
+    assert!(rows[1].after_columns().iter().filter(|column| column.index() != 257).all(|column| column.updated()));
+}
+
+#[test]
+fn delete_with_single_present_column_beyond_64() {
+    let mut session = Session::new();
+    session.table_map(COLUMNS);
+    let before = present(&[200]);
+    let images = vec![image(&before, &[], 5), image(&before, &[], 6)];
+    let entry = session.rows(EventType::DeleteRowsEvent, &rows_body(COLUMNS, &before, None, &images)).unwrap();
+    let rows = entry.row_change().unwrap().row_datas();
+    assert_eq!(rows.len(), 2);
+    for (row, row_data) in [5u32, 6].iter().zip(rows) {
+        assert!(row_data.after_columns().is_empty());
+        assert_eq!(row_data.before_columns().len(), 1);
+        assert_column(&row_data.before_columns()[0], 200, *row, &[]);
+    }
+}
+
+#[test]
+fn rows_wider_than_table_map_are_rejected() {
+    let mut session = Session::new();
+    session.table_map(COLUMNS);
+    // 多出来的一列为NULL, 不需要列信息也能解析出值, 转换时不能按下标越界
+    let wider = vec![true; COLUMNS + 1];
+    let mut image = bitmap(&(0..=COLUMNS).map(|i| i == COLUMNS).collect::<Vec<_>>());
+    for index in 0..COLUMNS {
+        let mut single = vec![false; COLUMNS];
+        single[index] = true;
+        image.extend_from_slice(&self::image(&single, &[], 1)[1..]);
+    }
+    let error = session.rows(EventType::WriteRowsEvent, &rows_body(COLUMNS + 1, &wider, None, &[image])).unwrap_err();
+    assert!(error.contains("301 columns"), "{}", error);
+}